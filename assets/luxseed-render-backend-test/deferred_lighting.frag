@@ -0,0 +1,15 @@
+#version 450
+
+// G-buffer attachments written by subpass 0, read at the current pixel only
+layout(input_attachment_index = 0, binding = 0) uniform subpassInput inAlbedo;
+layout(input_attachment_index = 1, binding = 1) uniform subpassInput inNormal;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 albedo = subpassLoad(inAlbedo).rgb;
+    vec3 normal = subpassLoad(inNormal).xyz;
+    vec3 light_dir = normalize(vec3(0.5, 0.8, 1.0));
+    float diffuse = max(dot(normal, light_dir), 0.0);
+    outColor = vec4(albedo * (0.2 + 0.8 * diffuse), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D inputTexture;
+
+layout(location = 0) in vec2 inUV;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 color = texture(inputTexture, inUV).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    outColor = vec4(vec3(luminance), 1.0);
+}
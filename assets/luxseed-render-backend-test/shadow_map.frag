@@ -0,0 +1,24 @@
+#version 450
+
+// Comparison sampler: returns 1.0 when the reference depth passes the compare op
+layout(binding = 1) uniform sampler2DShadow shadowMap;
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 1) in vec4 fragLightPos;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 proj = fragLightPos.xyz / fragLightPos.w;
+    vec2 uv = proj.xy * 0.5 + 0.5;
+    // 3x3 PCF, each tap is already filtered by the linear comparison sampler
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            lit += texture(shadowMap, vec3(uv + vec2(x, y) * texel, proj.z - 0.002));
+        }
+    }
+    lit /= 9.0;
+    outColor = vec4(fragColor * (0.3 + 0.7 * lit), 1.0);
+}
@@ -0,0 +1,13 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view_proj;
+    mat4 light_view_proj;
+} ubo;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+
+void main() {
+    gl_Position = ubo.light_view_proj * vec4(inPosition, 1.0);
+}
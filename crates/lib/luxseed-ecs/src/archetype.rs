@@ -1,6 +1,6 @@
 use std::{alloc::Layout, any::TypeId, collections::HashMap, vec};
 
-use crate::component::Component;
+use crate::{component::Component, storage::AnyVec};
 
 const ENTITY_MIN_INCREMENT: u32 = 64;
 
@@ -9,6 +9,7 @@ pub struct Archetype {
     type_ids: Box<[TypeId]>,
     len: u32,
     entities: Box<[u32]>,
+    columns: Box<[AnyVec]>,
 }
 
 impl Archetype {
@@ -16,6 +17,10 @@ impl Archetype {
         Self::assert_types(&types);
         Self {
             type_ids: types.iter().map(|ty| ty.type_id()).collect(),
+            columns: types
+                .iter()
+                .map(|ty| AnyVec::new(ty.layout(), 0, Some(ty.drop_fn())))
+                .collect(),
             types,
             len: 0,
             entities: Box::new([]),
@@ -26,6 +31,60 @@ impl Archetype {
         self.type_ids.contains(&TypeId::of::<T>())
     }
 
+    pub fn has_dynamic(&self, ty: TypeId) -> bool {
+        self.type_ids.contains(&ty)
+    }
+
+    #[inline]
+    pub fn types(&self) -> &[TypeMeta] {
+        &self.types
+    }
+
+    #[inline]
+    pub fn type_ids(&self) -> &[TypeId] {
+        &self.type_ids
+    }
+
+    /// Ids of the entities stored in the archetype, in storage order
+    #[inline]
+    pub fn entities(&self) -> &[u32] {
+        &self.entities[..self.len as usize]
+    }
+
+    pub fn get<T: Component>(&self, index: u32) -> Option<&T> {
+        let ptr = self.get_dynamic(TypeId::of::<T>(), index)?;
+        Some(unsafe { &*(ptr as *const T) })
+    }
+
+    pub fn get_mut<T: Component>(&mut self, index: u32) -> Option<&mut T> {
+        let ptr = self.get_dynamic(TypeId::of::<T>(), index)?;
+        Some(unsafe { &mut *(ptr as *mut T) })
+    }
+
+    /// Returns a pointer to the component of type `ty` stored at `index`
+    pub(crate) fn get_dynamic(&self, ty: TypeId, index: u32) -> Option<*mut u8> {
+        if index >= self.len {
+            return None;
+        }
+        let column = self.type_ids.iter().position(|id| *id == ty)?;
+        Some(unsafe { self.columns[column].get_unchecked(index as usize) })
+    }
+
+    /// Moves a component into the column of type `ty`.
+    ///
+    /// Every column must receive exactly one component after each `alloc`.
+    ///
+    /// # Safety
+    /// `component` must point to a valid value of type `ty`, which must not be used afterwards.
+    pub(crate) unsafe fn put_dynamic(&mut self, ty: TypeId, component: *const u8) {
+        let column = self
+            .type_ids
+            .iter()
+            .position(|id| *id == ty)
+            .expect("type not in archetype");
+        self.columns[column].push(component);
+    }
+
     /// Allocates a new entity in the archetype and returns the index
     pub(crate) fn alloc(&mut self, entity_id: u32) -> u32 {
         if self.len() == self.capacity() {
@@ -36,8 +95,24 @@ impl Archetype {
         self.len - 1
     }
 
+    /// Removes the entity at `index` by swapping in the last one.
+    ///
+    /// If `drop` is false the components must have been moved out before.
+    ///
+    /// Returns the id of the entity moved into `index`, if any.
     pub(crate) fn free(&mut self, index: u32, drop: bool) -> Option<u32> {
-        todo!()
+        assert!(index < self.len);
+        for column in self.columns.iter_mut() {
+            unsafe { column.swap_remove(index as usize, drop) };
+        }
+
+        self.len -= 1;
+        if index == self.len {
+            return None;
+        }
+        let moved = self.entities[self.len as usize];
+        self.entities[index as usize] = moved;
+        Some(moved)
     }
 
     pub(crate) fn reserve(&mut self, additional: u32) {
@@ -55,8 +130,11 @@ impl Archetype {
         let old_cap = self.capacity();
         let new_cap = old_cap + increment;
         let mut new_entities = vec![!0; new_cap as usize].into_boxed_slice();
-        new_entities[..self.len() as usize].copy_from_slice(&self.entities);
+        new_entities[..self.len() as usize].copy_from_slice(self.entities());
         self.entities = new_entities;
+        for column in self.columns.iter_mut() {
+            column.reserve_exact(new_cap as usize - column.len());
+        }
     }
 
     #[inline]
@@ -74,8 +152,14 @@ impl Archetype {
         self.entities.len() as u32
     }
 
+    /// `types` is sorted, so a component type listed twice shows up as two neighbours.
     fn assert_types(types: &[TypeMeta]) {
-        //todo!()
+        if let Some(pair) = types.windows(2).find(|pair| pair[0] == pair[1]) {
+            panic!(
+                "component {} appears more than once in an archetype",
+                pair[0].type_name()
+            );
+        }
     }
 }
 
@@ -93,6 +177,32 @@ impl Archetypes {
             archetypes: vec![Archetype::new(Vec::new())],
         }
     }
+
+    /// Returns the index of the archetype made of `types`, creating it if needed.
+    pub fn get_or_insert(&mut self, mut types: Vec<TypeMeta>) -> u32 {
+        types.sort_unstable();
+        let key: Box<[TypeId]> = types.iter().map(|ty| ty.type_id()).collect();
+        if let Some(&id) = self.mapping.get(&key) {
+            return id;
+        }
+
+        let id = self.archetypes.len() as u32;
+        self.archetypes.push(Archetype::new(types));
+        self.mapping.insert(key, id);
+        id
+    }
+
+    /// Borrows two different archetypes mutably at once.
+    pub fn get_pair_mut(&mut self, a: u32, b: u32) -> (&mut Archetype, &mut Archetype) {
+        assert_ne!(a, b);
+        if a < b {
+            let (left, right) = self.archetypes.split_at_mut(b as usize);
+            (&mut left[a as usize], &mut right[0])
+        } else {
+            let (left, right) = self.archetypes.split_at_mut(a as usize);
+            (&mut right[0], &mut left[b as usize])
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
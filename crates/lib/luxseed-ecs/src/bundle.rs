@@ -1 +1,152 @@
-pub trait Bundle {}
+use std::mem::ManuallyDrop;
+
+use crate::{
+    archetype::TypeMeta,
+    component::{Component, ComponentBundle},
+};
+
+/// Components spawned together, implemented for tuples of up to 12 components.
+///
+/// The whole tuple lands in its archetype at once instead of moving the entity once per
+/// component. A tuple may hold each component type only once.
+pub trait Bundle: ComponentBundle {}
+impl<T: ComponentBundle> Bundle for T {}
+
+macro_rules! impl_component_bundle {
+    ($($name:ident),+) => {
+        impl<$($name: Component),+> ComponentBundle for ($($name,)+) {
+            fn type_infos(&self) -> Vec<TypeMeta> {
+                vec![$(TypeMeta::new::<$name>()),+]
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn put(self, f: &mut dyn FnMut(*const u8, TypeMeta)) {
+                let ($($name,)+) = self;
+                $(
+                    let $name = ManuallyDrop::new($name);
+                    f(&*$name as *const $name as *const u8, TypeMeta::new::<$name>());
+                )+
+            }
+        }
+    };
+}
+
+impl_component_bundle!(A);
+impl_component_bundle!(A, B);
+impl_component_bundle!(A, B, C);
+impl_component_bundle!(A, B, C, D);
+impl_component_bundle!(A, B, C, D, E);
+impl_component_bundle!(A, B, C, D, E, F);
+impl_component_bundle!(A, B, C, D, E, F, G);
+impl_component_bundle!(A, B, C, D, E, F, G, H);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, sync::Arc};
+
+    use crate::world::World;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f32, f32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f32, f32);
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq)]
+    struct Name(String);
+    #[derive(Debug, PartialEq)]
+    struct Player;
+
+    fn archetype_types(world: &World, entity: crate::entity::Entity) -> Vec<TypeId> {
+        let loc = world.entities.get(entity).unwrap();
+        let mut types = world.archetypes.archetypes[loc.archetype as usize]
+            .type_ids()
+            .to_vec();
+        types.sort();
+        types
+    }
+
+    fn sorted(mut types: Vec<TypeId>) -> Vec<TypeId> {
+        types.sort();
+        types
+    }
+
+    #[test]
+    fn spawn_bundles() {
+        let mut world = World::new();
+        let one = world.spawn((Health(10),));
+        let three = world.spawn((Position(1.0, 2.0), Velocity(0.5, 0.0), Health(3)));
+        let five = world.spawn((
+            Name("hero".to_owned()),
+            Position(0.0, 0.0),
+            Velocity(1.0, 1.0),
+            Health(100),
+            Player,
+        ));
+
+        assert_eq!(archetype_types(&world, one), vec![TypeId::of::<Health>()]);
+        assert_eq!(
+            archetype_types(&world, three),
+            sorted(vec![
+                TypeId::of::<Position>(),
+                TypeId::of::<Velocity>(),
+                TypeId::of::<Health>(),
+            ])
+        );
+        assert_eq!(
+            archetype_types(&world, five),
+            sorted(vec![
+                TypeId::of::<Name>(),
+                TypeId::of::<Position>(),
+                TypeId::of::<Velocity>(),
+                TypeId::of::<Health>(),
+                TypeId::of::<Player>(),
+            ])
+        );
+
+        assert_eq!(world.get_component::<Health>(one).unwrap(), &Health(10));
+        assert!(world.get_component::<Position>(one).is_err());
+        assert_eq!(
+            world.get_component::<Velocity>(three).unwrap(),
+            &Velocity(0.5, 0.0)
+        );
+        let hero = world.entity(five).unwrap();
+        assert_eq!(hero.get::<Name>().unwrap(), &Name("hero".to_owned()));
+        assert_eq!(hero.get::<Health>().unwrap(), &Health(100));
+        assert!(hero.get::<Player>().is_some());
+
+        // The order inside the tuple doesn't matter
+        let reordered = world.spawn((Health(4), Velocity(0.0, 1.0), Position(5.0, 5.0)));
+        let loc = world.entities.get(three).unwrap();
+        assert_eq!(
+            world.entities.get(reordered).unwrap().archetype,
+            loc.archetype
+        );
+        assert_eq!(world.archetypes.archetypes[loc.archetype as usize].len(), 2);
+        assert_eq!(
+            world.get_component::<Position>(reordered).unwrap(),
+            &Position(5.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn bundle_components_are_dropped_once() {
+        let shared = Arc::new(());
+        let mut world = World::new();
+        let entity = world.spawn((shared.clone(), Health(1)));
+        assert_eq!(Arc::strong_count(&shared), 2);
+        world.destroy(entity).unwrap();
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once")]
+    fn duplicate_component_in_bundle() {
+        let mut world = World::new();
+        world.spawn((Health(1), Health(2)));
+    }
+}
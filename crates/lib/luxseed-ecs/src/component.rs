@@ -5,4 +5,18 @@ impl<T: Send + Sync + 'static> Component for T {}
 
 pub trait ComponentBundle {
     fn type_infos(&self) -> Vec<TypeMeta>;
+
+    /// Moves each component out of the bundle by handing a pointer to it to `f`.
+    ///
+    /// # Safety
+    /// `f` must take ownership of every component it receives.
+    unsafe fn put(self, f: &mut dyn FnMut(*const u8, TypeMeta));
+}
+
+impl ComponentBundle for () {
+    fn type_infos(&self) -> Vec<TypeMeta> {
+        Vec::new()
+    }
+
+    unsafe fn put(self, _f: &mut dyn FnMut(*const u8, TypeMeta)) {}
 }
@@ -1,16 +1,45 @@
 use crate::EcsError;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     mem,
     num::NonZeroU32,
     sync::atomic::{AtomicIsize, Ordering},
 };
 
-#[derive(Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Entity {
     pub(crate) id: u32,
     pub(crate) generation: NonZeroU32,
 }
 
+impl Entity {
+    /// Packs the generation and the id into a single `u64`.
+    pub fn to_bits(self) -> u64 {
+        (u64::from(self.generation.get()) << 32) | u64::from(self.id)
+    }
+
+    /// Reconstructs an entity from `to_bits`, returns `None` if the generation is zero.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Some(Self {
+            id: bits as u32,
+            generation: NonZeroU32::new((bits >> 32) as u32)?,
+        })
+    }
+}
+
+impl Serialize for Entity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.to_bits())
+    }
+}
+
+impl<'de> Deserialize<'de> for Entity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = u64::deserialize(deserializer)?;
+        Entity::from_bits(bits).ok_or_else(|| de::Error::custom("invalid entity generation"))
+    }
+}
+
 #[derive(Copy, Clone)]
 pub(crate) struct EntityLocation {
     pub archetype: u32,
@@ -1,6 +1,6 @@
 use std::{marker::PhantomData, ptr::NonNull};
 
-use crate::{archetype::Archetype, component::Component, entity::Entity};
+use crate::{archetype::Archetype, component::Component, entity::Entity, world::World, EcsError};
 
 pub struct EntityRef<'a> {
     entity: Entity,
@@ -19,7 +19,62 @@ impl<'a> EntityRef<'a> {
 
     #[inline]
     pub fn get<T: Component>(&self) -> Option<&'a T> {
-        todo!()
+        self.archetype.get::<T>(self.index)
+    }
+
+    #[inline]
+    pub fn contains<T: Component>(&self) -> bool {
+        self.archetype.has::<T>()
+    }
+
+    #[inline]
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// Mutable access to one entity, adding or removing components moves it to another
+/// archetype so the location is looked up on every call.
+pub struct EntityMut<'a> {
+    entity: Entity,
+    world: &'a mut World,
+}
+
+impl<'a> EntityMut<'a> {
+    pub(crate) fn new(entity: Entity, world: &'a mut World) -> Self {
+        Self { entity, world }
+    }
+
+    #[inline]
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.world.get_component::<T>(self.entity).ok()
+    }
+
+    #[inline]
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.world.get_component_mut::<T>(self.entity).ok()
+    }
+
+    #[inline]
+    pub fn contains<T: Component>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Adds a component, replacing the existing one of the same type.
+    pub fn insert<T: Component>(&mut self, component: T) -> &mut Self {
+        // The entity is alive for as long as `self` borrows the world
+        self.world.add_component(self.entity, component).unwrap();
+        self
+    }
+
+    /// Removes a component and returns it.
+    pub fn remove<T: Component>(&mut self) -> Result<T, EcsError> {
+        self.world.remove_component::<T>(self.entity)
+    }
+
+    #[inline]
+    pub fn id(&self) -> Entity {
+        self.entity
     }
 }
 
@@ -38,3 +93,48 @@ pub trait ComponentRef<'a> {
 impl<'a, T: Component> ComponentRef<'a> for &'a T {
     type Ref = &'a T;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{world::World, EcsError};
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq)]
+    struct Shield(u32);
+
+    #[test]
+    fn read_through_entity_ref() {
+        let mut world = World::new();
+        let player = world.spawn((Health(10),));
+        let entity = world.entity(player).unwrap();
+        assert_eq!(entity.id(), player);
+        assert_eq!(entity.get::<Health>(), Some(&Health(10)));
+        assert!(entity.contains::<Health>());
+        assert!(!entity.contains::<Shield>());
+    }
+
+    #[test]
+    fn mutate_through_entity_mut() {
+        let mut world = World::new();
+        let player = world.spawn((Health(10),));
+        let mut entity = world.entity_mut(player).unwrap();
+        entity.get_mut::<Health>().unwrap().0 -= 3;
+        assert_eq!(entity.get::<Health>(), Some(&Health(7)));
+
+        entity.insert(Shield(5));
+        assert!(entity.contains::<Shield>());
+        assert_eq!(entity.get::<Health>(), Some(&Health(7)));
+
+        assert_eq!(entity.remove::<Health>().unwrap(), Health(7));
+        assert!(!entity.contains::<Health>());
+        assert!(matches!(
+            entity.remove::<Health>(),
+            Err(EcsError::MissingComponent)
+        ));
+
+        assert!(world.entity(player).unwrap().contains::<Shield>());
+        world.destroy(player).unwrap();
+        assert!(world.entity_mut(player).is_err());
+    }
+}
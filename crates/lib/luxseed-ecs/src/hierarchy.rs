@@ -0,0 +1,171 @@
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{entity::Entity, world::World, EcsError};
+
+/// The parent of an entity. Maintained by [`World::set_parent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    #[inline]
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+/// The direct children of an entity, in the order they were attached.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl World {
+    /// Attaches `child` to `parent`, detaching it from its previous parent first.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), EcsError> {
+        if !self.contain_entity(parent) {
+            return Err(EcsError::EntityNotFound);
+        }
+
+        // Walk up from the new parent to make sure we are not creating a cycle
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == child {
+                return Err(EcsError::HierarchyCycle);
+            }
+            ancestor = self.get_component::<Parent>(current).ok().map(Parent::get);
+        }
+
+        self.remove_parent(child)?;
+        self.add_component(child, Parent(parent))?;
+        match self.get_component_mut::<Children>(parent) {
+            Ok(children) => children.0.push(child),
+            Err(_) => self.add_component(parent, Children(vec![child]))?,
+        }
+        Ok(())
+    }
+
+    /// Detaches `child` from its parent, if any. Its own children are kept.
+    pub fn remove_parent(&mut self, child: Entity) -> Result<(), EcsError> {
+        let parent = match self.remove_component::<Parent>(child) {
+            Ok(parent) => parent.get(),
+            Err(EcsError::MissingComponent) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        if let Ok(children) = self.get_component_mut::<Children>(parent) {
+            children.0.retain(|&e| e != child);
+            if children.is_empty() {
+                self.remove_component::<Children>(parent)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Destroys the entity and all of its descendants.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.remove_parent(entity)?;
+
+        let mut stack = vec![entity];
+        while let Some(current) = stack.pop() {
+            if let Ok(children) = self.get_component::<Children>(current) {
+                stack.extend_from_slice(children);
+            }
+            self.destroy(current)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn children_of(world: &World, entity: Entity) -> Vec<Entity> {
+        world
+            .get_component::<Children>(entity)
+            .map(|c| c.to_vec())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn build_three_level_hierarchy() {
+        let mut world = World::new();
+        let root = world.spawn(());
+        let child = world.spawn(());
+        let grandchild = world.spawn(());
+        world.add_component(child, 7u32).unwrap();
+
+        world.set_parent(child, root).unwrap();
+        world.set_parent(grandchild, child).unwrap();
+
+        assert_eq!(children_of(&world, root), vec![child]);
+        assert_eq!(children_of(&world, child), vec![grandchild]);
+        assert_eq!(world.get_component::<Parent>(child).unwrap().get(), root);
+        assert_eq!(
+            world.get_component::<Parent>(grandchild).unwrap().get(),
+            child
+        );
+        assert!(world.get_component::<Parent>(root).is_err());
+        assert_eq!(*world.get_component::<u32>(child).unwrap(), 7);
+
+        assert!(matches!(
+            world.set_parent(root, grandchild),
+            Err(EcsError::HierarchyCycle)
+        ));
+    }
+
+    #[test]
+    fn reparent_updates_both_sides() {
+        let mut world = World::new();
+        let a = world.spawn(());
+        let b = world.spawn(());
+        let child = world.spawn(());
+        let sibling = world.spawn(());
+
+        world.set_parent(child, a).unwrap();
+        world.set_parent(sibling, a).unwrap();
+        world.set_parent(child, b).unwrap();
+
+        assert_eq!(children_of(&world, a), vec![sibling]);
+        assert_eq!(children_of(&world, b), vec![child]);
+        assert_eq!(world.get_component::<Parent>(child).unwrap().get(), b);
+
+        world.set_parent(sibling, b).unwrap();
+        assert!(world.get_component::<Children>(a).is_err());
+        assert_eq!(children_of(&world, b), vec![child, sibling]);
+    }
+
+    #[test]
+    fn despawn_subtree() {
+        let mut world = World::new();
+        let root = world.spawn(());
+        let keep = world.spawn(());
+        let branch = world.spawn(());
+        let leaf_a = world.spawn(());
+        let leaf_b = world.spawn(());
+
+        world.set_parent(keep, root).unwrap();
+        world.set_parent(branch, root).unwrap();
+        world.set_parent(leaf_a, branch).unwrap();
+        world.set_parent(leaf_b, branch).unwrap();
+        world.add_component(leaf_b, String::from("leaf")).unwrap();
+
+        world.despawn_recursive(branch).unwrap();
+
+        for e in [branch, leaf_a, leaf_b] {
+            assert!(!world.contain_entity(e));
+        }
+        assert!(world.contain_entity(root));
+        assert!(world.contain_entity(keep));
+        assert_eq!(children_of(&world, root), vec![keep]);
+        assert_eq!(world.get_component::<Parent>(keep).unwrap().get(), root);
+    }
+}
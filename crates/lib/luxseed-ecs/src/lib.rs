@@ -5,6 +5,9 @@ pub mod bundle;
 pub mod component;
 pub mod entity;
 pub mod entity_ref;
+pub mod hierarchy;
+pub mod schedule;
+pub mod serialize;
 pub mod storage;
 pub mod unsafe_world_cell;
 pub mod world;
@@ -15,4 +18,12 @@ pub enum EcsError {
     EntityNotFound,
     #[error("missing component")]
     MissingComponent,
+    #[error("hierarchy would contain a cycle")]
+    HierarchyCycle,
+    #[error("unknown component `{0}`")]
+    UnknownComponent(String),
+    #[error("invalid serialized data: {0}")]
+    InvalidData(String),
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
 }
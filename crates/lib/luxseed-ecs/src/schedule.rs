@@ -0,0 +1,308 @@
+use std::{any::TypeId, collections::HashSet};
+
+use crate::{unsafe_world_cell::UnsafeWorldCell, world::World};
+
+/// The set of data a system reads and writes.
+///
+/// Any `'static` type can be tracked, so resources can be declared the same way as components.
+#[derive(Default, Clone, Debug)]
+pub struct Access {
+    reads: HashSet<TypeId>,
+    writes: HashSet<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.reads.insert(TypeId::of::<T>());
+        self
+    }
+
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.writes.insert(TypeId::of::<T>());
+        self
+    }
+
+    /// Two accesses are compatible when neither writes something the other touches.
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        self.writes.is_disjoint(&other.writes)
+            && self.writes.is_disjoint(&other.reads)
+            && self.reads.is_disjoint(&other.writes)
+    }
+}
+
+pub trait System: Send {
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// The data touched by `run`. Accessing anything else is undefined behavior.
+    fn access(&self) -> Access;
+
+    fn run(&mut self, world: UnsafeWorldCell<'_>);
+}
+
+struct FnSystem<F> {
+    name: String,
+    access: Access,
+    f: F,
+}
+
+impl<F: FnMut(UnsafeWorldCell<'_>) + Send> System for FnSystem<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn access(&self) -> Access {
+        self.access.clone()
+    }
+
+    fn run(&mut self, world: UnsafeWorldCell<'_>) {
+        (self.f)(world)
+    }
+}
+
+/// Runs systems in insertion order, executing non-conflicting ones concurrently.
+///
+/// Systems are grouped into stages: a system is placed in the stage right after the last one
+/// holding a system it conflicts with, so conflicting systems always run in insertion order.
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Box<dyn System>>,
+    stage_of: Vec<usize>,
+    stage_count: usize,
+    /// Threads running the stages with several systems, as many as the widest stage has
+    /// systems. Kept from one run to the next.
+    workers: Option<rayon::ThreadPool>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_system(&mut self, system: impl System + 'static) -> &mut Self {
+        let access = system.access();
+        let stage = self
+            .systems
+            .iter()
+            .zip(self.stage_of.iter())
+            .filter(|(other, _)| !other.access().is_compatible(&access))
+            .map(|(_, &stage)| stage + 1)
+            .max()
+            .unwrap_or(0);
+
+        self.systems.push(Box::new(system));
+        self.stage_of.push(stage);
+        self.stage_count = self.stage_count.max(stage + 1);
+        self
+    }
+
+    pub fn add_fn(
+        &mut self,
+        name: &str,
+        access: Access,
+        f: impl FnMut(UnsafeWorldCell<'_>) + Send + 'static,
+    ) -> &mut Self {
+        self.add_system(FnSystem {
+            name: name.to_owned(),
+            access,
+            f,
+        })
+    }
+
+    #[inline]
+    pub fn stage_count(&self) -> usize {
+        self.stage_count
+    }
+
+    /// Names of the systems in each stage.
+    pub fn stages(&self) -> Vec<Vec<&str>> {
+        let mut stages = vec![Vec::new(); self.stage_count];
+        for (system, &stage) in self.systems.iter().zip(self.stage_of.iter()) {
+            stages[stage].push(system.name());
+        }
+        stages
+    }
+
+    pub fn run(&mut self, world: &mut World) {
+        self.prepare_workers();
+        world.flush();
+        let cell = UnsafeWorldCell::new_mutable(world);
+
+        for stage in 0..self.stage_count {
+            let mut systems: Vec<_> = self
+                .systems
+                .iter_mut()
+                .zip(self.stage_of.iter())
+                .filter(|(_, &s)| s == stage)
+                .map(|(system, _)| system)
+                .collect();
+
+            if let [system] = systems.as_mut_slice() {
+                system.run(cell);
+                continue;
+            }
+
+            let workers = self
+                .workers
+                .as_ref()
+                .expect("Worker threads are created above");
+            workers.scope(|scope| {
+                for system in systems {
+                    scope.spawn(move |_| system.run(cell));
+                }
+            });
+        }
+    }
+
+    /// Makes sure there is a worker for every system of the widest stage.
+    fn prepare_workers(&mut self) {
+        let mut widths = vec![0; self.stage_count];
+        self.stage_of.iter().for_each(|&stage| widths[stage] += 1);
+        let widest = widths.into_iter().max().unwrap_or(0);
+        if widest < 2
+            || self
+                .workers
+                .as_ref()
+                .is_some_and(|w| w.current_num_threads() == widest)
+        {
+            return;
+        }
+        let workers = rayon::ThreadPoolBuilder::new()
+            .num_threads(widest)
+            .thread_name(|i| format!("schedule-worker-{}", i))
+            .build()
+            .expect("Failed to create the schedule worker threads");
+        self.workers = Some(workers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    struct A(u32);
+    struct B;
+
+    /// Records the thread it ran on and the highest number of systems seen running together.
+    fn probe(
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+        threads: Arc<Mutex<Vec<thread::ThreadId>>>,
+    ) -> impl FnMut(UnsafeWorldCell<'_>) + Send + 'static {
+        move |_| {
+            threads.lock().unwrap().push(thread::current().id());
+            running.fetch_add(1, Ordering::SeqCst);
+            let start = Instant::now();
+            while running.load(Ordering::SeqCst) < 2 && start.elapsed() < Duration::from_millis(500)
+            {
+                thread::yield_now();
+            }
+            max_running.fetch_max(running.load(Ordering::SeqCst), Ordering::SeqCst);
+            running.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn disjoint_systems_run_in_parallel() {
+        let mut world = World::new();
+        let entity = world.spawn(());
+        world.add_component(entity, A(0)).unwrap();
+        world.add_component(entity, B).unwrap();
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let threads = Arc::new(Mutex::new(Vec::new()));
+
+        let mut schedule = Schedule::new();
+        let mut probe_a = probe(running.clone(), max_running.clone(), threads.clone());
+        schedule.add_fn("write_a", Access::new().write::<A>(), move |world| {
+            probe_a(world);
+            unsafe { world.get_component_mut::<A>(entity).unwrap().0 += 1 };
+        });
+        let mut probe_b = probe(running.clone(), max_running.clone(), threads.clone());
+        schedule.add_fn("write_b", Access::new().write::<B>(), move |world| {
+            probe_b(world);
+            unsafe { world.get_component_mut::<B>(entity).unwrap() };
+        });
+
+        assert_eq!(schedule.stages(), vec![vec!["write_a", "write_b"]]);
+        schedule.run(&mut world);
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        let threads = threads.lock().unwrap();
+        assert_eq!(threads.len(), 2);
+        assert_ne!(threads[0], threads[1]);
+        assert_eq!(world.get_component::<A>(entity).unwrap().0, 1);
+    }
+
+    #[test]
+    fn conflicting_systems_are_serialized() {
+        let mut world = World::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let threads = Arc::new(Mutex::new(Vec::new()));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_fn(
+                "write_a",
+                Access::new().write::<A>(),
+                probe(running.clone(), max_running.clone(), threads.clone()),
+            )
+            .add_fn(
+                "read_a",
+                Access::new().read::<A>().write::<B>(),
+                probe(running.clone(), max_running.clone(), threads.clone()),
+            )
+            .add_fn("read_b", Access::new().read::<B>(), |_| {});
+
+        assert_eq!(
+            schedule.stages(),
+            vec![vec!["write_a"], vec!["read_a"], vec!["read_b"]]
+        );
+        schedule.run(&mut world);
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+        assert!(Access::new()
+            .read::<A>()
+            .is_compatible(&Access::new().read::<A>()));
+    }
+
+    #[test]
+    fn repeated_runs_reuse_the_worker_threads() {
+        let mut world = World::new();
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+
+        let mut schedule = Schedule::new();
+        for name in ["write_a", "write_b", "write_c"] {
+            let threads = threads.clone();
+            schedule.add_fn(name, Access::new(), move |_| {
+                threads.lock().unwrap().insert(thread::current().id());
+            });
+        }
+        assert_eq!(schedule.stage_count(), 1);
+        for _ in 0..20 {
+            schedule.run(&mut world);
+        }
+        // Spawning threads for every run would have used 60 of them
+        let threads = threads.lock().unwrap();
+        assert!(
+            !threads.is_empty() && threads.len() <= 3,
+            "ran on {} threads",
+            threads.len()
+        );
+        assert!(!threads.contains(&thread::current().id()));
+    }
+}
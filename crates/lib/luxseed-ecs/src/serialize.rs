@@ -0,0 +1,356 @@
+use std::{any::TypeId, collections::HashMap};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    component::Component,
+    entity::Entity,
+    hierarchy::{Children, Parent},
+    world::World,
+    EcsError,
+};
+
+/// Components holding entity references implement this so they can be remapped on load.
+pub trait MapEntities {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>);
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        self.0 = map.get(&self.0).copied().unwrap_or(self.0);
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, map: &HashMap<Entity, Entity>) {
+        for child in self.0.iter_mut() {
+            *child = map.get(child).copied().unwrap_or(*child);
+        }
+    }
+}
+
+type SerializeFn = fn(*const u8) -> Result<Value, serde_json::Error>;
+type DeserializeFn =
+    fn(&mut World, Entity, &Value, &HashMap<Entity, Entity>) -> Result<(), EcsError>;
+
+struct ComponentEntry {
+    name: String,
+    serialize: SerializeFn,
+    deserialize: DeserializeFn,
+}
+
+/// Maps component types to stable string ids and their serialization functions.
+///
+/// Components that are not registered are skipped when serializing a world.
+pub struct ComponentRegistry {
+    entries: Vec<ComponentEntry>,
+    by_type: HashMap<TypeId, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+impl ComponentRegistry {
+    /// Creates a registry with the hierarchy components already registered.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            entries: Vec::new(),
+            by_type: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+        registry.register_mapped::<Parent>("luxseed::Parent");
+        registry.register_mapped::<Children>("luxseed::Children");
+        registry
+    }
+
+    pub fn register<T: Component + Serialize + DeserializeOwned>(&mut self, name: &str) {
+        self.insert::<T>(name, deserialize_component::<T>);
+    }
+
+    /// Registers a component whose entity references are remapped on load.
+    pub fn register_mapped<T: Component + Serialize + DeserializeOwned + MapEntities>(
+        &mut self,
+        name: &str,
+    ) {
+        self.insert::<T>(name, deserialize_mapped_component::<T>);
+    }
+
+    fn insert<T: Component + Serialize>(&mut self, name: &str, deserialize: DeserializeFn) {
+        let entry = ComponentEntry {
+            name: name.to_owned(),
+            serialize: serialize_component::<T>,
+            deserialize,
+        };
+        let index = match self.by_type.get(&TypeId::of::<T>()) {
+            Some(&index) => {
+                self.by_name.remove(&self.entries[index].name);
+                self.entries[index] = entry;
+                index
+            }
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+        self.by_type.insert(TypeId::of::<T>(), index);
+        self.by_name.insert(name.to_owned(), index);
+    }
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn serialize_component<T: Component + Serialize>(
+    ptr: *const u8,
+) -> Result<Value, serde_json::Error> {
+    serde_json::to_value(unsafe { &*(ptr as *const T) })
+}
+
+fn deserialize_component<T: Component + DeserializeOwned>(
+    world: &mut World,
+    entity: Entity,
+    value: &Value,
+    _map: &HashMap<Entity, Entity>,
+) -> Result<(), EcsError> {
+    world.add_component(entity, T::deserialize(value)?)
+}
+
+fn deserialize_mapped_component<T: Component + DeserializeOwned + MapEntities>(
+    world: &mut World,
+    entity: Entity,
+    value: &Value,
+    map: &HashMap<Entity, Entity>,
+) -> Result<(), EcsError> {
+    let mut component = T::deserialize(value)?;
+    component.map_entities(map);
+    world.add_component(entity, component)
+}
+
+impl World {
+    /// Serializes every entity and its registered components.
+    ///
+    /// The output is a list of `{ "entity": id, "components": { name: value } }` objects.
+    pub fn serialize(&self, registry: &ComponentRegistry) -> Result<Value, EcsError> {
+        let mut entities = Vec::new();
+        for archetype in self.archetypes.archetypes.iter() {
+            for (index, &id) in archetype.entities().iter().enumerate() {
+                let entity = Entity {
+                    id,
+                    generation: self.entities.metas[id as usize].generation,
+                };
+
+                let mut components = Map::new();
+                for ty in archetype.types() {
+                    if let Some(&entry) = registry.by_type.get(&ty.type_id()) {
+                        let entry = &registry.entries[entry];
+                        let ptr = archetype.get_dynamic(ty.type_id(), index as u32).unwrap();
+                        components.insert(entry.name.clone(), (entry.serialize)(ptr)?);
+                    }
+                }
+
+                let mut object = Map::new();
+                object.insert("entity".to_owned(), serde_json::to_value(entity)?);
+                object.insert("components".to_owned(), Value::Object(components));
+                entities.push(Value::Object(object));
+            }
+        }
+        Ok(Value::Array(entities))
+    }
+
+    /// Spawns the entities from `serialize` into this world.
+    ///
+    /// Every entity is given a fresh id, references held by `MapEntities` components are
+    /// remapped accordingly. Returns the mapping from the saved entities to the new ones.
+    /// On error the world is left as it was, every entity spawned so far is destroyed.
+    pub fn deserialize(
+        &mut self,
+        registry: &ComponentRegistry,
+        value: &Value,
+    ) -> Result<HashMap<Entity, Entity>, EcsError> {
+        let records = value
+            .as_array()
+            .ok_or_else(|| EcsError::InvalidData("expected an array of entities".to_owned()))?;
+
+        let mut map = HashMap::with_capacity(records.len());
+        if let Err(err) = self.deserialize_records(registry, records, &mut map) {
+            for entity in map.into_values() {
+                self.destroy(entity)?;
+            }
+            return Err(err);
+        }
+        Ok(map)
+    }
+
+    fn deserialize_records(
+        &mut self,
+        registry: &ComponentRegistry,
+        records: &[Value],
+        map: &mut HashMap<Entity, Entity>,
+    ) -> Result<(), EcsError> {
+        for record in records {
+            let saved = Entity::deserialize(&record["entity"])?;
+            map.insert(saved, self.spawn(()));
+        }
+
+        for record in records {
+            let entity = map[&Entity::deserialize(&record["entity"])?];
+            let components = record["components"]
+                .as_object()
+                .ok_or_else(|| EcsError::InvalidData("expected a component map".to_owned()))?;
+            for (name, value) in components {
+                let entry = registry
+                    .by_name
+                    .get(name)
+                    .ok_or_else(|| EcsError::UnknownComponent(name.clone()))?;
+                (registry.entries[*entry].deserialize)(self, entity, value, map)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+
+    struct Transient;
+
+    fn registry() -> ComponentRegistry {
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Position>("test::Position");
+        registry.register::<Name>("test::Name");
+        registry
+    }
+
+    #[test]
+    fn round_trip_hundred_entities() {
+        let mut world = World::new();
+        let mut entities = Vec::new();
+        for i in 0..100 {
+            let entity = world.spawn(());
+            world
+                .add_component(
+                    entity,
+                    Position {
+                        x: i as f32,
+                        y: -(i as f32),
+                    },
+                )
+                .unwrap();
+            if i % 3 == 0 {
+                world
+                    .add_component(entity, Name(format!("entity {i}")))
+                    .unwrap();
+            }
+            if i % 10 == 0 {
+                world.add_component(entity, Transient).unwrap();
+            }
+            if i > 0 && i % 5 == 0 {
+                world.set_parent(entity, entities[i - 5]).unwrap();
+            }
+            entities.push(entity);
+        }
+
+        let value = world.serialize(&registry()).unwrap();
+
+        // Load into a world that already holds entities so the ids have to be remapped
+        let mut loaded = World::new();
+        for _ in 0..7 {
+            loaded.spawn(());
+        }
+        let map = loaded.deserialize(&registry(), &value).unwrap();
+        assert_eq!(map.len(), 100);
+
+        for (i, old) in entities.iter().enumerate() {
+            let new = map[old];
+            assert_eq!(
+                loaded.get_component::<Position>(new).unwrap(),
+                world.get_component::<Position>(*old).unwrap()
+            );
+            assert_eq!(
+                loaded.get_component::<Name>(new).ok(),
+                world.get_component::<Name>(*old).ok()
+            );
+            assert!(loaded.get_component::<Transient>(new).is_err());
+
+            match world.get_component::<Parent>(*old) {
+                Ok(parent) => {
+                    assert_eq!(
+                        loaded.get_component::<Parent>(new).unwrap().get(),
+                        map[&parent.get()]
+                    )
+                }
+                Err(_) => assert!(loaded.get_component::<Parent>(new).is_err()),
+            }
+            if i % 5 == 0 && i < 95 {
+                let children = loaded.get_component::<Children>(new).unwrap();
+                assert_eq!(&children[..], &[map[&entities[i + 5]]]);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_component_is_an_error() {
+        let mut world = World::new();
+        let entity = world.spawn(());
+        world
+            .add_component(entity, Position { x: 1.0, y: 2.0 })
+            .unwrap();
+        let value = world.serialize(&registry()).unwrap();
+
+        let mut loaded = World::new();
+        assert!(matches!(
+            loaded.deserialize(&ComponentRegistry::new(), &value),
+            Err(EcsError::UnknownComponent(name)) if name == "test::Position"
+        ));
+    }
+
+    #[test]
+    fn failed_load_leaves_the_world_untouched() {
+        let mut world = World::new();
+        for i in 0..3 {
+            let entity = world.spawn(());
+            world
+                .add_component(
+                    entity,
+                    Position {
+                        x: i as f32,
+                        y: 0.0,
+                    },
+                )
+                .unwrap();
+        }
+        let mut value = world.serialize(&registry()).unwrap();
+        // The last entity's position is malformed, the first two load fine
+        value[2]["components"]["test::Position"] = serde_json::json!({ "x": "one" });
+
+        let mut loaded = World::new();
+        let existing = loaded.spawn(());
+        loaded
+            .add_component(existing, Position { x: 9.0, y: 9.0 })
+            .unwrap();
+        assert!(matches!(
+            loaded.deserialize(&registry(), &value),
+            Err(EcsError::Serialization(_))
+        ));
+
+        // Only the entity that was there before is left
+        let left = loaded.serialize(&registry()).unwrap();
+        assert_eq!(left.as_array().unwrap().len(), 1);
+        assert_eq!(
+            loaded.get_component::<Position>(existing).unwrap(),
+            &Position { x: 9.0, y: 9.0 }
+        );
+    }
+}
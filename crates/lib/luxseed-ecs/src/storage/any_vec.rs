@@ -1,6 +1,6 @@
-use std::num::NonZeroUsize;
 use std::{alloc::Layout, ptr::NonNull};
 
+/// A type-erased vector storing values of a single layout contiguously.
 pub struct AnyVec {
     data: NonNull<u8>,
     capacity: usize,
@@ -9,26 +9,94 @@ pub struct AnyVec {
     drop_fn: Option<unsafe fn(*mut u8)>,
 }
 
+// Only `Component` values (which are `Send + Sync`) are stored in an `AnyVec`.
+unsafe impl Send for AnyVec {}
+unsafe impl Sync for AnyVec {}
+
 impl AnyVec {
     pub fn new(layout: Layout, capacity: usize, drop: Option<unsafe fn(*mut u8)>) -> Self {
-        let data = unsafe { std::alloc::alloc(layout) };
-        Self {
-            data: NonNull::new(data).expect("Failed to allocate memory"),
-            capacity,
+        let mut vec = Self {
+            data: Self::dangling(layout),
+            capacity: if layout.size() == 0 { usize::MAX } else { 0 },
             len: 0,
             layout,
             drop_fn: drop,
-        }
+        };
+        vec.reserve_exact(capacity);
+        vec
+    }
+
+    fn dangling(layout: Layout) -> NonNull<u8> {
+        NonNull::new(layout.align() as *mut u8).unwrap()
+    }
+
+    fn array_layout(&self, capacity: usize) -> Layout {
+        Layout::from_size_align(self.layout.size() * capacity, self.layout.align())
+            .expect("capacity overflow")
     }
 
-    fn grow_exact(&mut self, increment: NonZeroUsize) {
-        let new_capacity = self.capacity + increment.get();
-        let new_layout =
-            Layout::from_size_align(self.layout.size() * new_capacity, self.layout.align())
-                .unwrap();
-        let new_data = unsafe { std::alloc::realloc(self.data.as_ptr(), new_layout, new_capacity) };
+    /// Makes sure there is room for at least `additional` more elements.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        let required = self.len + additional;
+        if required <= self.capacity {
+            return;
+        }
+
+        let new_layout = self.array_layout(required);
+        let new_data = unsafe {
+            if self.capacity == 0 {
+                std::alloc::alloc(new_layout)
+            } else {
+                std::alloc::realloc(
+                    self.data.as_ptr(),
+                    self.array_layout(self.capacity),
+                    new_layout.size(),
+                )
+            }
+        };
         self.data = NonNull::new(new_data).expect("Failed to allocate memory");
-        self.capacity = new_capacity;
+        self.capacity = required;
+    }
+
+    /// Moves the value pointed by `value` to the end of the vector.
+    ///
+    /// # Safety
+    /// `value` must point to a valid value matching the layout of this vector, and the caller
+    /// must not use or drop it afterwards.
+    pub unsafe fn push(&mut self, value: *const u8) {
+        if self.len == self.capacity {
+            self.reserve_exact(self.capacity.max(4));
+        }
+        std::ptr::copy_nonoverlapping(value, self.get_unchecked(self.len), self.layout.size());
+        self.len += 1;
+    }
+
+    /// Removes the element at `index` by moving the last element into its place.
+    ///
+    /// If `drop` is false the removed value is not dropped, the caller must have moved it out.
+    ///
+    /// # Safety
+    /// `index` must be in bounds.
+    pub unsafe fn swap_remove(&mut self, index: usize, drop: bool) {
+        debug_assert!(index < self.len);
+        let removed = self.get_unchecked(index);
+        if drop {
+            if let Some(drop_fn) = self.drop_fn {
+                drop_fn(removed);
+            }
+        }
+        let last = self.len - 1;
+        if index != last {
+            std::ptr::copy_nonoverlapping(self.get_unchecked(last), removed, self.layout.size());
+        }
+        self.len = last;
+    }
+
+    /// # Safety
+    /// `index` must be smaller than the capacity.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, index: usize) -> *mut u8 {
+        self.data.as_ptr().add(index * self.layout.size())
     }
 
     pub fn clear(&mut self) {
@@ -68,5 +136,8 @@ impl AnyVec {
 impl Drop for AnyVec {
     fn drop(&mut self) {
         self.clear();
+        if self.layout.size() != 0 && self.capacity != 0 {
+            unsafe { std::alloc::dealloc(self.data.as_ptr(), self.array_layout(self.capacity)) };
+        }
     }
 }
@@ -1 +1,3 @@
-mod any_vec;
\ No newline at end of file
+mod any_vec;
+
+pub(crate) use any_vec::AnyVec;
@@ -1,10 +1,15 @@
-use std::{cell::UnsafeCell, marker::PhantomData};
+use std::{any::TypeId, cell::UnsafeCell, marker::PhantomData};
 
-use crate::world::World;
+use crate::{component::Component, entity::Entity, world::World, EcsError};
 
 #[derive(Copy, Clone)]
 pub struct UnsafeWorldCell<'a>(*mut World, PhantomData<(&'a World, &'a UnsafeCell<World>)>);
 
+// Sharing the cell across threads is fine as long as the accesses made through it do not
+// overlap, which is what `Schedule` guarantees from the declared system accesses.
+unsafe impl Send for UnsafeWorldCell<'_> {}
+unsafe impl Sync for UnsafeWorldCell<'_> {}
+
 impl<'a> UnsafeWorldCell<'a> {
     #[inline]
     pub(crate) fn new_readonly(world: &'a World) -> Self {
@@ -15,4 +20,31 @@ impl<'a> UnsafeWorldCell<'a> {
     pub(crate) fn new_mutable(world: &'a mut World) -> Self {
         UnsafeWorldCell(world as *mut World, PhantomData)
     }
+
+    /// # Safety
+    /// No structural change (spawn, insert, remove, destroy) may happen while the reference lives.
+    #[inline]
+    pub unsafe fn world(self) -> &'a World {
+        &*self.0
+    }
+
+    /// # Safety
+    /// Nobody may be writing components of type `T` at the same time.
+    pub unsafe fn get_component<T: Component>(self, entity: Entity) -> Result<&'a T, EcsError> {
+        self.world().get_component::<T>(entity)
+    }
+
+    /// # Safety
+    /// The caller must have exclusive access to the components of type `T`.
+    pub unsafe fn get_component_mut<T: Component>(
+        self,
+        entity: Entity,
+    ) -> Result<&'a mut T, EcsError> {
+        let world = self.world();
+        let loc = world.entities.get(entity)?;
+        let ptr = world.archetypes.archetypes[loc.archetype as usize]
+            .get_dynamic(TypeId::of::<T>(), loc.index)
+            .ok_or(EcsError::MissingComponent)?;
+        Ok(&mut *(ptr as *mut T))
+    }
 }
@@ -1,14 +1,17 @@
+use std::{any::TypeId, mem::ManuallyDrop};
+
 use crate::{
-    archetype::Archetypes,
+    archetype::{Archetypes, TypeMeta},
+    bundle::Bundle,
     component::{Component, ComponentBundle},
     entity::{Entities, Entity, EntityLocation},
-    entity_ref::EntityRef,
+    entity_ref::{EntityMut, EntityRef},
     EcsError,
 };
 
 pub struct World {
-    entities: Entities,
-    archetypes: Archetypes,
+    pub(crate) entities: Entities,
+    pub(crate) archetypes: Archetypes,
 }
 
 impl World {
@@ -28,7 +31,13 @@ impl World {
         ))
     }
 
-    pub fn spawn_entity(&mut self, bundle: impl ComponentBundle) -> Entity {
+    pub fn entity_mut(&mut self, entity: Entity) -> Result<EntityMut<'_>, EcsError> {
+        self.entities.get(entity)?;
+        Ok(EntityMut::new(entity, self))
+    }
+
+    /// Spawns an entity with every component of `bundle`, e.g. `world.spawn((a, b, c))`.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
         self.flush();
         let entity = self.entities.alloc_entity();
         self.alloc_storage(entity, bundle);
@@ -36,15 +45,17 @@ impl World {
     }
 
     fn alloc_storage(&mut self, entity: Entity, bundle: impl ComponentBundle) {
-        let archetype_id: u32 = 0;
+        let archetype_id = self.archetypes.get_or_insert(bundle.type_infos());
 
         let archetype = &mut self.archetypes.archetypes[archetype_id as usize];
         let index = archetype.alloc(entity.id);
+        unsafe {
+            bundle.put(&mut |ptr, ty| archetype.put_dynamic(ty.type_id(), ptr));
+        }
         self.entities.metas[entity.id as usize].location = EntityLocation {
             archetype: archetype_id,
             index,
         };
-        todo!();
     }
 
     pub fn reserve_entity(&self) -> Entity {
@@ -66,15 +77,112 @@ impl World {
         Ok(())
     }
 
+    /// Adds a component to the entity, replacing the existing one of the same type.
     pub fn add_component<T: Component>(
         &mut self,
         entity: Entity,
         component: T,
     ) -> Result<(), EcsError> {
-        todo!()
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        if let Some(existing) =
+            self.archetypes.archetypes[loc.archetype as usize].get_mut::<T>(loc.index)
+        {
+            *existing = component;
+            return Ok(());
+        }
+
+        let mut types = self.archetypes.archetypes[loc.archetype as usize]
+            .types()
+            .to_vec();
+        types.push(TypeMeta::new::<T>());
+        let target = self.archetypes.get_or_insert(types);
+
+        let component = ManuallyDrop::new(component);
+        unsafe {
+            self.move_entity(entity, loc, target, |_, _| unreachable!());
+            self.archetypes.archetypes[target as usize]
+                .put_dynamic(TypeId::of::<T>(), &*component as *const T as *const u8);
+        }
+        Ok(())
+    }
+
+    /// Removes a component from the entity and returns it.
+    pub fn remove_component<T: Component>(&mut self, entity: Entity) -> Result<T, EcsError> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let source = &self.archetypes.archetypes[loc.archetype as usize];
+        if !source.has::<T>() {
+            return Err(EcsError::MissingComponent);
+        }
+
+        let types = source
+            .types()
+            .iter()
+            .filter(|ty| ty.type_id() != TypeId::of::<T>())
+            .copied()
+            .collect();
+        let target = self.archetypes.get_or_insert(types);
+
+        let mut removed = None;
+        unsafe {
+            self.move_entity(entity, loc, target, |_, ptr| {
+                removed = Some(std::ptr::read(ptr as *const T));
+            });
+        }
+        removed.ok_or(EcsError::MissingComponent)
+    }
+
+    pub fn get_component<T: Component>(&self, entity: Entity) -> Result<&T, EcsError> {
+        let loc = self.entities.get(entity)?;
+        self.archetypes.archetypes[loc.archetype as usize]
+            .get::<T>(loc.index)
+            .ok_or(EcsError::MissingComponent)
+    }
+
+    pub fn get_component_mut<T: Component>(&mut self, entity: Entity) -> Result<&mut T, EcsError> {
+        let loc = self.entities.get(entity)?;
+        self.archetypes.archetypes[loc.archetype as usize]
+            .get_mut::<T>(loc.index)
+            .ok_or(EcsError::MissingComponent)
+    }
+
+    /// Moves the entity to the `target` archetype, carrying over the shared components.
+    ///
+    /// Components missing from the target are handed to `removed`, which takes ownership.
+    /// Components only present in the target must be put by the caller right after.
+    unsafe fn move_entity(
+        &mut self,
+        entity: Entity,
+        loc: EntityLocation,
+        target: u32,
+        mut removed: impl FnMut(TypeMeta, *mut u8),
+    ) {
+        let (source, dest) = self.archetypes.get_pair_mut(loc.archetype, target);
+        let index = dest.alloc(entity.id);
+        for ty in source.types() {
+            let ptr = source.get_dynamic(ty.type_id(), loc.index).unwrap();
+            if dest.has_dynamic(ty.type_id()) {
+                dest.put_dynamic(ty.type_id(), ptr);
+            } else {
+                removed(*ty, ptr);
+            }
+        }
+
+        if let Some(moved) = source.free(loc.index, false) {
+            self.entities.metas[moved as usize].location.index = loc.index;
+        }
+        self.entities.metas[entity.id as usize].location = EntityLocation {
+            archetype: target,
+            index,
+        };
     }
 
     pub fn flush(&mut self) {
-        todo!()
+        let archetype = &mut self.archetypes.archetypes[0];
+        self.entities.flush_reserved_entities(|id, location| {
+            location.archetype = 0;
+            location.index = archetype.alloc(id);
+        });
     }
 }
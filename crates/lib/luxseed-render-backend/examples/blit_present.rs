@@ -0,0 +1,330 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const TARGET_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+const VERT: &str = r#"#version 450
+
+layout(push_constant) uniform Push {
+    float time;
+    float aspect;
+} push;
+
+layout(location = 0) out vec3 outColor;
+
+const vec2 POSITIONS[3] = vec2[](vec2(0.0, -0.6), vec2(0.6, 0.45), vec2(-0.6, 0.45));
+const vec3 COLORS[3] = vec3[](vec3(1.0, 0.2, 0.2), vec3(0.2, 1.0, 0.2), vec3(0.2, 0.2, 1.0));
+
+void main() {
+    float c = cos(push.time);
+    float s = sin(push.time);
+    vec2 position = mat2(c, s, -s, c) * POSITIONS[gl_VertexIndex];
+    gl_Position = vec4(position.x / push.aspect, position.y, 0.0, 1.0);
+    outColor = COLORS[gl_VertexIndex];
+}
+"#;
+
+const FRAG: &str = r#"#version 450
+
+layout(location = 0) in vec3 inColor;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(inColor, 1.0);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Blit Present")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            // Up and down change the render resolution
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode:
+                                    Some(key @ (VirtualKeyCode::Up | VirtualKeyCode::Down)),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let step = if key == VirtualKeyCode::Up { 0.25 } else { -0.25 };
+                app.scale = (app.scale + step).clamp(0.25, 1.0);
+                println!("Rendering at {}% of the window resolution.", app.scale * 100.0);
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Color target as big as the window, only the top left `scale` part of it is rendered to.
+pub struct Target {
+    pub extent: (u32, u32),
+    pub image: Handle<Image>,
+    pub view: Handle<ImageView>,
+    pub framebuffer: Handle<Framebuffer>,
+}
+
+impl Target {
+    fn create(sys: &mut RenderSystem, render_pass: Handle<RenderPass>) -> anyhow::Result<Self> {
+        let extent = sys.backend.get_swapchain_extent(sys.swapchain)?;
+        let image = sys.backend.create_image(&ImageCreateDesc {
+            usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC,
+            ..ImageCreateDesc::new_2d("render_target", TARGET_FORMAT, extent.0, extent.1)
+        })?;
+        let view = sys.backend.create_image_view(
+            image,
+            &ImageViewCreateDesc::new_2d(None, ImageAspectFlags::COLOR),
+        )?;
+        let framebuffer = sys.backend.create_framebuffer(&FramebufferCreateDesc {
+            render_pass,
+            color_views: &[view],
+            depth_stencil_view: None,
+            resolve_views: &[],
+            extent: None,
+        })?;
+        Ok(Self { extent, image, view, framebuffer })
+    }
+
+    fn destroy(&self, sys: &mut RenderSystem) -> anyhow::Result<()> {
+        sys.backend.destroy_framebuffer(self.framebuffer)?;
+        sys.backend.destroy_image(self.image)
+    }
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    /// Render resolution relative to the window.
+    pub scale: f32,
+    pub vs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub render_pass: Handle<RenderPass>,
+    pub target: Target,
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "blit_present.vert",
+            VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "blit_present.frag",
+            FRAG,
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        // The pass leaves the target as a color attachment, present_via_blit takes it from there
+        let output = RenderPassOutput::builder()
+            .add_color(
+                TARGET_FORMAT,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let render_pass = sys.backend.create_render_pass(&output)?;
+        let target = Target::create(&mut sys, render_pass)?;
+
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[],
+            push_constant_ranges: &[PushConstantRange {
+                stage_flags: ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: 2 * std::mem::size_of::<f32>() as u32,
+            }],
+        })?;
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[vs, fs],
+            render_pass_output: output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            scale: 0.5,
+            vs,
+            fs,
+            render_pass,
+            target,
+            pipeline_layout,
+            pipeline,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            if self.target.extent != (width, height) {
+                self.sys.backend.device_wait_idle()?;
+                self.target.destroy(&mut self.sys)?;
+                self.target = Target::create(&mut self.sys, self.render_pass)?;
+            }
+            let render_width = ((width as f32 * self.scale) as u32).max(1);
+            let render_height = ((height as f32 * self.scale) as u32).max(1);
+            let push = [self.start.elapsed().as_secs_f32(), width as f32 / height as f32];
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            // The previous frame may still be blitting from the target
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::TRANSFER,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    self.target.image,
+                    ImageAspectFlags::COLOR,
+                    ImageLayout::Undefined,
+                    ImageLayout::ColorAttachmentOptimal,
+                    AccessFlags::empty(),
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )],
+            )?;
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                self.render_pass,
+                self.target.framebuffer,
+                Some(&[ClearColor::new([0.02, 0.02, 0.05, 1.0])]),
+                None,
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_apply_viewport(
+                cb,
+                &Viewport::new(render_width as f32, render_height as f32),
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, render_width, render_height)?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.sys.backend.cmd_push_constants(
+                cb,
+                self.pipeline_layout,
+                ShaderStageFlags::VERTEX,
+                0,
+                as_byte_slice_unchecked(&push),
+            )?;
+            self.sys.backend.cmd_draw(cb, 3, 1, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+
+            // Scale the rendered corner up to the whole back buffer
+            self.sys.backend.present_via_blit(
+                cb,
+                self.target.image,
+                [render_width, render_height],
+                self.sys.swapchain,
+                self.sys.image_index,
+            )?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.target.destroy(&mut self.sys).unwrap();
+        self.sys.backend.destroy_render_pass(self.render_pass).unwrap();
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
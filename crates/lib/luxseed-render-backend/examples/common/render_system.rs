@@ -1,15 +1,23 @@
-extern crate shaderc;
-
-use anyhow::{self, Ok, Result};
-use luxseed_render_backend::{create_render_backend, define::*, enums::*, flag::*, RenderBackend};
+// Shared by the examples, each of them only uses part of it
+#![allow(dead_code)]
+
+use anyhow::{self, bail, Ok, Result};
+use luxseed_render_backend::{
+    create_render_backend, define::*, enums::*, flag::*, frame_pacer::FramePacer,
+    glsl::compile_glsl, upload_context::UploadContext, RenderBackend,
+};
 use luxseed_utility::pool::Handle;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 use winit::window::Window;
 
+/// How many frames the CPU may record ahead of the GPU, independent of the swapchain length.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
+
 pub struct RenderSystem {
     pub backend: Box<dyn RenderBackend>,
     pub surface: Handle<Surface>,
     pub graphics_queue: Handle<Queue>,
+    pub present_queue: Handle<Queue>,
 
     pub image_index: usize,
     pub swapchain: Handle<Swapchain>,
@@ -17,20 +25,47 @@ pub struct RenderSystem {
     pub swapchain_output: RenderPassOutput,
     pub swapchain_framebuffers: Vec<Handle<Framebuffer>>,
 
+    /// Per-frame resources are indexed by `frame`, per-image ones by `image_index`.
     pub frame: usize,
     pub max_frames_in_flight: usize,
     pub in_flight_fences: Vec<Handle<Fence>>,
+    /// Per frame, the image isn't known before it is acquired.
     pub image_availables: Vec<Handle<Semaphore>>,
+    /// Per swapchain image, so a semaphore is never signaled again before the present that
+    /// waits on it has consumed it.
     pub render_finisheds: Vec<Handle<Semaphore>>,
+    /// Fence of the frame that last rendered to each swapchain image.
+    pub images_in_flight: Vec<Option<Handle<Fence>>>,
 
     pub command_pool: Handle<CommandPool>,
 
     pub depth_image: Handle<Image>,
     pub depth_image_view: Handle<ImageView>,
+
+    /// Paces frames when vsync is off, see `set_frame_pacing`.
+    pub frame_pacer: Option<FramePacer>,
 }
 
 impl RenderSystem {
     pub fn create(window: &Window) -> Result<Self> {
+        Self::create_with_features(window, RequestedFeatures::empty())
+    }
+
+    /// Like `create`, with core features on top, e.g. `RequestedFeatures::GEOMETRY_SHADER`.
+    pub fn create_with_features(
+        window: &Window,
+        requested_features: RequestedFeatures,
+    ) -> Result<Self> {
+        Self::create_with_device_features(window, DeviceFeatures::empty(), requested_features)
+    }
+
+    /// Like `create_with_features`, with optional device features too, e.g.
+    /// `DeviceFeatures::DYNAMIC_RENDERING`.
+    pub fn create_with_device_features(
+        window: &Window,
+        features: DeviceFeatures,
+        requested_features: RequestedFeatures,
+    ) -> Result<Self> {
         let mut rhi = create_render_backend(
             BackendType::Vulkan,
             RenderBackendCreateDesc {
@@ -38,26 +73,38 @@ impl RenderSystem {
                 app_version: 0,
                 enable_debugging: true,
                 raw_display_handle: window.raw_display_handle(),
+                debug_callback: None,
+                robust_buffer_access: false,
+                min_api_version: (1, 2, 0),
+                additional_instance_extensions: &[],
             },
         )?;
 
-        rhi.create_device(0)?;
-
         let surface = rhi.create_surface(SurfaceCreateDesc {
             raw_display_handle: window.raw_display_handle(),
             raw_window_handle: window.raw_window_handle(),
         })?;
 
-        let format = Format::B8G8R8A8_SRGB;
+        rhi.create_device(&DeviceCreateDesc {
+            present_surface: Some(surface),
+            features,
+            requested_features,
+            ..Default::default()
+        })?;
+
         let swapchain = rhi.create_swapchain(SwapchainCreateDesc {
             width: window.inner_size().width,
             height: window.inner_size().height,
-            surface: surface,
+            surface,
             vsync: true,
-            format,
+            format: Format::B8G8R8A8_SRGB,
+            desired_image_count: 3,
         })?;
-        let max_frames_in_flight = rhi.get_swapchain_image_count(swapchain)? as usize;
+        let format = rhi.get_swapchain_format(swapchain)?;
+        let (width, height) = rhi.get_swapchain_extent(swapchain)?;
+        let image_count = rhi.get_swapchain_image_count(swapchain)? as usize;
         let graphics_queue = rhi.get_queue(QueueType::Graphics)?;
+        let present_queue = rhi.get_queue(QueueType::Present)?;
         let command_pool = rhi.create_command_pool(graphics_queue).unwrap();
 
         let swapchain_output = RenderPassOutput::builder()
@@ -65,6 +112,7 @@ impl RenderSystem {
                 format,
                 ImageLayout::PresentSrcKhr,
                 RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
                 SampleCount::Sample1,
             )
             .set_depth_stencil(
@@ -72,6 +120,7 @@ impl RenderSystem {
                 ImageLayout::DepthStencilAttachmentOptimal,
                 RenderTargetLoadAction::Clear,
                 RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::DontCare,
                 SampleCount::Sample1,
             )
             .build();
@@ -79,11 +128,15 @@ impl RenderSystem {
 
         let mut in_flight_fences = Vec::new();
         let mut image_availables = Vec::new();
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            in_flight_fences.push(rhi.create_fence(true)?);
+            image_availables.push(rhi.create_semaphore()?);
+        }
         let mut render_finisheds = Vec::new();
         let mut swapchain_framebuffers = Vec::new();
 
-        let (depth_image, depth_image_view) =
-            create_depth(&mut rhi, window.inner_size().width, window.inner_size().height)?;
+        let (depth_image, depth_image_view) = create_depth(&mut rhi, width, height)?;
+        let depth_aspects = rhi.get_image_format(depth_image)?.aspect_mask();
 
         transition_image_layout(
             &mut rhi,
@@ -92,12 +145,10 @@ impl RenderSystem {
             depth_image,
             ImageLayout::Undefined,
             ImageLayout::DepthStencilAttachmentOptimal,
-            ImageAspectFlags::DEPTH,
+            depth_aspects,
         )?;
 
-        for i in 0..max_frames_in_flight {
-            in_flight_fences.push(rhi.create_fence(true)?);
-            image_availables.push(rhi.create_semaphore()?);
+        for i in 0..image_count {
             render_finisheds.push(rhi.create_semaphore()?);
 
             let back_buffer = rhi.get_swapchain_back_buffer(swapchain, i as usize)?;
@@ -109,6 +160,8 @@ impl RenderSystem {
                 render_pass: swapchain_render_pass,
                 color_views: &[view],
                 depth_stencil_view: Some(depth_image_view),
+                resolve_views: &[],
+                extent: None,
             })?;
             swapchain_framebuffers.push(fb);
         }
@@ -117,6 +170,7 @@ impl RenderSystem {
             backend: rhi,
             surface,
             graphics_queue,
+            present_queue,
 
             image_index: 0,
             swapchain,
@@ -125,20 +179,27 @@ impl RenderSystem {
             swapchain_framebuffers,
 
             frame: 0,
-            max_frames_in_flight,
+            max_frames_in_flight: MAX_FRAMES_IN_FLIGHT,
             in_flight_fences,
             image_availables,
             render_finisheds,
+            images_in_flight: vec![None; image_count],
 
             command_pool,
 
             depth_image,
             depth_image_view,
+
+            frame_pacer: None,
         })
     }
 
     pub fn begin_frame(&mut self, width: u32, height: u32) -> Result<bool> {
-        self.backend.wait_for_fences(&[self.get_in_flight_fence()], true, u64::MAX)?;
+        if self.backend.wait_for_fences(&[self.get_in_flight_fence()], true, u64::MAX)?
+            == FenceWaitResult::DeviceLost
+        {
+            bail!("Device lost while waiting for the previous frame.");
+        }
 
         let image_index = self.backend.acquire_swapchain_next_image(
             self.swapchain,
@@ -153,6 +214,18 @@ impl RenderSystem {
         }
 
         self.image_index = image_index.0 as usize;
+
+        // The image may still be rendered to by another frame when the swapchain has fewer
+        // images than frames in flight, or hands them out of order
+        if let Some(fence) = self.images_in_flight[self.image_index] {
+            if fence != self.get_in_flight_fence()
+                && self.backend.wait_for_fences(&[fence], true, u64::MAX)?
+                    == FenceWaitResult::DeviceLost
+            {
+                bail!("Device lost while waiting for a swapchain image.");
+            }
+        }
+        self.images_in_flight[self.image_index] = Some(self.get_in_flight_fence());
         self.backend.reset_fences(&[self.get_in_flight_fence()])?;
 
         Ok(true)
@@ -160,7 +233,7 @@ impl RenderSystem {
 
     pub fn end_frame(&mut self, recreate_swapchain: bool, width: u32, height: u32) -> Result<()> {
         let suboptimal = self.backend.queue_present(
-            self.graphics_queue,
+            self.present_queue,
             &QueuePresentDesc {
                 swapchain: self.swapchain,
                 image_index: self.image_index as u32,
@@ -172,10 +245,25 @@ impl RenderSystem {
             self.recreate_swapchain(width, height)?;
         }
 
+        if let Some(pacer) = self.frame_pacer.as_mut() {
+            pacer.wait();
+        }
+
         self.frame = (self.frame + 1) % self.max_frames_in_flight;
         Ok(())
     }
 
+    /// Turns vsync off and paces frames to `target_hz` instead, `None` goes back to vsync.
+    pub fn set_frame_pacing(
+        &mut self,
+        target_hz: Option<f64>,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        self.frame_pacer = target_hz.map(FramePacer::new);
+        self.recreate_swapchain(width, height)
+    }
+
     pub fn get_swapchain_framebuffer(&self) -> Handle<Framebuffer> {
         self.swapchain_framebuffers[self.image_index]
     }
@@ -185,7 +273,7 @@ impl RenderSystem {
     }
 
     pub fn get_render_finished_semaphore(&self) -> Handle<Semaphore> {
-        self.render_finisheds[self.frame]
+        self.render_finisheds[self.image_index]
     }
 
     pub fn get_in_flight_fence(&self) -> Handle<Fence> {
@@ -207,17 +295,20 @@ impl RenderSystem {
         self.cleanup_swapchain()?;
 
         self.swapchain = self.backend.create_swapchain(SwapchainCreateDesc {
-            width: width,
-            height: height,
+            width,
+            height,
             surface: self.surface,
-            vsync: true,
+            vsync: self.frame_pacer.is_none(),
             format: Format::B8G8R8A8_SRGB,
+            desired_image_count: 3,
         })?;
+        let (width, height) = self.backend.get_swapchain_extent(self.swapchain)?;
 
         let (depth_image, depth_image_view) = create_depth(&mut self.backend, width, height)?;
         self.depth_image = depth_image;
         self.depth_image_view = depth_image_view;
 
+        let depth_aspects = self.backend.get_image_format(depth_image)?.aspect_mask();
         transition_image_layout(
             &mut self.backend,
             self.command_pool,
@@ -225,11 +316,19 @@ impl RenderSystem {
             depth_image,
             ImageLayout::Undefined,
             ImageLayout::DepthStencilAttachmentOptimal,
-            ImageAspectFlags::DEPTH,
+            depth_aspects,
         )?;
 
+        // The new swapchain may have another number of images
+        for semaphore in self.render_finisheds.drain(..) {
+            self.backend.destroy_semaphore(semaphore)?;
+        }
+        let image_count = self.backend.get_swapchain_image_count(self.swapchain)? as usize;
+        self.images_in_flight = vec![None; image_count];
+
         self.swapchain_framebuffers.clear();
-        for i in 0..self.max_frames_in_flight {
+        for i in 0..image_count {
+            self.render_finisheds.push(self.backend.create_semaphore()?);
             let back_buffer = self.backend.get_swapchain_back_buffer(self.swapchain, i as usize)?;
             let view = self.backend.create_image_view(
                 back_buffer,
@@ -239,6 +338,8 @@ impl RenderSystem {
                 render_pass: self.swapchain_render_pass,
                 color_views: &[view],
                 depth_stencil_view: Some(self.depth_image_view),
+                resolve_views: &[],
+                extent: None,
             })?;
             self.swapchain_framebuffers.push(fb);
         }
@@ -251,7 +352,9 @@ impl RenderSystem {
         for i in 0..self.max_frames_in_flight {
             self.backend.destroy_fence(self.in_flight_fences[i])?;
             self.backend.destroy_semaphore(self.image_availables[i])?;
-            self.backend.destroy_semaphore(self.render_finisheds[i])?;
+        }
+        for semaphore in self.render_finisheds.iter() {
+            self.backend.destroy_semaphore(*semaphore)?;
         }
 
         self.cleanup_swapchain()?;
@@ -276,72 +379,56 @@ pub fn create_depth(
     width: u32,
     height: u32,
 ) -> Result<(Handle<Image>, Handle<ImageView>)> {
-    let depth_format = rhi.get_supported_format_from_candidates(
-        &[Format::D32_SFLOAT, Format::D24_UNORM_S8_UINT, Format::D32_SFLOAT_S8_UINT],
-        ImageTiling::Optimal,
-        FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-    )?;
+    let depth_format = rhi.best_depth_format(false)?;
     let depth_image =
         rhi.create_image(&ImageCreateDesc::new_depth("depth", depth_format, width, height))?;
     let depth_image_view = rhi.create_image_view(
         depth_image,
-        &ImageViewCreateDesc::new_2d(None, ImageAspectFlags::DEPTH),
+        &ImageViewCreateDesc::new_2d(None, depth_format.aspect_mask()),
     )?;
     Ok((depth_image, depth_image_view))
 }
 
-pub fn copy_buffer(
+pub fn upload_image_by_staging_buffer(
     rhi: &mut Box<dyn RenderBackend>,
     command_pool: Handle<CommandPool>,
     queue: Handle<Queue>,
-    src: Handle<Buffer>,
-    dst: Handle<Buffer>,
-    size: u64,
+    image: Handle<Image>,
+    data: &[u8],
+    width: u32,
+    height: u32,
 ) -> Result<()> {
-    let cb = begin_single_time_commands(rhi, command_pool)?;
-    rhi.cmd_copy_buffer(cb, src, dst, &[BufferCopyRegion { size, ..Default::default() }])?;
-    end_single_time_commands(rhi, cb, queue)?;
-    Ok(())
+    let mut upload = UploadContext::begin(rhi.as_mut(), command_pool, queue)?;
+    upload.upload_image(rhi.as_mut(), image, data, width, height)?;
+    upload.finish(rhi.as_mut())
 }
 
-pub fn upload_image_by_staging_buffer(
+/// Mip levels of a block compressed texture, mip 0 first.
+pub struct CompressedImageData<'a> {
+    pub data: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub mip_levels: u32,
+}
+
+/// Uploads every mip level of a block compressed texture.
+pub fn upload_compressed_image(
     rhi: &mut Box<dyn RenderBackend>,
     command_pool: Handle<CommandPool>,
     queue: Handle<Queue>,
     image: Handle<Image>,
-    data: &[u8],
-    width: u32,
-    height: u32,
+    texture: &CompressedImageData,
 ) -> Result<()> {
-    let size = data.len() as u64;
-    let staging_buffer = rhi.create_buffer(&BufferCreateDesc {
-        name: "Staging Buffer",
-        size: size,
-        usage: BufferUsageFlags::TRANSFER_SRC,
-        memory: MemoryLocation::CpuToGpu,
-        initial_data: Some(data),
-    })?;
-    transition_image_layout(
-        rhi,
-        command_pool,
-        queue,
+    let mut upload = UploadContext::begin(rhi.as_mut(), command_pool, queue)?;
+    upload.upload_compressed_image(
+        rhi.as_mut(),
         image,
-        ImageLayout::Undefined,
-        ImageLayout::TransferDstOptimal,
-        ImageAspectFlags::COLOR,
+        texture.data,
+        texture.width,
+        texture.height,
+        texture.mip_levels,
     )?;
-    copy_buffer_to_image(rhi, command_pool, queue, staging_buffer, image, width, height)?;
-    transition_image_layout(
-        rhi,
-        command_pool,
-        queue,
-        image,
-        ImageLayout::TransferDstOptimal,
-        ImageLayout::ShaderReadOnlyOptimal,
-        ImageAspectFlags::COLOR,
-    )?;
-    rhi.destroy_buffer(staging_buffer)?;
-    Ok(())
+    upload.finish(rhi.as_mut())
 }
 
 pub fn upload_buffer_by_staging_buffer(
@@ -351,48 +438,9 @@ pub fn upload_buffer_by_staging_buffer(
     buffer: Handle<Buffer>,
     data: &[u8],
 ) -> Result<()> {
-    let size = data.len() as u64;
-    let staging_buffer = rhi.create_buffer(&BufferCreateDesc {
-        name: "Staging Buffer",
-        size: size,
-        usage: BufferUsageFlags::TRANSFER_SRC,
-        memory: MemoryLocation::CpuToGpu,
-        initial_data: Some(data),
-    })?;
-    copy_buffer(rhi, command_pool, queue, staging_buffer, buffer, size)?;
-    rhi.destroy_buffer(staging_buffer)?;
-    Ok(())
-}
-
-pub fn copy_buffer_to_image(
-    rhi: &mut Box<dyn RenderBackend>,
-    command_pool: Handle<CommandPool>,
-    queue: Handle<Queue>,
-    src: Handle<Buffer>,
-    dst: Handle<Image>,
-    width: u32,
-    height: u32,
-) -> Result<()> {
-    let cb = begin_single_time_commands(rhi, command_pool)?;
-    rhi.cmd_copy_buffer_to_image(
-        cb,
-        src,
-        dst,
-        ImageLayout::TransferDstOptimal,
-        &[BufferImageCopyRegion {
-            buffer_offset: 0,
-            buffer_row_length: 0,
-            buffer_image_height: 0,
-            aspect_mask: ImageAspectFlags::COLOR,
-            mip_level: 0,
-            base_array_layer: 0,
-            layer_count: 1,
-            image_offset: [0, 0, 0],
-            image_extent: [width, height, 1],
-        }],
-    )?;
-    end_single_time_commands(rhi, cb, queue)?;
-    Ok(())
+    let mut upload = UploadContext::begin(rhi.as_mut(), command_pool, queue)?;
+    upload.upload_buffer(rhi.as_mut(), buffer, data)?;
+    upload.finish(rhi.as_mut())
 }
 
 pub fn compile_shader_glsl(
@@ -402,26 +450,11 @@ pub fn compile_shader_glsl(
     stage: ShaderStageFlags,
     entry: &str,
 ) -> Result<Handle<Shader>> {
-    let compiler = shaderc::Compiler::new().unwrap();
     backend.create_shader_module(&ShaderModuleCreation {
         name,
-        code: compiler
-            .compile_into_spirv(
-                code,
-                match stage {
-                    ShaderStageFlags::VERTEX => shaderc::ShaderKind::Vertex,
-                    ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
-                    ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
-                    _ => panic!("Unsupported shader stage"),
-                },
-                "shader.glsl",
-                entry,
-                None,
-            )
-            .unwrap()
-            .as_binary(),
+        code: &compile_glsl(name, code, stage, entry)?,
         stage,
-        entry: entry,
+        entry,
     })
 }
 
@@ -430,7 +463,8 @@ pub fn begin_single_time_commands(
     command_pool: Handle<CommandPool>,
 ) -> Result<Handle<CommandBuffer>> {
     let cb = backend.create_command_buffer(command_pool, CommandBufferLevel::Primary)?;
-    backend.cmd_begin(cb, CommandBufferBeginDesc { one_time_submit: true })?;
+    backend
+        .cmd_begin(cb, CommandBufferBeginDesc { one_time_submit: true, ..Default::default() })?;
     Ok(cb)
 }
 
@@ -444,9 +478,11 @@ pub fn end_single_time_commands(
         queue,
         &QueueSubmitDesc {
             wait_semaphore: None,
+            wait_values: None,
             wait_stage: None,
             command_buffer: &[cb],
             finish_semaphore: None,
+            signal_values: None,
             fence: None,
         },
     )?;
@@ -494,13 +530,15 @@ pub fn transition_image_layout(
         cb,
         src_stage_mask,
         dst_stage_mask,
+        &[],
+        &[],
         &[ImageMemoryBarrier {
-            image: image,
-            old_layout: old_layout,
-            new_layout: new_layout,
+            image,
+            old_layout,
+            new_layout,
             src_queue_family_index: None,
             dst_queue_family_index: None,
-            aspect_mask: aspect_mask,
+            aspect_mask,
             base_mip_level: 0,
             level_count: 1,
             base_array_layer: 0,
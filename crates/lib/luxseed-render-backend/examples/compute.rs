@@ -0,0 +1,173 @@
+use luxseed_render_backend::{
+    create_render_backend, define::*, enums::*, flag::*, glsl::compile_glsl,
+};
+use raw_window_handle::HasRawDisplayHandle;
+use winit::{event_loop::EventLoop, window::WindowBuilder};
+
+const VALUE_COUNT: usize = 256;
+const GROUP_SIZE: usize = 64;
+/// Flips the shader from squaring to cubing the values through a specialization constant.
+const CUBE: bool = true;
+
+const POWER_CS: &str = r#"
+#version 450
+layout(local_size_x_id = 0) in;
+layout(constant_id = 1) const bool CUBE = false;
+layout(set = 0, binding = 0) buffer Values {
+    uint values[];
+};
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    uint value = values[i];
+    values[i] = CUBE ? value * value * value : value * value;
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Only the display handle is needed, nothing is presented
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().with_visible(false).build(&event_loop)?;
+
+    let mut rhi = create_render_backend(
+        BackendType::Vulkan,
+        RenderBackendCreateDesc {
+            app_name: "Luxseed Vulkan - Compute",
+            app_version: 0,
+            enable_debugging: true,
+            raw_display_handle: window.raw_display_handle(),
+            debug_callback: None,
+            robust_buffer_access: false,
+            min_api_version: (1, 2, 0),
+            additional_instance_extensions: &[],
+        },
+    )?;
+    rhi.create_device(&DeviceCreateDesc::default())?;
+    let queue = rhi.get_queue(QueueType::Graphics)?;
+
+    // Storage buffer holding 0..VALUE_COUNT, raised to a power in place by the shader
+    let values: Vec<u32> = (0..VALUE_COUNT as u32).collect();
+    let bytes =
+        unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, values.len() * 4) };
+    let buffer = rhi.create_buffer(&BufferCreateDesc {
+        name: "Compute_Values",
+        size: bytes.len() as u64,
+        usage: BufferUsageFlags::STORAGE_BUFFER,
+        memory: MemoryLocation::CpuToGpu,
+        initial_data: None,
+    })?;
+    rhi.get_buffer_mapped_slice_mut(buffer)?[..bytes.len()].copy_from_slice(bytes);
+    rhi.flush_buffer(buffer, 0, WHOLE_SIZE)?;
+
+    let code = compile_glsl("power.comp", POWER_CS, ShaderStageFlags::COMPUTE, "main")?;
+    let shader = rhi.create_shader_module(&ShaderModuleCreation {
+        name: "Power_CS",
+        code: &code,
+        stage: ShaderStageFlags::COMPUTE,
+        entry: "main",
+    })?;
+
+    let descriptor_set_layout = rhi.create_descriptor_set_layout(
+        &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+            index: 0,
+            type_: DescriptorType::StorageBuffer,
+            count: 1,
+            stage_flags: ShaderStageFlags::COMPUTE,
+            flags: DescriptorBindingFlags::empty(),
+        }),
+    )?;
+    let descriptor_pool = rhi.create_descriptor_pool(&DescriptorPoolCreateDesc {
+        max_sets: 1,
+        pool_sizes: &[DescriptorPoolSize {
+            descriptor_type: DescriptorType::StorageBuffer,
+            descriptor_count: 1,
+        }],
+        update_after_bind: false,
+    })?;
+    let descriptor_set = rhi.create_descriptor_set(
+        &DescriptorSetCreateDesc::new(descriptor_pool, descriptor_set_layout)
+            .bind_storage_buffer(0, buffer),
+    )?;
+    let pipeline_layout = rhi.create_pipeline_layout(&PipelineLayoutCreateDesc {
+        descriptor_set_layouts: &[descriptor_set_layout],
+        push_constant_ranges: &[],
+    })?;
+    // The workgroup size and the power are picked here, not in the GLSL
+    let group_size = (GROUP_SIZE as u32).to_ne_bytes();
+    let cube = (CUBE as u32).to_ne_bytes();
+    let pipeline = rhi.create_compute_pipeline(&ComputePipelineCreateDesc {
+        shader,
+        pipeline_layout,
+        specialization: &[
+            SpecializationConstant { id: 0, data: &group_size },
+            SpecializationConstant { id: 1, data: &cube },
+        ],
+    })?;
+
+    // Dispatch and wait for it
+    let command_pool = rhi.create_command_pool(queue)?;
+    let cb = rhi.create_command_buffer(command_pool, CommandBufferLevel::Primary)?;
+    rhi.cmd_begin(cb, CommandBufferBeginDesc { one_time_submit: true, ..Default::default() })?;
+    rhi.cmd_bind_compute_pipeline(cb, pipeline)?;
+    rhi.cmd_bind_descriptor_sets(
+        cb,
+        PipelineBindPoint::Compute,
+        pipeline_layout,
+        0,
+        &[descriptor_set],
+        &[],
+    )?;
+    rhi.cmd_dispatch(cb, (VALUE_COUNT / GROUP_SIZE) as u32, 1, 1)?;
+    // Make the shader writes visible to the host read below
+    rhi.cmd_pipeline_barrier(
+        cb,
+        PipelineStageFlags::COMPUTE_SHADER,
+        PipelineStageFlags::HOST,
+        &[],
+        &[BufferMemoryBarrier::whole_buffer(
+            buffer,
+            AccessFlags::SHADER_WRITE,
+            AccessFlags::HOST_READ,
+        )],
+        &[],
+    )?;
+    rhi.cmd_end(cb)?;
+
+    let fence = rhi.create_fence(false)?;
+    rhi.queue_submit(
+        queue,
+        &QueueSubmitDesc {
+            wait_semaphore: None,
+            wait_values: None,
+            wait_stage: None,
+            command_buffer: &[cb],
+            finish_semaphore: None,
+            signal_values: None,
+            fence: Some(fence),
+        },
+    )?;
+    if rhi.wait_for_fences(&[fence], true, u64::MAX)? != FenceWaitResult::Signaled {
+        anyhow::bail!("Compute dispatch didn't finish.");
+    }
+
+    // Read back
+    rhi.invalidate_buffer(buffer, 0, WHOLE_SIZE)?;
+    let mapped = rhi.get_buffer_mapped_slice_mut(buffer)?;
+    for (i, chunk) in mapped[..bytes.len()].chunks_exact(4).enumerate() {
+        let value = u32::from_ne_bytes(chunk.try_into()?);
+        let expected = if CUBE { i * i * i } else { i * i };
+        assert_eq!(value, expected as u32, "value {} has the wrong power", i);
+    }
+    println!("Raised {} values to the power of {} on the GPU.", VALUE_COUNT, 2 + CUBE as u32);
+
+    rhi.destroy_fence(fence)?;
+    rhi.destroy_command_buffer(cb)?;
+    rhi.destroy_command_pool(command_pool)?;
+    rhi.destroy_compute_pipeline(pipeline)?;
+    rhi.destroy_pipeline_layout(pipeline_layout)?;
+    rhi.destroy_descriptor_pool(descriptor_pool)?;
+    rhi.destroy_descriptor_set_layout(descriptor_set_layout)?;
+    rhi.destroy_shader_module(shader)?;
+    rhi.destroy_buffer(buffer)?;
+    rhi.destroy_device()?;
+    Ok(())
+}
@@ -0,0 +1,584 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec3, Mat4, Vec3};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::{fs, mem::size_of};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const NORMAL_FORMAT: Format = Format::R32G32B32A32_SFLOAT;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Deferred")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: Vec3,
+    pub color: Vec3,
+    pub normal: Vec3,
+}
+
+impl Vertex {
+    const fn new(pos: Vec3, color: Vec3, normal: Vec3) -> Self {
+        Self { pos, color, normal }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UniformBufferObject {
+    pub model: Mat4,
+    pub view_proj: Mat4,
+}
+
+/// Albedo and normal attachments, sized to the swapchain.
+pub struct GBuffer {
+    pub albedo: Handle<Image>,
+    pub albedo_view: Handle<ImageView>,
+    pub normal: Handle<Image>,
+    pub normal_view: Handle<ImageView>,
+    pub framebuffers: Vec<Handle<Framebuffer>>,
+    pub swapchain: Handle<Swapchain>,
+}
+
+impl GBuffer {
+    fn create(
+        sys: &mut RenderSystem,
+        render_pass: Handle<RenderPass>,
+        width: u32,
+        height: u32,
+    ) -> anyhow::Result<Self> {
+        let mut create_attachment = |name, format| -> anyhow::Result<_> {
+            let image = sys.backend.create_image(&ImageCreateDesc {
+                usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::INPUT_ATTACHMENT,
+                ..ImageCreateDesc::new_2d(name, format, width, height)
+            })?;
+            let view = sys.backend.create_image_view(
+                image,
+                &ImageViewCreateDesc::new_2d(None, ImageAspectFlags::COLOR),
+            )?;
+            Ok((image, view))
+        };
+        let (albedo, albedo_view) = create_attachment("gbuffer_albedo", ALBEDO_FORMAT)?;
+        let (normal, normal_view) = create_attachment("gbuffer_normal", NORMAL_FORMAT)?;
+
+        // The lighting subpass writes the back buffer, so each swapchain image needs a framebuffer
+        let mut framebuffers = Vec::new();
+        for i in 0..sys.backend.get_swapchain_image_count(sys.swapchain)? as usize {
+            let back_buffer = sys.backend.get_swapchain_back_buffer(sys.swapchain, i)?;
+            let view =
+                sys.backend.create_image_view(back_buffer, &ImageViewCreateDesc::default())?;
+            framebuffers.push(sys.backend.create_framebuffer(&FramebufferCreateDesc {
+                render_pass,
+                color_views: &[view, albedo_view, normal_view],
+                depth_stencil_view: Some(sys.depth_image_view),
+                resolve_views: &[],
+                extent: None,
+            })?);
+        }
+
+        Ok(Self {
+            albedo,
+            albedo_view,
+            normal,
+            normal_view,
+            framebuffers,
+            swapchain: sys.swapchain,
+        })
+    }
+
+    fn destroy(&self, sys: &mut RenderSystem) -> anyhow::Result<()> {
+        for fb in self.framebuffers.iter() {
+            sys.backend.destroy_framebuffer(*fb)?;
+        }
+        sys.backend.destroy_image(self.albedo)?;
+        sys.backend.destroy_image(self.normal)?;
+        Ok(())
+    }
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub shaders: Vec<Handle<Shader>>,
+
+    pub render_pass: Handle<RenderPass>,
+    pub gbuffer: GBuffer,
+
+    pub gbuffer_set_layout: Handle<DescriptorSetLayout>,
+    pub gbuffer_pipeline_layout: Handle<PipelineLayout>,
+    pub gbuffer_pipeline: Handle<RasterPipeline>,
+
+    pub lighting_set_layout: Handle<DescriptorSetLayout>,
+    pub lighting_pipeline_layout: Handle<PipelineLayout>,
+    pub lighting_pipeline: Handle<RasterPipeline>,
+    pub lighting_pool: Handle<DescriptorPool>,
+    pub lighting_set: Handle<DescriptorSet>,
+
+    pub descriptor_pool: Handle<DescriptorPool>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+    pub uniform_buffers: Vec<Handle<Buffer>>,
+    pub descriptor_sets: Vec<Handle<DescriptorSet>>,
+
+    pub vertex_buffer: Handle<Buffer>,
+    pub index_buffer: Handle<Buffer>,
+    pub indices: Vec<u16>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let mut shaders = Vec::new();
+        for (file, stage) in [
+            ("deferred_gbuffer.vert", ShaderStageFlags::VERTEX),
+            ("deferred_gbuffer.frag", ShaderStageFlags::FRAGMENT),
+            ("deferred_lighting.vert", ShaderStageFlags::VERTEX),
+            ("deferred_lighting.frag", ShaderStageFlags::FRAGMENT),
+        ] {
+            shaders.push(compile_shader_glsl(
+                &mut sys.backend,
+                file,
+                &fs::read_to_string(format!("assets/luxseed-render-backend-test/{}", file))
+                    .expect("Should have been able to read the file"),
+                stage,
+                "main",
+            )?);
+        }
+
+        // Attachment 0 is the back buffer, 1 and 2 the G-buffer. Subpass 0 fills the G-buffer,
+        // subpass 1 reads it as input attachments and writes the lit result to the back buffer.
+        let output = RenderPassOutput::builder()
+            .add_color(
+                sys.swapchain_output.color_formats[0],
+                ImageLayout::PresentSrcKhr,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .add_color(
+                ALBEDO_FORMAT,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .add_color(
+                NORMAL_FORMAT,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .set_depth_stencil(
+                sys.swapchain_output.depth_stencil_format,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::DontCare,
+                SampleCount::Sample1,
+            )
+            .add_subpass(&[1, 2], &[], true)
+            .add_subpass(&[0], &[1, 2], false)
+            .build();
+        let render_pass = sys.backend.create_render_pass(&output)?;
+        let size = window.inner_size();
+        let gbuffer = GBuffer::create(&mut sys, render_pass, size.width, size.height)?;
+
+        // Cube with per-face colors and normals
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u16> = Vec::new();
+        for (normal, color) in [
+            (Vec3::X, vec3(0.9, 0.3, 0.3)),
+            (Vec3::NEG_X, vec3(0.3, 0.9, 0.3)),
+            (Vec3::Y, vec3(0.3, 0.3, 0.9)),
+            (Vec3::NEG_Y, vec3(0.9, 0.9, 0.3)),
+            (Vec3::Z, vec3(0.3, 0.9, 0.9)),
+            (Vec3::NEG_Z, vec3(0.9, 0.3, 0.9)),
+        ] {
+            let u = vec3(normal.y, normal.z, normal.x);
+            let v = normal.cross(u);
+            let base = vertices.len() as u16;
+            for (su, sv) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let pos = (normal + u * su + v * sv) * 0.5;
+                vertices.push(Vertex::new(pos, color, normal));
+            }
+            indices.extend([base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+
+        let vertex_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Cube_Vertex",
+            size: (vertices.len() * size_of::<Vertex>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            vertex_buffer,
+            as_byte_slice_unchecked(&vertices),
+        )?;
+
+        let index_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Cube_Index",
+            size: (indices.len() * size_of::<u16>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            index_buffer,
+            as_byte_slice_unchecked(&indices),
+        )?;
+
+        // UBOs
+        let mut uniform_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            uniform_buffers.push(sys.backend.create_buffer(&BufferCreateDesc {
+                name: "Cube_UBO",
+                size: size_of::<UniformBufferObject>() as u64,
+                usage: BufferUsageFlags::UNIFORM_BUFFER,
+                memory: MemoryLocation::CpuToGpu,
+                initial_data: None,
+            })?);
+        }
+
+        // G-buffer pass descriptors
+        let gbuffer_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+                index: 0,
+                type_: DescriptorType::UniformBuffer,
+                count: 1,
+                stage_flags: ShaderStageFlags::VERTEX,
+                flags: DescriptorBindingFlags::empty(),
+            }),
+        )?;
+        let descriptor_pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: sys.max_frames_in_flight as u32,
+            pool_sizes: &[DescriptorPoolSize {
+                descriptor_type: DescriptorType::UniformBuffer,
+                descriptor_count: sys.max_frames_in_flight as u32,
+            }],
+            update_after_bind: false,
+        })?;
+        let gbuffer_pipeline_layout =
+            sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+                descriptor_set_layouts: &[gbuffer_set_layout],
+                push_constant_ranges: &[],
+            })?;
+
+        // Lighting pass descriptors, one input attachment per G-buffer target
+        let input_attachment = |index| DescriptorBindingInfo {
+            index,
+            type_: DescriptorType::InputAttachment,
+            count: 1,
+            stage_flags: ShaderStageFlags::FRAGMENT,
+            flags: DescriptorBindingFlags::empty(),
+        };
+        let lighting_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new()
+                .add_binding_info(input_attachment(0))
+                .add_binding_info(input_attachment(1)),
+        )?;
+        let lighting_pipeline_layout =
+            sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+                descriptor_set_layouts: &[lighting_set_layout],
+                push_constant_ranges: &[],
+            })?;
+        let (lighting_pool, lighting_set) =
+            Self::create_lighting_set(&mut sys, lighting_set_layout, &gbuffer)?;
+
+        let vertex_input_bindings = [VertexInputBinding {
+            stride: size_of::<Vertex>(),
+            input_rate: VertexInputRate::Vertex,
+            attributes: &[
+                VertexInputAttribute { offset: 0, format: Format::R32G32B32_SFLOAT },
+                VertexInputAttribute {
+                    offset: size_of::<Vec3>(),
+                    format: Format::R32G32B32_SFLOAT,
+                },
+                VertexInputAttribute {
+                    offset: size_of::<Vec3>() * 2,
+                    format: Format::R32G32B32_SFLOAT,
+                },
+            ],
+        }];
+
+        let gbuffer_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&vertex_input_bindings),
+            shader_stages: &[shaders[0], shaders[1]],
+            render_pass_output: output,
+            subpass: 0,
+            blend_states: &[],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout: gbuffer_pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+        // Fullscreen triangle generated in the vertex shader, no depth attachment in subpass 1
+        let lighting_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[shaders[2], shaders[3]],
+            render_pass_output: output,
+            subpass: 1,
+            blend_states: &[],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            pipeline_layout: lighting_pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        let mut descriptor_sets = Vec::new();
+        for ub in uniform_buffers.iter() {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+            descriptor_sets.push(
+                sys.backend.create_descriptor_set(
+                    &DescriptorSetCreateDesc::new(descriptor_pool, gbuffer_set_layout)
+                        .bind_uniform_buffer(0, *ub),
+                )?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            shaders,
+            render_pass,
+            gbuffer,
+            gbuffer_set_layout,
+            gbuffer_pipeline_layout,
+            gbuffer_pipeline,
+            lighting_set_layout,
+            lighting_pipeline_layout,
+            lighting_pipeline,
+            lighting_pool,
+            lighting_set,
+            descriptor_pool,
+            command_buffers,
+            uniform_buffers,
+            descriptor_sets,
+            vertex_buffer,
+            index_buffer,
+            indices,
+        })
+    }
+
+    /// The lighting set points at the G-buffer views, so it is rebuilt with the G-buffer.
+    fn create_lighting_set(
+        sys: &mut RenderSystem,
+        layout: Handle<DescriptorSetLayout>,
+        gbuffer: &GBuffer,
+    ) -> anyhow::Result<(Handle<DescriptorPool>, Handle<DescriptorSet>)> {
+        let pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: 1,
+            pool_sizes: &[DescriptorPoolSize {
+                descriptor_type: DescriptorType::InputAttachment,
+                descriptor_count: 2,
+            }],
+            update_after_bind: false,
+        })?;
+        let set = sys.backend.create_descriptor_set(
+            &DescriptorSetCreateDesc::new(pool, layout)
+                .bind_input_attachment(0, gbuffer.albedo_view)
+                .bind_input_attachment(1, gbuffer.normal_view),
+        )?;
+        Ok((pool, set))
+    }
+
+    fn recreate_gbuffer(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        self.sys.backend.device_wait_idle()?;
+        self.gbuffer.destroy(&mut self.sys)?;
+        self.sys.backend.destroy_descriptor_pool(self.lighting_pool)?;
+
+        self.gbuffer = GBuffer::create(&mut self.sys, self.render_pass, width, height)?;
+        (self.lighting_pool, self.lighting_set) =
+            Self::create_lighting_set(&mut self.sys, self.lighting_set_layout, &self.gbuffer)?;
+        Ok(())
+    }
+
+    fn update_uniform_buffer(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f32();
+
+        let mut proj =
+            Mat4::perspective_rh(45.0_f32.to_radians(), width as f32 / height as f32, 0.1, 10.0);
+        proj.col_mut(1)[1] *= -1.0;
+        let view = Mat4::look_at_rh(vec3(2.0, 2.0, 2.0), Vec3::ZERO, Vec3::Z);
+
+        let ubo = UniformBufferObject {
+            model: Mat4::from_rotation_z(time * 0.8) * Mat4::from_rotation_x(time * 0.5),
+            view_proj: proj * view,
+        };
+        let ub = self.uniform_buffers[self.sys.frame];
+        self.sys
+            .backend
+            .get_buffer_mapped_slice_mut(ub)?
+            .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            // The framebuffers reference the back buffers of the swapchain they were built with
+            if self.gbuffer.swapchain != self.sys.swapchain {
+                self.recreate_gbuffer(width, height)?;
+            }
+            self.update_uniform_buffer(width, height)?;
+
+            let cb = self.command_buffers[self.sys.frame];
+            let backend = &self.sys.backend;
+            backend.reset_command_buffer(cb, false)?;
+            backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            let background = ClearColor::new([0.1, 0.1, 0.15, 1.0]);
+            let cleared = ClearColor::new([0.0, 0.0, 0.0, 0.0]);
+            backend.cmd_begin_render_pass(
+                cb,
+                self.render_pass,
+                self.gbuffer.framebuffers[self.sys.image_index],
+                Some(&[background, background, cleared]),
+                Some(ClearDepthStencil { depth: 1.0, stencil: 0 }),
+                SubpassContents::Inline,
+            )?;
+            backend.cmd_set_viewport(cb, 0.0, 0.0, width as f32, height as f32, 0.0, 1.0)?;
+            backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+
+            // Subpass 0: G-buffer
+            backend.cmd_bind_raster_pipeline(cb, self.gbuffer_pipeline)?;
+            backend.cmd_bind_vertex_buffers(cb, 0, &[self.vertex_buffer], &[0])?;
+            backend.cmd_bind_index_buffer(cb, self.index_buffer, 0, IndexType::U16)?;
+            backend.cmd_bind_descriptor_sets(
+                cb,
+                PipelineBindPoint::Graphics,
+                self.gbuffer_pipeline_layout,
+                0,
+                &[self.descriptor_sets[self.sys.frame]],
+                &[],
+            )?;
+            backend.cmd_draw_indexed(cb, self.indices.len() as u32, 1, 0, 0, 0)?;
+
+            // Subpass 1: lighting
+            backend.cmd_next_subpass(cb, SubpassContents::Inline)?;
+            backend.cmd_bind_raster_pipeline(cb, self.lighting_pipeline)?;
+            backend.cmd_bind_descriptor_sets(
+                cb,
+                PipelineBindPoint::Graphics,
+                self.lighting_pipeline_layout,
+                0,
+                &[self.lighting_set],
+                &[],
+            )?;
+            backend.cmd_draw(cb, 3, 1, 0, 0)?;
+
+            backend.cmd_end_render_pass(cb)?;
+            backend.cmd_end(cb)?;
+
+            backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_buffer(self.vertex_buffer).unwrap();
+        self.sys.backend.destroy_buffer(self.index_buffer).unwrap();
+        for ub in self.uniform_buffers.iter() {
+            self.sys.backend.destroy_buffer(*ub).unwrap();
+        }
+
+        self.gbuffer.destroy(&mut self.sys).unwrap();
+        self.sys.backend.destroy_render_pass(self.render_pass).unwrap();
+
+        for shader in self.shaders.iter() {
+            self.sys.backend.destroy_shader_module(*shader).unwrap();
+        }
+        self.sys.backend.destroy_descriptor_pool(self.lighting_pool).unwrap();
+        self.sys.backend.destroy_descriptor_pool(self.descriptor_pool).unwrap();
+        self.sys.backend.destroy_descriptor_set_layout(self.lighting_set_layout).unwrap();
+        self.sys.backend.destroy_descriptor_set_layout(self.gbuffer_set_layout).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.lighting_pipeline_layout).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.gbuffer_pipeline_layout).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.lighting_pipeline).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.gbuffer_pipeline).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
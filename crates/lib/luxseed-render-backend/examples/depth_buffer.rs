@@ -1,8 +1,9 @@
+#[path = "common/render_system.rs"]
 mod render_system;
 
 use glam::{vec2, vec3, Mat4, Vec2, Vec3};
 use image::{io::Reader as ImageReader, EncodableLayout};
-use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_render_backend::{define::*, enums::*, flag::*, upload_context::UploadContext};
 use luxseed_utility::pool::Handle;
 use render_system::*;
 use std::{fs, mem::size_of};
@@ -131,10 +132,11 @@ impl App {
             img.width(),
             img.height(),
         ))?;
-        upload_image_by_staging_buffer(
-            &mut sys.backend,
-            sys.command_pool,
-            sys.graphics_queue,
+        // Every upload is recorded into one command buffer and waited for once
+        let mut upload =
+            UploadContext::begin(sys.backend.as_mut(), sys.command_pool, sys.graphics_queue)?;
+        upload.upload_image(
+            sys.backend.as_mut(),
             image,
             img.to_rgba8().as_bytes(),
             img.width(),
@@ -155,6 +157,10 @@ impl App {
             mip_lod_bias: 0.0,
             compare_op: None,
             max_anisotropy: None,
+            border_color: BorderColor::FloatTransparentBlack,
+            min_lod: 0.0,
+            max_lod: LOD_CLAMP_NONE,
+            unnormalized_coordinates: false,
         })?;
 
         // Vertex buffer
@@ -175,10 +181,8 @@ impl App {
             memory: MemoryLocation::GpuOnly,
             initial_data: None,
         })?;
-        upload_buffer_by_staging_buffer(
-            &mut sys.backend,
-            sys.command_pool,
-            sys.graphics_queue,
+        upload.upload_buffer(
+            sys.backend.as_mut(),
             vertex_buffer,
             as_byte_slice_unchecked(&vertices),
         )?;
@@ -192,13 +196,12 @@ impl App {
             memory: MemoryLocation::GpuOnly,
             initial_data: None,
         })?;
-        upload_buffer_by_staging_buffer(
-            &mut sys.backend,
-            sys.command_pool,
-            sys.graphics_queue,
+        upload.upload_buffer(
+            sys.backend.as_mut(),
             index_buffer,
             as_byte_slice_unchecked(&indices),
         )?;
+        upload.finish(sys.backend.as_mut())?;
 
         // UBOs
         let mut uniform_buffers = Vec::new();
@@ -213,22 +216,9 @@ impl App {
             uniform_buffers.push(ub);
         }
 
-        // Descriptor set layout
-        let descriptor_set_layout = sys.backend.create_descriptor_set_layout(
-            &DescriptorSetLayoutCreateDesc::new()
-                .add_binding_info(DescriptorBindingInfo {
-                    index: 0,
-                    type_: DescriptorType::UniformBuffer,
-                    count: 1,
-                    stage_flags: ShaderStageFlags::VERTEX,
-                })
-                .add_binding_info(DescriptorBindingInfo {
-                    index: 1,
-                    type_: DescriptorType::CombinedImageSampler,
-                    count: 1,
-                    stage_flags: ShaderStageFlags::FRAGMENT,
-                }),
-        )?;
+        // Descriptor set layout, reflected from the shaders
+        let layouts = sys.backend.reflect_descriptor_layouts(&[vs, fs])?;
+        let descriptor_set_layout = sys.backend.create_descriptor_set_layout(&layouts[0])?;
 
         // Descriptor pool
         let descriptor_pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
@@ -243,11 +233,13 @@ impl App {
                     descriptor_count: sys.max_frames_in_flight as u32,
                 },
             ],
+            update_after_bind: false,
         })?;
 
         // Pipeline layout
         let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
             descriptor_set_layouts: &[descriptor_set_layout],
+            push_constant_ranges: &[],
         })?;
 
         // Pipeline
@@ -271,13 +263,18 @@ impl App {
                 }]),
                 shader_stages: &[vs, fs],
                 render_pass_output: sys.swapchain_output,
+                subpass: 0,
                 blend_states: &[BlendState::default()],
+                logic_op: None,
                 raster_state: RasterState {
                     front_face: FrontFace::CounterClockwise,
                     ..Default::default()
                 },
                 depth_state: DepthState::default(),
                 pipeline_layout,
+                specialization: &[],
+                patch_control_points: 0,
+                dynamic_rendering: false,
             })
             .unwrap();
 
@@ -338,7 +335,7 @@ impl App {
             .backend
             .get_buffer_mapped_slice_mut(ub)?
             .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
-        Ok(())
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
     }
 
     pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
@@ -346,6 +343,8 @@ impl App {
         let height = window.inner_size().height;
 
         if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
             self.update_uniform_buffer(width, height)?;
 
             let cb = self.command_buffers[self.sys.frame];
@@ -355,7 +354,14 @@ impl App {
             let fb = self.sys.get_swapchain_framebuffer();
             let cv = ClearColor::new([0.0, 0.0, 0.0, 1.0]);
             let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
-            self.sys.backend.cmd_begin_render_pass(cb, rp, fb, Some(&[cv]), Some(cd))?;
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
             self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
             self.sys.backend.cmd_set_viewport(
                 cb,
@@ -384,9 +390,11 @@ impl App {
                 self.sys.graphics_queue,
                 &QueueSubmitDesc {
                     wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
                     wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
                     command_buffer: &[cb],
                     finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
                     fence: Some(self.sys.get_in_flight_fence()),
                 },
             )?;
@@ -0,0 +1,280 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use anyhow::bail;
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+
+const VERT: &str = r#"#version 450
+
+layout(push_constant) uniform Push {
+    float time;
+    float aspect;
+} push;
+
+layout(location = 0) out vec3 outColor;
+
+const vec2 POSITIONS[3] = vec2[](vec2(0.0, -0.6), vec2(0.6, 0.45), vec2(-0.6, 0.45));
+const vec3 COLORS[3] = vec3[](vec3(1.0, 0.2, 0.2), vec3(0.2, 1.0, 0.2), vec3(0.2, 0.2, 1.0));
+
+void main() {
+    // A spinning triangle, no vertex buffer needed
+    float c = cos(push.time);
+    float s = sin(push.time);
+    vec2 position = mat2(c, s, -s, c) * POSITIONS[gl_VertexIndex];
+    gl_Position = vec4(position.x / push.aspect, position.y, 0.5, 1.0);
+    outColor = COLORS[gl_VertexIndex];
+}
+"#;
+
+const FRAG: &str = r#"#version 450
+
+layout(location = 0) in vec3 inColor;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(inColor, 1.0);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Dynamic Rendering")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub vs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create_with_device_features(
+            window,
+            DeviceFeatures::DYNAMIC_RENDERING,
+            RequestedFeatures::empty(),
+        )?;
+        if !sys.backend.enabled_features()?.features.contains(DeviceFeatures::DYNAMIC_RENDERING) {
+            bail!("The adapter doesn't support dynamic rendering.");
+        }
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "dynamic_rendering.vert",
+            VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "dynamic_rendering.frag",
+            FRAG,
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[],
+            push_constant_ranges: &[PushConstantRange {
+                stage_flags: ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: 2 * std::mem::size_of::<f32>() as u32,
+            }],
+        })?;
+        // Only the formats of the swapchain output are used, no render pass is created for it
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[vs, fs],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: true,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            vs,
+            fs,
+            pipeline_layout,
+            pipeline,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            let push = [self.start.elapsed().as_secs_f32(), width as f32 / height as f32];
+
+            // Views are cached by the image, so this is the same view every frame
+            let back_buffer = self
+                .sys
+                .backend
+                .get_swapchain_back_buffer(self.sys.swapchain, self.sys.image_index)?;
+            let back_buffer_view =
+                self.sys.backend.create_image_view(back_buffer, &ImageViewCreateDesc::default())?;
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            // Without a render pass the layout transitions are up to us
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    back_buffer,
+                    ImageAspectFlags::COLOR,
+                    ImageLayout::Undefined,
+                    ImageLayout::ColorAttachmentOptimal,
+                    AccessFlags::empty(),
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )],
+            )?;
+            self.sys.backend.cmd_begin_rendering(
+                cb,
+                &RenderingInfo {
+                    color_attachments: &[ColorAttachment {
+                        view: back_buffer_view,
+                        clear_value: Some(ClearColor::new([0.02, 0.02, 0.05, 1.0])),
+                        load_op: RenderTargetLoadAction::Clear,
+                        store_op: RenderTargetStoreAction::Store,
+                    }],
+                    depth_stencil_attachment: Some(DepthStencilAttachment {
+                        view: self.sys.depth_image_view,
+                        depth_clear_value: Some(1.0),
+                        depth_load_op: RenderTargetLoadAction::Clear,
+                        depth_store_op: RenderTargetStoreAction::DontCare,
+                        stencil_clear_value: None,
+                        stencil_load_op: RenderTargetLoadAction::DontCare,
+                        stencil_store_op: RenderTargetStoreAction::DontCare,
+                    }),
+                    extent: None,
+                },
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.sys.backend.cmd_apply_viewport(cb, &Viewport::new(width as f32, height as f32))?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.sys.backend.cmd_push_constants(
+                cb,
+                self.pipeline_layout,
+                ShaderStageFlags::VERTEX,
+                0,
+                as_byte_slice_unchecked(&push),
+            )?;
+            self.sys.backend.cmd_draw(cb, 3, 1, 0, 0)?;
+            self.sys.backend.cmd_end_rendering(cb)?;
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::BOTTOM_OF_PIPE,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    back_buffer,
+                    ImageAspectFlags::COLOR,
+                    ImageLayout::ColorAttachmentOptimal,
+                    ImageLayout::PresentSrcKhr,
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    AccessFlags::empty(),
+                )],
+            )?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -0,0 +1,435 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec3, Mat4, Quat, Vec3, Vec4};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::{fs, mem::size_of};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+/// Cubes per side of the grid, 20 * 20 * 10 instances in total.
+const GRID: (i32, i32, i32) = (20, 20, 10);
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Instancing")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: Vec3,
+    pub color: Vec3,
+}
+
+impl Vertex {
+    const fn new(pos: Vec3, color: Vec3) -> Self {
+        Self { pos, color }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UniformBufferObject {
+    pub view: Mat4,
+    pub proj: Mat4,
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub vs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub descriptor_set_layout: Handle<DescriptorSetLayout>,
+    pub descriptor_pool: Handle<DescriptorPool>,
+
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+    pub uniform_buffers: Vec<Handle<Buffer>>,
+    pub descriptor_sets: Vec<Handle<DescriptorSet>>,
+
+    pub vertex_buffer: Handle<Buffer>,
+    pub index_buffer: Handle<Buffer>,
+    pub indices: Vec<u16>,
+    pub instance_buffer: Handle<Buffer>,
+    pub instance_count: u32,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "instancing",
+            &fs::read_to_string("assets/luxseed-render-backend-test/instancing.vert")
+                .expect("Should have been able to read the file"),
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "instancing",
+            &fs::read_to_string("assets/luxseed-render-backend-test/triangle.frag")
+                .expect("Should have been able to read the file"),
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        // Cube mesh, binding 0
+        let vertices = [
+            Vertex::new(vec3(-0.5, -0.5, -0.5), vec3(0.0, 0.0, 0.0)),
+            Vertex::new(vec3(0.5, -0.5, -0.5), vec3(1.0, 0.0, 0.0)),
+            Vertex::new(vec3(0.5, 0.5, -0.5), vec3(1.0, 1.0, 0.0)),
+            Vertex::new(vec3(-0.5, 0.5, -0.5), vec3(0.0, 1.0, 0.0)),
+            Vertex::new(vec3(-0.5, -0.5, 0.5), vec3(0.0, 0.0, 1.0)),
+            Vertex::new(vec3(0.5, -0.5, 0.5), vec3(1.0, 0.0, 1.0)),
+            Vertex::new(vec3(0.5, 0.5, 0.5), vec3(1.0, 1.0, 1.0)),
+            Vertex::new(vec3(-0.5, 0.5, 0.5), vec3(0.0, 1.0, 1.0)),
+        ];
+        let vertex_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Cube_Vertex",
+            size: (vertices.len() * size_of::<Vertex>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            vertex_buffer,
+            as_byte_slice_unchecked(&vertices),
+        )?;
+
+        let indices: Vec<u16> = vec![
+            0, 1, 2, 2, 3, 0, // back
+            4, 6, 5, 6, 4, 7, // front
+            0, 3, 7, 7, 4, 0, // left
+            1, 5, 6, 6, 2, 1, // right
+            0, 4, 5, 5, 1, 0, // bottom
+            3, 2, 6, 6, 7, 3, // top
+        ];
+        let index_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Cube_Index",
+            size: (indices.len() * size_of::<u16>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            index_buffer,
+            as_byte_slice_unchecked(&indices),
+        )?;
+
+        // One transform per cube, binding 1
+        let mut transforms = Vec::new();
+        for x in 0..GRID.0 {
+            for y in 0..GRID.1 {
+                for z in 0..GRID.2 {
+                    let position = vec3(
+                        (x - GRID.0 / 2) as f32 * 1.5,
+                        (y - GRID.1 / 2) as f32 * 1.5,
+                        (z - GRID.2 / 2) as f32 * 1.5,
+                    );
+                    let rotation = Quat::from_euler(
+                        glam::EulerRot::XYZ,
+                        x as f32 * 0.3,
+                        y as f32 * 0.2,
+                        z as f32 * 0.1,
+                    );
+                    transforms.push(Mat4::from_rotation_translation(rotation, position));
+                }
+            }
+        }
+        let instance_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Cube_Instance",
+            size: (transforms.len() * size_of::<Mat4>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            instance_buffer,
+            as_byte_slice_unchecked(&transforms),
+        )?;
+
+        // UBOs
+        let mut uniform_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            uniform_buffers.push(sys.backend.create_buffer(&BufferCreateDesc {
+                name: "Instancing_UBO",
+                size: size_of::<UniformBufferObject>() as u64,
+                usage: BufferUsageFlags::UNIFORM_BUFFER,
+                memory: MemoryLocation::CpuToGpu,
+                initial_data: None,
+            })?);
+        }
+
+        let descriptor_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+                index: 0,
+                type_: DescriptorType::UniformBuffer,
+                count: 1,
+                stage_flags: ShaderStageFlags::VERTEX,
+                flags: DescriptorBindingFlags::empty(),
+            }),
+        )?;
+        let descriptor_pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: sys.max_frames_in_flight as u32,
+            pool_sizes: &[DescriptorPoolSize {
+                descriptor_type: DescriptorType::UniformBuffer,
+                descriptor_count: sys.max_frames_in_flight as u32,
+            }],
+            update_after_bind: false,
+        })?;
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[descriptor_set_layout],
+            push_constant_ranges: &[],
+        })?;
+
+        // A mat4 attribute is passed as its four columns
+        let column = |i: usize| VertexInputAttribute {
+            offset: i * size_of::<Vec4>(),
+            format: Format::R32G32B32A32_SFLOAT,
+        };
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&[
+                VertexInputBinding {
+                    stride: size_of::<Vertex>(),
+                    input_rate: VertexInputRate::Vertex,
+                    attributes: &[
+                        VertexInputAttribute { offset: 0, format: Format::R32G32B32_SFLOAT },
+                        VertexInputAttribute {
+                            offset: size_of::<Vec3>(),
+                            format: Format::R32G32B32_SFLOAT,
+                        },
+                    ],
+                },
+                VertexInputBinding {
+                    stride: size_of::<Mat4>(),
+                    input_rate: VertexInputRate::Instance,
+                    attributes: &[column(0), column(1), column(2), column(3)],
+                },
+            ]),
+            shader_stages: &[vs, fs],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        let mut descriptor_sets = Vec::new();
+        for ub in uniform_buffers.iter() {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+            descriptor_sets.push(
+                sys.backend.create_descriptor_set(
+                    &DescriptorSetCreateDesc::new(descriptor_pool, descriptor_set_layout)
+                        .bind_uniform_buffer(0, *ub),
+                )?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            vs,
+            fs,
+            pipeline_layout,
+            pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            command_buffers,
+            uniform_buffers,
+            descriptor_sets,
+            vertex_buffer,
+            index_buffer,
+            indices,
+            instance_buffer,
+            instance_count: transforms.len() as u32,
+        })
+    }
+
+    fn update_uniform_buffer(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f32();
+
+        // Orbit around the grid
+        let angle = time * 20.0_f32.to_radians();
+        let eye = vec3(angle.cos() * 45.0, angle.sin() * 45.0, 25.0);
+        let mut ubo = UniformBufferObject {
+            view: Mat4::look_at_rh(eye, Vec3::ZERO, vec3(0.0, 0.0, 1.0)),
+            proj: Mat4::perspective_rh(
+                45.0_f32.to_radians(),
+                width as f32 / height as f32,
+                0.1,
+                200.0,
+            ),
+        };
+        ubo.proj.col_mut(1)[1] *= -1.0;
+
+        let ub = self.uniform_buffers[self.sys.frame];
+        self.sys
+            .backend
+            .get_buffer_mapped_slice_mut(ub)?
+            .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            self.update_uniform_buffer(width, height)?;
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.05, 0.05, 0.08, 1.0]);
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.sys.backend.cmd_set_viewport(
+                cb,
+                0.0,
+                0.0,
+                width as f32,
+                height as f32,
+                0.0,
+                1.0,
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.sys.backend.cmd_bind_vertex_buffers(
+                cb,
+                0,
+                &[self.vertex_buffer, self.instance_buffer],
+                &[0, 0],
+            )?;
+            self.sys.backend.cmd_bind_index_buffer(cb, self.index_buffer, 0, IndexType::U16)?;
+            self.sys.backend.cmd_bind_descriptor_sets(
+                cb,
+                PipelineBindPoint::Graphics,
+                self.pipeline_layout,
+                0,
+                &[self.descriptor_sets[self.sys.frame]],
+                &[],
+            )?;
+            // Every cube in one draw
+            self.sys.backend.cmd_draw_indexed(
+                cb,
+                self.indices.len() as u32,
+                self.instance_count,
+                0,
+                0,
+                0,
+            )?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_buffer(self.vertex_buffer).unwrap();
+        self.sys.backend.destroy_buffer(self.index_buffer).unwrap();
+        self.sys.backend.destroy_buffer(self.instance_buffer).unwrap();
+        for ub in self.uniform_buffers.iter() {
+            self.sys.backend.destroy_buffer(*ub).unwrap();
+        }
+
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+        self.sys.backend.destroy_descriptor_set_layout(self.descriptor_set_layout).unwrap();
+
+        self.sys.backend.destroy_descriptor_pool(self.descriptor_pool).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -0,0 +1,598 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec2, vec3, Mat4, Vec2};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::{fs, mem::size_of};
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const OFFSCREEN_SIZE: u32 = 1024;
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Offscreen")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            // P saves the offscreen target to a PNG
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::P),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } if !destroying => app.save_screenshot("offscreen.png").unwrap(),
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct QuadVertex {
+    pub pos: Vec2,
+    pub tex_coord: Vec2,
+}
+
+impl QuadVertex {
+    const fn new(pos: Vec2, tex_coord: Vec2) -> Self {
+        Self { pos, tex_coord }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UniformBufferObject {
+    pub model: Mat4,
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub shaders: Vec<Handle<Shader>>,
+
+    // Offscreen pass, a spinning triangle rendered into a sampled color target
+    pub offscreen: Handle<Image>,
+    pub offscreen_view: Handle<ImageView>,
+    pub offscreen_render_pass: Handle<RenderPass>,
+    pub offscreen_framebuffer: Handle<Framebuffer>,
+    pub triangle_set_layout: Handle<DescriptorSetLayout>,
+    pub triangle_pipeline_layout: Handle<PipelineLayout>,
+    pub triangle_pipeline: Handle<RasterPipeline>,
+    pub uniform_buffers: Vec<Handle<Buffer>>,
+    pub triangle_sets: Vec<Handle<DescriptorSet>>,
+
+    // Swapchain pass, a quad textured with the offscreen target
+    pub sampler: Handle<Sampler>,
+    pub quad_set_layout: Handle<DescriptorSetLayout>,
+    pub quad_pipeline_layout: Handle<PipelineLayout>,
+    pub quad_pipeline: Handle<RasterPipeline>,
+    pub quad_set: Handle<DescriptorSet>,
+    pub vertex_buffer: Handle<Buffer>,
+    pub index_buffer: Handle<Buffer>,
+    pub indices: Vec<u16>,
+
+    pub descriptor_pool: Handle<DescriptorPool>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let mut shaders = Vec::new();
+        for (file, stage) in [
+            ("offscreen_triangle.vert", ShaderStageFlags::VERTEX),
+            ("triangle.frag", ShaderStageFlags::FRAGMENT),
+            ("offscreen_quad.vert", ShaderStageFlags::VERTEX),
+            ("offscreen_quad.frag", ShaderStageFlags::FRAGMENT),
+        ] {
+            shaders.push(compile_shader_glsl(
+                &mut sys.backend,
+                file,
+                &fs::read_to_string(format!("assets/luxseed-render-backend-test/{}", file))
+                    .expect("Should have been able to read the file"),
+                stage,
+                "main",
+            )?);
+        }
+
+        // Color target that is rendered to, then sampled, and copied from for screenshots
+        let offscreen = sys.backend.create_image(&ImageCreateDesc {
+            usage: ImageUsageFlags::COLOR_ATTACHMENT
+                | ImageUsageFlags::SAMPLED
+                | ImageUsageFlags::TRANSFER_SRC,
+            ..ImageCreateDesc::new_2d("offscreen", OFFSCREEN_FORMAT, OFFSCREEN_SIZE, OFFSCREEN_SIZE)
+        })?;
+        let offscreen_view = sys.backend.create_image_view(
+            offscreen,
+            &ImageViewCreateDesc::new_2d(None, ImageAspectFlags::COLOR),
+        )?;
+
+        // The render pass leaves the target as a color attachment, the transition to a shader
+        // readable layout is an explicit barrier
+        let offscreen_output = RenderPassOutput::builder()
+            .add_color(
+                OFFSCREEN_FORMAT,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let offscreen_render_pass = sys.backend.create_render_pass(&offscreen_output)?;
+        let offscreen_framebuffer = sys.backend.create_framebuffer(&FramebufferCreateDesc {
+            render_pass: offscreen_render_pass,
+            color_views: &[offscreen_view],
+            depth_stencil_view: None,
+            resolve_views: &[],
+            extent: None,
+        })?;
+
+        // Triangle
+        let mut uniform_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            uniform_buffers.push(sys.backend.create_buffer(&BufferCreateDesc {
+                name: "Offscreen_UBO",
+                size: size_of::<UniformBufferObject>() as u64,
+                usage: BufferUsageFlags::UNIFORM_BUFFER,
+                memory: MemoryLocation::CpuToGpu,
+                initial_data: None,
+            })?);
+        }
+        let triangle_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+                index: 0,
+                type_: DescriptorType::UniformBuffer,
+                count: 1,
+                stage_flags: ShaderStageFlags::VERTEX,
+                flags: DescriptorBindingFlags::empty(),
+            }),
+        )?;
+        let triangle_pipeline_layout =
+            sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+                descriptor_set_layouts: &[triangle_set_layout],
+                push_constant_ranges: &[],
+            })?;
+        let triangle_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[shaders[0], shaders[1]],
+            render_pass_output: offscreen_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            pipeline_layout: triangle_pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        // Quad
+        let vertices = [
+            QuadVertex::new(vec2(-0.8, -0.8), vec2(0.0, 0.0)),
+            QuadVertex::new(vec2(0.8, -0.8), vec2(1.0, 0.0)),
+            QuadVertex::new(vec2(0.8, 0.8), vec2(1.0, 1.0)),
+            QuadVertex::new(vec2(-0.8, 0.8), vec2(0.0, 1.0)),
+        ];
+        let vertex_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Quad_Vertex",
+            size: (vertices.len() * size_of::<QuadVertex>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            vertex_buffer,
+            as_byte_slice_unchecked(&vertices),
+        )?;
+        let indices: Vec<u16> = vec![0, 1, 2, 2, 3, 0];
+        let index_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Quad_Index",
+            size: (indices.len() * size_of::<u16>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            index_buffer,
+            as_byte_slice_unchecked(&indices),
+        )?;
+
+        let sampler = sys.backend.create_sampler(&SamplerCreateDesc {
+            mag_filter: FilterType::Linear,
+            min_filter: FilterType::Linear,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            mip_lod_bias: 0.0,
+            compare_op: None,
+            max_anisotropy: None,
+            border_color: BorderColor::FloatTransparentBlack,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            unnormalized_coordinates: false,
+        })?;
+        let quad_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+                index: 0,
+                type_: DescriptorType::CombinedImageSampler,
+                count: 1,
+                stage_flags: ShaderStageFlags::FRAGMENT,
+                flags: DescriptorBindingFlags::empty(),
+            }),
+        )?;
+        let quad_pipeline_layout =
+            sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+                descriptor_set_layouts: &[quad_set_layout],
+                push_constant_ranges: &[],
+            })?;
+        let quad_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&[VertexInputBinding {
+                stride: size_of::<QuadVertex>(),
+                input_rate: VertexInputRate::Vertex,
+                attributes: &[
+                    VertexInputAttribute { offset: 0, format: Format::R32G32_SFLOAT },
+                    VertexInputAttribute {
+                        offset: size_of::<Vec2>(),
+                        format: Format::R32G32_SFLOAT,
+                    },
+                ],
+            }]),
+            shader_stages: &[shaders[2], shaders[3]],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout: quad_pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        // Descriptor sets
+        let descriptor_pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: sys.max_frames_in_flight as u32 + 1,
+            pool_sizes: &[
+                DescriptorPoolSize {
+                    descriptor_type: DescriptorType::UniformBuffer,
+                    descriptor_count: sys.max_frames_in_flight as u32,
+                },
+                DescriptorPoolSize {
+                    descriptor_type: DescriptorType::CombinedImageSampler,
+                    descriptor_count: 1,
+                },
+            ],
+            update_after_bind: false,
+        })?;
+        let mut triangle_sets = Vec::new();
+        for ub in uniform_buffers.iter() {
+            triangle_sets.push(
+                sys.backend.create_descriptor_set(
+                    &DescriptorSetCreateDesc::new(descriptor_pool, triangle_set_layout)
+                        .bind_uniform_buffer(0, *ub),
+                )?,
+            );
+        }
+        let quad_set = sys.backend.create_descriptor_set(
+            &DescriptorSetCreateDesc::new(descriptor_pool, quad_set_layout)
+                .bind_combined_image_sampler(0, offscreen_view, sampler),
+        )?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            shaders,
+            offscreen,
+            offscreen_view,
+            offscreen_render_pass,
+            offscreen_framebuffer,
+            triangle_set_layout,
+            triangle_pipeline_layout,
+            triangle_pipeline,
+            uniform_buffers,
+            triangle_sets,
+            sampler,
+            quad_set_layout,
+            quad_pipeline_layout,
+            quad_pipeline,
+            quad_set,
+            vertex_buffer,
+            index_buffer,
+            indices,
+            descriptor_pool,
+            command_buffers,
+        })
+    }
+
+    fn update_uniform_buffer(&mut self) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f32();
+        let ubo = UniformBufferObject {
+            model: Mat4::from_axis_angle(vec3(0.0, 0.0, 1.0), time * 90.0_f32.to_radians()),
+        };
+        let ub = self.uniform_buffers[self.sys.frame];
+        self.sys
+            .backend
+            .get_buffer_mapped_slice_mut(ub)?
+            .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            self.update_uniform_buffer()?;
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            // Spinning triangle into the offscreen target
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                self.offscreen_render_pass,
+                self.offscreen_framebuffer,
+                Some(&[ClearColor::new([0.1, 0.1, 0.15, 1.0])]),
+                None,
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_set_viewport(
+                cb,
+                0.0,
+                0.0,
+                OFFSCREEN_SIZE as f32,
+                OFFSCREEN_SIZE as f32,
+                0.0,
+                1.0,
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, OFFSCREEN_SIZE, OFFSCREEN_SIZE)?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.triangle_pipeline)?;
+            self.sys.backend.cmd_bind_descriptor_sets(
+                cb,
+                PipelineBindPoint::Graphics,
+                self.triangle_pipeline_layout,
+                0,
+                &[self.triangle_sets[self.sys.frame]],
+                &[],
+            )?;
+            self.sys.backend.cmd_draw(cb, 3, 1, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+
+            // Color attachment to shader read, waiting for the writes before sampling
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    self.offscreen,
+                    ImageAspectFlags::COLOR,
+                    ImageLayout::ColorAttachmentOptimal,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    AccessFlags::SHADER_READ,
+                )],
+            )?;
+
+            // Textured quad into the back buffer
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.0, 0.0, 0.0, 1.0]);
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_set_viewport(
+                cb,
+                0.0,
+                0.0,
+                width as f32,
+                height as f32,
+                0.0,
+                1.0,
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.quad_pipeline)?;
+            self.sys.backend.cmd_bind_vertex_buffers(cb, 0, &[self.vertex_buffer], &[0])?;
+            self.sys.backend.cmd_bind_index_buffer(cb, self.index_buffer, 0, IndexType::U16)?;
+            self.sys.backend.cmd_bind_descriptor_sets(
+                cb,
+                PipelineBindPoint::Graphics,
+                self.quad_pipeline_layout,
+                0,
+                &[self.quad_set],
+                &[],
+            )?;
+            self.sys.backend.cmd_draw_indexed(cb, self.indices.len() as u32, 1, 0, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the offscreen target back through a `GpuToCpu` buffer and writes it to `path`.
+    pub fn save_screenshot(&mut self, path: &str) -> anyhow::Result<()> {
+        // The last frames still write the target
+        self.sys.backend.device_wait_idle()?;
+
+        let readback = self.sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Screenshot_Readback",
+            size: (OFFSCREEN_SIZE * OFFSCREEN_SIZE * 4) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST,
+            memory: MemoryLocation::GpuToCpu,
+            initial_data: None,
+        })?;
+        let cb = begin_single_time_commands(&mut self.sys.backend, self.sys.command_pool)?;
+        let transition = |old_layout, new_layout, src_access_mask, dst_access_mask| {
+            ImageMemoryBarrier::whole_image(
+                self.offscreen,
+                ImageAspectFlags::COLOR,
+                old_layout,
+                new_layout,
+                src_access_mask,
+                dst_access_mask,
+            )
+        };
+        self.sys.backend.cmd_pipeline_barrier(
+            cb,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            PipelineStageFlags::TRANSFER,
+            &[],
+            &[],
+            &[transition(
+                ImageLayout::ShaderReadOnlyOptimal,
+                ImageLayout::TransferSrcOptimal,
+                AccessFlags::SHADER_READ,
+                AccessFlags::TRANSFER_READ,
+            )],
+        )?;
+        self.sys.backend.cmd_copy_image_to_buffer(
+            cb,
+            self.offscreen,
+            ImageLayout::TransferSrcOptimal,
+            readback,
+            &[BufferImageCopyRegion::color(OFFSCREEN_SIZE, OFFSCREEN_SIZE)],
+        )?;
+        self.sys.backend.cmd_pipeline_barrier(
+            cb,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            &[],
+            &[],
+            &[transition(
+                ImageLayout::TransferSrcOptimal,
+                ImageLayout::ShaderReadOnlyOptimal,
+                AccessFlags::TRANSFER_READ,
+                AccessFlags::SHADER_READ,
+            )],
+        )?;
+        end_single_time_commands(&mut self.sys.backend, cb, self.sys.graphics_queue)?;
+
+        self.sys.backend.invalidate_buffer(readback, 0, WHOLE_SIZE)?;
+        let pixels = self.sys.backend.get_buffer_mapped_slice_mut(readback)?.to_vec();
+        self.sys.backend.destroy_buffer(readback)?;
+        image::save_buffer(
+            path,
+            &pixels[..(OFFSCREEN_SIZE * OFFSCREEN_SIZE * 4) as usize],
+            OFFSCREEN_SIZE,
+            OFFSCREEN_SIZE,
+            image::ColorType::Rgba8,
+        )?;
+        println!("Saved the offscreen target to {}.", path);
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_descriptor_pool(self.descriptor_pool).unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.quad_pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.quad_pipeline_layout).unwrap();
+        self.sys.backend.destroy_descriptor_set_layout(self.quad_set_layout).unwrap();
+        self.sys.backend.destroy_sampler(self.sampler).unwrap();
+        self.sys.backend.destroy_buffer(self.vertex_buffer).unwrap();
+        self.sys.backend.destroy_buffer(self.index_buffer).unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.triangle_pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.triangle_pipeline_layout).unwrap();
+        self.sys.backend.destroy_descriptor_set_layout(self.triangle_set_layout).unwrap();
+        for ub in self.uniform_buffers.iter() {
+            self.sys.backend.destroy_buffer(*ub).unwrap();
+        }
+
+        self.sys.backend.destroy_framebuffer(self.offscreen_framebuffer).unwrap();
+        self.sys.backend.destroy_render_pass(self.offscreen_render_pass).unwrap();
+        self.sys.backend.destroy_image(self.offscreen).unwrap();
+        for shader in self.shaders.iter() {
+            self.sys.backend.destroy_shader_module(*shader).unwrap();
+        }
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+/// Points drawn, each one becomes a sprite in the geometry shader.
+const POINT_COUNT: u32 = 512;
+
+const VERT: &str = r#"#version 450
+
+layout(push_constant) uniform Push {
+    float time;
+    float aspect;
+    float count;
+} push;
+
+layout(location = 0) out vec3 outColor;
+
+void main() {
+    // Points on a slowly turning spiral, no vertex buffer needed
+    float t = float(gl_VertexIndex) / push.count;
+    float angle = t * 40.0 + push.time * 0.5;
+    float radius = 0.05 + t * 0.85;
+    gl_Position = vec4(cos(angle) * radius / push.aspect, sin(angle) * radius, 0.0, 1.0);
+    outColor = mix(vec3(1.0, 0.6, 0.1), vec3(0.2, 0.5, 1.0), t);
+}
+"#;
+
+const GEOM: &str = r#"#version 450
+
+layout(points) in;
+layout(triangle_strip, max_vertices = 4) out;
+
+layout(push_constant) uniform Push {
+    float time;
+    float aspect;
+    float count;
+} push;
+
+layout(location = 0) in vec3 inColor[];
+layout(location = 0) out vec3 outColor;
+layout(location = 1) out vec2 outCorner;
+
+const float SIZE = 0.03;
+
+void main() {
+    vec2 corners[4] = vec2[](vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+    for (int i = 0; i < 4; i++) {
+        vec2 offset = corners[i] * SIZE * vec2(1.0 / push.aspect, 1.0);
+        gl_Position = gl_in[0].gl_Position + vec4(offset, 0.0, 0.0);
+        outColor = inColor[0];
+        outCorner = corners[i];
+        EmitVertex();
+    }
+    EndPrimitive();
+}
+"#;
+
+const FRAG: &str = r#"#version 450
+
+layout(location = 0) in vec3 inColor;
+layout(location = 1) in vec2 inCorner;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // Round sprites out of the quads
+    float distance = length(inCorner);
+    if (distance > 1.0) {
+        discard;
+    }
+    outColor = vec4(inColor * (1.2 - distance), 1.0);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Point Sprites")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub vs: Handle<Shader>,
+    pub gs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys =
+            RenderSystem::create_with_features(window, RequestedFeatures::GEOMETRY_SHADER)?;
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "point_sprites.vert",
+            VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let gs = compile_shader_glsl(
+            &mut sys.backend,
+            "point_sprites.geom",
+            GEOM,
+            ShaderStageFlags::GEOMETRY,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "point_sprites.frag",
+            FRAG,
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        // Time, aspect ratio and point count, read by the vertex and geometry stages
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[],
+            push_constant_ranges: &[PushConstantRange {
+                stage_flags: ShaderStageFlags::VERTEX | ShaderStageFlags::GEOMETRY,
+                offset: 0,
+                size: 3 * std::mem::size_of::<f32>() as u32,
+            }],
+        })?;
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[vs, gs, fs],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState {
+                cull_mode: CullMode::None,
+                topology: PrimitiveTopology::PointList,
+                ..Default::default()
+            },
+            depth_state: DepthState::disabled(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            vs,
+            gs,
+            fs,
+            pipeline_layout,
+            pipeline,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            let push = [
+                self.start.elapsed().as_secs_f32(),
+                width as f32 / height as f32,
+                POINT_COUNT as f32,
+            ];
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.02, 0.02, 0.05, 1.0]);
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.sys.backend.cmd_apply_viewport(cb, &Viewport::new(width as f32, height as f32))?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.sys.backend.cmd_push_constants(
+                cb,
+                self.pipeline_layout,
+                ShaderStageFlags::VERTEX | ShaderStageFlags::GEOMETRY,
+                0,
+                as_byte_slice_unchecked(&push),
+            )?;
+            self.sys.backend.cmd_draw(cb, POINT_COUNT, 1, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.gs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use luxseed_render_backend::{define::*, enums::*, flag::*, fullscreen::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::fs;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Post Process")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+pub struct App {
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub shaders: Vec<Handle<Shader>>,
+
+    pub offscreen: Handle<Image>,
+    pub offscreen_view: Handle<ImageView>,
+    pub offscreen_render_pass: Handle<RenderPass>,
+    pub offscreen_framebuffer: Handle<Framebuffer>,
+    pub scene_pipeline_layout: Handle<PipelineLayout>,
+    pub scene_pipeline: Handle<RasterPipeline>,
+
+    pub tonemap: FullscreenPass,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let mut shaders = Vec::new();
+        for (file, stage) in [
+            ("triangle.vert", ShaderStageFlags::VERTEX),
+            ("triangle.frag", ShaderStageFlags::FRAGMENT),
+            ("grayscale.frag", ShaderStageFlags::FRAGMENT),
+        ] {
+            shaders.push(compile_shader_glsl(
+                &mut sys.backend,
+                file,
+                &fs::read_to_string(format!("assets/luxseed-render-backend-test/{}", file))
+                    .expect("Should have been able to read the file"),
+                stage,
+                "main",
+            )?);
+        }
+        shaders.push(compile_shader_glsl(
+            &mut sys.backend,
+            "fullscreen.vert",
+            FULLSCREEN_TRIANGLE_VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?);
+
+        // Offscreen target, rendered to then sampled by the tonemap pass. It has a fixed size,
+        // the tonemap pass scales it to the window.
+        let offscreen = sys.backend.create_image(&ImageCreateDesc {
+            usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+            ..ImageCreateDesc::new_2d("offscreen", OFFSCREEN_FORMAT, WIDTH, HEIGHT)
+        })?;
+        let offscreen_view = sys.backend.create_image_view(
+            offscreen,
+            &ImageViewCreateDesc::new_2d(None, ImageAspectFlags::COLOR),
+        )?;
+        let offscreen_output = RenderPassOutput::builder()
+            .add_color(
+                OFFSCREEN_FORMAT,
+                ImageLayout::ShaderReadOnlyOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let offscreen_render_pass = sys.backend.create_render_pass(&offscreen_output)?;
+        let offscreen_framebuffer = sys.backend.create_framebuffer(&FramebufferCreateDesc {
+            render_pass: offscreen_render_pass,
+            color_views: &[offscreen_view],
+            depth_stencil_view: None,
+            resolve_views: &[],
+            extent: None,
+        })?;
+
+        let scene_pipeline_layout =
+            sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+                descriptor_set_layouts: &[],
+                push_constant_ranges: &[],
+            })?;
+        let scene_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[shaders[0], shaders[1]],
+            render_pass_output: offscreen_output,
+            subpass: 0,
+            blend_states: &[],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            pipeline_layout: scene_pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        // Grayscale tonemap drawn straight into the swapchain render pass
+        let tonemap = FullscreenPass::new(
+            sys.backend.as_mut(),
+            &FullscreenPassCreateDesc {
+                vertex_shader: shaders[3],
+                fragment_shader: shaders[2],
+                render_pass_output: sys.swapchain_output,
+                subpass: 0,
+                max_inputs: 1,
+            },
+        )?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            sys,
+            resize: false,
+            shaders,
+            offscreen,
+            offscreen_view,
+            offscreen_render_pass,
+            offscreen_framebuffer,
+            scene_pipeline_layout,
+            scene_pipeline,
+            tonemap,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            // Scene pass into the offscreen target
+            let cv = ClearColor::new([0.1, 0.1, 0.15, 1.0]);
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                self.offscreen_render_pass,
+                self.offscreen_framebuffer,
+                Some(&[cv]),
+                None,
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_set_viewport(
+                cb,
+                0.0,
+                0.0,
+                WIDTH as f32,
+                HEIGHT as f32,
+                0.0,
+                1.0,
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, WIDTH, HEIGHT)?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.scene_pipeline)?;
+            self.sys.backend.cmd_draw(cb, 3, 1, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+
+            // Make the color writes visible to the tonemap fragment shader
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    self.offscreen,
+                    ImageAspectFlags::COLOR,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    AccessFlags::SHADER_READ,
+                )],
+            )?;
+
+            // Tonemap pass into the back buffer
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_set_viewport(
+                cb,
+                0.0,
+                0.0,
+                width as f32,
+                height as f32,
+                0.0,
+                1.0,
+            )?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.tonemap.draw(self.sys.backend.as_mut(), cb, self.offscreen_view)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.tonemap.destroy(self.sys.backend.as_mut()).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.scene_pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.scene_pipeline_layout).unwrap();
+        self.sys.backend.destroy_framebuffer(self.offscreen_framebuffer).unwrap();
+        self.sys.backend.destroy_render_pass(self.offscreen_render_pass).unwrap();
+        self.sys.backend.destroy_image(self.offscreen).unwrap();
+        for shader in self.shaders.iter() {
+            self.sys.backend.destroy_shader_module(*shader).unwrap();
+        }
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec3, Mat4};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::mem::size_of;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+/// Triangles per row and column of the grid.
+const GRID: usize = 4;
+
+const VERT: &str = r#"#version 450
+
+layout(push_constant) uniform Push {
+    mat4 transform;
+} push;
+
+layout(location = 0) out vec3 fragColor;
+
+vec2 positions[3] = vec2[](vec2(0.0, -0.5), vec2(0.5, 0.5), vec2(-0.5, 0.5));
+vec3 colors[3] = vec3[](vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0));
+
+void main() {
+    gl_Position = push.transform * vec4(positions[gl_VertexIndex], 0.0, 1.0);
+    fragColor = colors[gl_VertexIndex];
+}
+"#;
+
+const FRAG: &str = r#"#version 450
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(fragColor, 1.0);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Push Constants")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Places triangle `i` in its grid cell, spinning at its own speed.
+fn transform(i: usize, time: f32, aspect: f32) -> Mat4 {
+    let cell = 2.0 / GRID as f32;
+    let x = -1.0 + cell * ((i % GRID) as f32 + 0.5);
+    let y = -1.0 + cell * ((i / GRID) as f32 + 0.5);
+    Mat4::from_translation(vec3(x, y, 0.0))
+        * Mat4::from_scale(vec3(cell / aspect, cell, 1.0))
+        * Mat4::from_rotation_z(time * (i + 1) as f32 * 0.5)
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub vs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "push_constants.vert",
+            VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "push_constants.frag",
+            FRAG,
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        // No descriptor sets, each draw gets its transform through a push constant
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[],
+            push_constant_ranges: &[PushConstantRange {
+                stage_flags: ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: size_of::<Mat4>() as u32,
+            }],
+        })?;
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            shader_stages: &[vs, fs],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            vs,
+            fs,
+            pipeline_layout,
+            pipeline,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            let time = self.start.elapsed().as_secs_f32();
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.05, 0.05, 0.05, 1.0]);
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.sys.backend.cmd_apply_viewport(cb, &Viewport::new(width as f32, height as f32))?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            for i in 0..GRID * GRID {
+                let transform = transform(i, time, width as f32 / height as f32);
+                self.sys.backend.cmd_push_constants(
+                    cb,
+                    self.pipeline_layout,
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    as_byte_slice_unchecked(&[transform]),
+                )?;
+                self.sys.backend.cmd_draw(cb, 3, 1, 0, 0)?;
+            }
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
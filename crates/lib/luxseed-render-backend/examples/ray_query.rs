@@ -0,0 +1,248 @@
+use luxseed_render_backend::{
+    create_render_backend, define::*, enums::*, flag::*, glsl::compile_glsl, RenderBackend,
+};
+use luxseed_utility::pool::Handle;
+use raw_window_handle::HasRawDisplayHandle;
+use winit::{event_loop::EventLoop, window::WindowBuilder};
+
+/// Rays from z = -1 towards +z, the first one through the triangle and the second one past it.
+const RAY_ORIGINS: [[f32; 2]; 2] = [[0.0, 0.0], [2.0, 0.0]];
+
+const TRACE_CS: &str = r#"
+#version 460
+#extension GL_EXT_ray_query : require
+layout(local_size_x = 2) in;
+layout(set = 0, binding = 0) uniform accelerationStructureEXT scene;
+layout(set = 0, binding = 1) buffer Rays {
+    vec2 origins[2];
+    // Hit distance, -1 for a miss
+    float hits[2];
+};
+void main() {
+    uint i = gl_LocalInvocationID.x;
+    rayQueryEXT query;
+    rayQueryInitializeEXT(query, scene, gl_RayFlagsOpaqueEXT, 0xFF,
+        vec3(origins[i], -1.0), 0.0, vec3(0.0, 0.0, 1.0), 100.0);
+    while (rayQueryProceedEXT(query)) {}
+    bool hit = rayQueryGetIntersectionTypeEXT(query, true)
+        == gl_RayQueryCommittedIntersectionTriangleEXT;
+    hits[i] = hit ? rayQueryGetIntersectionTEXT(query, true) : -1.0;
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Only the display handle is needed, nothing is presented
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().with_visible(false).build(&event_loop)?;
+
+    let mut rhi = create_render_backend(
+        BackendType::Vulkan,
+        RenderBackendCreateDesc {
+            app_name: "Luxseed Vulkan - Ray Query",
+            app_version: 0,
+            enable_debugging: true,
+            raw_display_handle: window.raw_display_handle(),
+            debug_callback: None,
+            robust_buffer_access: false,
+            min_api_version: (1, 2, 0),
+            additional_instance_extensions: &[],
+        },
+    )?;
+    rhi.create_device(&DeviceCreateDesc {
+        features: DeviceFeatures::RAY_QUERY,
+        ..Default::default()
+    })?;
+    if !rhi.enabled_features()?.features.contains(DeviceFeatures::RAY_QUERY) {
+        anyhow::bail!("The adapter doesn't support ray queries.");
+    }
+    let queue = rhi.get_queue(QueueType::Graphics)?;
+
+    // A triangle in the z = 0 plane
+    let vertices: [f32; 9] = [-0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.0, 0.5, 0.0];
+    let vertex_buffer = create_host_buffer(
+        &mut *rhi,
+        "RayQuery_Vertices",
+        as_bytes(&vertices),
+        BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT
+            | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+    )?;
+    let blas = rhi.create_acceleration_structure(&AccelerationStructureCreateDesc {
+        name: "RayQuery_BLAS",
+        ty: AccelerationStructureType::BottomLevel,
+        geometries: &[AccelerationStructureGeometry::Triangles {
+            vertex_buffer,
+            vertex_offset: 0,
+            vertex_format: Format::R32G32B32_SFLOAT,
+            vertex_stride: 12,
+            max_vertex: 2,
+            index_buffer: None,
+            triangle_count: 1,
+            opaque: true,
+        }],
+    })?;
+
+    // One instance of the triangle, where it was modeled
+    let instance = AccelerationStructureInstance::new(
+        AccelerationStructureInstance::IDENTITY,
+        rhi.get_acceleration_structure_device_address(blas)?,
+    );
+    let instance_buffer = create_host_buffer(
+        &mut *rhi,
+        "RayQuery_Instances",
+        AccelerationStructureInstance::as_bytes(&[instance]),
+        BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT
+            | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+    )?;
+    let tlas = rhi.create_acceleration_structure(&AccelerationStructureCreateDesc {
+        name: "RayQuery_TLAS",
+        ty: AccelerationStructureType::TopLevel,
+        geometries: &[AccelerationStructureGeometry::Instances {
+            buffer: instance_buffer,
+            offset: 0,
+            count: 1,
+        }],
+    })?;
+
+    // Ray origins followed by room for the hit distances
+    let mut rays = Vec::new();
+    rays.extend(RAY_ORIGINS.iter().flatten().flat_map(|v| v.to_ne_bytes()));
+    rays.extend([0u8; 8]);
+    let ray_buffer =
+        create_host_buffer(&mut *rhi, "RayQuery_Rays", &rays, BufferUsageFlags::STORAGE_BUFFER)?;
+
+    let code = compile_glsl("trace.comp", TRACE_CS, ShaderStageFlags::COMPUTE, "main")?;
+    let shader = rhi.create_shader_module(&ShaderModuleCreation {
+        name: "Trace_CS",
+        code: &code,
+        stage: ShaderStageFlags::COMPUTE,
+        entry: "main",
+    })?;
+    let layouts = rhi.reflect_descriptor_layouts(&[shader])?;
+    let descriptor_set_layout = rhi.create_descriptor_set_layout(&layouts[0])?;
+    let descriptor_pool = rhi.create_descriptor_pool(&DescriptorPoolCreateDesc {
+        max_sets: 1,
+        pool_sizes: &[
+            DescriptorPoolSize {
+                descriptor_type: DescriptorType::AccelerationStructure,
+                descriptor_count: 1,
+            },
+            DescriptorPoolSize {
+                descriptor_type: DescriptorType::StorageBuffer,
+                descriptor_count: 1,
+            },
+        ],
+        update_after_bind: false,
+    })?;
+    let descriptor_set = rhi.create_descriptor_set(
+        &DescriptorSetCreateDesc::new(descriptor_pool, descriptor_set_layout)
+            .bind_acceleration_structure(0, tlas)
+            .bind_storage_buffer(1, ray_buffer),
+    )?;
+    let pipeline_layout = rhi.create_pipeline_layout(&PipelineLayoutCreateDesc {
+        descriptor_set_layouts: &[descriptor_set_layout],
+        push_constant_ranges: &[],
+    })?;
+    let pipeline = rhi.create_compute_pipeline(&ComputePipelineCreateDesc {
+        shader,
+        pipeline_layout,
+        specialization: &[],
+    })?;
+
+    // Build both structures and trace, the build makes its results visible to the dispatch
+    let command_pool = rhi.create_command_pool(queue)?;
+    let cb = rhi.create_command_buffer(command_pool, CommandBufferLevel::Primary)?;
+    rhi.cmd_begin(cb, CommandBufferBeginDesc { one_time_submit: true, ..Default::default() })?;
+    rhi.cmd_build_acceleration_structures(cb, &[blas, tlas])?;
+    rhi.cmd_bind_compute_pipeline(cb, pipeline)?;
+    rhi.cmd_bind_descriptor_sets(
+        cb,
+        PipelineBindPoint::Compute,
+        pipeline_layout,
+        0,
+        &[descriptor_set],
+        &[],
+    )?;
+    rhi.cmd_dispatch(cb, 1, 1, 1)?;
+    rhi.cmd_pipeline_barrier(
+        cb,
+        PipelineStageFlags::COMPUTE_SHADER,
+        PipelineStageFlags::HOST,
+        &[],
+        &[BufferMemoryBarrier::whole_buffer(
+            ray_buffer,
+            AccessFlags::SHADER_WRITE,
+            AccessFlags::HOST_READ,
+        )],
+        &[],
+    )?;
+    rhi.cmd_end(cb)?;
+
+    let fence = rhi.create_fence(false)?;
+    rhi.queue_submit(
+        queue,
+        &QueueSubmitDesc {
+            wait_semaphore: None,
+            wait_values: None,
+            wait_stage: None,
+            command_buffer: &[cb],
+            finish_semaphore: None,
+            signal_values: None,
+            fence: Some(fence),
+        },
+    )?;
+    if rhi.wait_for_fences(&[fence], true, u64::MAX)? != FenceWaitResult::Signaled {
+        anyhow::bail!("Ray query dispatch didn't finish.");
+    }
+
+    // Read back
+    rhi.invalidate_buffer(ray_buffer, 0, WHOLE_SIZE)?;
+    let mapped = rhi.get_buffer_mapped_slice_mut(ray_buffer)?;
+    let hits: Vec<f32> = mapped[16..24]
+        .chunks_exact(4)
+        .map(|chunk| f32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect();
+    assert!((hits[0] - 1.0).abs() < 1e-4, "the first ray hits the triangle at t = 1");
+    assert_eq!(hits[1], -1.0, "the second ray misses the triangle");
+    println!("Ray 0 hit the triangle at t = {}, ray 1 missed it.", hits[0]);
+
+    rhi.destroy_fence(fence)?;
+    rhi.destroy_command_buffer(cb)?;
+    rhi.destroy_command_pool(command_pool)?;
+    rhi.destroy_compute_pipeline(pipeline)?;
+    rhi.destroy_pipeline_layout(pipeline_layout)?;
+    rhi.destroy_descriptor_pool(descriptor_pool)?;
+    rhi.destroy_descriptor_set_layout(descriptor_set_layout)?;
+    rhi.destroy_shader_module(shader)?;
+    rhi.destroy_acceleration_structure(tlas)?;
+    rhi.destroy_acceleration_structure(blas)?;
+    rhi.destroy_buffer(ray_buffer)?;
+    rhi.destroy_buffer(instance_buffer)?;
+    rhi.destroy_buffer(vertex_buffer)?;
+    rhi.destroy_device()?;
+    Ok(())
+}
+
+/// A host visible buffer holding `data`.
+fn create_host_buffer(
+    rhi: &mut dyn RenderBackend,
+    name: &str,
+    data: &[u8],
+    usage: BufferUsageFlags,
+) -> anyhow::Result<Handle<Buffer>> {
+    let buffer = rhi.create_buffer(&BufferCreateDesc {
+        name,
+        size: data.len() as u64,
+        usage,
+        memory: MemoryLocation::CpuToGpu,
+        initial_data: None,
+    })?;
+    rhi.get_buffer_mapped_slice_mut(buffer)?[..data.len()].copy_from_slice(data);
+    rhi.flush_buffer(buffer, 0, WHOLE_SIZE)?;
+    Ok(buffer)
+}
+
+fn as_bytes(values: &[f32]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(values.as_ptr() as *const u8, std::mem::size_of_val(values))
+    }
+}
@@ -0,0 +1,304 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec3, Mat4, Vec3, Vec4};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::mem::size_of;
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const NEAR: f32 = 0.01;
+const FAR: f32 = 1000.0;
+/// Seconds spent in each depth mode before switching to the other one.
+const MODE_SECONDS: u64 = 3;
+
+const VERT: &str = r#"#version 450
+
+layout(location = 0) in vec4 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 0) out vec3 fragColor;
+
+void main() {
+    gl_Position = inPosition;
+    fragColor = inColor;
+}
+"#;
+
+const FRAG: &str = r#"#version 450
+
+layout(location = 0) in vec3 fragColor;
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    outColor = vec4(fragColor, 1.0);
+}
+"#;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Reverse-Z")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Clip space position, projected once on the CPU since the camera never moves.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: Vec4,
+    pub color: Vec3,
+}
+
+/// Two long strips a few millimeters apart running away from the camera. The upper one is
+/// drawn last and should hide the lower one everywhere, with standard depth the lower one
+/// bleeds through in the distance.
+fn strips(view_proj: Mat4) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+    for (height, color) in [(-1.0, vec3(0.9, 0.2, 0.2)), (-0.995, vec3(0.2, 0.4, 0.9))] {
+        let corners = [
+            vec3(-2.0, height, -1.0),
+            vec3(2.0, height, -1.0),
+            vec3(2.0, height, -FAR * 0.5),
+            vec3(-2.0, height, -FAR * 0.5),
+        ];
+        for i in [0, 1, 2, 2, 3, 0] {
+            vertices.push(Vertex { pos: view_proj * corners[i].extend(1.0), color });
+        }
+    }
+    vertices
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub reverse_z: Option<bool>,
+    pub vs: Handle<Shader>,
+    pub fs: Handle<Shader>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub standard_pipeline: Handle<RasterPipeline>,
+    pub reverse_z_pipeline: Handle<RasterPipeline>,
+
+    pub vertex_buffer: Handle<Buffer>,
+    pub vertex_count: u32,
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let vs = compile_shader_glsl(
+            &mut sys.backend,
+            "reverse_z.vert",
+            VERT,
+            ShaderStageFlags::VERTEX,
+            "main",
+        )?;
+        let fs = compile_shader_glsl(
+            &mut sys.backend,
+            "reverse_z.frag",
+            FRAG,
+            ShaderStageFlags::FRAGMENT,
+            "main",
+        )?;
+
+        // The same projection feeds both modes, only the viewport depth range differs
+        let mut proj =
+            Mat4::perspective_rh(45.0_f32.to_radians(), WIDTH as f32 / HEIGHT as f32, NEAR, FAR);
+        proj.col_mut(1)[1] *= -1.0;
+        let view =
+            Mat4::look_at_rh(vec3(0.0, 0.0, 0.0), vec3(0.0, -0.2, -100.0), vec3(0.0, 1.0, 0.0));
+        let vertices = strips(proj * view);
+
+        let vertex_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Strips_Vertex",
+            size: (vertices.len() * size_of::<Vertex>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            vertex_buffer,
+            as_byte_slice_unchecked(&vertices),
+        )?;
+
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[],
+            push_constant_ranges: &[],
+        })?;
+
+        let mut pipelines = Vec::new();
+        for depth_state in [DepthState::default(), DepthState::reverse_z()] {
+            pipelines.push(sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+                vertex_input_bindings: Some(&[VertexInputBinding {
+                    stride: size_of::<Vertex>(),
+                    input_rate: VertexInputRate::Vertex,
+                    attributes: &[
+                        VertexInputAttribute { offset: 0, format: Format::R32G32B32A32_SFLOAT },
+                        VertexInputAttribute {
+                            offset: size_of::<Vec4>(),
+                            format: Format::R32G32B32_SFLOAT,
+                        },
+                    ],
+                }]),
+                shader_stages: &[vs, fs],
+                render_pass_output: sys.swapchain_output,
+                subpass: 0,
+                blend_states: &[BlendState::default()],
+                logic_op: None,
+                raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+                depth_state,
+                pipeline_layout,
+                specialization: &[],
+                patch_control_points: 0,
+                dynamic_rendering: false,
+            })?);
+        }
+
+        let mut command_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            reverse_z: None,
+            vs,
+            fs,
+            pipeline_layout,
+            standard_pipeline: pipelines[0],
+            reverse_z_pipeline: pipelines[1],
+            vertex_buffer,
+            vertex_count: vertices.len() as u32,
+            command_buffers,
+        })
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        // Alternate between the two modes to compare the far end of the strips
+        let reverse_z = (self.start.elapsed().as_secs() / MODE_SECONDS) % 2 == 1;
+        if self.reverse_z != Some(reverse_z) {
+            self.reverse_z = Some(reverse_z);
+            window.set_title(if reverse_z {
+                "Luxseed RHI Reverse-Z (reverse-Z depth)"
+            } else {
+                "Luxseed RHI Reverse-Z (standard depth)"
+            });
+        }
+
+        if self.sys.begin_frame(width, height)? {
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+
+            // Reverse-Z clears to the far value 0.0 and keeps fragments with greater depth
+            let (pipeline, viewport, clear_depth) = if reverse_z {
+                (
+                    self.reverse_z_pipeline,
+                    Viewport::new(width as f32, height as f32).reverse_z(),
+                    0.0,
+                )
+            } else {
+                (self.standard_pipeline, Viewport::new(width as f32, height as f32), 1.0)
+            };
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.05, 0.05, 0.05, 1.0]);
+            let cd = ClearDepthStencil { depth: clear_depth, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, pipeline)?;
+            self.sys.backend.cmd_apply_viewport(cb, &viewport)?;
+            self.sys.backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+            self.sys.backend.cmd_bind_vertex_buffers(cb, 0, &[self.vertex_buffer], &[0])?;
+            self.sys.backend.cmd_draw(cb, self.vertex_count, 1, 0, 0)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_buffer(self.vertex_buffer).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.reverse_z_pipeline).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.standard_pipeline).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_shader_module(self.vs).unwrap();
+        self.sys.backend.destroy_shader_module(self.fs).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+#[path = "common/render_system.rs"]
 mod render_system;
 
 use glam::{vec2, vec3, Mat4, Vec2, Vec3};
@@ -154,6 +155,10 @@ impl App {
             mip_lod_bias: 0.0,
             compare_op: None,
             max_anisotropy: None,
+            border_color: BorderColor::FloatTransparentBlack,
+            min_lod: 0.0,
+            max_lod: LOD_CLAMP_NONE,
+            unnormalized_coordinates: false,
         })?;
 
         // Vertex buffer
@@ -216,12 +221,14 @@ impl App {
                     type_: DescriptorType::UniformBuffer,
                     count: 1,
                     stage_flags: ShaderStageFlags::VERTEX,
+                    flags: DescriptorBindingFlags::empty(),
                 })
                 .add_binding_info(DescriptorBindingInfo {
                     index: 1,
                     type_: DescriptorType::CombinedImageSampler,
                     count: 1,
                     stage_flags: ShaderStageFlags::FRAGMENT,
+                    flags: DescriptorBindingFlags::empty(),
                 }),
         )?;
 
@@ -238,11 +245,13 @@ impl App {
                     descriptor_count: sys.max_frames_in_flight as u32,
                 },
             ],
+            update_after_bind: false,
         })?;
 
         // Pipeline layout
         let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
             descriptor_set_layouts: &[descriptor_set_layout],
+            push_constant_ranges: &[],
         })?;
 
         // Pipeline
@@ -266,10 +275,15 @@ impl App {
                 }]),
                 shader_stages: &[vs, fs],
                 render_pass_output: sys.swapchain_output,
+                subpass: 0,
                 blend_states: &[BlendState::default()],
+                logic_op: None,
                 raster_state: RasterState::default(),
                 depth_state: DepthState::default(),
                 pipeline_layout,
+                specialization: &[],
+                patch_control_points: 0,
+                dynamic_rendering: false,
             })
             .unwrap();
 
@@ -329,7 +343,7 @@ impl App {
             .backend
             .get_buffer_mapped_slice_mut(ub)?
             .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
-        Ok(())
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
     }
 
     pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
@@ -337,6 +351,8 @@ impl App {
         let height = window.inner_size().height;
 
         if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
             self.update_uniform_buffer(width, height)?;
 
             let cb = self.command_buffers[self.sys.frame];
@@ -345,7 +361,14 @@ impl App {
             let rp = self.sys.swapchain_render_pass;
             let fb = self.sys.get_swapchain_framebuffer();
             let cv = ClearColor::new([0.0, 0.0, 0.0, 1.0]);
-            self.sys.backend.cmd_begin_render_pass(cb, rp, fb, Some(&[cv]), None)?;
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(ClearDepthStencil { depth: 1.0, stencil: 0 }),
+                SubpassContents::Inline,
+            )?;
             self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
             self.sys.backend.cmd_set_viewport(
                 cb,
@@ -374,9 +397,11 @@ impl App {
                 self.sys.graphics_queue,
                 &QueueSubmitDesc {
                     wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
                     wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
                     command_buffer: &[cb],
                     finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
                     fence: Some(self.sys.get_in_flight_fence()),
                 },
             )?;
@@ -0,0 +1,494 @@
+#[path = "common/render_system.rs"]
+mod render_system;
+
+use glam::{vec3, Mat4, Vec3};
+use luxseed_render_backend::{define::*, enums::*, flag::*};
+use luxseed_utility::pool::Handle;
+use render_system::*;
+use std::{fs, mem::size_of};
+use winit::{
+    dpi::LogicalSize,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
+};
+
+const WIDTH: u32 = 1600;
+const HEIGHT: u32 = 900;
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+fn main() -> anyhow::Result<()> {
+    // Window
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title("Luxseed RHI Shadow Map")
+        .with_inner_size(LogicalSize::new(WIDTH, HEIGHT))
+        .build(&event_loop)?;
+
+    // App
+    let mut app = App::create(&window)?;
+    let mut destroying = false;
+    let mut minimized = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::MainEventsCleared if !destroying && !minimized => app.render(&window).unwrap(),
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                if size.width == 0 || size.height == 0 {
+                    minimized = true;
+                } else {
+                    minimized = false;
+                    app.resize = true;
+                }
+            }
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                destroying = true;
+                *control_flow = ControlFlow::Exit;
+                app.destroy();
+            }
+            _ => {}
+        }
+    });
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub pos: Vec3,
+    pub color: Vec3,
+}
+
+impl Vertex {
+    const fn new(pos: Vec3, color: Vec3) -> Self {
+        Self { pos, color }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UniformBufferObject {
+    pub view_proj: Mat4,
+    pub light_view_proj: Mat4,
+}
+
+pub struct App {
+    pub start: std::time::Instant,
+    pub sys: RenderSystem,
+    pub resize: bool,
+    pub shaders: Vec<Handle<Shader>>,
+
+    pub shadow_map: Handle<Image>,
+    pub shadow_map_view: Handle<ImageView>,
+    pub shadow_sampler: Handle<Sampler>,
+    pub shadow_render_pass: Handle<RenderPass>,
+    pub shadow_framebuffer: Handle<Framebuffer>,
+    pub shadow_pipeline: Handle<RasterPipeline>,
+
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    pub descriptor_set_layout: Handle<DescriptorSetLayout>,
+    pub descriptor_pool: Handle<DescriptorPool>,
+
+    pub command_buffers: Vec<Handle<CommandBuffer>>,
+    pub uniform_buffers: Vec<Handle<Buffer>>,
+    pub descriptor_sets: Vec<Handle<DescriptorSet>>,
+
+    pub vertex_buffer: Handle<Buffer>,
+    pub index_buffer: Handle<Buffer>,
+    pub indices: Vec<u16>,
+}
+
+impl App {
+    pub fn create(window: &Window) -> anyhow::Result<Self> {
+        let mut sys = RenderSystem::create(window)?;
+
+        let mut shaders = Vec::new();
+        for (file, stage) in [
+            ("shadow_map_depth.vert", ShaderStageFlags::VERTEX),
+            ("shadow_map.vert", ShaderStageFlags::VERTEX),
+            ("shadow_map.frag", ShaderStageFlags::FRAGMENT),
+        ] {
+            shaders.push(compile_shader_glsl(
+                &mut sys.backend,
+                file,
+                &fs::read_to_string(format!("assets/luxseed-render-backend-test/{}", file))
+                    .expect("Should have been able to read the file"),
+                stage,
+                "main",
+            )?);
+        }
+
+        // Shadow map, rendered as a depth attachment then sampled by the main pass
+        let shadow_format = Format::D32_SFLOAT;
+        let shadow_map = sys.backend.create_image(&ImageCreateDesc {
+            usage: ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | ImageUsageFlags::SAMPLED,
+            ..ImageCreateDesc::new_depth(
+                "shadow_map",
+                shadow_format,
+                SHADOW_MAP_SIZE,
+                SHADOW_MAP_SIZE,
+            )
+        })?;
+        let shadow_map_view =
+            sys.backend.create_image_view(shadow_map, &ImageViewCreateDesc::new_depth())?;
+        let shadow_sampler = sys.backend.create_sampler(&SamplerCreateDesc {
+            mag_filter: FilterType::Linear,
+            min_filter: FilterType::Linear,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode_u: SamplerAddressMode::ClampToBorder,
+            address_mode_v: SamplerAddressMode::ClampToBorder,
+            address_mode_w: SamplerAddressMode::ClampToBorder,
+            mip_lod_bias: 0.0,
+            compare_op: Some(CompareOp::LessOrEqual),
+            max_anisotropy: None,
+            // Outside the shadow map is at the far plane, so it's lit
+            border_color: BorderColor::FloatOpaqueWhite,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            unnormalized_coordinates: false,
+        })?;
+
+        // Depth-only render pass: no color attachment at all
+        let shadow_output = RenderPassOutput::builder()
+            .set_depth_stencil(
+                shadow_format,
+                ImageLayout::ShaderReadOnlyOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let shadow_render_pass = sys.backend.create_render_pass(&shadow_output)?;
+        let shadow_framebuffer = sys.backend.create_framebuffer(&FramebufferCreateDesc {
+            render_pass: shadow_render_pass,
+            color_views: &[],
+            depth_stencil_view: Some(shadow_map_view),
+            resolve_views: &[],
+            extent: None,
+        })?;
+
+        // Ground plane and a floating quad casting a shadow onto it
+        let ground = vec3(0.8, 0.8, 0.8);
+        let caster = vec3(0.9, 0.4, 0.2);
+        let vertices = vec![
+            Vertex::new(vec3(-2.0, -2.0, 0.0), ground),
+            Vertex::new(vec3(2.0, -2.0, 0.0), ground),
+            Vertex::new(vec3(2.0, 2.0, 0.0), ground),
+            Vertex::new(vec3(-2.0, 2.0, 0.0), ground),
+            Vertex::new(vec3(-0.5, -0.5, 0.8), caster),
+            Vertex::new(vec3(0.5, -0.5, 0.8), caster),
+            Vertex::new(vec3(0.5, 0.5, 0.8), caster),
+            Vertex::new(vec3(-0.5, 0.5, 0.8), caster),
+        ];
+        let vertex_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Scene_Vertex",
+            size: (vertices.len() * size_of::<Vertex>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::VERTEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            vertex_buffer,
+            as_byte_slice_unchecked(&vertices),
+        )?;
+
+        let indices: Vec<u16> = vec![0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4];
+        let index_buffer = sys.backend.create_buffer(&BufferCreateDesc {
+            name: "Scene_Index",
+            size: (indices.len() * size_of::<u16>()) as u64,
+            usage: BufferUsageFlags::TRANSFER_DST | BufferUsageFlags::INDEX_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        upload_buffer_by_staging_buffer(
+            &mut sys.backend,
+            sys.command_pool,
+            sys.graphics_queue,
+            index_buffer,
+            as_byte_slice_unchecked(&indices),
+        )?;
+
+        // UBOs
+        let mut uniform_buffers = Vec::new();
+        for _ in 0..sys.max_frames_in_flight {
+            uniform_buffers.push(sys.backend.create_buffer(&BufferCreateDesc {
+                name: "Scene_UBO",
+                size: size_of::<UniformBufferObject>() as u64,
+                usage: BufferUsageFlags::UNIFORM_BUFFER,
+                memory: MemoryLocation::CpuToGpu,
+                initial_data: None,
+            })?);
+        }
+
+        // Descriptors, shared by both passes
+        let descriptor_set_layout = sys.backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new()
+                .add_binding_info(DescriptorBindingInfo {
+                    index: 0,
+                    type_: DescriptorType::UniformBuffer,
+                    count: 1,
+                    stage_flags: ShaderStageFlags::VERTEX,
+                    flags: DescriptorBindingFlags::empty(),
+                })
+                .add_binding_info(DescriptorBindingInfo {
+                    index: 1,
+                    type_: DescriptorType::CombinedImageSampler,
+                    count: 1,
+                    stage_flags: ShaderStageFlags::FRAGMENT,
+                    flags: DescriptorBindingFlags::empty(),
+                }),
+        )?;
+        let descriptor_pool = sys.backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: sys.max_frames_in_flight as u32,
+            pool_sizes: &[
+                DescriptorPoolSize {
+                    descriptor_type: DescriptorType::UniformBuffer,
+                    descriptor_count: sys.max_frames_in_flight as u32,
+                },
+                DescriptorPoolSize {
+                    descriptor_type: DescriptorType::CombinedImageSampler,
+                    descriptor_count: sys.max_frames_in_flight as u32,
+                },
+            ],
+            update_after_bind: false,
+        })?;
+        let pipeline_layout = sys.backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[descriptor_set_layout],
+            push_constant_ranges: &[],
+        })?;
+
+        let vertex_input_bindings = [VertexInputBinding {
+            stride: size_of::<Vertex>(),
+            input_rate: VertexInputRate::Vertex,
+            attributes: &[
+                VertexInputAttribute { offset: 0, format: Format::R32G32B32_SFLOAT },
+                VertexInputAttribute {
+                    offset: size_of::<Vec3>(),
+                    format: Format::R32G32B32_SFLOAT,
+                },
+            ],
+        }];
+
+        // Shadow pipeline: vertex stage only, no blend state since there is no color target
+        let shadow_pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&vertex_input_bindings),
+            shader_stages: &[shaders[0]],
+            render_pass_output: shadow_output,
+            subpass: 0,
+            blend_states: &[],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+        let pipeline = sys.backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&vertex_input_bindings),
+            shader_stages: &[shaders[1], shaders[2]],
+            render_pass_output: sys.swapchain_output,
+            subpass: 0,
+            blend_states: &[BlendState::default()],
+            logic_op: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::default(),
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        let mut command_buffers = Vec::new();
+        let mut descriptor_sets = Vec::new();
+        for ub in uniform_buffers.iter() {
+            command_buffers.push(
+                sys.backend.create_command_buffer(sys.command_pool, CommandBufferLevel::Primary)?,
+            );
+            descriptor_sets.push(
+                sys.backend.create_descriptor_set(
+                    &DescriptorSetCreateDesc::new(descriptor_pool, descriptor_set_layout)
+                        .bind_uniform_buffer(0, *ub)
+                        .bind_combined_image_sampler(1, shadow_map_view, shadow_sampler),
+                )?,
+            );
+        }
+
+        Ok(Self {
+            start: std::time::Instant::now(),
+            sys,
+            resize: false,
+            shaders,
+            shadow_map,
+            shadow_map_view,
+            shadow_sampler,
+            shadow_render_pass,
+            shadow_framebuffer,
+            shadow_pipeline,
+            pipeline_layout,
+            pipeline,
+            descriptor_set_layout,
+            descriptor_pool,
+            command_buffers,
+            uniform_buffers,
+            descriptor_sets,
+            vertex_buffer,
+            index_buffer,
+            indices,
+        })
+    }
+
+    fn update_uniform_buffer(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let time = self.start.elapsed().as_secs_f32();
+
+        let mut proj =
+            Mat4::perspective_rh(45.0_f32.to_radians(), width as f32 / height as f32, 0.1, 10.0);
+        proj.col_mut(1)[1] *= -1.0;
+        let view = Mat4::look_at_rh(vec3(3.0, 3.0, 3.0), Vec3::ZERO, Vec3::Z);
+
+        // The light circles above the scene so the shadow moves across the ground
+        let light_pos = vec3(time.cos() * 1.5, time.sin() * 1.5, 4.0);
+        let mut light_proj = Mat4::perspective_rh(60.0_f32.to_radians(), 1.0, 1.0, 10.0);
+        light_proj.col_mut(1)[1] *= -1.0;
+        let light_view = Mat4::look_at_rh(light_pos, Vec3::ZERO, Vec3::Y);
+
+        let ubo = UniformBufferObject {
+            view_proj: proj * view,
+            light_view_proj: light_proj * light_view,
+        };
+        let ub = self.uniform_buffers[self.sys.frame];
+        self.sys
+            .backend
+            .get_buffer_mapped_slice_mut(ub)?
+            .copy_from_slice(as_byte_slice_unchecked(&[ubo]));
+        self.sys.backend.flush_buffer(ub, 0, WHOLE_SIZE)
+    }
+
+    fn draw_scene(&self, cb: Handle<CommandBuffer>, width: u32, height: u32) -> anyhow::Result<()> {
+        let backend = &self.sys.backend;
+        backend.cmd_set_viewport(cb, 0.0, 0.0, width as f32, height as f32, 0.0, 1.0)?;
+        backend.cmd_set_scissor(cb, 0, 0, width, height)?;
+        backend.cmd_bind_vertex_buffers(cb, 0, &[self.vertex_buffer], &[0])?;
+        backend.cmd_bind_index_buffer(cb, self.index_buffer, 0, IndexType::U16)?;
+        backend.cmd_bind_descriptor_sets(
+            cb,
+            PipelineBindPoint::Graphics,
+            self.pipeline_layout,
+            0,
+            &[self.descriptor_sets[self.sys.frame]],
+            &[],
+        )?;
+        backend.cmd_draw_indexed(cb, self.indices.len() as u32, 1, 0, 0, 0)
+    }
+
+    pub fn render(&mut self, window: &Window) -> anyhow::Result<()> {
+        let width = window.inner_size().width;
+        let height = window.inner_size().height;
+
+        if self.sys.begin_frame(width, height)? {
+            // The surface can pick another size than the window reports
+            let (width, height) = self.sys.backend.get_swapchain_extent(self.sys.swapchain)?;
+            self.update_uniform_buffer(width, height)?;
+
+            let cb = self.command_buffers[self.sys.frame];
+            self.sys.backend.reset_command_buffer(cb, false)?;
+            self.sys.backend.cmd_begin(cb, CommandBufferBeginDesc::default())?;
+
+            // Shadow pass
+            let cd = ClearDepthStencil { depth: 1.0, stencil: 0 };
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                self.shadow_render_pass,
+                self.shadow_framebuffer,
+                None,
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.shadow_pipeline)?;
+            self.draw_scene(cb, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+
+            // Make the depth writes visible to the fragment shader of the main pass
+            self.sys.backend.cmd_pipeline_barrier(
+                cb,
+                PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                PipelineStageFlags::FRAGMENT_SHADER,
+                &[],
+                &[],
+                &[ImageMemoryBarrier::whole_image(
+                    self.shadow_map,
+                    ImageAspectFlags::DEPTH,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    AccessFlags::SHADER_READ,
+                )],
+            )?;
+
+            // Main pass
+            let rp = self.sys.swapchain_render_pass;
+            let fb = self.sys.get_swapchain_framebuffer();
+            let cv = ClearColor::new([0.1, 0.1, 0.15, 1.0]);
+            self.sys.backend.cmd_begin_render_pass(
+                cb,
+                rp,
+                fb,
+                Some(&[cv]),
+                Some(cd),
+                SubpassContents::Inline,
+            )?;
+            self.sys.backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+            self.draw_scene(cb, width, height)?;
+            self.sys.backend.cmd_end_render_pass(cb)?;
+            self.sys.backend.cmd_end(cb)?;
+
+            self.sys.backend.queue_submit(
+                self.sys.graphics_queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&[self.sys.get_image_available_semaphore()]),
+                    wait_values: None,
+                    wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[self.sys.get_render_finished_semaphore()]),
+                    signal_values: None,
+                    fence: Some(self.sys.get_in_flight_fence()),
+                },
+            )?;
+            self.sys.end_frame(self.resize, width, height)?;
+            self.resize = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn destroy(&mut self) {
+        self.sys.backend.device_wait_idle().unwrap();
+
+        self.sys.backend.destroy_buffer(self.vertex_buffer).unwrap();
+        self.sys.backend.destroy_buffer(self.index_buffer).unwrap();
+        for ub in self.uniform_buffers.iter() {
+            self.sys.backend.destroy_buffer(*ub).unwrap();
+        }
+
+        self.sys.backend.destroy_framebuffer(self.shadow_framebuffer).unwrap();
+        self.sys.backend.destroy_render_pass(self.shadow_render_pass).unwrap();
+        self.sys.backend.destroy_sampler(self.shadow_sampler).unwrap();
+        self.sys.backend.destroy_image(self.shadow_map).unwrap();
+
+        for shader in self.shaders.iter() {
+            self.sys.backend.destroy_shader_module(*shader).unwrap();
+        }
+        self.sys.backend.destroy_descriptor_set_layout(self.descriptor_set_layout).unwrap();
+        self.sys.backend.destroy_descriptor_pool(self.descriptor_pool).unwrap();
+        self.sys.backend.destroy_pipeline_layout(self.pipeline_layout).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.shadow_pipeline).unwrap();
+        self.sys.backend.destroy_raster_pipeline(self.pipeline).unwrap();
+
+        self.sys.destroy().unwrap();
+    }
+}
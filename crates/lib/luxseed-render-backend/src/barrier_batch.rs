@@ -0,0 +1,198 @@
+use anyhow::Result;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// Stages that access an image in `layout` and how they access it, used on both sides of a
+/// transition.
+pub fn layout_stage_access(layout: ImageLayout) -> (PipelineStageFlags, AccessFlags) {
+    let fragment_tests =
+        PipelineStageFlags::EARLY_FRAGMENT_TESTS | PipelineStageFlags::LATE_FRAGMENT_TESTS;
+    match layout {
+        ImageLayout::Undefined => (PipelineStageFlags::TOP_OF_PIPE, AccessFlags::empty()),
+        ImageLayout::General => {
+            (PipelineStageFlags::ALL_COMMANDS, AccessFlags::MEMORY_READ | AccessFlags::MEMORY_WRITE)
+        }
+        ImageLayout::ColorAttachmentOptimal => (
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            AccessFlags::COLOR_ATTACHMENT_READ | AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ),
+        ImageLayout::DepthStencilAttachmentOptimal
+        | ImageLayout::DepthAttachmentOptimal
+        | ImageLayout::StencilAttachmentOptimal => (
+            fragment_tests,
+            AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        ImageLayout::DepthStencilReadOnlyOptimal
+        | ImageLayout::DepthReadOnlyOptimal
+        | ImageLayout::StencilReadOnlyOptimal => (
+            fragment_tests | PipelineStageFlags::FRAGMENT_SHADER,
+            AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | AccessFlags::SHADER_READ,
+        ),
+        ImageLayout::ShaderReadOnlyOptimal => (
+            PipelineStageFlags::FRAGMENT_SHADER | PipelineStageFlags::COMPUTE_SHADER,
+            AccessFlags::SHADER_READ,
+        ),
+        ImageLayout::TransferSrcOptimal => {
+            (PipelineStageFlags::TRANSFER, AccessFlags::TRANSFER_READ)
+        }
+        ImageLayout::TransferDstOptimal => {
+            (PipelineStageFlags::TRANSFER, AccessFlags::TRANSFER_WRITE)
+        }
+        ImageLayout::Preinitialized => (PipelineStageFlags::HOST, AccessFlags::HOST_WRITE),
+        ImageLayout::PresentSrcKhr => (PipelineStageFlags::BOTTOM_OF_PIPE, AccessFlags::empty()),
+    }
+}
+
+/// The aspect a transition between two layouts covers, depth stencil images get both aspects
+/// from the backend unless per-aspect layouts are used.
+fn layout_aspect(old_layout: ImageLayout, new_layout: ImageLayout) -> ImageAspectFlags {
+    let aspect = |layout| match layout {
+        ImageLayout::StencilAttachmentOptimal | ImageLayout::StencilReadOnlyOptimal => {
+            Some(ImageAspectFlags::STENCIL)
+        }
+        ImageLayout::DepthStencilAttachmentOptimal
+        | ImageLayout::DepthStencilReadOnlyOptimal
+        | ImageLayout::DepthAttachmentOptimal
+        | ImageLayout::DepthReadOnlyOptimal => Some(ImageAspectFlags::DEPTH),
+        _ => None,
+    };
+    aspect(new_layout).or(aspect(old_layout)).unwrap_or(ImageAspectFlags::COLOR)
+}
+
+/// Collects whole image layout transitions and records them as a single pipeline barrier,
+/// waiting on the union of the stages the old layouts are used in, e.g. to make all G-buffer
+/// targets readable at once.
+pub struct BarrierBatch {
+    src_stage: PipelineStageFlags,
+    dst_stage: PipelineStageFlags,
+    barriers: Vec<ImageMemoryBarrier>,
+}
+
+impl Default for BarrierBatch {
+    fn default() -> Self {
+        Self {
+            src_stage: PipelineStageFlags::empty(),
+            dst_stage: PipelineStageFlags::empty(),
+            barriers: Vec::new(),
+        }
+    }
+}
+
+impl BarrierBatch {
+    /// Moves every mip level and layer of `image` from `old_layout` to `new_layout`, with the
+    /// stages and access masks derived from the layouts.
+    pub fn transition(
+        &mut self,
+        image: Handle<Image>,
+        old_layout: ImageLayout,
+        new_layout: ImageLayout,
+    ) -> &mut Self {
+        let (src_stage, src_access) = layout_stage_access(old_layout);
+        let (dst_stage, dst_access) = layout_stage_access(new_layout);
+        self.src_stage |= src_stage;
+        self.dst_stage |= dst_stage;
+        self.barriers.push(ImageMemoryBarrier::whole_image(
+            image,
+            layout_aspect(old_layout, new_layout),
+            old_layout,
+            new_layout,
+            src_access,
+            dst_access,
+        ));
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.barriers.is_empty()
+    }
+
+    /// Records the transitions pushed so far, if any, and empties the batch.
+    pub fn record(&mut self, backend: &dyn RenderBackend, cb: Handle<CommandBuffer>) -> Result<()> {
+        if self.barriers.is_empty() {
+            return Ok(());
+        }
+        let recorded = backend.cmd_pipeline_barrier(
+            cb,
+            self.src_stage,
+            self.dst_stage,
+            &[],
+            &[],
+            &self.barriers,
+        );
+        *self = Self::default();
+        recorded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Call, RecordingBackend};
+    use luxseed_utility::{impl_handle, pool::Pool};
+
+    #[derive(Default)]
+    struct TestImage {
+        handle: Option<Handle<Image>>,
+    }
+    impl_handle!(TestImage, Image, handle);
+
+    #[test]
+    fn transitions_share_one_barrier() {
+        let mut images = Pool::<TestImage>::with_size(4, Default::default);
+        let albedo = images.malloc().0;
+        let normal = images.malloc().0;
+        let depth = images.malloc().0;
+        let backend = RecordingBackend::default();
+
+        let mut batch = BarrierBatch::default();
+        batch
+            .transition(albedo, ImageLayout::Undefined, ImageLayout::ColorAttachmentOptimal)
+            .transition(
+                normal,
+                ImageLayout::TransferDstOptimal,
+                ImageLayout::ColorAttachmentOptimal,
+            )
+            .transition(
+                depth,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                ImageLayout::ShaderReadOnlyOptimal,
+            );
+        batch.record(&backend, Handle::default()).unwrap();
+
+        let calls = backend.calls();
+        assert_eq!(calls.len(), 1);
+        let Call::PipelineBarrier { src_stage, dst_stage, image_barriers, .. } = &calls[0] else {
+            panic!("expected a pipeline barrier, got {:?}", calls[0]);
+        };
+        assert_eq!(
+            *src_stage,
+            PipelineStageFlags::TOP_OF_PIPE
+                | PipelineStageFlags::TRANSFER
+                | PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | PipelineStageFlags::LATE_FRAGMENT_TESTS
+        );
+        assert_eq!(
+            *dst_stage,
+            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | PipelineStageFlags::FRAGMENT_SHADER
+                | PipelineStageFlags::COMPUTE_SHADER
+        );
+        assert_eq!(
+            image_barriers.iter().map(|b| (b.image, b.aspect_mask)).collect::<Vec<_>>(),
+            vec![
+                (albedo, ImageAspectFlags::COLOR),
+                (normal, ImageAspectFlags::COLOR),
+                (depth, ImageAspectFlags::DEPTH),
+            ]
+        );
+        drop(calls);
+
+        // The batch starts over once recorded
+        assert!(batch.is_empty());
+        batch.record(&backend, Handle::default()).unwrap();
+        assert_eq!(backend.calls().len(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+use bytemuck::Pod;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::Buffer, RenderBackend};
+
+impl dyn RenderBackend + '_ {
+    /// Copies `data` into the mapped memory of `buffer`, starting `offset` bytes in, and flushes
+    /// it. The buffer must be host visible, e.g. created with `MemoryLocation::CpuToGpu`.
+    ///
+    /// Only `Pod` types are accepted, so padding bytes or pointers can't end up on the GPU:
+    ///
+    /// ```compile_fail
+    /// use luxseed_render_backend::{define::Buffer, RenderBackend};
+    /// use luxseed_utility::pool::Handle;
+    ///
+    /// fn upload(backend: &mut dyn RenderBackend, buffer: Handle<Buffer>) {
+    ///     let names = [String::from("not plain data")];
+    ///     backend.write_to_buffer(buffer, 0, &names).unwrap();
+    /// }
+    /// ```
+    pub fn write_to_buffer<T: Pod>(
+        &mut self,
+        buffer: Handle<Buffer>,
+        offset: u64,
+        data: &[T],
+    ) -> Result<()> {
+        write_pod(self.get_buffer_mapped_slice_mut(buffer)?, offset, data)?;
+        self.flush_buffer(buffer, offset, std::mem::size_of_val(data) as u64)
+    }
+
+    /// Copies `value` to the start of `buffer`, the usual way to update a uniform buffer.
+    pub fn write_one<T: Pod>(&mut self, buffer: Handle<Buffer>, value: &T) -> Result<()> {
+        self.write_to_buffer(buffer, 0, std::slice::from_ref(value))
+    }
+}
+
+fn write_pod<T: Pod>(mapped: &mut [u8], offset: u64, data: &[T]) -> Result<()> {
+    let bytes: &[u8] = bytemuck::cast_slice(data);
+    let end = offset.checked_add(bytes.len() as u64);
+    match end {
+        Some(end) if end <= mapped.len() as u64 => {
+            mapped[offset as usize..end as usize].copy_from_slice(bytes);
+            Ok(())
+        }
+        _ => bail!(
+            "Writing {} bytes at offset {} overflows a mapped buffer of {} bytes.",
+            bytes.len(),
+            offset,
+            mapped.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Ubo {
+        model: [f32; 4],
+        frame: u32,
+        flags: u32,
+        _pad: [u32; 2],
+    }
+    unsafe impl Zeroable for Ubo {}
+    unsafe impl Pod for Ubo {}
+
+    #[test]
+    fn pod_round_trips_through_mapped_memory() {
+        let ubo = Ubo { model: [1.0, -2.0, 0.5, 4.0], frame: 7, flags: 0b101, _pad: [0; 2] };
+        let size = std::mem::size_of::<Ubo>();
+        let mut mapped = vec![0xffu8; size * 3];
+
+        write_pod(&mut mapped, size as u64, &[ubo, ubo]).unwrap();
+
+        assert!(mapped[..size].iter().all(|&b| b == 0xff), "bytes before the offset are kept");
+        let read: Ubo = bytemuck::pod_read_unaligned(&mapped[size..size * 2]);
+        assert_eq!(read, ubo);
+        let read: Ubo = bytemuck::pod_read_unaligned(&mapped[size * 2..]);
+        assert_eq!(read, ubo);
+    }
+
+    #[test]
+    fn out_of_range_writes_fail() {
+        let mut mapped = [0u8; 16];
+        assert!(write_pod(&mut mapped, 0, &[0u32; 4]).is_ok());
+        assert!(write_pod(&mut mapped, 4, &[0u32; 4]).is_err());
+        assert!(write_pod(&mut mapped, u64::MAX, &[0u8]).is_err());
+        assert_eq!(mapped, [0u8; 16]);
+    }
+}
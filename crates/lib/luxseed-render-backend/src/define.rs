@@ -1,6 +1,7 @@
 use luxseed_utility::pool::Handle;
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 use smallvec::SmallVec;
+use std::hash::{Hash, Hasher};
 
 use crate::{enums::*, flag::*};
 
@@ -9,8 +10,25 @@ pub const ERR_MSG_QUEUE_NOT_FOUND: &str = "Queue not found.";
 
 pub const MAX_DESCRIPTORS_PER_SET: usize = 16;
 pub const MAX_RENDER_TARGETS: usize = 8;
+pub const MAX_SUBPASSES: usize = 4;
 pub const MAX_SHADER_STAGES: usize = 5;
 
+/// Depth formats in order of preference, used by `RenderBackend::best_depth_format`.
+pub const DEPTH_FORMAT_CANDIDATES: [Format; 3] =
+    [Format::D32_SFLOAT, Format::D32_SFLOAT_S8_UINT, Format::D24_UNORM_S8_UINT];
+/// Depth formats with a stencil aspect in order of preference.
+pub const DEPTH_STENCIL_FORMAT_CANDIDATES: [Format; 2] =
+    [Format::D32_SFLOAT_S8_UINT, Format::D24_UNORM_S8_UINT];
+
+/// Covers every mip level from `base_mip_level` to the end of the chain.
+pub const REMAINING_MIP_LEVELS: u32 = u32::MAX;
+/// Covers every array layer from `base_array_layer` to the last one.
+pub const REMAINING_ARRAY_LAYERS: u32 = u32::MAX;
+/// Covers a buffer from the offset to its end.
+pub const WHOLE_SIZE: u64 = u64::MAX;
+/// `SamplerCreateDesc::max_lod` that doesn't clamp, every mip level can be sampled.
+pub const LOD_CLAMP_NONE: f32 = 1000.0;
+
 #[derive(Clone)]
 pub struct AdapterInfo {
     pub api_version: u32,
@@ -21,12 +39,60 @@ pub struct AdapterInfo {
     pub device_name: String,
 }
 
-#[derive(Clone, Copy)]
+/// Receives the messages reported by the validation layers.
+pub type DebugCallback = Box<dyn Fn(Severity, MessageType, &str) + Send + Sync>;
+
 pub struct RenderBackendCreateDesc<'a> {
     pub enable_debugging: bool,
     pub app_name: &'a str,
     pub app_version: u32,
     pub raw_display_handle: RawDisplayHandle,
+    /// Called for every validation message instead of printing it. Only used when
+    /// `enable_debugging` is set, creation fails if the validation layer is missing then.
+    pub debug_callback: Option<DebugCallback>,
+    /// Out of bounds buffer accesses in shaders read zero and drop writes instead of being
+    /// undefined behavior. Only enabled if the adapter supports it, it costs some performance.
+    pub robust_buffer_access: bool,
+    /// Lowest Vulkan version as `(major, minor, patch)` the app needs, creation fails if the
+    /// loader only supports an older one. Versions below 1.2, which the backend needs, are
+    /// raised to it.
+    pub min_api_version: (u32, u32, u32),
+    /// Instance extensions enabled on top of the window system and debug ones, e.g.
+    /// `"VK_EXT_swapchain_colorspace"`. Creation fails if one of them is not available.
+    pub additional_instance_extensions: &'a [&'a str],
+}
+
+#[derive(Default)]
+pub struct DeviceCreateDesc<'a> {
+    pub adapter_index: usize,
+    /// Device extensions enabled on top of the swapchain one when the adapter supports them,
+    /// e.g. `"VK_KHR_ray_query"`.
+    pub extensions: &'a [&'a str],
+    /// Enabled when the adapter supports them, see `EnabledFeatures` for what was.
+    pub features: DeviceFeatures,
+    /// Enabled on top of robust buffer access, device creation fails if the adapter lacks one
+    /// of them. Core features that weren't requested stay disabled.
+    pub requested_features: RequestedFeatures,
+    /// Surface the present queue must support, the graphics queue is used when it can present
+    /// to it and another queue family otherwise. Without one the graphics queue is assumed to
+    /// present, which swapchain creation checks.
+    pub present_surface: Option<Handle<Surface>>,
+}
+
+/// What a device was actually created with out of a `DeviceCreateDesc`.
+#[derive(Debug, Default, Clone)]
+pub struct EnabledFeatures {
+    pub extensions: Vec<String>,
+    pub unavailable_extensions: Vec<String>,
+    pub features: DeviceFeatures,
+    pub unavailable_features: DeviceFeatures,
+    pub requested_features: RequestedFeatures,
+}
+
+impl EnabledFeatures {
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|e| e == name)
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -42,6 +108,9 @@ pub struct SwapchainCreateDesc {
     pub surface: Handle<Surface>,
     pub vsync: bool,
     pub format: Format,
+    /// Number of images to ask for, clamped to what the surface supports. Zero picks the
+    /// default of three. The driver may create more, see `get_swapchain_image_count`.
+    pub desired_image_count: u32,
 }
 
 pub const TEXTURE_DEFAULT_NAME: &str = "Texture_Default";
@@ -57,6 +126,9 @@ pub struct ImageCreateDesc<'a> {
     pub array_layers: u32,
     pub samples: SampleCount,
     pub initial_layout: ImageLayout,
+    /// Lets views use another format of the same size class, e.g. an sRGB view of a UNORM
+    /// image. Can cost performance on some hardware, so it is opt-in.
+    pub mutable_format: bool,
 }
 
 impl<'a> ImageCreateDesc<'a> {
@@ -72,6 +144,7 @@ impl<'a> ImageCreateDesc<'a> {
             array_layers: 1,
             samples: SampleCount::Sample1,
             initial_layout: ImageLayout::Undefined,
+            mutable_format: false,
         }
     }
 
@@ -87,8 +160,66 @@ impl<'a> ImageCreateDesc<'a> {
             array_layers: 1,
             samples: SampleCount::Sample1,
             initial_layout: ImageLayout::Undefined,
+            mutable_format: false,
         }
     }
+
+    /// Starts from a sampled 2D image with one mip and layer, like `new_2d`.
+    pub fn builder(name: &'a str, format: Format, extent: [u32; 3]) -> ImageCreateDescBuilder<'a> {
+        ImageCreateDescBuilder {
+            desc: Self { extent, ..Self::new_2d(name, format, extent[0], extent[1]) },
+        }
+    }
+}
+
+pub struct ImageCreateDescBuilder<'a> {
+    desc: ImageCreateDesc<'a>,
+}
+
+impl<'a> ImageCreateDescBuilder<'a> {
+    pub fn usage(mut self, usage: ImageUsageFlags) -> Self {
+        self.desc.usage = usage;
+        self
+    }
+
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.desc.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.desc.array_layers = array_layers;
+        self
+    }
+
+    pub fn samples(mut self, samples: SampleCount) -> Self {
+        self.desc.samples = samples;
+        self
+    }
+
+    pub fn tiling(mut self, tiling: ImageTiling) -> Self {
+        self.desc.tiling = tiling;
+        self
+    }
+
+    pub fn image_type(mut self, type_: ImageType) -> Self {
+        self.desc.type_ = type_;
+        self
+    }
+
+    pub fn initial_layout(mut self, initial_layout: ImageLayout) -> Self {
+        self.desc.initial_layout = initial_layout;
+        self
+    }
+
+    pub fn mutable_format(mut self, mutable_format: bool) -> Self {
+        self.desc.mutable_format = mutable_format;
+        self
+    }
+
+    pub fn build(self) -> ImageCreateDesc<'a> {
+        self.desc
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -125,6 +256,11 @@ impl ImageViewCreateDesc {
             component_a: TextureComponentSwizzle::Identity,
         }
     }
+
+    /// A 2D view of the depth aspect, used to sample a depth texture such as a shadow map.
+    pub fn new_depth() -> ImageViewCreateDesc {
+        Self::new_2d(None, ImageAspectFlags::DEPTH)
+    }
 }
 
 impl Default for ImageViewCreateDesc {
@@ -145,21 +281,64 @@ impl Default for ImageViewCreateDesc {
     }
 }
 
-#[derive(Clone, Copy, Default)]
-pub struct ClearColor {
-    pub value: [f32; 4],
+/// Clear value of a color attachment, the variant has to match how the attachment format is
+/// read: `Uint` for `*_UINT` formats, `Int` for `*_SINT` ones and `Float` for everything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearColor {
+    Float([f32; 4]),
+    Uint([u32; 4]),
+    Int([i32; 4]),
+}
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        Self::Float([0.0; 4])
+    }
 }
 
 impl ClearColor {
     pub fn new(value: [f32; 4]) -> Self {
-        Self { value }
+        Self::Float(value)
+    }
+
+    /// Linear values, written as is to UNORM attachments and encoded by the hardware for sRGB
+    /// ones.
+    pub fn linear(value: [f32; 4]) -> Self {
+        Self::Float(value)
+    }
+
+    /// An sRGB encoded color, e.g. picked in an image editor, converted to the linear values
+    /// the clear expects. Alpha is not gamma encoded and is only normalized.
+    pub fn from_srgb(value: [u8; 4]) -> Self {
+        let [r, g, b, a] = value;
+        Self::Float([srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a as f32 / 255.0])
+    }
+
+    /// Values for an unsigned integer attachment, e.g. an object id target.
+    pub fn uint(value: [u32; 4]) -> Self {
+        Self::Uint(value)
+    }
+
+    /// Values for a signed integer attachment.
+    pub fn int(value: [i32; 4]) -> Self {
+        Self::Int(value)
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
     }
 }
 
 #[derive(Clone, Copy, Default)]
 pub struct ClearDepthStencil {
     pub depth: f32,
-    pub stencil: u32,
+    /// Only written when the format has a stencil aspect and the stencil load action is clear.
+    pub stencil: u8,
 }
 
 pub struct ShaderModuleCreation<'a> {
@@ -224,6 +403,65 @@ impl Default for DepthState {
     }
 }
 
+impl DepthState {
+    /// No depth or stencil testing, for render passes without a depth stencil attachment.
+    pub fn disabled() -> Self {
+        Self { depth_test_enable: false, depth_write_enable: false, ..Default::default() }
+    }
+
+    /// Depth testing for a reverse-Z viewport, where closer surfaces have greater depth.
+    pub fn reverse_z() -> Self {
+        Self { depth_compare_mode: CompareOp::GreaterOrEqual, ..Default::default() }
+    }
+}
+
+/// A viewport rectangle and the depth range normalized device depth is mapped to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    /// Covers `width` x `height` from the origin with the standard 0..1 depth range.
+    pub fn new(width: f32, height: f32) -> Self {
+        Self { x: 0.0, y: 0.0, width, height, min_depth: 0.0, max_depth: 1.0 }
+    }
+
+    /// Maps the near plane to 1.0 and the far plane to 0.0. Distant surfaces then land where a
+    /// floating point depth format is most precise, which reduces z-fighting far from the
+    /// camera. Depth grows towards the camera, so pipelines need `DepthState::reverse_z` and the
+    /// depth attachment is cleared to 0.0.
+    pub fn reverse_z(self) -> Self {
+        Self { min_depth: 1.0, max_depth: 0.0, ..self }
+    }
+
+    #[inline]
+    pub fn is_reverse_z(&self) -> bool {
+        self.min_depth > self.max_depth
+    }
+}
+
+/// A rectangle in pixels, e.g. a scissor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect2D {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect2D {
+    /// Covers `width` x `height` from the origin.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { x: 0, y: 0, width, height }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct BlendState {
     pub source_color: BlendFactor,
@@ -235,6 +473,8 @@ pub struct BlendState {
     pub alpha_op: BlendOp,
 
     pub blend_enable: bool,
+    /// Channels written to the attachment, the others keep their value.
+    pub color_write_mask: ColorComponentFlags,
 }
 
 impl Default for BlendState {
@@ -249,6 +489,7 @@ impl Default for BlendState {
             alpha_op: BlendOp::Add,
 
             blend_enable: false,
+            color_write_mask: ColorComponentFlags::RGBA,
         }
     }
 }
@@ -257,6 +498,13 @@ pub struct RasterState {
     pub cull_mode: CullMode,
     pub front_face: FrontFace,
     pub fill_mode: PolygonMode,
+    pub topology: PrimitiveTopology,
+    /// The index `0xFFFF` or `0xFFFFFFFF` starts a new strip, only for strip topologies.
+    pub primitive_restart_enable: bool,
+    pub dynamic_states: DynamicStateFlags,
+    /// Viewports and scissors the pipeline uses, a geometry shader picks one per primitive with
+    /// `gl_ViewportIndex`. More than 1 needs `RequestedFeatures::MULTI_VIEWPORT`.
+    pub viewport_count: u32,
 }
 
 impl Default for RasterState {
@@ -265,12 +513,29 @@ impl Default for RasterState {
             cull_mode: CullMode::Back,
             front_face: FrontFace::Clockwise,
             fill_mode: PolygonMode::Fill,
+            topology: PrimitiveTopology::TriangleList,
+            primitive_restart_enable: false,
+            dynamic_states: DynamicStateFlags::empty(),
+            viewport_count: 1,
         }
     }
 }
 
+/// Bytes `offset..offset + size` of the push constant block, visible to `stage_flags`. Offset
+/// and size are multiples of 4, and a stage can only be in one range of a layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PushConstantRange {
+    pub stage_flags: ShaderStageFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
 pub struct PipelineLayoutCreateDesc<'a> {
+    /// The layout at position `i` is the one of `set = i` in the shaders.
     pub descriptor_set_layouts: &'a [Handle<DescriptorSetLayout>],
+    /// Small constants written with `cmd_push_constants`, at most
+    /// `maxPushConstantsSize` bytes in total, which is at least 128.
+    pub push_constant_ranges: &'a [PushConstantRange],
 }
 
 pub struct RasterPipelineCreateDesc<'a> {
@@ -278,15 +543,52 @@ pub struct RasterPipelineCreateDesc<'a> {
     pub raster_state: RasterState,
     pub depth_state: DepthState,
     pub blend_states: &'a [BlendState],
+    /// Replaces blending on every attachment, requires the `logicOp` device feature.
+    pub logic_op: Option<LogicOp>,
     pub shader_stages: &'a [Handle<Shader>],
     pub render_pass_output: RenderPassOutput,
+    /// Index of the subpass of `render_pass_output` the pipeline is used in.
+    pub subpass: u32,
+    pub pipeline_layout: Handle<PipelineLayout>,
+    /// Shared by every stage, a stage ignores the ids it doesn't declare.
+    pub specialization: &'a [SpecializationConstant<'a>],
+    /// Vertices per patch when `shader_stages` has tessellation shaders, 0 otherwise.
+    pub patch_control_points: u32,
+    /// Used with `cmd_begin_rendering` instead of a render pass, `render_pass_output` then only
+    /// gives the attachment formats and samples. Needs `DeviceFeatures::DYNAMIC_RENDERING`.
+    pub dynamic_rendering: bool,
+}
+
+pub struct ComputePipelineCreateDesc<'a> {
+    /// A shader module created with `ShaderStageFlags::COMPUTE`.
+    pub shader: Handle<Shader>,
     pub pipeline_layout: Handle<PipelineLayout>,
+    pub specialization: &'a [SpecializationConstant<'a>],
+}
+
+/// Value of a `constant_id` constant, fixed when the pipeline is created instead of compiling
+/// the shader again. `data` holds the constant's bytes, a `bool` takes 4 like `VkBool32`.
+#[derive(Clone, Copy)]
+pub struct SpecializationConstant<'a> {
+    pub id: u32,
+    pub data: &'a [u8],
+}
+
+/// Attachments of `cmd_begin_rendering`, which needs neither a render pass nor a framebuffer.
+/// The views have to be in the color and depth stencil attachment layouts already.
+#[derive(Clone, Copy)]
+pub struct RenderingInfo<'a> {
+    pub color_attachments: &'a [ColorAttachment],
+    pub depth_stencil_attachment: Option<DepthStencilAttachment>,
+    /// Render area, `None` takes it from the attachments like `FramebufferCreateDesc::extent`.
+    pub extent: Option<FramebufferExtent>,
 }
 
 #[derive(Clone, Copy)]
 pub struct ColorAttachment {
     pub view: Handle<ImageView>,
-    pub clear_value: Option<[f32; 4]>,
+    /// Needed when `load_op` is `Clear`.
+    pub clear_value: Option<ClearColor>,
     pub load_op: RenderTargetLoadAction,
     pub store_op: RenderTargetStoreAction,
 }
@@ -294,6 +596,8 @@ pub struct ColorAttachment {
 #[derive(Clone, Copy)]
 pub struct DepthStencilAttachment {
     pub view: Handle<ImageView>,
+    /// Needed when the matching load op is `Clear`, the stencil one only for formats with a
+    /// stencil aspect.
     pub depth_clear_value: Option<f32>,
     pub depth_load_op: RenderTargetLoadAction,
     pub depth_store_op: RenderTargetStoreAction,
@@ -302,24 +606,65 @@ pub struct DepthStencilAttachment {
     pub stencil_store_op: RenderTargetStoreAction,
 }
 
+/// Attachments used by one subpass, as bitmasks over the color attachment indices.
+#[derive(Default, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct SubpassDesc {
+    pub color_attachments: u8,
+    pub input_attachments: u8,
+    pub use_depth_stencil: bool,
+}
+
+impl SubpassDesc {
+    #[inline]
+    pub fn num_colors(&self) -> u8 {
+        self.color_attachments.count_ones() as u8
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct RenderPassOutput {
     pub num_colors: u8,
     pub color_formats: [Format; MAX_RENDER_TARGETS],
     pub color_final_layouts: [ImageLayout; MAX_RENDER_TARGETS],
     pub color_loads: [RenderTargetLoadAction; MAX_RENDER_TARGETS],
+    pub color_stores: [RenderTargetStoreAction; MAX_RENDER_TARGETS],
     pub color_samples: [SampleCount; MAX_RENDER_TARGETS],
     pub depth_stencil_format: Format,
     pub depth_stencil_final_layout: ImageLayout,
     pub depth_load: RenderTargetLoadAction,
     pub stencil_load: RenderTargetLoadAction,
+    /// Applies to the stencil too when the format has one.
+    pub depth_stencil_store: RenderTargetStoreAction,
     pub depth_stencil_samples: SampleCount,
+    /// Bit `i` set means color `i` is multisampled and resolved into its own attachment, placed
+    /// after the depth stencil in color order.
+    pub resolve_mask: u8,
+    pub resolve_formats: [Format; MAX_RENDER_TARGETS],
+    pub resolve_final_layouts: [ImageLayout; MAX_RENDER_TARGETS],
+    /// Zero means a single subpass writing every color attachment and the depth stencil.
+    pub num_subpasses: u8,
+    pub subpasses: [SubpassDesc; MAX_SUBPASSES],
 }
 
 impl RenderPassOutput {
     pub fn builder() -> RenderPassOutputBuilder {
         RenderPassOutputBuilder::default()
     }
+
+    /// Whether a subpass has a depth stencil attachment to test against.
+    pub fn subpass_has_depth_stencil(&self, subpass: u32) -> bool {
+        self.depth_stencil_format != Format::Unknown
+            && (self.num_subpasses == 0 || self.subpasses[subpass as usize].use_depth_stencil)
+    }
+
+    /// Number of color attachments written by a subpass.
+    pub fn subpass_num_colors(&self, subpass: u32) -> u8 {
+        if self.num_subpasses == 0 {
+            self.num_colors
+        } else {
+            self.subpasses[subpass as usize].num_colors()
+        }
+    }
 }
 
 #[derive(Default)]
@@ -327,12 +672,16 @@ pub struct RenderPassOutputBuilder {
     pub color_formats: Vec<Format>,
     pub color_final_layouts: Vec<ImageLayout>,
     pub color_loads: Vec<RenderTargetLoadAction>,
+    pub color_stores: Vec<RenderTargetStoreAction>,
     pub color_samples: Vec<SampleCount>,
     pub depth_stencil_format: Format,
     pub depth_stencil_final_layout: ImageLayout,
     pub depth_load: RenderTargetLoadAction,
     pub stencil_load: RenderTargetLoadAction,
+    pub depth_stencil_store: RenderTargetStoreAction,
     pub depth_stencil_samples: SampleCount,
+    pub resolve_targets: Vec<(u8, Format, ImageLayout)>,
+    pub subpasses: Vec<SubpassDesc>,
 }
 
 impl RenderPassOutputBuilder {
@@ -340,12 +689,16 @@ impl RenderPassOutputBuilder {
         self.color_formats.clear();
         self.color_final_layouts.clear();
         self.color_loads.clear();
+        self.color_stores.clear();
         self.color_samples.clear();
         self.depth_stencil_format = Format::Unknown;
         self.depth_stencil_final_layout = ImageLayout::Undefined;
         self.depth_load = RenderTargetLoadAction::DontCare;
         self.stencil_load = RenderTargetLoadAction::DontCare;
+        self.depth_stencil_store = RenderTargetStoreAction::Store;
         self.depth_stencil_samples = SampleCount::Sample1;
+        self.resolve_targets.clear();
+        self.subpasses.clear();
         self
     }
 
@@ -354,55 +707,131 @@ impl RenderPassOutputBuilder {
         color_formats: Format,
         color_final_layouts: ImageLayout,
         color_load: RenderTargetLoadAction,
+        color_store: RenderTargetStoreAction,
         color_samples: SampleCount,
     ) -> Self {
         self.color_formats.push(color_formats);
         self.color_final_layouts.push(color_final_layouts);
         self.color_loads.push(color_load);
+        self.color_stores.push(color_store);
         self.color_samples.push(color_samples);
         self
     }
 
+    /// A depth stencil nothing reads after the pass can be `DontCare`, which saves writing it
+    /// back to memory on tiled GPUs.
     pub fn set_depth_stencil(
         mut self,
         depth_stencil_format: Format,
         depth_stencil_final_layout: ImageLayout,
         depth_load: RenderTargetLoadAction,
         stencil_load: RenderTargetLoadAction,
+        depth_stencil_store: RenderTargetStoreAction,
         depth_stencil_samples: SampleCount,
     ) -> Self {
         self.depth_stencil_format = depth_stencil_format;
         self.depth_stencil_final_layout = depth_stencil_final_layout;
         self.depth_load = depth_load;
         self.stencil_load = stencil_load;
+        self.depth_stencil_store = depth_stencil_store;
         self.depth_stencil_samples = depth_stencil_samples;
         self
     }
 
+    /// Resolves the multisampled color attachment `color` into a single-sampled target at the
+    /// end of the last subpass writing it. The framebuffer passes the target in `resolve_views`.
+    pub fn add_resolve_target(
+        mut self,
+        color: u8,
+        resolve_format: Format,
+        resolve_final_layout: ImageLayout,
+    ) -> Self {
+        self.resolve_targets.push((color, resolve_format, resolve_final_layout));
+        self
+    }
+
+    /// Adds an explicit subpass. `colors` are written, `inputs` are read as input attachments,
+    /// both index the color attachments. Without any subpass, a single one uses everything.
+    ///
+    /// Panics when an index is not below `MAX_RENDER_TARGETS`. Indices of colors that weren't
+    /// added fail the render pass creation.
+    pub fn add_subpass(mut self, colors: &[u8], inputs: &[u8], use_depth_stencil: bool) -> Self {
+        let mask = |indices: &[u8]| {
+            indices.iter().fold(0u8, |mask, &i| {
+                assert!(
+                    (i as usize) < MAX_RENDER_TARGETS,
+                    "Subpass attachment {} is out of range, a render pass has at most {} colors.",
+                    i,
+                    MAX_RENDER_TARGETS
+                );
+                mask | (1 << i)
+            })
+        };
+        self.subpasses.push(SubpassDesc {
+            color_attachments: mask(colors),
+            input_attachments: mask(inputs),
+            use_depth_stencil,
+        });
+        self
+    }
+
+    /// Panics when there are more than `MAX_RENDER_TARGETS` colors or `MAX_SUBPASSES` subpasses.
     pub fn build(self) -> RenderPassOutput {
+        assert!(
+            self.color_formats.len() <= MAX_RENDER_TARGETS,
+            "{} color attachments added, a render pass has at most {}.",
+            self.color_formats.len(),
+            MAX_RENDER_TARGETS
+        );
+        assert!(
+            self.subpasses.len() <= MAX_SUBPASSES,
+            "{} subpasses added, a render pass has at most {}.",
+            self.subpasses.len(),
+            MAX_SUBPASSES
+        );
         let num_colors = self.color_formats.len() as u8;
         let mut color_formats = [Default::default(); MAX_RENDER_TARGETS];
         let mut color_final_layouts = [Default::default(); MAX_RENDER_TARGETS];
         let mut color_loads = [RenderTargetLoadAction::default(); MAX_RENDER_TARGETS];
+        let mut color_stores = [RenderTargetStoreAction::default(); MAX_RENDER_TARGETS];
         let mut color_samples = [Default::default(); MAX_RENDER_TARGETS];
         for i in 0..num_colors {
             color_formats[i as usize] = *self.color_formats.get(i as usize).unwrap();
             color_final_layouts[i as usize] = *self.color_final_layouts.get(i as usize).unwrap();
             color_loads[i as usize] = *self.color_loads.get(i as usize).unwrap();
+            color_stores[i as usize] = *self.color_stores.get(i as usize).unwrap();
             color_samples[i as usize] = *self.color_samples.get(i as usize).unwrap();
         }
+        let mut resolve_mask = 0;
+        let mut resolve_formats = [Default::default(); MAX_RENDER_TARGETS];
+        let mut resolve_final_layouts = [Default::default(); MAX_RENDER_TARGETS];
+        for (color, format, final_layout) in self.resolve_targets {
+            resolve_mask |= 1 << color;
+            resolve_formats[color as usize] = format;
+            resolve_final_layouts[color as usize] = final_layout;
+        }
+        let num_subpasses = self.subpasses.len() as u8;
+        let mut subpasses = [SubpassDesc::default(); MAX_SUBPASSES];
+        subpasses[..self.subpasses.len()].copy_from_slice(&self.subpasses);
 
         RenderPassOutput {
             num_colors,
             color_formats,
             color_final_layouts,
             color_loads,
+            color_stores,
             color_samples,
             depth_stencil_format: self.depth_stencil_format,
             depth_stencil_final_layout: self.depth_stencil_final_layout,
             depth_load: self.depth_load,
             stencil_load: self.stencil_load,
+            depth_stencil_store: self.depth_stencil_store,
             depth_stencil_samples: self.depth_stencil_samples,
+            resolve_mask,
+            resolve_formats,
+            resolve_final_layouts,
+            num_subpasses,
+            subpasses,
         }
     }
 }
@@ -412,13 +841,31 @@ pub struct FramebufferCreateDesc<'a> {
     pub render_pass: Handle<RenderPass>,
     pub color_views: &'a [Handle<ImageView>],
     pub depth_stencil_view: Option<Handle<ImageView>>,
+    /// Single-sampled targets of the render pass resolve attachments, in color order.
+    pub resolve_views: &'a [Handle<ImageView>],
+    /// Size of the framebuffer, `None` takes it from the attachments. Either way it is checked
+    /// against the mip level and layers each attachment view selects.
+    pub extent: Option<FramebufferExtent>,
+}
+
+/// Also the render area used by `cmd_begin_render_pass`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramebufferExtent {
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
 }
 
 pub struct QueueSubmitDesc<'a> {
     pub wait_semaphore: Option<&'a [Handle<Semaphore>]>,
+    /// Counter value to wait for on each of `wait_semaphore`, needed when one of them is a
+    /// timeline semaphore. The values of binary semaphores are ignored.
+    pub wait_values: Option<&'a [u64]>,
     pub wait_stage: Option<&'a [PipelineStageFlags]>,
     pub command_buffer: &'a [Handle<CommandBuffer>],
     pub finish_semaphore: Option<&'a [Handle<Semaphore>]>,
+    /// Counter value to signal on each of `finish_semaphore`, like `wait_values`.
+    pub signal_values: Option<&'a [u64]>,
     pub fence: Option<Handle<Fence>>,
 }
 
@@ -428,6 +875,8 @@ pub struct QueuePresentDesc<'a> {
     pub image_index: u32,
 }
 
+/// Bindings are numbered in order, attribute locations too, continuing from one binding to the
+/// next.
 pub struct VertexInputBinding<'a> {
     pub stride: usize,
     pub attributes: &'a [VertexInputAttribute],
@@ -447,14 +896,241 @@ pub struct BufferCreateDesc<'a> {
     pub initial_data: Option<&'a [u8]>,
 }
 
-#[derive(Default, Clone, Copy)]
+/// Geometry of an acceleration structure, read from buffers created with
+/// `BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT | SHADER_DEVICE_ADDRESS`.
+#[derive(Clone, Copy, Debug)]
+pub enum AccelerationStructureGeometry {
+    /// Triangles of a bottom level structure, indexed when `index_buffer` is set.
+    Triangles {
+        vertex_buffer: Handle<Buffer>,
+        vertex_offset: u64,
+        vertex_format: Format,
+        vertex_stride: u64,
+        /// Highest vertex index the triangles use.
+        max_vertex: u32,
+        index_buffer: Option<(Handle<Buffer>, u64, IndexType)>,
+        triangle_count: u32,
+        opaque: bool,
+    },
+    /// `AccelerationStructureInstance`s of a top level structure.
+    Instances { buffer: Handle<Buffer>, offset: u64, count: u32 },
+}
+
+impl AccelerationStructureGeometry {
+    pub fn primitive_count(&self) -> u32 {
+        match self {
+            Self::Triangles { triangle_count, .. } => *triangle_count,
+            Self::Instances { count, .. } => *count,
+        }
+    }
+}
+
+/// The storage is sized for `geometries`, which are kept to build the structure with
+/// `cmd_build_acceleration_structures`.
+pub struct AccelerationStructureCreateDesc<'a> {
+    pub name: &'a str,
+    pub ty: AccelerationStructureType,
+    pub geometries: &'a [AccelerationStructureGeometry],
+}
+
+/// One instance of a bottom level structure, laid out like `VkAccelerationStructureInstanceKHR`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccelerationStructureInstance {
+    /// Row-major 3x4 object to world transform.
+    pub transform: [f32; 12],
+    /// 24 bit custom index in the low bits, 8 bit visibility mask in the high bits.
+    pub custom_index_and_mask: u32,
+    /// 24 bit hit group offset in the low bits, 8 bit instance flags in the high bits.
+    pub hit_group_offset_and_flags: u32,
+    /// See `RenderBackend::get_acceleration_structure_device_address`.
+    pub acceleration_structure_address: u64,
+}
+
+impl AccelerationStructureInstance {
+    pub const IDENTITY: [f32; 12] = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+    pub fn new(transform: [f32; 12], acceleration_structure_address: u64) -> Self {
+        Self {
+            transform,
+            custom_index_and_mask: 0xFF << 24,
+            hit_group_offset_and_flags: 0,
+            acceleration_structure_address,
+        }
+    }
+
+    pub fn custom_index(mut self, index: u32) -> Self {
+        self.custom_index_and_mask =
+            (self.custom_index_and_mask & 0xFF00_0000) | (index & 0xFF_FFFF);
+        self
+    }
+
+    pub fn mask(mut self, mask: u8) -> Self {
+        self.custom_index_and_mask =
+            (self.custom_index_and_mask & 0xFF_FFFF) | ((mask as u32) << 24);
+        self
+    }
+
+    pub fn as_bytes(instances: &[Self]) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                instances.as_ptr() as *const u8,
+                std::mem::size_of_val(instances),
+            )
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BufferCopyRegion {
     pub src_offset: u64,
     pub dst_offset: u64,
     pub size: u64,
 }
 
-#[derive(Clone, Copy)]
+/// Color region resolved from a multisampled image into a single sampled one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageResolveRegion {
+    pub src_mip_level: u32,
+    pub dst_mip_level: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+    pub src_offset: [i32; 3],
+    pub dst_offset: [i32; 3],
+    pub extent: [u32; 3],
+}
+
+impl ImageResolveRegion {
+    /// The first mip level and layer of both images, `width` by `height` from the origin.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            src_mip_level: 0,
+            dst_mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+            src_offset: [0; 3],
+            dst_offset: [0; 3],
+            extent: [width, height, 1],
+        }
+    }
+}
+
+/// Mip level and array layers of one aspect of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageSubresourceLayers {
+    pub aspect_mask: ImageAspectFlags,
+    pub mip_level: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+}
+
+impl ImageSubresourceLayers {
+    /// The first layer of a color mip level.
+    pub fn color(mip_level: u32) -> Self {
+        Self {
+            aspect_mask: ImageAspectFlags::COLOR,
+            mip_level,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+}
+
+/// Mip levels and array layers of the aspects of an image, e.g. the part of it a clear writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageSubresourceRange {
+    pub aspect_mask: ImageAspectFlags,
+    pub base_mip_level: u32,
+    pub level_count: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+}
+
+impl ImageSubresourceRange {
+    /// All mip levels and array layers of the aspects.
+    pub fn whole(aspect_mask: ImageAspectFlags) -> Self {
+        Self {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: REMAINING_MIP_LEVELS,
+            base_array_layer: 0,
+            layer_count: REMAINING_ARRAY_LAYERS,
+        }
+    }
+}
+
+/// Box of the source scaled into a box of the destination, each box given by two opposite
+/// corners. Swapping the corners mirrors the blit along that axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageBlitRegion {
+    pub src_subresource: ImageSubresourceLayers,
+    pub src_offsets: [[i32; 3]; 2],
+    pub dst_subresource: ImageSubresourceLayers,
+    pub dst_offsets: [[i32; 3]; 2],
+}
+
+impl ImageBlitRegion {
+    /// The whole first color layer of a `src_size` image scaled to a `dst_size` one.
+    pub fn new(src_size: [u32; 2], dst_size: [u32; 2]) -> Self {
+        let corner = |size: [u32; 2]| [size[0] as i32, size[1] as i32, 1];
+        Self {
+            src_subresource: ImageSubresourceLayers::color(0),
+            src_offsets: [[0; 3], corner(src_size)],
+            dst_subresource: ImageSubresourceLayers::color(0),
+            dst_offsets: [[0; 3], corner(dst_size)],
+        }
+    }
+
+    /// Downsamples mip level `dst_mip_level - 1` of a `width` by `height` color image into
+    /// `dst_mip_level`, one step of generating a mip chain.
+    pub fn mip_level(width: u32, height: u32, dst_mip_level: u32) -> Self {
+        let corner =
+            |level: u32| [(width >> level).max(1) as i32, (height >> level).max(1) as i32, 1];
+        Self {
+            src_subresource: ImageSubresourceLayers::color(dst_mip_level - 1),
+            src_offsets: [[0; 3], corner(dst_mip_level - 1)],
+            dst_subresource: ImageSubresourceLayers::color(dst_mip_level),
+            dst_offsets: [[0; 3], corner(dst_mip_level)],
+        }
+    }
+}
+
+/// Arguments of `cmd_blit_image`, `src` is read in `src_image_layout` and `dst` written in
+/// `dst_image_layout`.
+pub struct BlitImageDesc<'a> {
+    pub src: Handle<Image>,
+    pub src_image_layout: ImageLayout,
+    pub dst: Handle<Image>,
+    pub dst_image_layout: ImageLayout,
+    pub regions: &'a [ImageBlitRegion],
+    pub filter: FilterType,
+}
+
+/// Texels copied as they are between two images, `extent` big at `src_offset` and
+/// `dst_offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageCopyRegion {
+    pub src_subresource: ImageSubresourceLayers,
+    pub src_offset: [i32; 3],
+    pub dst_subresource: ImageSubresourceLayers,
+    pub dst_offset: [i32; 3],
+    pub extent: [u32; 3],
+}
+
+impl ImageCopyRegion {
+    /// The first color layer of both images, `width` by `height` from the origin.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            src_subresource: ImageSubresourceLayers::color(0),
+            src_offset: [0; 3],
+            dst_subresource: ImageSubresourceLayers::color(0),
+            dst_offset: [0; 3],
+            extent: [width, height, 1],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BufferImageCopyRegion {
     pub buffer_offset: u64,
     pub buffer_row_length: u32,
@@ -467,6 +1143,47 @@ pub struct BufferImageCopyRegion {
     pub image_extent: [u32; 3],
 }
 
+impl BufferImageCopyRegion {
+    /// The first color layer, `width` by `height` from the origin, tightly packed at the start
+    /// of the buffer.
+    pub fn color(width: u32, height: u32) -> Self {
+        Self {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            aspect_mask: ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+            image_offset: [0; 3],
+            image_extent: [width, height, 1],
+        }
+    }
+
+    /// Every mip level of the first layer of a 2D color image, tightly packed one after the
+    /// other from the start of the buffer. Rows and heights of compressed formats are rounded up
+    /// to whole blocks.
+    pub fn color_mips(format: Format, width: u32, height: u32, mip_levels: u32) -> Vec<Self> {
+        let [block_width, block_height] = format.block_extent();
+        let mut buffer_offset = 0;
+        (0..mip_levels)
+            .map(|mip_level| {
+                let width = (width >> mip_level).max(1);
+                let height = (height >> mip_level).max(1);
+                let region = Self {
+                    buffer_offset,
+                    buffer_row_length: width.next_multiple_of(block_width),
+                    buffer_image_height: height.next_multiple_of(block_height),
+                    mip_level,
+                    ..Self::color(width, height)
+                };
+                buffer_offset += format.level_size(width, height);
+                region
+            })
+            .collect()
+    }
+}
+
 pub struct DescriptorPoolSize {
     pub descriptor_type: DescriptorType,
     pub descriptor_count: u32,
@@ -475,6 +1192,8 @@ pub struct DescriptorPoolSize {
 pub struct DescriptorPoolCreateDesc<'a> {
     pub max_sets: u32,
     pub pool_sizes: &'a [DescriptorPoolSize],
+    /// Needed for sets whose layout has `DescriptorBindingFlags::UPDATE_AFTER_BIND` bindings.
+    pub update_after_bind: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -485,14 +1204,17 @@ pub struct DescriptorSetLayoutBinding {
     pub stage_flags: ShaderStageFlags,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct DescriptorBindingInfo {
     pub index: u16,
     pub type_: DescriptorType,
+    /// Array size of the binding, e.g. a bindless table of textures.
     pub count: u16,
     pub stage_flags: ShaderStageFlags,
+    pub flags: DescriptorBindingFlags,
 }
 
+#[derive(Clone, Default)]
 pub struct DescriptorSetLayoutCreateDesc {
     pub bindings: SmallVec<[DescriptorBindingInfo; MAX_DESCRIPTORS_PER_SET]>,
 }
@@ -514,6 +1236,12 @@ pub struct DescriptorBindingData {
     pub buffer: Option<Handle<Buffer>>,
     pub sampler: Option<Handle<Sampler>>,
     pub image_view: Option<Handle<ImageView>>,
+    pub acceleration_structure: Option<Handle<AccelerationStructure>>,
+    pub buffer_view: Option<Handle<BufferView>>,
+    /// Bytes of `buffer` the descriptor covers from its start, `WHOLE_SIZE` for all of it.
+    pub buffer_range: u64,
+    /// Element of an array binding the descriptor is written to.
+    pub array_element: u32,
 }
 
 pub struct DescriptorSetCreateDesc {
@@ -529,14 +1257,56 @@ impl DescriptorSetCreateDesc {
 
     pub fn bind_image_view(mut self, binding: u16, image_view: Handle<ImageView>) -> Self {
         self.bindings.push(DescriptorBindingData {
-            binding: binding,
+            binding,
             buffer: None,
             sampler: None,
             image_view: Some(image_view),
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
         });
         self
     }
 
+    /// Binds a color attachment of the current render pass, read in `ShaderReadOnlyOptimal`.
+    pub fn bind_input_attachment(mut self, binding: u16, image_view: Handle<ImageView>) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
+            buffer: None,
+            sampler: None,
+            image_view: Some(image_view),
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds consecutive elements of an array binding from `first_index` on, one per view. Made
+    /// for bindless tables of `SampledImage` or `StorageImage` descriptors.
+    pub fn bind_image_view_array(
+        mut self,
+        binding: u16,
+        first_index: u32,
+        image_views: &[Handle<ImageView>],
+    ) -> Self {
+        for (i, image_view) in image_views.iter().enumerate() {
+            self.bindings.push(DescriptorBindingData {
+                binding,
+                buffer: None,
+                sampler: None,
+                image_view: Some(*image_view),
+                acceleration_structure: None,
+                buffer_view: None,
+                buffer_range: WHOLE_SIZE,
+                array_element: first_index + i as u32,
+            });
+        }
+        self
+    }
+
     pub fn bind_combined_image_sampler(
         mut self,
         binding: u16,
@@ -544,40 +1314,159 @@ impl DescriptorSetCreateDesc {
         sampler: Handle<Sampler>,
     ) -> Self {
         self.bindings.push(DescriptorBindingData {
-            binding: binding,
+            binding,
             buffer: None,
             sampler: Some(sampler),
             image_view: Some(image_view),
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds an image view for shader reads and writes, the image needs
+    /// `ImageUsageFlags::STORAGE` and has to be in `ImageLayout::General` when used.
+    pub fn bind_storage_image(mut self, binding: u16, image_view: Handle<ImageView>) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
+            buffer: None,
+            sampler: None,
+            image_view: Some(image_view),
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
         });
         self
     }
 
     pub fn bind_uniform_buffer(mut self, binding: u16, buffer: Handle<Buffer>) -> Self {
         self.bindings.push(DescriptorBindingData {
-            binding: binding,
+            binding,
+            buffer: Some(buffer),
+            sampler: None,
+            image_view: None,
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds the first `range` bytes of a uniform buffer to a `UniformBufferDynamic` binding,
+    /// the dynamic offset given to `cmd_bind_descriptor_sets` moves that window through the
+    /// buffer, e.g. to a different draw's uniforms.
+    pub fn bind_dynamic_uniform_buffer(
+        mut self,
+        binding: u16,
+        buffer: Handle<Buffer>,
+        range: u64,
+    ) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
+            buffer: Some(buffer),
+            sampler: None,
+            image_view: None,
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: range,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds the whole buffer, which needs `BufferUsageFlags::STORAGE_BUFFER`.
+    pub fn bind_storage_buffer(mut self, binding: u16, buffer: Handle<Buffer>) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
             buffer: Some(buffer),
             sampler: None,
             image_view: None,
+            acceleration_structure: None,
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds a texel buffer view, for either a uniform or a storage texel buffer binding.
+    pub fn bind_texel_buffer_view(mut self, binding: u16, buffer_view: Handle<BufferView>) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
+            buffer: None,
+            sampler: None,
+            image_view: None,
+            acceleration_structure: None,
+            buffer_view: Some(buffer_view),
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
+        });
+        self
+    }
+
+    /// Binds a top level acceleration structure for ray queries.
+    pub fn bind_acceleration_structure(
+        mut self,
+        binding: u16,
+        acceleration_structure: Handle<AccelerationStructure>,
+    ) -> Self {
+        self.bindings.push(DescriptorBindingData {
+            binding,
+            buffer: None,
+            sampler: None,
+            image_view: None,
+            acceleration_structure: Some(acceleration_structure),
+            buffer_view: None,
+            buffer_range: WHOLE_SIZE,
+            array_element: 0,
         });
         self
     }
 }
 
+/// Orders every memory access of the source stages before those of the destination stages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct MemoryBarrier {
-    pub src_queue_family_index: u32,
-    pub dst_queue_family_index: u32,
-    pub src_access_mask: AccessFlag,
-    pub dst_access_mask: AccessFlag,
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct BufferMemoryBarrier {
     pub buffer: Handle<Buffer>,
-    pub src_queue_family_index: u32,
-    pub dst_queue_family_index: u32,
-    pub src_access_mask: AccessFlag,
-    pub dst_access_mask: AccessFlag,
+    pub offset: u64,
+    /// Bytes from `offset`, `WHOLE_SIZE` to reach the end of the buffer.
+    pub size: u64,
+    pub src_queue_family_index: Option<u32>,
+    pub dst_queue_family_index: Option<u32>,
+    pub src_access_mask: AccessFlags,
+    pub dst_access_mask: AccessFlags,
+}
+
+impl BufferMemoryBarrier {
+    /// A barrier over the whole buffer.
+    pub fn whole_buffer(
+        buffer: Handle<Buffer>,
+        src_access_mask: AccessFlags,
+        dst_access_mask: AccessFlags,
+    ) -> Self {
+        Self {
+            buffer,
+            offset: 0,
+            size: WHOLE_SIZE,
+            src_queue_family_index: None,
+            dst_queue_family_index: None,
+            src_access_mask,
+            dst_access_mask,
+        }
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ImageMemoryBarrier {
     pub image: Handle<Image>,
     pub aspect_mask: ImageAspectFlags,
@@ -593,6 +1482,36 @@ pub struct ImageMemoryBarrier {
     pub dst_access_mask: AccessFlags,
 }
 
+impl ImageMemoryBarrier {
+    /// A barrier over all mip levels and array layers of the image.
+    pub fn whole_image(
+        image: Handle<Image>,
+        aspect_mask: ImageAspectFlags,
+        old_layout: ImageLayout,
+        new_layout: ImageLayout,
+        src_access_mask: AccessFlags,
+        dst_access_mask: AccessFlags,
+    ) -> Self {
+        Self {
+            image,
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: REMAINING_MIP_LEVELS,
+            base_array_layer: 0,
+            layer_count: REMAINING_ARRAY_LAYERS,
+            old_layout,
+            new_layout,
+            src_queue_family_index: None,
+            dst_queue_family_index: None,
+            src_access_mask,
+            dst_access_mask,
+        }
+    }
+}
+
+/// Samplers are deduplicated by their description, `create_sampler` hands out the existing
+/// sampler for a description it has seen, floats compare by their bits.
+#[derive(Clone, Copy, Debug)]
 pub struct SamplerCreateDesc {
     pub min_filter: FilterType,
     pub mag_filter: FilterType,
@@ -601,13 +1520,107 @@ pub struct SamplerCreateDesc {
     pub address_mode_v: SamplerAddressMode,
     pub address_mode_w: SamplerAddressMode,
     pub mip_lod_bias: f32,
+    /// Makes a comparison sampler, `sampler2DShadow` in GLSL. The image view it's bound with must
+    /// use the depth aspect, see `ImageViewCreateDesc::new_depth`.
     pub compare_op: Option<CompareOp>,
+    /// Opt-in, the device isn't created with anisotropic filtering unless
+    /// `RequestedFeatures::SAMPLER_ANISOTROPY` is in `DeviceCreateDesc::requested_features`, and
+    /// creating the sampler fails without it. Clamped to the adapter's limit.
     pub max_anisotropy: Option<f32>,
+    /// Read outside the image with `SamplerAddressMode::ClampToBorder`.
+    pub border_color: BorderColor,
+    /// Sampled mip levels are clamped to `min_lod..=max_lod`, `LOD_CLAMP_NONE` for the whole chain.
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// Addresses in texels instead of `0.0..1.0`, only for single mip images sampled without
+    /// mipmapping, anisotropy or compare and clamped at the edges.
+    pub unnormalized_coordinates: bool,
+}
+
+type SamplerKey = (
+    FilterType,
+    FilterType,
+    SamplerMipmapMode,
+    [SamplerAddressMode; 3],
+    u32,
+    Option<CompareOp>,
+    Option<u32>,
+    BorderColor,
+    [u32; 2],
+    bool,
+);
+
+impl SamplerCreateDesc {
+    fn key(&self) -> SamplerKey {
+        (
+            self.min_filter,
+            self.mag_filter,
+            self.mipmap_mode,
+            [self.address_mode_u, self.address_mode_v, self.address_mode_w],
+            self.mip_lod_bias.to_bits(),
+            self.compare_op,
+            self.max_anisotropy.map(f32::to_bits),
+            self.border_color,
+            [self.min_lod.to_bits(), self.max_lod.to_bits()],
+            self.unnormalized_coordinates,
+        )
+    }
+}
+
+impl PartialEq for SamplerCreateDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerCreateDesc {}
+
+impl Hash for SamplerCreateDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
 }
 
 #[derive(Default, Clone, Copy)]
 pub struct CommandBufferBeginDesc {
     pub one_time_submit: bool,
+    /// Set for secondary command buffers that are executed inside a render pass, they then
+    /// record draws for that subpass without beginning the render pass themselves.
+    pub render_pass_continue: Option<CommandBufferInheritance>,
+}
+
+/// The render pass a secondary command buffer continues.
+#[derive(Clone, Copy)]
+pub struct CommandBufferInheritance {
+    pub render_pass: Handle<RenderPass>,
+    pub subpass: u32,
+    /// The framebuffer it will run with if known, which can help the driver.
+    pub framebuffer: Option<Handle<Framebuffer>>,
+}
+
+/// Resources of one type that are still allocated.
+#[derive(Default, Clone, Debug)]
+pub struct LiveResources {
+    pub type_name: &'static str,
+    pub count: usize,
+    /// Debug names of the resources created with one.
+    pub names: Vec<String>,
+}
+
+/// Every resource type with allocated handles, see `RenderBackend::debug_report_live_resources`.
+#[derive(Default, Clone, Debug)]
+pub struct LiveResourceReport {
+    pub resources: Vec<LiveResources>,
+}
+
+impl LiveResourceReport {
+    pub fn count(&self, type_name: &str) -> usize {
+        self.resources.iter().find(|r| r.type_name == type_name).map_or(0, |r| r.count)
+    }
+
+    pub fn total(&self) -> usize {
+        self.resources.iter().map(|r| r.count).sum()
+    }
 }
 
 macro_rules! define_rhi_resources {
@@ -627,6 +1640,7 @@ define_rhi_resources!(
     Shader,
     PipelineLayout,
     RasterPipeline,
+    ComputePipeline,
     RenderPass,
     Framebuffer,
     CommandPool,
@@ -634,7 +1648,100 @@ define_rhi_resources!(
     Semaphore,
     Fence,
     Buffer,
+    BufferView,
     DescriptorSetLayout,
     DescriptorPool,
-    DescriptorSet
+    DescriptorSet,
+    AccelerationStructure
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use luxseed_utility::{impl_handle, pool::Pool};
+
+    fn assert_close(actual: ClearColor, expected: [f32; 4]) {
+        let ClearColor::Float(actual) = actual else { panic!("{actual:?} is not a float color") };
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn clear_color_from_srgb() {
+        assert_close(ClearColor::from_srgb([0, 0, 0, 0]), [0.0, 0.0, 0.0, 0.0]);
+        assert_close(ClearColor::from_srgb([255, 255, 255, 255]), [1.0, 1.0, 1.0, 1.0]);
+        // Mid gray is much darker in linear space, alpha stays linear
+        assert_close(ClearColor::from_srgb([128, 128, 128, 128]), [0.2158, 0.2158, 0.2158, 0.5020]);
+        // Below the linear segment threshold
+        assert_close(ClearColor::from_srgb([10, 0, 0, 255]), [0.003035, 0.0, 0.0, 1.0]);
+        assert_close(ClearColor::linear([0.5, 0.25, 0.1, 1.0]), [0.5, 0.25, 0.1, 1.0]);
+    }
+
+    #[test]
+    fn reverse_z_viewport() {
+        let viewport = Viewport::new(1600.0, 900.0);
+        assert!(!viewport.is_reverse_z());
+
+        let reversed = viewport.reverse_z();
+        assert!(reversed.is_reverse_z());
+        assert_eq!((reversed.min_depth, reversed.max_depth), (1.0, 0.0));
+        assert_eq!((reversed.width, reversed.height), (1600.0, 900.0));
+        assert_eq!(DepthState::reverse_z().depth_compare_mode, CompareOp::GreaterOrEqual);
+    }
+
+    #[derive(Default)]
+    struct TestBuffer {
+        handle: Option<Handle<Buffer>>,
+    }
+    impl_handle!(TestBuffer, Buffer, handle);
+
+    #[test]
+    fn handle_debug_output() {
+        assert_eq!(
+            format!("{:?}", Handle::<Buffer>::default()),
+            "Handle<Buffer>(index=65535, gen=65535)"
+        );
+
+        let mut pool = Pool::<TestBuffer>::with_size(1, Default::default);
+        let first = pool.malloc().0;
+        pool.set_name(first, "vertices");
+        assert_eq!(format!("{:?}", first), "Handle<Buffer>(index=0, gen=0)");
+        assert_eq!(pool.resolve_name(first), Some("vertices"));
+
+        pool.free(first);
+        assert_eq!(pool.resolve_name(first), None);
+        let second = pool.malloc().0;
+        assert_eq!(format!("{:?}", second), "Handle<Buffer>(index=0, gen=1)");
+        assert_eq!(pool.resolve_name(second), None);
+        assert_eq!(
+            format!("{:?}", Handle::<Image>::default()),
+            "Handle<Image>(index=65535, gen=65535)"
+        );
+    }
+
+    fn two_colors() -> RenderPassOutputBuilder {
+        let color = |b: RenderPassOutputBuilder| {
+            b.add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ShaderReadOnlyOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+        };
+        color(color(RenderPassOutput::builder()))
+    }
+
+    #[test]
+    #[should_panic(expected = "5 subpasses added, a render pass has at most 4.")]
+    fn too_many_subpasses() {
+        (0..=MAX_SUBPASSES).fold(two_colors(), |b, _| b.add_subpass(&[0], &[], false)).build();
+    }
+
+    #[test]
+    #[should_panic(expected = "Subpass attachment 8 is out of range")]
+    fn subpass_attachment_out_of_range() {
+        two_colors().add_subpass(&[8], &[], false);
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+
+use crate::{define::DeviceCreateDesc, RenderBackend};
+
+impl dyn RenderBackend + '_ {
+    /// Recreates the device, e.g. once `is_device_lost` reports a driver reset, then calls
+    /// `rebuild` to make the app's resources again. The handles made before are all invalid by
+    /// the time `rebuild` runs, surfaces excepted.
+    pub fn recover_device(
+        &mut self,
+        desc: &DeviceCreateDesc,
+        rebuild: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        self.recreate_device(desc).context("Failed to recreate the device.")?;
+        rebuild(self).context("Failed to rebuild resources on the recreated device.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        define::BufferCreateDesc,
+        enums::MemoryLocation,
+        flag::BufferUsageFlags,
+        testing::{Call, RecordingBackend},
+    };
+
+    fn create_buffer(backend: &mut dyn RenderBackend) -> Result<()> {
+        backend.create_buffer(&BufferCreateDesc {
+            name: "Rebuilt",
+            size: 64,
+            usage: BufferUsageFlags::UNIFORM_BUFFER,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        })?;
+        Ok(())
+    }
+
+    #[test]
+    fn rebuild_runs_on_the_new_device() {
+        let mut recording = RecordingBackend::default();
+        let backend: &mut dyn RenderBackend = &mut recording;
+        create_buffer(backend).unwrap();
+        for _ in 0..3 {
+            backend.recover_device(&DeviceCreateDesc::default(), |b| create_buffer(b)).unwrap();
+        }
+        // Each cycle drops the buffers of the old device and rebuilds one on the new device
+        assert_eq!(recording.live_buffers(), 1);
+        let cycles: Vec<_> = recording.calls()[1..]
+            .chunks(3)
+            .map(|cycle| {
+                matches!(
+                    cycle,
+                    [Call::DestroyDevice, Call::CreateDevice, Call::CreateBuffer { .. }]
+                )
+            })
+            .collect();
+        assert_eq!(cycles, [true; 3]);
+
+        let mut recording = RecordingBackend::default();
+        recording.fail_device_creation = true;
+        let mut rebuilt = false;
+        let backend: &mut dyn RenderBackend = &mut recording;
+        let result = backend.recover_device(&DeviceCreateDesc::default(), |_| {
+            rebuilt = true;
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert!(!rebuilt);
+        assert_eq!(*recording.calls(), vec![Call::DestroyDevice, Call::CreateDevice]);
+    }
+}
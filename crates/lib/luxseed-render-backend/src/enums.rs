@@ -1,5 +1,7 @@
 use core::fmt;
 
+use crate::flag::ImageAspectFlags;
+
 #[derive(Default, Clone, Copy)]
 pub enum BackendType {
     #[default]
@@ -18,7 +20,7 @@ pub enum AdapterType {
     CPU,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum QueueType {
     Graphics,
     Compute,
@@ -37,6 +39,31 @@ impl fmt::Display for QueueType {
     }
 }
 
+/// How a wait on fences ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenceWaitResult {
+    Signaled,
+    /// The timeout elapsed before the fences were signaled.
+    TimedOut,
+    /// The device was lost while waiting, every resource made from it has to be recreated.
+    DeviceLost,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    General,
+    Validation,
+    Performance,
+}
+
 #[derive(Default, Clone, Copy, Debug)]
 pub enum ImageType {
     Texture1D,
@@ -76,7 +103,7 @@ pub enum TextureComponentSwizzle {
     A,
 }
 
-#[derive(Default, Clone, Copy, Debug, Hash)]
+#[derive(Default, Clone, Copy, Debug, Hash, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum Format {
     #[default]
@@ -84,6 +111,8 @@ pub enum Format {
     D32_SFLOAT,
     D32_SFLOAT_S8_UINT,
     D24_UNORM_S8_UINT,
+    /// Stencil only.
+    S8_UINT,
     B8G8R8A8_UNORM,
     B8G8R8A8_SRGB,
     R8G8B8A8_UNORM,
@@ -96,9 +125,168 @@ pub enum Format {
     R32G32_SFLOAT,
     R32G32B32_SFLOAT,
     R32G32B32A32_SFLOAT,
+    R8_UINT,
+    R16_UINT,
+    R32_UINT,
+    R32G32_UINT,
+    R32G32B32A32_UINT,
+    R32_SINT,
+    R32G32B32A32_SINT,
+    R16_SFLOAT,
+    R16G16_SFLOAT,
+    R16G16B16A16_SFLOAT,
+    /// Packed into 32 bits, `VK_FORMAT_A2B10G10R10_UNORM_PACK32`.
+    A2B10G10R10_UNORM,
+    /// Packed into 32 bits, `VK_FORMAT_B10G11R11_UFLOAT_PACK32`.
+    B10G11R11_UFLOAT,
+    /// Block compressed, 4x4 texels per block. Sampled only, they can't be attachments or
+    /// storage images.
+    BC1_RGB_UNORM,
+    BC1_RGB_SRGB,
+    BC1_RGBA_UNORM,
+    BC1_RGBA_SRGB,
+    BC2_UNORM,
+    BC2_SRGB,
+    BC3_UNORM,
+    BC3_SRGB,
+    BC4_UNORM,
+    BC4_SNORM,
+    BC5_UNORM,
+    BC5_SNORM,
+    BC6H_UFLOAT,
+    BC6H_SFLOAT,
+    BC7_UNORM,
+    BC7_SRGB,
+}
+
+impl Format {
+    /// Aspects an image of this format has, empty for `Unknown`.
+    pub fn aspect_mask(self) -> ImageAspectFlags {
+        match self {
+            Format::Unknown => ImageAspectFlags::empty(),
+            Format::D32_SFLOAT => ImageAspectFlags::DEPTH,
+            Format::D32_SFLOAT_S8_UINT | Format::D24_UNORM_S8_UINT => {
+                ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+            }
+            Format::S8_UINT => ImageAspectFlags::STENCIL,
+            _ => ImageAspectFlags::COLOR,
+        }
+    }
+
+    /// Size of a texel in bytes, of a 4x4 block for the compressed formats.
+    pub fn bytes_per_texel(self) -> u32 {
+        match self {
+            Format::Unknown => 0,
+            Format::S8_UINT | Format::R8_UINT => 1,
+            Format::R16_UINT | Format::R16_SFLOAT => 2,
+            Format::B8G8R8_UNORM
+            | Format::B8G8R8_SRGB
+            | Format::R8G8B8_UNORM
+            | Format::R8G8B8_SRGB => 3,
+            Format::D32_SFLOAT
+            | Format::D24_UNORM_S8_UINT
+            | Format::B8G8R8A8_UNORM
+            | Format::B8G8R8A8_SRGB
+            | Format::R8G8B8A8_UNORM
+            | Format::R8G8B8A8_SRGB
+            | Format::R32_SFLOAT
+            | Format::R32_UINT
+            | Format::R32_SINT
+            | Format::R16G16_SFLOAT
+            | Format::A2B10G10R10_UNORM
+            | Format::B10G11R11_UFLOAT => 4,
+            Format::D32_SFLOAT_S8_UINT => 5,
+            Format::R32G32_SFLOAT | Format::R32G32_UINT | Format::R16G16B16A16_SFLOAT => 8,
+            Format::R32G32B32_SFLOAT => 12,
+            Format::R32G32B32A32_SFLOAT | Format::R32G32B32A32_UINT | Format::R32G32B32A32_SINT => {
+                16
+            }
+            Format::BC1_RGB_UNORM
+            | Format::BC1_RGB_SRGB
+            | Format::BC1_RGBA_UNORM
+            | Format::BC1_RGBA_SRGB
+            | Format::BC4_UNORM
+            | Format::BC4_SNORM => 8,
+            Format::BC2_UNORM
+            | Format::BC2_SRGB
+            | Format::BC3_UNORM
+            | Format::BC3_SRGB
+            | Format::BC5_UNORM
+            | Format::BC5_SNORM
+            | Format::BC6H_UFLOAT
+            | Format::BC6H_SFLOAT
+            | Format::BC7_UNORM
+            | Format::BC7_SRGB => 16,
+        }
+    }
+
+    /// The BC1 to BC7 block compressed formats.
+    pub fn is_compressed(self) -> bool {
+        matches!(
+            self,
+            Format::BC1_RGB_UNORM
+                | Format::BC1_RGB_SRGB
+                | Format::BC1_RGBA_UNORM
+                | Format::BC1_RGBA_SRGB
+                | Format::BC2_UNORM
+                | Format::BC2_SRGB
+                | Format::BC3_UNORM
+                | Format::BC3_SRGB
+                | Format::BC4_UNORM
+                | Format::BC4_SNORM
+                | Format::BC5_UNORM
+                | Format::BC5_SNORM
+                | Format::BC6H_UFLOAT
+                | Format::BC6H_SFLOAT
+                | Format::BC7_UNORM
+                | Format::BC7_SRGB
+        )
+    }
+
+    /// Width and height in texels of what `bytes_per_texel` measures.
+    pub fn block_extent(self) -> [u32; 2] {
+        if self.is_compressed() {
+            [4, 4]
+        } else {
+            [1, 1]
+        }
+    }
+
+    /// Bytes of a tightly packed `width` by `height` image, compressed ones are made of whole
+    /// blocks even where the image ends inside one.
+    pub fn level_size(self, width: u32, height: u32) -> u64 {
+        let [block_width, block_height] = self.block_extent();
+        width.div_ceil(block_width) as u64
+            * height.div_ceil(block_height) as u64
+            * self.bytes_per_texel() as u64
+    }
+
+    pub fn is_depth(self) -> bool {
+        self.aspect_mask().contains(ImageAspectFlags::DEPTH)
+    }
+
+    pub fn is_stencil(self) -> bool {
+        self.aspect_mask().contains(ImageAspectFlags::STENCIL)
+    }
+
+    /// Whether reads and writes convert between sRGB and linear.
+    pub fn is_srgb(self) -> bool {
+        matches!(
+            self,
+            Format::B8G8R8A8_SRGB
+                | Format::R8G8B8A8_SRGB
+                | Format::B8G8R8_SRGB
+                | Format::R8G8B8_SRGB
+                | Format::BC1_RGB_SRGB
+                | Format::BC1_RGBA_SRGB
+                | Format::BC2_SRGB
+                | Format::BC3_SRGB
+                | Format::BC7_SRGB
+        )
+    }
 }
 
-#[derive(Default, Clone, Copy, Debug, Hash)]
+#[derive(Default, Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum ImageLayout {
     #[default]
     Undefined,
@@ -106,6 +294,11 @@ pub enum ImageLayout {
     ColorAttachmentOptimal,
     DepthStencilAttachmentOptimal,
     DepthStencilReadOnlyOptimal,
+    /// Per-aspect layouts, they need the separate depth stencil layouts feature.
+    DepthAttachmentOptimal,
+    DepthReadOnlyOptimal,
+    StencilAttachmentOptimal,
+    StencilReadOnlyOptimal,
     ShaderReadOnlyOptimal,
     TransferSrcOptimal,
     TransferDstOptimal,
@@ -146,6 +339,26 @@ pub enum BlendOp {
 }
 
 #[derive(Clone, Copy)]
+pub enum LogicOp {
+    Clear,
+    And,
+    AndReverse,
+    Copy,
+    AndInverted,
+    NoOp,
+    Xor,
+    Or,
+    Nor,
+    Equivalent,
+    Invert,
+    OrReverse,
+    CopyInverted,
+    OrInverted,
+    Nand,
+    Set,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CompareOp {
     Never,
     Less,
@@ -183,6 +396,25 @@ pub enum FrontFace {
     Clockwise,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimitiveTopology {
+    PointList,
+    LineList,
+    LineStrip,
+    TriangleList,
+    TriangleStrip,
+    /// Patches of `RasterPipelineCreateDesc::patch_control_points` vertices, the only topology
+    /// tessellation shaders take.
+    PatchList,
+}
+
+impl PrimitiveTopology {
+    /// Strips can be cut with the restart index, lists can't.
+    pub fn is_strip(&self) -> bool {
+        matches!(self, PrimitiveTopology::LineStrip | PrimitiveTopology::TriangleStrip)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum PolygonMode {
     Line,
@@ -217,13 +449,23 @@ pub enum RenderTargetStoreAction {
     DontCare,
 }
 
-#[derive(Default, Clone, Copy, Hash)]
+#[derive(Default, Clone, Copy, Hash, Debug, PartialEq, Eq)]
 pub enum CommandBufferLevel {
     #[default]
     Primary,
     Secondary,
 }
 
+/// How the commands of a subpass are provided.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubpassContents {
+    /// Commands are recorded directly into the primary command buffer.
+    #[default]
+    Inline,
+    /// Commands come from secondary command buffers executed inside the render pass.
+    SecondaryCommandBuffers,
+}
+
 #[derive(Clone, Copy)]
 pub enum VertexInputRate {
     Vertex,
@@ -238,13 +480,26 @@ pub enum MemoryLocation {
     GpuToCpu,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndexType {
+    /// Needs `DeviceFeatures::INDEX_TYPE_UINT8`.
+    U8,
     U16,
     U32,
 }
 
-#[derive(Clone, Copy)]
+impl IndexType {
+    /// Size of one index in bytes.
+    pub fn size(self) -> u64 {
+        match self {
+            IndexType::U8 => 1,
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DescriptorType {
     Sampler,
     CombinedImageSampler,
@@ -257,9 +512,19 @@ pub enum DescriptorType {
     UniformBufferDynamic,
     StorageBufferDynamic,
     InputAttachment,
+    /// A top level acceleration structure, needs `DeviceFeatures::RAY_QUERY`.
+    AccelerationStructure,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccelerationStructureType {
+    /// Holds geometry.
+    BottomLevel,
+    /// Holds instances of bottom level structures.
+    TopLevel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PipelineBindPoint {
     Graphics,
     Compute,
@@ -285,7 +550,7 @@ pub enum AccessFlag {
     MemoryWrite,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SamplerAddressMode {
     Repeat,
     MirroredRepeat,
@@ -294,14 +559,96 @@ pub enum SamplerAddressMode {
     MirrorClampToEdge,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BorderColor {
+    FloatTransparentBlack,
+    IntTransparentBlack,
+    FloatOpaqueBlack,
+    IntOpaqueBlack,
+    FloatOpaqueWhite,
+    IntOpaqueWhite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SamplerMipmapMode {
     Nearest,
     Linear,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum FilterType {
     Nearest,
     Linear,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_introspection() {
+        const DEPTH_STENCIL: ImageAspectFlags =
+            ImageAspectFlags::DEPTH.union(ImageAspectFlags::STENCIL);
+        // Every variant, add a row along with a new format
+        let table = [
+            (Format::Unknown, ImageAspectFlags::empty(), 0, false, false),
+            (Format::D32_SFLOAT, ImageAspectFlags::DEPTH, 4, false, false),
+            (Format::D32_SFLOAT_S8_UINT, DEPTH_STENCIL, 5, false, false),
+            (Format::D24_UNORM_S8_UINT, DEPTH_STENCIL, 4, false, false),
+            (Format::S8_UINT, ImageAspectFlags::STENCIL, 1, false, false),
+            (Format::B8G8R8A8_UNORM, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::B8G8R8A8_SRGB, ImageAspectFlags::COLOR, 4, true, false),
+            (Format::R8G8B8A8_UNORM, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::R8G8B8A8_SRGB, ImageAspectFlags::COLOR, 4, true, false),
+            (Format::B8G8R8_UNORM, ImageAspectFlags::COLOR, 3, false, false),
+            (Format::B8G8R8_SRGB, ImageAspectFlags::COLOR, 3, true, false),
+            (Format::R8G8B8_UNORM, ImageAspectFlags::COLOR, 3, false, false),
+            (Format::R8G8B8_SRGB, ImageAspectFlags::COLOR, 3, true, false),
+            (Format::R32_SFLOAT, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::R32G32_SFLOAT, ImageAspectFlags::COLOR, 8, false, false),
+            (Format::R32G32B32_SFLOAT, ImageAspectFlags::COLOR, 12, false, false),
+            (Format::R32G32B32A32_SFLOAT, ImageAspectFlags::COLOR, 16, false, false),
+            (Format::R8_UINT, ImageAspectFlags::COLOR, 1, false, false),
+            (Format::R16_UINT, ImageAspectFlags::COLOR, 2, false, false),
+            (Format::R32_UINT, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::R32G32_UINT, ImageAspectFlags::COLOR, 8, false, false),
+            (Format::R32G32B32A32_UINT, ImageAspectFlags::COLOR, 16, false, false),
+            (Format::R32_SINT, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::R32G32B32A32_SINT, ImageAspectFlags::COLOR, 16, false, false),
+            (Format::R16_SFLOAT, ImageAspectFlags::COLOR, 2, false, false),
+            (Format::R16G16_SFLOAT, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::R16G16B16A16_SFLOAT, ImageAspectFlags::COLOR, 8, false, false),
+            (Format::A2B10G10R10_UNORM, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::B10G11R11_UFLOAT, ImageAspectFlags::COLOR, 4, false, false),
+            (Format::BC1_RGB_UNORM, ImageAspectFlags::COLOR, 8, false, true),
+            (Format::BC1_RGB_SRGB, ImageAspectFlags::COLOR, 8, true, true),
+            (Format::BC1_RGBA_UNORM, ImageAspectFlags::COLOR, 8, false, true),
+            (Format::BC1_RGBA_SRGB, ImageAspectFlags::COLOR, 8, true, true),
+            (Format::BC2_UNORM, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC2_SRGB, ImageAspectFlags::COLOR, 16, true, true),
+            (Format::BC3_UNORM, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC3_SRGB, ImageAspectFlags::COLOR, 16, true, true),
+            (Format::BC4_UNORM, ImageAspectFlags::COLOR, 8, false, true),
+            (Format::BC4_SNORM, ImageAspectFlags::COLOR, 8, false, true),
+            (Format::BC5_UNORM, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC5_SNORM, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC6H_UFLOAT, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC6H_SFLOAT, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC7_UNORM, ImageAspectFlags::COLOR, 16, false, true),
+            (Format::BC7_SRGB, ImageAspectFlags::COLOR, 16, true, true),
+        ];
+        for (format, aspects, bytes, srgb, compressed) in table {
+            assert_eq!(format.aspect_mask(), aspects, "{:?}", format);
+            assert_eq!(format.bytes_per_texel(), bytes, "{:?}", format);
+            assert_eq!(format.is_srgb(), srgb, "{:?}", format);
+            assert_eq!(format.is_depth(), aspects.contains(ImageAspectFlags::DEPTH));
+            assert_eq!(format.is_stencil(), aspects.contains(ImageAspectFlags::STENCIL));
+            assert_eq!(format.is_compressed(), compressed, "{:?}", format);
+        }
+
+        // A 5x3 image is two by one blocks
+        assert_eq!(Format::BC7_SRGB.level_size(5, 3), 32);
+        assert_eq!(Format::BC1_RGB_UNORM.level_size(1, 1), 8);
+        assert_eq!(Format::R16G16B16A16_SFLOAT.level_size(5, 3), 120);
+    }
+}
@@ -15,7 +15,7 @@ bitflags! {
 }
 
 bitflags! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct BufferUsageFlags : u32 {
         const TRANSFER_SRC = 0b1;
         const TRANSFER_DST = 0b10;
@@ -26,6 +26,10 @@ bitflags! {
         const INDEX_BUFFER = 0b100_0000;
         const VERTEX_BUFFER = 0b1000_0000;
         const INDIRECT_BUFFER = 0b1_0000_0000;
+        const SHADER_DEVICE_ADDRESS = 0b10_0000_0000;
+        /// Vertex, index and instance data read by acceleration structure builds.
+        const ACCELERATION_STRUCTURE_BUILD_INPUT = 0b100_0000_0000;
+        const ACCELERATION_STRUCTURE_STORAGE = 0b1000_0000_0000;
     }
 }
 
@@ -63,6 +67,7 @@ bitflags! {
         const HOST = 0b100_0000_0000_0000;
         const ALL_GRAPHICS = 0b1000_0000_0000_0000;
         const ALL_COMMANDS = 0b1_0000_0000_0000_0000;
+        const ACCELERATION_STRUCTURE_BUILD = 0b10_0000_0000_0000_0000;
     }
 }
 
@@ -75,6 +80,17 @@ bitflags! {
     }
 }
 
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct ColorComponentFlags : u32 {
+        const R = 0b1;
+        const G = 0b10;
+        const B = 0b100;
+        const A = 0b1000;
+        const RGBA = 0b1111;
+    }
+}
+
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
     pub struct AccessFlags : u32 {
@@ -95,6 +111,8 @@ bitflags! {
         const HOST_WRITE = 0b100_0000_0000_0000;
         const MEMORY_READ = 0b1000_0000_0000_0000;
         const MEMORY_WRITE = 0b1_0000_0000_0000_0000;
+        const ACCELERATION_STRUCTURE_READ = 0b10_0000_0000_0000_0000;
+        const ACCELERATION_STRUCTURE_WRITE = 0b100_0000_0000_0000_0000;
     }
 }
 
@@ -116,3 +134,68 @@ bitflags! {
         const SAMPLED_IMAGE_FILTER_LINEAR = 0b1_0000_0000_0000;
     }
 }
+
+bitflags! {
+    /// Optional device features, each one also needs its extension on devices older than the
+    /// Vulkan version it became core in.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct DeviceFeatures : u32 {
+        const MULTIVIEW = 0b1;
+        const DESCRIPTOR_INDEXING = 0b10;
+        const TIMELINE_SEMAPHORE = 0b100;
+        const SYNCHRONIZATION_2 = 0b1000;
+        const DYNAMIC_RENDERING = 0b1_0000;
+        const EXTENDED_DYNAMIC_STATE = 0b10_0000;
+        const INDEX_TYPE_UINT8 = 0b100_0000;
+        /// Acceleration structures and ray queries from shaders.
+        const RAY_QUERY = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// Need `DeviceFeatures::DESCRIPTOR_INDEXING`.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct DescriptorBindingFlags : u32 {
+        /// Descriptors can be rewritten after the set is bound, the set has to come from a
+        /// `DescriptorPoolCreateDesc::update_after_bind` pool.
+        const UPDATE_AFTER_BIND = 0b1;
+        const UPDATE_UNUSED_WHILE_PENDING = 0b10;
+        /// Descriptors the shaders never access can be left unwritten.
+        const PARTIALLY_BOUND = 0b100;
+    }
+}
+
+bitflags! {
+    /// Pipeline state set while recording instead of baked into the pipeline, viewport and
+    /// scissor are always dynamic.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct DynamicStateFlags : u32 {
+        /// Set with `cmd_set_depth_bias`, also turns depth bias on.
+        const DEPTH_BIAS = 0b1;
+        /// Set with `cmd_set_line_width`.
+        const LINE_WIDTH = 0b10;
+        /// Set with `cmd_set_blend_constants`.
+        const BLEND_CONSTANTS = 0b100;
+    }
+}
+
+bitflags! {
+    /// Core Vulkan 1.0 features, enabled only when requested and required to be supported.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct RequestedFeatures : u32 {
+        /// `SamplerCreateDesc::max_anisotropy`, never enabled unless requested.
+        const SAMPLER_ANISOTROPY = 0b1;
+        /// `PolygonMode::Line` and `PolygonMode::Point`.
+        const FILL_MODE_NON_SOLID = 0b10;
+        const WIDE_LINES = 0b100;
+        const DEPTH_CLAMP = 0b1000;
+        const GEOMETRY_SHADER = 0b1_0000;
+        const TESSELLATION_SHADER = 0b10_0000;
+        const INDEPENDENT_BLEND = 0b100_0000;
+        /// `RasterPipelineCreateDesc::logic_op`.
+        const LOGIC_OP = 0b1000_0000;
+        const MULTI_DRAW_INDIRECT = 0b1_0000_0000;
+        /// `RasterState::viewport_count` above 1, and viewports or scissors past index 0.
+        const MULTI_VIEWPORT = 0b10_0000_0000;
+    }
+}
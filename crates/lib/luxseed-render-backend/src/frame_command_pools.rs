@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, RenderBackend};
+
+/// Command buffers allocated from one pool, handed out again once the pool is reset.
+#[derive(Default)]
+struct RecycledCommandBuffers {
+    buffers: Vec<Handle<CommandBuffer>>,
+    used: usize,
+}
+
+impl RecycledCommandBuffers {
+    fn next(
+        &mut self,
+        create: impl FnOnce() -> Result<Handle<CommandBuffer>>,
+    ) -> Result<Handle<CommandBuffer>> {
+        if self.used == self.buffers.len() {
+            self.buffers.push(create()?);
+        }
+        let cb = self.buffers[self.used];
+        self.used += 1;
+        Ok(cb)
+    }
+
+    fn recycle(&mut self) {
+        self.used = 0;
+    }
+}
+
+#[derive(Default)]
+struct FrameCommandPool {
+    pool: Handle<CommandPool>,
+    primary: RecycledCommandBuffers,
+    secondary: RecycledCommandBuffers,
+}
+
+/// One command pool per frame in flight, reset as a whole when its frame begins instead of
+/// resetting each command buffer.
+///
+/// Command buffers returned by `allocate` belong to the current frame and are handed out again
+/// after the next reset of its pool, so they must not be kept across frames.
+pub struct FrameCommandPools {
+    frames: Vec<FrameCommandPool>,
+    frame: usize,
+}
+
+impl FrameCommandPools {
+    pub fn new(
+        backend: &mut dyn RenderBackend,
+        queue: Handle<Queue>,
+        frames_in_flight: usize,
+    ) -> Result<Self> {
+        let mut frames = Vec::with_capacity(frames_in_flight);
+        for _ in 0..frames_in_flight {
+            frames.push(FrameCommandPool {
+                pool: backend.create_command_pool(queue)?,
+                ..Default::default()
+            });
+        }
+        Ok(Self { frames, frame: 0 })
+    }
+
+    /// Makes `frame` the current one and resets its pool. The GPU must be done with the command
+    /// buffers last submitted for it, e.g. by waiting on the frame's fence first.
+    pub fn begin_frame(&mut self, backend: &dyn RenderBackend, frame: usize) -> Result<()> {
+        let current = self.frames.get_mut(frame).context("Frame out of range.")?;
+        backend.reset_command_pool(current.pool)?;
+        current.primary.recycle();
+        current.secondary.recycle();
+        self.frame = frame;
+        Ok(())
+    }
+
+    /// A command buffer from the current frame's pool, in the initial state.
+    pub fn allocate(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        level: CommandBufferLevel,
+    ) -> Result<Handle<CommandBuffer>> {
+        let current = &mut self.frames[self.frame];
+        let pool = current.pool;
+        let buffers = match level {
+            CommandBufferLevel::Primary => &mut current.primary,
+            CommandBufferLevel::Secondary => &mut current.secondary,
+        };
+        buffers.next(|| backend.create_command_buffer(pool, level))
+    }
+
+    #[inline]
+    pub fn current_pool(&self) -> Handle<CommandPool> {
+        self.frames[self.frame].pool
+    }
+
+    pub fn destroy(&mut self, backend: &mut dyn RenderBackend) -> Result<()> {
+        for frame in self.frames.drain(..) {
+            for cb in frame.primary.buffers.iter().chain(frame.secondary.buffers.iter()) {
+                backend.destroy_command_buffer(*cb)?;
+            }
+            backend.destroy_command_pool(frame.pool)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use luxseed_utility::{impl_handle, pool::Pool};
+
+    #[derive(Default)]
+    struct TestCommandBuffer {
+        handle: Option<Handle<CommandBuffer>>,
+    }
+    impl_handle!(TestCommandBuffer, CommandBuffer, handle);
+
+    #[test]
+    fn buffers_are_recycled_per_frame() {
+        let mut p_command_buffer = Pool::<TestCommandBuffer>::with_size(16, Default::default);
+        let mut frames: Vec<RecycledCommandBuffers> = (0..3).map(|_| Default::default()).collect();
+        let mut created = 0;
+        let mut first_frame_buffers = Vec::new();
+
+        for frame in 0..6 {
+            let buffers = &mut frames[frame % 3];
+            buffers.recycle();
+            let mut allocated = Vec::new();
+            for _ in 0..2 {
+                allocated.push(
+                    buffers
+                        .next(|| {
+                            created += 1;
+                            Ok(p_command_buffer.malloc().0)
+                        })
+                        .unwrap(),
+                );
+            }
+
+            if frame < 3 {
+                first_frame_buffers.push(allocated);
+            } else {
+                // The second time around a frame gets the buffers it allocated before
+                assert_eq!(allocated, first_frame_buffers[frame % 3]);
+            }
+        }
+
+        assert_eq!(created, 6);
+        for buffers in first_frame_buffers.iter().flatten() {
+            assert!(p_command_buffer.get(*buffers).is_some());
+        }
+    }
+}
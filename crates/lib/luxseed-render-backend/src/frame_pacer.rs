@@ -0,0 +1,138 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Sleeping is only precise to about a millisecond, the end of each wait is spun instead.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Holds frames to a target frame time when presenting doesn't wait for the vertical blank,
+/// i.e. with a mailbox or immediate present mode.
+///
+/// Call `wait` once per frame after presenting, it blocks until the target frame time has
+/// passed since the previous call.
+pub struct FramePacer {
+    target: Duration,
+    frame_start: Option<Instant>,
+    measured: Duration,
+    gpu: Option<Duration>,
+}
+
+impl FramePacer {
+    pub fn new(target_hz: f64) -> Self {
+        Self::with_frame_time(Duration::from_secs_f64(1.0 / target_hz))
+    }
+
+    pub fn with_frame_time(target: Duration) -> Self {
+        Self { target, frame_start: None, measured: Duration::ZERO, gpu: None }
+    }
+
+    #[inline]
+    pub fn target_frame_time(&self) -> Duration {
+        self.target
+    }
+
+    /// Time between the starts of the last two frames, waiting included.
+    #[inline]
+    pub fn measured_frame_time(&self) -> Duration {
+        self.measured
+    }
+
+    #[inline]
+    pub fn gpu_frame_time(&self) -> Option<Duration> {
+        self.gpu
+    }
+
+    /// Reports how long the GPU took for the last frame, e.g. from timestamp queries. When the
+    /// GPU takes longer than the target, frames are paced to the GPU time instead so the CPU
+    /// doesn't queue up frames behind it.
+    pub fn set_gpu_frame_time(&mut self, gpu: Duration) {
+        self.gpu = Some(gpu);
+    }
+
+    /// The frame time frames are paced to, the target or the GPU frame time if that is longer.
+    pub fn effective_frame_time(&self) -> Duration {
+        self.target.max(self.gpu.unwrap_or_default())
+    }
+
+    /// How long to wait after a frame that took `elapsed` of wall time on the CPU.
+    pub fn wait_time(&self, elapsed: Duration) -> Duration {
+        self.effective_frame_time().saturating_sub(elapsed)
+    }
+
+    /// Blocks until the target frame time has passed since the previous call.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let frame_start = match self.frame_start {
+            Some(start) => start,
+            None => {
+                self.frame_start = Some(now);
+                return;
+            }
+        };
+
+        let deadline = now + self.wait_time(now - frame_start);
+        let (sleep, _) = split_wait(deadline - now);
+        if !sleep.is_zero() {
+            thread::sleep(sleep);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        let end = Instant::now();
+        self.measured = end - frame_start;
+        self.frame_start = Some(end);
+    }
+}
+
+/// Splits a wait into the part to sleep and the part to spin.
+fn split_wait(wait: Duration) -> (Duration, Duration) {
+    let sleep = wait.saturating_sub(SPIN_MARGIN);
+    (sleep, wait - sleep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pacing_math() {
+        let mut pacer = FramePacer::new(144.0);
+        let target = pacer.target_frame_time();
+        assert_eq!(target, Duration::from_secs_f64(1.0 / 144.0));
+
+        // A 4ms frame waits out the rest of the ~6.94ms budget
+        let wait = pacer.wait_time(Duration::from_millis(4));
+        assert_eq!(wait, target - Duration::from_millis(4));
+        assert_eq!(split_wait(wait), (wait - SPIN_MARGIN, SPIN_MARGIN));
+
+        // A frame over budget doesn't wait at all
+        assert_eq!(pacer.wait_time(Duration::from_millis(10)), Duration::ZERO);
+
+        // A GPU within budget doesn't change the CPU wall time wait
+        pacer.set_gpu_frame_time(Duration::from_millis(6));
+        assert_eq!(pacer.effective_frame_time(), target);
+        assert_eq!(pacer.wait_time(Duration::from_millis(2)), target - Duration::from_millis(2));
+        assert_eq!(pacer.wait_time(Duration::from_millis(5)), target - Duration::from_millis(5));
+
+        // A GPU over budget raises the frame time to its own
+        pacer.set_gpu_frame_time(Duration::from_millis(10));
+        assert_eq!(pacer.effective_frame_time(), Duration::from_millis(10));
+        assert_eq!(pacer.wait_time(Duration::from_millis(2)), Duration::from_millis(8));
+        assert_eq!(pacer.wait_time(Duration::from_millis(12)), Duration::ZERO);
+
+        // Short waits are spun entirely
+        let short = Duration::from_micros(300);
+        assert_eq!(split_wait(short), (Duration::ZERO, short));
+    }
+
+    #[test]
+    fn wait_holds_target_frame_time() {
+        let mut pacer = FramePacer::with_frame_time(Duration::from_millis(5));
+        pacer.wait();
+        assert_eq!(pacer.measured_frame_time(), Duration::ZERO);
+        pacer.wait();
+        assert!(pacer.measured_frame_time() >= Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// Vertex shader for `FullscreenPass`: a single triangle covering the screen, generated from
+/// `gl_VertexIndex`, with the texture coordinate of the pixel at location 0.
+pub const FULLSCREEN_TRIANGLE_VERT: &str = r#"#version 450
+
+layout(location = 0) out vec2 outUV;
+
+void main() {
+    outUV = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(outUV * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+pub struct FullscreenPassCreateDesc {
+    /// Compiled from `FULLSCREEN_TRIANGLE_VERT`.
+    pub vertex_shader: Handle<Shader>,
+    /// Reads the input texture from a `sampler2D` at binding 0 and the UV from location 0.
+    pub fragment_shader: Handle<Shader>,
+    pub render_pass_output: RenderPassOutput,
+    pub subpass: u32,
+    /// Number of distinct input views the pass can draw with.
+    pub max_inputs: u32,
+}
+
+/// A post-process pass drawing one fullscreen triangle that samples an input texture.
+///
+/// One descriptor set is created per input view on first use and kept until the view is passed to
+/// `forget` or `clear` is called, which must happen before the view is destroyed. Viewport and
+/// scissor are dynamic and must be set before `draw`.
+pub struct FullscreenPass {
+    pub sampler: Handle<Sampler>,
+    pub descriptor_set_layout: Handle<DescriptorSetLayout>,
+    pub descriptor_pool: Handle<DescriptorPool>,
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<RasterPipeline>,
+    descriptor_sets: InputSets,
+}
+
+impl FullscreenPass {
+    pub fn new(backend: &mut dyn RenderBackend, desc: &FullscreenPassCreateDesc) -> Result<Self> {
+        let sampler = backend.create_sampler(&SamplerCreateDesc {
+            min_filter: FilterType::Linear,
+            mag_filter: FilterType::Linear,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode_u: SamplerAddressMode::ClampToEdge,
+            address_mode_v: SamplerAddressMode::ClampToEdge,
+            address_mode_w: SamplerAddressMode::ClampToEdge,
+            mip_lod_bias: 0.0,
+            compare_op: None,
+            max_anisotropy: None,
+            border_color: BorderColor::FloatTransparentBlack,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            unnormalized_coordinates: false,
+        })?;
+        let descriptor_set_layout = backend.create_descriptor_set_layout(
+            &DescriptorSetLayoutCreateDesc::new().add_binding_info(DescriptorBindingInfo {
+                index: 0,
+                type_: DescriptorType::CombinedImageSampler,
+                count: 1,
+                stage_flags: ShaderStageFlags::FRAGMENT,
+                flags: DescriptorBindingFlags::empty(),
+            }),
+        )?;
+        let descriptor_pool = backend.create_descriptor_pool(&DescriptorPoolCreateDesc {
+            max_sets: desc.max_inputs,
+            pool_sizes: &[DescriptorPoolSize {
+                descriptor_type: DescriptorType::CombinedImageSampler,
+                descriptor_count: desc.max_inputs,
+            }],
+            update_after_bind: false,
+        })?;
+        let pipeline_layout = backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+            descriptor_set_layouts: &[descriptor_set_layout],
+            push_constant_ranges: &[],
+        })?;
+        let pipeline = backend.create_raster_pipeline(&RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState { cull_mode: CullMode::None, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &[desc.vertex_shader, desc.fragment_shader],
+            render_pass_output: desc.render_pass_output,
+            subpass: desc.subpass,
+            pipeline_layout,
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        })?;
+
+        Ok(Self {
+            sampler,
+            descriptor_set_layout,
+            descriptor_pool,
+            pipeline_layout,
+            pipeline,
+            descriptor_sets: InputSets::default(),
+        })
+    }
+
+    /// Draws the pass in the current render pass, sampling `input_view`.
+    pub fn draw(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        cb: Handle<CommandBuffer>,
+        input_view: Handle<ImageView>,
+    ) -> Result<()> {
+        let (pool, layout, sampler) =
+            (self.descriptor_pool, self.descriptor_set_layout, self.sampler);
+        let descriptor_set = self.descriptor_sets.get_or_create(input_view, || {
+            backend.create_descriptor_set(
+                &DescriptorSetCreateDesc::new(pool, layout)
+                    .bind_combined_image_sampler(0, input_view, sampler),
+            )
+        })?;
+
+        backend.cmd_bind_raster_pipeline(cb, self.pipeline)?;
+        backend.cmd_bind_descriptor_sets(
+            cb,
+            PipelineBindPoint::Graphics,
+            self.pipeline_layout,
+            0,
+            &[descriptor_set],
+            &[],
+        )?;
+        backend.cmd_draw(cb, 3, 1, 0, 0)
+    }
+
+    /// Frees the descriptor set of `input_view`, call it before destroying a view the pass
+    /// has drawn with, e.g. when recreating a render target.
+    pub fn forget(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        input_view: Handle<ImageView>,
+    ) -> Result<()> {
+        match self.descriptor_sets.remove(input_view) {
+            Some(set) => backend.destroy_descriptor_sets(&[set]),
+            None => Ok(()),
+        }
+    }
+
+    /// Frees the descriptor sets of all input views.
+    pub fn clear(&mut self, backend: &mut dyn RenderBackend) -> Result<()> {
+        backend.destroy_descriptor_sets(&self.descriptor_sets.take_all())
+    }
+
+    pub fn destroy(&mut self, backend: &mut dyn RenderBackend) -> Result<()> {
+        backend.destroy_raster_pipeline(self.pipeline)?;
+        backend.destroy_pipeline_layout(self.pipeline_layout)?;
+        backend.destroy_descriptor_pool(self.descriptor_pool)?;
+        backend.destroy_descriptor_set_layout(self.descriptor_set_layout)?;
+        backend.destroy_sampler(self.sampler)?;
+        self.descriptor_sets.take_all();
+        Ok(())
+    }
+}
+
+/// The descriptor set of each input view.
+#[derive(Default)]
+struct InputSets {
+    sets: HashMap<Handle<ImageView>, Handle<DescriptorSet>>,
+}
+
+impl InputSets {
+    fn get_or_create(
+        &mut self,
+        view: Handle<ImageView>,
+        create: impl FnOnce() -> Result<Handle<DescriptorSet>>,
+    ) -> Result<Handle<DescriptorSet>> {
+        if let Some(set) = self.sets.get(&view) {
+            return Ok(*set);
+        }
+        let set = create()?;
+        self.sets.insert(view, set);
+        Ok(set)
+    }
+
+    fn remove(&mut self, view: Handle<ImageView>) -> Option<Handle<DescriptorSet>> {
+        self.sets.remove(&view)
+    }
+
+    fn take_all(&mut self) -> Vec<Handle<DescriptorSet>> {
+        self.sets.drain().map(|(_, set)| set).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use luxseed_utility::{impl_handle, pool::Pool};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestImageView {
+        handle: Option<Handle<ImageView>>,
+    }
+    impl_handle!(TestImageView, ImageView, handle);
+
+    #[derive(Default)]
+    struct TestDescriptorSet {
+        handle: Option<Handle<DescriptorSet>>,
+    }
+    impl_handle!(TestDescriptorSet, DescriptorSet, handle);
+
+    #[test]
+    fn recreated_targets_dont_grow_the_sets() {
+        let mut views = Pool::<TestImageView>::with_size(8, Default::default);
+        let mut sets = Pool::<TestDescriptorSet>::with_size(8, Default::default);
+        let mut input_sets = InputSets::default();
+
+        let mut view = views.malloc().0;
+        let first = input_sets.get_or_create(view, || Ok(sets.malloc().0)).unwrap();
+        assert_eq!(input_sets.get_or_create(view, || unreachable!()).unwrap(), first);
+
+        // Every resize destroys the target view and creates a new one
+        for _ in 0..4 {
+            let old = view;
+            assert!(input_sets.remove(old).is_some());
+            views.free(old);
+            view = views.malloc().0;
+            input_sets.get_or_create(view, || Ok(sets.malloc().0)).unwrap();
+            assert_eq!(input_sets.sets.len(), 1);
+        }
+        assert!(input_sets.remove(view).is_some());
+        assert!(input_sets.remove(view).is_none());
+        assert!(input_sets.take_all().is_empty());
+    }
+}
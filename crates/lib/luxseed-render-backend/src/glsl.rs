@@ -0,0 +1,205 @@
+use std::{fmt, fs, path::PathBuf};
+
+use crate::flag::ShaderStageFlags;
+
+/// One message reported by the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    /// The shader or included file the message is about.
+    pub file: String,
+    /// 1-based line, `None` when the compiler didn't report one.
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderCompileError {
+    UnsupportedStage(ShaderStageFlags),
+    CompilerUnavailable,
+    /// The source has errors, `diagnostics` holds every message the compiler reported.
+    Compilation {
+        name: String,
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+    Internal(String),
+}
+
+impl fmt::Display for ShaderCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedStage(stage) => write!(f, "Unsupported shader stage {:?}.", stage),
+            Self::CompilerUnavailable => write!(f, "Failed to initialize the shader compiler."),
+            Self::Compilation { name, diagnostics } => {
+                write!(f, "Failed to compile shader {}:", name)?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Self::Internal(message) => write!(f, "Shader compiler error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ShaderCompileError {}
+
+/// Resolves an `#include`, gets the requested name and the name of the including file and
+/// returns the resolved name with the included source.
+pub type IncludeCallback<'a> = dyn Fn(&str, &str) -> Result<(String, String), String> + 'a;
+
+/// Resolves includes by looking the requested name up in each directory in order.
+pub fn include_dirs(dirs: Vec<PathBuf>) -> impl Fn(&str, &str) -> Result<(String, String), String> {
+    move |requested, requesting| {
+        for dir in dirs.iter() {
+            let path = dir.join(requested);
+            if let Ok(source) = fs::read_to_string(&path) {
+                return Ok((path.to_string_lossy().into_owned(), source));
+            }
+        }
+        Err(format!("{} included from {} not found in {:?}", requested, requesting, dirs))
+    }
+}
+
+/// Compiles GLSL source to SPIR-V.
+pub fn compile_glsl(
+    name: &str,
+    source: &str,
+    stage: ShaderStageFlags,
+    entry: &str,
+) -> Result<Vec<u32>, ShaderCompileError> {
+    compile_glsl_with_includes(name, source, stage, entry, None)
+}
+
+/// Compiles GLSL source to SPIR-V, resolving `#include` directives with `include`.
+pub fn compile_glsl_with_includes(
+    name: &str,
+    source: &str,
+    stage: ShaderStageFlags,
+    entry: &str,
+    include: Option<&IncludeCallback>,
+) -> Result<Vec<u32>, ShaderCompileError> {
+    let kind = match stage {
+        ShaderStageFlags::VERTEX => shaderc::ShaderKind::Vertex,
+        ShaderStageFlags::TESSELLATION_CONTROL => shaderc::ShaderKind::TessControl,
+        ShaderStageFlags::TESSELLATION_EVALUATION => shaderc::ShaderKind::TessEvaluation,
+        ShaderStageFlags::GEOMETRY => shaderc::ShaderKind::Geometry,
+        ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
+        ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
+        _ => return Err(ShaderCompileError::UnsupportedStage(stage)),
+    };
+
+    let compiler = shaderc::Compiler::new().ok_or(ShaderCompileError::CompilerUnavailable)?;
+    let mut options =
+        shaderc::CompileOptions::new().ok_or(ShaderCompileError::CompilerUnavailable)?;
+    // The backend needs Vulkan 1.2, whose SPIR-V 1.5 extensions like ray queries rely on
+    options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_2 as u32);
+    if let Some(include) = include {
+        options.set_include_callback(move |requested, _, requesting, _| {
+            include(requested, requesting)
+                .map(|(resolved_name, content)| shaderc::ResolvedInclude { resolved_name, content })
+        });
+    }
+
+    match compiler.compile_into_spirv(source, kind, name, entry, Some(&options)) {
+        Ok(artifact) => Ok(artifact.as_binary().to_vec()),
+        Err(shaderc::Error::CompilationError(_, text)) => Err(ShaderCompileError::Compilation {
+            name: name.to_owned(),
+            diagnostics: parse_diagnostics(name, &text),
+        }),
+        Err(err) => Err(ShaderCompileError::Internal(err.to_string())),
+    }
+}
+
+/// Splits compiler output like `shader.frag:3: error: 'x' : undeclared identifier` into
+/// diagnostics, lines that don't follow that shape are kept whole.
+fn parse_diagnostics(name: &str, text: &str) -> Vec<ShaderDiagnostic> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with("generated."))
+        .map(|line| {
+            let marker = [": error: ", ": warning: "].iter().find_map(|marker| line.find(marker));
+            match marker {
+                Some(i) => {
+                    let (file, line_number) = match line[..i].rsplit_once(':') {
+                        Some((file, number)) => match number.parse() {
+                            Ok(number) => (file, Some(number)),
+                            Err(_) => (&line[..i], None),
+                        },
+                        None => (&line[..i], None),
+                    };
+                    ShaderDiagnostic {
+                        file: file.to_owned(),
+                        line: line_number,
+                        message: line[i + 2..].to_owned(),
+                    }
+                }
+                None => {
+                    ShaderDiagnostic { file: name.to_owned(), line: None, message: line.to_owned() }
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_carry_file_and_line() {
+        let text = "broken.frag:4: error: 'colour' : undeclared identifier\n\
+                    lib/common.glsl:12: warning: unused variable\n\
+                    broken.frag: error: missing entry point\n\
+                    2 errors generated.\n";
+        assert_eq!(
+            parse_diagnostics("broken.frag", text),
+            vec![
+                ShaderDiagnostic {
+                    file: "broken.frag".to_owned(),
+                    line: Some(4),
+                    message: "error: 'colour' : undeclared identifier".to_owned(),
+                },
+                ShaderDiagnostic {
+                    file: "lib/common.glsl".to_owned(),
+                    line: Some(12),
+                    message: "warning: unused variable".to_owned(),
+                },
+                ShaderDiagnostic {
+                    file: "broken.frag".to_owned(),
+                    line: None,
+                    message: "error: missing entry point".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_shader_reports_diagnostics() {
+        let source = "#version 450\n\
+                      layout(location = 0) out vec4 out_color;\n\
+                      void main() {\n\
+                          out_color = colour;\n\
+                      }\n";
+        let err =
+            compile_glsl("broken.frag", source, ShaderStageFlags::FRAGMENT, "main").unwrap_err();
+        match &err {
+            ShaderCompileError::Compilation { name, diagnostics } => {
+                assert_eq!(name, "broken.frag");
+                assert_eq!(diagnostics[0].file, "broken.frag");
+                assert_eq!(diagnostics[0].line, Some(4));
+                assert!(diagnostics[0].message.contains("colour"));
+            }
+            _ => panic!("unexpected error {}", err),
+        }
+        assert!(err.to_string().contains("broken.frag:4"));
+    }
+}
@@ -1,6 +1,21 @@
+pub mod barrier_batch;
+#[cfg(feature = "bytemuck")]
+pub mod buffer_write;
 pub mod define;
+pub mod device_recovery;
 pub mod enums;
 pub mod flag;
+pub mod frame_command_pools;
+pub mod frame_pacer;
+pub mod fullscreen;
+#[cfg(feature = "glsl")]
+pub mod glsl;
+pub mod mipmaps;
+pub mod present;
+pub mod simple_compute;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod upload_context;
 pub mod vulkan;
 
 use anyhow::Result;
@@ -48,6 +63,31 @@ pub trait RenderBackend {
         feature: FormatFeatureFlags,
     ) -> Result<Format>;
 
+    /// Checks whether the format supports the given features with the given tiling.
+    ///
+    /// Returns `false` when the device has not been created.
+    fn is_format_supported(
+        &self,
+        format: Format,
+        tiling: ImageTiling,
+        feature: FormatFeatureFlags,
+    ) -> bool;
+
+    /// Picks the preferred depth format usable as an optimal tiling depth attachment.
+    ///
+    /// # Arguments
+    ///
+    /// * `with_stencil` - Only consider formats that also have a stencil aspect.
+    fn best_depth_format(&self, with_stencil: bool) -> Result<Format> {
+        let candidates: &[Format] =
+            if with_stencil { &DEPTH_STENCIL_FORMAT_CANDIDATES } else { &DEPTH_FORMAT_CANDIDATES };
+        self.get_supported_format_from_candidates(
+            candidates,
+            ImageTiling::Optimal,
+            FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+
     /// Enumerates the adapter infos.
     ///
     /// # Returns
@@ -56,30 +96,79 @@ pub trait RenderBackend {
     fn enumerate_adapter_infos(&self) -> &[AdapterInfo];
 
     fn is_device_created(&self) -> bool;
-    fn create_device(&mut self, adapter_index: usize) -> Result<()>;
+    fn create_device(&mut self, desc: &DeviceCreateDesc) -> Result<()>;
+    /// Extensions and features the device was created with, requested ones the adapter lacks
+    /// are listed as unavailable.
+    fn enabled_features(&self) -> Result<&EnabledFeatures>;
+    /// Destroys the device along with every resource still made from it, so another device
+    /// can be created afterwards. Surfaces are kept.
     fn destroy_device(&mut self) -> Result<()>;
     fn device_wait_idle(&self) -> Result<()>;
+    /// Whether a submit, present or wait reported `VK_ERROR_DEVICE_LOST`, e.g. after a driver
+    /// reset. Nothing made from a lost device can be used again, see `recreate_device`.
+    fn is_device_lost(&self) -> bool;
+    /// Destroys the device and creates it again from `desc`, which may pick another adapter.
+    /// Every handle made before is invalid afterwards, see `recover_device` for rebuilding them.
+    fn recreate_device(&mut self, desc: &DeviceCreateDesc) -> Result<()> {
+        self.destroy_device()?;
+        self.create_device(desc)
+    }
+
+    /// Lists the handles that were never destroyed, meant to track down leaks before shutdown.
+    fn debug_report_live_resources(&self) -> LiveResourceReport;
 
     // Fence
     fn create_fence(&mut self, signal: bool) -> Result<Handle<Fence>>;
     fn destroy_fence(&mut self, handle: Handle<Fence>) -> Result<()>;
-    fn wait_for_fences(&self, fences: &[Handle<Fence>], wait_all: bool, timeout: u64)
-        -> Result<()>;
+    /// Waits up to `timeout` nanoseconds, a timeout or a lost device is reported in the
+    /// result instead of as an error so a frame loop can tell a hang from success.
+    fn wait_for_fences(
+        &self,
+        fences: &[Handle<Fence>],
+        wait_all: bool,
+        timeout: u64,
+    ) -> Result<FenceWaitResult>;
     fn reset_fences(&self, fences: &[Handle<Fence>]) -> Result<()>;
+    /// Polls the fence without blocking, `true` once it is signaled.
+    fn get_fence_status(&self, fence: Handle<Fence>) -> Result<bool>;
 
     // Semaphore
     fn create_semaphore(&mut self) -> Result<Handle<Semaphore>>;
     fn destroy_semaphore(&mut self, handle: Handle<Semaphore>) -> Result<()>;
+    /// Needs `DeviceFeatures::TIMELINE_SEMAPHORE`. The semaphore counts up from `initial_value`
+    /// and can be waited on from the host.
+    fn create_timeline_semaphore(&mut self, initial_value: u64) -> Result<Handle<Semaphore>>;
+    /// Waits up to `timeout` nanoseconds for the timeline semaphore to reach `value`, reported
+    /// like `wait_for_fences`.
+    fn wait_timeline_semaphore(
+        &self,
+        semaphore: Handle<Semaphore>,
+        value: u64,
+        timeout: u64,
+    ) -> Result<FenceWaitResult>;
+    /// Waits up to `timeout` nanoseconds until every timeline semaphore reaches its value.
+    fn wait_semaphores(
+        &self,
+        semaphores: &[(Handle<Semaphore>, u64)],
+        timeout: u64,
+    ) -> Result<FenceWaitResult>;
+    /// Sets the timeline semaphore to `value` from the host.
+    fn signal_timeline_semaphore(&self, semaphore: Handle<Semaphore>, value: u64) -> Result<()>;
+    fn get_timeline_value(&self, semaphore: Handle<Semaphore>) -> Result<u64>;
 
     // Queue
     fn get_queue(&self, queue_type: QueueType) -> Result<Handle<Queue>>;
     fn queue_submit(&self, handle: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()>;
+    /// Submits several descs with a single queue submission. They signal at most one fence,
+    /// descs either leave `fence` unset or name the same one.
+    fn queue_submit_batch(&self, handle: Handle<Queue>, descs: &[QueueSubmitDesc]) -> Result<()>;
 
     /// Presents the swapchain.
     ///
     /// # Arguments
     ///
-    /// * `handle` - A handle to the queue.
+    /// * `handle` - A handle to the queue, `get_queue(QueueType::Present)` is the one that can
+    ///   present to the surface.
     /// * `desc` - A description of the present operation.
     ///
     /// # Returns
@@ -95,6 +184,9 @@ pub trait RenderBackend {
 
     /// Creates a new swapchain with the given description and returns a handle to it.
     ///
+    /// Each window has its own surface and swapchain, a surface takes only one swapchain at a
+    /// time so the old one must be destroyed before recreating it.
+    ///
     /// # Arguments
     ///
     /// * `desc` - A reference to the description of the swapchain to create.
@@ -140,8 +232,18 @@ pub trait RenderBackend {
         index: usize,
     ) -> Result<Handle<Image>>;
 
+    /// Gets the number of images the swapchain was actually created with, which can be more
+    /// than `SwapchainCreateDesc::desired_image_count`.
     fn get_swapchain_image_count(&self, handle: Handle<Swapchain>) -> Result<u8>;
 
+    /// Gets the extent the swapchain was actually created with, the surface can override or
+    /// clamp the requested size.
+    fn get_swapchain_extent(&self, handle: Handle<Swapchain>) -> Result<(u32, u32)>;
+
+    /// Gets the format of the swapchain images, which falls back to another format when the
+    /// surface doesn't support the requested one.
+    fn get_swapchain_format(&self, handle: Handle<Swapchain>) -> Result<Format>;
+
     fn destroy_swapchain(&mut self, swapchain: Handle<Swapchain>) -> Result<()>;
 
     fn create_descriptor_set_layout(
@@ -160,11 +262,21 @@ pub trait RenderBackend {
         desc: &DescriptorSetCreateDesc,
     ) -> Result<Handle<DescriptorSet>>;
 
+    /// Rebinds only `bindings` of an existing set, e.g. built with a `DescriptorSetCreateDesc`.
+    /// Fails when one of them isn't declared by the layout of the set.
+    fn update_descriptor_set(
+        &mut self,
+        set: Handle<DescriptorSet>,
+        bindings: &[DescriptorBindingData],
+    ) -> Result<()>;
+
     fn destroy_descriptor_sets(&mut self, sets: &[Handle<DescriptorSet>]) -> Result<()>;
 
     // Image / Image View
     fn create_image(&mut self, desc: &ImageCreateDesc) -> Result<Handle<Image>>;
     fn destroy_image(&mut self, handle: Handle<Image>) -> Result<()>;
+    fn get_image_format(&self, handle: Handle<Image>) -> Result<Format>;
+    fn get_image_mip_levels(&self, handle: Handle<Image>) -> Result<u32>;
     fn create_image_view(
         &mut self,
         image: Handle<Image>,
@@ -172,19 +284,69 @@ pub trait RenderBackend {
     ) -> Result<Handle<ImageView>>;
     fn destroy_image_view(&mut self, handle: Handle<ImageView>) -> Result<()>;
 
+    /// Identical descriptions share one sampler, each call must still be paired with a
+    /// `destroy_sampler` and the sampler goes away with the last one.
     fn create_sampler(&mut self, desc: &SamplerCreateDesc) -> Result<Handle<Sampler>>;
     fn destroy_sampler(&mut self, handle: Handle<Sampler>) -> Result<()>;
 
     // Shader
     fn create_shader_module(&mut self, desc: &ShaderModuleCreation) -> Result<Handle<Shader>>;
     fn destroy_shader_module(&mut self, shader_module: Handle<Shader>) -> Result<()>;
+    /// Descriptor set layouts the shaders of one pipeline declare, with the stage flags of every
+    /// shader using a binding. Saves mirroring the shaders in `DescriptorSetLayoutCreateDesc`.
+    fn reflect_descriptor_layouts(
+        &self,
+        shaders: &[Handle<Shader>],
+    ) -> Result<Vec<DescriptorSetLayoutCreateDesc>>;
 
     // Buffer
     fn create_buffer(&mut self, desc: &BufferCreateDesc) -> Result<Handle<Buffer>>;
 
     fn destroy_buffer(&mut self, buffer: Handle<Buffer>) -> Result<()>;
 
+    /// The mapped memory of a host visible buffer. Writes through it are only guaranteed to
+    /// reach the GPU after `flush_buffer`, and GPU writes only show up in it after
+    /// `invalidate_buffer`, unless the memory happens to be host coherent.
     fn get_buffer_mapped_slice_mut(&mut self, buffer: Handle<Buffer>) -> Result<&mut [u8]>;
+    /// Makes writes to `size` bytes of the mapped buffer from `offset` visible to the GPU,
+    /// `WHOLE_SIZE` covers the rest of the buffer. A no-op for host coherent memory.
+    fn flush_buffer(&self, buffer: Handle<Buffer>, offset: u64, size: u64) -> Result<()>;
+    /// Makes GPU writes to `size` bytes of the buffer from `offset` visible in its mapped
+    /// memory, `WHOLE_SIZE` covers the rest of the buffer. A no-op for host coherent memory.
+    fn invalidate_buffer(&self, buffer: Handle<Buffer>, offset: u64, size: u64) -> Result<()>;
+
+    /// A texel view of `range` bytes of the buffer from `offset`, `WHOLE_SIZE` for the rest of
+    /// it. The buffer needs `UNIFORM_TEXEL_BUFFER` or `STORAGE_TEXEL_BUFFER` usage and the
+    /// format has to support buffer views of that kind.
+    fn create_buffer_view(
+        &mut self,
+        buffer: Handle<Buffer>,
+        format: Format,
+        offset: u64,
+        range: u64,
+    ) -> Result<Handle<BufferView>>;
+    fn destroy_buffer_view(&mut self, handle: Handle<BufferView>) -> Result<()>;
+
+    /// The GPU address of the buffer, for shaders that read it through a raw pointer. The
+    /// buffer has to be created with `BufferUsageFlags::SHADER_DEVICE_ADDRESS`.
+    fn get_buffer_device_address(&self, buffer: Handle<Buffer>) -> Result<u64>;
+
+    // Acceleration Structure, needs `DeviceFeatures::RAY_QUERY`
+    fn create_acceleration_structure(
+        &mut self,
+        desc: &AccelerationStructureCreateDesc,
+    ) -> Result<Handle<AccelerationStructure>>;
+
+    fn destroy_acceleration_structure(
+        &mut self,
+        acceleration_structure: Handle<AccelerationStructure>,
+    ) -> Result<()>;
+
+    /// Referenced by the `AccelerationStructureInstance`s of a top level structure.
+    fn get_acceleration_structure_device_address(
+        &self,
+        acceleration_structure: Handle<AccelerationStructure>,
+    ) -> Result<u64>;
 
     fn create_pipeline_layout(
         &mut self,
@@ -195,6 +357,9 @@ pub trait RenderBackend {
 
     /// Creates a new raster pipeline with the given description and returns a handle to it.
     ///
+    /// Fails if `desc.depth_state` enables depth or stencil testing while the subpass has no
+    /// depth stencil attachment.
+    ///
     /// # Arguments
     ///
     /// * `desc` - A reference to the description of the pipeline to create.
@@ -207,13 +372,52 @@ pub trait RenderBackend {
         desc: &RasterPipelineCreateDesc,
     ) -> Result<Handle<RasterPipeline>>;
 
+    /// Creates several raster pipelines with a single driver call.
+    ///
+    /// Render passes and pipeline layouts are resolved for every description first, then all
+    /// pipelines are compiled together so the driver can parallelize the work. The returned
+    /// handles are in the same order as `descs`.
+    fn create_raster_pipelines(
+        &mut self,
+        descs: &[RasterPipelineCreateDesc],
+    ) -> Result<Vec<Handle<RasterPipeline>>>;
+
     fn destroy_raster_pipeline(&mut self, raster_pipeline: Handle<RasterPipeline>) -> Result<()>;
 
+    /// Fails if the shader module wasn't created for the compute stage.
+    fn create_compute_pipeline(
+        &mut self,
+        desc: &ComputePipelineCreateDesc,
+    ) -> Result<Handle<ComputePipeline>>;
+    fn destroy_compute_pipeline(&mut self, compute_pipeline: Handle<ComputePipeline>)
+        -> Result<()>;
+    /// Creates the descriptor set layout of `bindings` at `set = 0`, a pipeline layout with one
+    /// compute push constant block of `push_constant_size` bytes and the compute pipeline of
+    /// `shader`, for small kernels like mip generation.
+    ///
+    /// Fails if `push_constant_size` isn't a multiple of 4 or a binding isn't visible to
+    /// `ShaderStageFlags::COMPUTE`.
+    fn create_simple_compute(
+        &mut self,
+        shader: Handle<Shader>,
+        bindings: &[DescriptorBindingInfo],
+        push_constant_size: u32,
+    ) -> Result<simple_compute::SimpleCompute> {
+        simple_compute::create(self, shader, bindings, push_constant_size)
+    }
+    fn destroy_simple_compute(&mut self, compute: simple_compute::SimpleCompute) -> Result<()> {
+        self.destroy_compute_pipeline(compute.pipeline)?;
+        self.destroy_pipeline_layout(compute.pipeline_layout)?;
+        self.destroy_descriptor_set_layout(compute.descriptor_set_layout)
+    }
+
     // Render pass
     fn create_render_pass(&mut self, output: &RenderPassOutput) -> Result<Handle<RenderPass>>;
     fn destroy_render_pass(&mut self, handle: Handle<RenderPass>) -> Result<()>;
 
     // Framebuffer
+    /// Fails if the attachments differ in size or layer count, or if the depth stencil view
+    /// has neither the depth nor the stencil aspect.
     fn create_framebuffer(
         &mut self,
         creation: &FramebufferCreateDesc,
@@ -239,6 +443,7 @@ pub trait RenderBackend {
     // CMDs
     fn cmd_begin(&self, cb: Handle<CommandBuffer>, desc: CommandBufferBeginDesc) -> Result<()>;
     fn cmd_end(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    /// Fails if an attachment with a clear load action has no clear value.
     fn cmd_begin_render_pass(
         &self,
         cb: Handle<CommandBuffer>,
@@ -246,13 +451,33 @@ pub trait RenderBackend {
         framebuffer: Handle<Framebuffer>,
         clear_values: Option<&[ClearColor]>,
         clear_depth_stencil: Option<ClearDepthStencil>,
+        contents: SubpassContents,
     ) -> Result<()>;
+    fn cmd_next_subpass(&self, cb: Handle<CommandBuffer>, contents: SubpassContents) -> Result<()>;
     fn cmd_end_render_pass(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    /// Starts drawing to `info`'s attachments without a render pass or framebuffer, for
+    /// pipelines created with `dynamic_rendering`. Needs `DeviceFeatures::DYNAMIC_RENDERING`.
+    fn cmd_begin_rendering(&self, cb: Handle<CommandBuffer>, info: &RenderingInfo) -> Result<()>;
+    fn cmd_end_rendering(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    /// Runs ended secondary command buffers from `primary`, e.g. draw batches recorded on
+    /// several threads. Inside a render pass the subpass has to be begun with
+    /// `SubpassContents::SecondaryCommandBuffers` and the secondaries with
+    /// `CommandBufferBeginDesc::render_pass_continue` for that subpass.
+    fn cmd_execute_commands(
+        &self,
+        primary: Handle<CommandBuffer>,
+        secondaries: &[Handle<CommandBuffer>],
+    ) -> Result<()>;
     fn cmd_bind_raster_pipeline(
         &self,
         cb: Handle<CommandBuffer>,
         pipeline: Handle<RasterPipeline>,
     ) -> Result<()>;
+    fn cmd_bind_compute_pipeline(
+        &self,
+        cb: Handle<CommandBuffer>,
+        pipeline: Handle<ComputePipeline>,
+    ) -> Result<()>;
     fn cmd_set_viewport(
         &self,
         cb: Handle<CommandBuffer>,
@@ -263,6 +488,18 @@ pub trait RenderBackend {
         min_depth: f32,
         max_depth: f32,
     ) -> Result<()>;
+    /// Sets the viewport from a `Viewport`, e.g. `Viewport::new(w, h).reverse_z()`.
+    fn cmd_apply_viewport(&self, cb: Handle<CommandBuffer>, viewport: &Viewport) -> Result<()> {
+        self.cmd_set_viewport(
+            cb,
+            viewport.x,
+            viewport.y,
+            viewport.width,
+            viewport.height,
+            viewport.min_depth,
+            viewport.max_depth,
+        )
+    }
     fn cmd_set_scissor(
         &self,
         cb: Handle<CommandBuffer>,
@@ -271,11 +508,44 @@ pub trait RenderBackend {
         width: u32,
         height: u32,
     ) -> Result<()>;
+    /// Sets viewports `first..first + viewports.len()`, indices past 0 need
+    /// `RequestedFeatures::MULTI_VIEWPORT`.
+    fn cmd_set_viewports(
+        &self,
+        cb: Handle<CommandBuffer>,
+        first: u32,
+        viewports: &[Viewport],
+    ) -> Result<()>;
+    /// Sets scissors `first..first + scissors.len()`, indices past 0 need
+    /// `RequestedFeatures::MULTI_VIEWPORT`.
+    fn cmd_set_scissors(
+        &self,
+        cb: Handle<CommandBuffer>,
+        first: u32,
+        scissors: &[Rect2D],
+    ) -> Result<()>;
+    /// Needs a pipeline with `DynamicStateFlags::DEPTH_BIAS`. A non-zero `clamp` needs the
+    /// `depthBiasClamp` device feature.
+    fn cmd_set_depth_bias(
+        &self,
+        cb: Handle<CommandBuffer>,
+        constant_factor: f32,
+        clamp: f32,
+        slope_factor: f32,
+    ) -> Result<()>;
+    /// Needs a pipeline with `DynamicStateFlags::LINE_WIDTH`, widths other than 1 need
+    /// `RequestedFeatures::WIDE_LINES`.
+    fn cmd_set_line_width(&self, cb: Handle<CommandBuffer>, width: f32) -> Result<()>;
+    /// Needs a pipeline with `DynamicStateFlags::BLEND_CONSTANTS`.
+    fn cmd_set_blend_constants(&self, cb: Handle<CommandBuffer>, constants: [f32; 4])
+        -> Result<()>;
     fn cmd_pipeline_barrier(
         &self,
         cb: Handle<CommandBuffer>,
         src_stage_mask: PipelineStageFlags,
         dst_stage_mask: PipelineStageFlags,
+        memory_barriers: &[MemoryBarrier],
+        buffer_memory_barriers: &[BufferMemoryBarrier],
         image_memory_barriers: &[ImageMemoryBarrier],
     ) -> Result<()>;
     fn cmd_bind_descriptor_sets(
@@ -287,6 +557,17 @@ pub trait RenderBackend {
         descriptor_sets: &[Handle<DescriptorSet>],
         dynamic_offsets: &[u32],
     ) -> Result<()>;
+    /// Writes `data` to bytes `offset..offset + data.len()` of the push constants of
+    /// `pipeline_layout`, for the stages of the ranges they fall in. Both have to be multiples
+    /// of 4, and the values stay set until they are pushed again.
+    fn cmd_push_constants(
+        &self,
+        cb: Handle<CommandBuffer>,
+        pipeline_layout: Handle<PipelineLayout>,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<()>;
 
     fn cmd_bind_vertex_buffers(
         &self,
@@ -309,6 +590,31 @@ pub trait RenderBackend {
         dst: Handle<Buffer>,
         regions: &[BufferCopyRegion],
     ) -> Result<()>;
+    /// Writes `data` into the buffer inline, without a staging buffer. Meant for small updates,
+    /// `data` is at most 64KB and both its length and `offset` are multiples of 4.
+    fn cmd_update_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<()>;
+    /// Fills `size` bytes from `offset` with repeats of `value`, `WHOLE_SIZE` fills to the end.
+    fn cmd_fill_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+        size: u64,
+        value: u32,
+    ) -> Result<()>;
+    /// Builds the structures from the geometries they were created with, bottom level ones
+    /// before top level ones. The results are visible to every command recorded after.
+    fn cmd_build_acceleration_structures(
+        &self,
+        cb: Handle<CommandBuffer>,
+        acceleration_structures: &[Handle<AccelerationStructure>],
+    ) -> Result<()>;
     fn cmd_copy_buffer_to_image(
         &self,
         cb: Handle<CommandBuffer>,
@@ -317,6 +623,119 @@ pub trait RenderBackend {
         dst_image_layout: ImageLayout,
         regions: &[BufferImageCopyRegion],
     ) -> Result<()>;
+    /// Reads regions of a single sampled image in `TransferSrcOptimal` or `General` into a
+    /// buffer, the inverse of `cmd_copy_buffer_to_image`. With a `MemoryLocation::GpuToCpu`
+    /// buffer this reads rendered images back, e.g. for screenshots.
+    fn cmd_copy_image_to_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Buffer>,
+        regions: &[BufferImageCopyRegion],
+    ) -> Result<()>;
+    /// Copies regions between images without scaling or conversion, so the formats have to be
+    /// the same size and the sample counts equal. The images have to be in `TransferSrcOptimal`
+    /// and `TransferDstOptimal` or `General`.
+    fn cmd_copy_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageCopyRegion],
+    ) -> Result<()>;
+    /// Clears ranges of a color image outside a render pass, e.g. a storage image before a
+    /// compute pass. The image has to be in `General` or `TransferDstOptimal` and created with
+    /// `TRANSFER_DST` usage, it is not transitioned here.
+    fn cmd_clear_color_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        image: Handle<Image>,
+        image_layout: ImageLayout,
+        color: ClearColor,
+        ranges: &[ImageSubresourceRange],
+    ) -> Result<()>;
+    /// Depth and stencil version of `cmd_clear_color_image`, with the same layout and usage
+    /// requirements.
+    fn cmd_clear_depth_stencil_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        image: Handle<Image>,
+        image_layout: ImageLayout,
+        value: ClearDepthStencil,
+        ranges: &[ImageSubresourceRange],
+    ) -> Result<()>;
+    /// Resolves a multisampled color image into a single sampled one of the same format, in the
+    /// `TransferSrcOptimal` and `TransferDstOptimal` layouts or `General`.
+    fn cmd_resolve_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageResolveRegion],
+    ) -> Result<()>;
+    /// Copies regions between single sampled images with scaling and format conversion, e.g.
+    /// to fill a mip chain or scale an offscreen target to the swapchain. The images have to
+    /// be in `TransferSrcOptimal` and `TransferDstOptimal` or `General`.
+    ///
+    /// Fails if the formats can't be converted into each other, if the formats don't support
+    /// `FormatFeatureFlags::BLIT_SRC` and `BLIT_DST`, or if `FilterType::Linear` is used on a
+    /// format without `SAMPLED_IMAGE_FILTER_LINEAR`.
+    fn cmd_blit_image(&self, cb: Handle<CommandBuffer>, desc: &BlitImageDesc) -> Result<()>;
+    /// Fills mip levels `1..mip_levels` of a 2D color image by linearly blitting each one from
+    /// the level above it. Every level has to be in `TransferDstOptimal`, e.g. after uploading
+    /// level 0, and ends up in `ShaderReadOnlyOptimal`.
+    ///
+    /// Fails if `format` doesn't support `FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR` with
+    /// optimal tiling, or if `mip_levels` is longer than the chain of a `width` by `height`
+    /// image, see `mipmaps::max_mip_levels`.
+    fn generate_mipmaps(
+        &self,
+        cb: Handle<CommandBuffer>,
+        image: Handle<Image>,
+        format: Format,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+    ) -> Result<()> {
+        let filter_linear = FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+        if !self.is_format_supported(format, ImageTiling::Optimal, filter_linear) {
+            anyhow::bail!(
+                "{:?} doesn't support linear filtering, mipmaps can't be blitted.",
+                format
+            );
+        }
+        mipmaps::record(self, cb, image, width, height, mip_levels)
+    }
+    /// Scales `source`, a `source_size` color image in `ColorAttachmentOptimal`, onto the whole
+    /// back buffer `image_index` of `swapchain`, e.g. to render at a lower resolution than the
+    /// window. The back buffer ends up in `PresentSrcKhr` and `source` in `TransferSrcOptimal`.
+    ///
+    /// The submit has to wait for the acquired image at `COLOR_ATTACHMENT_OUTPUT` or earlier.
+    /// The blit is linear when the sizes differ and the format of `source` supports linear
+    /// filtering, nearest otherwise.
+    fn present_via_blit(
+        &self,
+        cb: Handle<CommandBuffer>,
+        source: Handle<Image>,
+        source_size: [u32; 2],
+        swapchain: Handle<Swapchain>,
+        image_index: usize,
+    ) -> Result<()> {
+        let target = self.get_swapchain_back_buffer(swapchain, image_index)?;
+        let (width, height) = self.get_swapchain_extent(swapchain)?;
+        let linear_supported = self.is_format_supported(
+            self.get_image_format(source)?,
+            ImageTiling::Optimal,
+            FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        );
+        let filter = present::blit_filter(source_size, [width, height], linear_supported);
+        present::record(self, cb, source, source_size, target, [width, height], filter)
+    }
     fn cmd_draw(
         &self,
         cb: Handle<CommandBuffer>,
@@ -325,6 +744,7 @@ pub trait RenderBackend {
         first_vertex: u32,
         first_instance: u32,
     ) -> Result<()>;
+    /// In debug builds, fails when the draw reads past the end of the bound index buffer.
     fn cmd_draw_indexed(
         &self,
         cb: Handle<CommandBuffer>,
@@ -335,6 +755,26 @@ pub trait RenderBackend {
         first_instance: u32,
     ) -> Result<()>;
 
+    /// Dispatches `group_x * group_y * group_z` work groups of the bound compute pipeline, must
+    /// be recorded outside of a render pass.
+    fn cmd_dispatch(
+        &self,
+        cb: Handle<CommandBuffer>,
+        group_x: u32,
+        group_y: u32,
+        group_z: u32,
+    ) -> Result<()>;
+
+    /// Dispatches with the group counts read from a `VkDispatchIndirectCommand`, three `u32`,
+    /// at `offset` in `buffer` when the command executes. The buffer needs
+    /// `BufferUsageFlags::INDIRECT_BUFFER` and the offset has to be a multiple of 4.
+    fn cmd_dispatch_indirect(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+    ) -> Result<()>;
+
     // Debug
     fn cmd_begin_event(
         &self,
@@ -343,6 +783,10 @@ pub trait RenderBackend {
         color: [f32; 4],
     ) -> Result<()>;
     fn cmd_end_event(&self, command_buffer: Handle<CommandBuffer>) -> Result<()>;
+    /// Opens a label on a queue, grouping every submit until `queue_end_event` in captures.
+    fn queue_begin_event(&self, queue: Handle<Queue>, name: &str, color: [f32; 4]) -> Result<()>;
+    fn queue_end_event(&self, queue: Handle<Queue>) -> Result<()>;
+    fn queue_insert_marker(&self, queue: Handle<Queue>, name: &str, color: [f32; 4]) -> Result<()>;
     fn cmd_set_marker(
         &self,
         command_buffer: Handle<CommandBuffer>,
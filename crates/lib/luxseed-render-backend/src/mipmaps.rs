@@ -0,0 +1,185 @@
+use anyhow::Result;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// Length of the full mip chain of a `width` by `height` image, down to 1x1.
+pub fn max_mip_levels(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+fn level_barrier(
+    image: Handle<Image>,
+    level: u32,
+    old_layout: ImageLayout,
+    new_layout: ImageLayout,
+    src_access_mask: AccessFlags,
+    dst_access_mask: AccessFlags,
+) -> ImageMemoryBarrier {
+    ImageMemoryBarrier {
+        base_mip_level: level,
+        level_count: 1,
+        ..ImageMemoryBarrier::whole_image(
+            image,
+            ImageAspectFlags::COLOR,
+            old_layout,
+            new_layout,
+            src_access_mask,
+            dst_access_mask,
+        )
+    }
+}
+
+/// Blits every level from the one above it, leaving each in `ShaderReadOnlyOptimal` once it
+/// has been read. All levels start in `TransferDstOptimal`, e.g. right after an upload.
+pub(crate) fn record<B: RenderBackend + ?Sized>(
+    backend: &B,
+    cb: Handle<CommandBuffer>,
+    image: Handle<Image>,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+) -> Result<()> {
+    if mip_levels == 0 || mip_levels > max_mip_levels(width, height) {
+        anyhow::bail!(
+            "A {}x{} image has 1 to {} mip levels, not {}.",
+            width,
+            height,
+            max_mip_levels(width, height),
+            mip_levels
+        );
+    }
+    for level in 1..mip_levels {
+        backend.cmd_pipeline_barrier(
+            cb,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::TRANSFER,
+            &[],
+            &[],
+            &[level_barrier(
+                image,
+                level - 1,
+                ImageLayout::TransferDstOptimal,
+                ImageLayout::TransferSrcOptimal,
+                AccessFlags::TRANSFER_WRITE,
+                AccessFlags::TRANSFER_READ,
+            )],
+        )?;
+        backend.cmd_blit_image(
+            cb,
+            &BlitImageDesc {
+                src: image,
+                src_image_layout: ImageLayout::TransferSrcOptimal,
+                dst: image,
+                dst_image_layout: ImageLayout::TransferDstOptimal,
+                regions: &[ImageBlitRegion::mip_level(width, height, level)],
+                filter: FilterType::Linear,
+            },
+        )?;
+        backend.cmd_pipeline_barrier(
+            cb,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            &[],
+            &[],
+            &[level_barrier(
+                image,
+                level - 1,
+                ImageLayout::TransferSrcOptimal,
+                ImageLayout::ShaderReadOnlyOptimal,
+                AccessFlags::TRANSFER_READ,
+                AccessFlags::SHADER_READ,
+            )],
+        )?;
+    }
+    // The last level is only written to
+    backend.cmd_pipeline_barrier(
+        cb,
+        PipelineStageFlags::TRANSFER,
+        PipelineStageFlags::FRAGMENT_SHADER,
+        &[],
+        &[],
+        &[level_barrier(
+            image,
+            mip_levels - 1,
+            ImageLayout::TransferDstOptimal,
+            ImageLayout::ShaderReadOnlyOptimal,
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::SHADER_READ,
+        )],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Call, RecordingBackend};
+
+    #[derive(Debug, PartialEq)]
+    enum MipCall {
+        /// Level and new layout.
+        Barrier(u32, ImageLayout),
+        /// Source and destination level, and the destination size.
+        Blit(u32, u32, [i32; 3]),
+    }
+
+    /// The barriers and blits of each level, in order.
+    fn mip_calls(backend: &RecordingBackend) -> Vec<MipCall> {
+        let mut calls = Vec::new();
+        for call in backend.calls().iter() {
+            match call {
+                Call::PipelineBarrier { image_barriers, .. } => {
+                    for barrier in image_barriers {
+                        assert_eq!(barrier.level_count, 1);
+                        calls.push(MipCall::Barrier(barrier.base_mip_level, barrier.new_layout));
+                    }
+                }
+                Call::BlitImage { regions, filter, .. } => {
+                    assert_eq!(*filter, FilterType::Linear);
+                    for region in regions {
+                        calls.push(MipCall::Blit(
+                            region.src_subresource.mip_level,
+                            region.dst_subresource.mip_level,
+                            region.dst_offsets[1],
+                        ));
+                    }
+                }
+                other => panic!("unexpected call {:?}", other),
+            }
+        }
+        calls
+    }
+
+    #[test]
+    fn blit_down_loop_of_a_full_chain() {
+        assert_eq!(max_mip_levels(256, 64), 9);
+        assert_eq!(max_mip_levels(1, 1), 1);
+        assert_eq!(max_mip_levels(1920, 1080), 11);
+
+        let backend = RecordingBackend::default();
+        record(&backend, Handle::default(), Handle::default(), 4, 2, 3).unwrap();
+        use ImageLayout::*;
+        assert_eq!(
+            mip_calls(&backend),
+            vec![
+                MipCall::Barrier(0, TransferSrcOptimal),
+                MipCall::Blit(0, 1, [2, 1, 1]),
+                MipCall::Barrier(0, ShaderReadOnlyOptimal),
+                MipCall::Barrier(1, TransferSrcOptimal),
+                MipCall::Blit(1, 2, [1, 1, 1]),
+                MipCall::Barrier(1, ShaderReadOnlyOptimal),
+                MipCall::Barrier(2, ShaderReadOnlyOptimal),
+            ]
+        );
+
+        // A single level only needs the final transition
+        let backend = RecordingBackend::default();
+        record(&backend, Handle::default(), Handle::default(), 4, 2, 1).unwrap();
+        assert_eq!(mip_calls(&backend), vec![MipCall::Barrier(0, ShaderReadOnlyOptimal)]);
+
+        let backend = RecordingBackend::default();
+        assert!(record(&backend, Handle::default(), Handle::default(), 4, 2, 4).is_err());
+        assert!(record(&backend, Handle::default(), Handle::default(), 4, 2, 0).is_err());
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// Filter scaling a `src_size` image to `dst_size`. Same sized blits copy texels as they are,
+/// linear filtering is only used when the source format supports it.
+pub fn blit_filter(src_size: [u32; 2], dst_size: [u32; 2], linear_supported: bool) -> FilterType {
+    if src_size == dst_size || !linear_supported {
+        FilterType::Nearest
+    } else {
+        FilterType::Linear
+    }
+}
+
+/// Blits the whole `source` onto the whole `target`. `source` goes from
+/// `ColorAttachmentOptimal` to `TransferSrcOptimal` and stays there, `target` is discarded and
+/// left in `PresentSrcKhr`.
+pub(crate) fn record<B: RenderBackend + ?Sized>(
+    backend: &B,
+    cb: Handle<CommandBuffer>,
+    source: Handle<Image>,
+    source_size: [u32; 2],
+    target: Handle<Image>,
+    target_size: [u32; 2],
+    filter: FilterType,
+) -> Result<()> {
+    // The acquire semaphore is waited on at the color attachment output stage, like for
+    // rendering into the back buffer
+    backend.cmd_pipeline_barrier(
+        cb,
+        PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        PipelineStageFlags::TRANSFER,
+        &[],
+        &[],
+        &[
+            ImageMemoryBarrier::whole_image(
+                source,
+                ImageAspectFlags::COLOR,
+                ImageLayout::ColorAttachmentOptimal,
+                ImageLayout::TransferSrcOptimal,
+                AccessFlags::COLOR_ATTACHMENT_WRITE,
+                AccessFlags::TRANSFER_READ,
+            ),
+            ImageMemoryBarrier::whole_image(
+                target,
+                ImageAspectFlags::COLOR,
+                ImageLayout::Undefined,
+                ImageLayout::TransferDstOptimal,
+                AccessFlags::empty(),
+                AccessFlags::TRANSFER_WRITE,
+            ),
+        ],
+    )?;
+    backend.cmd_blit_image(
+        cb,
+        &BlitImageDesc {
+            src: source,
+            src_image_layout: ImageLayout::TransferSrcOptimal,
+            dst: target,
+            dst_image_layout: ImageLayout::TransferDstOptimal,
+            regions: &[ImageBlitRegion::new(source_size, target_size)],
+            filter,
+        },
+    )?;
+    backend.cmd_pipeline_barrier(
+        cb,
+        PipelineStageFlags::TRANSFER,
+        PipelineStageFlags::BOTTOM_OF_PIPE,
+        &[],
+        &[],
+        &[ImageMemoryBarrier::whole_image(
+            target,
+            ImageAspectFlags::COLOR,
+            ImageLayout::TransferDstOptimal,
+            ImageLayout::PresentSrcKhr,
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::empty(),
+        )],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Call, RecordingBackend};
+
+    #[derive(Debug, PartialEq)]
+    enum PresentCall {
+        /// Old and new layout.
+        Barrier(ImageLayout, ImageLayout),
+        Blit(ImageBlitRegion, FilterType),
+    }
+
+    fn present_calls(backend: &RecordingBackend) -> Vec<PresentCall> {
+        let mut calls = Vec::new();
+        for call in backend.calls().iter() {
+            match call {
+                Call::PipelineBarrier { image_barriers, .. } => {
+                    for barrier in image_barriers {
+                        calls.push(PresentCall::Barrier(barrier.old_layout, barrier.new_layout));
+                    }
+                }
+                Call::BlitImage { src_layout, dst_layout, regions, filter, .. } => {
+                    assert_eq!(*src_layout, ImageLayout::TransferSrcOptimal);
+                    assert_eq!(*dst_layout, ImageLayout::TransferDstOptimal);
+                    for region in regions {
+                        calls.push(PresentCall::Blit(*region, *filter));
+                    }
+                }
+                other => panic!("unexpected call {:?}", other),
+            }
+        }
+        calls
+    }
+
+    #[test]
+    fn half_resolution_blit_to_the_back_buffer() {
+        assert_eq!(blit_filter([800, 450], [1600, 900], true), FilterType::Linear);
+        assert_eq!(blit_filter([800, 450], [1600, 900], false), FilterType::Nearest);
+        assert_eq!(blit_filter([1600, 900], [1600, 900], true), FilterType::Nearest);
+
+        let backend = RecordingBackend::default();
+        let filter = blit_filter([800, 450], [1600, 900], true);
+        let (source, target) = (Handle::default(), Handle::default());
+        record(&backend, Handle::default(), source, [800, 450], target, [1600, 900], filter)
+            .unwrap();
+        use ImageLayout::*;
+        let region = ImageBlitRegion {
+            src_subresource: ImageSubresourceLayers::color(0),
+            src_offsets: [[0, 0, 0], [800, 450, 1]],
+            dst_subresource: ImageSubresourceLayers::color(0),
+            dst_offsets: [[0, 0, 0], [1600, 900, 1]],
+        };
+        assert_eq!(
+            present_calls(&backend),
+            vec![
+                PresentCall::Barrier(ColorAttachmentOptimal, TransferSrcOptimal),
+                PresentCall::Barrier(Undefined, TransferDstOptimal),
+                PresentCall::Blit(region, FilterType::Linear),
+                PresentCall::Barrier(TransferDstOptimal, PresentSrcKhr),
+            ]
+        );
+    }
+}
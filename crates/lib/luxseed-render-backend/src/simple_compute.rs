@@ -0,0 +1,123 @@
+use anyhow::Result;
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, flag::*, RenderBackend};
+
+/// Everything a small compute kernel is dispatched with, made by
+/// `RenderBackend::create_simple_compute` and destroyed by
+/// `RenderBackend::destroy_simple_compute`.
+#[derive(Clone, Copy, Debug)]
+pub struct SimpleCompute {
+    /// The layout of `set = 0`, descriptor sets for the kernel are allocated with it.
+    pub descriptor_set_layout: Handle<DescriptorSetLayout>,
+    pub pipeline_layout: Handle<PipelineLayout>,
+    pub pipeline: Handle<ComputePipeline>,
+}
+
+/// Creates the set layout of `bindings`, a pipeline layout with a single compute push constant
+/// block of `push_constant_size` bytes, and the pipeline of `shader`.
+pub(crate) fn create<B: RenderBackend + ?Sized>(
+    backend: &mut B,
+    shader: Handle<Shader>,
+    bindings: &[DescriptorBindingInfo],
+    push_constant_size: u32,
+) -> Result<SimpleCompute> {
+    if !push_constant_size.is_multiple_of(4) {
+        anyhow::bail!(
+            "Push constant blocks are made of 4 byte words, {} bytes is not.",
+            push_constant_size
+        );
+    }
+    if let Some(binding) =
+        bindings.iter().find(|b| !b.stage_flags.contains(ShaderStageFlags::COMPUTE))
+    {
+        anyhow::bail!("Binding {} is not visible to the compute stage.", binding.index);
+    }
+
+    let descriptor_set_layout =
+        backend.create_descriptor_set_layout(&DescriptorSetLayoutCreateDesc {
+            bindings: bindings.iter().copied().collect(),
+        })?;
+    let push_constant_range = PushConstantRange {
+        stage_flags: ShaderStageFlags::COMPUTE,
+        offset: 0,
+        size: push_constant_size,
+    };
+    let push_constant_ranges: &[PushConstantRange] =
+        if push_constant_size == 0 { &[] } else { &[push_constant_range] };
+    let pipeline_layout = backend.create_pipeline_layout(&PipelineLayoutCreateDesc {
+        descriptor_set_layouts: &[descriptor_set_layout],
+        push_constant_ranges,
+    })?;
+    let pipeline = backend.create_compute_pipeline(&ComputePipelineCreateDesc {
+        shader,
+        pipeline_layout,
+        specialization: &[],
+    })?;
+
+    Ok(SimpleCompute { descriptor_set_layout, pipeline_layout, pipeline })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        enums::DescriptorType,
+        testing::{Call, RecordingBackend},
+    };
+
+    fn storage_buffer(stage_flags: ShaderStageFlags) -> DescriptorBindingInfo {
+        DescriptorBindingInfo {
+            index: 0,
+            type_: DescriptorType::StorageBuffer,
+            count: 1,
+            stage_flags,
+            flags: DescriptorBindingFlags::empty(),
+        }
+    }
+
+    #[test]
+    fn storage_buffer_and_push_constants() {
+        let mut backend = RecordingBackend::default();
+        let bindings = [storage_buffer(ShaderStageFlags::COMPUTE)];
+        let compute = create(&mut backend, Handle::default(), &bindings, 16).unwrap();
+
+        let range =
+            PushConstantRange { stage_flags: ShaderStageFlags::COMPUTE, offset: 0, size: 16 };
+        assert_eq!(
+            *backend.calls(),
+            vec![
+                Call::CreateDescriptorSetLayout {
+                    layout: compute.descriptor_set_layout,
+                    bindings: bindings.to_vec(),
+                },
+                Call::CreatePipelineLayout {
+                    layout: compute.pipeline_layout,
+                    descriptor_set_layouts: vec![compute.descriptor_set_layout],
+                    push_constant_ranges: vec![range],
+                },
+                Call::CreateComputePipeline {
+                    pipeline: compute.pipeline,
+                    shader: Handle::default(),
+                    pipeline_layout: compute.pipeline_layout,
+                    specialization_count: 0,
+                },
+            ]
+        );
+
+        // Without push constants the layout has no range at all
+        let mut backend = RecordingBackend::default();
+        create(&mut backend, Handle::default(), &bindings, 0).unwrap();
+        assert!(backend.calls().iter().any(|call| matches!(
+            call,
+            Call::CreatePipelineLayout { push_constant_ranges, .. } if push_constant_ranges.is_empty()
+        )));
+
+        let mut backend = RecordingBackend::default();
+        assert!(create(&mut backend, Handle::default(), &bindings, 6).is_err());
+        let fragment_only = [storage_buffer(ShaderStageFlags::FRAGMENT)];
+        assert!(create(&mut backend, Handle::default(), &fragment_only, 16).is_err());
+        // Nothing is created when the inputs are rejected
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -0,0 +1,964 @@
+use std::cell::{Ref, RefCell};
+
+use anyhow::Result;
+use luxseed_utility::pool::{Handle, Handled, Pool};
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// A recorded backend call, with the arguments tests look at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    CreateDevice,
+    DestroyDevice,
+    CreateFence(Handle<Fence>),
+    DestroyFence(Handle<Fence>),
+    WaitForFences(Vec<Handle<Fence>>),
+    QueueSubmit {
+        queue: Handle<Queue>,
+        command_buffers: Vec<Handle<CommandBuffer>>,
+        fence: Option<Handle<Fence>>,
+    },
+    CreateDescriptorSetLayout {
+        layout: Handle<DescriptorSetLayout>,
+        bindings: Vec<DescriptorBindingInfo>,
+    },
+    CreateBuffer {
+        buffer: Handle<Buffer>,
+        size: u64,
+        usage: BufferUsageFlags,
+    },
+    DestroyBuffer(Handle<Buffer>),
+    CreatePipelineLayout {
+        layout: Handle<PipelineLayout>,
+        descriptor_set_layouts: Vec<Handle<DescriptorSetLayout>>,
+        push_constant_ranges: Vec<PushConstantRange>,
+    },
+    CreateComputePipeline {
+        pipeline: Handle<ComputePipeline>,
+        shader: Handle<Shader>,
+        pipeline_layout: Handle<PipelineLayout>,
+        specialization_count: usize,
+    },
+    CreateCommandBuffer(Handle<CommandBuffer>),
+    DestroyCommandBuffer(Handle<CommandBuffer>),
+    Begin(Handle<CommandBuffer>),
+    End(Handle<CommandBuffer>),
+    PipelineBarrier {
+        cb: Handle<CommandBuffer>,
+        src_stage: PipelineStageFlags,
+        dst_stage: PipelineStageFlags,
+        memory_barriers: Vec<MemoryBarrier>,
+        buffer_barriers: Vec<BufferMemoryBarrier>,
+        image_barriers: Vec<ImageMemoryBarrier>,
+    },
+    CopyBuffer {
+        cb: Handle<CommandBuffer>,
+        src: Handle<Buffer>,
+        dst: Handle<Buffer>,
+        regions: Vec<BufferCopyRegion>,
+    },
+    CopyBufferToImage {
+        cb: Handle<CommandBuffer>,
+        src: Handle<Buffer>,
+        dst: Handle<Image>,
+        dst_layout: ImageLayout,
+        regions: Vec<BufferImageCopyRegion>,
+    },
+    BlitImage {
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_layout: ImageLayout,
+        regions: Vec<ImageBlitRegion>,
+        filter: FilterType,
+    },
+    ResolveImage {
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_layout: ImageLayout,
+        regions: Vec<ImageResolveRegion>,
+    },
+}
+
+/// A `RenderBackend` that records what it is asked to do instead of talking to a GPU, for
+/// testing code built on top of the backend. Created resources get distinct handles, the
+/// other calls tests don't need panic.
+pub struct RecordingBackend {
+    /// Format `get_image_format` reports for every image.
+    pub image_format: Format,
+    /// Mip count `get_image_mip_levels` reports for every image.
+    pub image_mip_levels: u32,
+    /// What every `wait_for_fences` ends with.
+    pub fence_wait_result: FenceWaitResult,
+    /// Makes `create_device` fail after recording the attempt.
+    pub fail_device_creation: bool,
+    calls: RefCell<Vec<Call>>,
+    resources: Resources,
+}
+
+impl Default for RecordingBackend {
+    fn default() -> Self {
+        Self {
+            image_format: Format::R8G8B8A8_UNORM,
+            image_mip_levels: 1,
+            fence_wait_result: FenceWaitResult::Signaled,
+            fail_device_creation: false,
+            calls: RefCell::default(),
+            resources: Resources::default(),
+        }
+    }
+}
+
+impl RecordingBackend {
+    /// The calls so far, in order.
+    pub fn calls(&self) -> Ref<'_, Vec<Call>> {
+        self.calls.borrow()
+    }
+
+    /// Buffers created and not destroyed yet.
+    pub fn live_buffers(&self) -> usize {
+        self.resources.buffers.len()
+    }
+
+    /// Command buffers created and not destroyed yet.
+    pub fn live_command_buffers(&self) -> usize {
+        self.resources.command_buffers.len()
+    }
+
+    fn record(&self, call: Call) -> Result<()> {
+        self.calls.borrow_mut().push(call);
+        Ok(())
+    }
+}
+
+/// Hands out the handles of one resource type.
+struct Slot<T> {
+    handle: Option<Handle<T>>,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self { handle: None }
+    }
+}
+
+impl<T: Copy> Handled for Slot<T> {
+    type HandleType = T;
+
+    fn get_handle(&self) -> Option<Handle<T>> {
+        self.handle
+    }
+
+    fn set_handle(&mut self, handle: Option<Handle<T>>) {
+        self.handle = handle;
+    }
+}
+
+/// The live resources of the current device.
+struct Resources {
+    fences: Pool<Slot<Fence>>,
+    buffers: Pool<Slot<Buffer>>,
+    command_buffers: Pool<Slot<CommandBuffer>>,
+    descriptor_set_layouts: Pool<Slot<DescriptorSetLayout>>,
+    pipeline_layouts: Pool<Slot<PipelineLayout>>,
+    compute_pipelines: Pool<Slot<ComputePipeline>>,
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self {
+            fences: Pool::with_size(8, Default::default),
+            buffers: Pool::with_size(64, Default::default),
+            command_buffers: Pool::with_size(8, Default::default),
+            descriptor_set_layouts: Pool::with_size(8, Default::default),
+            pipeline_layouts: Pool::with_size(8, Default::default),
+            compute_pipelines: Pool::with_size(8, Default::default),
+        }
+    }
+}
+
+impl RenderBackend for RecordingBackend {
+    fn get_backend_type(&self) -> BackendType {
+        unimplemented!("get_backend_type")
+    }
+
+    fn get_supported_format_from_candidates(
+        &self,
+        _: &[Format],
+        _: ImageTiling,
+        _: FormatFeatureFlags,
+    ) -> Result<Format> {
+        unimplemented!("get_supported_format_from_candidates")
+    }
+
+    fn is_format_supported(&self, _: Format, _: ImageTiling, _: FormatFeatureFlags) -> bool {
+        unimplemented!("is_format_supported")
+    }
+
+    fn enumerate_adapter_infos(&self) -> &[AdapterInfo] {
+        unimplemented!("enumerate_adapter_infos")
+    }
+
+    fn is_device_created(&self) -> bool {
+        unimplemented!("is_device_created")
+    }
+
+    fn create_device(&mut self, _: &DeviceCreateDesc) -> Result<()> {
+        self.record(Call::CreateDevice)?;
+        if self.fail_device_creation {
+            anyhow::bail!("No adapter left.");
+        }
+        Ok(())
+    }
+
+    fn enabled_features(&self) -> Result<&EnabledFeatures> {
+        unimplemented!("enabled_features")
+    }
+
+    fn destroy_device(&mut self) -> Result<()> {
+        // Resources die with their device
+        self.resources = Resources::default();
+        self.record(Call::DestroyDevice)
+    }
+
+    fn device_wait_idle(&self) -> Result<()> {
+        unimplemented!("device_wait_idle")
+    }
+
+    fn is_device_lost(&self) -> bool {
+        unimplemented!("is_device_lost")
+    }
+
+    fn debug_report_live_resources(&self) -> LiveResourceReport {
+        unimplemented!("debug_report_live_resources")
+    }
+
+    fn create_fence(&mut self, _: bool) -> Result<Handle<Fence>> {
+        let fence = self.resources.fences.malloc().0;
+        self.record(Call::CreateFence(fence))?;
+        Ok(fence)
+    }
+
+    fn destroy_fence(&mut self, fence: Handle<Fence>) -> Result<()> {
+        self.resources.fences.free(fence);
+        self.record(Call::DestroyFence(fence))
+    }
+
+    fn wait_for_fences(
+        &self,
+        fences: &[Handle<Fence>],
+        _: bool,
+        _: u64,
+    ) -> Result<FenceWaitResult> {
+        self.record(Call::WaitForFences(fences.to_vec()))?;
+        Ok(self.fence_wait_result)
+    }
+
+    fn reset_fences(&self, _: &[Handle<Fence>]) -> Result<()> {
+        unimplemented!("reset_fences")
+    }
+
+    fn get_fence_status(&self, _: Handle<Fence>) -> Result<bool> {
+        unimplemented!("get_fence_status")
+    }
+
+    fn create_semaphore(&mut self) -> Result<Handle<Semaphore>> {
+        unimplemented!("create_semaphore")
+    }
+
+    fn destroy_semaphore(&mut self, _: Handle<Semaphore>) -> Result<()> {
+        unimplemented!("destroy_semaphore")
+    }
+
+    fn create_timeline_semaphore(&mut self, _: u64) -> Result<Handle<Semaphore>> {
+        unimplemented!("create_timeline_semaphore")
+    }
+
+    fn wait_timeline_semaphore(
+        &self,
+        _: Handle<Semaphore>,
+        _: u64,
+        _: u64,
+    ) -> Result<FenceWaitResult> {
+        unimplemented!("wait_timeline_semaphore")
+    }
+
+    fn wait_semaphores(&self, _: &[(Handle<Semaphore>, u64)], _: u64) -> Result<FenceWaitResult> {
+        unimplemented!("wait_semaphores")
+    }
+
+    fn signal_timeline_semaphore(&self, _: Handle<Semaphore>, _: u64) -> Result<()> {
+        unimplemented!("signal_timeline_semaphore")
+    }
+
+    fn get_timeline_value(&self, _: Handle<Semaphore>) -> Result<u64> {
+        unimplemented!("get_timeline_value")
+    }
+
+    fn get_queue(&self, _: QueueType) -> Result<Handle<Queue>> {
+        unimplemented!("get_queue")
+    }
+
+    fn queue_submit(&self, queue: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()> {
+        self.record(Call::QueueSubmit {
+            queue,
+            command_buffers: desc.command_buffer.to_vec(),
+            fence: desc.fence,
+        })
+    }
+
+    fn queue_submit_batch(&self, _: Handle<Queue>, _: &[QueueSubmitDesc]) -> Result<()> {
+        unimplemented!("queue_submit_batch")
+    }
+
+    fn queue_present(&self, _: Handle<Queue>, _: &QueuePresentDesc) -> Result<bool> {
+        unimplemented!("queue_present")
+    }
+
+    fn queue_wait_idle(&self, _: Handle<Queue>) -> Result<()> {
+        unimplemented!("queue_wait_idle")
+    }
+
+    fn create_surface(&mut self, _: SurfaceCreateDesc) -> Result<Handle<Surface>> {
+        unimplemented!("create_surface")
+    }
+
+    fn destroy_surface(&mut self, _: Handle<Surface>) -> Result<()> {
+        unimplemented!("destroy_surface")
+    }
+
+    fn create_swapchain(&mut self, _: SwapchainCreateDesc) -> Result<Handle<Swapchain>> {
+        unimplemented!("create_swapchain")
+    }
+
+    fn acquire_swapchain_next_image(
+        &self,
+        _: Handle<Swapchain>,
+        _: u64,
+        _: Handle<Semaphore>,
+        _: Option<Handle<Fence>>,
+    ) -> Result<(usize, bool)> {
+        unimplemented!("acquire_swapchain_next_image")
+    }
+
+    fn get_swapchain_back_buffer(&self, _: Handle<Swapchain>, _: usize) -> Result<Handle<Image>> {
+        unimplemented!("get_swapchain_back_buffer")
+    }
+
+    fn get_swapchain_image_count(&self, _: Handle<Swapchain>) -> Result<u8> {
+        unimplemented!("get_swapchain_image_count")
+    }
+
+    fn get_swapchain_extent(&self, _: Handle<Swapchain>) -> Result<(u32, u32)> {
+        unimplemented!("get_swapchain_extent")
+    }
+
+    fn get_swapchain_format(&self, _: Handle<Swapchain>) -> Result<Format> {
+        unimplemented!("get_swapchain_format")
+    }
+
+    fn destroy_swapchain(&mut self, _: Handle<Swapchain>) -> Result<()> {
+        unimplemented!("destroy_swapchain")
+    }
+
+    fn create_descriptor_set_layout(
+        &mut self,
+        desc: &DescriptorSetLayoutCreateDesc,
+    ) -> Result<Handle<DescriptorSetLayout>> {
+        let layout = self.resources.descriptor_set_layouts.malloc().0;
+        self.record(Call::CreateDescriptorSetLayout { layout, bindings: desc.bindings.to_vec() })?;
+        Ok(layout)
+    }
+
+    fn destroy_descriptor_set_layout(&mut self, _: Handle<DescriptorSetLayout>) -> Result<()> {
+        unimplemented!("destroy_descriptor_set_layout")
+    }
+
+    fn create_descriptor_pool(
+        &mut self,
+        _: &DescriptorPoolCreateDesc,
+    ) -> Result<Handle<DescriptorPool>> {
+        unimplemented!("create_descriptor_pool")
+    }
+
+    fn destroy_descriptor_pool(&mut self, _: Handle<DescriptorPool>) -> Result<()> {
+        unimplemented!("destroy_descriptor_pool")
+    }
+
+    fn create_descriptor_set(
+        &mut self,
+        _: &DescriptorSetCreateDesc,
+    ) -> Result<Handle<DescriptorSet>> {
+        unimplemented!("create_descriptor_set")
+    }
+
+    fn update_descriptor_set(
+        &mut self,
+        _: Handle<DescriptorSet>,
+        _: &[DescriptorBindingData],
+    ) -> Result<()> {
+        unimplemented!("update_descriptor_set")
+    }
+
+    fn destroy_descriptor_sets(&mut self, _: &[Handle<DescriptorSet>]) -> Result<()> {
+        unimplemented!("destroy_descriptor_sets")
+    }
+
+    fn create_image(&mut self, _: &ImageCreateDesc) -> Result<Handle<Image>> {
+        unimplemented!("create_image")
+    }
+
+    fn destroy_image(&mut self, _: Handle<Image>) -> Result<()> {
+        unimplemented!("destroy_image")
+    }
+
+    fn get_image_format(&self, _: Handle<Image>) -> Result<Format> {
+        Ok(self.image_format)
+    }
+
+    fn get_image_mip_levels(&self, _: Handle<Image>) -> Result<u32> {
+        Ok(self.image_mip_levels)
+    }
+
+    fn create_image_view(
+        &mut self,
+        _: Handle<Image>,
+        _: &ImageViewCreateDesc,
+    ) -> Result<Handle<ImageView>> {
+        unimplemented!("create_image_view")
+    }
+
+    fn destroy_image_view(&mut self, _: Handle<ImageView>) -> Result<()> {
+        unimplemented!("destroy_image_view")
+    }
+
+    fn create_sampler(&mut self, _: &SamplerCreateDesc) -> Result<Handle<Sampler>> {
+        unimplemented!("create_sampler")
+    }
+
+    fn destroy_sampler(&mut self, _: Handle<Sampler>) -> Result<()> {
+        unimplemented!("destroy_sampler")
+    }
+
+    fn create_shader_module(&mut self, _: &ShaderModuleCreation) -> Result<Handle<Shader>> {
+        unimplemented!("create_shader_module")
+    }
+
+    fn destroy_shader_module(&mut self, _: Handle<Shader>) -> Result<()> {
+        unimplemented!("destroy_shader_module")
+    }
+
+    fn reflect_descriptor_layouts(
+        &self,
+        _: &[Handle<Shader>],
+    ) -> Result<Vec<DescriptorSetLayoutCreateDesc>> {
+        unimplemented!("reflect_descriptor_layouts")
+    }
+
+    fn create_buffer(&mut self, desc: &BufferCreateDesc) -> Result<Handle<Buffer>> {
+        let buffer = self.resources.buffers.malloc().0;
+        self.record(Call::CreateBuffer { buffer, size: desc.size, usage: desc.usage })?;
+        Ok(buffer)
+    }
+
+    fn destroy_buffer(&mut self, buffer: Handle<Buffer>) -> Result<()> {
+        self.resources.buffers.free(buffer);
+        self.record(Call::DestroyBuffer(buffer))
+    }
+
+    fn get_buffer_mapped_slice_mut(&mut self, _: Handle<Buffer>) -> Result<&mut [u8]> {
+        unimplemented!("get_buffer_mapped_slice_mut")
+    }
+
+    fn flush_buffer(&self, _: Handle<Buffer>, _: u64, _: u64) -> Result<()> {
+        unimplemented!("flush_buffer")
+    }
+
+    fn invalidate_buffer(&self, _: Handle<Buffer>, _: u64, _: u64) -> Result<()> {
+        unimplemented!("invalidate_buffer")
+    }
+
+    fn create_buffer_view(
+        &mut self,
+        _: Handle<Buffer>,
+        _: Format,
+        _: u64,
+        _: u64,
+    ) -> Result<Handle<BufferView>> {
+        unimplemented!("create_buffer_view")
+    }
+
+    fn destroy_buffer_view(&mut self, _: Handle<BufferView>) -> Result<()> {
+        unimplemented!("destroy_buffer_view")
+    }
+
+    fn get_buffer_device_address(&self, _: Handle<Buffer>) -> Result<u64> {
+        unimplemented!("get_buffer_device_address")
+    }
+
+    fn create_acceleration_structure(
+        &mut self,
+        _: &AccelerationStructureCreateDesc,
+    ) -> Result<Handle<AccelerationStructure>> {
+        unimplemented!("create_acceleration_structure")
+    }
+
+    fn destroy_acceleration_structure(&mut self, _: Handle<AccelerationStructure>) -> Result<()> {
+        unimplemented!("destroy_acceleration_structure")
+    }
+
+    fn get_acceleration_structure_device_address(
+        &self,
+        _: Handle<AccelerationStructure>,
+    ) -> Result<u64> {
+        unimplemented!("get_acceleration_structure_device_address")
+    }
+
+    fn create_pipeline_layout(
+        &mut self,
+        desc: &PipelineLayoutCreateDesc,
+    ) -> Result<Handle<PipelineLayout>> {
+        let layout = self.resources.pipeline_layouts.malloc().0;
+        self.record(Call::CreatePipelineLayout {
+            layout,
+            descriptor_set_layouts: desc.descriptor_set_layouts.to_vec(),
+            push_constant_ranges: desc.push_constant_ranges.to_vec(),
+        })?;
+        Ok(layout)
+    }
+
+    fn destroy_pipeline_layout(&mut self, _: Handle<PipelineLayout>) -> Result<()> {
+        unimplemented!("destroy_pipeline_layout")
+    }
+
+    fn create_raster_pipeline(
+        &mut self,
+        _: &RasterPipelineCreateDesc,
+    ) -> Result<Handle<RasterPipeline>> {
+        unimplemented!("create_raster_pipeline")
+    }
+
+    fn create_raster_pipelines(
+        &mut self,
+        _: &[RasterPipelineCreateDesc],
+    ) -> Result<Vec<Handle<RasterPipeline>>> {
+        unimplemented!("create_raster_pipelines")
+    }
+
+    fn destroy_raster_pipeline(&mut self, _: Handle<RasterPipeline>) -> Result<()> {
+        unimplemented!("destroy_raster_pipeline")
+    }
+
+    fn create_compute_pipeline(
+        &mut self,
+        desc: &ComputePipelineCreateDesc,
+    ) -> Result<Handle<ComputePipeline>> {
+        let pipeline = self.resources.compute_pipelines.malloc().0;
+        self.record(Call::CreateComputePipeline {
+            pipeline,
+            shader: desc.shader,
+            pipeline_layout: desc.pipeline_layout,
+            specialization_count: desc.specialization.len(),
+        })?;
+        Ok(pipeline)
+    }
+
+    fn destroy_compute_pipeline(&mut self, _: Handle<ComputePipeline>) -> Result<()> {
+        unimplemented!("destroy_compute_pipeline")
+    }
+
+    fn create_render_pass(&mut self, _: &RenderPassOutput) -> Result<Handle<RenderPass>> {
+        unimplemented!("create_render_pass")
+    }
+
+    fn destroy_render_pass(&mut self, _: Handle<RenderPass>) -> Result<()> {
+        unimplemented!("destroy_render_pass")
+    }
+
+    fn create_framebuffer(&mut self, _: &FramebufferCreateDesc) -> Result<Handle<Framebuffer>> {
+        unimplemented!("create_framebuffer")
+    }
+
+    fn destroy_framebuffer(&mut self, _: Handle<Framebuffer>) -> Result<()> {
+        unimplemented!("destroy_framebuffer")
+    }
+
+    fn create_command_pool(&mut self, _: Handle<Queue>) -> Result<Handle<CommandPool>> {
+        unimplemented!("create_command_pool")
+    }
+
+    fn reset_command_pool(&self, _: Handle<CommandPool>) -> Result<()> {
+        unimplemented!("reset_command_pool")
+    }
+
+    fn destroy_command_pool(&mut self, _: Handle<CommandPool>) -> Result<()> {
+        unimplemented!("destroy_command_pool")
+    }
+
+    fn create_command_buffer(
+        &mut self,
+        _: Handle<CommandPool>,
+        _: CommandBufferLevel,
+    ) -> Result<Handle<CommandBuffer>> {
+        let cb = self.resources.command_buffers.malloc().0;
+        self.record(Call::CreateCommandBuffer(cb))?;
+        Ok(cb)
+    }
+
+    fn reset_command_buffer(&self, _: Handle<CommandBuffer>, _: bool) -> Result<()> {
+        unimplemented!("reset_command_buffer")
+    }
+
+    fn destroy_command_buffer(&mut self, cb: Handle<CommandBuffer>) -> Result<()> {
+        self.resources.command_buffers.free(cb);
+        self.record(Call::DestroyCommandBuffer(cb))
+    }
+
+    fn cmd_begin(&self, cb: Handle<CommandBuffer>, _: CommandBufferBeginDesc) -> Result<()> {
+        self.record(Call::Begin(cb))
+    }
+
+    fn cmd_end(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        self.record(Call::End(cb))
+    }
+
+    fn cmd_begin_render_pass(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<RenderPass>,
+        _: Handle<Framebuffer>,
+        _: Option<&[ClearColor]>,
+        _: Option<ClearDepthStencil>,
+        _: SubpassContents,
+    ) -> Result<()> {
+        unimplemented!("cmd_begin_render_pass")
+    }
+
+    fn cmd_next_subpass(&self, _: Handle<CommandBuffer>, _: SubpassContents) -> Result<()> {
+        unimplemented!("cmd_next_subpass")
+    }
+
+    fn cmd_end_render_pass(&self, _: Handle<CommandBuffer>) -> Result<()> {
+        unimplemented!("cmd_end_render_pass")
+    }
+
+    fn cmd_begin_rendering(&self, _: Handle<CommandBuffer>, _: &RenderingInfo) -> Result<()> {
+        unimplemented!("cmd_begin_rendering")
+    }
+
+    fn cmd_end_rendering(&self, _: Handle<CommandBuffer>) -> Result<()> {
+        unimplemented!("cmd_end_rendering")
+    }
+
+    fn cmd_execute_commands(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: &[Handle<CommandBuffer>],
+    ) -> Result<()> {
+        unimplemented!("cmd_execute_commands")
+    }
+
+    fn cmd_bind_raster_pipeline(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<RasterPipeline>,
+    ) -> Result<()> {
+        unimplemented!("cmd_bind_raster_pipeline")
+    }
+
+    fn cmd_bind_compute_pipeline(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<ComputePipeline>,
+    ) -> Result<()> {
+        unimplemented!("cmd_bind_compute_pipeline")
+    }
+
+    fn cmd_set_viewport(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: f32,
+        _: f32,
+        _: f32,
+        _: f32,
+        _: f32,
+        _: f32,
+    ) -> Result<()> {
+        unimplemented!("cmd_set_viewport")
+    }
+
+    fn cmd_set_scissor(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: u32,
+    ) -> Result<()> {
+        unimplemented!("cmd_set_scissor")
+    }
+
+    fn cmd_set_viewports(&self, _: Handle<CommandBuffer>, _: u32, _: &[Viewport]) -> Result<()> {
+        unimplemented!("cmd_set_viewports")
+    }
+
+    fn cmd_set_scissors(&self, _: Handle<CommandBuffer>, _: u32, _: &[Rect2D]) -> Result<()> {
+        unimplemented!("cmd_set_scissors")
+    }
+
+    fn cmd_set_depth_bias(&self, _: Handle<CommandBuffer>, _: f32, _: f32, _: f32) -> Result<()> {
+        unimplemented!("cmd_set_depth_bias")
+    }
+
+    fn cmd_set_line_width(&self, _: Handle<CommandBuffer>, _: f32) -> Result<()> {
+        unimplemented!("cmd_set_line_width")
+    }
+
+    fn cmd_set_blend_constants(&self, _: Handle<CommandBuffer>, _: [f32; 4]) -> Result<()> {
+        unimplemented!("cmd_set_blend_constants")
+    }
+
+    fn cmd_pipeline_barrier(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src_stage: PipelineStageFlags,
+        dst_stage: PipelineStageFlags,
+        memory_barriers: &[MemoryBarrier],
+        buffer_barriers: &[BufferMemoryBarrier],
+        image_barriers: &[ImageMemoryBarrier],
+    ) -> Result<()> {
+        self.record(Call::PipelineBarrier {
+            cb,
+            src_stage,
+            dst_stage,
+            memory_barriers: memory_barriers.to_vec(),
+            buffer_barriers: buffer_barriers.to_vec(),
+            image_barriers: image_barriers.to_vec(),
+        })
+    }
+
+    fn cmd_bind_descriptor_sets(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: PipelineBindPoint,
+        _: Handle<PipelineLayout>,
+        _: u32,
+        _: &[Handle<DescriptorSet>],
+        _: &[u32],
+    ) -> Result<()> {
+        unimplemented!("cmd_bind_descriptor_sets")
+    }
+
+    fn cmd_push_constants(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<PipelineLayout>,
+        _: ShaderStageFlags,
+        _: u32,
+        _: &[u8],
+    ) -> Result<()> {
+        unimplemented!("cmd_push_constants")
+    }
+
+    fn cmd_bind_vertex_buffers(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: u32,
+        _: &[Handle<Buffer>],
+        _: &[u64],
+    ) -> Result<()> {
+        unimplemented!("cmd_bind_vertex_buffers")
+    }
+
+    fn cmd_bind_index_buffer(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Buffer>,
+        _: u64,
+        _: IndexType,
+    ) -> Result<()> {
+        unimplemented!("cmd_bind_index_buffer")
+    }
+
+    fn cmd_copy_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Buffer>,
+        dst: Handle<Buffer>,
+        regions: &[BufferCopyRegion],
+    ) -> Result<()> {
+        self.record(Call::CopyBuffer { cb, src, dst, regions: regions.to_vec() })
+    }
+
+    fn cmd_update_buffer(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Buffer>,
+        _: u64,
+        _: &[u8],
+    ) -> Result<()> {
+        unimplemented!("cmd_update_buffer")
+    }
+
+    fn cmd_fill_buffer(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Buffer>,
+        _: u64,
+        _: u64,
+        _: u32,
+    ) -> Result<()> {
+        unimplemented!("cmd_fill_buffer")
+    }
+
+    fn cmd_build_acceleration_structures(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: &[Handle<AccelerationStructure>],
+    ) -> Result<()> {
+        unimplemented!("cmd_build_acceleration_structures")
+    }
+
+    fn cmd_copy_buffer_to_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Buffer>,
+        dst: Handle<Image>,
+        dst_layout: ImageLayout,
+        regions: &[BufferImageCopyRegion],
+    ) -> Result<()> {
+        self.record(Call::CopyBufferToImage { cb, src, dst, dst_layout, regions: regions.to_vec() })
+    }
+
+    fn cmd_copy_image_to_buffer(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Image>,
+        _: ImageLayout,
+        _: Handle<Buffer>,
+        _: &[BufferImageCopyRegion],
+    ) -> Result<()> {
+        unimplemented!("cmd_copy_image_to_buffer")
+    }
+
+    fn cmd_copy_image(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Image>,
+        _: ImageLayout,
+        _: Handle<Image>,
+        _: ImageLayout,
+        _: &[ImageCopyRegion],
+    ) -> Result<()> {
+        unimplemented!("cmd_copy_image")
+    }
+
+    fn cmd_clear_color_image(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Image>,
+        _: ImageLayout,
+        _: ClearColor,
+        _: &[ImageSubresourceRange],
+    ) -> Result<()> {
+        unimplemented!("cmd_clear_color_image")
+    }
+
+    fn cmd_clear_depth_stencil_image(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Image>,
+        _: ImageLayout,
+        _: ClearDepthStencil,
+        _: &[ImageSubresourceRange],
+    ) -> Result<()> {
+        unimplemented!("cmd_clear_depth_stencil_image")
+    }
+
+    fn cmd_resolve_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_layout: ImageLayout,
+        regions: &[ImageResolveRegion],
+    ) -> Result<()> {
+        self.record(Call::ResolveImage {
+            cb,
+            src,
+            src_layout,
+            dst,
+            dst_layout,
+            regions: regions.to_vec(),
+        })
+    }
+
+    fn cmd_blit_image(&self, cb: Handle<CommandBuffer>, desc: &BlitImageDesc) -> Result<()> {
+        self.record(Call::BlitImage {
+            cb,
+            src: desc.src,
+            src_layout: desc.src_image_layout,
+            dst: desc.dst,
+            dst_layout: desc.dst_image_layout,
+            regions: desc.regions.to_vec(),
+            filter: desc.filter,
+        })
+    }
+
+    fn cmd_draw(&self, _: Handle<CommandBuffer>, _: u32, _: u32, _: u32, _: u32) -> Result<()> {
+        unimplemented!("cmd_draw")
+    }
+
+    fn cmd_draw_indexed(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: u32,
+        _: u32,
+        _: u32,
+        _: i32,
+        _: u32,
+    ) -> Result<()> {
+        unimplemented!("cmd_draw_indexed")
+    }
+
+    fn cmd_dispatch(&self, _: Handle<CommandBuffer>, _: u32, _: u32, _: u32) -> Result<()> {
+        unimplemented!("cmd_dispatch")
+    }
+
+    fn cmd_dispatch_indirect(
+        &self,
+        _: Handle<CommandBuffer>,
+        _: Handle<Buffer>,
+        _: u64,
+    ) -> Result<()> {
+        unimplemented!("cmd_dispatch_indirect")
+    }
+
+    fn cmd_begin_event(&self, _: Handle<CommandBuffer>, _: &str, _: [f32; 4]) -> Result<()> {
+        unimplemented!("cmd_begin_event")
+    }
+
+    fn cmd_end_event(&self, _: Handle<CommandBuffer>) -> Result<()> {
+        unimplemented!("cmd_end_event")
+    }
+
+    fn queue_begin_event(&self, _: Handle<Queue>, _: &str, _: [f32; 4]) -> Result<()> {
+        unimplemented!("queue_begin_event")
+    }
+
+    fn queue_end_event(&self, _: Handle<Queue>) -> Result<()> {
+        unimplemented!("queue_end_event")
+    }
+
+    fn queue_insert_marker(&self, _: Handle<Queue>, _: &str, _: [f32; 4]) -> Result<()> {
+        unimplemented!("queue_insert_marker")
+    }
+
+    fn cmd_set_marker(&self, _: Handle<CommandBuffer>, _: &str, _: [f32; 4]) -> Result<()> {
+        unimplemented!("cmd_set_marker")
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::{bail, Result};
+use luxseed_utility::pool::Handle;
+
+use crate::{define::*, enums::*, flag::*, RenderBackend};
+
+/// Records staging uploads into a single command buffer, then submits and waits for all of them
+/// at once instead of stalling the GPU after every upload.
+///
+/// Staging buffers are kept alive until `finish` has waited for the submit, `finish` releases them
+/// and the command buffer even when the submit fails. Images are left in
+/// `ImageLayout::ShaderReadOnlyOptimal`.
+pub struct UploadContext {
+    queue: Handle<Queue>,
+    cb: Handle<CommandBuffer>,
+    staging: Vec<Handle<Buffer>>,
+}
+
+impl UploadContext {
+    /// Starts recording into a new command buffer from `command_pool`, which must belong to
+    /// `queue`'s family.
+    pub fn begin(
+        backend: &mut dyn RenderBackend,
+        command_pool: Handle<CommandPool>,
+        queue: Handle<Queue>,
+    ) -> Result<Self> {
+        let cb = backend.create_command_buffer(command_pool, CommandBufferLevel::Primary)?;
+        backend.cmd_begin(
+            cb,
+            CommandBufferBeginDesc { one_time_submit: true, ..Default::default() },
+        )?;
+        Ok(Self { queue, cb, staging: Vec::new() })
+    }
+
+    /// Copies `data` to the start of `buffer`, which needs `BufferUsageFlags::TRANSFER_DST`.
+    pub fn upload_buffer(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        buffer: Handle<Buffer>,
+        data: &[u8],
+    ) -> Result<()> {
+        let staging = self.create_staging(backend, data)?;
+        backend.cmd_copy_buffer(
+            self.cb,
+            staging,
+            buffer,
+            &[BufferCopyRegion { size: data.len() as u64, ..Default::default() }],
+        )
+    }
+
+    /// Copies tightly packed `data` into the first mip level and layer of a 2D color `image`.
+    pub fn upload_image(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        image: Handle<Image>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let aspects = backend.get_image_format(image)?.aspect_mask();
+        // A copy writes one aspect, the depth of a depth stencil image
+        let aspect_mask = if aspects.contains(ImageAspectFlags::DEPTH) {
+            ImageAspectFlags::DEPTH
+        } else {
+            aspects
+        };
+        let region =
+            BufferImageCopyRegion { aspect_mask, ..BufferImageCopyRegion::color(width, height) };
+        let staging = self.create_staging(backend, data)?;
+        self.copy_buffer_to_image(backend, staging, image, &[region])
+    }
+
+    /// Copies `mip_levels` levels of block compressed `data` into the first layer of a 2D
+    /// `image`, mip 0 first and each level right after the previous one, in whole 4x4 blocks.
+    /// KTX2 files store the smallest level first, take the levels in the order of the file's
+    /// level index. Fails when the image has fewer than `mip_levels` levels.
+    pub fn upload_compressed_image(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        image: Handle<Image>,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+    ) -> Result<()> {
+        let format = backend.get_image_format(image)?;
+        if !format.is_compressed() {
+            bail!("upload_compressed_image needs a block compressed image, got {:?}.", format);
+        }
+        let image_mip_levels = backend.get_image_mip_levels(image)?;
+        if mip_levels == 0 || mip_levels > image_mip_levels {
+            bail!("Can't upload {} mip levels to an image with {}.", mip_levels, image_mip_levels);
+        }
+        let regions = BufferImageCopyRegion::color_mips(format, width, height, mip_levels);
+        let size = regions.last().map_or(0, |last| {
+            last.buffer_offset + format.level_size(last.image_extent[0], last.image_extent[1])
+        });
+        if data.len() as u64 != size {
+            bail!(
+                "{} mip levels of a {}x{} {:?} image are {} bytes, got {}.",
+                mip_levels,
+                width,
+                height,
+                format,
+                size,
+                data.len()
+            );
+        }
+        let staging = self.create_staging(backend, data)?;
+        self.copy_buffer_to_image(backend, staging, image, &regions)
+    }
+
+    /// Submits everything recorded, waits for it and releases the staging buffers. Fails unless
+    /// the submit's fence is signaled.
+    pub fn finish(self, backend: &mut dyn RenderBackend) -> Result<()> {
+        let submitted = self.submit_and_wait(backend);
+        // Released on every path, the first error is reported
+        let mut released = backend.destroy_command_buffer(self.cb);
+        for staging in self.staging {
+            released = released.and(backend.destroy_buffer(staging));
+        }
+        submitted.and(released)
+    }
+
+    fn create_staging(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        data: &[u8],
+    ) -> Result<Handle<Buffer>> {
+        let staging = backend.create_buffer(&BufferCreateDesc {
+            name: "Upload Staging Buffer",
+            size: data.len() as u64,
+            usage: BufferUsageFlags::TRANSFER_SRC,
+            memory: MemoryLocation::CpuToGpu,
+            initial_data: Some(data),
+        })?;
+        self.staging.push(staging);
+        Ok(staging)
+    }
+
+    /// Copies `regions` into `dst`, which ends up in `ImageLayout::ShaderReadOnlyOptimal`.
+    fn copy_buffer_to_image(
+        &self,
+        backend: &dyn RenderBackend,
+        src: Handle<Buffer>,
+        dst: Handle<Image>,
+        regions: &[BufferImageCopyRegion],
+    ) -> Result<()> {
+        let aspects = backend.get_image_format(dst)?.aspect_mask();
+        backend.cmd_pipeline_barrier(
+            self.cb,
+            PipelineStageFlags::TOP_OF_PIPE,
+            PipelineStageFlags::TRANSFER,
+            &[],
+            &[],
+            &[ImageMemoryBarrier::whole_image(
+                dst,
+                aspects,
+                ImageLayout::Undefined,
+                ImageLayout::TransferDstOptimal,
+                AccessFlags::empty(),
+                AccessFlags::TRANSFER_WRITE,
+            )],
+        )?;
+        backend.cmd_copy_buffer_to_image(
+            self.cb,
+            src,
+            dst,
+            ImageLayout::TransferDstOptimal,
+            regions,
+        )?;
+        backend.cmd_pipeline_barrier(
+            self.cb,
+            PipelineStageFlags::TRANSFER,
+            PipelineStageFlags::FRAGMENT_SHADER,
+            &[],
+            &[],
+            &[ImageMemoryBarrier::whole_image(
+                dst,
+                aspects,
+                ImageLayout::TransferDstOptimal,
+                ImageLayout::ShaderReadOnlyOptimal,
+                AccessFlags::TRANSFER_WRITE,
+                AccessFlags::SHADER_READ,
+            )],
+        )
+    }
+
+    /// Ends the command buffer, submits it and waits for it.
+    fn submit_and_wait(&self, backend: &mut dyn RenderBackend) -> Result<()> {
+        backend.cmd_end(self.cb)?;
+        let fence = backend.create_fence(false)?;
+        let waited = backend
+            .queue_submit(
+                self.queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: None,
+                    wait_values: None,
+                    wait_stage: None,
+                    command_buffer: &[self.cb],
+                    finish_semaphore: None,
+                    signal_values: None,
+                    fence: Some(fence),
+                },
+            )
+            .and_then(|_| backend.wait_for_fences(&[fence], true, u64::MAX));
+        backend.destroy_fence(fence)?;
+        match waited? {
+            FenceWaitResult::Signaled => Ok(()),
+            result => bail!("Upload submit didn't complete, the wait ended {:?}.", result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Call, RecordingBackend};
+    use luxseed_utility::{impl_handle, pool::Pool};
+
+    #[derive(Default)]
+    struct TestBuffer {
+        handle: Option<Handle<Buffer>>,
+    }
+    impl_handle!(TestBuffer, Buffer, handle);
+
+    fn begin(backend: &mut RecordingBackend) -> UploadContext {
+        UploadContext::begin(backend, Handle::default(), Handle::default()).unwrap()
+    }
+
+    /// The regions of every buffer to image copy, in order.
+    fn image_regions(backend: &RecordingBackend) -> Vec<BufferImageCopyRegion> {
+        let calls = backend.calls();
+        let copies = calls.iter().filter_map(|call| match call {
+            Call::CopyBufferToImage { regions, .. } => Some(regions.clone()),
+            _ => None,
+        });
+        copies.flatten().collect()
+    }
+
+    #[test]
+    fn uploads_are_submitted_once() {
+        let mut backend = RecordingBackend::default();
+        let mut dst = Pool::<TestBuffer>::with_size(64, Default::default);
+
+        let mut context = begin(&mut backend);
+        for i in 0..50u32 {
+            let buffer = dst.malloc().0;
+            context.upload_buffer(&mut backend, buffer, &i.to_le_bytes()).unwrap();
+        }
+        context.finish(&mut backend).unwrap();
+
+        let calls = backend.calls();
+        let position = |f: fn(&Call) -> bool| calls.iter().position(f).unwrap();
+        let copies = calls.iter().filter(|call| matches!(call, Call::CopyBuffer { .. }));
+        assert_eq!(copies.count(), 50);
+        let submits = calls.iter().filter(|call| matches!(call, Call::QueueSubmit { .. }));
+        assert_eq!(submits.count(), 1);
+        // Copies are recorded before the submit, staging buffers live until it completes
+        let submit = position(|call| matches!(call, Call::QueueSubmit { .. }));
+        assert!(
+            calls.iter().rposition(|call| matches!(call, Call::CopyBuffer { .. })).unwrap()
+                < submit
+        );
+        assert!(
+            position(|call| matches!(call, Call::WaitForFences(_)))
+                < position(|call| matches!(call, Call::DestroyBuffer(_)))
+        );
+        drop(calls);
+        assert_eq!(backend.live_buffers(), 0);
+        assert_eq!(backend.live_command_buffers(), 0);
+    }
+
+    #[test]
+    fn failed_submits_still_release_everything() {
+        let mut dst = Pool::<TestBuffer>::with_size(4, Default::default);
+        for fence_wait_result in [FenceWaitResult::DeviceLost, FenceWaitResult::TimedOut] {
+            let mut backend = RecordingBackend::default();
+            backend.fence_wait_result = fence_wait_result;
+            let mut context = begin(&mut backend);
+            let buffer = dst.malloc().0;
+            context.upload_buffer(&mut backend, buffer, &[0; 4]).unwrap();
+            context.upload_buffer(&mut backend, buffer, &[1; 4]).unwrap();
+
+            let err = context.finish(&mut backend).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Upload submit didn't complete, the wait ended {:?}.", fence_wait_result)
+            );
+            assert_eq!(backend.live_buffers(), 0);
+            assert_eq!(backend.live_command_buffers(), 0);
+        }
+    }
+
+    #[test]
+    fn compressed_mips_are_block_aligned() {
+        let mut backend = RecordingBackend::default();
+        backend.image_format = Format::BC7_SRGB;
+        backend.image_mip_levels = 7;
+        let mut context = begin(&mut backend);
+        let image = Handle::default();
+
+        // 64x32 down to 1x1, the last levels are a single block each
+        let data = vec![0u8; 2048 + 512 + 128 + 32 + 16 * 3];
+        context.upload_compressed_image(&mut backend, image, &data, 64, 32, 7).unwrap();
+        let regions = image_regions(&backend);
+        assert_eq!(regions.len(), 7);
+        assert_eq!(
+            regions.iter().map(|r| r.buffer_offset).collect::<Vec<_>>(),
+            [0, 2048, 2560, 2688, 2720, 2736, 2752]
+        );
+        // 4x2 is stored as a whole 4x4 block
+        assert_eq!(regions[4].image_extent, [4, 2, 1]);
+        assert_eq!((regions[4].buffer_row_length, regions[4].buffer_image_height), (4, 4));
+        assert_eq!(regions[6].mip_level, 6);
+
+        let err = context
+            .upload_compressed_image(&mut backend, image, &data[1..], 64, 32, 7)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "7 mip levels of a 64x32 BC7_SRGB image are 2768 bytes, got 2767."
+        );
+
+        // More levels than the image has
+        let err =
+            context.upload_compressed_image(&mut backend, image, &data, 64, 32, 8).unwrap_err();
+        assert_eq!(err.to_string(), "Can't upload 8 mip levels to an image with 7.");
+
+        backend.image_format = Format::R8G8B8A8_UNORM;
+        assert!(context.upload_compressed_image(&mut backend, image, &data, 64, 32, 7).is_err());
+        context.upload_image(&mut backend, image, &[0; 16], 2, 2).unwrap();
+        assert_eq!(image_regions(&backend)[7].buffer_row_length, 0);
+    }
+}
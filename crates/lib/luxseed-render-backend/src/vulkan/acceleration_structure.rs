@@ -0,0 +1,403 @@
+use anyhow::{bail, Context, Result};
+use ash::vk;
+use luxseed_utility::impl_handle;
+use luxseed_utility::pool::{Handle, Pool};
+use smallvec::SmallVec;
+
+use crate::{define::*, enums::*, flag::BufferUsageFlags};
+
+use super::{buffer::VulkanBuffer, command::VulkanCommandBuffer, device::VulkanDevice};
+
+#[derive(Default)]
+pub struct VulkanAccelerationStructure {
+    pub handle: Option<Handle<AccelerationStructure>>,
+    pub raw: vk::AccelerationStructureKHR,
+    pub ty: vk::AccelerationStructureTypeKHR,
+    pub storage: Option<Handle<Buffer>>,
+    pub scratch: Option<Handle<Buffer>>,
+    pub scratch_address: u64,
+    pub device_address: u64,
+    pub geometries: Vec<AccelerationStructureGeometry>,
+}
+impl_handle!(VulkanAccelerationStructure, AccelerationStructure, handle);
+
+impl VulkanAccelerationStructure {
+    pub fn init(
+        &mut self,
+        device: &mut VulkanDevice,
+        desc: &AccelerationStructureCreateDesc,
+        p_buffer: &mut Pool<VulkanBuffer>,
+    ) -> Result<()> {
+        let loader = device.acceleration_structure_loader()?.clone();
+        let ty = desc.ty.into();
+        let (geometries, ranges) = build_geometries(desc.geometries, |buffer| {
+            Ok(p_buffer.get(buffer).context("Buffer not found.")?.device_address(device))
+        })?;
+        let max_primitive_counts: SmallVec<[u32; 4]> =
+            ranges.iter().map(|range| range.primitive_count).collect();
+        let sizes = unsafe {
+            loader.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info(ty, &geometries).build(),
+                &max_primitive_counts,
+            )
+        };
+
+        // The scratch buffer is padded so its start can be aligned
+        let alignment = device.acceleration_structure_scratch_alignment();
+        let (storage, storage_raw) = create_buffer(
+            device,
+            p_buffer,
+            desc.name,
+            sizes.acceleration_structure_size,
+            BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE
+                | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+        let (scratch, _) = create_buffer(
+            device,
+            p_buffer,
+            desc.name,
+            sizes.build_scratch_size + alignment - 1,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+        )?;
+        let scratch_address =
+            p_buffer.get(scratch).unwrap().device_address(device).next_multiple_of(alignment);
+
+        let raw = unsafe {
+            loader.create_acceleration_structure(
+                &vk::AccelerationStructureCreateInfoKHR::builder()
+                    .buffer(storage_raw)
+                    .size(sizes.acceleration_structure_size)
+                    .ty(ty),
+                None,
+            )?
+        };
+        let device_address = unsafe {
+            loader.get_acceleration_structure_device_address(
+                &vk::AccelerationStructureDeviceAddressInfoKHR::builder()
+                    .acceleration_structure(raw),
+            )
+        };
+
+        self.raw = raw;
+        self.ty = ty;
+        self.storage = Some(storage);
+        self.scratch = Some(scratch);
+        self.scratch_address = scratch_address;
+        self.device_address = device_address;
+        self.geometries = desc.geometries.to_vec();
+
+        Ok(())
+    }
+
+    pub fn destroy(
+        &mut self,
+        device: &mut VulkanDevice,
+        p_buffer: &mut Pool<VulkanBuffer>,
+    ) -> Result<()> {
+        unsafe {
+            device.acceleration_structure_loader()?.destroy_acceleration_structure(self.raw, None);
+        }
+        for buffer in [self.storage.take(), self.scratch.take()].into_iter().flatten() {
+            if let Some(b) = p_buffer.get_mut(buffer) {
+                b.destroy(device)?;
+                p_buffer.free(buffer);
+            }
+        }
+        self.raw = vk::AccelerationStructureKHR::null();
+        self.ty = vk::AccelerationStructureTypeKHR::default();
+        self.scratch_address = 0;
+        self.device_address = 0;
+        self.geometries.clear();
+        Ok(())
+    }
+}
+
+impl VulkanCommandBuffer {
+    /// Bottom level structures are built first so top level ones can reference them, the
+    /// results are visible to every command recorded after.
+    pub fn build_acceleration_structures(
+        &self,
+        device: &VulkanDevice,
+        structures: &[&VulkanAccelerationStructure],
+        p_buffer: &Pool<VulkanBuffer>,
+    ) -> Result<()> {
+        let loader = device.acceleration_structure_loader()?;
+        let address = |buffer: Handle<Buffer>| {
+            Ok(p_buffer.get(buffer).context("Buffer not found.")?.device_address(device))
+        };
+        for structures in build_batches(structures).iter() {
+            if structures.is_empty() {
+                continue;
+            }
+            let mut geometries = Vec::with_capacity(structures.len());
+            let mut ranges = Vec::with_capacity(structures.len());
+            for s in structures.iter() {
+                let (g, r) = build_geometries(&s.geometries, address)?;
+                geometries.push(g);
+                ranges.push(r);
+            }
+            let infos: SmallVec<[_; 8]> = structures
+                .iter()
+                .zip(geometries.iter())
+                .map(|(s, g)| {
+                    build_info(s.ty, g)
+                        .dst_acceleration_structure(s.raw)
+                        .scratch_data(vk::DeviceOrHostAddressKHR {
+                            device_address: s.scratch_address,
+                        })
+                        .build()
+                })
+                .collect();
+            let ranges: SmallVec<[&[_]; 8]> = ranges.iter().map(|r| r.as_slice()).collect();
+            unsafe {
+                loader.cmd_build_acceleration_structures(self.raw, &infos, &ranges);
+            }
+            self.acceleration_structure_barrier(device);
+        }
+        Ok(())
+    }
+
+    fn acceleration_structure_barrier(&self, device: &VulkanDevice) {
+        let barrier = vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR)
+            .dst_access_mask(vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR)
+            .build();
+        unsafe {
+            device.raw().cmd_pipeline_barrier(
+                self.raw,
+                vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[barrier],
+                &[],
+                &[],
+            );
+        }
+    }
+}
+
+/// Structures built together and then synchronized with the next batch, bottom level ones
+/// before top level ones.
+fn build_batches<'a>(
+    structures: &[&'a VulkanAccelerationStructure],
+) -> [Vec<&'a VulkanAccelerationStructure>; 2] {
+    let (bottom, top) = structures
+        .iter()
+        .copied()
+        .partition(|s| s.ty == vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL);
+    [bottom, top]
+}
+
+fn build_info(
+    ty: vk::AccelerationStructureTypeKHR,
+    geometries: &[vk::AccelerationStructureGeometryKHR],
+) -> vk::AccelerationStructureBuildGeometryInfoKHRBuilder<'_> {
+    vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+        .ty(ty)
+        .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+        .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+        .geometries(geometries)
+}
+
+type BuildGeometries = (
+    SmallVec<[vk::AccelerationStructureGeometryKHR; 4]>,
+    SmallVec<[vk::AccelerationStructureBuildRangeInfoKHR; 4]>,
+);
+
+/// Describes `geometries` for a build, `address` resolves the device address of a buffer.
+fn build_geometries(
+    geometries: &[AccelerationStructureGeometry],
+    address: impl Fn(Handle<Buffer>) -> Result<u64>,
+) -> Result<BuildGeometries> {
+    let mut ret = BuildGeometries::default();
+    for geometry in geometries {
+        let (ty, data, flags) = match *geometry {
+            AccelerationStructureGeometry::Triangles {
+                vertex_buffer,
+                vertex_offset,
+                vertex_format,
+                vertex_stride,
+                max_vertex,
+                index_buffer,
+                opaque,
+                ..
+            } => {
+                let (index_type, index_address) = match index_buffer {
+                    None => (vk::IndexType::NONE_KHR, 0),
+                    Some((_, _, IndexType::U8)) => {
+                        bail!("Acceleration structures only take 16 or 32 bit indices.")
+                    }
+                    Some((buffer, offset, ty)) => (ty.into(), address(buffer)? + offset),
+                };
+                let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
+                    .vertex_format(vertex_format.into())
+                    .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                        device_address: address(vertex_buffer)? + vertex_offset,
+                    })
+                    .vertex_stride(vertex_stride)
+                    .max_vertex(max_vertex)
+                    .index_type(index_type)
+                    .index_data(vk::DeviceOrHostAddressConstKHR { device_address: index_address })
+                    .build();
+                let flags = if opaque {
+                    vk::GeometryFlagsKHR::OPAQUE
+                } else {
+                    vk::GeometryFlagsKHR::empty()
+                };
+                (
+                    vk::GeometryTypeKHR::TRIANGLES,
+                    vk::AccelerationStructureGeometryDataKHR { triangles },
+                    flags,
+                )
+            }
+            AccelerationStructureGeometry::Instances { buffer, offset, .. } => {
+                let instances = vk::AccelerationStructureGeometryInstancesDataKHR::builder()
+                    .data(vk::DeviceOrHostAddressConstKHR {
+                        device_address: address(buffer)? + offset,
+                    })
+                    .build();
+                (
+                    vk::GeometryTypeKHR::INSTANCES,
+                    vk::AccelerationStructureGeometryDataKHR { instances },
+                    vk::GeometryFlagsKHR::empty(),
+                )
+            }
+        };
+        ret.0.push(
+            vk::AccelerationStructureGeometryKHR::builder()
+                .geometry_type(ty)
+                .geometry(data)
+                .flags(flags)
+                .build(),
+        );
+        ret.1.push(
+            vk::AccelerationStructureBuildRangeInfoKHR::builder()
+                .primitive_count(geometry.primitive_count())
+                .build(),
+        );
+    }
+    Ok(ret)
+}
+
+fn create_buffer(
+    device: &mut VulkanDevice,
+    p_buffer: &mut Pool<VulkanBuffer>,
+    name: &str,
+    size: u64,
+    usage: BufferUsageFlags,
+) -> Result<(Handle<Buffer>, vk::Buffer)> {
+    let (handle, buffer) = p_buffer.malloc();
+    buffer.init(
+        device,
+        &BufferCreateDesc {
+            name,
+            size,
+            usage,
+            memory: MemoryLocation::GpuOnly,
+            initial_data: None,
+        },
+    )?;
+    Ok((handle, buffer.raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometries_resolve_buffer_addresses() {
+        let mut p_buffer = Pool::<VulkanBuffer>::with_size(4, Default::default);
+        let vertices = p_buffer.malloc().0;
+        let indices = p_buffer.malloc().0;
+        let instances = p_buffer.malloc().0;
+        let address = |buffer: Handle<Buffer>| {
+            Ok(if buffer == vertices {
+                0x1000
+            } else if buffer == indices {
+                0x2000
+            } else {
+                0x3000
+            })
+        };
+
+        let triangle = AccelerationStructureGeometry::Triangles {
+            vertex_buffer: vertices,
+            vertex_offset: 16,
+            vertex_format: Format::R32G32B32_SFLOAT,
+            vertex_stride: 12,
+            max_vertex: 2,
+            index_buffer: Some((indices, 4, IndexType::U32)),
+            triangle_count: 1,
+            opaque: true,
+        };
+        let (geometries, ranges) = build_geometries(&[triangle], address).unwrap();
+        assert_eq!(geometries[0].geometry_type, vk::GeometryTypeKHR::TRIANGLES);
+        assert_eq!(geometries[0].flags, vk::GeometryFlagsKHR::OPAQUE);
+        let triangles = unsafe { geometries[0].geometry.triangles };
+        assert_eq!(unsafe { triangles.vertex_data.device_address }, 0x1010);
+        assert_eq!(unsafe { triangles.index_data.device_address }, 0x2004);
+        assert_eq!(triangles.index_type, vk::IndexType::UINT32);
+        assert_eq!(triangles.vertex_format, vk::Format::R32G32B32_SFLOAT);
+        assert_eq!(triangles.max_vertex, 2);
+        assert_eq!(ranges[0].primitive_count, 1);
+
+        let instances =
+            AccelerationStructureGeometry::Instances { buffer: instances, offset: 64, count: 3 };
+        let (geometries, ranges) = build_geometries(&[instances], address).unwrap();
+        assert_eq!(geometries[0].geometry_type, vk::GeometryTypeKHR::INSTANCES);
+        assert_eq!(unsafe { geometries[0].geometry.instances.data.device_address }, 0x3040);
+        assert_eq!(ranges[0].primitive_count, 3);
+
+        let u8_indices = AccelerationStructureGeometry::Triangles {
+            vertex_buffer: vertices,
+            vertex_offset: 0,
+            vertex_format: Format::R32G32B32_SFLOAT,
+            vertex_stride: 12,
+            max_vertex: 2,
+            index_buffer: Some((indices, 0, IndexType::U8)),
+            triangle_count: 1,
+            opaque: true,
+        };
+        assert!(build_geometries(&[u8_indices], address).is_err());
+    }
+
+    #[test]
+    fn bottom_level_structures_are_built_first() {
+        let structure = |ty, raw| VulkanAccelerationStructure {
+            ty,
+            raw: vk::Handle::from_raw(raw),
+            ..Default::default()
+        };
+        let tlas = structure(vk::AccelerationStructureTypeKHR::TOP_LEVEL, 1);
+        let triangle = structure(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL, 2);
+        let quad = structure(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL, 3);
+
+        // The order they are passed in doesn't matter
+        let raw = |batch: &Vec<&VulkanAccelerationStructure>| {
+            batch.iter().map(|s| vk::Handle::as_raw(s.raw)).collect::<Vec<_>>()
+        };
+        let [bottom, top] = build_batches(&[&tlas, &triangle, &quad]);
+        assert_eq!((raw(&bottom), raw(&top)), (vec![2, 3], vec![1]));
+        let [bottom, top] = build_batches(&[&tlas]);
+        assert!(bottom.is_empty());
+        assert_eq!(raw(&top), vec![1]);
+    }
+
+    #[test]
+    fn instance_layout() {
+        assert_eq!(std::mem::size_of::<AccelerationStructureInstance>(), 64);
+        assert_eq!(
+            std::mem::size_of::<AccelerationStructureInstance>(),
+            std::mem::size_of::<vk::AccelerationStructureInstanceKHR>()
+        );
+
+        let instance =
+            AccelerationStructureInstance::new(AccelerationStructureInstance::IDENTITY, 42)
+                .custom_index(7)
+                .mask(0x0F);
+        assert_eq!(instance.custom_index_and_mask, 0x0F00_0007);
+        assert_eq!(instance.custom_index(0x1FF_FFFF).custom_index_and_mask, 0x0FFF_FFFF);
+        assert_eq!(AccelerationStructureInstance::as_bytes(&[instance, instance]).len(), 128);
+    }
+}
@@ -4,7 +4,10 @@ use gpu_allocator::vulkan::*;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::Handle;
 
-use crate::define::{Buffer, BufferCreateDesc};
+use crate::{
+    define::{Buffer, BufferCreateDesc, BufferView, WHOLE_SIZE},
+    flag::BufferUsageFlags,
+};
 
 use super::device::VulkanDevice;
 
@@ -15,6 +18,7 @@ pub struct VulkanBuffer {
     pub requirements: vk::MemoryRequirements,
     pub allocation: Option<Allocation>,
     pub size: u64,
+    pub usage: BufferUsageFlags,
 }
 impl_handle!(VulkanBuffer, Buffer, handle);
 
@@ -50,10 +54,73 @@ impl VulkanBuffer {
         self.raw = raw;
         self.requirements = requirements;
         self.size = desc.size as u64;
+        self.usage = desc.usage;
+        if desc.initial_data.is_some() {
+            self.flush(device, 0, WHOLE_SIZE)?;
+        }
 
         Ok(())
     }
 
+    /// Makes host writes to the mapped range visible to the device. Nothing to do for
+    /// coherent memory.
+    pub fn flush(&self, device: &VulkanDevice, offset: u64, size: u64) -> Result<()> {
+        if let Some(range) = self.non_coherent_range(device, offset, size)? {
+            unsafe { device.raw().flush_mapped_memory_ranges(&[range])? };
+        }
+        Ok(())
+    }
+
+    /// Makes device writes to the mapped range visible to the host. Nothing to do for
+    /// coherent memory.
+    pub fn invalidate(&self, device: &VulkanDevice, offset: u64, size: u64) -> Result<()> {
+        if let Some(range) = self.non_coherent_range(device, offset, size)? {
+            unsafe { device.raw().invalidate_mapped_memory_ranges(&[range])? };
+        }
+        Ok(())
+    }
+
+    fn non_coherent_range(
+        &self,
+        device: &VulkanDevice,
+        offset: u64,
+        size: u64,
+    ) -> Result<Option<vk::MappedMemoryRange>> {
+        let allocation = self.allocation.as_ref().context("Buffer not allocated.")?;
+        if allocation.mapped_ptr().is_none() {
+            anyhow::bail!("Buffer not mapped.");
+        }
+        let (offset, size) = mapped_memory_range(
+            allocation.offset(),
+            allocation.size(),
+            self.size,
+            offset,
+            size,
+            device.get_adapter().properties.limits.non_coherent_atom_size,
+        )?;
+        if size == 0
+            || allocation.memory_properties().contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+        {
+            return Ok(None);
+        }
+        Ok(Some(
+            vk::MappedMemoryRange::builder()
+                .memory(unsafe { allocation.memory() })
+                .offset(offset)
+                .size(size)
+                .build(),
+        ))
+    }
+
+    /// Needs `BufferUsageFlags::SHADER_DEVICE_ADDRESS`.
+    pub fn device_address(&self, device: &VulkanDevice) -> u64 {
+        unsafe {
+            device
+                .raw()
+                .get_buffer_device_address(&vk::BufferDeviceAddressInfo::builder().buffer(self.raw))
+        }
+    }
+
     pub fn destroy(&mut self, device: &mut VulkanDevice) -> Result<()> {
         if let Some(allocation) = self.allocation.take() {
             device.get_mut_allocator().free(allocation)?;
@@ -66,7 +133,168 @@ impl VulkanBuffer {
         self.allocation = None;
         self.requirements = vk::MemoryRequirements::default();
         self.size = 0;
+        self.usage = BufferUsageFlags::empty();
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct VulkanBufferView {
+    pub raw: vk::BufferView,
+    pub handle: Option<Handle<BufferView>>,
+    pub buffer: Option<Handle<Buffer>>,
+}
+impl_handle!(VulkanBufferView, BufferView, handle);
 
+impl VulkanBufferView {
+    pub fn init(
+        &mut self,
+        device: &VulkanDevice,
+        buffer: &VulkanBuffer,
+        format: vk::Format,
+        offset: u64,
+        range: u64,
+    ) -> Result<()> {
+        let info = vk::BufferViewCreateInfo::builder()
+            .buffer(buffer.raw)
+            .format(format)
+            .offset(offset)
+            .range(range)
+            .build();
+        self.raw = unsafe { device.raw().create_buffer_view(&info, None)? };
+        self.buffer = buffer.handle;
         Ok(())
     }
+
+    pub fn destroy(&mut self, device: &VulkanDevice) {
+        unsafe {
+            device.raw().destroy_buffer_view(self.raw, None);
+        }
+        self.raw = vk::BufferView::null();
+        self.buffer = None;
+    }
+}
+
+/// The format features a texel view of a buffer with `usage` needs, one for each kind of
+/// texel buffer the buffer can be bound as.
+pub fn texel_buffer_features(usage: BufferUsageFlags) -> Result<vk::FormatFeatureFlags> {
+    let mut features = vk::FormatFeatureFlags::empty();
+    if usage.contains(BufferUsageFlags::UNIFORM_TEXEL_BUFFER) {
+        features |= vk::FormatFeatureFlags::UNIFORM_TEXEL_BUFFER;
+    }
+    if usage.contains(BufferUsageFlags::STORAGE_TEXEL_BUFFER) {
+        features |= vk::FormatFeatureFlags::STORAGE_TEXEL_BUFFER;
+    }
+    if features.is_empty() {
+        anyhow::bail!(
+            "Buffer views need a buffer created with UNIFORM_TEXEL_BUFFER or STORAGE_TEXEL_BUFFER."
+        );
+    }
+    Ok(features)
+}
+
+/// A texel view starts at a multiple of `alignment` inside the buffer and covers at least one
+/// byte, `WHOLE_SIZE` runs to the end of the buffer.
+pub fn check_buffer_view_range(
+    offset: u64,
+    range: u64,
+    buffer_size: u64,
+    alignment: u64,
+) -> Result<()> {
+    if !offset.is_multiple_of(alignment) {
+        anyhow::bail!("Buffer view offset {} is not a multiple of {}.", offset, alignment);
+    }
+    if offset >= buffer_size {
+        anyhow::bail!(
+            "Buffer view offset {} is past the end of a {} byte buffer.",
+            offset,
+            buffer_size
+        );
+    }
+    if range != WHOLE_SIZE && (range == 0 || range > buffer_size - offset) {
+        anyhow::bail!(
+            "Buffer view of {} bytes at offset {} doesn't fit a {} byte buffer.",
+            range,
+            offset,
+            buffer_size
+        );
+    }
+    Ok(())
+}
+
+/// The memory range `offset..offset + size` of a buffer bound at `memory_offset` covers,
+/// widened to whole `atom_size` blocks as flushes and invalidations need. Widening past the
+/// end of the allocation runs the range to the end of the mapped memory block instead.
+fn mapped_memory_range(
+    memory_offset: u64,
+    memory_size: u64,
+    buffer_size: u64,
+    offset: u64,
+    size: u64,
+    atom_size: u64,
+) -> Result<(u64, u64)> {
+    let size = if size == WHOLE_SIZE { buffer_size.saturating_sub(offset) } else { size };
+    if offset.checked_add(size).is_none_or(|end| end > buffer_size) {
+        anyhow::bail!(
+            "Mapped range of {} bytes at offset {} is outside a {} byte buffer.",
+            size,
+            offset,
+            buffer_size
+        );
+    }
+    let start = (memory_offset + offset) / atom_size * atom_size;
+    let end = (memory_offset + offset + size).next_multiple_of(atom_size);
+    if end > memory_offset + memory_size {
+        return Ok((start, vk::WHOLE_SIZE));
+    }
+    Ok((start, end - start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texel_views_need_texel_usage() {
+        assert_eq!(
+            texel_buffer_features(BufferUsageFlags::UNIFORM_TEXEL_BUFFER).unwrap(),
+            vk::FormatFeatureFlags::UNIFORM_TEXEL_BUFFER
+        );
+        assert_eq!(
+            texel_buffer_features(
+                BufferUsageFlags::STORAGE_TEXEL_BUFFER | BufferUsageFlags::TRANSFER_DST
+            )
+            .unwrap(),
+            vk::FormatFeatureFlags::STORAGE_TEXEL_BUFFER
+        );
+        assert!(texel_buffer_features(BufferUsageFlags::STORAGE_BUFFER).is_err());
+
+        assert!(check_buffer_view_range(0, WHOLE_SIZE, 256, 16).is_ok());
+        assert!(check_buffer_view_range(64, 128, 256, 16).is_ok());
+        assert_eq!(
+            check_buffer_view_range(8, WHOLE_SIZE, 256, 16).unwrap_err().to_string(),
+            "Buffer view offset 8 is not a multiple of 16."
+        );
+        assert!(check_buffer_view_range(256, WHOLE_SIZE, 256, 16).is_err());
+        assert!(check_buffer_view_range(128, 256, 256, 16).is_err());
+        assert!(check_buffer_view_range(0, 0, 256, 16).is_err());
+    }
+
+    #[test]
+    fn mapped_ranges_cover_whole_atoms() {
+        // Allocation at 256 in its block, 64 byte atoms
+        assert_eq!(mapped_memory_range(256, 512, 512, 0, WHOLE_SIZE, 64).unwrap(), (256, 512));
+        assert_eq!(mapped_memory_range(256, 512, 512, 70, 4, 64).unwrap(), (320, 64));
+        assert_eq!(mapped_memory_range(256, 512, 512, 60, 8, 64).unwrap(), (256, 128));
+        // Rounding the end up would leave the allocation
+        assert_eq!(
+            mapped_memory_range(0, 100, 100, 0, WHOLE_SIZE, 64).unwrap(),
+            (0, vk::WHOLE_SIZE)
+        );
+        assert_eq!(
+            mapped_memory_range(0, 512, 512, 500, 16, 64).unwrap_err().to_string(),
+            "Mapped range of 16 bytes at offset 500 is outside a 512 byte buffer."
+        );
+    }
 }
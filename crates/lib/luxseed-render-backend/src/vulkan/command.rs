@@ -3,16 +3,28 @@ use ash::vk::{self};
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Handled, Pool};
 use smallvec::SmallVec;
+use std::cell::Cell;
 
-use crate::{define::*, enums::*, flag::PipelineStageFlags, MAX_RENDER_TARGETS};
+use crate::{
+    define::*,
+    enums::*,
+    flag::{PipelineStageFlags, RequestedFeatures},
+    MAX_RENDER_TARGETS,
+};
 
 use super::{
     buffer::VulkanBuffer,
     device::{VulkanDevice, VulkanQueue},
     framebuffer::VulkanFramebuffer,
-    image::VulkanImage,
-    pipeline::{VulkanPipelineLayout, VulkanRasterPipeline},
+    image::{VulkanImage, VulkanImageDesc},
+    pipeline::{VulkanComputePipeline, VulkanPipelineLayout, VulkanRasterPipeline},
     render_pass::VulkanRenderPass,
+    render_pass::VulkanRenderPassOutput,
+    rendering::VulkanRenderingAttachments,
+    util::{
+        format_aspects, format_size_class, has_stencil_aspect, integer_format_sign,
+        is_blit_compatible, resolve_barrier_aspect,
+    },
 };
 
 #[derive(Default)]
@@ -62,9 +74,45 @@ pub struct VulkanCommandBuffer {
     pub pool: Option<Handle<CommandPool>>,
     cache_render_pass: Option<Handle<RenderPass>>,
     cache_framebuffer: Option<Handle<Framebuffer>>,
+    state: Cell<CommandBufferState>,
+    // Recorded at bind time so indexed draws can be validated in debug builds
+    bound_index_buffer: Cell<Option<BoundIndexBuffer>>,
+    level: CommandBufferLevel,
+    // Subpass of the render pass a secondary command buffer was begun to continue
+    inherited_subpass: Cell<Option<u32>>,
+    // Contents and index of the subpass a primary command buffer is recording
+    subpass: Cell<Option<(SubpassContents, u32)>>,
+    // Whether the pass being recorded was begun by `cmd_begin_rendering`
+    dynamic_rendering: Cell<bool>,
 }
 impl_handle!(VulkanCommandBuffer, CommandBuffer, handle);
 
+/// Lifecycle of a command buffer as seen by the backend, used to catch commands recorded at the
+/// wrong time in debug builds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommandBufferState {
+    #[default]
+    Initial,
+    Recording,
+    InRenderPass,
+    Executable,
+}
+
+/// Where a command may be recorded relative to a render pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderPassScope {
+    Outside,
+    Inside,
+    Any,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BoundIndexBuffer {
+    pub index_type: IndexType,
+    /// Bytes from the bind offset to the end of the buffer.
+    pub size: u64,
+}
+
 impl VulkanCommandBuffer {
     pub fn init(
         &mut self,
@@ -83,21 +131,159 @@ impl VulkanCommandBuffer {
         }[0];
         self.raw = raw;
         self.pool = pool.get_handle();
+        self.level = level;
         self.cache_framebuffer = None;
         self.cache_render_pass = None;
+        self.state.set(CommandBufferState::Initial);
+        Ok(())
+    }
+
+    /// Checks `cmd` may be recorded in the current state.
+    #[inline]
+    pub fn validate_scope(&self, scope: RenderPassScope, cmd: &str) -> anyhow::Result<()> {
+        validate_scope(self.state.get(), scope, cmd)
+    }
+
+    #[inline]
+    pub fn validate_begin(&self, desc: &CommandBufferBeginDesc) -> anyhow::Result<()> {
+        validate_begin(self.state.get())?;
+        if self.level == CommandBufferLevel::Primary && desc.render_pass_continue.is_some() {
+            anyhow::bail!("Only secondary command buffers can continue a render pass.");
+        }
+        Ok(())
+    }
+
+    /// A secondary command buffer that continues a render pass ends inside it.
+    #[inline]
+    pub fn validate_end(&self) -> anyhow::Result<()> {
+        let scope = match self.inherited_subpass.get() {
+            Some(_) => RenderPassScope::Inside,
+            None => RenderPassScope::Outside,
+        };
+        validate_scope(self.state.get(), scope, "cmd_end")
+    }
+
+    /// A pass begun by `cmd_begin_rendering` is only ended by `cmd_end_rendering`, render pass
+    /// commands need a render pass.
+    #[inline]
+    pub fn validate_rendering(&self, dynamic_rendering: bool, cmd: &str) -> anyhow::Result<()> {
+        validate_rendering(self.dynamic_rendering.get(), dynamic_rendering, cmd)
+    }
+
+    /// Render passes are begun, advanced and ended by primary command buffers only.
+    #[inline]
+    pub fn validate_primary(&self, cmd: &str) -> anyhow::Result<()> {
+        if self.level != CommandBufferLevel::Primary {
+            anyhow::bail!("{} called on a secondary command buffer.", cmd);
+        }
+        Ok(())
+    }
+
+    /// Secondary command buffers run inside a render pass if and only if they were begun to
+    /// continue it, in the subpass the primary is recording, which has to take its contents
+    /// from secondary command buffers.
+    pub fn validate_execute(&self, secondaries: &[&VulkanCommandBuffer]) -> anyhow::Result<()> {
+        self.validate_primary("cmd_execute_commands")?;
+        self.validate_scope(RenderPassScope::Any, "cmd_execute_commands")?;
+        let subpass = self.subpass.get();
+        if let Some((contents, index)) = subpass {
+            if contents != SubpassContents::SecondaryCommandBuffers {
+                anyhow::bail!(
+                    "cmd_execute_commands called in subpass {} which has {:?} contents, begin it with SubpassContents::SecondaryCommandBuffers.",
+                    index,
+                    contents
+                );
+            }
+        }
+        for (i, secondary) in secondaries.iter().enumerate() {
+            if secondary.level != CommandBufferLevel::Secondary {
+                anyhow::bail!("cmd_execute_commands got a primary command buffer at {}.", i);
+            }
+            if secondary.state.get() != CommandBufferState::Executable {
+                anyhow::bail!(
+                    "Secondary command buffer {} is in the {:?} state, it must be ended first.",
+                    i,
+                    secondary.state.get()
+                );
+            }
+            match (subpass, secondary.inherited_subpass.get()) {
+                (Some(_), None) => anyhow::bail!(
+                    "Secondary command buffer {} is executed inside a render pass but wasn't begun with render_pass_continue.",
+                    i
+                ),
+                (None, Some(_)) => anyhow::bail!(
+                    "Secondary command buffer {} continues a render pass but is executed outside one.",
+                    i
+                ),
+                (Some((_, index)), Some(inherited)) if index != inherited => anyhow::bail!(
+                    "Secondary command buffer {} continues subpass {} but is executed in subpass {}.",
+                    i,
+                    inherited,
+                    index
+                ),
+                _ => {}
+            }
+        }
         Ok(())
     }
 
     #[inline]
-    pub fn begin(&self, device: &VulkanDevice, desc: CommandBufferBeginDesc) -> anyhow::Result<()> {
+    pub fn validate_submit(&self) -> anyhow::Result<()> {
+        if self.level != CommandBufferLevel::Primary {
+            anyhow::bail!(
+                "queue_submit called with a secondary command buffer, see cmd_execute_commands."
+            );
+        }
+        if self.state.get() != CommandBufferState::Executable {
+            anyhow::bail!(
+                "queue_submit called with a command buffer in the {:?} state, it must be ended first.",
+                self.state.get()
+            );
+        }
+        Ok(())
+    }
+
+    /// `render_pass` and `framebuffer` are the ones `desc.render_pass_continue` refers to.
+    #[inline]
+    pub fn begin(
+        &self,
+        device: &VulkanDevice,
+        desc: CommandBufferBeginDesc,
+        render_pass: Option<&VulkanRenderPass>,
+        framebuffer: Option<&VulkanFramebuffer>,
+    ) -> anyhow::Result<()> {
         let mut flag = vk::CommandBufferUsageFlags::empty();
         if desc.one_time_submit {
             flag |= vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT;
         }
-        let begin_info = vk::CommandBufferBeginInfo::builder().flags(flag).build();
+        // Secondary command buffers always need inheritance info, empty outside render passes
+        let mut inheritance_info = vk::CommandBufferInheritanceInfo::default();
+        if let (Some(continued), Some(render_pass)) = (desc.render_pass_continue, render_pass) {
+            flag |= vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE;
+            inheritance_info.render_pass = render_pass.raw;
+            inheritance_info.subpass = continued.subpass;
+            inheritance_info.framebuffer = framebuffer.map_or(vk::Framebuffer::null(), |fb| fb.raw);
+        }
+        let mut begin_info = vk::CommandBufferBeginInfo::builder().flags(flag);
+        if self.level == CommandBufferLevel::Secondary {
+            begin_info = begin_info.inheritance_info(&inheritance_info);
+        }
         unsafe {
             device.raw().begin_command_buffer(self.raw, &begin_info)?;
         }
+        self.bound_index_buffer.set(None);
+        self.subpass.set(None);
+        self.dynamic_rendering.set(false);
+        match desc.render_pass_continue {
+            Some(continued) => {
+                self.inherited_subpass.set(Some(continued.subpass));
+                self.state.set(CommandBufferState::InRenderPass);
+            }
+            None => {
+                self.inherited_subpass.set(None);
+                self.state.set(CommandBufferState::Recording);
+            }
+        }
         Ok(())
     }
 
@@ -106,6 +292,7 @@ impl VulkanCommandBuffer {
         unsafe {
             device.raw().end_command_buffer(self.raw)?;
         }
+        self.state.set(CommandBufferState::Executable);
         Ok(())
     }
 
@@ -117,21 +304,10 @@ impl VulkanCommandBuffer {
         framebuffer: &VulkanFramebuffer,
         clear_values: Option<&[ClearColor]>,
         clear_depth_stencil: Option<ClearDepthStencil>,
+        contents: SubpassContents,
     ) -> anyhow::Result<()> {
-        let mut vk_clear_values = [vk::ClearValue::default(); MAX_RENDER_TARGETS + 1];
-        {
-            if clear_values.is_some() {
-                let values = clear_values.unwrap();
-                for attachment in 0..render_pass.output.num_colors {
-                    vk_clear_values[attachment as usize] = values[attachment as usize].into();
-                }
-            }
-
-            if clear_depth_stencil.is_some() {
-                let value = clear_depth_stencil.unwrap();
-                vk_clear_values[render_pass.output.num_colors as usize] = value.into();
-            }
-        }
+        let vk_clear_values =
+            render_pass_clear_values(&render_pass.output, clear_values, clear_depth_stencil)?;
 
         let create_info = vk::RenderPassBeginInfo::builder()
             .render_pass(render_pass.raw)
@@ -151,17 +327,54 @@ impl VulkanCommandBuffer {
             .build();
 
         unsafe {
-            device.raw().cmd_begin_render_pass(self.raw, &create_info, vk::SubpassContents::INLINE);
+            device.raw().cmd_begin_render_pass(self.raw, &create_info, contents.into());
         }
+        self.state.set(CommandBufferState::InRenderPass);
+        self.subpass.set(Some((contents, 0)));
 
         Ok(())
     }
 
     #[inline]
+    pub fn next_subpass(
+        &self,
+        device: &VulkanDevice,
+        contents: SubpassContents,
+    ) -> anyhow::Result<()> {
+        unsafe {
+            device.raw().cmd_next_subpass(self.raw, contents.into());
+        }
+        let index = self.subpass.get().map_or(0, |(_, index)| index + 1);
+        self.subpass.set(Some((contents, index)));
+        Ok(())
+    }
+
     pub fn end_render_pass(&self, device: &VulkanDevice) -> anyhow::Result<()> {
         unsafe {
             device.raw().cmd_end_render_pass(self.raw);
         }
+        self.state.set(CommandBufferState::Recording);
+        self.subpass.set(None);
+        Ok(())
+    }
+
+    pub fn begin_rendering(
+        &self,
+        device: &VulkanDevice,
+        attachments: &VulkanRenderingAttachments,
+    ) -> anyhow::Result<()> {
+        device.cmd_begin_rendering(self.raw, &attachments.rendering_info());
+        self.state.set(CommandBufferState::InRenderPass);
+        self.subpass.set(Some((SubpassContents::Inline, 0)));
+        self.dynamic_rendering.set(true);
+        Ok(())
+    }
+
+    pub fn end_rendering(&self, device: &VulkanDevice) -> anyhow::Result<()> {
+        device.cmd_end_rendering(self.raw);
+        self.state.set(CommandBufferState::Recording);
+        self.subpass.set(None);
+        self.dynamic_rendering.set(false);
         Ok(())
     }
 
@@ -177,6 +390,54 @@ impl VulkanCommandBuffer {
         Ok(())
     }
 
+    #[inline]
+    pub fn execute_commands(&self, device: &VulkanDevice, secondaries: &[vk::CommandBuffer]) {
+        unsafe {
+            device.raw().cmd_execute_commands(self.raw, secondaries);
+        }
+    }
+
+    #[inline]
+    pub fn bind_compute_pipeline(&self, device: &VulkanDevice, pipeline: &VulkanComputePipeline) {
+        unsafe {
+            device.raw().cmd_bind_pipeline(self.raw, vk::PipelineBindPoint::COMPUTE, pipeline.raw);
+        }
+    }
+
+    #[inline]
+    pub fn push_constants(
+        &self,
+        device: &VulkanDevice,
+        pipeline_layout: &VulkanPipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        unsafe {
+            device.raw().cmd_push_constants(
+                self.raw,
+                pipeline_layout.raw,
+                stage_flags,
+                offset,
+                data,
+            );
+        }
+    }
+
+    #[inline]
+    pub fn dispatch(&self, device: &VulkanDevice, group_x: u32, group_y: u32, group_z: u32) {
+        unsafe {
+            device.raw().cmd_dispatch(self.raw, group_x, group_y, group_z);
+        }
+    }
+
+    #[inline]
+    pub fn dispatch_indirect(&self, device: &VulkanDevice, buffer: &VulkanBuffer, offset: u64) {
+        unsafe {
+            device.raw().cmd_dispatch_indirect(self.raw, buffer.raw, offset);
+        }
+    }
+
     #[inline]
     pub fn set_viewport(
         &self,
@@ -202,6 +463,21 @@ impl VulkanCommandBuffer {
         Ok(())
     }
 
+    pub fn set_viewports(&self, device: &VulkanDevice, first: u32, viewports: &[Viewport]) {
+        let viewports: SmallVec<[vk::Viewport; 4]> =
+            viewports.iter().map(|v| (*v).into()).collect();
+        unsafe {
+            device.raw().cmd_set_viewport(self.raw, first, &viewports);
+        }
+    }
+
+    pub fn set_scissors(&self, device: &VulkanDevice, first: u32, scissors: &[Rect2D]) {
+        let scissors: SmallVec<[vk::Rect2D; 4]> = scissors.iter().map(|s| (*s).into()).collect();
+        unsafe {
+            device.raw().cmd_set_scissor(self.raw, first, &scissors);
+        }
+    }
+
     #[inline]
     pub fn set_scissor(
         &self,
@@ -221,6 +497,30 @@ impl VulkanCommandBuffer {
         Ok(())
     }
 
+    pub fn set_depth_bias(
+        &self,
+        device: &VulkanDevice,
+        constant_factor: f32,
+        clamp: f32,
+        slope_factor: f32,
+    ) {
+        unsafe {
+            device.raw().cmd_set_depth_bias(self.raw, constant_factor, clamp, slope_factor);
+        }
+    }
+
+    pub fn set_line_width(&self, device: &VulkanDevice, width: f32) {
+        unsafe {
+            device.raw().cmd_set_line_width(self.raw, width);
+        }
+    }
+
+    pub fn set_blend_constants(&self, device: &VulkanDevice, constants: [f32; 4]) {
+        unsafe {
+            device.raw().cmd_set_blend_constants(self.raw, &constants);
+        }
+    }
+
     #[inline]
     pub fn draw(
         &self,
@@ -241,6 +541,26 @@ impl VulkanCommandBuffer {
         }
     }
 
+    #[inline]
+    pub fn bind_index_buffer(
+        &self,
+        device: &VulkanDevice,
+        buffer: &VulkanBuffer,
+        offset: u64,
+        index_type: IndexType,
+    ) {
+        unsafe {
+            device.raw().cmd_bind_index_buffer(self.raw, buffer.raw, offset, index_type.into());
+        }
+        self.bound_index_buffer
+            .set(Some(BoundIndexBuffer { index_type, size: buffer.size.saturating_sub(offset) }));
+    }
+
+    #[inline]
+    pub fn validate_indexed_draw(&self, first_index: u32, index_count: u32) -> anyhow::Result<()> {
+        validate_indexed_draw(self.bound_index_buffer.get(), first_index, index_count)
+    }
+
     #[inline]
     pub fn draw_indexed(
         &self,
@@ -267,6 +587,7 @@ impl VulkanCommandBuffer {
     pub fn bind_descriptor_sets(
         &self,
         device: &VulkanDevice,
+        bind_point: PipelineBindPoint,
         pipeline_layout: &VulkanPipelineLayout,
         first_set: u32,
         descriptor_sets: &[vk::DescriptorSet],
@@ -275,7 +596,7 @@ impl VulkanCommandBuffer {
         unsafe {
             device.raw().cmd_bind_descriptor_sets(
                 self.raw,
-                vk::PipelineBindPoint::GRAPHICS,
+                bind_point.into(),
                 pipeline_layout.raw,
                 first_set,
                 descriptor_sets,
@@ -301,6 +622,33 @@ impl VulkanCommandBuffer {
         }
     }
 
+    #[inline]
+    pub fn update_buffer(
+        &self,
+        device: &VulkanDevice,
+        dst: &VulkanBuffer,
+        offset: u64,
+        data: &[u8],
+    ) {
+        unsafe {
+            device.raw().cmd_update_buffer(self.raw, dst.raw, offset, data);
+        }
+    }
+
+    #[inline]
+    pub fn fill_buffer(
+        &self,
+        device: &VulkanDevice,
+        dst: &VulkanBuffer,
+        offset: u64,
+        size: u64,
+        value: u32,
+    ) {
+        unsafe {
+            device.raw().cmd_fill_buffer(self.raw, dst.raw, offset, size, value);
+        }
+    }
+
     #[inline]
     pub fn copy_buffer_to_image(
         &self,
@@ -310,38 +658,164 @@ impl VulkanCommandBuffer {
         dst_image_layout: ImageLayout,
         regions: &[BufferImageCopyRegion],
     ) {
-        let mut regions_ = SmallVec::<[vk::BufferImageCopy; 4]>::new();
+        unsafe {
+            device.raw().cmd_copy_buffer_to_image(
+                self.raw,
+                src.raw,
+                dst.raw,
+                dst_image_layout.into(),
+                &buffer_image_copies(regions),
+            );
+        }
+    }
+
+    #[inline]
+    pub fn copy_image_to_buffer(
+        &self,
+        device: &VulkanDevice,
+        src: &VulkanImage,
+        src_image_layout: ImageLayout,
+        dst: &VulkanBuffer,
+        regions: &[BufferImageCopyRegion],
+    ) {
+        unsafe {
+            device.raw().cmd_copy_image_to_buffer(
+                self.raw,
+                src.raw,
+                src_image_layout.into(),
+                dst.raw,
+                &buffer_image_copies(regions),
+            );
+        }
+    }
+
+    pub fn copy_image(
+        &self,
+        device: &VulkanDevice,
+        src: &VulkanImage,
+        src_image_layout: ImageLayout,
+        dst: &VulkanImage,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageCopyRegion],
+    ) {
+        let subresource = |layers: ImageSubresourceLayers| {
+            vk::ImageSubresourceLayers::builder()
+                .aspect_mask(layers.aspect_mask.into())
+                .mip_level(layers.mip_level)
+                .base_array_layer(layers.base_array_layer)
+                .layer_count(layers.layer_count)
+                .build()
+        };
+        let offset = |offset: [i32; 3]| vk::Offset3D { x: offset[0], y: offset[1], z: offset[2] };
+        let mut regions_ = SmallVec::<[vk::ImageCopy; 4]>::new();
         for region in regions {
             regions_.push(
-                vk::BufferImageCopy::builder()
-                    .buffer_offset(region.buffer_offset)
-                    .buffer_image_height(region.buffer_image_height)
-                    .buffer_row_length(region.buffer_row_length)
-                    .image_subresource(
-                        vk::ImageSubresourceLayers::builder()
-                            .aspect_mask(region.aspect_mask.into())
-                            .mip_level(region.mip_level)
-                            .base_array_layer(region.base_array_layer)
-                            .layer_count(region.layer_count)
-                            .build(),
-                    )
-                    .image_offset(vk::Offset3D {
-                        x: region.image_offset[0],
-                        y: region.image_offset[1],
-                        z: region.image_offset[2],
+                vk::ImageCopy::builder()
+                    .src_subresource(subresource(region.src_subresource))
+                    .src_offset(offset(region.src_offset))
+                    .dst_subresource(subresource(region.dst_subresource))
+                    .dst_offset(offset(region.dst_offset))
+                    .extent(vk::Extent3D {
+                        width: region.extent[0],
+                        height: region.extent[1],
+                        depth: region.extent[2],
                     })
-                    .image_extent(vk::Extent3D {
-                        width: region.image_extent[0],
-                        height: region.image_extent[1],
-                        depth: region.image_extent[2],
+                    .build(),
+            );
+        }
+        unsafe {
+            device.raw().cmd_copy_image(
+                self.raw,
+                src.raw,
+                src_image_layout.into(),
+                dst.raw,
+                dst_image_layout.into(),
+                &regions_,
+            );
+        }
+    }
+
+    pub fn clear_color_image(
+        &self,
+        device: &VulkanDevice,
+        image: &VulkanImage,
+        image_layout: ImageLayout,
+        color: ClearColor,
+        ranges: &[ImageSubresourceRange],
+    ) {
+        let ranges: SmallVec<[vk::ImageSubresourceRange; 4]> =
+            ranges.iter().map(|range| range.into()).collect();
+        unsafe {
+            device.raw().cmd_clear_color_image(
+                self.raw,
+                image.raw,
+                image_layout.into(),
+                &color.into(),
+                &ranges,
+            );
+        }
+    }
+
+    pub fn clear_depth_stencil_image(
+        &self,
+        device: &VulkanDevice,
+        image: &VulkanImage,
+        image_layout: ImageLayout,
+        value: ClearDepthStencil,
+        ranges: &[ImageSubresourceRange],
+    ) {
+        let ranges: SmallVec<[vk::ImageSubresourceRange; 4]> =
+            ranges.iter().map(|range| range.into()).collect();
+        unsafe {
+            device.raw().cmd_clear_depth_stencil_image(
+                self.raw,
+                image.raw,
+                image_layout.into(),
+                &value.into(),
+                &ranges,
+            );
+        }
+    }
+
+    pub fn resolve_image(
+        &self,
+        device: &VulkanDevice,
+        src: &VulkanImage,
+        src_image_layout: ImageLayout,
+        dst: &VulkanImage,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageResolveRegion],
+    ) {
+        let subresource = |mip_level, region: &ImageResolveRegion| {
+            vk::ImageSubresourceLayers::builder()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .mip_level(mip_level)
+                .base_array_layer(region.base_array_layer)
+                .layer_count(region.layer_count)
+                .build()
+        };
+        let offset = |offset: [i32; 3]| vk::Offset3D { x: offset[0], y: offset[1], z: offset[2] };
+        let mut regions_ = SmallVec::<[vk::ImageResolve; 4]>::new();
+        for region in regions {
+            regions_.push(
+                vk::ImageResolve::builder()
+                    .src_subresource(subresource(region.src_mip_level, region))
+                    .src_offset(offset(region.src_offset))
+                    .dst_subresource(subresource(region.dst_mip_level, region))
+                    .dst_offset(offset(region.dst_offset))
+                    .extent(vk::Extent3D {
+                        width: region.extent[0],
+                        height: region.extent[1],
+                        depth: region.extent[2],
                     })
                     .build(),
             );
         }
         unsafe {
-            device.raw().cmd_copy_buffer_to_image(
+            device.raw().cmd_resolve_image(
                 self.raw,
                 src.raw,
+                src_image_layout.into(),
                 dst.raw,
                 dst_image_layout.into(),
                 &regions_,
@@ -349,50 +823,101 @@ impl VulkanCommandBuffer {
         }
     }
 
+    pub fn blit_image(
+        &self,
+        device: &VulkanDevice,
+        src: &VulkanImage,
+        dst: &VulkanImage,
+        desc: &BlitImageDesc,
+    ) {
+        let subresource = |layers: ImageSubresourceLayers| {
+            vk::ImageSubresourceLayers::builder()
+                .aspect_mask(layers.aspect_mask.into())
+                .mip_level(layers.mip_level)
+                .base_array_layer(layers.base_array_layer)
+                .layer_count(layers.layer_count)
+                .build()
+        };
+        let offsets = |offsets: [[i32; 3]; 2]| {
+            offsets.map(|offset| vk::Offset3D { x: offset[0], y: offset[1], z: offset[2] })
+        };
+        let mut regions_ = SmallVec::<[vk::ImageBlit; 4]>::new();
+        for region in desc.regions {
+            regions_.push(
+                vk::ImageBlit::builder()
+                    .src_subresource(subresource(region.src_subresource))
+                    .src_offsets(offsets(region.src_offsets))
+                    .dst_subresource(subresource(region.dst_subresource))
+                    .dst_offsets(offsets(region.dst_offsets))
+                    .build(),
+            );
+        }
+        unsafe {
+            device.raw().cmd_blit_image(
+                self.raw,
+                src.raw,
+                desc.src_image_layout.into(),
+                dst.raw,
+                desc.dst_image_layout.into(),
+                &regions_,
+                desc.filter.into(),
+            );
+        }
+    }
+
     #[inline]
     pub fn pipeline_barrier(
         &self,
         device: &VulkanDevice,
         src_stage: PipelineStageFlags,
         dst_stage: PipelineStageFlags,
+        memory_barriers: &[MemoryBarrier],
+        buffer_barriers: &[BufferMemoryBarrier],
         image_barriers: &[ImageMemoryBarrier],
+        p_buffer: &Pool<VulkanBuffer>,
         p_image: &Pool<VulkanImage>,
     ) -> anyhow::Result<()> {
-        let mut image_memory_barriers = SmallVec::<[vk::ImageMemoryBarrier; 4]>::new();
-        for barrier in image_barriers {
-            image_memory_barriers.push(
-                vk::ImageMemoryBarrier::builder()
-                    .old_layout(barrier.old_layout.into())
-                    .new_layout(barrier.new_layout.into())
+        let memory_barriers =
+            memory_barriers.iter().map(vk::MemoryBarrier::from).collect::<SmallVec<[_; 2]>>();
+        let mut buffer_memory_barriers = SmallVec::<[vk::BufferMemoryBarrier; 4]>::new();
+        for barrier in buffer_barriers {
+            let buffer = p_buffer.get(barrier.buffer).context("Buffer not found")?;
+            buffer_memory_barriers.push(
+                vk::BufferMemoryBarrier::builder()
                     .src_queue_family_index(
                         barrier.src_queue_family_index.unwrap_or(vk::QUEUE_FAMILY_IGNORED),
                     )
                     .dst_queue_family_index(
                         barrier.dst_queue_family_index.unwrap_or(vk::QUEUE_FAMILY_IGNORED),
                     )
-                    .image(p_image.get(barrier.image).context("Image not found")?.raw)
-                    .subresource_range(
-                        vk::ImageSubresourceRange::builder()
-                            .aspect_mask(barrier.aspect_mask.into())
-                            .base_mip_level(barrier.base_mip_level)
-                            .level_count(barrier.level_count)
-                            .base_array_layer(barrier.base_array_layer)
-                            .layer_count(barrier.layer_count)
-                            .build(),
-                    )
+                    .buffer(buffer.raw)
+                    .offset(barrier.offset)
+                    .size(barrier.size)
                     .src_access_mask(barrier.src_access_mask.into())
                     .dst_access_mask(barrier.dst_access_mask.into())
                     .build(),
             );
         }
+        let mut image_memory_barriers = SmallVec::<[vk::ImageMemoryBarrier; 4]>::new();
+        for barrier in image_barriers {
+            let image = p_image.get(barrier.image).context("Image not found")?;
+            let aspect_mask = resolve_barrier_aspect(
+                image.desc.format,
+                barrier.aspect_mask.into(),
+                barrier.old_layout.into(),
+                barrier.new_layout.into(),
+                device.separate_depth_stencil_layouts(),
+            )?;
+            image_memory_barriers.push(image_barrier(barrier, image.raw, aspect_mask));
+        }
         unsafe {
             device.raw().cmd_pipeline_barrier(
                 self.raw,
                 src_stage.into(),
                 dst_stage.into(),
                 vk::DependencyFlags::empty(),
-                &[],
-                &[],
+                &memory_barriers,
+                &buffer_memory_barriers,
                 &image_memory_barriers,
             );
         }
@@ -409,6 +934,7 @@ impl VulkanCommandBuffer {
         unsafe {
             device.raw().reset_command_buffer(self.raw, flags)?;
         }
+        self.state.set(CommandBufferState::Initial);
         Ok(())
     }
 
@@ -420,5 +946,1328 @@ impl VulkanCommandBuffer {
         self.pool = None;
         self.cache_framebuffer = None;
         self.cache_render_pass = None;
+        self.state.set(CommandBufferState::Initial);
+    }
+
+    /// Called when the owning pool is reset, which returns every buffer to the initial state.
+    #[inline]
+    pub(crate) fn pool_reset(&self) {
+        self.state.set(CommandBufferState::Initial);
+    }
+}
+
+/// Clear values in attachment order, colors then the depth stencil. Every attachment with a clear
+/// load action needs its value, the stencil one only counts for formats with a stencil aspect.
+fn render_pass_clear_values(
+    output: &VulkanRenderPassOutput,
+    clear_values: Option<&[ClearColor]>,
+    clear_depth_stencil: Option<ClearDepthStencil>,
+) -> anyhow::Result<SmallVec<[vk::ClearValue; MAX_RENDER_TARGETS + 1]>> {
+    let mut values = SmallVec::new();
+    for i in 0..output.num_colors as usize {
+        match clear_values.and_then(|v| v.get(i)) {
+            Some(color) => {
+                check_clear_color(output.color_formats[i], *color)
+                    .with_context(|| format!("Color attachment {}", i))?;
+                values.push((*color).into())
+            }
+            None if output.color_load[i] == vk::AttachmentLoadOp::CLEAR => {
+                anyhow::bail!("Color attachment {} is cleared but has no clear color.", i)
+            }
+            None => values.push(vk::ClearValue::default()),
+        }
+    }
+
+    if output.has_depth_stencil() {
+        let cleared = output.depth_load == vk::AttachmentLoadOp::CLEAR
+            || (output.stencil_load == vk::AttachmentLoadOp::CLEAR
+                && has_stencil_aspect(output.depth_stencil_format));
+        match clear_depth_stencil {
+            Some(value) => values.push(value.into()),
+            None if cleared => {
+                anyhow::bail!("Depth stencil attachment is cleared but has no clear value.")
+            }
+            None => values.push(vk::ClearValue::default()),
+        }
+    }
+    Ok(values)
+}
+
+/// Integer attachments are cleared with integer values of the same signedness, clearing them
+/// with floats is invalid.
+pub fn check_clear_color(format: vk::Format, color: ClearColor) -> anyhow::Result<()> {
+    let sign = integer_format_sign(format);
+    if !matches!(
+        (sign, color),
+        (None, ClearColor::Float(_))
+            | (Some(false), ClearColor::Uint(_))
+            | (Some(true), ClearColor::Int(_))
+    ) {
+        let expected = match sign {
+            None => "Float",
+            Some(false) => "Uint",
+            Some(true) => "Int",
+        };
+        anyhow::bail!(
+            "A {:?} attachment needs a {} clear color, got {:?}.",
+            format,
+            expected,
+            color
+        );
+    }
+    Ok(())
+}
+
+/// A resolve goes from a multisampled image to a single sampled one of the same format, each
+/// region has to fit in the mip levels it reads and writes.
+pub fn check_resolve(
+    src: &VulkanImageDesc,
+    dst: &VulkanImageDesc,
+    regions: &[ImageResolveRegion],
+) -> anyhow::Result<()> {
+    if src.samples == vk::SampleCountFlags::TYPE_1 {
+        anyhow::bail!("cmd_resolve_image needs a multisampled source image.");
+    }
+    if dst.samples != vk::SampleCountFlags::TYPE_1 {
+        anyhow::bail!(
+            "cmd_resolve_image needs a single sampled destination image, got {:?}.",
+            dst.samples
+        );
+    }
+    if src.format != dst.format {
+        anyhow::bail!(
+            "cmd_resolve_image source is {:?} but the destination is {:?}.",
+            src.format,
+            dst.format
+        );
+    }
+    for (i, region) in regions.iter().enumerate() {
+        for (image, mip_level, offset, side) in [
+            (src, region.src_mip_level, region.src_offset, "source"),
+            (dst, region.dst_mip_level, region.dst_offset, "destination"),
+        ] {
+            let width = (image.extent.width >> mip_level).max(1);
+            let height = (image.extent.height >> mip_level).max(1);
+            let fits = offset[0] >= 0
+                && offset[1] >= 0
+                && offset[0] as u64 + region.extent[0] as u64 <= width as u64
+                && offset[1] as u64 + region.extent[1] as u64 <= height as u64;
+            if !fits {
+                anyhow::bail!(
+                    "Resolve region {} is {}x{} at {:?}, outside the {}x{} {} mip level {}.",
+                    i,
+                    region.extent[0],
+                    region.extent[1],
+                    offset,
+                    width,
+                    height,
+                    side,
+                    mip_level
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Each work group count of a dispatch is bounded by `maxComputeWorkGroupCount`.
+pub fn check_dispatch(groups: [u32; 3], max_groups: [u32; 3]) -> anyhow::Result<()> {
+    if groups.iter().zip(max_groups).any(|(count, max)| *count > max) {
+        anyhow::bail!(
+            "cmd_dispatch of {:?} work groups exceeds the device limit of {:?}.",
+            groups,
+            max_groups
+        );
+    }
+    Ok(())
+}
+
+fn buffer_image_copies(regions: &[BufferImageCopyRegion]) -> SmallVec<[vk::BufferImageCopy; 4]> {
+    let mut regions_ = SmallVec::new();
+    for region in regions {
+        regions_.push(
+            vk::BufferImageCopy::builder()
+                .buffer_offset(region.buffer_offset)
+                .buffer_image_height(region.buffer_image_height)
+                .buffer_row_length(region.buffer_row_length)
+                .image_subresource(
+                    vk::ImageSubresourceLayers::builder()
+                        .aspect_mask(region.aspect_mask.into())
+                        .mip_level(region.mip_level)
+                        .base_array_layer(region.base_array_layer)
+                        .layer_count(region.layer_count)
+                        .build(),
+                )
+                .image_offset(vk::Offset3D {
+                    x: region.image_offset[0],
+                    y: region.image_offset[1],
+                    z: region.image_offset[2],
+                })
+                .image_extent(vk::Extent3D {
+                    width: region.image_extent[0],
+                    height: region.image_extent[1],
+                    depth: region.image_extent[2],
+                })
+                .build(),
+        );
+    }
+    regions_
+}
+
+/// The raw barrier for `barrier`, with the aspect already resolved for the image format.
+fn image_barrier(
+    barrier: &ImageMemoryBarrier,
+    image: vk::Image,
+    aspect_mask: vk::ImageAspectFlags,
+) -> vk::ImageMemoryBarrier {
+    let mut range: vk::ImageSubresourceRange = barrier.into();
+    range.aspect_mask = aspect_mask;
+    vk::ImageMemoryBarrier::builder()
+        .old_layout(barrier.old_layout.into())
+        .new_layout(barrier.new_layout.into())
+        .src_queue_family_index(barrier.src_queue_family_index.unwrap_or(vk::QUEUE_FAMILY_IGNORED))
+        .dst_queue_family_index(barrier.dst_queue_family_index.unwrap_or(vk::QUEUE_FAMILY_IGNORED))
+        .image(image)
+        .subresource_range(range)
+        .src_access_mask(barrier.src_access_mask.into())
+        .dst_access_mask(barrier.dst_access_mask.into())
+        .build()
+}
+
+/// Fails unless `extent` texels at `offset` fit in mip level `mip_level` and the layers
+/// `base_array_layer..base_array_layer + layer_count` of `image`.
+fn check_copy_box(
+    image: &VulkanImageDesc,
+    mip_level: u32,
+    base_array_layer: u32,
+    layer_count: u32,
+    offset: [i32; 3],
+    extent: [u32; 3],
+    what: std::fmt::Arguments,
+) -> anyhow::Result<()> {
+    if mip_level >= image.mip_levels
+        || base_array_layer as u64 + layer_count as u64 > image.array_layers as u64
+    {
+        anyhow::bail!(
+            "{} uses mip level {} and layers {}..{}, the image has {} and {}.",
+            what,
+            mip_level,
+            base_array_layer,
+            base_array_layer as u64 + layer_count as u64,
+            image.mip_levels,
+            image.array_layers
+        );
+    }
+    let size = [image.extent.width, image.extent.height, image.extent.depth]
+        .map(|extent| (extent >> mip_level).max(1) as i64);
+    let fits = (0..3)
+        .all(|axis| offset[axis] >= 0 && offset[axis] as i64 + extent[axis] as i64 <= size[axis]);
+    if !fits {
+        anyhow::bail!(
+            "{} is {}x{}x{} at {:?}, outside the {}x{}x{} mip level {}.",
+            what,
+            extent[0],
+            extent[1],
+            extent[2],
+            offset,
+            size[0],
+            size[1],
+            size[2],
+            mip_level
+        );
+    }
+    Ok(())
+}
+
+/// A copy between images moves texels without converting them, so both formats have to be
+/// the same or the same size, and the images the same sample count. Every region has to fit
+/// in both images.
+pub fn check_copy_image(
+    src: &VulkanImageDesc,
+    dst: &VulkanImageDesc,
+    regions: &[ImageCopyRegion],
+) -> anyhow::Result<()> {
+    if src.samples != dst.samples {
+        anyhow::bail!(
+            "cmd_copy_image copies between images with the same sample count, not {:?} and {:?}.",
+            src.samples,
+            dst.samples
+        );
+    }
+    let same_size = matches!(
+        (format_size_class(src.format), format_size_class(dst.format)),
+        (Some(a), Some(b)) if a == b
+    );
+    if src.format != dst.format && !same_size {
+        anyhow::bail!(
+            "cmd_copy_image can't copy {:?} texels into {:?}, see cmd_blit_image.",
+            src.format,
+            dst.format
+        );
+    }
+    for (i, region) in regions.iter().enumerate() {
+        for (image, layers, offset, side) in [
+            (src, region.src_subresource, region.src_offset, "source"),
+            (dst, region.dst_subresource, region.dst_offset, "destination"),
+        ] {
+            check_copy_box(
+                image,
+                layers.mip_level,
+                layers.base_array_layer,
+                layers.layer_count,
+                offset,
+                region.extent,
+                format_args!("Copy region {} {}", i, side),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// A copy into a buffer reads a single sampled image, and every region has to fit in the
+/// image and, when the texel size of the format is known, in the `buffer_size` bytes.
+pub fn check_copy_image_to_buffer(
+    src: &VulkanImageDesc,
+    buffer_size: u64,
+    regions: &[BufferImageCopyRegion],
+) -> anyhow::Result<()> {
+    if src.samples != vk::SampleCountFlags::TYPE_1 {
+        anyhow::bail!("cmd_copy_image_to_buffer needs a single sampled image, resolve it first.");
+    }
+    let texel_size = format_size_class(src.format).map(|bits| bits as u64 / 8);
+    for (i, region) in regions.iter().enumerate() {
+        check_copy_box(
+            src,
+            region.mip_level,
+            region.base_array_layer,
+            region.layer_count,
+            region.image_offset,
+            region.image_extent,
+            format_args!("Copy region {}", i),
+        )?;
+        let Some(texel_size) = texel_size else { continue };
+        if !region.buffer_offset.is_multiple_of(texel_size) {
+            anyhow::bail!(
+                "Copy region {} starts at byte {}, not a multiple of the {} byte {:?} texels.",
+                i,
+                region.buffer_offset,
+                texel_size,
+                src.format
+            );
+        }
+        // Zero row length or image height means tightly packed
+        let [width, height, depth] = region.image_extent.map(|extent| extent as u64);
+        let row_length =
+            if region.buffer_row_length == 0 { width } else { region.buffer_row_length as u64 };
+        let image_height = if region.buffer_image_height == 0 {
+            height
+        } else {
+            region.buffer_image_height as u64
+        };
+        let slices = depth * region.layer_count as u64;
+        if width == 0 || height == 0 || slices == 0 {
+            continue;
+        }
+        let texels = ((slices - 1) * image_height + height - 1) * row_length + width;
+        let end = region.buffer_offset.saturating_add(texels.saturating_mul(texel_size));
+        if end > buffer_size {
+            anyhow::bail!(
+                "Copy region {} writes bytes {}..{}, past the end of the {} byte buffer.",
+                i,
+                region.buffer_offset,
+                end,
+                buffer_size
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Clears outside a render pass write images in the `General` or `TransferDstOptimal` layout,
+/// which they are not transitioned into. Every range has to use aspects of the format, color
+/// ones for `cmd_clear_color_image` and depth or stencil ones otherwise, and fit in the image.
+pub fn check_clear_image(
+    image: &VulkanImageDesc,
+    image_layout: ImageLayout,
+    ranges: &[ImageSubresourceRange],
+    depth_stencil: bool,
+) -> anyhow::Result<()> {
+    let name =
+        if depth_stencil { "cmd_clear_depth_stencil_image" } else { "cmd_clear_color_image" };
+    if !matches!(image_layout, ImageLayout::General | ImageLayout::TransferDstOptimal) {
+        anyhow::bail!(
+            "{} needs the image in General or TransferDstOptimal, not {:?}.",
+            name,
+            image_layout
+        );
+    }
+    let aspects = format_aspects(image.format);
+    if depth_stencil == (aspects == vk::ImageAspectFlags::COLOR) {
+        anyhow::bail!("{} can't clear a {:?} image.", name, image.format);
+    }
+    for (i, range) in ranges.iter().enumerate() {
+        let range_aspects: vk::ImageAspectFlags = range.aspect_mask.into();
+        if range_aspects.is_empty() || !aspects.contains(range_aspects) {
+            anyhow::bail!(
+                "Clear range {} covers {:?}, a {:?} image only has {:?}.",
+                i,
+                range_aspects,
+                image.format,
+                aspects
+            );
+        }
+        let fits = |base: u32, count: u32, remaining: u32, total: u32| {
+            base < total && (count == remaining || base as u64 + count as u64 <= total as u64)
+        };
+        if !fits(range.base_mip_level, range.level_count, REMAINING_MIP_LEVELS, image.mip_levels)
+            || !fits(
+                range.base_array_layer,
+                range.layer_count,
+                REMAINING_ARRAY_LAYERS,
+                image.array_layers,
+            )
+        {
+            anyhow::bail!(
+                "Clear range {} is outside the {} mip levels and {} layers of the image.",
+                i,
+                image.mip_levels,
+                image.array_layers
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A blit reads and writes single sampled images whose formats convert into each other and
+/// support blitting, `src_features` and `dst_features` are those of the images' tiling.
+/// Linear filtering also needs the source format to support it, and every region has to fit
+/// in the mip levels and layers it reads and writes.
+pub fn check_blit(
+    src: &VulkanImageDesc,
+    src_features: vk::FormatFeatureFlags,
+    dst: &VulkanImageDesc,
+    dst_features: vk::FormatFeatureFlags,
+    regions: &[ImageBlitRegion],
+    filter: FilterType,
+) -> anyhow::Result<()> {
+    if src.samples != vk::SampleCountFlags::TYPE_1 || dst.samples != vk::SampleCountFlags::TYPE_1 {
+        anyhow::bail!("cmd_blit_image needs single sampled images, see cmd_resolve_image.");
+    }
+    if !is_blit_compatible(src.format, dst.format) {
+        anyhow::bail!("cmd_blit_image can't convert {:?} into {:?}.", src.format, dst.format);
+    }
+    if !src_features.contains(vk::FormatFeatureFlags::BLIT_SRC) {
+        anyhow::bail!("{:?} can't be blitted from with {:?} tiling.", src.format, src.tiling);
+    }
+    if !dst_features.contains(vk::FormatFeatureFlags::BLIT_DST) {
+        anyhow::bail!("{:?} can't be blitted to with {:?} tiling.", dst.format, dst.tiling);
+    }
+    if filter == FilterType::Linear {
+        if format_aspects(src.format)
+            .intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL)
+        {
+            anyhow::bail!("Depth and stencil blits of {:?} need FilterType::Nearest.", src.format);
+        }
+        if !src_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+            anyhow::bail!("{:?} doesn't support linear filtering in blits.", src.format);
+        }
+    }
+    for (i, region) in regions.iter().enumerate() {
+        for (image, layers, offsets, side) in [
+            (src, region.src_subresource, region.src_offsets, "source"),
+            (dst, region.dst_subresource, region.dst_offsets, "destination"),
+        ] {
+            if layers.mip_level >= image.mip_levels
+                || layers.base_array_layer as u64 + layers.layer_count as u64
+                    > image.array_layers as u64
+            {
+                anyhow::bail!(
+                    "Blit region {} uses {} mip level {} and layers {}..{}, the image has {} and {}.",
+                    i,
+                    side,
+                    layers.mip_level,
+                    layers.base_array_layer,
+                    layers.base_array_layer as u64 + layers.layer_count as u64,
+                    image.mip_levels,
+                    image.array_layers
+                );
+            }
+            let size = [image.extent.width, image.extent.height, image.extent.depth]
+                .map(|extent| (extent >> layers.mip_level).max(1) as i64);
+            let fits = offsets
+                .iter()
+                .all(|offset| (0..3).all(|axis| (0..=size[axis]).contains(&(offset[axis] as i64))));
+            if !fits {
+                anyhow::bail!(
+                    "Blit region {} spans {:?} to {:?}, outside the {}x{}x{} {} mip level {}.",
+                    i,
+                    offsets[0],
+                    offsets[1],
+                    size[0],
+                    size[1],
+                    size[2],
+                    side,
+                    layers.mip_level
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Inline updates are recorded into the command buffer, so they are capped at 64KB and like
+/// their offset have to be a multiple of 4 bytes.
+pub fn check_update_buffer(offset: u64, size: usize, buffer_size: u64) -> anyhow::Result<()> {
+    const MAX_UPDATE_SIZE: usize = 65536;
+    if !offset.is_multiple_of(4) || !size.is_multiple_of(4) {
+        anyhow::bail!(
+            "cmd_update_buffer needs a 4 byte aligned offset and size, got {} bytes at {}.",
+            size,
+            offset
+        );
+    }
+    if size == 0 || size > MAX_UPDATE_SIZE {
+        anyhow::bail!("cmd_update_buffer writes 1 to {} bytes, got {}.", MAX_UPDATE_SIZE, size);
+    }
+    if offset.checked_add(size as u64).is_none_or(|end| end > buffer_size) {
+        anyhow::bail!(
+            "cmd_update_buffer writes {} bytes at offset {} past the end of a {} byte buffer.",
+            size,
+            offset,
+            buffer_size
+        );
+    }
+    Ok(())
+}
+
+/// Fills write whole 4 byte words from a 4 byte aligned offset, `WHOLE_SIZE` fills to the
+/// last full word of the buffer.
+pub fn check_fill_buffer(offset: u64, size: u64, buffer_size: u64) -> anyhow::Result<()> {
+    if !offset.is_multiple_of(4) {
+        anyhow::bail!("cmd_fill_buffer offset {} is not a multiple of 4.", offset);
+    }
+    if offset >= buffer_size {
+        anyhow::bail!(
+            "cmd_fill_buffer offset {} is past the end of a {} byte buffer.",
+            offset,
+            buffer_size
+        );
+    }
+    if size != WHOLE_SIZE && (size == 0 || !size.is_multiple_of(4) || size > buffer_size - offset) {
+        anyhow::bail!(
+            "cmd_fill_buffer can't fill {} bytes at offset {} of a {} byte buffer.",
+            size,
+            offset,
+            buffer_size
+        );
+    }
+    Ok(())
+}
+
+/// A buffer barrier covers a non-empty range inside the buffer, `WHOLE_SIZE` runs to its end.
+pub fn check_buffer_barrier(offset: u64, size: u64, buffer_size: u64) -> anyhow::Result<()> {
+    if offset >= buffer_size {
+        anyhow::bail!(
+            "Buffer barrier offset {} is past the end of a {} byte buffer.",
+            offset,
+            buffer_size
+        );
+    }
+    if size != WHOLE_SIZE && (size == 0 || size > buffer_size - offset) {
+        anyhow::bail!(
+            "Buffer barrier of {} bytes at offset {} doesn't fit a {} byte buffer.",
+            size,
+            offset,
+            buffer_size
+        );
+    }
+    Ok(())
+}
+
+/// The group counts of an indirect dispatch are read from a 4 byte aligned offset, and all
+/// three have to be inside the buffer.
+pub fn check_dispatch_indirect(offset: u64, buffer_size: u64) -> anyhow::Result<()> {
+    const COMMAND_SIZE: u64 = std::mem::size_of::<vk::DispatchIndirectCommand>() as u64;
+    if !offset.is_multiple_of(4) {
+        anyhow::bail!("cmd_dispatch_indirect offset {} is not a multiple of 4.", offset);
+    }
+    if offset.checked_add(COMMAND_SIZE).is_none_or(|end| end > buffer_size) {
+        anyhow::bail!(
+            "cmd_dispatch_indirect reads {} bytes at offset {} past the end of a {} byte buffer.",
+            COMMAND_SIZE,
+            offset,
+            buffer_size
+        );
+    }
+    Ok(())
+}
+
+/// Commands need a begun command buffer, draws need a render pass and transfers must happen
+/// outside one.
+fn validate_scope(
+    state: CommandBufferState,
+    scope: RenderPassScope,
+    cmd: &str,
+) -> anyhow::Result<()> {
+    match (state, scope) {
+        (CommandBufferState::Initial | CommandBufferState::Executable, _) => {
+            anyhow::bail!("{} called on a command buffer that is not recording.", cmd)
+        }
+        (CommandBufferState::Recording, RenderPassScope::Inside) => {
+            anyhow::bail!("{} called outside a render pass.", cmd)
+        }
+        (CommandBufferState::InRenderPass, RenderPassScope::Outside) => {
+            anyhow::bail!("{} called inside a render pass.", cmd)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_rendering(begun: bool, dynamic_rendering: bool, cmd: &str) -> anyhow::Result<()> {
+    match (begun, dynamic_rendering) {
+        (true, false) => {
+            anyhow::bail!(
+                "{} called inside cmd_begin_rendering, end it with cmd_end_rendering.",
+                cmd
+            )
+        }
+        (false, true) => {
+            anyhow::bail!("{} called inside a render pass, end it with cmd_end_render_pass.", cmd)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Beginning an executable command buffer implicitly resets it, the pool allows that.
+fn validate_begin(state: CommandBufferState) -> anyhow::Result<()> {
+    match state {
+        CommandBufferState::Initial | CommandBufferState::Executable => Ok(()),
+        CommandBufferState::Recording | CommandBufferState::InRenderPass => {
+            anyhow::bail!("cmd_begin called on a command buffer that is already recording.")
+        }
+    }
+}
+
+/// Vulkan needs both depth bounds in 0..1, either order is fine so reverse-Z viewports pass.
+pub fn validate_viewport(viewport: &Viewport) -> anyhow::Result<()> {
+    if viewport.width <= 0.0 || viewport.height == 0.0 {
+        anyhow::bail!("Viewport of {}x{} is empty.", viewport.width, viewport.height);
+    }
+    if !(0.0..=1.0).contains(&viewport.min_depth) || !(0.0..=1.0).contains(&viewport.max_depth) {
+        anyhow::bail!(
+            "Viewport depth range {}..{} is outside 0..1.",
+            viewport.min_depth,
+            viewport.max_depth
+        );
+    }
+    Ok(())
+}
+
+/// Viewport or scissor indices `first..first + count`, only index 0 is available without
+/// `RequestedFeatures::MULTI_VIEWPORT`.
+pub fn check_viewport_range(
+    first: u32,
+    count: usize,
+    enabled: RequestedFeatures,
+) -> anyhow::Result<()> {
+    if count == 0 {
+        anyhow::bail!("No viewports or scissors to set.");
+    }
+    if first as usize + count > 1 && !enabled.contains(RequestedFeatures::MULTI_VIEWPORT) {
+        anyhow::bail!(
+            "Indices {}..{} need RequestedFeatures::MULTI_VIEWPORT.",
+            first,
+            first as usize + count
+        );
+    }
+    Ok(())
+}
+
+/// Lines are 1 pixel wide unless `RequestedFeatures::WIDE_LINES` is enabled.
+pub fn check_line_width(width: f32, enabled: RequestedFeatures) -> anyhow::Result<()> {
+    if width.is_nan() || width <= 0.0 {
+        anyhow::bail!("Line width {} is not positive.", width);
+    }
+    if width != 1.0 && !enabled.contains(RequestedFeatures::WIDE_LINES) {
+        anyhow::bail!("Line width {} needs RequestedFeatures::WIDE_LINES.", width);
+    }
+    Ok(())
+}
+
+/// Checks an indexed draw only reads indices inside the bound index buffer, reading them with
+/// the wrong `IndexType` typically runs past its end.
+fn validate_indexed_draw(
+    bound: Option<BoundIndexBuffer>,
+    first_index: u32,
+    index_count: u32,
+) -> anyhow::Result<()> {
+    let bound = bound.context("Indexed draw without a bound index buffer.")?;
+    let end = (first_index as u64 + index_count as u64) * bound.index_type.size();
+    if end > bound.size {
+        anyhow::bail!(
+            "Indexed draw reads {} bytes of {:?} indices but the bound index buffer only has {} bytes.",
+            end,
+            bound.index_type,
+            bound.size
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_stencil_clear_follows_colors() {
+        let output = VulkanRenderPassOutput::from(
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::B8G8R8A8_UNORM,
+                    ImageLayout::PresentSrcKhr,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .set_depth_stencil(
+                    Format::D24_UNORM_S8_UINT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Load,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .build(),
+        );
+        let color = ClearColor::new([0.0, 0.0, 0.0, 1.0]);
+
+        let values = render_pass_clear_values(
+            &output,
+            Some(&[color]),
+            Some(ClearDepthStencil { depth: 1.0, stencil: 0x80 }),
+        )
+        .unwrap();
+        assert_eq!(values.len(), 2);
+        let depth_stencil = unsafe { values[1].depth_stencil };
+        assert_eq!((depth_stencil.depth, depth_stencil.stencil), (1.0, 0x80));
+
+        // Only the stencil is cleared, it still needs a value
+        let err = render_pass_clear_values(&output, Some(&[color]), None).err().unwrap();
+        assert_eq!(err.to_string(), "Depth stencil attachment is cleared but has no clear value.");
+        assert!(render_pass_clear_values(&output, None, None).is_err());
+    }
+
+    #[test]
+    fn uint_attachment_clears_with_integers() {
+        let output = VulkanRenderPassOutput::from(
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::R8G8B8A8_UNORM,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .add_color(
+                    Format::R32_UINT,
+                    ImageLayout::ShaderReadOnlyOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .build(),
+        );
+        let color = ClearColor::new([0.0, 0.0, 0.0, 1.0]);
+
+        let values = render_pass_clear_values(
+            &output,
+            Some(&[color, ClearColor::uint([u32::MAX; 4])]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(unsafe { values[1].color.uint32 }, [u32::MAX; 4]);
+
+        let err = render_pass_clear_values(&output, Some(&[color, color]), None).err().unwrap();
+        assert_eq!(err.to_string(), "Color attachment 1");
+        assert_eq!(
+            err.root_cause().to_string(),
+            "A R32_UINT attachment needs a Uint clear color, got Float([0.0, 0.0, 0.0, 1.0])."
+        );
+        assert!(render_pass_clear_values(&output, Some(&[color, ClearColor::int([-1; 4])]), None)
+            .is_err());
+    }
+
+    #[test]
+    fn stencil_clear_needs_a_stencil_aspect() {
+        let output = VulkanRenderPassOutput::from(
+            RenderPassOutput::builder()
+                .set_depth_stencil(
+                    Format::D32_SFLOAT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Load,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .build(),
+        );
+        let values = render_pass_clear_values(&output, None, None).unwrap();
+        assert_eq!(values.len(), 1);
+    }
+
+    #[test]
+    fn index_type_mismatch() {
+        // Six u16 indices
+        let size = 6 * 2;
+        let u16_buffer = BoundIndexBuffer { index_type: IndexType::U16, size };
+        assert!(validate_indexed_draw(Some(u16_buffer), 0, 6).is_ok());
+        assert!(validate_indexed_draw(Some(u16_buffer), 3, 4).is_err());
+
+        // The same buffer bound as U32 overruns when drawing its six indices
+        let as_u32 = BoundIndexBuffer { index_type: IndexType::U32, size };
+        let err = validate_indexed_draw(Some(as_u32), 0, 6).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Indexed draw reads 24 bytes of U32 indices but the bound index buffer only has 12 bytes."
+        );
+
+        assert!(validate_indexed_draw(None, 0, 3).is_err());
+    }
+
+    #[test]
+    fn viewport_depth_range() {
+        let viewport = Viewport::new(1600.0, 900.0);
+        assert!(validate_viewport(&viewport).is_ok());
+        assert!(validate_viewport(&viewport.reverse_z()).is_ok());
+
+        let err = validate_viewport(&Viewport { max_depth: 2.0, ..viewport }).unwrap_err();
+        assert_eq!(err.to_string(), "Viewport depth range 0..2 is outside 0..1.");
+        assert!(validate_viewport(&Viewport { min_depth: -1.0, ..viewport }).is_err());
+        assert!(validate_viewport(&Viewport::new(0.0, 900.0)).is_err());
+    }
+
+    #[test]
+    fn viewports_past_the_first_need_multi_viewport() {
+        assert!(check_viewport_range(0, 1, RequestedFeatures::empty()).is_ok());
+        assert_eq!(
+            check_viewport_range(0, 4, RequestedFeatures::empty()).unwrap_err().to_string(),
+            "Indices 0..4 need RequestedFeatures::MULTI_VIEWPORT."
+        );
+        assert!(check_viewport_range(1, 1, RequestedFeatures::empty()).is_err());
+        assert!(check_viewport_range(2, 4, RequestedFeatures::MULTI_VIEWPORT).is_ok());
+        assert!(check_viewport_range(0, 0, RequestedFeatures::MULTI_VIEWPORT).is_err());
+    }
+
+    #[test]
+    fn wide_lines_need_the_feature() {
+        assert!(check_line_width(1.0, RequestedFeatures::empty()).is_ok());
+        assert_eq!(
+            check_line_width(2.5, RequestedFeatures::empty()).unwrap_err().to_string(),
+            "Line width 2.5 needs RequestedFeatures::WIDE_LINES."
+        );
+        assert!(check_line_width(2.5, RequestedFeatures::WIDE_LINES).is_ok());
+        assert!(check_line_width(0.0, RequestedFeatures::WIDE_LINES).is_err());
+        assert!(check_line_width(f32::NAN, RequestedFeatures::WIDE_LINES).is_err());
+    }
+
+    fn scope_error(state: CommandBufferState, scope: RenderPassScope, cmd: &str) -> String {
+        validate_scope(state, scope, cmd).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn commands_need_a_recording_command_buffer() {
+        for state in [CommandBufferState::Initial, CommandBufferState::Executable] {
+            for scope in [RenderPassScope::Outside, RenderPassScope::Inside, RenderPassScope::Any] {
+                assert_eq!(
+                    scope_error(state, scope, "cmd_set_viewport"),
+                    "cmd_set_viewport called on a command buffer that is not recording."
+                );
+            }
+        }
+        assert_eq!(
+            scope_error(CommandBufferState::Executable, RenderPassScope::Outside, "cmd_end"),
+            "cmd_end called on a command buffer that is not recording."
+        );
+    }
+
+    #[test]
+    fn draw_outside_render_pass() {
+        assert_eq!(
+            scope_error(CommandBufferState::Recording, RenderPassScope::Inside, "cmd_draw"),
+            "cmd_draw called outside a render pass."
+        );
+        assert_eq!(
+            scope_error(
+                CommandBufferState::Recording,
+                RenderPassScope::Inside,
+                "cmd_end_render_pass"
+            ),
+            "cmd_end_render_pass called outside a render pass."
+        );
+        assert!(validate_scope(
+            CommandBufferState::InRenderPass,
+            RenderPassScope::Inside,
+            "cmd_draw"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn render_pass_begun_twice() {
+        assert_eq!(
+            scope_error(
+                CommandBufferState::InRenderPass,
+                RenderPassScope::Outside,
+                "cmd_begin_render_pass"
+            ),
+            "cmd_begin_render_pass called inside a render pass."
+        );
+        assert_eq!(
+            scope_error(CommandBufferState::InRenderPass, RenderPassScope::Outside, "cmd_end"),
+            "cmd_end called inside a render pass."
+        );
+        assert!(validate_scope(CommandBufferState::InRenderPass, RenderPassScope::Any, "cmd_bind")
+            .is_ok());
+    }
+
+    #[test]
+    fn dynamic_rendering_ends_with_its_own_command() {
+        assert_eq!(
+            validate_rendering(true, false, "cmd_end_render_pass").unwrap_err().to_string(),
+            "cmd_end_render_pass called inside cmd_begin_rendering, end it with cmd_end_rendering."
+        );
+        assert_eq!(
+            validate_rendering(true, false, "cmd_next_subpass").unwrap_err().to_string(),
+            "cmd_next_subpass called inside cmd_begin_rendering, end it with cmd_end_rendering."
+        );
+        assert_eq!(
+            validate_rendering(false, true, "cmd_end_rendering").unwrap_err().to_string(),
+            "cmd_end_rendering called inside a render pass, end it with cmd_end_render_pass."
+        );
+        assert!(validate_rendering(true, true, "cmd_end_rendering").is_ok());
+        assert!(validate_rendering(false, false, "cmd_end_render_pass").is_ok());
+    }
+
+    #[test]
+    fn begin_while_recording() {
+        assert!(validate_begin(CommandBufferState::Initial).is_ok());
+        assert!(validate_begin(CommandBufferState::Executable).is_ok());
+        for state in [CommandBufferState::Recording, CommandBufferState::InRenderPass] {
+            assert_eq!(
+                validate_begin(state).unwrap_err().to_string(),
+                "cmd_begin called on a command buffer that is already recording."
+            );
+        }
+    }
+
+    #[test]
+    fn submit_needs_an_ended_command_buffer() {
+        let cb = VulkanCommandBuffer::default();
+        assert!(cb.validate_submit().is_err());
+        cb.state.set(CommandBufferState::InRenderPass);
+        assert_eq!(
+            cb.validate_submit().unwrap_err().to_string(),
+            "queue_submit called with a command buffer in the InRenderPass state, it must be ended first."
+        );
+        cb.state.set(CommandBufferState::Executable);
+        assert!(cb.validate_submit().is_ok());
+    }
+
+    #[test]
+    fn resolve_needs_matching_images() {
+        let image = |samples, width, height| VulkanImageDesc {
+            format: vk::Format::R8G8B8A8_UNORM,
+            extent: vk::Extent3D { width, height, depth: 1 },
+            samples,
+            mip_levels: 1,
+            array_layers: 1,
+            ..Default::default()
+        };
+        let msaa = image(vk::SampleCountFlags::TYPE_4, 1280, 720);
+        let single = image(vk::SampleCountFlags::TYPE_1, 1280, 720);
+        let region = ImageResolveRegion::new(1280, 720);
+
+        assert!(check_resolve(&msaa, &single, &[region]).is_ok());
+        assert_eq!(
+            check_resolve(&single, &single, &[region]).unwrap_err().to_string(),
+            "cmd_resolve_image needs a multisampled source image."
+        );
+        assert!(check_resolve(&msaa, &msaa, &[region]).is_err());
+        let srgb = VulkanImageDesc { format: vk::Format::R8G8B8A8_SRGB, ..single };
+        assert!(check_resolve(&msaa, &srgb, &[region]).is_err());
+
+        let small = image(vk::SampleCountFlags::TYPE_1, 640, 360);
+        assert_eq!(
+            check_resolve(&msaa, &small, &[region]).unwrap_err().to_string(),
+            "Resolve region 0 is 1280x720 at [0, 0, 0], outside the 640x360 destination mip level 0."
+        );
+    }
+
+    #[test]
+    fn blit_formats_filters_and_regions() {
+        let image = |format, width, height, mip_levels| VulkanImageDesc {
+            format,
+            extent: vk::Extent3D { width, height, depth: 1 },
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels,
+            array_layers: 1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            ..Default::default()
+        };
+        let blit = vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST;
+        let filterable = blit | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+        let offscreen = image(vk::Format::R32G32B32A32_SFLOAT, 1920, 1080, 1);
+        let swapchain = image(vk::Format::B8G8R8A8_SRGB, 1280, 720, 1);
+        let scale = ImageBlitRegion::new([1920, 1080], [1280, 720]);
+
+        // Float into normalized is a conversion blits can do
+        let check = |src, src_features, dst, regions: &[ImageBlitRegion], filter| {
+            check_blit(src, src_features, dst, blit, regions, filter)
+        };
+        assert!(check(&offscreen, filterable, &swapchain, &[scale], FilterType::Linear).is_ok());
+        assert_eq!(
+            check(&offscreen, blit, &swapchain, &[scale], FilterType::Linear)
+                .unwrap_err()
+                .to_string(),
+            "R32G32B32A32_SFLOAT doesn't support linear filtering in blits."
+        );
+        assert!(check(&offscreen, blit, &swapchain, &[scale], FilterType::Nearest).is_ok());
+        assert!(check(
+            &offscreen,
+            vk::FormatFeatureFlags::BLIT_DST,
+            &swapchain,
+            &[scale],
+            FilterType::Nearest
+        )
+        .is_err());
+
+        let uint = image(vk::Format::R32_UINT, 1280, 720, 1);
+        assert_eq!(
+            check(&offscreen, filterable, &uint, &[scale], FilterType::Nearest)
+                .unwrap_err()
+                .to_string(),
+            "cmd_blit_image can't convert R32G32B32A32_SFLOAT into R32_UINT."
+        );
+        let depth = image(vk::Format::D32_SFLOAT, 1280, 720, 1);
+        let region = ImageBlitRegion::new([1280, 720], [1280, 720]);
+        assert!(check(&depth, blit, &depth, &[region], FilterType::Nearest).is_ok());
+        // Even when the format reports linear filtering for sampling
+        assert_eq!(
+            check(&depth, filterable, &depth, &[region], FilterType::Linear)
+                .unwrap_err()
+                .to_string(),
+            "Depth and stencil blits of D32_SFLOAT need FilterType::Nearest."
+        );
+        let stencil = image(vk::Format::S8_UINT, 1280, 720, 1);
+        assert!(check(&stencil, filterable, &stencil, &[region], FilterType::Linear).is_err());
+        let depth_stencil = image(vk::Format::D24_UNORM_S8_UINT, 1280, 720, 1);
+        assert!(check(&depth, blit, &depth_stencil, &[region], FilterType::Nearest).is_err());
+
+        // Mip chain of a 256x128 texture, level 8 is 1x1
+        let texture = image(vk::Format::R8G8B8A8_UNORM, 256, 128, 9);
+        for level in 1..9 {
+            let region = ImageBlitRegion::mip_level(256, 128, level);
+            assert!(check(&texture, filterable, &texture, &[region], FilterType::Linear).is_ok());
+        }
+        assert_eq!(ImageBlitRegion::mip_level(256, 128, 8).dst_offsets[1], [1, 1, 1]);
+        let past_the_chain = ImageBlitRegion::mip_level(256, 128, 9);
+        assert!(
+            check(&texture, filterable, &texture, &[past_the_chain], FilterType::Linear).is_err()
+        );
+        let mut too_large = ImageBlitRegion::mip_level(256, 128, 1);
+        too_large.dst_offsets[1] = [256, 128, 1];
+        assert_eq!(
+            check(&texture, filterable, &texture, &[too_large], FilterType::Linear)
+                .unwrap_err()
+                .to_string(),
+            "Blit region 0 spans [0, 0, 0] to [256, 128, 1], outside the 128x64x1 destination mip level 1."
+        );
+
+        // Mirrored blits swap the corners
+        let mut mirrored = ImageBlitRegion::new([1280, 720], [1280, 720]);
+        mirrored.dst_offsets = [[1280, 0, 0], [0, 720, 1]];
+        assert!(check(&swapchain, blit, &swapchain, &[mirrored], FilterType::Nearest).is_ok());
+
+        let msaa = VulkanImageDesc { samples: vk::SampleCountFlags::TYPE_4, ..swapchain };
+        assert!(check(&msaa, blit, &swapchain, &[region], FilterType::Nearest).is_err());
+    }
+
+    #[test]
+    fn indirect_dispatch_offsets() {
+        assert!(check_dispatch_indirect(0, 12).is_ok());
+        // Second of two packed commands
+        assert!(check_dispatch_indirect(12, 24).is_ok());
+        assert_eq!(
+            check_dispatch_indirect(6, 64).unwrap_err().to_string(),
+            "cmd_dispatch_indirect offset 6 is not a multiple of 4."
+        );
+        assert!(check_dispatch_indirect(16, 24).is_err());
+        assert!(check_dispatch_indirect(u64::MAX - 3, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn image_barriers_keep_their_access_masks() {
+        let barrier = ImageMemoryBarrier::whole_image(
+            Handle::default(),
+            crate::flag::ImageAspectFlags::COLOR,
+            ImageLayout::ColorAttachmentOptimal,
+            ImageLayout::ShaderReadOnlyOptimal,
+            crate::flag::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            crate::flag::AccessFlags::SHADER_READ,
+        );
+        let raw = image_barrier(&barrier, vk::Image::null(), vk::ImageAspectFlags::COLOR);
+        assert_eq!(raw.src_access_mask, vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        assert_eq!(raw.dst_access_mask, vk::AccessFlags::SHADER_READ);
+        assert_eq!(raw.new_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(raw.src_queue_family_index, vk::QUEUE_FAMILY_IGNORED);
+        assert_eq!(raw.subresource_range.level_count, vk::REMAINING_MIP_LEVELS);
+    }
+
+    #[test]
+    fn inline_buffer_updates_and_fills() {
+        assert!(check_update_buffer(0, 64, 64).is_ok());
+        assert!(check_update_buffer(16, 65536, 65552).is_ok());
+        assert_eq!(
+            check_update_buffer(0, 65540, 1 << 20).unwrap_err().to_string(),
+            "cmd_update_buffer writes 1 to 65536 bytes, got 65540."
+        );
+        assert_eq!(
+            check_update_buffer(4, 6, 64).unwrap_err().to_string(),
+            "cmd_update_buffer needs a 4 byte aligned offset and size, got 6 bytes at 4."
+        );
+        assert!(check_update_buffer(2, 4, 64).is_err());
+        assert!(check_update_buffer(0, 0, 64).is_err());
+        assert!(check_update_buffer(32, 64, 64).is_err());
+
+        assert!(check_fill_buffer(0, WHOLE_SIZE, 64).is_ok());
+        assert!(check_fill_buffer(8, 56, 64).is_ok());
+        assert!(check_fill_buffer(60, WHOLE_SIZE, 64).is_ok());
+        assert_eq!(
+            check_fill_buffer(2, WHOLE_SIZE, 64).unwrap_err().to_string(),
+            "cmd_fill_buffer offset 2 is not a multiple of 4."
+        );
+        assert!(check_fill_buffer(64, WHOLE_SIZE, 64).is_err());
+        assert!(check_fill_buffer(0, 6, 64).is_err());
+        assert!(check_fill_buffer(8, 64, 64).is_err());
+    }
+
+    #[test]
+    fn buffer_barriers_stay_inside_the_buffer() {
+        assert!(check_buffer_barrier(0, WHOLE_SIZE, 64).is_ok());
+        assert!(check_buffer_barrier(16, 48, 64).is_ok());
+        assert!(check_buffer_barrier(48, WHOLE_SIZE, 64).is_ok());
+        assert_eq!(
+            check_buffer_barrier(64, WHOLE_SIZE, 64).unwrap_err().to_string(),
+            "Buffer barrier offset 64 is past the end of a 64 byte buffer."
+        );
+        assert_eq!(
+            check_buffer_barrier(16, 64, 64).unwrap_err().to_string(),
+            "Buffer barrier of 64 bytes at offset 16 doesn't fit a 64 byte buffer."
+        );
+        assert!(check_buffer_barrier(0, 0, 64).is_err());
+    }
+
+    #[test]
+    fn dispatch_within_device_limits() {
+        let max_groups = [65535, 65535, 65535];
+        assert!(check_dispatch([64, 64, 1], max_groups).is_ok());
+        assert!(check_dispatch([0, 0, 0], max_groups).is_ok());
+        assert_eq!(
+            check_dispatch([65536, 1, 1], max_groups).unwrap_err().to_string(),
+            "cmd_dispatch of [65536, 1, 1] work groups exceeds the device limit of [65535, 65535, 65535]."
+        );
+    }
+
+    #[test]
+    fn image_copies_match_formats_and_fit() {
+        let image = |format, width, height| VulkanImageDesc {
+            format,
+            extent: vk::Extent3D { width, height, depth: 1 },
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
+            ..Default::default()
+        };
+        let rgba = image(vk::Format::R8G8B8A8_UNORM, 1280, 720);
+        let region = ImageCopyRegion::new(1280, 720);
+        assert!(check_copy_image(&rgba, &rgba, &[region]).is_ok());
+        // Same texel size, copied bit for bit
+        let float = image(vk::Format::R32_SFLOAT, 1280, 720);
+        assert!(check_copy_image(&rgba, &float, &[region]).is_ok());
+        assert_eq!(
+            check_copy_image(&rgba, &image(vk::Format::R32G32_SFLOAT, 1280, 720), &[region])
+                .unwrap_err()
+                .to_string(),
+            "cmd_copy_image can't copy R8G8B8A8_UNORM texels into R32G32_SFLOAT, see cmd_blit_image."
+        );
+        let depth = image(vk::Format::D32_SFLOAT, 1280, 720);
+        assert!(check_copy_image(&depth, &depth, &[region]).is_ok());
+        assert!(check_copy_image(&depth, &float, &[region]).is_err());
+        let msaa = VulkanImageDesc { samples: vk::SampleCountFlags::TYPE_4, ..rgba };
+        assert!(check_copy_image(&msaa, &rgba, &[region]).is_err());
+
+        let small = image(vk::Format::R8G8B8A8_UNORM, 640, 360);
+        assert_eq!(
+            check_copy_image(&rgba, &small, &[region]).unwrap_err().to_string(),
+            "Copy region 0 destination is 1280x720x1 at [0, 0, 0], outside the 640x360x1 mip level 0."
+        );
+        let corner =
+            ImageCopyRegion { src_offset: [640, 360, 0], ..ImageCopyRegion::new(640, 360) };
+        assert!(check_copy_image(&rgba, &small, &[corner]).is_ok());
+        let layers = ImageCopyRegion {
+            dst_subresource: ImageSubresourceLayers {
+                layer_count: 2,
+                ..ImageSubresourceLayers::color(0)
+            },
+            ..ImageCopyRegion::new(640, 360)
+        };
+        assert!(check_copy_image(&rgba, &small, &[layers]).is_err());
+    }
+
+    #[test]
+    fn readback_fits_in_the_buffer() {
+        let rgba = VulkanImageDesc {
+            format: vk::Format::R8G8B8A8_UNORM,
+            extent: vk::Extent3D { width: 1280, height: 720, depth: 1 },
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
+            ..Default::default()
+        };
+        let whole = BufferImageCopyRegion::color(1280, 720);
+        let size = 1280 * 720 * 4;
+        assert!(check_copy_image_to_buffer(&rgba, size, &[whole]).is_ok());
+        assert_eq!(
+            check_copy_image_to_buffer(&rgba, size - 1, &[whole]).unwrap_err().to_string(),
+            "Copy region 0 writes bytes 0..3686400, past the end of the 3686399 byte buffer."
+        );
+        // Rows padded to 256 bytes, the last one isn't
+        let padded = BufferImageCopyRegion { buffer_row_length: 1344, ..whole };
+        assert!(check_copy_image_to_buffer(&rgba, 1344 * 719 * 4 + 1280 * 4, &[padded]).is_ok());
+        assert!(check_copy_image_to_buffer(&rgba, size, &[padded]).is_err());
+        let unaligned = BufferImageCopyRegion { buffer_offset: 2, ..whole };
+        assert!(check_copy_image_to_buffer(&rgba, size * 2, &[unaligned]).is_err());
+        let outside = BufferImageCopyRegion { image_offset: [1, 0, 0], ..whole };
+        assert!(check_copy_image_to_buffer(&rgba, size * 2, &[outside]).is_err());
+
+        let msaa = VulkanImageDesc { samples: vk::SampleCountFlags::TYPE_4, ..rgba };
+        assert!(check_copy_image_to_buffer(&msaa, size, &[whole]).is_err());
+        // Depth texels have no known size, only the image bounds are checked
+        let depth = VulkanImageDesc { format: vk::Format::D24_UNORM_S8_UINT, ..rgba };
+        let depth_region =
+            BufferImageCopyRegion { aspect_mask: crate::flag::ImageAspectFlags::DEPTH, ..whole };
+        assert!(check_copy_image_to_buffer(&depth, 0, &[depth_region]).is_ok());
+    }
+
+    #[test]
+    fn clears_outside_render_passes() {
+        let image = |format, mip_levels| VulkanImageDesc {
+            format,
+            extent: vk::Extent3D { width: 256, height: 256, depth: 1 },
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels,
+            array_layers: 1,
+            ..Default::default()
+        };
+        let storage = image(vk::Format::R32G32B32A32_SFLOAT, 9);
+        let color = ImageSubresourceRange::whole(crate::flag::ImageAspectFlags::COLOR);
+        assert!(check_clear_image(&storage, ImageLayout::General, &[color], false).is_ok());
+        assert!(
+            check_clear_image(&storage, ImageLayout::TransferDstOptimal, &[color], false).is_ok()
+        );
+        assert_eq!(
+            check_clear_image(&storage, ImageLayout::ShaderReadOnlyOptimal, &[color], false)
+                .unwrap_err()
+                .to_string(),
+            "cmd_clear_color_image needs the image in General or TransferDstOptimal, not ShaderReadOnlyOptimal."
+        );
+        let last_level = ImageSubresourceRange { base_mip_level: 8, level_count: 1, ..color };
+        assert!(check_clear_image(&storage, ImageLayout::General, &[last_level], false).is_ok());
+        let past_chain = ImageSubresourceRange { level_count: 2, ..last_level };
+        assert_eq!(
+            check_clear_image(&storage, ImageLayout::General, &[color, past_chain], false)
+                .unwrap_err()
+                .to_string(),
+            "Clear range 1 is outside the 9 mip levels and 1 layers of the image."
+        );
+
+        let depth = image(vk::Format::D32_SFLOAT, 1);
+        let depth_range = ImageSubresourceRange::whole(crate::flag::ImageAspectFlags::DEPTH);
+        assert!(check_clear_image(&depth, ImageLayout::General, &[depth_range], true).is_ok());
+        assert_eq!(
+            check_clear_image(&depth, ImageLayout::General, &[color], false)
+                .unwrap_err()
+                .to_string(),
+            "cmd_clear_color_image can't clear a D32_SFLOAT image."
+        );
+        assert!(check_clear_image(&storage, ImageLayout::General, &[depth_range], true).is_err());
+        // No stencil aspect to clear
+        let stencil_range = ImageSubresourceRange::whole(
+            crate::flag::ImageAspectFlags::DEPTH | crate::flag::ImageAspectFlags::STENCIL,
+        );
+        assert!(check_clear_image(&depth, ImageLayout::General, &[stencil_range], true).is_err());
+        let depth_stencil = image(vk::Format::D24_UNORM_S8_UINT, 1);
+        assert!(
+            check_clear_image(&depth_stencil, ImageLayout::General, &[stencil_range], true).is_ok()
+        );
+
+        assert!(check_clear_color(vk::Format::R32_UINT, ClearColor::uint([0; 4])).is_ok());
+        assert!(check_clear_color(vk::Format::R32_UINT, ClearColor::new([0.0; 4])).is_err());
+    }
+
+    #[test]
+    fn secondaries_continue_the_render_pass_they_run_in() {
+        let secondary = |inherited_subpass| {
+            let cb =
+                VulkanCommandBuffer { level: CommandBufferLevel::Secondary, ..Default::default() };
+            cb.inherited_subpass.set(inherited_subpass);
+            cb.state.set(CommandBufferState::Executable);
+            cb
+        };
+        let continuing = secondary(Some(0));
+        let standalone = secondary(None);
+        let primary = VulkanCommandBuffer::default();
+        primary.state.set(CommandBufferState::Recording);
+        assert!(primary.validate_execute(&[&standalone]).is_ok());
+        assert_eq!(
+            primary.validate_execute(&[&continuing]).unwrap_err().to_string(),
+            "Secondary command buffer 0 continues a render pass but is executed outside one."
+        );
+
+        primary.state.set(CommandBufferState::InRenderPass);
+        primary.subpass.set(Some((SubpassContents::SecondaryCommandBuffers, 0)));
+        assert!(primary.validate_execute(&[&continuing, &continuing]).is_ok());
+        assert_eq!(
+            primary.validate_execute(&[&continuing, &standalone]).unwrap_err().to_string(),
+            "Secondary command buffer 1 is executed inside a render pass but wasn't begun with render_pass_continue."
+        );
+        assert!(primary.validate_execute(&[&secondary(Some(1))]).is_err());
+        primary.subpass.set(Some((SubpassContents::Inline, 0)));
+        assert!(primary.validate_execute(&[&continuing]).is_err());
+        primary.subpass.set(Some((SubpassContents::SecondaryCommandBuffers, 0)));
+
+        // Only ended secondaries run, and only from primaries
+        let recording = secondary(Some(0));
+        recording.state.set(CommandBufferState::InRenderPass);
+        assert!(primary.validate_execute(&[&recording]).is_err());
+        assert!(primary.validate_execute(&[&primary]).is_err());
+        assert!(continuing.validate_execute(&[]).is_err());
+        assert!(continuing.validate_submit().is_err());
+
+        // A continuing secondary records draws and ends inside the render pass
+        let begin = CommandBufferBeginDesc {
+            render_pass_continue: Some(CommandBufferInheritance {
+                render_pass: Handle::default(),
+                subpass: 0,
+                framebuffer: None,
+            }),
+            ..Default::default()
+        };
+        assert!(primary.validate_begin(&begin).is_err());
+        assert!(continuing.validate_begin(&begin).is_ok());
+        recording.inherited_subpass.set(Some(0));
+        assert!(recording.validate_scope(RenderPassScope::Inside, "cmd_draw").is_ok());
+        assert!(recording.validate_end().is_ok());
+        assert!(recording.validate_primary("cmd_end_render_pass").is_err());
     }
 }
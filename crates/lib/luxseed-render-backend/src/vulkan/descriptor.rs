@@ -1,13 +1,18 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use ash::vk;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Handled, Pool};
 use smallvec::SmallVec;
 
-use crate::{define::*, enums::DescriptorType};
+use crate::{
+    define::*,
+    enums::DescriptorType,
+    flag::{DescriptorBindingFlags, DeviceFeatures},
+};
 
 use super::{
-    buffer::VulkanBuffer,
+    acceleration_structure::VulkanAccelerationStructure,
+    buffer::{VulkanBuffer, VulkanBufferView},
     device::VulkanDevice,
     image::{VulkanImageView, VulkanSampler},
 };
@@ -42,6 +47,7 @@ impl VulkanDescriptorSetLayout {
                 type_: input_binding.type_,
                 count: input_binding.count,
                 stage_flags: input_binding.stage_flags,
+                flags: input_binding.flags,
             };
             self.binding_infos.push(binding);
             self.index_to_binding[binding.index as usize] = idx as u8;
@@ -55,13 +61,26 @@ impl VulkanDescriptorSetLayout {
             self.vk_bindings.push(vk_binding);
         }
 
-        let raw = unsafe {
-            device.raw().create_descriptor_set_layout(
-                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&self.vk_bindings).build(),
-                None,
-            )?
-        };
-        self.raw = raw;
+        let binding_flags = self
+            .binding_infos
+            .iter()
+            .map(|b| b.flags.into())
+            .collect::<SmallVec<[vk::DescriptorBindingFlags; MAX_DESCRIPTORS_PER_SET]>>();
+        let mut flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(&binding_flags);
+        let mut create_info =
+            vk::DescriptorSetLayoutCreateInfo::builder().bindings(&self.vk_bindings);
+        if self.binding_infos.iter().any(|b| !b.flags.is_empty()) {
+            if !device.enabled_features().features.contains(DeviceFeatures::DESCRIPTOR_INDEXING) {
+                bail!("Descriptor binding flags need DeviceFeatures::DESCRIPTOR_INDEXING.");
+            }
+            create_info = create_info.push_next(&mut flags_info);
+        }
+        if self.update_after_bind() {
+            create_info =
+                create_info.flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL);
+        }
+        self.raw = unsafe { device.raw().create_descriptor_set_layout(&create_info, None)? };
         Ok(())
     }
 
@@ -75,10 +94,43 @@ impl VulkanDescriptorSetLayout {
         self.vk_bindings.clear();
     }
 
+    /// Whether sets of this layout have to come from an update after bind pool.
+    pub fn update_after_bind(&self) -> bool {
+        self.binding_infos
+            .iter()
+            .any(|b| b.flags.contains(DescriptorBindingFlags::UPDATE_AFTER_BIND))
+    }
+
+    /// Dynamic buffer descriptors in the layout, each takes a dynamic offset when bound.
+    pub fn dynamic_descriptor_count(&self) -> u32 {
+        self.vk_bindings
+            .iter()
+            .filter(|b| {
+                matches!(
+                    b.descriptor_type,
+                    vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+                        | vk::DescriptorType::STORAGE_BUFFER_DYNAMIC
+                )
+            })
+            .map(|b| b.descriptor_count)
+            .sum()
+    }
+
+    /// Layouts are compatible when their bindings are defined identically.
+    pub fn is_compatible_with(&self, other: &VulkanDescriptorSetLayout) -> bool {
+        self.vk_bindings.len() == other.vk_bindings.len()
+            && self.vk_bindings.iter().zip(other.vk_bindings.iter()).all(|(a, b)| {
+                a.binding == b.binding
+                    && a.descriptor_type == b.descriptor_type
+                    && a.descriptor_count == b.descriptor_count
+                    && a.stage_flags == b.stage_flags
+            })
+    }
+
     #[inline]
     pub fn get_binding_info(&self, binding: u16) -> Option<&DescriptorBindingInfo> {
-        let binding_index = self.index_to_binding[binding as usize];
-        self.binding_infos.get(binding_index as usize)
+        let binding_index = *self.index_to_binding.get(binding as usize)?;
+        self.binding_infos.get(binding_index as usize).filter(|info| info.index == binding)
     }
 }
 
@@ -86,6 +138,7 @@ impl VulkanDescriptorSetLayout {
 pub struct VulkanDescriptorPool {
     pub handle: Option<Handle<DescriptorPool>>,
     pub raw: vk::DescriptorPool,
+    pub update_after_bind: bool,
 }
 impl_handle!(VulkanDescriptorPool, DescriptorPool, handle);
 
@@ -98,9 +151,18 @@ impl VulkanDescriptorPool {
                 .descriptor_count(p.descriptor_count);
             pool_sizes.push(pool_size.build());
         }
+        // Sets are freed one by one in `destroy_descriptor_sets`
+        let mut flags = vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET;
+        if desc.update_after_bind {
+            if !device.enabled_features().features.contains(DeviceFeatures::DESCRIPTOR_INDEXING) {
+                bail!("Update after bind pools need DeviceFeatures::DESCRIPTOR_INDEXING.");
+            }
+            flags |= vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND;
+        }
         let raw = unsafe {
             device.raw().create_descriptor_pool(
                 &vk::DescriptorPoolCreateInfo::builder()
+                    .flags(flags)
                     .pool_sizes(&pool_sizes)
                     .max_sets(desc.max_sets)
                     .build(),
@@ -108,6 +170,7 @@ impl VulkanDescriptorPool {
             )?
         };
         self.raw = raw;
+        self.update_after_bind = desc.update_after_bind;
         Ok(())
     }
 
@@ -129,6 +192,15 @@ pub struct VulkanDescriptorSet {
 }
 impl_handle!(VulkanDescriptorSet, DescriptorSet, handle);
 
+/// The pools the resources bound to a descriptor set are looked up in.
+pub struct DescriptorResourcePools<'a> {
+    pub buffer: &'a Pool<VulkanBuffer>,
+    pub buffer_view: &'a Pool<VulkanBufferView>,
+    pub image_view: &'a Pool<VulkanImageView>,
+    pub sampler: &'a Pool<VulkanSampler>,
+    pub acceleration_structure: &'a Pool<VulkanAccelerationStructure>,
+}
+
 impl VulkanDescriptorSet {
     pub fn init(
         &mut self,
@@ -136,12 +208,13 @@ impl VulkanDescriptorSet {
         desc: &DescriptorSetCreateDesc,
         p_pool: &Pool<VulkanDescriptorPool>,
         p_layout: &Pool<VulkanDescriptorSetLayout>,
-        p_buffer: &Pool<VulkanBuffer>,
-        p_image_view: &Pool<VulkanImageView>,
-        p_sampler: &Pool<VulkanSampler>,
+        pools: &DescriptorResourcePools,
     ) -> Result<()> {
         let pool = p_pool.get(desc.pool).context("Descriptor Pool not found")?;
         let layout = p_layout.get(desc.layout).context("Descriptor Set Layout not found")?;
+        if layout.update_after_bind() && !pool.update_after_bind {
+            bail!("Sets with UPDATE_AFTER_BIND bindings need an update_after_bind pool.");
+        }
         let raw = unsafe {
             device.raw().allocate_descriptor_sets(
                 &vk::DescriptorSetAllocateInfo::builder()
@@ -151,24 +224,7 @@ impl VulkanDescriptorSet {
             )?
         }[0];
 
-        // Update descriptor sets
-        let mut writes = SmallVec::<[vk::WriteDescriptorSet; MAX_DESCRIPTORS_PER_SET]>::new();
-        let mut buffers = SmallVec::<[vk::DescriptorBufferInfo; MAX_DESCRIPTORS_PER_SET]>::new();
-        let mut images = SmallVec::<[vk::DescriptorImageInfo; MAX_DESCRIPTORS_PER_SET]>::new();
-        device.fill_write_descriptor_sets(
-            layout,
-            raw,
-            &desc.bindings,
-            p_buffer,
-            p_image_view,
-            p_sampler,
-            &mut writes,
-            &mut buffers,
-            &mut images,
-        )?;
-        unsafe {
-            device.raw().update_descriptor_sets(&writes, &[]);
-        }
+        device.write_descriptor_set(layout, raw, &desc.bindings, pools)?;
 
         self.raw = raw;
         self.pool = pool.get_handle();
@@ -181,6 +237,22 @@ impl VulkanDescriptorSet {
         Ok(())
     }
 
+    /// Rewrites `bindings` of the set, every other binding keeps what it was bound to.
+    pub fn update(
+        &mut self,
+        device: &VulkanDevice,
+        bindings: &[DescriptorBindingData],
+        p_layout: &Pool<VulkanDescriptorSetLayout>,
+        pools: &DescriptorResourcePools,
+    ) -> Result<()> {
+        let layout = p_layout
+            .get(self.layout.context("Descriptor Set has no layout")?)
+            .context("Descriptor Set Layout not found")?;
+        device.write_descriptor_set(layout, self.raw, bindings, pools)?;
+        merge_binding_datas(&mut self.binding_datas, bindings);
+        Ok(())
+    }
+
     pub fn destroy(
         &mut self,
         device: &ash::Device,
@@ -199,68 +271,466 @@ impl VulkanDescriptorSet {
 }
 
 impl VulkanDevice {
-    pub fn fill_write_descriptor_sets(
+    /// Writes `binding_datas` into the descriptor set in a single update.
+    pub fn write_descriptor_set(
         &self,
         layout: &VulkanDescriptorSetLayout,
         descriptor_set: vk::DescriptorSet,
-        binding_datas: &SmallVec<[DescriptorBindingData; MAX_DESCRIPTORS_PER_SET]>,
-        p_buffer: &Pool<VulkanBuffer>,
-        p_image_view: &Pool<VulkanImageView>,
-        p_sampler: &Pool<VulkanSampler>,
-        write_sets: &mut SmallVec<[vk::WriteDescriptorSet; MAX_DESCRIPTORS_PER_SET]>,
-        buffer_infos: &mut SmallVec<[vk::DescriptorBufferInfo; MAX_DESCRIPTORS_PER_SET]>,
-        image_infos: &mut SmallVec<[vk::DescriptorImageInfo; MAX_DESCRIPTORS_PER_SET]>,
+        binding_datas: &[DescriptorBindingData],
+        pools: &DescriptorResourcePools,
     ) -> Result<()> {
-        for binding_data in binding_datas.iter() {
-            let binding_info =
-                layout.get_binding_info(binding_data.binding).context("Can't find binding")?;
-
-            let mut dst_set = vk::WriteDescriptorSet::builder()
-                .dst_set(descriptor_set)
-                .dst_binding(binding_data.binding as u32)
-                .dst_array_element(0)
-                .descriptor_type(binding_info.type_.into());
-            match binding_info.type_ {
-                DescriptorType::UniformBuffer => {
-                    let buffer_start_index = buffer_infos.len();
-
-                    let buffer =
-                        p_buffer.get(binding_data.buffer.unwrap()).context("Buffer not found")?;
-                    let buffer_info = vk::DescriptorBufferInfo::builder()
-                        .buffer(buffer.raw)
-                        .offset(0)
-                        .range(buffer.size)
-                        .build();
-                    buffer_infos.push(buffer_info);
-
-                    dst_set = dst_set.buffer_info(&buffer_infos[buffer_start_index..]);
-                }
-                DescriptorType::CombinedImageSampler => {
-                    let image_start_index = image_infos.len();
-
-                    let image_view = p_image_view
-                        .get(binding_data.image_view.unwrap())
-                        .context("Image View not found")?;
-                    let sampler = p_sampler
-                        .get(binding_data.sampler.unwrap())
-                        .context("Sampler not found")?;
-                    let image_info = vk::DescriptorImageInfo::builder()
-                        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                        .image_view(image_view.raw)
-                        .sampler(sampler.raw)
-                        .build();
-                    image_infos.push(image_info);
-
-                    dst_set = dst_set.image_info(&image_infos[image_start_index..]);
-                }
-                _ => {
-                    todo!()
+        check_bindings_in_layout(layout, binding_datas)?;
+        let mut writes = DescriptorWrites::default();
+        fill_write_descriptor_sets(layout, descriptor_set, binding_datas, pools, &mut writes)?;
+        unsafe {
+            self.raw().update_descriptor_sets(&writes.write_sets, &[]);
+        }
+        Ok(())
+    }
+}
+
+/// The writes of a descriptor set update along with the arrays they point into, so it must
+/// not be moved between filling it and passing `write_sets` to the driver.
+#[derive(Default)]
+struct DescriptorWrites {
+    write_sets: SmallVec<[vk::WriteDescriptorSet; MAX_DESCRIPTORS_PER_SET]>,
+    buffer_infos: SmallVec<[vk::DescriptorBufferInfo; MAX_DESCRIPTORS_PER_SET]>,
+    image_infos: SmallVec<[vk::DescriptorImageInfo; MAX_DESCRIPTORS_PER_SET]>,
+    texel_buffer_views: SmallVec<[vk::BufferView; MAX_DESCRIPTORS_PER_SET]>,
+    acceleration_structures: SmallVec<[vk::AccelerationStructureKHR; MAX_DESCRIPTORS_PER_SET]>,
+    acceleration_structure_writes:
+        SmallVec<[vk::WriteDescriptorSetAccelerationStructureKHR; MAX_DESCRIPTORS_PER_SET]>,
+}
+
+/// Builds the writes of `binding_datas`, consecutive elements of an array binding share one
+/// write.
+fn fill_write_descriptor_sets(
+    layout: &VulkanDescriptorSetLayout,
+    descriptor_set: vk::DescriptorSet,
+    binding_datas: &[DescriptorBindingData],
+    pools: &DescriptorResourcePools,
+    writes: &mut DescriptorWrites,
+) -> Result<()> {
+    let DescriptorWrites {
+        write_sets,
+        buffer_infos,
+        image_infos,
+        texel_buffer_views,
+        acceleration_structures,
+        acceleration_structure_writes,
+    } = writes;
+    // Every binding gets at most one entry, so the arrays never move while the writes
+    // point into them
+    buffer_infos.reserve(binding_datas.len());
+    image_infos.reserve(binding_datas.len());
+    texel_buffer_views.reserve(binding_datas.len());
+    acceleration_structures.reserve(binding_datas.len());
+    acceleration_structure_writes.reserve(binding_datas.len());
+    for binding_data in binding_datas.iter() {
+        let binding_info =
+            layout.get_binding_info(binding_data.binding).context("Can't find binding")?;
+
+        let mut dst_set = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(binding_data.binding as u32)
+            .dst_array_element(binding_data.array_element)
+            .descriptor_type(binding_info.type_.into());
+        match binding_info.type_ {
+            DescriptorType::UniformBuffer
+            | DescriptorType::StorageBuffer
+            | DescriptorType::UniformBufferDynamic
+            | DescriptorType::StorageBufferDynamic => {
+                let buffer_start_index = buffer_infos.len();
+
+                let buffer =
+                    pools.buffer.get(binding_data.buffer.unwrap()).context("Buffer not found")?;
+                let range = match binding_data.buffer_range {
+                    WHOLE_SIZE => buffer.size,
+                    range if range <= buffer.size => range,
+                    range => bail!(
+                        "Binding {} covers {} bytes of a {} byte buffer.",
+                        binding_data.binding,
+                        range,
+                        buffer.size
+                    ),
+                };
+                let buffer_info = vk::DescriptorBufferInfo::builder()
+                    .buffer(buffer.raw)
+                    .offset(0)
+                    .range(range)
+                    .build();
+                buffer_infos.push(buffer_info);
+
+                dst_set = dst_set.buffer_info(&buffer_infos[buffer_start_index..]);
+            }
+            DescriptorType::UniformTexelBuffer | DescriptorType::StorageTexelBuffer => {
+                let buffer_view = pools
+                    .buffer_view
+                    .get(binding_data.buffer_view.context("Binding has no buffer view")?)
+                    .context("Buffer View not found")?;
+                texel_buffer_views.push(buffer_view.raw);
+
+                dst_set =
+                    dst_set.texel_buffer_view(&texel_buffer_views[texel_buffer_views.len() - 1..]);
+            }
+            DescriptorType::CombinedImageSampler => {
+                let image_start_index = image_infos.len();
+
+                let image_view = pools
+                    .image_view
+                    .get(binding_data.image_view.unwrap())
+                    .context("Image View not found")?;
+                let sampler = pools
+                    .sampler
+                    .get(binding_data.sampler.unwrap())
+                    .context("Sampler not found")?;
+                if cfg!(debug_assertions) {
+                    validate_sampler_view_pair(sampler.compare_enable, image_view.desc.aspect_mask)
+                        .with_context(|| format!("Invalid binding {}", binding_data.binding))?;
                 }
+                let image_info = vk::DescriptorImageInfo::builder()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(image_view.raw)
+                    .sampler(sampler.raw)
+                    .build();
+                image_infos.push(image_info);
+
+                dst_set = dst_set.image_info(&image_infos[image_start_index..]);
+            }
+            DescriptorType::InputAttachment
+            | DescriptorType::SampledImage
+            | DescriptorType::StorageImage => {
+                let image_start_index = image_infos.len();
+
+                let image_view = pools
+                    .image_view
+                    .get(binding_data.image_view.unwrap())
+                    .context("Image View not found")?;
+                let image_info = vk::DescriptorImageInfo::builder()
+                    .image_layout(unsampled_image_layout(binding_info.type_))
+                    .image_view(image_view.raw)
+                    .build();
+                image_infos.push(image_info);
+
+                dst_set = dst_set.image_info(&image_infos[image_start_index..]);
+            }
+            DescriptorType::AccelerationStructure => {
+                let acceleration_structure = pools
+                    .acceleration_structure
+                    .get(binding_data.acceleration_structure.unwrap())
+                    .context("Acceleration structure not found")?;
+                acceleration_structures.push(acceleration_structure.raw);
+                acceleration_structure_writes.push(
+                    vk::WriteDescriptorSetAccelerationStructureKHR::builder()
+                        .acceleration_structures(
+                            &acceleration_structures[acceleration_structures.len() - 1..],
+                        )
+                        .build(),
+                );
+
+                let last = acceleration_structure_writes.len() - 1;
+                let mut write = dst_set.push_next(&mut acceleration_structure_writes[last]).build();
+                // Not derived from an info array like the other types
+                write.descriptor_count = 1;
+                write_sets.push(write);
+                continue;
             }
+            _ => {
+                todo!()
+            }
+        }
 
-            write_sets.push(dst_set.build());
+        let write = dst_set.build();
+        match write_sets.last_mut() {
+            Some(last) if continues_write(last, &write) => last.descriptor_count += 1,
+            _ => write_sets.push(write),
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Whether `write` is the next element of the array `last` writes, with its info right after
+/// the infos of `last`.
+fn continues_write(last: &vk::WriteDescriptorSet, write: &vk::WriteDescriptorSet) -> bool {
+    let count = last.descriptor_count as usize;
+    last.dst_set == write.dst_set
+        && last.dst_binding == write.dst_binding
+        && last.descriptor_type == write.descriptor_type
+        && last.dst_array_element + last.descriptor_count == write.dst_array_element
+        && last.p_next.is_null()
+        && (last.p_buffer_info.is_null()
+            || last.p_buffer_info.wrapping_add(count) == write.p_buffer_info)
+        && (last.p_image_info.is_null()
+            || last.p_image_info.wrapping_add(count) == write.p_image_info)
+        && (last.p_texel_buffer_view.is_null()
+            || last.p_texel_buffer_view.wrapping_add(count) == write.p_texel_buffer_view)
+}
+
+/// Binding sets take exactly one dynamic offset for each dynamic buffer descriptor in them.
+pub fn check_dynamic_offsets(
+    layouts: &[&VulkanDescriptorSetLayout],
+    offset_count: usize,
+) -> Result<()> {
+    let expected: u32 = layouts.iter().map(|l| l.dynamic_descriptor_count()).sum();
+    if expected as usize != offset_count {
+        bail!(
+            "The bound sets have {} dynamic descriptors but {} dynamic offsets were given.",
+            expected,
+            offset_count
+        );
+    }
+    Ok(())
+}
+
+/// Every binding being written has to be declared by the layout of the set, and array elements
+/// have to be inside the array.
+fn check_bindings_in_layout(
+    layout: &VulkanDescriptorSetLayout,
+    bindings: &[DescriptorBindingData],
+) -> Result<()> {
+    for binding in bindings {
+        let Some(info) = layout.get_binding_info(binding.binding) else {
+            bail!("Binding {} is not in the layout of the descriptor set.", binding.binding);
+        };
+        if binding.array_element >= info.count as u32 {
+            bail!(
+                "Element {} is out of range for binding {} of {} descriptors.",
+                binding.array_element,
+                binding.binding,
+                info.count
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the stored data of every updated binding element, adding those that weren't bound
+/// yet.
+fn merge_binding_datas(
+    stored: &mut SmallVec<[DescriptorBindingData; MAX_DESCRIPTORS_PER_SET]>,
+    updates: &[DescriptorBindingData],
+) {
+    for update in updates {
+        match stored
+            .iter_mut()
+            .find(|b| (b.binding, b.array_element) == (update.binding, update.array_element))
+        {
+            Some(b) => *b = *update,
+            None => stored.push(*update),
+        }
+    }
+}
+
+/// The layout an image bound without a sampler is expected in, storage images are written
+/// by shaders and stay in `General`.
+fn unsampled_image_layout(type_: DescriptorType) -> vk::ImageLayout {
+    match type_ {
+        DescriptorType::StorageImage => vk::ImageLayout::GENERAL,
+        _ => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }
+}
+
+/// A comparison sampler only produces meaningful results on a depth view.
+fn validate_sampler_view_pair(
+    compare_enable: bool,
+    aspect_mask: vk::ImageAspectFlags,
+) -> Result<()> {
+    if compare_enable && !aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+        bail!("Comparison sampler must be paired with a depth view, got aspect {:?}", aspect_mask);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        enums::Format,
+        vulkan::image::{VulkanImage, VulkanImageViewDesc},
+    };
+
+    #[test]
+    fn comparison_sampler_requires_depth_view() {
+        let depth_view = VulkanImageViewDesc::from_create_desc(
+            &ImageViewCreateDesc {
+                format: Some(Format::D32_SFLOAT),
+                ..ImageViewCreateDesc::new_depth()
+            },
+            &VulkanImage::default(),
+        );
+        assert_eq!(depth_view.format, vk::Format::D32_SFLOAT);
+        assert!(validate_sampler_view_pair(true, depth_view.aspect_mask).is_ok());
+        assert!(validate_sampler_view_pair(true, vk::ImageAspectFlags::COLOR).is_err());
+        assert!(validate_sampler_view_pair(false, vk::ImageAspectFlags::COLOR).is_ok());
+    }
+
+    #[test]
+    fn updates_stay_inside_the_layout() {
+        let mut layout = VulkanDescriptorSetLayout::default();
+        for (i, index) in [0u16, 2].into_iter().enumerate() {
+            layout.binding_infos.push(DescriptorBindingInfo {
+                index,
+                type_: DescriptorType::UniformBuffer,
+                count: 1,
+                stage_flags: crate::flag::ShaderStageFlags::VERTEX,
+                flags: DescriptorBindingFlags::empty(),
+            });
+            layout.index_to_binding.resize(index as usize + 1, 0);
+            layout.index_to_binding[index as usize] = i as u8;
+        }
+
+        let mut p_buffer = Pool::<VulkanBuffer>::with_size(4, Default::default);
+        let (first, second) = (p_buffer.malloc().0, p_buffer.malloc().0);
+        let bind = |binding, buffer| {
+            DescriptorSetCreateDesc::new(Handle::default(), Handle::default())
+                .bind_uniform_buffer(binding, buffer)
+                .bindings[0]
+        };
+
+        assert!(check_bindings_in_layout(&layout, &[bind(0, first), bind(2, first)]).is_ok());
+        // Binding 1 sits inside the index table but was never declared
+        assert_eq!(
+            check_bindings_in_layout(&layout, &[bind(1, first)]).unwrap_err().to_string(),
+            "Binding 1 is not in the layout of the descriptor set."
+        );
+        assert!(check_bindings_in_layout(&layout, &[bind(7, first)]).is_err());
+
+        let mut stored = SmallVec::new();
+        stored.push(bind(0, first));
+        merge_binding_datas(&mut stored, &[bind(0, second), bind(2, second)]);
+        assert_eq!(stored.len(), 2);
+        assert!(stored.iter().all(|b| b.buffer == Some(second)));
+    }
+
+    #[test]
+    fn array_elements_stay_inside_the_array() {
+        let mut layout = VulkanDescriptorSetLayout::default();
+        layout.binding_infos.push(DescriptorBindingInfo {
+            index: 0,
+            type_: DescriptorType::SampledImage,
+            count: 4,
+            stage_flags: crate::flag::ShaderStageFlags::FRAGMENT,
+            flags: DescriptorBindingFlags::PARTIALLY_BOUND,
+        });
+        layout.index_to_binding.push(0);
+
+        let mut p_view = Pool::<VulkanImageView>::with_size(4, Default::default);
+        let views = [p_view.malloc().0, p_view.malloc().0, p_view.malloc().0];
+        let bind = |first_index, views: &[Handle<ImageView>]| {
+            DescriptorSetCreateDesc::new(Handle::default(), Handle::default())
+                .bind_image_view_array(0, first_index, views)
+                .bindings
+        };
+
+        let table = bind(1, &views);
+        assert_eq!(table.iter().map(|b| b.array_element).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(check_bindings_in_layout(&layout, &table).is_ok());
+        assert_eq!(
+            check_bindings_in_layout(&layout, &bind(2, &views)).unwrap_err().to_string(),
+            "Element 4 is out of range for binding 0 of 4 descriptors."
+        );
+
+        // Elements of the same binding are merged one by one
+        let mut stored = SmallVec::new();
+        stored.extend(table);
+        merge_binding_datas(&mut stored, &bind(0, &views[..2]));
+        assert_eq!(stored.len(), 4);
+        assert!(!layout.update_after_bind());
+    }
+
+    #[test]
+    fn one_dynamic_offset_per_dynamic_descriptor() {
+        let layout = |types: &[(vk::DescriptorType, u32)]| {
+            let mut layout = VulkanDescriptorSetLayout::default();
+            for (i, (type_, count)) in types.iter().enumerate() {
+                layout.vk_bindings.push(
+                    vk::DescriptorSetLayoutBinding::builder()
+                        .binding(i as u32)
+                        .descriptor_type(*type_)
+                        .descriptor_count(*count)
+                        .build(),
+                );
+            }
+            layout
+        };
+        let per_draw = layout(&[
+            (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+        ]);
+        let per_frame = layout(&[
+            (vk::DescriptorType::UNIFORM_BUFFER, 1),
+            (vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, 2),
+        ]);
+        assert_eq!(per_frame.dynamic_descriptor_count(), 2);
+
+        assert!(check_dynamic_offsets(&[&per_draw, &per_frame], 3).is_ok());
+        assert!(check_dynamic_offsets(&[&per_draw], 1).is_ok());
+        assert_eq!(
+            check_dynamic_offsets(&[&per_draw], 0).unwrap_err().to_string(),
+            "The bound sets have 1 dynamic descriptors but 0 dynamic offsets were given."
+        );
+        assert!(check_dynamic_offsets(&[], 1).is_err());
+    }
+
+    #[test]
+    fn array_elements_share_one_write() {
+        let mut layout = VulkanDescriptorSetLayout::default();
+        layout.binding_infos.push(DescriptorBindingInfo {
+            index: 0,
+            type_: DescriptorType::SampledImage,
+            count: 32,
+            stage_flags: crate::flag::ShaderStageFlags::FRAGMENT,
+            flags: DescriptorBindingFlags::PARTIALLY_BOUND,
+        });
+        layout.index_to_binding.push(0);
+
+        let mut p_view = Pool::<VulkanImageView>::with_size(32, Default::default);
+        let views: Vec<_> = (0..24).map(|_| p_view.malloc().0).collect();
+        // More elements than fit inline, with a hole between element 19 and 21
+        let bindings = DescriptorSetCreateDesc::new(Handle::default(), Handle::default())
+            .bind_image_view_array(0, 0, &views[..20])
+            .bind_image_view_array(0, 21, &views[20..])
+            .bindings;
+
+        let pools = DescriptorResourcePools {
+            buffer: &Pool::with_size(1, Default::default),
+            buffer_view: &Pool::with_size(1, Default::default),
+            image_view: &p_view,
+            sampler: &Pool::with_size(1, Default::default),
+            acceleration_structure: &Pool::with_size(1, Default::default),
+        };
+        let mut writes = DescriptorWrites::default();
+        fill_write_descriptor_sets(
+            &layout,
+            vk::DescriptorSet::null(),
+            &bindings,
+            &pools,
+            &mut writes,
+        )
+        .unwrap();
+        let DescriptorWrites { write_sets, image_infos, .. } = &writes;
+
+        assert_eq!(image_infos.len(), 24);
+        assert_eq!(write_sets.len(), 2);
+        assert_eq!(
+            write_sets
+                .iter()
+                .map(|w| (w.dst_array_element, w.descriptor_count))
+                .collect::<Vec<_>>(),
+            [(0, 20), (21, 4)]
+        );
+        // Every write points at its own elements of the final info array
+        assert_eq!(write_sets[0].p_image_info, image_infos.as_ptr());
+        assert_eq!(write_sets[1].p_image_info, image_infos[20..].as_ptr());
+    }
+
+    #[test]
+    fn storage_images_are_bound_in_general_layout() {
+        assert_eq!(unsampled_image_layout(DescriptorType::StorageImage), vk::ImageLayout::GENERAL);
+        assert_eq!(
+            unsampled_image_layout(DescriptorType::InputAttachment),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
     }
 }
@@ -1,20 +1,29 @@
 use anyhow::{Context, Result};
-use ash::{extensions::khr, vk};
+use ash::{
+    extensions::{ext::DebugUtils, khr},
+    vk,
+};
 use gpu_allocator::vulkan::*;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Pool};
 use smallvec::SmallVec;
-use std::{collections::HashMap, ffi::CStr, mem::ManuallyDrop};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    mem::ManuallyDrop,
+};
 
-use crate::{define::*, enums::*};
+use crate::{define::*, enums::*, flag::*};
 
 use super::{
     command::VulkanCommandBuffer,
     framebuffer::VulkanFramebufferDesc,
     instance::VulkanInstance,
     render_pass::VulkanRenderPassOutput,
+    surface::VulkanSurface,
     swapchain::VulkanSwapchain,
     sync::{VulkanFence, VulkanSemaphore},
+    util::{fence_wait_result, has_extension},
 };
 
 #[derive(Clone)]
@@ -77,6 +86,16 @@ pub struct VulkanDevice {
     present_queue: Option<Handle<Queue>>,
     pub render_pass_cache: HashMap<VulkanRenderPassOutput, ash::vk::RenderPass>,
     pub framebuffer_cache: HashMap<VulkanFramebufferDesc, ash::vk::Framebuffer>,
+    pub pipeline_cache: vk::PipelineCache,
+    acceleration_structure_loader: Option<khr::AccelerationStructure>,
+    acceleration_structure_scratch_alignment: u64,
+    /// Only set when timeline semaphores came from the extension rather than Vulkan 1.2.
+    timeline_semaphore_loader: Option<khr::TimelineSemaphore>,
+    /// Only set when dynamic rendering came from the extension rather than Vulkan 1.3.
+    dynamic_rendering_loader: Option<khr::DynamicRendering>,
+    separate_depth_stencil_layouts: bool,
+    robust_buffer_access: bool,
+    enabled_features: EnabledFeatures,
 }
 
 impl VulkanDevice {
@@ -84,6 +103,9 @@ impl VulkanDevice {
         instance: &VulkanInstance,
         adapter: &VulkanAdapter,
         p_queue: &mut Pool<VulkanQueue>,
+        desc: &DeviceCreateDesc,
+        present_surface: Option<&VulkanSurface>,
+        robust_buffer_access: bool,
     ) -> anyhow::Result<VulkanDevice> {
         // Find Queue Family
         let mut main_queue_family_index = u32::MAX;
@@ -124,6 +146,20 @@ impl VulkanDevice {
             }
         }
 
+        // Present queue, presenting from a family without surface support is undefined
+        let present_queue_family_index = match present_surface {
+            Some(surface) => select_present_family(
+                main_queue_family_index,
+                adapter.queue_family_properties.len() as u32,
+                |i| surface.supports_present(adapter.raw, i),
+            )?
+            .context("No queue family can present to the surface.")?,
+            None => main_queue_family_index,
+        };
+        let present_has_own_family = present_queue_family_index != main_queue_family_index
+            && present_queue_family_index != compute_queue_family_index
+            && present_queue_family_index != transfer_queue_family_index;
+
         let mut queue_infos: Vec<vk::DeviceQueueCreateInfo> = Vec::new();
         {
             let mut main_queue = vk::DeviceQueueCreateInfo::builder()
@@ -153,14 +189,152 @@ impl VulkanDevice {
                 transfer_queue.queue_count = 1;
                 queue_infos.push(transfer_queue);
             }
+
+            if present_has_own_family {
+                let mut present_queue = vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(present_queue_family_index)
+                    .queue_priorities(&[1.0])
+                    .build();
+                present_queue.queue_count = 1;
+                queue_infos.push(present_queue);
+            }
+        }
+
+        let available_extensions =
+            unsafe { instance.raw.enumerate_device_extension_properties(adapter.raw)? };
+        let (requested_extensions, unavailable_extensions) =
+            select_extensions(&available_extensions, desc.extensions);
+
+        // Optional features, from the core version both the instance and the adapter support or
+        // from their extension
+        let api_version = adapter.properties.api_version.min(instance.api_version);
+        let feature_sources =
+            select_feature_sources(desc.features, api_version, &available_extensions);
+        let queryable_features =
+            feature_sources.iter().fold(DeviceFeatures::empty(), |acc, (f, _)| acc | *f);
+
+        let mut multiview = vk::PhysicalDeviceMultiviewFeatures::default();
+        let mut descriptor_indexing = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+        let mut timeline_semaphore = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+        let mut synchronization2 = vk::PhysicalDeviceSynchronization2Features::default();
+        let mut dynamic_rendering = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+        let mut extended_dynamic_state =
+            vk::PhysicalDeviceExtendedDynamicStateFeaturesEXT::default();
+        let mut index_type_uint8 = vk::PhysicalDeviceIndexTypeUint8FeaturesEXT::default();
+        let mut acceleration_structure =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut ray_query = vk::PhysicalDeviceRayQueryFeaturesKHR::default();
+        {
+            let mut features = vk::PhysicalDeviceFeatures2::builder();
+            if queryable_features.contains(DeviceFeatures::MULTIVIEW) {
+                features = features.push_next(&mut multiview);
+            }
+            if queryable_features.contains(DeviceFeatures::DESCRIPTOR_INDEXING) {
+                features = features.push_next(&mut descriptor_indexing);
+            }
+            if queryable_features.contains(DeviceFeatures::TIMELINE_SEMAPHORE) {
+                features = features.push_next(&mut timeline_semaphore);
+            }
+            if queryable_features.contains(DeviceFeatures::SYNCHRONIZATION_2) {
+                features = features.push_next(&mut synchronization2);
+            }
+            if queryable_features.contains(DeviceFeatures::DYNAMIC_RENDERING) {
+                features = features.push_next(&mut dynamic_rendering);
+            }
+            if queryable_features.contains(DeviceFeatures::EXTENDED_DYNAMIC_STATE) {
+                features = features.push_next(&mut extended_dynamic_state);
+            }
+            if queryable_features.contains(DeviceFeatures::INDEX_TYPE_UINT8) {
+                features = features.push_next(&mut index_type_uint8);
+            }
+            if queryable_features.contains(DeviceFeatures::RAY_QUERY) {
+                features =
+                    features.push_next(&mut acceleration_structure).push_next(&mut ray_query);
+            }
+            let mut features = features.build();
+            unsafe { instance.raw.get_physical_device_features2(adapter.raw, &mut features) };
+            multiview.p_next = std::ptr::null_mut();
+            descriptor_indexing.p_next = std::ptr::null_mut();
+            timeline_semaphore.p_next = std::ptr::null_mut();
+            synchronization2.p_next = std::ptr::null_mut();
+            dynamic_rendering.p_next = std::ptr::null_mut();
+            extended_dynamic_state.p_next = std::ptr::null_mut();
+            index_type_uint8.p_next = std::ptr::null_mut();
+            acceleration_structure.p_next = std::ptr::null_mut();
+            ray_query.p_next = std::ptr::null_mut();
+        }
+
+        let mut enabled_features = DeviceFeatures::empty();
+        enabled_features.set(DeviceFeatures::MULTIVIEW, multiview.multiview == vk::TRUE);
+        enabled_features.set(
+            DeviceFeatures::DESCRIPTOR_INDEXING,
+            descriptor_indexing.runtime_descriptor_array == vk::TRUE,
+        );
+        enabled_features.set(
+            DeviceFeatures::TIMELINE_SEMAPHORE,
+            timeline_semaphore.timeline_semaphore == vk::TRUE,
+        );
+        enabled_features
+            .set(DeviceFeatures::SYNCHRONIZATION_2, synchronization2.synchronization2 == vk::TRUE);
+        enabled_features.set(
+            DeviceFeatures::DYNAMIC_RENDERING,
+            dynamic_rendering.dynamic_rendering == vk::TRUE,
+        );
+        enabled_features.set(
+            DeviceFeatures::EXTENDED_DYNAMIC_STATE,
+            extended_dynamic_state.extended_dynamic_state == vk::TRUE,
+        );
+        enabled_features
+            .set(DeviceFeatures::INDEX_TYPE_UINT8, index_type_uint8.index_type_uint8 == vk::TRUE);
+        enabled_features.set(
+            DeviceFeatures::RAY_QUERY,
+            acceleration_structure.acceleration_structure == vk::TRUE
+                && ray_query.ray_query == vk::TRUE,
+        );
+
+        // Device extensions, the swapchain one is always required
+        let mut extension_names: Vec<CString> = vec![khr::Swapchain::name().to_owned()];
+        for (feature, extension) in feature_sources.iter() {
+            if let (true, Some(extension)) = (enabled_features.contains(*feature), extension) {
+                extension_names.push((*extension).to_owned());
+            }
+        }
+        if enabled_features.contains(DeviceFeatures::RAY_QUERY) {
+            // Ray queries go through acceleration structures, which need deferred operations
+            extension_names.push(khr::AccelerationStructure::name().to_owned());
+            extension_names.push(khr::DeferredHostOperations::name().to_owned());
+        }
+        for name in requested_extensions {
+            extension_names.push(CString::new(name)?);
+        }
+        let mut unique_extension_names = Vec::with_capacity(extension_names.len());
+        for name in extension_names {
+            if !unique_extension_names.contains(&name) {
+                unique_extension_names.push(name);
+            }
         }
+        let device_extensions =
+            unique_extension_names.iter().map(|name| name.as_ptr()).collect::<Vec<_>>();
 
-        // Required device extensions
-        let device_extensions = vec![khr::Swapchain::name().as_ptr()];
+        // Only the requested core features
+        let mut physical_features = vk::PhysicalDeviceFeatures2::builder()
+            .features(select_core_features(desc.requested_features, &adapter.features)?)
+            .build();
+        physical_features.features.robust_buffer_access = select_robust_buffer_access(
+            robust_buffer_access,
+            adapter.features.robust_buffer_access,
+        );
 
-        // Enable all features
-        let mut physical_features = vk::PhysicalDeviceFeatures2::builder().build();
-        unsafe { instance.raw.get_physical_device_features2(adapter.raw, &mut physical_features) };
+        // Depth and stencil aspects can be in different layouts when supported
+        let mut separate_depth_stencil_layouts =
+            vk::PhysicalDeviceSeparateDepthStencilLayoutsFeatures::default();
+        {
+            let mut features = vk::PhysicalDeviceFeatures2::builder()
+                .push_next(&mut separate_depth_stencil_layouts)
+                .build();
+            unsafe { instance.raw.get_physical_device_features2(adapter.raw, &mut features) };
+            separate_depth_stencil_layouts.p_next = std::ptr::null_mut();
+        }
 
         // gpu-allocator need this feature
         let mut buffer_device_address = vk::PhysicalDeviceBufferDeviceAddressFeatures::builder()
@@ -168,12 +342,38 @@ impl VulkanDevice {
             .build();
 
         // Create device info
-        let device_create_info = vk::DeviceCreateInfo::builder()
+        let mut device_create_info = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_infos)
             .enabled_extension_names(&device_extensions)
             .push_next(&mut physical_features)
             .push_next(&mut buffer_device_address)
-            .build();
+            .push_next(&mut separate_depth_stencil_layouts);
+        if enabled_features.contains(DeviceFeatures::MULTIVIEW) {
+            device_create_info = device_create_info.push_next(&mut multiview);
+        }
+        if enabled_features.contains(DeviceFeatures::DESCRIPTOR_INDEXING) {
+            device_create_info = device_create_info.push_next(&mut descriptor_indexing);
+        }
+        if enabled_features.contains(DeviceFeatures::TIMELINE_SEMAPHORE) {
+            device_create_info = device_create_info.push_next(&mut timeline_semaphore);
+        }
+        if enabled_features.contains(DeviceFeatures::SYNCHRONIZATION_2) {
+            device_create_info = device_create_info.push_next(&mut synchronization2);
+        }
+        if enabled_features.contains(DeviceFeatures::DYNAMIC_RENDERING) {
+            device_create_info = device_create_info.push_next(&mut dynamic_rendering);
+        }
+        if enabled_features.contains(DeviceFeatures::EXTENDED_DYNAMIC_STATE) {
+            device_create_info = device_create_info.push_next(&mut extended_dynamic_state);
+        }
+        if enabled_features.contains(DeviceFeatures::INDEX_TYPE_UINT8) {
+            device_create_info = device_create_info.push_next(&mut index_type_uint8);
+        }
+        if enabled_features.contains(DeviceFeatures::RAY_QUERY) {
+            device_create_info =
+                device_create_info.push_next(&mut acceleration_structure).push_next(&mut ray_query);
+        }
+        let device_create_info = device_create_info.build();
 
         // Create device
         let device = unsafe { instance.raw.create_device(adapter.raw, &device_create_info, None)? };
@@ -189,7 +389,40 @@ impl VulkanDevice {
         };
         let allocator = ManuallyDrop::new(Allocator::new(&allocator_create_desc)?);
 
+        // Shared by every pipeline created on this device
+        let pipeline_cache = unsafe {
+            device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::builder().build(), None)?
+        };
+
+        let acceleration_structure_loader = enabled_features
+            .contains(DeviceFeatures::RAY_QUERY)
+            .then(|| khr::AccelerationStructure::new(&instance.raw, &device));
+        let timeline_semaphore_loader = unique_extension_names
+            .iter()
+            .any(|name| name.as_c_str() == khr::TimelineSemaphore::name())
+            .then(|| khr::TimelineSemaphore::new(&instance.raw, &device));
+        let dynamic_rendering_loader = unique_extension_names
+            .iter()
+            .any(|name| name.as_c_str() == khr::DynamicRendering::name())
+            .then(|| khr::DynamicRendering::new(&instance.raw, &device));
+        let mut acceleration_structure_properties =
+            vk::PhysicalDeviceAccelerationStructurePropertiesKHR::default();
+        if enabled_features.contains(DeviceFeatures::RAY_QUERY) {
+            let mut properties = vk::PhysicalDeviceProperties2::builder()
+                .push_next(&mut acceleration_structure_properties)
+                .build();
+            unsafe { instance.raw.get_physical_device_properties2(adapter.raw, &mut properties) };
+            acceleration_structure_properties.p_next = std::ptr::null_mut();
+        }
+
         let mut ret = Self {
+            acceleration_structure_loader,
+            acceleration_structure_scratch_alignment: (acceleration_structure_properties
+                .min_acceleration_structure_scratch_offset_alignment
+                as u64)
+                .max(1),
+            timeline_semaphore_loader,
+            dynamic_rendering_loader,
             raw: device,
             adapter: adapter.clone(),
             allocator,
@@ -199,6 +432,24 @@ impl VulkanDevice {
             present_queue: None,
             render_pass_cache: Default::default(),
             framebuffer_cache: Default::default(),
+            pipeline_cache,
+            separate_depth_stencil_layouts: separate_depth_stencil_layouts
+                .separate_depth_stencil_layouts
+                == vk::TRUE,
+            robust_buffer_access: physical_features.features.robust_buffer_access == vk::TRUE,
+            enabled_features: EnabledFeatures {
+                extensions: unique_extension_names
+                    .iter()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .collect(),
+                unavailable_extensions: unavailable_extensions
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+                features: enabled_features,
+                unavailable_features: desc.features - enabled_features,
+                requested_features: desc.requested_features,
+            },
         };
 
         // Get queue
@@ -206,7 +457,6 @@ impl VulkanDevice {
             let main_queue = p_queue.malloc();
             main_queue.1.init(&ret.raw, main_queue_family_index, 0);
             ret.graphics_queue = Some(main_queue.0);
-            ret.present_queue = Some(main_queue.0);
         }
 
         {
@@ -221,6 +471,18 @@ impl VulkanDevice {
             ret.transfer_queue = Some(transfer_queue.0);
         }
 
+        ret.present_queue = if present_queue_family_index == main_queue_family_index {
+            ret.graphics_queue
+        } else if present_queue_family_index == compute_queue_family_index {
+            ret.compute_queue
+        } else if present_queue_family_index == transfer_queue_family_index {
+            ret.transfer_queue
+        } else {
+            let present_queue = p_queue.malloc();
+            present_queue.1.init(&ret.raw, present_queue_family_index, 0);
+            Some(present_queue.0)
+        };
+
         Ok(ret)
     }
 
@@ -234,6 +496,24 @@ impl VulkanDevice {
         &self.adapter
     }
 
+    /// Whether depth and stencil aspects can be transitioned and used in different layouts.
+    #[inline]
+    pub fn separate_depth_stencil_layouts(&self) -> bool {
+        self.separate_depth_stencil_layouts
+    }
+
+    /// Whether out of bounds buffer accesses are defined, see `RenderBackendCreateDesc`.
+    #[inline]
+    pub fn robust_buffer_access(&self) -> bool {
+        self.robust_buffer_access
+    }
+
+    /// Extensions and features the device was created with, see `DeviceCreateDesc`.
+    #[inline]
+    pub fn enabled_features(&self) -> &EnabledFeatures {
+        &self.enabled_features
+    }
+
     #[inline]
     pub fn get_allocator(&self) -> &Allocator {
         &self.allocator
@@ -244,6 +524,93 @@ impl VulkanDevice {
         &mut self.allocator
     }
 
+    #[inline]
+    pub fn acceleration_structure_loader(&self) -> anyhow::Result<&khr::AccelerationStructure> {
+        self.acceleration_structure_loader
+            .as_ref()
+            .context("Acceleration structures need `DeviceFeatures::RAY_QUERY`.")
+    }
+
+    /// Waits until `semaphore` reaches `value`, the timeline counterpart of `wait_for_fences`.
+    pub fn wait_timeline_semaphore(
+        &self,
+        semaphore: &VulkanSemaphore,
+        value: u64,
+        timeout: u64,
+    ) -> anyhow::Result<FenceWaitResult> {
+        self.wait_timeline_semaphores(&[semaphore], &[value], timeout)
+    }
+
+    /// Waits until every semaphore reaches its value.
+    pub fn wait_timeline_semaphores(
+        &self,
+        semaphores: &[&VulkanSemaphore],
+        values: &[u64],
+        timeout: u64,
+    ) -> anyhow::Result<FenceWaitResult> {
+        let semaphores = semaphores
+            .iter()
+            .map(|s| s.timeline_raw())
+            .collect::<anyhow::Result<SmallVec<[vk::Semaphore; 4]>>>()?;
+        let info = vk::SemaphoreWaitInfo::builder().semaphores(&semaphores).values(values);
+        fence_wait_result(unsafe {
+            match &self.timeline_semaphore_loader {
+                Some(loader) => loader.wait_semaphores(&info, timeout),
+                None => self.raw().wait_semaphores(&info, timeout),
+            }
+        })
+    }
+
+    pub fn signal_timeline_semaphore(
+        &self,
+        semaphore: &VulkanSemaphore,
+        value: u64,
+    ) -> anyhow::Result<()> {
+        let info =
+            vk::SemaphoreSignalInfo::builder().semaphore(semaphore.timeline_raw()?).value(value);
+        unsafe {
+            match &self.timeline_semaphore_loader {
+                Some(loader) => loader.signal_semaphore(&info)?,
+                None => self.raw().signal_semaphore(&info)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_timeline_value(&self, semaphore: &VulkanSemaphore) -> anyhow::Result<u64> {
+        let raw = semaphore.timeline_raw()?;
+        Ok(unsafe {
+            match &self.timeline_semaphore_loader {
+                Some(loader) => loader.get_semaphore_counter_value(raw)?,
+                None => self.raw().get_semaphore_counter_value(raw)?,
+            }
+        })
+    }
+
+    pub fn cmd_begin_rendering(&self, cb: vk::CommandBuffer, info: &vk::RenderingInfo) {
+        unsafe {
+            match &self.dynamic_rendering_loader {
+                Some(loader) => loader.cmd_begin_rendering(cb, info),
+                None => self.raw().cmd_begin_rendering(cb, info),
+            }
+        }
+    }
+
+    pub fn cmd_end_rendering(&self, cb: vk::CommandBuffer) {
+        unsafe {
+            match &self.dynamic_rendering_loader {
+                Some(loader) => loader.cmd_end_rendering(cb),
+                None => self.raw().cmd_end_rendering(cb),
+            }
+        }
+    }
+
+    /// Scratch buffers of acceleration structure builds start at a multiple of this.
+    #[inline]
+    pub fn acceleration_structure_scratch_alignment(&self) -> u64 {
+        self.acceleration_structure_scratch_alignment
+    }
+
     #[inline]
     pub fn wait_idle(&self) -> anyhow::Result<()> {
         unsafe { self.raw().device_wait_idle()? };
@@ -257,13 +624,12 @@ impl VulkanDevice {
         wait_all: bool,
         timeout: u64,
         p_fence: &Pool<VulkanFence>,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<FenceWaitResult> {
         let mut raw_fences = SmallVec::<[vk::Fence; 4]>::new();
         for fence in fences {
             raw_fences.push(p_fence.get(*fence).context("Fence not found.")?.raw);
         }
-        unsafe { self.raw().wait_for_fences(&raw_fences, wait_all, timeout)? };
-        Ok(())
+        fence_wait_result(unsafe { self.raw().wait_for_fences(&raw_fences, wait_all, timeout) })
     }
 
     #[inline]
@@ -294,13 +660,21 @@ impl VulkanDevice {
 
     pub fn destroy(&mut self) {
         unsafe {
+            for (_, framebuffer) in self.framebuffer_cache.drain() {
+                self.raw.destroy_framebuffer(framebuffer, None);
+            }
+            for (_, render_pass) in self.render_pass_cache.drain() {
+                self.raw.destroy_render_pass(render_pass, None);
+            }
             ManuallyDrop::drop(&mut self.allocator);
+            self.raw.destroy_pipeline_cache(self.pipeline_cache, None);
             self.raw.destroy_device(None);
         }
         self.graphics_queue = None;
         self.compute_queue = None;
         self.transfer_queue = None;
         self.present_queue = None;
+        self.acceleration_structure_loader = None;
     }
 }
 
@@ -319,12 +693,54 @@ impl VulkanQueue {
         self.family_index = queue_family_index;
     }
 
+    /// Opens a label grouping the following submits in captures, a no-op without debug utils.
+    pub fn begin_label(
+        &self,
+        debug_utils: Option<&DebugUtils>,
+        name: &str,
+        color: [f32; 4],
+    ) -> anyhow::Result<()> {
+        if let Some(debug_utils) = debug_utils {
+            let name = CString::new(name)?;
+            let info = vk::DebugUtilsLabelEXT::builder().label_name(&name).color(color).build();
+            unsafe {
+                debug_utils.queue_begin_debug_utils_label(self.raw, &info);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn end_label(&self, debug_utils: Option<&DebugUtils>) {
+        if let Some(debug_utils) = debug_utils {
+            unsafe {
+                debug_utils.queue_end_debug_utils_label(self.raw);
+            }
+        }
+    }
+
+    pub fn insert_label(
+        &self,
+        debug_utils: Option<&DebugUtils>,
+        name: &str,
+        color: [f32; 4],
+    ) -> anyhow::Result<()> {
+        if let Some(debug_utils) = debug_utils {
+            let name = CString::new(name)?;
+            let info = vk::DebugUtilsLabelEXT::builder().label_name(&name).color(color).build();
+            unsafe {
+                debug_utils.queue_insert_debug_utils_label(self.raw, &info);
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     pub fn wait_idle(&self, device: &ash::Device) -> anyhow::Result<()> {
         unsafe { device.queue_wait_idle(self.raw)? };
         Ok(())
     }
 
+    #[inline]
     pub fn submit(
         &self,
         device: &VulkanDevice,
@@ -333,48 +749,45 @@ impl VulkanQueue {
         p_semaphore: &Pool<VulkanSemaphore>,
         p_command_buffer: &Pool<VulkanCommandBuffer>,
     ) -> anyhow::Result<()> {
-        let fence = if let Some(f) = desc.fence {
-            p_fence.get(f).context("Fence not found.")?.raw
-        } else {
-            ash::vk::Fence::null()
-        };
-
-        let mut cbs = SmallVec::<[vk::CommandBuffer; 4]>::new();
-        let mut wait = SmallVec::<[vk::Semaphore; 4]>::new();
-        let mut stage = SmallVec::<[vk::PipelineStageFlags; 4]>::new();
-        let mut signal = SmallVec::<[vk::Semaphore; 4]>::new();
-
-        for cb in desc.command_buffer.iter() {
-            cbs.push(p_command_buffer.get(*cb).unwrap().raw);
-        }
-
-        if let Some(wait_semaphores) = desc.wait_semaphore {
-            for s in wait_semaphores {
-                wait.push(p_semaphore.get(*s).unwrap().raw);
-            }
-        }
+        self.submit_batch(
+            device,
+            std::slice::from_ref(desc),
+            p_fence,
+            p_semaphore,
+            p_command_buffer,
+        )
+    }
 
-        if let Some(wait_dst_stage_mask) = desc.wait_stage {
-            for s in wait_dst_stage_mask {
-                stage.push((*s).into());
-            }
-        }
+    /// Submits every desc with a single `vkQueueSubmit`, signaling the one fence they share.
+    pub fn submit_batch(
+        &self,
+        device: &VulkanDevice,
+        descs: &[QueueSubmitDesc],
+        p_fence: &Pool<VulkanFence>,
+        p_semaphore: &Pool<VulkanSemaphore>,
+        p_command_buffer: &Pool<VulkanCommandBuffer>,
+    ) -> anyhow::Result<()> {
+        let fence = match batch_fence(descs)? {
+            Some(f) => p_fence.get(f).context("Fence not found.")?.raw,
+            None => vk::Fence::null(),
+        };
 
-        if let Some(signal_semaphores) = desc.finish_semaphore {
-            for s in signal_semaphores {
-                signal.push(p_semaphore.get(*s).unwrap().raw);
+        let mut batch = SubmitBatch::default();
+        for desc in descs {
+            if cfg!(debug_assertions) {
+                for cb in desc.command_buffer {
+                    p_command_buffer
+                        .get(*cb)
+                        .context("Command buffer not found.")?
+                        .validate_submit()?;
+                }
             }
+            batch.push(desc, p_semaphore, p_command_buffer)?;
         }
 
-        let submit_info = vk::SubmitInfo::builder()
-            .command_buffers(&cbs)
-            .wait_semaphores(&wait)
-            .wait_dst_stage_mask(&stage)
-            .signal_semaphores(&signal)
-            .build();
-
+        let mut timeline_infos = SmallVec::new();
         unsafe {
-            device.raw().queue_submit(self.raw, &[submit_info], fence)?;
+            device.raw().queue_submit(self.raw, &batch.infos(&mut timeline_infos), fence)?;
         }
         Ok(())
     }
@@ -386,6 +799,7 @@ impl VulkanQueue {
         p_semaphore: &Pool<VulkanSemaphore>,
     ) -> anyhow::Result<bool> {
         let swapchain = p_swapchain.get(desc.swapchain).context("Swapchain not found.")?;
+        swapchain.check_present_queue(self.family_index)?;
         let wait_semaphores = desc
             .wait_semaphores
             .iter()
@@ -401,3 +815,499 @@ impl VulkanQueue {
         Ok(res)
     }
 }
+
+/// Robust buffer access is only enabled on request, falling back to the default behavior when the
+/// adapter lacks it.
+fn select_robust_buffer_access(requested: bool, supported: vk::Bool32) -> vk::Bool32 {
+    if requested && supported == vk::TRUE {
+        vk::TRUE
+    } else {
+        vk::FALSE
+    }
+}
+
+/// The core features to create the device with, fails naming the ones the adapter lacks.
+fn select_core_features(
+    requested: RequestedFeatures,
+    supported: &vk::PhysicalDeviceFeatures,
+) -> Result<vk::PhysicalDeviceFeatures> {
+    let mut features = vk::PhysicalDeviceFeatures::default();
+    let mut missing = RequestedFeatures::empty();
+    for feature in requested.iter() {
+        let (enabled, available) = match feature {
+            RequestedFeatures::SAMPLER_ANISOTROPY => {
+                (&mut features.sampler_anisotropy, supported.sampler_anisotropy)
+            }
+            RequestedFeatures::FILL_MODE_NON_SOLID => {
+                (&mut features.fill_mode_non_solid, supported.fill_mode_non_solid)
+            }
+            RequestedFeatures::WIDE_LINES => (&mut features.wide_lines, supported.wide_lines),
+            RequestedFeatures::DEPTH_CLAMP => (&mut features.depth_clamp, supported.depth_clamp),
+            RequestedFeatures::GEOMETRY_SHADER => {
+                (&mut features.geometry_shader, supported.geometry_shader)
+            }
+            RequestedFeatures::TESSELLATION_SHADER => {
+                (&mut features.tessellation_shader, supported.tessellation_shader)
+            }
+            RequestedFeatures::INDEPENDENT_BLEND => {
+                (&mut features.independent_blend, supported.independent_blend)
+            }
+            RequestedFeatures::LOGIC_OP => (&mut features.logic_op, supported.logic_op),
+            RequestedFeatures::MULTI_DRAW_INDIRECT => {
+                (&mut features.multi_draw_indirect, supported.multi_draw_indirect)
+            }
+            RequestedFeatures::MULTI_VIEWPORT => {
+                (&mut features.multi_viewport, supported.multi_viewport)
+            }
+            _ => unreachable!("Not a single core feature"),
+        };
+        if available == vk::TRUE {
+            *enabled = vk::TRUE;
+        } else {
+            missing |= feature;
+        }
+    }
+    if !missing.is_empty() {
+        anyhow::bail!("The adapter doesn't support the requested features {:?}.", missing);
+    }
+    Ok(features)
+}
+
+/// The first queue family that can present, trying `preferred` before the others.
+fn select_present_family(
+    preferred: u32,
+    family_count: u32,
+    mut supports_present: impl FnMut(u32) -> anyhow::Result<bool>,
+) -> anyhow::Result<Option<u32>> {
+    if preferred < family_count && supports_present(preferred)? {
+        return Ok(Some(preferred));
+    }
+    for i in (0..family_count).filter(|i| *i != preferred) {
+        if supports_present(i)? {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+/// Splits the requested extensions into the ones the adapter has and the ones it lacks.
+fn select_extensions<'a>(
+    available: &[vk::ExtensionProperties],
+    requested: &[&'a str],
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    requested.iter().copied().partition(|name| has_extension(available, name.as_bytes()))
+}
+
+/// Vulkan version a feature became core in, if any, and the extension exposing it otherwise.
+fn feature_requirement(feature: DeviceFeatures) -> (Option<u32>, &'static CStr) {
+    match feature {
+        DeviceFeatures::MULTIVIEW => (Some(vk::API_VERSION_1_1), vk::KhrMultiviewFn::name()),
+        DeviceFeatures::DESCRIPTOR_INDEXING => {
+            (Some(vk::API_VERSION_1_2), vk::ExtDescriptorIndexingFn::name())
+        }
+        DeviceFeatures::TIMELINE_SEMAPHORE => {
+            (Some(vk::API_VERSION_1_2), vk::KhrTimelineSemaphoreFn::name())
+        }
+        DeviceFeatures::SYNCHRONIZATION_2 => {
+            (Some(vk::API_VERSION_1_3), vk::KhrSynchronization2Fn::name())
+        }
+        DeviceFeatures::DYNAMIC_RENDERING => {
+            (Some(vk::API_VERSION_1_3), vk::KhrDynamicRenderingFn::name())
+        }
+        DeviceFeatures::EXTENDED_DYNAMIC_STATE => {
+            (Some(vk::API_VERSION_1_3), vk::ExtExtendedDynamicStateFn::name())
+        }
+        DeviceFeatures::INDEX_TYPE_UINT8 => (None, vk::ExtIndexTypeUint8Fn::name()),
+        DeviceFeatures::RAY_QUERY => (None, vk::KhrRayQueryFn::name()),
+        _ => unreachable!("Not a single device feature"),
+    }
+}
+
+/// The requested features the adapter can expose at all, with the extension each one needs or
+/// `None` when it is core in `api_version`.
+fn select_feature_sources(
+    requested: DeviceFeatures,
+    api_version: u32,
+    available: &[vk::ExtensionProperties],
+) -> Vec<(DeviceFeatures, Option<&'static CStr>)> {
+    let mut sources = Vec::new();
+    for feature in requested.iter() {
+        let (core_version, extension) = feature_requirement(feature);
+        if core_version.is_some_and(|v| api_version >= v) {
+            sources.push((feature, None));
+        } else if has_extension(available, extension.to_bytes()) {
+            sources.push((feature, Some(extension)));
+        }
+    }
+    sources
+}
+
+/// The fence a batch signals, descs either leave it unset or name the same one.
+fn batch_fence(descs: &[QueueSubmitDesc]) -> anyhow::Result<Option<Handle<Fence>>> {
+    let mut fence = None;
+    for f in descs.iter().filter_map(|desc| desc.fence) {
+        match fence {
+            Some(other) if other != f => {
+                anyhow::bail!("A batched submit signals a single fence, got several.")
+            }
+            _ => fence = Some(f),
+        }
+    }
+    Ok(fence)
+}
+
+/// Raw handles of several submits in flat arrays, the `vk::SubmitInfo`s point into them.
+#[derive(Default)]
+struct SubmitBatch {
+    command_buffers: SmallVec<[vk::CommandBuffer; 8]>,
+    wait: SmallVec<[vk::Semaphore; 8]>,
+    stage: SmallVec<[vk::PipelineStageFlags; 8]>,
+    signal: SmallVec<[vk::Semaphore; 8]>,
+    wait_values: SmallVec<[u64; 8]>,
+    signal_values: SmallVec<[u64; 8]>,
+    // End of each submit in the command buffer, wait and signal arrays
+    ends: SmallVec<[(usize, usize, usize); 4]>,
+    // End of each submit in the wait and signal value arrays
+    value_ends: SmallVec<[(usize, usize); 4]>,
+}
+
+/// Timeline semaphores need a value for every semaphore of their side of the submit, binary
+/// ones ignore theirs.
+fn check_semaphore_values(
+    semaphores: &[&VulkanSemaphore],
+    values: Option<&[u64]>,
+    side: &str,
+) -> anyhow::Result<()> {
+    match values {
+        Some(values) if values.len() != semaphores.len() => anyhow::bail!(
+            "Submit {} {} semaphores but has {} values.",
+            side,
+            semaphores.len(),
+            values.len()
+        ),
+        None if semaphores.iter().any(|s| s.timeline) => {
+            anyhow::bail!("Submit {} a timeline semaphore without values.", side)
+        }
+        _ => Ok(()),
+    }
+}
+
+impl SubmitBatch {
+    fn push(
+        &mut self,
+        desc: &QueueSubmitDesc,
+        p_semaphore: &Pool<VulkanSemaphore>,
+        p_command_buffer: &Pool<VulkanCommandBuffer>,
+    ) -> anyhow::Result<()> {
+        let wait = desc.wait_semaphore.unwrap_or_default();
+        let stage = desc.wait_stage.unwrap_or_default();
+        if wait.len() != stage.len() {
+            anyhow::bail!(
+                "Submit waits on {} semaphores but has {} wait stages.",
+                wait.len(),
+                stage.len()
+            );
+        }
+
+        let semaphores = |handles: &[Handle<Semaphore>]| {
+            handles
+                .iter()
+                .map(|s| p_semaphore.get(*s).context("Semaphore not found."))
+                .collect::<anyhow::Result<SmallVec<[&VulkanSemaphore; 4]>>>()
+        };
+        let wait = semaphores(wait)?;
+        let signal = semaphores(desc.finish_semaphore.unwrap_or_default())?;
+        check_semaphore_values(&wait, desc.wait_values, "waits on")?;
+        check_semaphore_values(&signal, desc.signal_values, "signals")?;
+
+        for cb in desc.command_buffer {
+            self.command_buffers
+                .push(p_command_buffer.get(*cb).context("Command buffer not found.")?.raw);
+        }
+        self.wait.extend(wait.iter().map(|s| s.raw));
+        self.stage.extend(stage.iter().map(|s| vk::PipelineStageFlags::from(*s)));
+        self.signal.extend(signal.iter().map(|s| s.raw));
+        self.wait_values.extend_from_slice(desc.wait_values.unwrap_or_default());
+        self.signal_values.extend_from_slice(desc.signal_values.unwrap_or_default());
+        self.ends.push((self.command_buffers.len(), self.wait.len(), self.signal.len()));
+        self.value_ends.push((self.wait_values.len(), self.signal_values.len()));
+        Ok(())
+    }
+
+    /// The submit infos, chaining the entries of `timeline_infos` to the submits with values.
+    /// `timeline_infos` has to stay in place while the infos are used.
+    fn infos(
+        &self,
+        timeline_infos: &mut SmallVec<[vk::TimelineSemaphoreSubmitInfo; 4]>,
+    ) -> SmallVec<[vk::SubmitInfo; 4]> {
+        let mut start = (0, 0);
+        timeline_infos.clear();
+        for &end in &self.value_ends {
+            timeline_infos.push(
+                vk::TimelineSemaphoreSubmitInfo::builder()
+                    .wait_semaphore_values(&self.wait_values[start.0..end.0])
+                    .signal_semaphore_values(&self.signal_values[start.1..end.1])
+                    .build(),
+            );
+            start = end;
+        }
+
+        let mut start = (0, 0, 0);
+        let mut infos = SmallVec::new();
+        for (i, &end) in self.ends.iter().enumerate() {
+            let mut info = vk::SubmitInfo::builder()
+                .command_buffers(&self.command_buffers[start.0..end.0])
+                .wait_semaphores(&self.wait[start.1..end.1])
+                .wait_dst_stage_mask(&self.stage[start.1..end.1])
+                .signal_semaphores(&self.signal[start.2..end.2])
+                .build();
+            let timeline = &timeline_infos[i];
+            if timeline.wait_semaphore_value_count > 0 || timeline.signal_semaphore_value_count > 0
+            {
+                info.p_next = timeline as *const vk::TimelineSemaphoreSubmitInfo as *const _;
+            }
+            infos.push(info);
+            start = end;
+        }
+        infos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extension_properties(names: &[&str]) -> Vec<vk::ExtensionProperties> {
+        names
+            .iter()
+            .map(|name| {
+                let mut props = vk::ExtensionProperties::default();
+                for (dst, src) in props.extension_name.iter_mut().zip(name.bytes()) {
+                    *dst = src as std::ffi::c_char;
+                }
+                props
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unavailable_extensions_are_reported() {
+        let available = extension_properties(&["VK_KHR_swapchain", "VK_KHR_maintenance1"]);
+        let (enabled, unavailable) = select_extensions(
+            &available,
+            &["VK_KHR_maintenance1", "VK_LUXSEED_implausible_extension"],
+        );
+        assert_eq!(enabled, vec!["VK_KHR_maintenance1"]);
+        assert_eq!(unavailable, vec!["VK_LUXSEED_implausible_extension"]);
+    }
+
+    #[test]
+    fn features_come_from_core_or_extension() {
+        let available = extension_properties(&["VK_KHR_synchronization2"]);
+        let sources = select_feature_sources(
+            DeviceFeatures::MULTIVIEW
+                | DeviceFeatures::SYNCHRONIZATION_2
+                | DeviceFeatures::DYNAMIC_RENDERING,
+            vk::API_VERSION_1_2,
+            &available,
+        );
+        assert_eq!(
+            sources,
+            vec![
+                (DeviceFeatures::MULTIVIEW, None),
+                (DeviceFeatures::SYNCHRONIZATION_2, Some(vk::KhrSynchronization2Fn::name())),
+            ]
+        );
+    }
+
+    #[test]
+    fn core_features_are_enabled_only_when_requested() {
+        let supported = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            fill_mode_non_solid: vk::TRUE,
+            geometry_shader: vk::TRUE,
+            ..Default::default()
+        };
+
+        let features = select_core_features(RequestedFeatures::empty(), &supported).unwrap();
+        assert_eq!(features.sampler_anisotropy, vk::FALSE);
+        assert_eq!(features.geometry_shader, vk::FALSE);
+
+        let features =
+            select_core_features(RequestedFeatures::FILL_MODE_NON_SOLID, &supported).unwrap();
+        assert_eq!(features.fill_mode_non_solid, vk::TRUE);
+        assert_eq!(features.sampler_anisotropy, vk::FALSE);
+
+        let err = select_core_features(
+            RequestedFeatures::SAMPLER_ANISOTROPY
+                | RequestedFeatures::WIDE_LINES
+                | RequestedFeatures::LOGIC_OP,
+            &supported,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("WIDE_LINES | LOGIC_OP"));
+        assert!(!err.to_string().contains("SAMPLER_ANISOTROPY"));
+    }
+
+    #[test]
+    fn present_family_supports_the_surface() {
+        let supported = |families: &'static [u32]| move |i| Ok(families.contains(&i));
+
+        // The graphics family wins when it can present
+        assert_eq!(select_present_family(0, 3, supported(&[0, 2])).unwrap(), Some(0));
+        // Otherwise the first family that can
+        let present = select_present_family(0, 3, supported(&[2])).unwrap().unwrap();
+        assert_eq!(present, 2);
+        assert!(supported(&[2])(present).unwrap());
+        assert_eq!(select_present_family(1, 3, supported(&[])).unwrap(), None);
+        assert!(select_present_family(0, 2, |_| Err(anyhow::anyhow!("lost"))).is_err());
+    }
+
+    #[test]
+    fn batched_submit_keeps_each_wait() {
+        use ash::vk::Handle as _;
+
+        let mut p_semaphore = Pool::<VulkanSemaphore>::with_size(4, Default::default);
+        let mut p_command_buffer = Pool::<VulkanCommandBuffer>::with_size(4, Default::default);
+        let mut semaphores = Vec::new();
+        let mut command_buffers = Vec::new();
+        for i in 1..=2 {
+            let (handle, semaphore) = p_semaphore.malloc();
+            semaphore.raw = vk::Semaphore::from_raw(i);
+            semaphores.push(handle);
+            let (handle, cb) = p_command_buffer.malloc();
+            cb.raw = vk::CommandBuffer::from_raw(i + 10);
+            command_buffers.push(handle);
+        }
+
+        let descs = [0, 1].map(|i| QueueSubmitDesc {
+            wait_semaphore: Some(&semaphores[i..i + 1]),
+            wait_values: None,
+            wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+            command_buffer: &command_buffers[i..i + 1],
+            finish_semaphore: None,
+            signal_values: None,
+            fence: None,
+        });
+        let mut batch = SubmitBatch::default();
+        for desc in &descs {
+            batch.push(desc, &p_semaphore, &p_command_buffer).unwrap();
+        }
+
+        let mut timeline_infos = SmallVec::new();
+        let infos = batch.infos(&mut timeline_infos);
+        assert_eq!(infos.len(), 2);
+        for (i, info) in infos.iter().enumerate() {
+            assert_eq!((info.command_buffer_count, info.wait_semaphore_count), (1, 1));
+            assert_eq!(info.signal_semaphore_count, 0);
+            assert!(info.p_next.is_null());
+            let (cb, wait) = unsafe { (*info.p_command_buffers, *info.p_wait_semaphores) };
+            assert_eq!(cb.as_raw(), i as u64 + 11);
+            assert_eq!(wait.as_raw(), i as u64 + 1);
+        }
+
+        // Every wait semaphore needs its stage
+        let desc = QueueSubmitDesc { wait_stage: None, ..descs[0] };
+        assert!(batch.push(&desc, &p_semaphore, &p_command_buffer).is_err());
+    }
+
+    #[test]
+    fn timeline_values_follow_their_submit() {
+        let mut p_semaphore = Pool::<VulkanSemaphore>::with_size(4, Default::default);
+        let p_command_buffer = Pool::<VulkanCommandBuffer>::with_size(1, Default::default);
+        let binary = p_semaphore.malloc().0;
+        let (timeline, semaphore) = p_semaphore.malloc();
+        semaphore.timeline = true;
+
+        // Waits on the binary acquire semaphore, then signals the frame counter
+        let frame = QueueSubmitDesc {
+            wait_semaphore: Some(&[binary]),
+            wait_values: None,
+            wait_stage: Some(&[PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT]),
+            command_buffer: &[],
+            finish_semaphore: Some(&[timeline]),
+            signal_values: Some(&[7]),
+            fence: None,
+        };
+        let mut batch = SubmitBatch::default();
+        batch.push(&frame, &p_semaphore, &p_command_buffer).unwrap();
+        let next = QueueSubmitDesc {
+            wait_semaphore: Some(&[timeline, binary]),
+            wait_values: Some(&[7, 0]),
+            wait_stage: Some(&[PipelineStageFlags::TRANSFER, PipelineStageFlags::TRANSFER]),
+            finish_semaphore: None,
+            signal_values: None,
+            ..frame
+        };
+        batch.push(&next, &p_semaphore, &p_command_buffer).unwrap();
+
+        let mut timeline_infos = SmallVec::new();
+        let infos = batch.infos(&mut timeline_infos);
+        let chained = |info: &vk::SubmitInfo| unsafe {
+            let timeline = &*(info.p_next as *const vk::TimelineSemaphoreSubmitInfo);
+            (
+                std::slice::from_raw_parts(
+                    timeline.p_wait_semaphore_values,
+                    timeline.wait_semaphore_value_count as usize,
+                )
+                .to_vec(),
+                std::slice::from_raw_parts(
+                    timeline.p_signal_semaphore_values,
+                    timeline.signal_semaphore_value_count as usize,
+                )
+                .to_vec(),
+            )
+        };
+        assert_eq!(chained(&infos[0]), (vec![], vec![7]));
+        assert_eq!(chained(&infos[1]), (vec![7, 0], vec![]));
+
+        let missing = QueueSubmitDesc { signal_values: None, ..frame };
+        assert_eq!(
+            batch.push(&missing, &p_semaphore, &p_command_buffer).unwrap_err().to_string(),
+            "Submit signals a timeline semaphore without values."
+        );
+        let short = QueueSubmitDesc { wait_values: Some(&[7]), ..next };
+        assert_eq!(
+            batch.push(&short, &p_semaphore, &p_command_buffer).unwrap_err().to_string(),
+            "Submit waits on 2 semaphores but has 1 values."
+        );
+    }
+
+    #[test]
+    fn batched_submit_shares_one_fence() {
+        let mut p_fence = Pool::<VulkanFence>::with_size(2, Default::default);
+        let a = p_fence.malloc().0;
+        let b = p_fence.malloc().0;
+        let desc = |fence| QueueSubmitDesc {
+            wait_semaphore: None,
+            wait_values: None,
+            wait_stage: None,
+            command_buffer: &[],
+            finish_semaphore: None,
+            signal_values: None,
+            fence,
+        };
+
+        assert_eq!(batch_fence(&[desc(None), desc(None)]).unwrap(), None);
+        assert_eq!(batch_fence(&[desc(None), desc(Some(a)), desc(Some(a))]).unwrap(), Some(a));
+        let err = batch_fence(&[desc(Some(a)), desc(Some(b))]).unwrap_err();
+        assert_eq!(err.to_string(), "A batched submit signals a single fence, got several.");
+    }
+
+    #[test]
+    fn queue_labels_without_debug_utils() {
+        let queue = VulkanQueue::default();
+        // Without debug utils the null queue is never touched
+        queue.begin_label(None, "Shadow pass", [1.0, 0.0, 0.0, 1.0]).unwrap();
+        queue.insert_label(None, "Shadow submit", [1.0, 1.0, 0.0, 1.0]).unwrap();
+        queue.end_label(None);
+    }
+
+    #[test]
+    fn robust_buffer_access_only_when_requested_and_supported() {
+        assert_eq!(select_robust_buffer_access(true, vk::TRUE), vk::TRUE);
+        // Unsupported adapters still get a device, just without robustness
+        assert_eq!(select_robust_buffer_access(true, vk::FALSE), vk::FALSE);
+        assert_eq!(select_robust_buffer_access(false, vk::TRUE), vk::FALSE);
+    }
+}
@@ -1,17 +1,19 @@
-use anyhow::{Context, Ok};
+use anyhow::{bail, Context, Ok};
 use ash::vk::{self};
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Pool};
 use smallvec::SmallVec;
+use std::fmt;
 
 use crate::{
-    define::{Framebuffer, FramebufferCreateDesc},
+    define::{Framebuffer, FramebufferCreateDesc, FramebufferExtent},
     MAX_RENDER_TARGETS,
 };
 
 use super::{
     device::VulkanDevice,
     image::{VulkanImage, VulkanImageView},
+    render_pass::VulkanRenderPassOutput,
 };
 
 #[derive(Default)]
@@ -41,7 +43,7 @@ impl VulkanFramebuffer {
 pub struct VulkanFramebufferDesc {
     pub render_pass: vk::RenderPass,
     pub num_attachments: u8,
-    pub views: [vk::ImageView; MAX_RENDER_TARGETS + 1],
+    pub views: [vk::ImageView; 2 * MAX_RENDER_TARGETS + 1],
     pub width: u32,
     pub height: u32,
     pub layers: u32,
@@ -50,39 +52,161 @@ pub struct VulkanFramebufferDesc {
 impl VulkanFramebufferDesc {
     pub fn from_create_desc(
         render_pass: vk::RenderPass,
+        output: &VulkanRenderPassOutput,
         creation: &FramebufferCreateDesc,
         p_texture: &Pool<VulkanImage>,
         p_texture_view: &Pool<VulkanImageView>,
     ) -> anyhow::Result<Self> {
-        let mut views = [ash::vk::ImageView::null(); MAX_RENDER_TARGETS + 1];
+        let mut views = [ash::vk::ImageView::null(); 2 * MAX_RENDER_TARGETS + 1];
         let mut num_attachments = 0;
-        let mut layers = 1;
-        let mut width = 0;
-        let mut height = 0;
+        // Width, height and layers of the first attachment, the others have to match
+        let mut size = None;
 
-        for view in creation.color_views.iter() {
+        for (i, view) in creation.color_views.iter().enumerate() {
             let view = p_texture_view.get(*view).context("Color texture view not found")?;
             views[num_attachments as usize] = view.raw;
             num_attachments += 1;
             let texture = p_texture
                 .get(view.texture.context("Texture view's texture is none")?)
                 .context("Texture not found")?;
-            width = texture.desc.extent.width;
-            height = texture.desc.extent.height;
-            layers = texture.desc.array_layers as u32;
+            check_attachment_size(
+                &mut size,
+                texture,
+                view,
+                format_args!("Color attachment {}", i),
+            )?;
         }
 
         if let Some(depth_view) = creation.depth_stencil_view {
             let view =
                 p_texture_view.get(depth_view).context("Depth stencil texture view not found")?;
+            if !view
+                .desc
+                .aspect_mask
+                .intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL)
+            {
+                bail!(
+                    "Depth stencil attachment view has the {:?} aspect, it needs the depth or stencil aspect.",
+                    view.desc.aspect_mask
+                );
+            }
             views[num_attachments as usize] = view.raw;
             num_attachments += 1;
+
+            // Depth-only passes (e.g. shadow maps) take their size from the depth attachment
+            let texture = p_texture
+                .get(view.texture.context("Texture view's texture is none")?)
+                .context("Texture not found")?;
+            check_attachment_size(
+                &mut size,
+                texture,
+                view,
+                format_args!("Depth stencil attachment"),
+            )?;
+        }
+
+        if creation.resolve_views.len() != output.num_resolves() as usize {
+            bail!(
+                "Framebuffer has {} resolve views but the render pass resolves {} colors.",
+                creation.resolve_views.len(),
+                output.num_resolves()
+            );
+        }
+        for (i, (view, format)) in
+            creation.resolve_views.iter().zip(output.resolve_formats()).enumerate()
+        {
+            let view = p_texture_view.get(*view).context("Resolve texture view not found")?;
+            views[num_attachments as usize] = view.raw;
+            num_attachments += 1;
+            let texture = p_texture
+                .get(view.texture.context("Texture view's texture is none")?)
+                .context("Texture not found")?;
+            if texture.desc.samples != vk::SampleCountFlags::TYPE_1 {
+                bail!(
+                    "Resolve attachment {} has {:?} samples, it needs a single sample.",
+                    i,
+                    texture.desc.samples
+                );
+            }
+            if view.desc.format != format {
+                bail!(
+                    "Resolve attachment {} is {:?} but the render pass resolves into {:?}.",
+                    i,
+                    view.desc.format,
+                    format
+                );
+            }
+            check_attachment_size(
+                &mut size,
+                texture,
+                view,
+                format_args!("Resolve attachment {}", i),
+            )?;
         }
 
+        let [width, height, layers] = render_area(creation.extent, size)?;
         Ok(Self { render_pass, num_attachments, views, width, height, layers })
     }
 }
 
+/// `extent` if the attachments of `size` cover it, otherwise their size.
+pub(super) fn render_area(
+    extent: Option<FramebufferExtent>,
+    size: Option<[u32; 3]>,
+) -> anyhow::Result<[u32; 3]> {
+    Ok(match (extent, size) {
+        (Some(extent), Some([width, height, layers]))
+            if extent.width > width || extent.height > height || extent.layers > layers =>
+        {
+            bail!(
+                    "Framebuffer extent {}x{} with {} layers is larger than its attachments, which are {}x{} with {} layers.",
+                    extent.width,
+                    extent.height,
+                    extent.layers,
+                    width,
+                    height,
+                    layers
+                )
+        }
+        (Some(FramebufferExtent { width, height, layers }), _) => [width, height, layers],
+        (None, size) => size.unwrap_or([0, 0, 1]),
+    })
+}
+
+/// Size of the mip level and layers `view` selects, what the view is as an attachment.
+fn attachment_extent(texture: &VulkanImage, view: &VulkanImageView) -> [u32; 3] {
+    let mip = u32::from(view.desc.base_mip_level);
+    [
+        (texture.desc.extent.width >> mip).max(1),
+        (texture.desc.extent.height >> mip).max(1),
+        u32::from(view.desc.layer_count),
+    ]
+}
+
+pub(super) fn check_attachment_size(
+    size: &mut Option<[u32; 3]>,
+    texture: &VulkanImage,
+    view: &VulkanImageView,
+    attachment: fmt::Arguments,
+) -> anyhow::Result<()> {
+    let extent = attachment_extent(texture, view);
+    match size {
+        None => *size = Some(extent),
+        Some(first) if *first != extent => bail!(
+            "{} is {}x{} with {} layers but the first attachment is {}x{} with {} layers.",
+            attachment,
+            extent[0],
+            extent[1],
+            extent[2],
+            first[0],
+            first[1],
+            first[2]
+        ),
+        Some(_) => {}
+    }
+    Ok(())
+}
+
 impl VulkanDevice {
     pub fn get_or_create_framebuffer(
         &mut self,
@@ -100,7 +224,8 @@ impl VulkanDevice {
         device: &ash::Device,
         desc: &VulkanFramebufferDesc,
     ) -> anyhow::Result<vk::Framebuffer> {
-        let mut attachments: SmallVec<[vk::ImageView; MAX_RENDER_TARGETS + 1]> = Default::default();
+        let mut attachments: SmallVec<[vk::ImageView; 2 * MAX_RENDER_TARGETS + 1]> =
+            Default::default();
         for i in 0..desc.num_attachments {
             attachments.push(desc.views[i as usize]);
         }
@@ -115,3 +240,179 @@ impl VulkanDevice {
         Ok(raw)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::define::ImageView;
+
+    fn add_view(
+        p_texture: &mut Pool<VulkanImage>,
+        p_texture_view: &mut Pool<VulkanImageView>,
+        width: u32,
+        height: u32,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Handle<ImageView> {
+        let (texture, item) = p_texture.malloc();
+        item.desc.extent = vk::Extent3D { width, height, depth: 1 };
+        item.desc.array_layers = 1;
+        let (view, item) = p_texture_view.malloc();
+        item.texture = Some(texture);
+        item.desc.aspect_mask = aspect_mask;
+        item.desc.level_count = 1;
+        item.desc.layer_count = 1;
+        view
+    }
+
+    #[test]
+    fn mismatched_attachments() {
+        let mut p_texture = Pool::<VulkanImage>::with_size(8, Default::default);
+        let mut p_texture_view = Pool::<VulkanImageView>::with_size(8, Default::default);
+        let color = vk::ImageAspectFlags::COLOR;
+        let depth = vk::ImageAspectFlags::DEPTH;
+        let stencil = vk::ImageAspectFlags::STENCIL;
+        let color_a = add_view(&mut p_texture, &mut p_texture_view, 800, 600, color);
+        let color_b = add_view(&mut p_texture, &mut p_texture_view, 1024, 768, color);
+        let depth_a = add_view(&mut p_texture, &mut p_texture_view, 800, 600, depth);
+        let depth_b = add_view(&mut p_texture, &mut p_texture_view, 1024, 768, depth);
+        let stencil = add_view(&mut p_texture, &mut p_texture_view, 800, 600, stencil);
+
+        let build = |color_views: &[Handle<ImageView>], depth_stencil_view| {
+            VulkanFramebufferDesc::from_create_desc(
+                vk::RenderPass::null(),
+                &Default::default(),
+                &FramebufferCreateDesc {
+                    render_pass: Handle::default(),
+                    color_views,
+                    depth_stencil_view,
+                    resolve_views: &[],
+                    extent: None,
+                },
+                &p_texture,
+                &p_texture_view,
+            )
+        };
+
+        let desc = build(&[color_a], Some(depth_a)).unwrap();
+        assert_eq!((desc.width, desc.height, desc.layers, desc.num_attachments), (800, 600, 1, 2));
+
+        assert_eq!(
+            build(&[color_a, color_b], None).unwrap_err().to_string(),
+            "Color attachment 1 is 1024x768 with 1 layers but the first attachment is 800x600 with 1 layers."
+        );
+        assert_eq!(
+            build(&[color_a], Some(depth_b)).unwrap_err().to_string(),
+            "Depth stencil attachment is 1024x768 with 1 layers but the first attachment is 800x600 with 1 layers."
+        );
+        assert_eq!(
+            build(&[color_a], Some(color_a)).unwrap_err().to_string(),
+            "Depth stencil attachment view has the COLOR aspect, it needs the depth or stencil aspect."
+        );
+
+        // A stencil-only attachment
+        assert!(build(&[color_a], Some(stencil)).is_ok());
+    }
+    #[test]
+    fn mip_level_render_area() {
+        let mut p_texture = Pool::<VulkanImage>::with_size(8, Default::default);
+        let mut p_texture_view = Pool::<VulkanImageView>::with_size(8, Default::default);
+        let color = vk::ImageAspectFlags::COLOR;
+        let mip_0 = add_view(&mut p_texture, &mut p_texture_view, 1024, 512, color);
+        let (texture, desc) = {
+            let view = p_texture_view.get(mip_0).unwrap();
+            (view.texture, view.desc)
+        };
+        let (mip_1, item) = p_texture_view.malloc();
+        item.texture = texture;
+        item.desc = desc;
+        item.desc.base_mip_level = 1;
+
+        let build = |color_views: &[Handle<ImageView>], extent| {
+            VulkanFramebufferDesc::from_create_desc(
+                vk::RenderPass::null(),
+                &Default::default(),
+                &FramebufferCreateDesc {
+                    render_pass: Handle::default(),
+                    color_views,
+                    depth_stencil_view: None,
+                    resolve_views: &[],
+                    extent,
+                },
+                &p_texture,
+                &p_texture_view,
+            )
+        };
+
+        let desc = build(&[mip_1], None).unwrap();
+        assert_eq!((desc.width, desc.height, desc.layers), (512, 256, 1));
+        assert_eq!(
+            build(&[mip_0, mip_1], None).unwrap_err().to_string(),
+            "Color attachment 1 is 512x256 with 1 layers but the first attachment is 1024x512 with 1 layers."
+        );
+
+        let half = FramebufferExtent { width: 256, height: 128, layers: 1 };
+        let desc = build(&[mip_1], Some(half)).unwrap();
+        assert_eq!((desc.width, desc.height, desc.layers), (256, 128, 1));
+        assert_eq!(
+            build(&[mip_1], Some(FramebufferExtent { width: 1024, height: 512, layers: 1 }))
+                .unwrap_err()
+                .to_string(),
+            "Framebuffer extent 1024x512 with 1 layers is larger than its attachments, which are 512x256 with 1 layers."
+        );
+    }
+
+    #[test]
+    fn resolve_views_are_single_sampled() {
+        let mut p_texture = Pool::<VulkanImage>::with_size(8, Default::default);
+        let mut p_texture_view = Pool::<VulkanImageView>::with_size(8, Default::default);
+        let color = vk::ImageAspectFlags::COLOR;
+        let mut add_target = |samples, format| {
+            let view = add_view(&mut p_texture, &mut p_texture_view, 800, 600, color);
+            let item = p_texture_view.get_mut(view).unwrap();
+            item.desc.format = format;
+            let texture = item.texture.unwrap();
+            p_texture.get_mut(texture).unwrap().desc.samples = samples;
+            view
+        };
+        let msaa = add_target(vk::SampleCountFlags::TYPE_4, vk::Format::R8G8B8A8_UNORM);
+        let resolve = add_target(vk::SampleCountFlags::TYPE_1, vk::Format::R8G8B8A8_UNORM);
+        let srgb = add_target(vk::SampleCountFlags::TYPE_1, vk::Format::R8G8B8A8_SRGB);
+
+        let mut output =
+            VulkanRenderPassOutput { num_colors: 1, resolve_mask: 1, ..Default::default() };
+        output.color_formats[0] = vk::Format::R8G8B8A8_UNORM;
+        output.color_samples[0] = vk::SampleCountFlags::TYPE_4;
+        output.resolve_formats[0] = vk::Format::R8G8B8A8_UNORM;
+
+        let build = |resolve_views: &[Handle<ImageView>]| {
+            VulkanFramebufferDesc::from_create_desc(
+                vk::RenderPass::null(),
+                &output,
+                &FramebufferCreateDesc {
+                    render_pass: Handle::default(),
+                    color_views: &[msaa],
+                    depth_stencil_view: None,
+                    resolve_views,
+                    extent: None,
+                },
+                &p_texture,
+                &p_texture_view,
+            )
+        };
+
+        let desc = build(&[resolve]).unwrap();
+        assert_eq!(desc.num_attachments, 2);
+        assert_eq!(
+            build(&[]).unwrap_err().to_string(),
+            "Framebuffer has 0 resolve views but the render pass resolves 1 colors."
+        );
+        assert_eq!(
+            build(&[msaa]).unwrap_err().to_string(),
+            "Resolve attachment 0 has TYPE_4 samples, it needs a single sample."
+        );
+        assert_eq!(
+            build(&[srgb]).unwrap_err().to_string(),
+            "Resolve attachment 0 is R8G8B8A8_SRGB but the render pass resolves into R8G8B8A8_UNORM."
+        );
+    }
+}
@@ -1,13 +1,20 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use ash::vk;
 use gpu_allocator::vulkan::*;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Handled, Pool};
 use std::collections::HashMap;
 
-use crate::define::*;
+use crate::{
+    define::*,
+    enums::{SamplerAddressMode, SamplerMipmapMode},
+    flag::RequestedFeatures,
+};
 
-use super::device::VulkanDevice;
+use super::{
+    device::VulkanDevice,
+    util::{format_size_class, is_compressed_format},
+};
 
 #[derive(Default, Clone, Copy)]
 pub struct VulkanImageDesc {
@@ -19,6 +26,7 @@ pub struct VulkanImageDesc {
     pub array_layers: u32,
     pub tiling: vk::ImageTiling,
     pub usage: vk::ImageUsageFlags,
+    pub flags: vk::ImageCreateFlags,
 }
 
 #[derive(Default)]
@@ -38,32 +46,8 @@ impl VulkanImage {
         device: &mut VulkanDevice,
         desc: &ImageCreateDesc,
     ) -> anyhow::Result<()> {
-        let image_desc = VulkanImageDesc {
-            image_type: desc.type_.into(),
-            format: desc.format.into(),
-            extent: vk::Extent3D {
-                width: desc.extent[0],
-                height: desc.extent[1],
-                depth: desc.extent[2],
-            },
-            samples: desc.samples.into(),
-            mip_levels: desc.mip_levels,
-            array_layers: desc.array_layers,
-            tiling: desc.tiling.into(),
-            usage: desc.usage.into(),
-        };
-        let image_info = vk::ImageCreateInfo::builder()
-            .image_type(image_desc.image_type)
-            .extent(image_desc.extent)
-            .mip_levels(image_desc.mip_levels)
-            .array_layers(image_desc.array_layers)
-            .format(image_desc.format)
-            .tiling(image_desc.tiling)
-            .samples(image_desc.samples)
-            .usage(image_desc.usage)
-            .initial_layout(desc.initial_layout.into())
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .flags(vk::ImageCreateFlags::empty());
+        let (image_desc, image_info) = image_create_info(desc);
+        check_image_format(&image_desc).with_context(|| format!("Image {}", desc.name))?;
         let raw = unsafe { device.raw().create_image(&image_info, None)? };
 
         let requirements = unsafe { device.raw().get_image_memory_requirements(raw) };
@@ -107,14 +91,27 @@ impl VulkanImage {
         device: &VulkanDevice,
         desc: &VulkanImageViewDesc,
         p_image_view: &mut Pool<VulkanImageView>,
+    ) -> anyhow::Result<Handle<ImageView>> {
+        self.get_or_insert_view(desc, |image| {
+            let item = p_image_view.malloc();
+            item.1.init(device, image, desc)?;
+            Ok(item.0)
+        })
+    }
+
+    /// Views are cached by their whole description, so views of different mips, layers or
+    /// swizzles of the image are kept apart.
+    fn get_or_insert_view(
+        &mut self,
+        desc: &VulkanImageViewDesc,
+        create: impl FnOnce(&Self) -> anyhow::Result<Handle<ImageView>>,
     ) -> anyhow::Result<Handle<ImageView>> {
         if let Some(handle) = self.views.get(desc) {
             return Ok(*handle);
         }
-        let item = p_image_view.malloc();
-        item.1.init(device, self, desc)?;
-        self.views.insert(*desc, item.0);
-        Ok(item.0)
+        let handle = create(self)?;
+        self.views.insert(*desc, handle);
+        Ok(handle)
     }
 }
 
@@ -139,7 +136,7 @@ impl VulkanImageViewDesc {
         Self {
             view_type: desc.view_type.into(),
             aspect_mask: desc.aspect_mask.into(),
-            format: format,
+            format,
             component_r: desc.component_r.into(),
             component_g: desc.component_g.into(),
             component_b: desc.component_b.into(),
@@ -209,38 +206,149 @@ impl VulkanImageView {
 pub struct VulkanSampler {
     pub handle: Option<Handle<Sampler>>,
     pub raw: vk::Sampler,
+    pub compare_enable: bool,
+    pub desc: Option<SamplerCreateDesc>,
+    /// Number of `create_sampler` calls that got this sampler and weren't destroyed yet.
+    pub ref_count: u32,
 }
 impl_handle!(VulkanSampler, Sampler, handle);
 
+/// Hands out one sampler per description, `maxSamplerAllocationCount` is easily reached when
+/// every texture makes its own.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerCreateDesc, Handle<Sampler>>,
+}
+
+impl SamplerCache {
+    /// Returns the sampler made for `desc`, calling `create` to make it the first time.
+    pub fn acquire(
+        &mut self,
+        desc: &SamplerCreateDesc,
+        p_sampler: &mut Pool<VulkanSampler>,
+        create: impl FnOnce(&mut VulkanSampler) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Handle<Sampler>> {
+        if let Some(handle) = self.samplers.get(desc) {
+            if let Some(sampler) = p_sampler.get_mut(*handle) {
+                sampler.ref_count += 1;
+                return Ok(*handle);
+            }
+        }
+
+        let (handle, sampler) = p_sampler.malloc();
+        if let Err(err) = create(sampler) {
+            p_sampler.free(handle);
+            return Err(err);
+        }
+        sampler.desc = Some(*desc);
+        sampler.ref_count = 1;
+        self.samplers.insert(*desc, handle);
+        Ok(handle)
+    }
+
+    /// Drops one reference, `destroy` is called once the last one is gone.
+    pub fn release(
+        &mut self,
+        handle: Handle<Sampler>,
+        p_sampler: &mut Pool<VulkanSampler>,
+        destroy: impl FnOnce(&mut VulkanSampler),
+    ) {
+        if let Some(sampler) = p_sampler.get_mut(handle) {
+            sampler.ref_count = sampler.ref_count.saturating_sub(1);
+            if sampler.ref_count > 0 {
+                return;
+            }
+            if let Some(desc) = sampler.desc.take() {
+                self.samplers.remove(&desc);
+            }
+            destroy(sampler);
+            p_sampler.free(handle);
+        }
+    }
+}
+
+/// Vulkan only allows unnormalized coordinates on simple lookups of the first mip level.
+fn check_sampler(desc: &SamplerCreateDesc) -> anyhow::Result<()> {
+    if desc.min_lod > desc.max_lod {
+        anyhow::bail!("Sampler min_lod {} is above max_lod {}.", desc.min_lod, desc.max_lod);
+    }
+    if !desc.unnormalized_coordinates {
+        return Ok(());
+    }
+    if desc.min_filter != desc.mag_filter {
+        anyhow::bail!("Unnormalized coordinates need the same min and mag filter.");
+    }
+    if desc.mipmap_mode != SamplerMipmapMode::Nearest || desc.min_lod != 0.0 || desc.max_lod != 0.0
+    {
+        anyhow::bail!(
+            "Unnormalized coordinates need the Nearest mipmap mode and min_lod and max_lod of 0."
+        );
+    }
+    if [desc.address_mode_u, desc.address_mode_v].iter().any(|mode| {
+        !matches!(mode, SamplerAddressMode::ClampToEdge | SamplerAddressMode::ClampToBorder)
+    }) {
+        anyhow::bail!("Unnormalized coordinates need ClampToEdge or ClampToBorder in u and v.");
+    }
+    if desc.max_anisotropy.is_some() || desc.compare_op.is_some() {
+        anyhow::bail!("Unnormalized coordinates can't be used with anisotropy or compare.");
+    }
+    Ok(())
+}
+
+/// The anisotropy the sampler is made with, clamped to `1.0..=limit`. Doesn't fall back to plain
+/// filtering when the feature wasn't enabled, that would silently blur at grazing angles.
+fn sampler_anisotropy(requested: Option<f32>, enabled: bool, limit: f32) -> Result<Option<f32>> {
+    let Some(max_anisotropy) = requested else {
+        return Ok(None);
+    };
+    if !enabled {
+        anyhow::bail!(
+            "Anisotropic filtering needs `RequestedFeatures::SAMPLER_ANISOTROPY`, request it in `DeviceCreateDesc::requested_features`."
+        );
+    }
+    if !max_anisotropy.is_finite() {
+        anyhow::bail!("Sampler max_anisotropy {} isn't a finite number.", max_anisotropy);
+    }
+    Ok(Some(max_anisotropy.clamp(1.0, limit.max(1.0))))
+}
+
 impl VulkanSampler {
     pub fn init(&mut self, device: &VulkanDevice, desc: &SamplerCreateDesc) -> anyhow::Result<()> {
+        check_sampler(desc)?;
+
         let mut compare_op = vk::CompareOp::ALWAYS;
         if let Some(op) = desc.compare_op {
             compare_op = op.into();
         }
 
-        let device_max_anisotropy = device.get_adapter().properties.limits.max_sampler_anisotropy;
-        let mut max_anisotropy = desc.max_anisotropy.unwrap_or(1.0);
-        max_anisotropy = max_anisotropy.min(device_max_anisotropy);
+        let max_anisotropy = sampler_anisotropy(
+            desc.max_anisotropy,
+            device
+                .enabled_features()
+                .requested_features
+                .contains(RequestedFeatures::SAMPLER_ANISOTROPY),
+            device.get_adapter().properties.limits.max_sampler_anisotropy,
+        )?;
 
         let sampler_info = vk::SamplerCreateInfo::builder()
             .mag_filter(desc.mag_filter.into())
             .min_filter(desc.min_filter.into())
             .mipmap_mode(desc.mipmap_mode.into())
-            .min_lod(0.0)
-            .max_lod(0.0)
+            .min_lod(desc.min_lod)
+            .max_lod(desc.max_lod)
             .mip_lod_bias(desc.mip_lod_bias)
             .address_mode_u(desc.address_mode_u.into())
             .address_mode_v(desc.address_mode_v.into())
             .address_mode_w(desc.address_mode_w.into())
-            .anisotropy_enable(desc.max_anisotropy.is_some())
-            .max_anisotropy(max_anisotropy)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
             .compare_enable(desc.compare_op.is_some())
             .compare_op(compare_op)
-            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-            .unnormalized_coordinates(false);
+            .border_color(desc.border_color.into())
+            .unnormalized_coordinates(desc.unnormalized_coordinates);
 
         self.raw = unsafe { device.raw().create_sampler(&sampler_info, None)? };
+        self.compare_enable = desc.compare_op.is_some();
         Ok(())
     }
 
@@ -249,5 +357,338 @@ impl VulkanSampler {
             device.raw().destroy_sampler(self.raw, None);
         }
         self.raw = vk::Sampler::null();
+        self.compare_enable = false;
+    }
+}
+
+fn image_create_info(desc: &ImageCreateDesc) -> (VulkanImageDesc, vk::ImageCreateInfo) {
+    let image_desc = VulkanImageDesc {
+        image_type: desc.type_.into(),
+        format: desc.format.into(),
+        extent: vk::Extent3D {
+            width: desc.extent[0],
+            height: desc.extent[1],
+            depth: desc.extent[2],
+        },
+        samples: desc.samples.into(),
+        mip_levels: desc.mip_levels,
+        array_layers: desc.array_layers,
+        tiling: desc.tiling.into(),
+        usage: desc.usage.into(),
+        flags: if desc.mutable_format {
+            vk::ImageCreateFlags::MUTABLE_FORMAT
+        } else {
+            vk::ImageCreateFlags::empty()
+        },
+    };
+    let image_info = vk::ImageCreateInfo::builder()
+        .image_type(image_desc.image_type)
+        .extent(image_desc.extent)
+        .mip_levels(image_desc.mip_levels)
+        .array_layers(image_desc.array_layers)
+        .format(image_desc.format)
+        .tiling(image_desc.tiling)
+        .samples(image_desc.samples)
+        .usage(image_desc.usage)
+        .initial_layout(desc.initial_layout.into())
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .flags(image_desc.flags)
+        .build();
+    (image_desc, image_info)
+}
+
+/// Fails on `Format::Unknown` instead of handing Vulkan an undefined format, and on compressed
+/// images that are used for anything but sampling and copies.
+fn check_image_format(image: &VulkanImageDesc) -> Result<()> {
+    if image.format == vk::Format::UNDEFINED {
+        anyhow::bail!("Images need a format, got `Format::Unknown`.");
+    }
+    let unsupported = vk::ImageUsageFlags::COLOR_ATTACHMENT
+        | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        | vk::ImageUsageFlags::STORAGE
+        | vk::ImageUsageFlags::INPUT_ATTACHMENT;
+    if is_compressed_format(image.format) && image.usage.intersects(unsupported) {
+        anyhow::bail!(
+            "Compressed {:?} images can only be sampled and copied, got usage {:?}.",
+            image.format,
+            image.usage
+        );
+    }
+    Ok(())
+}
+
+/// A view may only use another format than its image when the image was created with
+/// `mutable_format` and both formats are in the same size class.
+pub fn check_view_format(image: &VulkanImageDesc, view_format: vk::Format) -> Result<()> {
+    if view_format == vk::Format::UNDEFINED {
+        anyhow::bail!("Image views need a format, got `Format::Unknown`.");
+    }
+    if view_format == image.format {
+        return Ok(());
+    }
+    if !image.flags.contains(vk::ImageCreateFlags::MUTABLE_FORMAT) {
+        anyhow::bail!(
+            "A {:?} view of a {:?} image needs the image to be created with `mutable_format`.",
+            view_format,
+            image.format
+        );
+    }
+    match (format_size_class(image.format), format_size_class(view_format)) {
+        (Some(image_class), Some(view_class)) if image_class == view_class => Ok(()),
+        _ => anyhow::bail!(
+            "A {:?} image can't be viewed as {:?}, the formats are not in the same class.",
+            image.format,
+            view_format
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{
+        BorderColor, FilterType, Format, ImageLayout, ImageTiling, ImageType, SampleCount,
+        SamplerAddressMode, SamplerMipmapMode, TextureComponentSwizzle,
+    };
+    use crate::flag::{ImageAspectFlags, ImageUsageFlags};
+
+    #[test]
+    fn image_builder_create_info() {
+        let desc = ImageCreateDesc::builder("target", Format::R8G8B8A8_UNORM, [512, 256, 1])
+            .usage(
+                ImageUsageFlags::SAMPLED
+                    | ImageUsageFlags::COLOR_ATTACHMENT
+                    | ImageUsageFlags::STORAGE,
+            )
+            .mip_levels(4)
+            .samples(SampleCount::Sample1)
+            .build();
+        let (_, info) = image_create_info(&desc);
+        assert_eq!(info.image_type, vk::ImageType::TYPE_2D);
+        assert_eq!(info.format, vk::Format::R8G8B8A8_UNORM);
+        assert_eq!((info.extent.width, info.extent.height, info.extent.depth), (512, 256, 1));
+        assert_eq!(
+            info.usage,
+            vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::STORAGE
+        );
+        assert_eq!((info.mip_levels, info.array_layers), (4, 1));
+        assert_eq!(info.samples, vk::SampleCountFlags::TYPE_1);
+        assert_eq!(info.tiling, vk::ImageTiling::OPTIMAL);
+        assert_eq!(info.initial_layout, vk::ImageLayout::UNDEFINED);
+
+        let desc = ImageCreateDesc::builder("volume", Format::R32_SFLOAT, [32, 32, 32])
+            .image_type(ImageType::Texture3D)
+            .tiling(ImageTiling::Linear)
+            .initial_layout(ImageLayout::General)
+            .array_layers(1)
+            .build();
+        let (image_desc, info) = image_create_info(&desc);
+        assert_eq!(info.image_type, vk::ImageType::TYPE_3D);
+        assert_eq!(image_desc.extent.depth, 32);
+        assert_eq!(info.tiling, vk::ImageTiling::LINEAR);
+        assert_eq!(info.initial_layout, vk::ImageLayout::GENERAL);
+    }
+
+    #[test]
+    fn identical_samplers_are_shared() {
+        let mut p_sampler = Pool::<VulkanSampler>::with_size(4, Default::default);
+        let mut cache = SamplerCache::default();
+        let desc = SamplerCreateDesc {
+            min_filter: FilterType::Linear,
+            mag_filter: FilterType::Linear,
+            mipmap_mode: SamplerMipmapMode::Linear,
+            address_mode_u: SamplerAddressMode::Repeat,
+            address_mode_v: SamplerAddressMode::Repeat,
+            address_mode_w: SamplerAddressMode::Repeat,
+            mip_lod_bias: 0.0,
+            compare_op: None,
+            max_anisotropy: Some(16.0),
+            border_color: BorderColor::FloatTransparentBlack,
+            min_lod: 0.0,
+            max_lod: LOD_CLAMP_NONE,
+            unnormalized_coordinates: false,
+        };
+
+        let mut created = 0;
+        let handles: Vec<_> = (0..1000)
+            .map(|_| {
+                cache
+                    .acquire(&desc, &mut p_sampler, |_| {
+                        created += 1;
+                        Ok(())
+                    })
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(created, 1);
+        assert!(handles.iter().all(|h| *h == handles[0]));
+
+        let nearest = SamplerCreateDesc { min_filter: FilterType::Nearest, ..desc };
+        let other = cache.acquire(&nearest, &mut p_sampler, |_| Ok(())).unwrap();
+        assert_ne!(other, handles[0]);
+
+        let mut destroyed = 0;
+        for handle in handles {
+            cache.release(handle, &mut p_sampler, |_| destroyed += 1);
+        }
+        assert_eq!(destroyed, 1);
+        assert!(p_sampler.get(other).is_some());
+
+        // Released for good, the next request makes a new one
+        cache.acquire(&desc, &mut p_sampler, |_| Ok(())).unwrap();
+        assert_eq!(cache.samplers.len(), 2);
+    }
+
+    #[test]
+    fn unnormalized_samplers_are_checked() {
+        let desc = SamplerCreateDesc {
+            min_filter: FilterType::Nearest,
+            mag_filter: FilterType::Nearest,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            address_mode_u: SamplerAddressMode::ClampToBorder,
+            address_mode_v: SamplerAddressMode::ClampToBorder,
+            address_mode_w: SamplerAddressMode::ClampToBorder,
+            mip_lod_bias: 0.0,
+            compare_op: None,
+            max_anisotropy: None,
+            border_color: BorderColor::FloatOpaqueWhite,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            unnormalized_coordinates: true,
+        };
+        check_sampler(&desc).unwrap();
+
+        let repeat = SamplerCreateDesc { address_mode_v: SamplerAddressMode::Repeat, ..desc };
+        assert!(check_sampler(&repeat).is_err());
+        let mipmapped = SamplerCreateDesc { max_lod: LOD_CLAMP_NONE, ..desc };
+        assert!(check_sampler(&mipmapped).is_err());
+        // Normalized, the whole mip chain is fine
+        check_sampler(&SamplerCreateDesc { unnormalized_coordinates: false, ..mipmapped }).unwrap();
+        let inverted = SamplerCreateDesc { min_lod: 2.0, max_lod: 1.0, ..mipmapped };
+        assert_eq!(
+            check_sampler(&inverted).err().unwrap().to_string(),
+            "Sampler min_lod 2 is above max_lod 1."
+        );
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_the_limit() {
+        assert_eq!(sampler_anisotropy(None, false, 16.0).unwrap(), None);
+        assert_eq!(sampler_anisotropy(Some(64.0), true, 16.0).unwrap(), Some(16.0));
+        assert_eq!(sampler_anisotropy(Some(0.0), true, 16.0).unwrap(), Some(1.0));
+        assert_eq!(sampler_anisotropy(Some(8.0), true, 16.0).unwrap(), Some(8.0));
+        assert!(sampler_anisotropy(Some(f32::NAN), true, 16.0).is_err());
+        let err = sampler_anisotropy(Some(16.0), false, 16.0).unwrap_err();
+        assert!(err.to_string().contains("RequestedFeatures::SAMPLER_ANISOTROPY"));
+    }
+
+    #[test]
+    fn views_of_different_mips_are_cached_apart() {
+        let mut p_image_view = Pool::<VulkanImageView>::with_size(4, Default::default);
+        let mut image = VulkanImage {
+            desc: VulkanImageDesc {
+                format: vk::Format::R8G8B8A8_UNORM,
+                mip_levels: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut view = |image: &mut VulkanImage, desc: ImageViewCreateDesc| {
+            let desc = VulkanImageViewDesc::from_create_desc(&desc, image);
+            image
+                .get_or_insert_view(&desc, |_| {
+                    let item = p_image_view.malloc();
+                    item.1.desc = desc;
+                    Ok(item.0)
+                })
+                .unwrap()
+        };
+
+        let mip0 = ImageViewCreateDesc::new_2d(None, ImageAspectFlags::COLOR);
+        let mip1 = ImageViewCreateDesc { base_mip_level: 1, ..mip0 };
+        let swizzled = ImageViewCreateDesc { component_r: TextureComponentSwizzle::One, ..mip0 };
+        let mip0_view = view(&mut image, mip0);
+        let mip1_view = view(&mut image, mip1);
+        let swizzled_view = view(&mut image, swizzled);
+        assert_ne!(mip0_view, mip1_view);
+        assert_ne!(mip0_view, swizzled_view);
+
+        // Identical requests reuse the cached views
+        assert_eq!(view(&mut image, mip0), mip0_view);
+        assert_eq!(view(&mut image, mip1), mip1_view);
+        assert_eq!(image.views.len(), 3);
+        assert_eq!(p_image_view.len(), 3);
+        assert_eq!(p_image_view.get(mip1_view).unwrap().desc.base_mip_level, 1);
+    }
+
+    #[test]
+    fn srgb_view_of_unorm_image() {
+        let desc = ImageCreateDesc::builder("albedo", Format::R8G8B8A8_UNORM, [256, 256, 1])
+            .mutable_format(true)
+            .build();
+        let (image_desc, info) = image_create_info(&desc);
+        assert_eq!(info.flags, vk::ImageCreateFlags::MUTABLE_FORMAT);
+        let image = VulkanImage { desc: image_desc, ..Default::default() };
+
+        let srgb =
+            ImageViewCreateDesc::new_2d(Some(Format::R8G8B8A8_SRGB), ImageAspectFlags::COLOR);
+        let view = VulkanImageViewDesc::from_create_desc(&srgb, &image);
+        assert_eq!(view.format, vk::Format::R8G8B8A8_SRGB);
+        assert!(check_view_format(&image.desc, view.format).is_ok());
+        assert!(check_view_format(&image.desc, vk::Format::B8G8R8A8_SRGB).is_ok());
+        assert_eq!(
+            check_view_format(&image.desc, vk::Format::R32G32_SFLOAT).unwrap_err().to_string(),
+            "A R8G8B8A8_UNORM image can't be viewed as R32G32_SFLOAT, the formats are not in the same class."
+        );
+
+        // Without `mutable_format` only the image's own format works
+        let (fixed, info) = image_create_info(&ImageCreateDesc { mutable_format: false, ..desc });
+        assert!(info.flags.is_empty());
+        assert!(check_view_format(&fixed, vk::Format::R8G8B8A8_UNORM).is_ok());
+        assert_eq!(
+            check_view_format(&fixed, vk::Format::R8G8B8A8_SRGB).unwrap_err().to_string(),
+            "A R8G8B8A8_SRGB view of a R8G8B8A8_UNORM image needs the image to be created with `mutable_format`."
+        );
+
+        // Depth formats are only compatible with themselves
+        let depth = VulkanImageDesc {
+            format: vk::Format::D32_SFLOAT,
+            flags: vk::ImageCreateFlags::MUTABLE_FORMAT,
+            ..Default::default()
+        };
+        assert!(check_view_format(&depth, vk::Format::R32_SFLOAT).is_err());
+    }
+
+    #[test]
+    fn unknown_and_compressed_formats_fail_loudly() {
+        let (unknown, _) = image_create_info(
+            &ImageCreateDesc::builder("unknown", Format::Unknown, [64, 64, 1]).build(),
+        );
+        assert_eq!(
+            check_image_format(&unknown).unwrap_err().to_string(),
+            "Images need a format, got `Format::Unknown`."
+        );
+
+        let texture = ImageCreateDesc::builder("albedo", Format::BC7_SRGB, [64, 64, 1])
+            .usage(ImageUsageFlags::SAMPLED | ImageUsageFlags::TRANSFER_DST)
+            .build();
+        let (compressed, _) = image_create_info(&texture);
+        assert_eq!(compressed.format, vk::Format::BC7_SRGB_BLOCK);
+        check_image_format(&compressed).unwrap();
+        let (target, _) = image_create_info(&ImageCreateDesc {
+            usage: ImageUsageFlags::COLOR_ATTACHMENT,
+            ..texture
+        });
+        assert!(check_image_format(&target).is_err());
+
+        let (hdr, _) = image_create_info(&ImageCreateDesc {
+            format: Format::R16G16B16A16_SFLOAT,
+            usage: ImageUsageFlags::COLOR_ATTACHMENT,
+            ..texture
+        });
+        check_image_format(&hdr).unwrap();
+        assert!(check_view_format(&hdr, vk::Format::UNDEFINED).is_err());
     }
 }
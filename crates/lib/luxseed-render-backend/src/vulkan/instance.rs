@@ -1,4 +1,6 @@
-use crate::define::RenderBackendCreateDesc;
+use super::util::has_extension;
+use crate::define::{DebugCallback, RenderBackendCreateDesc};
+use anyhow::bail;
 use ash::{extensions::ext::DebugUtils, vk};
 use log::error;
 use raw_window_handle::RawDisplayHandle;
@@ -10,19 +12,41 @@ use std::{
 pub struct VulkanInstance {
     pub entry: ash::Entry,
     pub raw: ash::Instance,
+    pub api_version: u32,
     pub enable_debugging: bool,
     pub debug_utils: Option<DebugUtils>,
     pub debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
+    // Boxed again so the messenger can hold a thin pointer to it
+    pub debug_callback: Option<Box<DebugCallback>>,
 }
 
 impl VulkanInstance {
     pub fn new(creation: RenderBackendCreateDesc) -> anyhow::Result<Self> {
         let entry = ash::Entry::linked();
 
-        let mut enable_debugging = creation.enable_debugging;
-        if enable_debugging && !VulkanInstance::is_validation_layer_support(&entry)? {
-            enable_debugging = false;
+        let enable_debugging = creation.enable_debugging
+            && select_debugging(
+                VulkanInstance::is_validation_layer_support(&entry)?,
+                creation.debug_callback.is_some(),
+            )?;
+
+        let (major, minor, patch) = creation.min_api_version;
+        let api_version = instance_api_version(vk::make_api_version(0, major, minor, patch));
+        // Vulkan 1.0 loaders don't have vkEnumerateInstanceVersion
+        let loader_version = entry.try_enumerate_instance_version()?.unwrap_or(vk::API_VERSION_1_0);
+        check_api_version(api_version, loader_version)?;
+
+        let available_extensions = entry.enumerate_instance_extension_properties(None)?;
+        let missing =
+            missing_extensions(&available_extensions, creation.additional_instance_extensions);
+        if !missing.is_empty() {
+            bail!("Vulkan instance extensions not available: {}", missing.join(", "));
         }
+        let additional_extensions = creation
+            .additional_instance_extensions
+            .iter()
+            .map(|name| CString::new(*name))
+            .collect::<Result<Vec<_>, _>>()?;
 
         let app_name = CString::new(creation.app_name)?;
         let engine_name = CString::new("Luxseed Engine")?;
@@ -31,12 +55,17 @@ impl VulkanInstance {
             .application_version(creation.app_version)
             .engine_name(&engine_name)
             .engine_version(vk::make_api_version(0, 1, 0, 0))
-            .api_version(vk::API_VERSION_1_2)
+            .api_version(api_version)
             .build();
 
         let layer_names = VulkanInstance::get_layer_names(enable_debugging);
-        let extension_names =
+        let mut extension_names =
             VulkanInstance::get_extension_names(creation.raw_display_handle, enable_debugging)?;
+        for name in additional_extensions.iter() {
+            if !extension_names.iter().any(|n| unsafe { CStr::from_ptr(*n) } == name.as_c_str()) {
+                extension_names.push(name.as_ptr());
+            }
+        }
 
         let create_flags = if cfg!(any(target_os = "macos", target_os = "ios")) {
             vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
@@ -55,18 +84,33 @@ impl VulkanInstance {
         let instance = unsafe { entry.create_instance(&instance_create_info, None)? };
 
         // Create Debug Utils Messenger
+        let debug_callback = creation.debug_callback.map(Box::new);
         let (debug_utils, debug_messenger) = if enable_debugging {
             let debug_utils = DebugUtils::new(&entry, &instance);
-            let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-                .message_severity(
+            // A user callback does its own filtering, so it gets every message
+            let (message_severity, user_data) = match &debug_callback {
+                Some(callback) => (
+                    vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                        | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                        | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                        | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+                    &**callback as *const DebugCallback as *mut std::os::raw::c_void,
+                ),
+                None => (
                     vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
                         | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
-                )
+                    std::ptr::null_mut(),
+                ),
+            };
+            let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+                .message_severity(message_severity)
                 .message_type(
-                    vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                        | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
                         | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
                 )
                 .pfn_user_callback(Some(debug_utils_callback))
+                .user_data(user_data)
                 .build();
             let debug_messenger =
                 unsafe { debug_utils.create_debug_utils_messenger(&debug_info, None) }?;
@@ -78,9 +122,11 @@ impl VulkanInstance {
         Ok(Self {
             entry,
             raw: instance,
+            api_version,
             enable_debugging,
             debug_utils,
             debug_messenger,
+            debug_callback,
         })
     }
 
@@ -154,11 +200,54 @@ impl Drop for VulkanInstance {
     }
 }
 
+fn format_api_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+/// The backend relies on Vulkan 1.2 core features, lower requested versions are raised to it.
+const MIN_API_VERSION: u32 = vk::API_VERSION_1_2;
+
+fn instance_api_version(requested: u32) -> u32 {
+    requested.max(MIN_API_VERSION)
+}
+
+/// Debugging is skipped without the validation layer, unless the app asked for its messages.
+fn select_debugging(validation_available: bool, has_callback: bool) -> anyhow::Result<bool> {
+    if !validation_available && has_callback {
+        bail!("A debug callback was given but the validation layer is not available");
+    }
+    Ok(validation_available)
+}
+
+fn check_api_version(requested: u32, supported: u32) -> anyhow::Result<()> {
+    // The variant bits are always 0 for Vulkan, so plain comparison orders versions
+    if requested > supported {
+        bail!(
+            "Vulkan {} requested but the loader only supports Vulkan {}",
+            format_api_version(requested),
+            format_api_version(supported)
+        );
+    }
+    Ok(())
+}
+
+fn missing_extensions<'a>(
+    available: &[vk::ExtensionProperties],
+    requested: &[&'a str],
+) -> Vec<&'a str> {
+    requested.iter().filter(|name| !has_extension(available, name.as_bytes())).copied().collect()
+}
+
 unsafe extern "system" fn debug_utils_callback(
-    _severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    _types: vk::DebugUtilsMessageTypeFlagsEXT,
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    types: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _user_data: *mut std::os::raw::c_void,
+    user_data: *mut std::os::raw::c_void,
 ) -> vk::Bool32 {
     let d = *p_callback_data;
 
@@ -174,7 +263,113 @@ unsafe extern "system" fn debug_utils_callback(
         CStr::from_ptr(d.p_message).to_string_lossy()
     };
 
+    if let Some(callback) = (user_data as *const DebugCallback).as_ref() {
+        callback(severity.into(), types.into(), &message);
+        return vk::FALSE;
+    }
+
     println!("MessageID: {message_id_name} {message_id_number}\nMessage: {message}\n\n");
 
     vk::FALSE
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enums::{MessageType, Severity};
+    use std::sync::{Arc, Mutex};
+
+    // Calls the messenger's callback directly, there is no validation layer to trigger it
+    #[test]
+    fn debug_callback_receives_messages() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let callback: DebugCallback = Box::new(move |severity, ty, message| {
+            sink.lock().unwrap().push((severity, ty, message.to_owned()));
+        });
+        let callback = Box::new(callback);
+
+        let message = CString::new("vkCmdDraw: no pipeline bound").unwrap();
+        let data = vk::DebugUtilsMessengerCallbackDataEXT::builder().message(&message).build();
+        // Every type the messenger is created for reaches the callback as its own variant
+        for ty in [
+            vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+        ] {
+            let ret = unsafe {
+                debug_utils_callback(
+                    vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+                    ty,
+                    &data,
+                    &*callback as *const DebugCallback as *mut std::os::raw::c_void,
+                )
+            };
+            assert_eq!(ret, vk::FALSE);
+        }
+
+        let received = received.lock().unwrap();
+        let message = "vkCmdDraw: no pipeline bound".to_owned();
+        assert_eq!(
+            *received,
+            vec![
+                (Severity::Error, MessageType::General, message.clone()),
+                (Severity::Error, MessageType::Validation, message.clone()),
+                (Severity::Error, MessageType::Performance, message),
+            ]
+        );
+    }
+
+    #[test]
+    fn debug_callback_needs_validation() {
+        assert!(select_debugging(true, true).unwrap());
+        assert!(select_debugging(true, false).unwrap());
+        assert!(!select_debugging(false, false).unwrap());
+        assert_eq!(
+            select_debugging(false, true).unwrap_err().to_string(),
+            "A debug callback was given but the validation layer is not available"
+        );
+    }
+
+    #[test]
+    fn api_version_check() {
+        let v1_3 = vk::make_api_version(0, 1, 3, 0);
+        assert!(check_api_version(v1_3, vk::make_api_version(0, 1, 3, 250)).is_ok());
+        assert!(check_api_version(vk::API_VERSION_1_2, v1_3).is_ok());
+
+        let err = check_api_version(v1_3, vk::API_VERSION_1_2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Vulkan 1.3.0 requested but the loader only supports Vulkan 1.2.0"
+        );
+    }
+
+    #[test]
+    fn api_version_is_at_least_1_2() {
+        assert_eq!(instance_api_version(vk::API_VERSION_1_0), vk::API_VERSION_1_2);
+        assert_eq!(instance_api_version(vk::API_VERSION_1_1), vk::API_VERSION_1_2);
+        let v1_3 = vk::make_api_version(0, 1, 3, 0);
+        assert_eq!(instance_api_version(v1_3), v1_3);
+        // A loader without 1.2 can't run the backend at all
+        let err = check_api_version(instance_api_version(0), vk::API_VERSION_1_1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Vulkan 1.2.0 requested but the loader only supports Vulkan 1.1.0"
+        );
+    }
+
+    #[test]
+    fn missing_instance_extensions() {
+        let mut surface = vk::ExtensionProperties::default();
+        for (dst, src) in surface.extension_name.iter_mut().zip(b"VK_KHR_surface") {
+            *dst = *src as c_char;
+        }
+        let available = [surface];
+
+        assert!(missing_extensions(&available, &["VK_KHR_surface"]).is_empty());
+        assert_eq!(
+            missing_extensions(&available, &["VK_KHR_surface", "VK_EXT_swapchain_colorspace"]),
+            vec!["VK_EXT_swapchain_colorspace"]
+        );
+    }
+}
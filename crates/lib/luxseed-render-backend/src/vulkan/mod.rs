@@ -1,3 +1,4 @@
+pub mod acceleration_structure;
 pub mod buffer;
 pub mod command;
 pub mod descriptor;
@@ -6,7 +7,9 @@ pub mod framebuffer;
 pub mod image;
 pub mod instance;
 pub mod pipeline;
+pub mod reflection;
 pub mod render_pass;
+pub mod rendering;
 pub mod shader;
 pub mod surface;
 pub mod swapchain;
@@ -18,6 +21,7 @@ use anyhow::Result;
 use luxseed_utility::define_resource_pool;
 use luxseed_utility::pool::*;
 use smallvec::SmallVec;
+use std::cell::Cell;
 use std::ffi::CString;
 
 use crate::define::*;
@@ -25,6 +29,7 @@ use crate::enums::*;
 use crate::flag::*;
 use crate::{RenderBackend, RenderBackendCreateDesc};
 
+use self::acceleration_structure::VulkanAccelerationStructure;
 use self::buffer::*;
 use self::command::*;
 use self::descriptor::*;
@@ -33,6 +38,7 @@ use self::framebuffer::*;
 use self::image::*;
 use self::pipeline::*;
 use self::render_pass::VulkanRenderPass;
+use self::rendering::VulkanRenderingAttachments;
 use self::shader::VulkanShader;
 use self::surface::VulkanSurface;
 use self::swapchain::VulkanSwapchain;
@@ -41,14 +47,15 @@ use self::sync::*;
 define_resource_pool!(
     VulkanResourcePool,
     (VulkanQueue, queue, 4),
-    (VulkanSurface, surface, 1),
-    (VulkanSwapchain, swapchain, 1),
+    (VulkanSurface, surface, 4),
+    (VulkanSwapchain, swapchain, 4),
     (VulkanImage, image, 128),
     (VulkanImageView, image_view, 128),
     (VulkanSampler, sampler, 32),
     (VulkanShader, shader_module, 32),
     (VulkanPipelineLayout, pipeline_layout, 32),
     (VulkanRasterPipeline, raster_pipeline, 32),
+    (VulkanComputePipeline, compute_pipeline, 16),
     (VulkanRenderPass, render_pass, 32),
     (VulkanFramebuffer, framebuffer, 8),
     (VulkanCommandPool, command_pool, 4),
@@ -56,21 +63,130 @@ define_resource_pool!(
     (VulkanFence, fence, 4),
     (VulkanSemaphore, semaphore, 4),
     (VulkanBuffer, buffer, 32),
+    (VulkanBufferView, buffer_view, 32),
     (VulkanDescriptorSetLayout, descriptor_set_layout, 32),
     (VulkanDescriptorPool, descriptor_pool, 32),
-    (VulkanDescriptorSet, descriptor_set, 32)
+    (VulkanDescriptorSet, descriptor_set, 32),
+    (VulkanAccelerationStructure, acceleration_structure, 8)
 );
 
+fn handles<T: Handled>(pool: &Pool<T>) -> Vec<Handle<T::HandleType>>
+where
+    T::HandleType: Copy,
+{
+    pool.iter().map(|(handle, _)| handle).collect()
+}
+
+fn live_resources<T: Handled>(pool: &Pool<T>, type_name: &'static str) -> LiveResources
+where
+    T::HandleType: Copy,
+{
+    LiveResources {
+        type_name,
+        count: pool.len(),
+        names: pool
+            .iter()
+            .filter_map(|(handle, _)| pool.resolve_name(handle))
+            .map(str::to_owned)
+            .collect(),
+    }
+}
+
+impl VulkanResourcePool {
+    /// The live swapchain made for `surface`, a surface takes one swapchain at a time.
+    pub fn swapchain_of_surface(&self, surface: Handle<Surface>) -> Option<Handle<Swapchain>> {
+        self.swapchain
+            .iter()
+            .find(|(_, swapchain)| swapchain.surface == Some(surface))
+            .map(|(handle, _)| handle)
+    }
+
+    /// Fails when the buffer wasn't created with every flag of `required`, `command` names
+    /// what needs them.
+    pub fn check_buffer_usage(
+        &self,
+        handle: Handle<Buffer>,
+        required: BufferUsageFlags,
+        command: &str,
+    ) -> Result<()> {
+        let buffer = self.buffer.get(handle).context("Buffer not found.")?;
+        let missing = required.difference(buffer.usage);
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "{} needs buffer {} to be created with {:?}.",
+                command,
+                self.buffer.resolve_name(handle).map_or(format!("{:?}", handle), str::to_owned),
+                missing
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails when the image wasn't created with every flag of `required`, `command` names
+    /// what needs them.
+    pub fn check_image_usage(
+        &self,
+        handle: Handle<Image>,
+        required: ImageUsageFlags,
+        command: &str,
+    ) -> Result<()> {
+        let image = self.image.get(handle).context("Image not found.")?;
+        let missing: ImageUsageFlags =
+            required.iter().filter(|flag| !image.desc.usage.contains((*flag).into())).collect();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "{} needs image {} to be created with {:?}.",
+                command,
+                self.image.resolve_name(handle).map_or(format!("{:?}", handle), str::to_owned),
+                missing
+            );
+        }
+        Ok(())
+    }
+
+    pub fn live_resources(&self) -> LiveResourceReport {
+        let resources = vec![
+            live_resources(&self.queue, "Queue"),
+            live_resources(&self.surface, "Surface"),
+            live_resources(&self.swapchain, "Swapchain"),
+            live_resources(&self.image, "Image"),
+            live_resources(&self.image_view, "ImageView"),
+            live_resources(&self.sampler, "Sampler"),
+            live_resources(&self.shader_module, "Shader"),
+            live_resources(&self.pipeline_layout, "PipelineLayout"),
+            live_resources(&self.raster_pipeline, "RasterPipeline"),
+            live_resources(&self.compute_pipeline, "ComputePipeline"),
+            live_resources(&self.render_pass, "RenderPass"),
+            live_resources(&self.framebuffer, "Framebuffer"),
+            live_resources(&self.command_pool, "CommandPool"),
+            live_resources(&self.command_buffer, "CommandBuffer"),
+            live_resources(&self.fence, "Fence"),
+            live_resources(&self.semaphore, "Semaphore"),
+            live_resources(&self.buffer, "Buffer"),
+            live_resources(&self.buffer_view, "BufferView"),
+            live_resources(&self.descriptor_set_layout, "DescriptorSetLayout"),
+            live_resources(&self.descriptor_pool, "DescriptorPool"),
+            live_resources(&self.descriptor_set, "DescriptorSet"),
+            live_resources(&self.acceleration_structure, "AccelerationStructure"),
+        ];
+        LiveResourceReport { resources: resources.into_iter().filter(|r| r.count > 0).collect() }
+    }
+}
+
 pub struct VulkanBackend {
     instance: instance::VulkanInstance,
     res_pool: VulkanResourcePool,
     adapters: Vec<VulkanAdapter>,
     adapter_infos: Vec<AdapterInfo>,
     device: Option<VulkanDevice>,
+    robust_buffer_access: bool,
+    sampler_cache: SamplerCache,
+    device_lost: Cell<bool>,
 }
 
 impl VulkanBackend {
     pub fn new(desc: RenderBackendCreateDesc) -> Result<Self> {
+        let robust_buffer_access = desc.robust_buffer_access;
         let instance = instance::VulkanInstance::new(desc)?;
 
         // Enumerate Adapters
@@ -84,7 +200,16 @@ impl VulkanBackend {
         }
 
         let resource_pool = VulkanResourcePool::new();
-        Ok(Self { instance, res_pool: resource_pool, adapters, adapter_infos, device: None })
+        Ok(Self {
+            instance,
+            res_pool: resource_pool,
+            adapters,
+            adapter_infos,
+            device: None,
+            robust_buffer_access,
+            sampler_cache: Default::default(),
+            device_lost: Cell::new(false),
+        })
     }
 
     #[inline]
@@ -96,6 +221,86 @@ impl VulkanBackend {
     pub fn get_mut_device(&mut self) -> Result<&mut VulkanDevice> {
         self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)
     }
+
+    /// Remembers a `VK_ERROR_DEVICE_LOST` coming out of `result` for `is_device_lost`.
+    fn track_device_lost<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(err) = &result {
+            if util::is_device_lost_error(err) {
+                self.device_lost.set(true);
+            }
+        }
+        result
+    }
+
+    /// Destroys every resource made from the device, dependents first. Descriptor sets and
+    /// command buffers go with their pools, render passes and framebuffers with the device
+    /// caches they come from.
+    fn destroy_device_resources(&mut self) -> Result<()> {
+        for handle in handles(&self.res_pool.swapchain) {
+            self.destroy_swapchain(handle)?;
+        }
+        for handle in handles(&self.res_pool.framebuffer) {
+            self.res_pool.framebuffer.free(handle);
+        }
+        for handle in handles(&self.res_pool.raster_pipeline) {
+            self.destroy_raster_pipeline(handle)?;
+        }
+        for handle in handles(&self.res_pool.compute_pipeline) {
+            self.destroy_compute_pipeline(handle)?;
+        }
+        for handle in handles(&self.res_pool.render_pass) {
+            self.res_pool.render_pass.free(handle);
+        }
+        for handle in handles(&self.res_pool.pipeline_layout) {
+            self.destroy_pipeline_layout(handle)?;
+        }
+        for handle in handles(&self.res_pool.shader_module) {
+            self.destroy_shader_module(handle)?;
+        }
+        for handle in handles(&self.res_pool.descriptor_set) {
+            self.res_pool.descriptor_set.free(handle);
+        }
+        for handle in handles(&self.res_pool.descriptor_pool) {
+            self.destroy_descriptor_pool(handle)?;
+        }
+        for handle in handles(&self.res_pool.descriptor_set_layout) {
+            self.destroy_descriptor_set_layout(handle)?;
+        }
+        for handle in handles(&self.res_pool.command_buffer) {
+            self.res_pool.command_buffer.free(handle);
+        }
+        for handle in handles(&self.res_pool.command_pool) {
+            self.destroy_command_pool(handle)?;
+        }
+        for handle in handles(&self.res_pool.fence) {
+            self.destroy_fence(handle)?;
+        }
+        for handle in handles(&self.res_pool.semaphore) {
+            self.destroy_semaphore(handle)?;
+        }
+        for handle in handles(&self.res_pool.acceleration_structure) {
+            self.destroy_acceleration_structure(handle)?;
+        }
+        for handle in handles(&self.res_pool.image) {
+            self.destroy_image(handle)?;
+        }
+        for handle in handles(&self.res_pool.buffer_view) {
+            self.destroy_buffer_view(handle)?;
+        }
+        for handle in handles(&self.res_pool.buffer) {
+            self.destroy_buffer(handle)?;
+        }
+        // Samplers are shared through the cache, whatever their reference count
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        for handle in handles(&self.res_pool.sampler) {
+            if let Some(sampler) = self.res_pool.sampler.get_mut(handle) {
+                sampler.destroy(device);
+                self.res_pool.sampler.free(handle);
+            }
+        }
+        self.sampler_cache = Default::default();
+        Ok(())
+    }
 }
 
 impl Drop for VulkanBackend {
@@ -116,22 +321,31 @@ impl RenderBackend for VulkanBackend {
         tiling: ImageTiling,
         feature: FormatFeatureFlags,
     ) -> Result<Format> {
-        let adapter = self.get_device()?.get_adapter().raw;
+        self.get_device()?;
         candidates
             .iter()
             .cloned()
-            .find(|f| {
-                let props = unsafe {
-                    self.instance.raw.get_physical_device_format_properties(adapter, (*f).into())
-                };
-                match tiling {
-                    ImageTiling::Linear => props.linear_tiling_features.contains(feature.into()),
-                    ImageTiling::Optimal => props.optimal_tiling_features.contains(feature.into()),
-                }
-            })
+            .find(|f| self.is_format_supported(*f, tiling, feature))
             .ok_or_else(|| anyhow::anyhow!("No supported format found."))
     }
 
+    fn is_format_supported(
+        &self,
+        format: Format,
+        tiling: ImageTiling,
+        feature: FormatFeatureFlags,
+    ) -> bool {
+        let Ok(device) = self.get_device() else {
+            return false;
+        };
+        let props = unsafe {
+            self.instance
+                .raw
+                .get_physical_device_format_properties(device.get_adapter().raw, format.into())
+        };
+        util::is_format_feature_supported(&props, tiling, feature.into())
+    }
+
     #[inline]
     fn enumerate_adapter_infos(&self) -> &[AdapterInfo] {
         &self.adapter_infos
@@ -142,23 +356,59 @@ impl RenderBackend for VulkanBackend {
         self.device.is_some()
     }
 
-    fn create_device(&mut self, adapter_index: usize) -> Result<()> {
+    fn create_device(&mut self, desc: &DeviceCreateDesc) -> Result<()> {
         if self.is_device_created() {
             return Err(anyhow::anyhow!("Device already created."));
         }
-        let adapter = self.adapters.get(adapter_index).context("Adapter not found.")?;
-        self.device = Some(VulkanDevice::new(&self.instance, adapter, &mut self.res_pool.queue)?);
+        let adapter = self.adapters.get(desc.adapter_index).context("Adapter not found.")?;
+        let present_surface = match desc.present_surface {
+            Some(handle) => Some(self.res_pool.surface.get(handle).context("Surface not found.")?),
+            None => None,
+        };
+        self.device = Some(VulkanDevice::new(
+            &self.instance,
+            adapter,
+            &mut self.res_pool.queue,
+            desc,
+            present_surface,
+            self.robust_buffer_access,
+        )?);
         Ok(())
     }
 
+    fn enabled_features(&self) -> Result<&EnabledFeatures> {
+        Ok(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.enabled_features())
+    }
+
     fn destroy_device(&mut self) -> Result<()> {
-        self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?.destroy();
+        // A lost device fails the wait but its objects still have to be destroyed
+        let _ = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.wait_idle();
+        self.destroy_device_resources()?;
+        if let Some(mut device) = self.device.take() {
+            device.destroy();
+        }
+        // Queues were allocated with the device
+        for handle in handles(&self.res_pool.queue) {
+            self.res_pool.queue.free(handle);
+        }
+        self.device_lost.set(false);
         Ok(())
     }
 
     #[inline]
     fn device_wait_idle(&self) -> Result<()> {
-        self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.wait_idle()
+        self.track_device_lost(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.wait_idle(),
+        )
+    }
+
+    #[inline]
+    fn is_device_lost(&self) -> bool {
+        self.device_lost.get()
+    }
+
+    fn debug_report_live_resources(&self) -> LiveResourceReport {
+        self.res_pool.live_resources()
     }
 
     #[inline]
@@ -168,23 +418,40 @@ impl RenderBackend for VulkanBackend {
 
     fn queue_submit(&self, handle: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()> {
         let queue = self.res_pool.queue.get(handle).context(ERR_MSG_QUEUE_NOT_FOUND)?;
-        queue.submit(
+        self.track_device_lost(queue.submit(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             desc,
             &self.res_pool.fence,
             &self.res_pool.semaphore,
             &self.res_pool.command_buffer,
-        )
+        ))
+    }
+
+    fn queue_submit_batch(&self, handle: Handle<Queue>, descs: &[QueueSubmitDesc]) -> Result<()> {
+        let queue = self.res_pool.queue.get(handle).context(ERR_MSG_QUEUE_NOT_FOUND)?;
+        self.track_device_lost(queue.submit_batch(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            descs,
+            &self.res_pool.fence,
+            &self.res_pool.semaphore,
+            &self.res_pool.command_buffer,
+        ))
     }
 
     fn queue_present(&self, handle: Handle<Queue>, desc: &QueuePresentDesc) -> Result<bool> {
         let queue = self.res_pool.queue.get(handle).context(ERR_MSG_QUEUE_NOT_FOUND)?;
-        queue.present(desc, &self.res_pool.swapchain, &self.res_pool.semaphore)
+        self.track_device_lost(queue.present(
+            desc,
+            &self.res_pool.swapchain,
+            &self.res_pool.semaphore,
+        ))
     }
 
     fn queue_wait_idle(&self, handle: Handle<Queue>) -> Result<()> {
         let queue = self.res_pool.queue.get(handle).context(ERR_MSG_QUEUE_NOT_FOUND)?;
-        queue.wait_idle(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.raw())
+        self.track_device_lost(
+            queue.wait_idle(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.raw()),
+        )
     }
 
     fn create_fence(&mut self, signal: bool) -> Result<Handle<Fence>> {
@@ -206,13 +473,17 @@ impl RenderBackend for VulkanBackend {
         handles: &[Handle<Fence>],
         wait_all: bool,
         timeout: u64,
-    ) -> Result<()> {
-        self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.wait_for_fences(
+    ) -> Result<FenceWaitResult> {
+        let result = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.wait_for_fences(
             handles,
             wait_all,
             timeout,
             &self.res_pool.fence,
-        )
+        )?;
+        if result == FenceWaitResult::DeviceLost {
+            self.device_lost.set(true);
+        }
+        Ok(result)
     }
 
     fn reset_fences(&self, handles: &[Handle<Fence>]) -> Result<()> {
@@ -222,12 +493,28 @@ impl RenderBackend for VulkanBackend {
             .reset_fences(handles, &self.res_pool.fence)
     }
 
+    fn get_fence_status(&self, handle: Handle<Fence>) -> Result<bool> {
+        let fence = self.res_pool.fence.get(handle).context("Fence not found.")?;
+        self.track_device_lost(
+            fence.status(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?),
+        )
+    }
+
     fn create_semaphore(&mut self) -> Result<Handle<Semaphore>> {
         let item = self.res_pool.semaphore.malloc();
         item.1.init(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)?;
         Ok(item.0)
     }
 
+    fn create_timeline_semaphore(&mut self, initial_value: u64) -> Result<Handle<Semaphore>> {
+        let item = self.res_pool.semaphore.malloc();
+        item.1.init_timeline(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            initial_value,
+        )?;
+        Ok(item.0)
+    }
+
     fn destroy_semaphore(&mut self, handle: Handle<Semaphore>) -> Result<()> {
         if let Some(s) = self.res_pool.semaphore.get_mut(handle) {
             s.destroy(self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?);
@@ -236,6 +523,58 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn wait_timeline_semaphore(
+        &self,
+        semaphore: Handle<Semaphore>,
+        value: u64,
+        timeout: u64,
+    ) -> Result<FenceWaitResult> {
+        let semaphore = self.res_pool.semaphore.get(semaphore).context("Semaphore not found.")?;
+        let result = self
+            .device
+            .as_ref()
+            .context(ERR_MSG_DEVICE_NOT_CREATED)?
+            .wait_timeline_semaphore(semaphore, value, timeout)?;
+        if result == FenceWaitResult::DeviceLost {
+            self.device_lost.set(true);
+        }
+        Ok(result)
+    }
+
+    fn wait_semaphores(
+        &self,
+        semaphores: &[(Handle<Semaphore>, u64)],
+        timeout: u64,
+    ) -> Result<FenceWaitResult> {
+        let raws = semaphores
+            .iter()
+            .map(|(h, _)| self.res_pool.semaphore.get(*h).context("Semaphore not found."))
+            .collect::<Result<SmallVec<[_; 4]>>>()?;
+        let values = semaphores.iter().map(|(_, v)| *v).collect::<SmallVec<[_; 4]>>();
+        let result = self
+            .device
+            .as_ref()
+            .context(ERR_MSG_DEVICE_NOT_CREATED)?
+            .wait_timeline_semaphores(&raws, &values, timeout)?;
+        if result == FenceWaitResult::DeviceLost {
+            self.device_lost.set(true);
+        }
+        Ok(result)
+    }
+
+    fn signal_timeline_semaphore(&self, semaphore: Handle<Semaphore>, value: u64) -> Result<()> {
+        let semaphore = self.res_pool.semaphore.get(semaphore).context("Semaphore not found.")?;
+        self.device
+            .as_ref()
+            .context(ERR_MSG_DEVICE_NOT_CREATED)?
+            .signal_timeline_semaphore(semaphore, value)
+    }
+
+    fn get_timeline_value(&self, semaphore: Handle<Semaphore>) -> Result<u64> {
+        let semaphore = self.res_pool.semaphore.get(semaphore).context("Semaphore not found.")?;
+        self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.get_timeline_value(semaphore)
+    }
+
     fn create_surface(&mut self, desc: SurfaceCreateDesc) -> Result<Handle<Surface>> {
         let item = self.res_pool.surface.malloc();
         item.1.init(&self.instance, desc)?;
@@ -251,6 +590,13 @@ impl RenderBackend for VulkanBackend {
     }
 
     fn create_swapchain(&mut self, desc: SwapchainCreateDesc) -> Result<Handle<Swapchain>> {
+        if let Some(existing) = self.res_pool.swapchain_of_surface(desc.surface) {
+            anyhow::bail!(
+                "Surface {:?} already has swapchain {:?}, destroy it before creating another.",
+                desc.surface,
+                existing
+            );
+        }
         let item = self.res_pool.swapchain.malloc();
         item.1.init(
             &self.instance,
@@ -302,7 +648,9 @@ impl RenderBackend for VulkanBackend {
         } else {
             None
         };
-        Ok(swapchain.acquire_next_image(timeout, semaphore, fence)?)
+        self.track_device_lost(
+            swapchain.acquire_next_image(timeout, semaphore, fence).map_err(Into::into),
+        )
     }
 
     fn get_swapchain_back_buffer(
@@ -319,6 +667,16 @@ impl RenderBackend for VulkanBackend {
         Ok(swapchain.image_count)
     }
 
+    fn get_swapchain_extent(&self, handle: Handle<Swapchain>) -> Result<(u32, u32)> {
+        let swapchain = self.res_pool.swapchain.get(handle).context("Swapchain not found.")?;
+        Ok((swapchain.extent.width, swapchain.extent.height))
+    }
+
+    fn get_swapchain_format(&self, handle: Handle<Swapchain>) -> Result<Format> {
+        let swapchain = self.res_pool.swapchain.get(handle).context("Swapchain not found.")?;
+        Ok(swapchain.surface_format.format.into())
+    }
+
     fn create_descriptor_set_layout(
         &mut self,
         desc: &DescriptorSetLayoutCreateDesc,
@@ -363,13 +721,37 @@ impl RenderBackend for VulkanBackend {
             desc,
             &self.res_pool.descriptor_pool,
             &self.res_pool.descriptor_set_layout,
-            &self.res_pool.buffer,
-            &self.res_pool.image_view,
-            &self.res_pool.sampler,
+            &DescriptorResourcePools {
+                buffer: &self.res_pool.buffer,
+                buffer_view: &self.res_pool.buffer_view,
+                image_view: &self.res_pool.image_view,
+                sampler: &self.res_pool.sampler,
+                acceleration_structure: &self.res_pool.acceleration_structure,
+            },
         )?;
         Ok(item.0)
     }
 
+    fn update_descriptor_set(
+        &mut self,
+        set: Handle<DescriptorSet>,
+        bindings: &[DescriptorBindingData],
+    ) -> Result<()> {
+        let set = self.res_pool.descriptor_set.get_mut(set).context("Descriptor set not found.")?;
+        set.update(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            bindings,
+            &self.res_pool.descriptor_set_layout,
+            &DescriptorResourcePools {
+                buffer: &self.res_pool.buffer,
+                buffer_view: &self.res_pool.buffer_view,
+                image_view: &self.res_pool.image_view,
+                sampler: &self.res_pool.sampler,
+                acceleration_structure: &self.res_pool.acceleration_structure,
+            },
+        )
+    }
+
     fn destroy_descriptor_sets(&mut self, sets: &[Handle<DescriptorSet>]) -> Result<()> {
         for set in sets {
             if let Some(ds) = self.res_pool.descriptor_set.get_mut(*set) {
@@ -384,9 +766,22 @@ impl RenderBackend for VulkanBackend {
     }
 
     fn create_image(&mut self, desc: &ImageCreateDesc) -> Result<Handle<Image>> {
-        let item = self.res_pool.image.malloc();
-        item.1.init(self.device.as_mut().context("Device not created.")?, desc)?;
-        Ok(item.0)
+        let (handle, item) = self.res_pool.image.malloc();
+        item.init(self.device.as_mut().context("Device not created.")?, desc)?;
+        if !desc.name.is_empty() {
+            self.res_pool.image.set_name(handle, desc.name);
+        }
+        Ok(handle)
+    }
+
+    fn get_image_format(&self, handle: Handle<Image>) -> Result<Format> {
+        let image = self.res_pool.image.get(handle).context("Image not found.")?;
+        Ok(image.desc.format.into())
+    }
+
+    fn get_image_mip_levels(&self, handle: Handle<Image>) -> Result<u32> {
+        let image = self.res_pool.image.get(handle).context("Image not found.")?;
+        Ok(image.desc.mip_levels)
     }
 
     fn destroy_image(&mut self, handle: Handle<Image>) -> Result<()> {
@@ -412,6 +807,7 @@ impl RenderBackend for VulkanBackend {
     ) -> Result<Handle<ImageView>> {
         let texture = self.res_pool.image.get_mut(texture).context("Texture not found.")?;
         let desc = VulkanImageViewDesc::from_create_desc(desc, texture);
+        check_view_format(&texture.desc, desc.format)?;
         texture.get_or_create_view(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             &desc,
@@ -433,23 +829,38 @@ impl RenderBackend for VulkanBackend {
     }
 
     fn create_sampler(&mut self, desc: &SamplerCreateDesc) -> Result<Handle<Sampler>> {
-        let item = self.res_pool.sampler.malloc();
-        item.1.init(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, desc)?;
-        Ok(item.0)
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        self.sampler_cache.acquire(desc, &mut self.res_pool.sampler, |s| s.init(device, desc))
     }
 
     fn destroy_sampler(&mut self, handle: Handle<Sampler>) -> Result<()> {
-        if let Some(s) = self.res_pool.sampler.get_mut(handle) {
-            s.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
-            self.res_pool.sampler.free(handle);
-        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        self.sampler_cache.release(handle, &mut self.res_pool.sampler, |s| s.destroy(device));
         Ok(())
     }
 
     fn create_shader_module(&mut self, creation: &ShaderModuleCreation) -> Result<Handle<Shader>> {
-        let item = self.res_pool.shader_module.malloc();
-        item.1.init(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, creation)?;
-        Ok(item.0)
+        let (handle, item) = self.res_pool.shader_module.malloc();
+        item.init(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, creation)?;
+        if !creation.name.is_empty() {
+            self.res_pool.shader_module.set_name(handle, creation.name);
+        }
+        Ok(handle)
+    }
+
+    fn reflect_descriptor_layouts(
+        &self,
+        shaders: &[Handle<Shader>],
+    ) -> Result<Vec<DescriptorSetLayoutCreateDesc>> {
+        let mut layouts = Vec::new();
+        for handle in shaders {
+            let shader = self.res_pool.shader_module.get(*handle).context("Shader not found.")?;
+            let reflected = shader.descriptor_layouts().with_context(|| {
+                format!("Shader {:?} has no descriptor layouts.", handle)
+            })?;
+            reflection::merge_descriptor_layouts(&mut layouts, reflected)?;
+        }
+        Ok(layouts)
     }
 
     fn destroy_shader_module(&mut self, handle: Handle<Shader>) -> Result<()> {
@@ -461,9 +872,12 @@ impl RenderBackend for VulkanBackend {
     }
 
     fn create_buffer(&mut self, desc: &BufferCreateDesc) -> Result<Handle<Buffer>> {
-        let item = self.res_pool.buffer.malloc();
-        item.1.init(self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?, desc)?;
-        Ok(item.0)
+        let (handle, item) = self.res_pool.buffer.malloc();
+        item.init(self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?, desc)?;
+        if !desc.name.is_empty() {
+            self.res_pool.buffer.set_name(handle, desc.name);
+        }
+        Ok(handle)
     }
 
     fn get_buffer_mapped_slice_mut(&mut self, buffer: Handle<Buffer>) -> Result<&mut [u8]> {
@@ -472,6 +886,60 @@ impl RenderBackend for VulkanBackend {
         Ok(allocation.mapped_slice_mut().context("Buffer not mapped.")?)
     }
 
+    fn flush_buffer(&self, buffer: Handle<Buffer>, offset: u64, size: u64) -> Result<()> {
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        buffer.flush(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, offset, size)
+    }
+
+    fn invalidate_buffer(&self, buffer: Handle<Buffer>, offset: u64, size: u64) -> Result<()> {
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        buffer.invalidate(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, offset, size)
+    }
+
+    fn create_buffer_view(
+        &mut self,
+        buffer: Handle<Buffer>,
+        format: Format,
+        offset: u64,
+        range: u64,
+    ) -> Result<Handle<BufferView>> {
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        let b = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        let features = texel_buffer_features(b.usage)?;
+        let props = unsafe {
+            self.instance
+                .raw
+                .get_physical_device_format_properties(device.get_adapter().raw, format.into())
+        };
+        if !props.buffer_features.contains(features) {
+            anyhow::bail!("Format {:?} doesn't support {:?} buffer views.", format, features);
+        }
+        let limits = &device.get_adapter().properties.limits;
+        check_buffer_view_range(offset, range, b.size, limits.min_texel_buffer_offset_alignment)?;
+
+        let (handle, item) = self.res_pool.buffer_view.malloc();
+        item.init(device, b, format.into(), offset, range)?;
+        Ok(handle)
+    }
+
+    fn destroy_buffer_view(&mut self, handle: Handle<BufferView>) -> Result<()> {
+        if let Some(view) = self.res_pool.buffer_view.get_mut(handle) {
+            view.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
+            self.res_pool.buffer_view.free(handle);
+        }
+        Ok(())
+    }
+
+    fn get_buffer_device_address(&self, buffer: Handle<Buffer>) -> Result<u64> {
+        self.res_pool.check_buffer_usage(
+            buffer,
+            BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            "get_buffer_device_address",
+        )?;
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        Ok(buffer.device_address(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?))
+    }
+
     fn destroy_buffer(&mut self, buffer: Handle<Buffer>) -> Result<()> {
         if let Some(b) = self.res_pool.buffer.get_mut(buffer) {
             b.destroy(self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?)?;
@@ -480,6 +948,66 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn create_acceleration_structure(
+        &mut self,
+        desc: &AccelerationStructureCreateDesc,
+    ) -> Result<Handle<AccelerationStructure>> {
+        if cfg!(debug_assertions) {
+            for geometry in desc.geometries {
+                let buffers = match *geometry {
+                    AccelerationStructureGeometry::Triangles { vertex_buffer, index_buffer, .. } => {
+                        [Some(vertex_buffer), index_buffer.map(|(buffer, _, _)| buffer)]
+                    }
+                    AccelerationStructureGeometry::Instances { buffer, .. } => [Some(buffer), None],
+                };
+                for buffer in buffers.into_iter().flatten() {
+                    self.res_pool.check_buffer_usage(
+                        buffer,
+                        BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT
+                            | BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+                        "create_acceleration_structure",
+                    )?;
+                }
+            }
+        }
+        let (handle, item) = self.res_pool.acceleration_structure.malloc();
+        item.init(
+            self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            desc,
+            &mut self.res_pool.buffer,
+        )?;
+        if !desc.name.is_empty() {
+            self.res_pool.acceleration_structure.set_name(handle, desc.name);
+        }
+        Ok(handle)
+    }
+
+    fn destroy_acceleration_structure(
+        &mut self,
+        acceleration_structure: Handle<AccelerationStructure>,
+    ) -> Result<()> {
+        if let Some(a) = self.res_pool.acceleration_structure.get_mut(acceleration_structure) {
+            a.destroy(
+                self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+                &mut self.res_pool.buffer,
+            )?;
+            self.res_pool.acceleration_structure.free(acceleration_structure);
+        }
+        Ok(())
+    }
+
+    fn get_acceleration_structure_device_address(
+        &self,
+        acceleration_structure: Handle<AccelerationStructure>,
+    ) -> Result<u64> {
+        let a = self
+            .res_pool
+            .acceleration_structure
+            .get(acceleration_structure)
+            .context("Acceleration structure not found.")?;
+        Ok(a.device_address)
+    }
+
     fn create_pipeline_layout(
         &mut self,
         desc: &PipelineLayoutCreateDesc,
@@ -505,11 +1033,10 @@ impl RenderBackend for VulkanBackend {
         &mut self,
         desc: &RasterPipelineCreateDesc,
     ) -> Result<Handle<RasterPipeline>> {
-        let render_pass = self
-            .device
-            .as_mut()
-            .context(ERR_MSG_DEVICE_NOT_CREATED)?
-            .get_or_create_render_pass(&desc.render_pass_output.into())?;
+        let render_pass = raster_pipeline_render_pass(
+            self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            desc,
+        )?;
         let pipeline_layout = self
             .res_pool
             .pipeline_layout
@@ -526,6 +1053,38 @@ impl RenderBackend for VulkanBackend {
         Ok(item.0)
     }
 
+    fn create_raster_pipelines(
+        &mut self,
+        descs: &[RasterPipelineCreateDesc],
+    ) -> Result<Vec<Handle<RasterPipeline>>> {
+        let device = self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        let mut states = Vec::with_capacity(descs.len());
+        for desc in descs {
+            let render_pass = raster_pipeline_render_pass(device, desc)?;
+            let pipeline_layout = self
+                .res_pool
+                .pipeline_layout
+                .get(desc.pipeline_layout)
+                .context("Pipeline layout not found.")?;
+            states.push(VulkanRasterPipelineState::new(
+                render_pass,
+                pipeline_layout,
+                desc,
+                &self.res_pool.shader_module,
+            )?);
+        }
+
+        let raws = create_vulkan_raster_pipelines(device, &states)?;
+        Ok(raws
+            .into_iter()
+            .map(|raw| {
+                let item = self.res_pool.raster_pipeline.malloc();
+                item.1.raw = raw;
+                item.0
+            })
+            .collect())
+    }
+
     fn destroy_raster_pipeline(&mut self, handle: Handle<RasterPipeline>) -> Result<()> {
         if let Some(pipeline) = self.res_pool.raster_pipeline.get_mut(handle) {
             pipeline.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
@@ -534,6 +1093,33 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn create_compute_pipeline(
+        &mut self,
+        desc: &ComputePipelineCreateDesc,
+    ) -> Result<Handle<ComputePipeline>> {
+        let pipeline_layout = self
+            .res_pool
+            .pipeline_layout
+            .get(desc.pipeline_layout)
+            .context("Pipeline layout not found.")?;
+        let item = self.res_pool.compute_pipeline.malloc();
+        item.1.init(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            pipeline_layout,
+            desc,
+            &self.res_pool.shader_module,
+        )?;
+        Ok(item.0)
+    }
+
+    fn destroy_compute_pipeline(&mut self, handle: Handle<ComputePipeline>) -> Result<()> {
+        if let Some(pipeline) = self.res_pool.compute_pipeline.get_mut(handle) {
+            pipeline.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
+            self.res_pool.compute_pipeline.free(handle);
+        }
+        Ok(())
+    }
+
     fn create_render_pass(&mut self, output: &RenderPassOutput) -> Result<Handle<RenderPass>> {
         let item = self.res_pool.render_pass.malloc();
         let output = (*output).into();
@@ -548,7 +1134,10 @@ impl RenderBackend for VulkanBackend {
 
     fn destroy_render_pass(&mut self, handle: Handle<RenderPass>) -> Result<()> {
         if let Some(rp) = self.res_pool.render_pass.get_mut(handle) {
-            rp.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
+            let device = self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+            // Not handed out again, nor destroyed a second time with the device
+            device.render_pass_cache.retain(|_, raw| *raw != rp.raw);
+            rp.destroy(device);
             self.res_pool.render_pass.free(handle);
         }
         Ok(())
@@ -557,9 +1146,9 @@ impl RenderBackend for VulkanBackend {
     fn create_framebuffer(&mut self, desc: &FramebufferCreateDesc) -> Result<Handle<Framebuffer>> {
         let rp =
             self.res_pool.render_pass.get(desc.render_pass).context("Render pass not found.")?;
-        let item = self.res_pool.framebuffer.malloc();
         let desc = VulkanFramebufferDesc::from_create_desc(
             rp.raw,
+            &rp.output,
             &desc,
             &self.res_pool.image,
             &self.res_pool.image_view,
@@ -569,13 +1158,16 @@ impl RenderBackend for VulkanBackend {
             .as_mut()
             .context(ERR_MSG_DEVICE_NOT_CREATED)?
             .get_or_create_framebuffer(&desc)?;
+        let item = self.res_pool.framebuffer.malloc();
         item.1.init(fb, desc);
         Ok(item.0)
     }
 
     fn destroy_framebuffer(&mut self, handle: Handle<Framebuffer>) -> Result<()> {
         if let Some(fb) = self.res_pool.framebuffer.get_mut(handle) {
-            fb.destroy(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?);
+            let device = self.device.as_mut().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+            device.framebuffer_cache.retain(|_, raw| *raw != fb.raw);
+            fb.destroy(device);
             self.res_pool.framebuffer.free(handle);
         }
         Ok(())
@@ -608,7 +1200,14 @@ impl RenderBackend for VulkanBackend {
 
     fn reset_command_pool(&self, handle: Handle<CommandPool>) -> Result<()> {
         let cp = self.res_pool.command_pool.get(handle).context("Command pool not found.")?;
-        cp.reset(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)
+        cp.reset(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)?;
+        // Every command buffer allocated from the pool goes back to the initial state
+        for (_, cb) in self.res_pool.command_buffer.iter() {
+            if cb.pool == Some(handle) {
+                cb.pool_reset();
+            }
+        }
+        Ok(())
     }
 
     fn create_command_buffer(
@@ -637,11 +1236,39 @@ impl RenderBackend for VulkanBackend {
 
     fn cmd_begin(&self, cb: Handle<CommandBuffer>, desc: CommandBufferBeginDesc) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
-        cb.begin(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, desc)
+        if cfg!(debug_assertions) {
+            cb.validate_begin(&desc)?;
+        }
+        let (render_pass, framebuffer) = match desc.render_pass_continue {
+            Some(continued) => (
+                Some(
+                    self.res_pool
+                        .render_pass
+                        .get(continued.render_pass)
+                        .context("Render pass not found.")?,
+                ),
+                match continued.framebuffer {
+                    Some(fb) => {
+                        Some(self.res_pool.framebuffer.get(fb).context("Framebuffer not found.")?)
+                    }
+                    None => None,
+                },
+            ),
+            None => (None, None),
+        };
+        cb.begin(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            desc,
+            render_pass,
+            framebuffer,
+        )
     }
 
     fn cmd_end(&self, cb: Handle<CommandBuffer>) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_end()?;
+        }
         cb.end(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)
     }
 
@@ -652,8 +1279,13 @@ impl RenderBackend for VulkanBackend {
         framebuffer: Handle<Framebuffer>,
         clear_values: Option<&[ClearColor]>,
         clear_depth_stencil: Option<ClearDepthStencil>,
+        contents: SubpassContents,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_primary("cmd_begin_render_pass")?;
+            cb.validate_scope(RenderPassScope::Outside, "cmd_begin_render_pass")?;
+        }
         let rp = self.res_pool.render_pass.get(render_pass).context("Render pass not found.")?;
         let fb = self.res_pool.framebuffer.get(framebuffer).context("Framebuffer not found.")?;
         cb.begin_render_pass(
@@ -662,25 +1294,113 @@ impl RenderBackend for VulkanBackend {
             fb,
             clear_values,
             clear_depth_stencil,
+            contents,
         )
     }
 
-    fn cmd_end_render_pass(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+    fn cmd_next_subpass(&self, cb: Handle<CommandBuffer>, contents: SubpassContents) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_primary("cmd_next_subpass")?;
+            cb.validate_scope(RenderPassScope::Inside, "cmd_next_subpass")?;
+            cb.validate_rendering(false, "cmd_next_subpass")?;
+        }
+        cb.next_subpass(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, contents)
+    }
+
+    fn cmd_end_render_pass(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_primary("cmd_end_render_pass")?;
+            cb.validate_scope(RenderPassScope::Inside, "cmd_end_render_pass")?;
+            cb.validate_rendering(false, "cmd_end_render_pass")?;
+        }
         cb.end_render_pass(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)
     }
 
+    fn cmd_begin_rendering(&self, cb: Handle<CommandBuffer>, info: &RenderingInfo) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_primary("cmd_begin_rendering")?;
+            cb.validate_scope(RenderPassScope::Outside, "cmd_begin_rendering")?;
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        if !device.enabled_features().features.contains(DeviceFeatures::DYNAMIC_RENDERING) {
+            anyhow::bail!("cmd_begin_rendering needs DeviceFeatures::DYNAMIC_RENDERING.");
+        }
+        let attachments = VulkanRenderingAttachments::from_info(
+            info,
+            &self.res_pool.image,
+            &self.res_pool.image_view,
+        )?;
+        cb.begin_rendering(device, &attachments)
+    }
+
+    fn cmd_end_rendering(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_primary("cmd_end_rendering")?;
+            cb.validate_scope(RenderPassScope::Inside, "cmd_end_rendering")?;
+            cb.validate_rendering(true, "cmd_end_rendering")?;
+        }
+        cb.end_rendering(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?)
+    }
+
+    fn cmd_execute_commands(
+        &self,
+        primary: Handle<CommandBuffer>,
+        secondaries: &[Handle<CommandBuffer>],
+    ) -> Result<()> {
+        let primary =
+            self.res_pool.command_buffer.get(primary).context("Command buffer not found.")?;
+        let mut executed = SmallVec::<[&VulkanCommandBuffer; 8]>::new();
+        for secondary in secondaries {
+            executed.push(
+                self.res_pool
+                    .command_buffer
+                    .get(*secondary)
+                    .context("Secondary command buffer not found.")?,
+            );
+        }
+        if cfg!(debug_assertions) {
+            primary.validate_execute(&executed)?;
+        }
+        let raws: SmallVec<[ash::vk::CommandBuffer; 8]> =
+            executed.iter().map(|cb| cb.raw).collect();
+        primary.execute_commands(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, &raws);
+        Ok(())
+    }
+
     fn cmd_bind_raster_pipeline(
         &self,
         cb: Handle<CommandBuffer>,
         pipeline: Handle<RasterPipeline>,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_bind_raster_pipeline")?;
+        }
         let pipeline =
             self.res_pool.raster_pipeline.get(pipeline).context("Raster pipeline not found.")?;
         cb.bind_raster_pipeline(self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?, pipeline)
     }
 
+    fn cmd_bind_compute_pipeline(
+        &self,
+        cb: Handle<CommandBuffer>,
+        pipeline: Handle<ComputePipeline>,
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_bind_compute_pipeline")?;
+        }
+        let pipeline =
+            self.res_pool.compute_pipeline.get(pipeline).context("Compute pipeline not found.")?;
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        cb.bind_compute_pipeline(device, pipeline);
+        Ok(())
+    }
+
     fn cmd_bind_descriptor_sets(
         &self,
         cb: Handle<CommandBuffer>,
@@ -691,29 +1411,46 @@ impl RenderBackend for VulkanBackend {
         dynamic_offsets: &[u32],
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_bind_descriptor_sets")?;
+        }
         let pipeline_layout = self
             .res_pool
             .pipeline_layout
             .get(pipeline_layout)
             .context("Pipeline layout not found.")?;
-        let mut sets = SmallVec::<[ash::vk::DescriptorSet; 4]>::new();
+        let mut bound = SmallVec::<[&VulkanDescriptorSet; 4]>::new();
         for set in descriptor_sets {
-            sets.push(self.res_pool.descriptor_set.get(*set).unwrap().raw);
+            bound.push(self.res_pool.descriptor_set.get(*set).context("Descriptor set not found.")?);
         }
-        unsafe {
-            self.device
-                .as_ref()
-                .context(ERR_MSG_DEVICE_NOT_CREATED)?
-                .raw()
-                .cmd_bind_descriptor_sets(
-                    cb.raw,
-                    bind_point.into(),
-                    pipeline_layout.raw,
-                    first_set,
-                    &sets,
-                    dynamic_offsets,
+        if cfg!(debug_assertions) {
+            pipeline_layout.validate_bound_sets(
+                bind_point,
+                first_set,
+                &bound,
+                &self.res_pool.descriptor_set_layout,
+            )?;
+            let mut layouts = SmallVec::<[&VulkanDescriptorSetLayout; 4]>::new();
+            for set in &bound {
+                let layout = set.layout.context("Descriptor set has no layout.")?;
+                layouts.push(
+                    self.res_pool
+                        .descriptor_set_layout
+                        .get(layout)
+                        .context("Descriptor set layout not found.")?,
                 );
+            }
+            check_dynamic_offsets(&layouts, dynamic_offsets.len())?;
         }
+        let sets: SmallVec<[ash::vk::DescriptorSet; 4]> = bound.iter().map(|s| s.raw).collect();
+        cb.bind_descriptor_sets(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            bind_point,
+            pipeline_layout,
+            first_set,
+            &sets,
+            dynamic_offsets,
+        );
         Ok(())
     }
 
@@ -726,6 +1463,9 @@ impl RenderBackend for VulkanBackend {
         height: u32,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_scissor")?;
+        }
         cb.set_scissor(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             x,
@@ -746,6 +1486,10 @@ impl RenderBackend for VulkanBackend {
         max_depth: f32,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_viewport")?;
+            validate_viewport(&Viewport { x, y, width, height, min_depth, max_depth })?;
+        }
         cb.set_viewport(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             x,
@@ -757,6 +1501,115 @@ impl RenderBackend for VulkanBackend {
         )
     }
 
+    fn cmd_set_viewports(
+        &self,
+        cb: Handle<CommandBuffer>,
+        first: u32,
+        viewports: &[Viewport],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_viewports")?;
+            for viewport in viewports {
+                validate_viewport(viewport)?;
+            }
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        check_viewport_range(first, viewports.len(), device.enabled_features().requested_features)?;
+        cb.set_viewports(device, first, viewports);
+        Ok(())
+    }
+
+    fn cmd_set_scissors(
+        &self,
+        cb: Handle<CommandBuffer>,
+        first: u32,
+        scissors: &[Rect2D],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_scissors")?;
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        check_viewport_range(first, scissors.len(), device.enabled_features().requested_features)?;
+        cb.set_scissors(device, first, scissors);
+        Ok(())
+    }
+
+    fn cmd_set_depth_bias(
+        &self,
+        cb: Handle<CommandBuffer>,
+        constant_factor: f32,
+        clamp: f32,
+        slope_factor: f32,
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_depth_bias")?;
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        cb.set_depth_bias(device, constant_factor, clamp, slope_factor);
+        Ok(())
+    }
+
+    fn cmd_set_line_width(&self, cb: Handle<CommandBuffer>, width: f32) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_line_width")?;
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        check_line_width(width, device.enabled_features().requested_features)?;
+        cb.set_line_width(device, width);
+        Ok(())
+    }
+
+    fn cmd_set_blend_constants(
+        &self,
+        cb: Handle<CommandBuffer>,
+        constants: [f32; 4],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_set_blend_constants")?;
+        }
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        cb.set_blend_constants(device, constants);
+        Ok(())
+    }
+
+    fn cmd_push_constants(
+        &self,
+        cb: Handle<CommandBuffer>,
+        pipeline_layout: Handle<PipelineLayout>,
+        stage_flags: ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        let pipeline_layout = self
+            .res_pool
+            .pipeline_layout
+            .get(pipeline_layout)
+            .context("Pipeline layout not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_push_constants")?;
+            check_push_constants(
+                &pipeline_layout.push_constant_ranges,
+                stage_flags.into(),
+                offset,
+                u32::try_from(data.len()).unwrap_or(u32::MAX),
+            )?;
+        }
+        cb.push_constants(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            pipeline_layout,
+            stage_flags.into(),
+            offset,
+            data,
+        );
+        Ok(())
+    }
+
     fn cmd_bind_vertex_buffers(
         &self,
         cb: Handle<CommandBuffer>,
@@ -765,6 +1618,16 @@ impl RenderBackend for VulkanBackend {
         offsets: &[u64],
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_bind_vertex_buffers")?;
+            for buffer in buffers {
+                self.res_pool.check_buffer_usage(
+                    *buffer,
+                    BufferUsageFlags::VERTEX_BUFFER,
+                    "cmd_bind_vertex_buffers",
+                )?;
+            }
+        }
         let mut v = SmallVec::<[ash::vk::Buffer; 4]>::new();
         for buffer in buffers {
             v.push(self.res_pool.buffer.get(*buffer).unwrap().raw);
@@ -787,15 +1650,22 @@ impl RenderBackend for VulkanBackend {
         index_type: IndexType,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_bind_index_buffer")?;
+            self.res_pool.check_buffer_usage(
+                buffer,
+                BufferUsageFlags::INDEX_BUFFER,
+                "cmd_bind_index_buffer",
+            )?;
+        }
         let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
-        unsafe {
-            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?.raw().cmd_bind_index_buffer(
-                cb.raw,
-                buffer.raw,
-                offset,
-                index_type.into(),
-            );
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        if index_type == IndexType::U8
+            && !device.enabled_features().features.contains(DeviceFeatures::INDEX_TYPE_UINT8)
+        {
+            anyhow::bail!("U8 indices need DeviceFeatures::INDEX_TYPE_UINT8.");
         }
+        cb.bind_index_buffer(device, buffer, offset, index_type);
         Ok(())
     }
 
@@ -807,6 +1677,11 @@ impl RenderBackend for VulkanBackend {
         regions: &[BufferCopyRegion],
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_copy_buffer")?;
+            self.res_pool.check_buffer_usage(src, BufferUsageFlags::TRANSFER_SRC, "cmd_copy_buffer")?;
+            self.res_pool.check_buffer_usage(dst, BufferUsageFlags::TRANSFER_DST, "cmd_copy_buffer")?;
+        }
         let src = self.res_pool.buffer.get(src).context("Source buffer not found.")?;
         let dst = self.res_pool.buffer.get(dst).context("Destination buffer not found.")?;
         cb.copy_buffer(
@@ -818,19 +1693,115 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn cmd_update_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        let handle = buffer;
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_update_buffer")?;
+            self.res_pool.check_buffer_usage(
+                handle,
+                BufferUsageFlags::TRANSFER_DST,
+                "cmd_update_buffer",
+            )?;
+        }
+        check_update_buffer(offset, data.len(), buffer.size)?;
+        cb.update_buffer(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            buffer,
+            offset,
+            data,
+        );
+        Ok(())
+    }
+
+    fn cmd_fill_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+        size: u64,
+        value: u32,
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        let handle = buffer;
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_fill_buffer")?;
+            self.res_pool.check_buffer_usage(
+                handle,
+                BufferUsageFlags::TRANSFER_DST,
+                "cmd_fill_buffer",
+            )?;
+        }
+        check_fill_buffer(offset, size, buffer.size)?;
+        cb.fill_buffer(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            buffer,
+            offset,
+            size,
+            value,
+        );
+        Ok(())
+    }
+
+    fn cmd_build_acceleration_structures(
+        &self,
+        cb: Handle<CommandBuffer>,
+        acceleration_structures: &[Handle<AccelerationStructure>],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_build_acceleration_structures")?;
+        }
+        let mut structures = SmallVec::<[&VulkanAccelerationStructure; 8]>::new();
+        for handle in acceleration_structures {
+            structures.push(
+                self.res_pool
+                    .acceleration_structure
+                    .get(*handle)
+                    .context("Acceleration structure not found.")?,
+            );
+        }
+        cb.build_acceleration_structures(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            &structures,
+            &self.res_pool.buffer,
+        )
+    }
+
     fn cmd_pipeline_barrier(
         &self,
         cb: Handle<CommandBuffer>,
         src_stage_mask: PipelineStageFlags,
         dst_stage_mask: PipelineStageFlags,
+        memory_barriers: &[MemoryBarrier],
+        buffer_memory_barriers: &[BufferMemoryBarrier],
         image_memory_barriers: &[ImageMemoryBarrier],
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Any, "cmd_pipeline_barrier")?;
+            for barrier in buffer_memory_barriers {
+                let buffer =
+                    self.res_pool.buffer.get(barrier.buffer).context("Buffer not found.")?;
+                check_buffer_barrier(barrier.offset, barrier.size, buffer.size)?;
+            }
+        }
         cb.pipeline_barrier(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             src_stage_mask,
             dst_stage_mask,
+            memory_barriers,
+            buffer_memory_barriers,
             image_memory_barriers,
+            &self.res_pool.buffer,
             &self.res_pool.image,
         )?;
         Ok(())
@@ -845,6 +1816,9 @@ impl RenderBackend for VulkanBackend {
         first_instance: u32,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Inside, "cmd_draw")?;
+        }
         cb.draw(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             vertex_count,
@@ -855,6 +1829,49 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn cmd_dispatch(
+        &self,
+        cb: Handle<CommandBuffer>,
+        group_x: u32,
+        group_y: u32,
+        group_z: u32,
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_dispatch")?;
+            let limits = &device.get_adapter().properties.limits;
+            check_dispatch([group_x, group_y, group_z], limits.max_compute_work_group_count)?;
+        }
+        cb.dispatch(device, group_x, group_y, group_z);
+        Ok(())
+    }
+
+    fn cmd_dispatch_indirect(
+        &self,
+        cb: Handle<CommandBuffer>,
+        buffer: Handle<Buffer>,
+        offset: u64,
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_dispatch_indirect")?;
+            self.res_pool.check_buffer_usage(
+                buffer,
+                BufferUsageFlags::INDIRECT_BUFFER,
+                "cmd_dispatch_indirect",
+            )?;
+        }
+        let buffer = self.res_pool.buffer.get(buffer).context("Buffer not found.")?;
+        check_dispatch_indirect(offset, buffer.size)?;
+        cb.dispatch_indirect(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            buffer,
+            offset,
+        );
+        Ok(())
+    }
+
     fn cmd_draw_indexed(
         &self,
         cb: Handle<CommandBuffer>,
@@ -865,6 +1882,10 @@ impl RenderBackend for VulkanBackend {
         first_instance: u32,
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Inside, "cmd_draw_indexed")?;
+            cb.validate_indexed_draw(first_index, index_count)?;
+        }
         cb.draw_indexed(
             self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
             index_count,
@@ -885,6 +1906,19 @@ impl RenderBackend for VulkanBackend {
         regions: &[BufferImageCopyRegion],
     ) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_copy_buffer_to_image")?;
+            self.res_pool.check_buffer_usage(
+                src,
+                BufferUsageFlags::TRANSFER_SRC,
+                "cmd_copy_buffer_to_image",
+            )?;
+            self.res_pool.check_image_usage(
+                dst,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_copy_buffer_to_image",
+            )?;
+        }
         let src = self.res_pool.buffer.get(src).context("Source buffer not found.")?;
         let dst = self.res_pool.image.get(dst).context("Destination texture not found.")?;
         cb.copy_buffer_to_image(
@@ -897,6 +1931,218 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn cmd_resolve_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageResolveRegion],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_resolve_image")?;
+            self.res_pool.check_image_usage(
+                src,
+                ImageUsageFlags::TRANSFER_SRC,
+                "cmd_resolve_image",
+            )?;
+            self.res_pool.check_image_usage(
+                dst,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_resolve_image",
+            )?;
+        }
+        let src = self.res_pool.image.get(src).context("Source texture not found.")?;
+        let dst = self.res_pool.image.get(dst).context("Destination texture not found.")?;
+        if cfg!(debug_assertions) {
+            check_resolve(&src.desc, &dst.desc, regions)?;
+        }
+        cb.resolve_image(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            src,
+            src_image_layout,
+            dst,
+            dst_image_layout,
+            regions,
+        );
+        Ok(())
+    }
+
+    fn cmd_copy_image_to_buffer(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Buffer>,
+        regions: &[BufferImageCopyRegion],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_copy_image_to_buffer")?;
+            self.res_pool.check_image_usage(
+                src,
+                ImageUsageFlags::TRANSFER_SRC,
+                "cmd_copy_image_to_buffer",
+            )?;
+            self.res_pool.check_buffer_usage(
+                dst,
+                BufferUsageFlags::TRANSFER_DST,
+                "cmd_copy_image_to_buffer",
+            )?;
+        }
+        let src = self.res_pool.image.get(src).context("Source texture not found.")?;
+        let dst = self.res_pool.buffer.get(dst).context("Destination buffer not found.")?;
+        check_copy_image_to_buffer(&src.desc, dst.size, regions)?;
+        cb.copy_image_to_buffer(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            src,
+            src_image_layout,
+            dst,
+            regions,
+        );
+        Ok(())
+    }
+
+    fn cmd_copy_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        src: Handle<Image>,
+        src_image_layout: ImageLayout,
+        dst: Handle<Image>,
+        dst_image_layout: ImageLayout,
+        regions: &[ImageCopyRegion],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_copy_image")?;
+            self.res_pool.check_image_usage(
+                src,
+                ImageUsageFlags::TRANSFER_SRC,
+                "cmd_copy_image",
+            )?;
+            self.res_pool.check_image_usage(
+                dst,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_copy_image",
+            )?;
+        }
+        let src = self.res_pool.image.get(src).context("Source texture not found.")?;
+        let dst = self.res_pool.image.get(dst).context("Destination texture not found.")?;
+        check_copy_image(&src.desc, &dst.desc, regions)?;
+        cb.copy_image(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            src,
+            src_image_layout,
+            dst,
+            dst_image_layout,
+            regions,
+        );
+        Ok(())
+    }
+
+    fn cmd_clear_color_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        image: Handle<Image>,
+        image_layout: ImageLayout,
+        color: ClearColor,
+        ranges: &[ImageSubresourceRange],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_clear_color_image")?;
+            self.res_pool.check_image_usage(
+                image,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_clear_color_image",
+            )?;
+        }
+        let image = self.res_pool.image.get(image).context("Texture not found.")?;
+        check_clear_image(&image.desc, image_layout, ranges, false)?;
+        check_clear_color(image.desc.format, color).context("cmd_clear_color_image")?;
+        cb.clear_color_image(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            image,
+            image_layout,
+            color,
+            ranges,
+        );
+        Ok(())
+    }
+
+    fn cmd_clear_depth_stencil_image(
+        &self,
+        cb: Handle<CommandBuffer>,
+        image: Handle<Image>,
+        image_layout: ImageLayout,
+        value: ClearDepthStencil,
+        ranges: &[ImageSubresourceRange],
+    ) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_clear_depth_stencil_image")?;
+            self.res_pool.check_image_usage(
+                image,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_clear_depth_stencil_image",
+            )?;
+        }
+        let image = self.res_pool.image.get(image).context("Texture not found.")?;
+        check_clear_image(&image.desc, image_layout, ranges, true)?;
+        cb.clear_depth_stencil_image(
+            self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?,
+            image,
+            image_layout,
+            value,
+            ranges,
+        );
+        Ok(())
+    }
+
+    fn cmd_blit_image(&self, cb: Handle<CommandBuffer>, desc: &BlitImageDesc) -> Result<()> {
+        let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
+        if cfg!(debug_assertions) {
+            cb.validate_scope(RenderPassScope::Outside, "cmd_blit_image")?;
+            self.res_pool.check_image_usage(
+                desc.src,
+                ImageUsageFlags::TRANSFER_SRC,
+                "cmd_blit_image",
+            )?;
+            self.res_pool.check_image_usage(
+                desc.dst,
+                ImageUsageFlags::TRANSFER_DST,
+                "cmd_blit_image",
+            )?;
+        }
+        let src = self.res_pool.image.get(desc.src).context("Source texture not found.")?;
+        let dst = self.res_pool.image.get(desc.dst).context("Destination texture not found.")?;
+        let device = self.device.as_ref().context(ERR_MSG_DEVICE_NOT_CREATED)?;
+        let features = |desc: &VulkanImageDesc| {
+            let props = unsafe {
+                self.instance
+                    .raw
+                    .get_physical_device_format_properties(device.get_adapter().raw, desc.format)
+            };
+            if desc.tiling == ash::vk::ImageTiling::LINEAR {
+                props.linear_tiling_features
+            } else {
+                props.optimal_tiling_features
+            }
+        };
+        check_blit(
+            &src.desc,
+            features(&src.desc),
+            &dst.desc,
+            features(&dst.desc),
+            desc.regions,
+            desc.filter,
+        )?;
+        cb.blit_image(device, src, dst, desc);
+        Ok(())
+    }
+
     fn cmd_begin_event(
         &self,
         cb: Handle<CommandBuffer>,
@@ -925,6 +2171,22 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 
+    fn queue_begin_event(&self, queue: Handle<Queue>, name: &str, color: [f32; 4]) -> Result<()> {
+        let queue = self.res_pool.queue.get(queue).context(ERR_MSG_QUEUE_NOT_FOUND)?;
+        queue.begin_label(self.instance.debug_utils.as_ref(), name, color)
+    }
+
+    fn queue_end_event(&self, queue: Handle<Queue>) -> Result<()> {
+        let queue = self.res_pool.queue.get(queue).context(ERR_MSG_QUEUE_NOT_FOUND)?;
+        queue.end_label(self.instance.debug_utils.as_ref());
+        Ok(())
+    }
+
+    fn queue_insert_marker(&self, queue: Handle<Queue>, name: &str, color: [f32; 4]) -> Result<()> {
+        let queue = self.res_pool.queue.get(queue).context(ERR_MSG_QUEUE_NOT_FOUND)?;
+        queue.insert_label(self.instance.debug_utils.as_ref(), name, color)
+    }
+
     fn cmd_set_marker(&self, cb: Handle<CommandBuffer>, name: &str, color: [f32; 4]) -> Result<()> {
         let cb = self.res_pool.command_buffer.get(cb).context("Command buffer not found.")?;
         if let Some(debug_utils) = &self.instance.debug_utils {
@@ -938,3 +2200,109 @@ impl RenderBackend for VulkanBackend {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk;
+
+    #[test]
+    fn live_resource_report_tracks_allocations() {
+        let mut res_pool = VulkanResourcePool::new();
+        assert_eq!(res_pool.live_resources().total(), 0);
+
+        let buffer = res_pool.buffer.malloc().0;
+        res_pool.buffer.set_name(buffer, "vertices");
+        let image = res_pool.image.malloc().0;
+        res_pool.image.set_name(image, "albedo");
+
+        let report = res_pool.live_resources();
+        assert_eq!(report.count("Buffer"), 1);
+        assert_eq!(report.count("Image"), 1);
+        assert_eq!(report.total(), 2);
+        let names: Vec<_> = report.resources.iter().flat_map(|r| r.names.iter()).collect();
+        assert_eq!(names, ["albedo", "vertices"]);
+
+        res_pool.buffer.free(buffer);
+        res_pool.image.free(image);
+        let report = res_pool.live_resources();
+        assert_eq!(report.total(), 0);
+        assert!(report.resources.is_empty());
+    }
+
+    #[test]
+    fn swapchains_of_two_surfaces() {
+        let mut res_pool = VulkanResourcePool::new();
+        let mut window = |width| {
+            let surface = res_pool.surface.malloc().0;
+            let (swapchain, item) = res_pool.swapchain.malloc();
+            item.surface = Some(surface);
+            item.extent = vk::Extent2D { width, height: 600 };
+            item.present_family_index = 0;
+            (surface, swapchain)
+        };
+        let (main_surface, main) = window(800);
+        let (tool_surface, tool) = window(300);
+
+        assert_ne!(main, tool);
+        assert_eq!(res_pool.swapchain_of_surface(main_surface), Some(main));
+        assert_eq!(res_pool.swapchain_of_surface(tool_surface), Some(tool));
+        for handle in [main, tool] {
+            res_pool.swapchain.get(handle).unwrap().check_present_queue(0).unwrap();
+        }
+        assert!(res_pool.swapchain.get(tool).unwrap().check_present_queue(1).is_err());
+
+        // Recreating one window leaves the other alone
+        res_pool.swapchain.free(tool);
+        assert_eq!(res_pool.swapchain_of_surface(tool_surface), None);
+        assert_eq!(res_pool.swapchain.get(main).unwrap().extent.width, 800);
+        let (recreated, item) = res_pool.swapchain.malloc();
+        item.surface = Some(tool_surface);
+        assert_eq!(res_pool.swapchain_of_surface(tool_surface), Some(recreated));
+        assert_eq!(res_pool.swapchain_of_surface(main_surface), Some(main));
+    }
+
+    #[test]
+    fn missing_usage_is_named() {
+        let mut res_pool = VulkanResourcePool::new();
+        let (staging, item) = res_pool.buffer.malloc();
+        item.usage = BufferUsageFlags::TRANSFER_DST;
+        res_pool.buffer.set_name(staging, "staging");
+        let (vertices, item) = res_pool.buffer.malloc();
+        item.usage = BufferUsageFlags::VERTEX_BUFFER | BufferUsageFlags::TRANSFER_DST;
+        let (texture, item) = res_pool.image.malloc();
+        item.desc.usage = vk::ImageUsageFlags::SAMPLED;
+
+        let err = res_pool
+            .check_buffer_usage(staging, BufferUsageFlags::VERTEX_BUFFER, "cmd_bind_vertex_buffers")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cmd_bind_vertex_buffers"), "{err}");
+        assert!(err.contains("staging") && err.contains("VERTEX_BUFFER"), "{err}");
+
+        let err = res_pool
+            .check_buffer_usage(staging, BufferUsageFlags::TRANSFER_SRC, "cmd_copy_buffer")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("TRANSFER_SRC") && !err.contains("TRANSFER_DST"), "{err}");
+
+        // Unnamed resources are reported by handle
+        let err = res_pool
+            .check_buffer_usage(vertices, BufferUsageFlags::TRANSFER_SRC, "cmd_copy_buffer")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Handle<Buffer>(index=1, gen=0)"), "{err}");
+        assert!(res_pool
+            .check_buffer_usage(vertices, BufferUsageFlags::VERTEX_BUFFER, "cmd_bind_vertex_buffers")
+            .is_ok());
+
+        let err = res_pool
+            .check_image_usage(texture, ImageUsageFlags::TRANSFER_DST, "cmd_copy_buffer_to_image")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("TRANSFER_DST"), "{err}");
+        assert!(res_pool
+            .check_image_usage(texture, ImageUsageFlags::SAMPLED, "cmd_copy_buffer_to_image")
+            .is_ok());
+    }
+}
@@ -2,17 +2,32 @@ use anyhow::{Context, Result};
 use ash::vk;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::{Handle, Pool};
+use smallvec::SmallVec;
 
-use crate::define::{
-    PipelineLayout, PipelineLayoutCreateDesc, RasterPipeline, RasterPipelineCreateDesc,
+use crate::{
+    define::{
+        ComputePipeline, ComputePipelineCreateDesc, DescriptorSetLayout, PipelineLayout,
+        PipelineLayoutCreateDesc, RasterPipeline, RasterPipelineCreateDesc, RenderPassOutput,
+        SpecializationConstant, MAX_RENDER_TARGETS,
+    },
+    enums::{PipelineBindPoint, PrimitiveTopology},
+    flag::{DeviceFeatures, DynamicStateFlags, RequestedFeatures},
 };
 
-use super::{descriptor::VulkanDescriptorSetLayout, device::VulkanDevice, shader::VulkanShader};
+use super::{
+    descriptor::{VulkanDescriptorSet, VulkanDescriptorSetLayout},
+    device::VulkanDevice,
+    shader::VulkanShader,
+    util::{dynamic_states, format_aspects},
+};
 
 #[derive(Default)]
 pub struct VulkanPipelineLayout {
     pub handle: Option<Handle<PipelineLayout>>,
     pub raw: vk::PipelineLayout,
+    /// Set layouts in set index order.
+    pub set_layouts: SmallVec<[Handle<DescriptorSetLayout>; 4]>,
+    pub push_constant_ranges: SmallVec<[vk::PushConstantRange; 2]>,
 }
 impl_handle!(VulkanPipelineLayout, PipelineLayout, handle);
 
@@ -23,7 +38,14 @@ impl VulkanPipelineLayout {
         desc: &PipelineLayoutCreateDesc,
         p_descriptor_set_layout: &Pool<VulkanDescriptorSetLayout>,
     ) -> Result<()> {
-        let mut set_layouts = smallvec::SmallVec::<[vk::DescriptorSetLayout; 4]>::new();
+        let push_constant_ranges: SmallVec<[vk::PushConstantRange; 2]> =
+            desc.push_constant_ranges.iter().map(|range| range.into()).collect();
+        check_push_constant_ranges(
+            &push_constant_ranges,
+            device.get_adapter().properties.limits.max_push_constants_size,
+        )?;
+
+        let mut set_layouts = SmallVec::<[vk::DescriptorSetLayout; 4]>::new();
         for handle in desc.descriptor_set_layouts {
             let layout =
                 p_descriptor_set_layout.get(*handle).context("Descriptor set layout not found")?;
@@ -34,12 +56,14 @@ impl VulkanPipelineLayout {
             device.raw().create_pipeline_layout(
                 &vk::PipelineLayoutCreateInfo::builder()
                     .set_layouts(&set_layouts)
-                    .push_constant_ranges(&[])
+                    .push_constant_ranges(&push_constant_ranges)
                     .build(),
                 None,
             )?
         };
         self.raw = raw;
+        self.set_layouts = desc.descriptor_set_layouts.iter().copied().collect();
+        self.push_constant_ranges = push_constant_ranges;
         Ok(())
     }
 
@@ -48,6 +72,59 @@ impl VulkanPipelineLayout {
             device.raw().destroy_pipeline_layout(self.raw, None);
         }
         self.raw = vk::PipelineLayout::null();
+        self.set_layouts.clear();
+        self.push_constant_ranges.clear();
+    }
+
+    /// Checks that the sets bound at `first_set..first_set + n` match the layouts this pipeline
+    /// layout declares at those indices, and that each set is visible to the stages of
+    /// `bind_point`.
+    pub fn validate_bound_sets(
+        &self,
+        bind_point: PipelineBindPoint,
+        first_set: u32,
+        sets: &[&VulkanDescriptorSet],
+        p_set_layout: &Pool<VulkanDescriptorSetLayout>,
+    ) -> Result<()> {
+        let end = first_set as usize + sets.len();
+        if end > self.set_layouts.len() {
+            anyhow::bail!(
+                "Binding sets {}..{} but the pipeline layout only declares {} set layouts.",
+                first_set,
+                end,
+                self.set_layouts.len()
+            );
+        }
+        for (i, set) in sets.iter().enumerate() {
+            let index = first_set as usize + i;
+            let expected = self.set_layouts[index];
+            let actual = set.layout.context("Descriptor set has no layout.")?;
+            let actual_layout = p_set_layout.get(actual).context("Set layout not found.")?;
+            let stages = match bind_point {
+                PipelineBindPoint::Graphics => vk::ShaderStageFlags::ALL_GRAPHICS,
+                PipelineBindPoint::Compute => vk::ShaderStageFlags::COMPUTE,
+            };
+            if !actual_layout.vk_bindings.is_empty()
+                && !actual_layout.vk_bindings.iter().any(|b| b.stage_flags.intersects(stages))
+            {
+                anyhow::bail!(
+                    "Descriptor set bound at index {} has no binding visible to the {:?} bind point.",
+                    index,
+                    bind_point
+                );
+            }
+            if actual == expected {
+                continue;
+            }
+            let expected_layout = p_set_layout.get(expected).context("Set layout not found.")?;
+            if !actual_layout.is_compatible_with(expected_layout) {
+                anyhow::bail!(
+                    "Descriptor set bound at index {} doesn't match the pipeline layout's set layout.",
+                    index
+                );
+            }
+        }
+        Ok(())
     }
 }
 
@@ -67,12 +144,257 @@ impl VulkanRasterPipeline {
         desc: &RasterPipelineCreateDesc,
         p_shader: &Pool<VulkanShader>,
     ) -> anyhow::Result<()> {
+        let state = VulkanRasterPipelineState::new(render_pass, pipeline_layout, desc, p_shader)?;
+        self.raw = create_vulkan_raster_pipelines(device, &[state])?[0];
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, device: &VulkanDevice) {
+        unsafe {
+            device.raw().destroy_pipeline(self.raw, None);
+        }
+        self.raw = vk::Pipeline::null();
+    }
+}
+
+#[derive(Default)]
+pub struct VulkanComputePipeline {
+    pub raw: vk::Pipeline,
+    pub handle: Option<Handle<ComputePipeline>>,
+}
+impl_handle!(VulkanComputePipeline, ComputePipeline, handle);
+
+impl VulkanComputePipeline {
+    pub fn init(
+        &mut self,
+        device: &VulkanDevice,
+        pipeline_layout: &VulkanPipelineLayout,
+        desc: &ComputePipelineCreateDesc,
+        p_shader: &Pool<VulkanShader>,
+    ) -> anyhow::Result<()> {
+        let shader = p_shader.get(desc.shader).context("Shader not found.")?;
+        check_compute_stage(shader.stage)?;
+        let specialization = VulkanSpecialization::new(desc.specialization)?;
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader.raw)
+            .name(&shader.entry)
+            .specialization_info(&specialization.info)
+            .build();
+        let create_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(pipeline_layout.raw)
+            .build();
+        let pipelines = unsafe {
+            device.raw().create_compute_pipelines(device.pipeline_cache, &[create_info], None)
+        }
+        .map_err(|(_, e)| anyhow::anyhow!("Failed to create compute pipeline: {:?}", e))?;
+        self.raw = pipelines[0];
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, device: &VulkanDevice) {
+        unsafe {
+            device.raw().destroy_pipeline(self.raw, None);
+        }
+        self.raw = vk::Pipeline::null();
+    }
+}
+
+/// Push constant ranges are non-empty, 4 byte aligned and inside the `max_size` bytes the
+/// device supports, and each stage is in at most one of them.
+pub fn check_push_constant_ranges(
+    ranges: &[vk::PushConstantRange],
+    max_size: u32,
+) -> anyhow::Result<()> {
+    let mut seen = vk::ShaderStageFlags::empty();
+    for (i, range) in ranges.iter().enumerate() {
+        if range.size == 0 || !range.offset.is_multiple_of(4) || !range.size.is_multiple_of(4) {
+            anyhow::bail!(
+                "Push constant range {} at {} with {} bytes needs a non-zero size and both a multiple of 4.",
+                i,
+                range.offset,
+                range.size
+            );
+        }
+        if range.offset as u64 + range.size as u64 > max_size as u64 {
+            anyhow::bail!(
+                "Push constant range {} ends at byte {}, the device supports {}.",
+                i,
+                range.offset as u64 + range.size as u64,
+                max_size
+            );
+        }
+        if range.stage_flags.is_empty() || seen.intersects(range.stage_flags) {
+            anyhow::bail!(
+                "Push constant range {} is for {:?}, every stage needs exactly one range.",
+                i,
+                range.stage_flags
+            );
+        }
+        seen |= range.stage_flags;
+    }
+    Ok(())
+}
+
+/// A push of `size` bytes at `offset` is 4 byte aligned, fits in a range of every stage in
+/// `stage_flags`, and names all the stages of the ranges it overlaps.
+pub fn check_push_constants(
+    ranges: &[vk::PushConstantRange],
+    stage_flags: vk::ShaderStageFlags,
+    offset: u32,
+    size: u32,
+) -> anyhow::Result<()> {
+    if size == 0 || !offset.is_multiple_of(4) || !size.is_multiple_of(4) {
+        anyhow::bail!(
+            "cmd_push_constants of {} bytes at {} needs a non-zero size and both a multiple of 4.",
+            size,
+            offset
+        );
+    }
+    let end = offset as u64 + size as u64;
+    let mut covered = vk::ShaderStageFlags::empty();
+    for range in ranges {
+        let range_end = range.offset as u64 + range.size as u64;
+        if (range.offset as u64) < end && (offset as u64) < range_end {
+            if !stage_flags.contains(range.stage_flags) {
+                anyhow::bail!(
+                    "cmd_push_constants to bytes {}..{} for {:?} overlaps the {:?} range, which needs all of its stages.",
+                    offset,
+                    end,
+                    stage_flags,
+                    range.stage_flags
+                );
+            }
+            if range.offset <= offset && end <= range_end {
+                covered |= range.stage_flags;
+            }
+        }
+    }
+    if stage_flags.is_empty() || !covered.contains(stage_flags) {
+        anyhow::bail!(
+            "cmd_push_constants to bytes {}..{} for {:?} isn't inside a range of the pipeline layout.",
+            offset,
+            end,
+            stage_flags
+        );
+    }
+    Ok(())
+}
+
+/// A compute pipeline is made of exactly one shader, which has to target the compute stage.
+pub fn check_compute_stage(stage: vk::ShaderStageFlags) -> anyhow::Result<()> {
+    if stage != vk::ShaderStageFlags::COMPUTE {
+        anyhow::bail!("Compute pipelines need a compute shader, got a {:?} one.", stage);
+    }
+    Ok(())
+}
+
+/// Map entries and packed bytes of the specialization constants of a pipeline.
+///
+/// `info` points into the vectors, keep it boxed so shader stages can point at it while it moves.
+pub struct VulkanSpecialization {
+    pub entries: Vec<vk::SpecializationMapEntry>,
+    pub data: Vec<u8>,
+    pub info: vk::SpecializationInfo,
+}
+
+impl VulkanSpecialization {
+    pub fn new(constants: &[SpecializationConstant]) -> Result<Box<Self>> {
+        let mut entries = Vec::with_capacity(constants.len());
+        let mut data = Vec::new();
+        for constant in constants {
+            if !matches!(constant.data.len(), 1 | 2 | 4 | 8) {
+                anyhow::bail!(
+                    "Specialization constant {} has {} bytes, scalars take 1, 2, 4 or 8.",
+                    constant.id,
+                    constant.data.len()
+                );
+            }
+            if entries.iter().any(|e: &vk::SpecializationMapEntry| e.constant_id == constant.id) {
+                anyhow::bail!("Specialization constant {} is set twice.", constant.id);
+            }
+            entries.push(vk::SpecializationMapEntry {
+                constant_id: constant.id,
+                offset: data.len() as u32,
+                size: constant.data.len(),
+            });
+            data.extend_from_slice(constant.data);
+        }
+        let mut specialization =
+            Box::new(Self { entries, data, info: vk::SpecializationInfo::default() });
+        specialization.info = vk::SpecializationInfo::builder()
+            .map_entries(&specialization.entries)
+            .data(&specialization.data)
+            .build();
+        Ok(specialization)
+    }
+}
+
+/// Owns the fixed-function state a `vk::GraphicsPipelineCreateInfo` points into.
+///
+/// The nested create infos only point at heap data, so the state can be moved freely, but it
+/// must outlive the create info returned by `create_info`.
+pub struct VulkanRasterPipelineState {
+    _vertex_input_bindings: Vec<vk::VertexInputBindingDescription>,
+    _vertex_input_attributes: Vec<vk::VertexInputAttributeDescription>,
+    _color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    _specialization: Box<VulkanSpecialization>,
+    _dynamic_states: Vec<vk::DynamicState>,
+    _rendering_color_formats: Vec<vk::Format>,
+    vertex_input_state: vk::PipelineVertexInputStateCreateInfo,
+    input_assembly_state: vk::PipelineInputAssemblyStateCreateInfo,
+    dynamic_state: vk::PipelineDynamicStateCreateInfo,
+    viewport_state: vk::PipelineViewportStateCreateInfo,
+    rasterization_state: vk::PipelineRasterizationStateCreateInfo,
+    multisample_state: vk::PipelineMultisampleStateCreateInfo,
+    depth_stencil_state: vk::PipelineDepthStencilStateCreateInfo,
+    color_blend_state: vk::PipelineColorBlendStateCreateInfo,
+    tessellation_state: Option<vk::PipelineTessellationStateCreateInfo>,
+    /// Attachment formats of a pipeline used without a render pass.
+    rendering_state: Option<vk::PipelineRenderingCreateInfo>,
+    shader_stages: Vec<vk::PipelineShaderStageCreateInfo>,
+    layout: vk::PipelineLayout,
+    render_pass: vk::RenderPass,
+    subpass: u32,
+}
+
+impl VulkanRasterPipelineState {
+    pub fn new(
+        render_pass: vk::RenderPass,
+        pipeline_layout: &VulkanPipelineLayout,
+        desc: &RasterPipelineCreateDesc,
+        p_shader: &Pool<VulkanShader>,
+    ) -> Result<Self> {
+        let output = &desc.render_pass_output;
+        if desc.dynamic_rendering {
+            check_dynamic_rendering(output)?;
+        }
+        if desc.subpass >= (output.num_subpasses as u32).max(1) {
+            anyhow::bail!(
+                "Subpass {} is out of range, the render pass has {} subpasses.",
+                desc.subpass,
+                output.num_subpasses.max(1)
+            );
+        }
+        let depth_state = &desc.depth_state;
+        if (depth_state.depth_test_enable || depth_state.stencil_test_enable)
+            && !output.subpass_has_depth_stencil(desc.subpass)
+        {
+            anyhow::bail!(
+                "Depth or stencil testing is enabled but subpass {} of the render pass has no depth stencil attachment, use `DepthState::disabled()` for color-only passes.",
+                desc.subpass
+            );
+        }
+
         // Vertex Input
         let mut vertex_input_bindings = Vec::new();
         let mut vertex_input_attributes = Vec::new();
 
         if let Some(bindings) = desc.vertex_input_bindings {
             let mut binding: u32 = 0;
+            // Locations keep counting across bindings so each attribute gets its own
+            let mut location: u32 = 0;
 
             for b in bindings.iter() {
                 let vib = vk::VertexInputBindingDescription::builder()
@@ -81,7 +403,6 @@ impl VulkanRasterPipeline {
                     .input_rate(b.input_rate.into())
                     .build();
 
-                let mut location: u32 = 0;
                 for a in b.attributes.iter() {
                     let via = vk::VertexInputAttributeDescription::builder()
                         .binding(binding)
@@ -104,18 +425,28 @@ impl VulkanRasterPipeline {
             .build();
 
         // Input Assembly State
+        let raster_state = &desc.raster_state;
+        if raster_state.viewport_count == 0 {
+            anyhow::bail!("Raster pipelines need at least one viewport.");
+        }
+        if raster_state.primitive_restart_enable && !raster_state.topology.is_strip() {
+            anyhow::bail!(
+                "Primitive restart needs a strip topology, got {:?}.",
+                raster_state.topology
+            );
+        }
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-            .primitive_restart_enable(false)
+            .topology(raster_state.topology.into())
+            .primitive_restart_enable(raster_state.primitive_restart_enable)
             .build();
 
         // Viewport and Scissor
-        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
-            .dynamic_states(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR])
-            .build();
+        let dynamic_states = dynamic_states(raster_state.dynamic_states);
+        let dynamic_state =
+            vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states).build();
         let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewport_count(1)
-            .scissor_count(1)
+            .viewport_count(raster_state.viewport_count)
+            .scissor_count(raster_state.viewport_count)
             .build();
 
         // Rasterization State
@@ -126,7 +457,7 @@ impl VulkanRasterPipeline {
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
             .line_width(1.0)
-            .depth_bias_enable(false)
+            .depth_bias_enable(raster_state.dynamic_states.contains(DynamicStateFlags::DEPTH_BIAS))
             .depth_bias_constant_factor(0.0)
             .depth_bias_clamp(0.0)
             .depth_bias_slope_factor(0.0)
@@ -134,7 +465,7 @@ impl VulkanRasterPipeline {
 
         // Multisample State
         let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(subpass_samples(output, desc.subpass)?)
             .sample_shading_enable(false)
             .min_sample_shading(1.0)
             .alpha_to_coverage_enable(false)
@@ -157,7 +488,7 @@ impl VulkanRasterPipeline {
 
         for bs in desc.blend_states.iter() {
             let blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
-                .color_write_mask(vk::ColorComponentFlags::RGBA)
+                .color_write_mask(bs.color_write_mask.into())
                 .blend_enable(bs.blend_enable)
                 .src_color_blend_factor(bs.source_color.into())
                 .dst_color_blend_factor(bs.destination_color.into())
@@ -170,23 +501,26 @@ impl VulkanRasterPipeline {
             num_blend_states = num_blend_states + 1;
         }
 
-        if num_blend_states == 0 {
+        // Every color target of the subpass needs a blend state, a depth-only pass has none
+        while num_blend_states < output.subpass_num_colors(desc.subpass) {
             color_blend_attachments.push(
                 vk::PipelineColorBlendAttachmentState::builder()
                     .blend_enable(false)
                     .color_write_mask(vk::ColorComponentFlags::RGBA)
                     .build(),
             );
+            num_blend_states += 1;
         }
 
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
-            .logic_op_enable(false)
-            .logic_op(vk::LogicOp::COPY)
+            .logic_op_enable(desc.logic_op.is_some())
+            .logic_op(desc.logic_op.map_or(vk::LogicOp::COPY, |op| op.into()))
             .attachments(&color_blend_attachments)
             .blend_constants([0.0, 0.0, 0.0, 0.0])
             .build();
 
         // Shader stages
+        let specialization = VulkanSpecialization::new(desc.specialization)?;
         let mut shader_stages = Vec::new();
         for handle in desc.shader_stages.iter() {
             let shader = p_shader.get(*handle).context("Shader not found")?;
@@ -194,41 +528,1151 @@ impl VulkanRasterPipeline {
                 .stage(shader.stage.into())
                 .module(shader.raw)
                 .name(shader.entry.as_c_str())
+                .specialization_info(&specialization.info)
                 .build();
             shader_stages.push(shader_stage);
         }
 
-        // Finish setting up the pipeline and create it
-        let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
-            .vertex_input_state(&vertex_input_state)
-            .input_assembly_state(&input_assembly_state)
-            .dynamic_state(&dynamic_state)
-            .viewport_state(&viewport_state)
-            .rasterization_state(&rasterization_state)
-            .depth_stencil_state(&depth_stencil_state)
-            .color_blend_state(&color_blend_state)
-            .multisample_state(&multisample_state)
-            .stages(&shader_stages)
-            .layout(pipeline_layout.raw)
-            .render_pass(render_pass)
-            .subpass(0)
+        // Tessellation State
+        let stages = shader_stages.iter().fold(vk::ShaderStageFlags::empty(), |s, i| s | i.stage);
+        check_tessellation(stages, raster_state.topology, desc.patch_control_points)?;
+        let tessellation_state =
+            (raster_state.topology == PrimitiveTopology::PatchList).then(|| {
+                vk::PipelineTessellationStateCreateInfo::builder()
+                    .patch_control_points(desc.patch_control_points)
+                    .build()
+            });
+
+        // Rendering State
+        let rendering_color_formats: Vec<vk::Format> =
+            output.color_formats[..output.num_colors as usize].iter().map(|&f| f.into()).collect();
+        let rendering_state = desc.dynamic_rendering.then(|| {
+            let depth_stencil_format: vk::Format = output.depth_stencil_format.into();
+            let aspects = match depth_stencil_format {
+                vk::Format::UNDEFINED => vk::ImageAspectFlags::empty(),
+                format => format_aspects(format),
+            };
+            let format_of = |aspect| {
+                if aspects.contains(aspect) {
+                    depth_stencil_format
+                } else {
+                    vk::Format::UNDEFINED
+                }
+            };
+            vk::PipelineRenderingCreateInfo::builder()
+                .color_attachment_formats(&rendering_color_formats)
+                .depth_attachment_format(format_of(vk::ImageAspectFlags::DEPTH))
+                .stencil_attachment_format(format_of(vk::ImageAspectFlags::STENCIL))
+                .build()
+        });
+
+        Ok(Self {
+            _vertex_input_bindings: vertex_input_bindings,
+            _vertex_input_attributes: vertex_input_attributes,
+            _color_blend_attachments: color_blend_attachments,
+            _specialization: specialization,
+            _dynamic_states: dynamic_states,
+            _rendering_color_formats: rendering_color_formats,
+            vertex_input_state,
+            input_assembly_state,
+            dynamic_state,
+            viewport_state,
+            rasterization_state,
+            multisample_state,
+            depth_stencil_state,
+            color_blend_state,
+            tessellation_state,
+            rendering_state,
+            shader_stages,
+            layout: pipeline_layout.raw,
+            render_pass,
+            subpass: desc.subpass,
+        })
+    }
+
+    /// Core features the state relies on.
+    pub fn required_features(&self) -> RequestedFeatures {
+        let mut features = RequestedFeatures::empty();
+        if self.rasterization_state.polygon_mode != vk::PolygonMode::FILL {
+            features |= RequestedFeatures::FILL_MODE_NON_SOLID;
+        }
+        if self.color_blend_state.logic_op_enable == vk::TRUE {
+            features |= RequestedFeatures::LOGIC_OP;
+        }
+        if self.viewport_state.viewport_count > 1 {
+            features |= RequestedFeatures::MULTI_VIEWPORT;
+        }
+        for stage in self.shader_stages.iter().map(|s| s.stage) {
+            if stage == vk::ShaderStageFlags::GEOMETRY {
+                features |= RequestedFeatures::GEOMETRY_SHADER;
+            }
+            if stage.intersects(
+                vk::ShaderStageFlags::TESSELLATION_CONTROL
+                    | vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            ) {
+                features |= RequestedFeatures::TESSELLATION_SHADER;
+            }
+        }
+        features
+    }
+
+    pub fn create_info(&self) -> vk::GraphicsPipelineCreateInfo {
+        let mut info = vk::GraphicsPipelineCreateInfo::builder();
+        if let Some(tessellation_state) = &self.tessellation_state {
+            info = info.tessellation_state(tessellation_state);
+        }
+        if let Some(rendering_state) = &self.rendering_state {
+            info.p_next = rendering_state as *const _ as *const std::ffi::c_void;
+        }
+        info.vertex_input_state(&self.vertex_input_state)
+            .input_assembly_state(&self.input_assembly_state)
+            .dynamic_state(&self.dynamic_state)
+            .viewport_state(&self.viewport_state)
+            .rasterization_state(&self.rasterization_state)
+            .depth_stencil_state(&self.depth_stencil_state)
+            .color_blend_state(&self.color_blend_state)
+            .multisample_state(&self.multisample_state)
+            .stages(&self.shader_stages)
+            .layout(self.layout)
+            .render_pass(self.render_pass)
+            .subpass(self.subpass)
             .base_pipeline_handle(vk::Pipeline::null())
             .base_pipeline_index(-1)
+            .build()
+    }
+}
+
+/// Tessellation needs both a control and an evaluation shader fed with patches, and patches are
+/// only for tessellation.
+pub fn check_tessellation(
+    stages: vk::ShaderStageFlags,
+    topology: PrimitiveTopology,
+    patch_control_points: u32,
+) -> Result<()> {
+    let tessellation =
+        vk::ShaderStageFlags::TESSELLATION_CONTROL | vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+    if stages.intersects(tessellation) {
+        if !stages.contains(tessellation) {
+            anyhow::bail!("Tessellation needs both a control and an evaluation shader.");
+        }
+        if topology != PrimitiveTopology::PatchList {
+            anyhow::bail!("Tessellation shaders take PatchList, got {:?}.", topology);
+        }
+        if patch_control_points == 0 {
+            anyhow::bail!("Patches need at least one control point.");
+        }
+    } else if topology == PrimitiveTopology::PatchList || patch_control_points != 0 {
+        anyhow::bail!("Patches need tessellation shaders.");
+    }
+    Ok(())
+}
+
+/// The render pass a pipeline is created against, none when it uses dynamic rendering.
+pub fn raster_pipeline_render_pass(
+    device: &mut VulkanDevice,
+    desc: &RasterPipelineCreateDesc,
+) -> Result<vk::RenderPass> {
+    if !desc.dynamic_rendering {
+        return device.get_or_create_render_pass(&desc.render_pass_output.into());
+    }
+    if !device.enabled_features().features.contains(DeviceFeatures::DYNAMIC_RENDERING) {
+        anyhow::bail!("Dynamic rendering pipelines need DeviceFeatures::DYNAMIC_RENDERING.");
+    }
+    Ok(vk::RenderPass::null())
+}
+
+/// Sample count the pipeline rasterizes with, which every attachment the subpass draws to has
+/// to share.
+fn subpass_samples(output: &RenderPassOutput, subpass: u32) -> Result<vk::SampleCountFlags> {
+    let colors = if output.num_subpasses == 0 {
+        u8::MAX
+    } else {
+        output.subpasses[subpass as usize].color_attachments
+    };
+    let mut samples: SmallVec<[vk::SampleCountFlags; MAX_RENDER_TARGETS + 1]> =
+        (0..output.num_colors as usize)
+            .filter(|i| colors & (1 << i) != 0)
+            .map(|i| output.color_samples[i].into())
+            .collect();
+    if output.subpass_has_depth_stencil(subpass) {
+        samples.push(output.depth_stencil_samples.into());
+    }
+    match samples.split_first() {
+        Some((first, rest)) if rest.iter().any(|s| s != first) => anyhow::bail!(
+            "The attachments of subpass {} have different sample counts {:?}.",
+            subpass,
+            samples
+        ),
+        Some((first, _)) => Ok(*first),
+        None => Ok(vk::SampleCountFlags::TYPE_1),
+    }
+}
+
+/// `cmd_begin_rendering` draws to every attachment in a single pass and takes no resolve targets.
+fn check_dynamic_rendering(output: &RenderPassOutput) -> Result<()> {
+    if output.num_subpasses != 0 {
+        anyhow::bail!(
+            "Dynamic rendering has no subpasses, the render pass output has {}.",
+            output.num_subpasses
+        );
+    }
+    if output.resolve_mask != 0 {
+        anyhow::bail!("Dynamic rendering has no resolve targets, resolve with cmd_resolve_image.");
+    }
+    Ok(())
+}
+
+/// Fails when a state relies on a core feature the device wasn't created with.
+fn check_required_features(
+    states: &[VulkanRasterPipelineState],
+    enabled: RequestedFeatures,
+) -> Result<()> {
+    for state in states {
+        let missing = state.required_features() - enabled;
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "The pipeline needs {:?}, request it in `DeviceCreateDesc::requested_features`.",
+                missing
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Creates all pipelines with a single `vkCreateGraphicsPipelines` call using the device cache.
+pub fn create_vulkan_raster_pipelines(
+    device: &VulkanDevice,
+    states: &[VulkanRasterPipelineState],
+) -> Result<Vec<vk::Pipeline>> {
+    check_required_features(states, device.enabled_features().requested_features)?;
+    let infos: Vec<_> = states.iter().map(|s| s.create_info()).collect();
+    unsafe { device.raw().create_graphics_pipelines(device.pipeline_cache, &infos, None) }.map_err(
+        |(pipelines, e)| {
+            for pipeline in pipelines.into_iter().filter(|p| *p != vk::Pipeline::null()) {
+                unsafe { device.raw().destroy_pipeline(pipeline, None) };
+            }
+            anyhow::anyhow!("Failed to create graphics pipeline: {:?}", e)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        define::{
+            BlendState, DepthState, RasterState, RenderPassOutput, Shader, StencilOpState,
+            VertexInputAttribute, VertexInputBinding,
+        },
+        enums::{
+            CompareOp, Format, ImageLayout, LogicOp, PolygonMode, PrimitiveTopology,
+            RenderTargetLoadAction, RenderTargetStoreAction, SampleCount, StencilOp,
+            VertexInputRate,
+        },
+        flag::ColorComponentFlags,
+    };
+
+    fn vertex_fragment_shaders() -> (Pool<VulkanShader>, Vec<Handle<Shader>>) {
+        let mut p_shader = Pool::<VulkanShader>::with_size(4, Default::default);
+        let mut shaders = Vec::new();
+        for stage in [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT] {
+            let item = p_shader.malloc();
+            item.1.stage = stage;
+            item.1.entry = std::ffi::CString::new("main").unwrap();
+            shaders.push(item.0);
+        }
+        (p_shader, shaders)
+    }
+
+    #[test]
+    fn batch_of_five_pipeline_states() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let attributes = [VertexInputAttribute { offset: 0, format: Format::R32G32B32_SFLOAT }];
+        let bindings = [VertexInputBinding {
+            stride: 12,
+            attributes: &attributes,
+            input_rate: VertexInputRate::Vertex,
+        }];
+
+        let descs: Vec<_> = (0..5)
+            .map(|num_colors| {
+                let mut output = RenderPassOutput::builder();
+                for _ in 0..num_colors {
+                    output = output.add_color(
+                        Format::R8G8B8A8_UNORM,
+                        ImageLayout::ColorAttachmentOptimal,
+                        RenderTargetLoadAction::Clear,
+                        RenderTargetStoreAction::Store,
+                        SampleCount::Sample1,
+                    );
+                }
+                RasterPipelineCreateDesc {
+                    vertex_input_bindings: Some(&bindings),
+                    raster_state: RasterState::default(),
+                    depth_state: DepthState::disabled(),
+                    blend_states: &[],
+                    logic_op: None,
+                    shader_stages: &shaders,
+                    render_pass_output: output.build(),
+                    subpass: 0,
+                    pipeline_layout: Default::default(),
+                    specialization: &[],
+                    patch_control_points: 0,
+                    dynamic_rendering: false,
+                }
+            })
+            .collect();
+        let states: Vec<_> = descs
+            .iter()
+            .map(|desc| {
+                VulkanRasterPipelineState::new(vk::RenderPass::null(), &layout, desc, &p_shader)
+                    .unwrap()
+            })
+            .collect();
+
+        let infos: Vec<_> = states.iter().map(|s| s.create_info()).collect();
+        assert_eq!(infos.len(), 5);
+        for (num_colors, info) in infos.iter().enumerate() {
+            assert_eq!(info.stage_count, 2);
+            let stages = unsafe { std::slice::from_raw_parts(info.p_stages, 2) };
+            assert_eq!(stages[1].stage, vk::ShaderStageFlags::FRAGMENT);
+            let blend = unsafe { &*info.p_color_blend_state };
+            assert_eq!(blend.attachment_count as usize, num_colors);
+            let vertex_input = unsafe { &*info.p_vertex_input_state };
+            assert_eq!(vertex_input.vertex_attribute_description_count, 1);
+            let dynamic = unsafe { &*info.p_dynamic_state };
+            assert_eq!(dynamic.dynamic_state_count, 2);
+        }
+    }
+
+    #[test]
+    fn per_vertex_and_per_instance_bindings() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let mesh = [
+            VertexInputAttribute { offset: 0, format: Format::R32G32B32_SFLOAT },
+            VertexInputAttribute { offset: 12, format: Format::R32G32B32_SFLOAT },
+        ];
+        let transform = [
+            VertexInputAttribute { offset: 0, format: Format::R32G32B32A32_SFLOAT },
+            VertexInputAttribute { offset: 16, format: Format::R32G32B32A32_SFLOAT },
+            VertexInputAttribute { offset: 32, format: Format::R32G32B32A32_SFLOAT },
+            VertexInputAttribute { offset: 48, format: Format::R32G32B32A32_SFLOAT },
+        ];
+        let bindings = [
+            VertexInputBinding {
+                stride: 24,
+                attributes: &mesh,
+                input_rate: VertexInputRate::Vertex,
+            },
+            VertexInputBinding {
+                stride: 64,
+                attributes: &transform,
+                input_rate: VertexInputRate::Instance,
+            },
+        ];
+        let desc = RasterPipelineCreateDesc {
+            vertex_input_bindings: Some(&bindings),
+            raster_state: RasterState::default(),
+            depth_state: DepthState::disabled(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output: RenderPassOutput::builder().build(),
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+        let state =
+            VulkanRasterPipelineState::new(vk::RenderPass::null(), &layout, &desc, &p_shader)
+                .unwrap();
+
+        let vertex_input = unsafe { &*state.create_info().p_vertex_input_state };
+        let vk_bindings = unsafe {
+            std::slice::from_raw_parts(
+                vertex_input.p_vertex_binding_descriptions,
+                vertex_input.vertex_binding_description_count as usize,
+            )
+        };
+        let vk_bindings: Vec<_> =
+            vk_bindings.iter().map(|b| (b.binding, b.stride, b.input_rate)).collect();
+        assert_eq!(
+            vk_bindings,
+            vec![(0, 24, vk::VertexInputRate::VERTEX), (1, 64, vk::VertexInputRate::INSTANCE)]
+        );
+
+        let vk_attributes = unsafe {
+            std::slice::from_raw_parts(
+                vertex_input.p_vertex_attribute_descriptions,
+                vertex_input.vertex_attribute_description_count as usize,
+            )
+        };
+        let vk_attributes: Vec<_> =
+            vk_attributes.iter().map(|a| (a.location, a.binding, a.offset)).collect();
+        assert_eq!(
+            vk_attributes,
+            vec![(0, 0, 0), (1, 0, 12), (2, 1, 0), (3, 1, 16), (4, 1, 32), (5, 1, 48)]
+        );
+    }
+
+    #[test]
+    fn color_write_mask_and_logic_op() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
             .build();
+        let desc = |blend_states, logic_op| RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState::default(),
+            depth_state: DepthState::disabled(),
+            blend_states,
+            logic_op,
+            shader_stages: &shaders,
+            render_pass_output: output,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+
+        let red_only =
+            [BlendState { color_write_mask: ColorComponentFlags::R, ..Default::default() }];
+        let red_state = VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(&red_only, None),
+            &p_shader,
+        )
+        .unwrap();
+        let blend = unsafe { &*red_state.create_info().p_color_blend_state };
+        assert_eq!(blend.logic_op_enable, vk::FALSE);
+        let attachments = unsafe { std::slice::from_raw_parts(blend.p_attachments, 1) };
+        assert_eq!(attachments[0].color_write_mask, vk::ColorComponentFlags::R);
+
+        let xor_state = VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(&[], Some(LogicOp::Xor)),
+            &p_shader,
+        )
+        .unwrap();
+        let blend = unsafe { &*xor_state.create_info().p_color_blend_state };
+        assert_eq!(blend.logic_op_enable, vk::TRUE);
+        assert_eq!(blend.logic_op, vk::LogicOp::XOR);
+        let attachments = unsafe { std::slice::from_raw_parts(blend.p_attachments, 1) };
+        assert_eq!(attachments[0].color_write_mask, vk::ColorComponentFlags::RGBA);
+    }
 
-        self.raw = unsafe {
-            device
-                .raw()
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
-                .map_err(|e| anyhow::anyhow!("Failed to create graphics pipeline: {:?}", e.1))?[0]
+    #[test]
+    fn stencil_state_is_applied() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .set_depth_stencil(
+                Format::S8_UINT,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let write_mask = StencilOpState {
+            pass_op: StencilOp::Replace,
+            compare_op: CompareOp::Always,
+            write_mask: 0xFF,
+            reference: 1,
+            ..Default::default()
         };
-        Ok(())
+        let test_mask = StencilOpState {
+            compare_op: CompareOp::Equal,
+            compare_mask: 0xFF,
+            reference: 1,
+            ..Default::default()
+        };
+        let state = |front, back| {
+            VulkanRasterPipelineState::new(
+                Default::default(),
+                &layout,
+                &RasterPipelineCreateDesc {
+                    vertex_input_bindings: None,
+                    raster_state: RasterState::default(),
+                    depth_state: DepthState {
+                        stencil_test_enable: true,
+                        stencil_front: front,
+                        stencil_back: back,
+                        ..DepthState::disabled()
+                    },
+                    blend_states: &[],
+                    logic_op: None,
+                    shader_stages: &shaders,
+                    render_pass_output: output,
+                    subpass: 0,
+                    pipeline_layout: Default::default(),
+                    specialization: &[],
+                    patch_control_points: 0,
+                    dynamic_rendering: false,
+                },
+                &p_shader,
+            )
+            .unwrap()
+        };
+
+        let state = state(write_mask, test_mask);
+        let depth_stencil = unsafe { &*state.create_info().p_depth_stencil_state };
+        assert_eq!(depth_stencil.depth_test_enable, vk::FALSE);
+        assert_eq!(depth_stencil.stencil_test_enable, vk::TRUE);
+        assert_eq!(depth_stencil.front.pass_op, vk::StencilOp::REPLACE);
+        assert_eq!(depth_stencil.front.fail_op, vk::StencilOp::KEEP);
+        assert_eq!(depth_stencil.front.compare_op, vk::CompareOp::ALWAYS);
+        assert_eq!((depth_stencil.front.write_mask, depth_stencil.front.reference), (0xFF, 1));
+        assert_eq!(depth_stencil.back.compare_op, vk::CompareOp::EQUAL);
+        assert_eq!((depth_stencil.back.compare_mask, depth_stencil.back.write_mask), (0xFF, 0));
     }
 
-    pub fn destroy(&mut self, device: &VulkanDevice) {
-        unsafe {
-            device.raw().destroy_pipeline(self.raw, None);
+    #[test]
+    fn wireframe_needs_fill_mode_non_solid() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let desc = |fill_mode, logic_op| RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState { fill_mode, ..Default::default() },
+            depth_state: DepthState::disabled(),
+            blend_states: &[],
+            logic_op,
+            shader_stages: &shaders,
+            render_pass_output: output,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+        let state = |fill_mode, logic_op| {
+            VulkanRasterPipelineState::new(
+                Default::default(),
+                &layout,
+                &desc(fill_mode, logic_op),
+                &p_shader,
+            )
+            .unwrap()
+        };
+
+        let solid = [state(PolygonMode::Fill, None)];
+        assert_eq!(solid[0].required_features(), RequestedFeatures::empty());
+        assert!(check_required_features(&solid, RequestedFeatures::empty()).is_ok());
+
+        let wireframe = [state(PolygonMode::Line, None)];
+        let rasterization = unsafe { &*wireframe[0].create_info().p_rasterization_state };
+        assert_eq!(rasterization.polygon_mode, vk::PolygonMode::LINE);
+        let err = check_required_features(&wireframe, RequestedFeatures::empty()).unwrap_err();
+        assert!(err.to_string().contains("FILL_MODE_NON_SOLID"));
+        assert!(check_required_features(&wireframe, RequestedFeatures::FILL_MODE_NON_SOLID).is_ok());
+
+        let xor = [state(PolygonMode::Fill, Some(LogicOp::Xor))];
+        assert_eq!(xor[0].required_features(), RequestedFeatures::LOGIC_OP);
+        assert!(check_required_features(&xor, RequestedFeatures::FILL_MODE_NON_SOLID).is_err());
+    }
+
+    #[test]
+    fn topology_and_primitive_restart() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let state = |topology, primitive_restart_enable| {
+            let desc = RasterPipelineCreateDesc {
+                vertex_input_bindings: None,
+                raster_state: RasterState {
+                    topology,
+                    primitive_restart_enable,
+                    ..Default::default()
+                },
+                depth_state: DepthState::disabled(),
+                blend_states: &[],
+                logic_op: None,
+                shader_stages: &shaders,
+                render_pass_output: output,
+                subpass: 0,
+                pipeline_layout: Default::default(),
+                specialization: &[],
+                patch_control_points: 0,
+                dynamic_rendering: false,
+            };
+            VulkanRasterPipelineState::new(Default::default(), &layout, &desc, &p_shader)
+        };
+
+        let list = state(PrimitiveTopology::TriangleList, false).unwrap();
+        let input_assembly = unsafe { &*list.create_info().p_input_assembly_state };
+        assert_eq!(input_assembly.topology, vk::PrimitiveTopology::TRIANGLE_LIST);
+
+        let terrain = state(PrimitiveTopology::TriangleStrip, true).unwrap();
+        let input_assembly = unsafe { &*terrain.create_info().p_input_assembly_state };
+        assert_eq!(input_assembly.topology, vk::PrimitiveTopology::TRIANGLE_STRIP);
+        assert_eq!(input_assembly.primitive_restart_enable, vk::TRUE);
+
+        let overlay = state(PrimitiveTopology::LineStrip, false).unwrap();
+        let input_assembly = unsafe { &*overlay.create_info().p_input_assembly_state };
+        assert_eq!(input_assembly.topology, vk::PrimitiveTopology::LINE_STRIP);
+
+        let err = state(PrimitiveTopology::LineList, true).err().unwrap();
+        assert_eq!(err.to_string(), "Primitive restart needs a strip topology, got LineList.");
+    }
+
+    #[test]
+    fn geometry_shaders_are_attached() {
+        let (mut p_shader, mut shaders) = vertex_fragment_shaders();
+        let item = p_shader.malloc();
+        item.1.stage = vk::ShaderStageFlags::GEOMETRY;
+        item.1.entry = std::ffi::CString::new("main").unwrap();
+        shaders.push(item.0);
+        let layout = VulkanPipelineLayout::default();
+        // Points expanded to quads in the geometry stage
+        let desc = RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState {
+                topology: PrimitiveTopology::PointList,
+                ..Default::default()
+            },
+            depth_state: DepthState::disabled(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output: RenderPassOutput::builder().build(),
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+        let sprites =
+            VulkanRasterPipelineState::new(Default::default(), &layout, &desc, &p_shader).unwrap();
+        let info = sprites.create_info();
+        let stages = unsafe { std::slice::from_raw_parts(info.p_stages, info.stage_count as _) };
+        assert!(stages.iter().any(|s| s.stage == vk::ShaderStageFlags::GEOMETRY));
+        assert_eq!(sprites.required_features(), RequestedFeatures::GEOMETRY_SHADER);
+        let err = check_required_features(&[sprites], RequestedFeatures::empty()).unwrap_err();
+        assert!(err.to_string().contains("GEOMETRY_SHADER"), "{err}");
+    }
+
+    #[test]
+    fn dynamic_rendering_formats() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let builder = || {
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::B8G8R8A8_UNORM,
+                    ImageLayout::PresentSrcKhr,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .set_depth_stencil(
+                    Format::D32_SFLOAT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetLoadAction::DontCare,
+                    RenderTargetStoreAction::DontCare,
+                    SampleCount::Sample1,
+                )
+        };
+        let desc = |render_pass_output| RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState::default(),
+            depth_state: DepthState::default(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: true,
+        };
+
+        let state = VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(builder().build()),
+            &p_shader,
+        )
+        .unwrap();
+        let info = state.create_info();
+        assert_eq!(info.render_pass, vk::RenderPass::null());
+        let rendering = unsafe { &*(info.p_next as *const vk::PipelineRenderingCreateInfo) };
+        assert_eq!(rendering.s_type, vk::StructureType::PIPELINE_RENDERING_CREATE_INFO);
+        let colors = unsafe {
+            std::slice::from_raw_parts(
+                rendering.p_color_attachment_formats,
+                rendering.color_attachment_count as _,
+            )
+        };
+        assert_eq!(colors, [vk::Format::B8G8R8A8_UNORM]);
+        assert_eq!(rendering.depth_attachment_format, vk::Format::D32_SFLOAT);
+        assert_eq!(rendering.stencil_attachment_format, vk::Format::UNDEFINED);
+
+        let subpasses = builder().add_subpass(&[0], &[], true).build();
+        assert_eq!(
+            VulkanRasterPipelineState::new(
+                Default::default(),
+                &layout,
+                &desc(subpasses),
+                &p_shader
+            )
+            .err()
+            .unwrap()
+            .to_string(),
+            "Dynamic rendering has no subpasses, the render pass output has 1."
+        );
+    }
+
+    #[test]
+    fn tessellated_terrain_patches() {
+        let (mut p_shader, mut shaders) = vertex_fragment_shaders();
+        for stage in [
+            vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+        ] {
+            let item = p_shader.malloc();
+            item.1.stage = stage;
+            item.1.entry = std::ffi::CString::new("main").unwrap();
+            shaders.push(item.0);
         }
-        self.raw = vk::Pipeline::null();
+        let layout = VulkanPipelineLayout::default();
+        let desc = RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState {
+                topology: PrimitiveTopology::PatchList,
+                ..Default::default()
+            },
+            depth_state: DepthState::disabled(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output: RenderPassOutput::builder().build(),
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 4,
+            dynamic_rendering: false,
+        };
+        let terrain =
+            VulkanRasterPipelineState::new(Default::default(), &layout, &desc, &p_shader).unwrap();
+        let info = terrain.create_info();
+        assert_eq!(info.stage_count, 4);
+        assert_eq!(unsafe { (*info.p_tessellation_state).patch_control_points }, 4);
+        assert_eq!(
+            unsafe { (*info.p_input_assembly_state).topology },
+            vk::PrimitiveTopology::PATCH_LIST
+        );
+        assert_eq!(terrain.required_features(), RequestedFeatures::TESSELLATION_SHADER);
+
+        let plain = RasterPipelineCreateDesc {
+            raster_state: RasterState::default(),
+            shader_stages: &shaders[..2],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+            ..desc
+        };
+        let plain =
+            VulkanRasterPipelineState::new(Default::default(), &layout, &plain, &p_shader).unwrap();
+        assert!(plain.create_info().p_tessellation_state.is_null());
+    }
+
+    #[test]
+    fn tessellation_needs_patches() {
+        let vertex = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+        let tessellation = vertex
+            | vk::ShaderStageFlags::TESSELLATION_CONTROL
+            | vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+        assert!(check_tessellation(vertex, PrimitiveTopology::TriangleList, 0).is_ok());
+        assert!(check_tessellation(tessellation, PrimitiveTopology::PatchList, 3).is_ok());
+
+        let err = check_tessellation(tessellation, PrimitiveTopology::TriangleList, 3).unwrap_err();
+        assert_eq!(err.to_string(), "Tessellation shaders take PatchList, got TriangleList.");
+        assert!(check_tessellation(tessellation, PrimitiveTopology::PatchList, 0).is_err());
+        let control_only = vertex | vk::ShaderStageFlags::TESSELLATION_CONTROL;
+        assert!(check_tessellation(control_only, PrimitiveTopology::PatchList, 3).is_err());
+        assert!(check_tessellation(vertex, PrimitiveTopology::PatchList, 3).is_err());
+        assert!(check_tessellation(vertex, PrimitiveTopology::TriangleList, 3).is_err());
+    }
+
+    #[test]
+    fn multiple_viewports_need_multi_viewport() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let state = |viewport_count| {
+            let desc = RasterPipelineCreateDesc {
+                vertex_input_bindings: None,
+                raster_state: RasterState { viewport_count, ..Default::default() },
+                depth_state: DepthState::disabled(),
+                blend_states: &[],
+                logic_op: None,
+                shader_stages: &shaders,
+                render_pass_output: RenderPassOutput::builder().build(),
+                subpass: 0,
+                pipeline_layout: Default::default(),
+                specialization: &[],
+                patch_control_points: 0,
+                dynamic_rendering: false,
+            };
+            VulkanRasterPipelineState::new(Default::default(), &layout, &desc, &p_shader)
+        };
+
+        let single = state(1).unwrap();
+        assert_eq!(single.required_features(), RequestedFeatures::empty());
+
+        // One viewport per layer of a cube map
+        let layered = state(6).unwrap();
+        let viewport_state = unsafe { &*layered.create_info().p_viewport_state };
+        assert_eq!((viewport_state.viewport_count, viewport_state.scissor_count), (6, 6));
+        assert_eq!(layered.required_features(), RequestedFeatures::MULTI_VIEWPORT);
+        assert!(check_required_features(&[layered], RequestedFeatures::empty()).is_err());
+
+        assert!(state(0).is_err());
+    }
+
+    #[test]
+    fn requested_dynamic_states_are_added() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = RenderPassOutput::builder()
+            .set_depth_stencil(
+                Format::D32_SFLOAT,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let desc = RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState {
+                dynamic_states: DynamicStateFlags::DEPTH_BIAS | DynamicStateFlags::LINE_WIDTH,
+                ..Default::default()
+            },
+            depth_state: DepthState::default(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output: output,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+        let shadow =
+            VulkanRasterPipelineState::new(Default::default(), &layout, &desc, &p_shader).unwrap();
+        let info = shadow.create_info();
+        let dynamic = unsafe { &*info.p_dynamic_state };
+        let states = unsafe {
+            std::slice::from_raw_parts(dynamic.p_dynamic_states, dynamic.dynamic_state_count as _)
+        };
+        assert_eq!(
+            states,
+            [
+                vk::DynamicState::VIEWPORT,
+                vk::DynamicState::SCISSOR,
+                vk::DynamicState::DEPTH_BIAS,
+                vk::DynamicState::LINE_WIDTH
+            ]
+        );
+        let rasterization = unsafe { &*info.p_rasterization_state };
+        assert_eq!(rasterization.depth_bias_enable, vk::TRUE);
+    }
+
+    #[test]
+    fn depth_testing_without_depth_attachment() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let color_only = RenderPassOutput::builder()
+            .add_color(
+                Format::B8G8R8A8_SRGB,
+                ImageLayout::PresentSrcKhr,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build();
+        let desc = |depth_state| RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState::default(),
+            depth_state,
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output: color_only,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+
+        let err = VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(DepthState::default()),
+            &p_shader,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("no depth stencil attachment"));
+        let stencil_only = DepthState { stencil_test_enable: true, ..DepthState::disabled() };
+        assert!(VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(stencil_only),
+            &p_shader
+        )
+        .is_err());
+        assert!(VulkanRasterPipelineState::new(
+            Default::default(),
+            &layout,
+            &desc(DepthState::disabled()),
+            &p_shader
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn set_bound_at_wrong_index() {
+        let mut p_set_layout = Pool::<VulkanDescriptorSetLayout>::with_size(2, Default::default);
+        let mut layouts = Vec::new();
+        for descriptor_type in
+            [vk::DescriptorType::UNIFORM_BUFFER, vk::DescriptorType::COMBINED_IMAGE_SAMPLER]
+        {
+            let (handle, layout) = p_set_layout.malloc();
+            layout.vk_bindings.push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(descriptor_type)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                    .build(),
+            );
+            layouts.push(handle);
+        }
+        let pipeline_layout = VulkanPipelineLayout {
+            set_layouts: layouts.iter().copied().collect(),
+            ..Default::default()
+        };
+        let set = VulkanDescriptorSet { layout: Some(layouts[0]), ..Default::default() };
+
+        let graphics = PipelineBindPoint::Graphics;
+        assert!(pipeline_layout.validate_bound_sets(graphics, 0, &[&set], &p_set_layout).is_ok());
+        let err =
+            pipeline_layout.validate_bound_sets(graphics, 1, &[&set], &p_set_layout).unwrap_err();
+        assert!(err.to_string().contains("index 1"));
+        assert!(pipeline_layout.validate_bound_sets(graphics, 2, &[&set], &p_set_layout).is_err());
+    }
+
+    #[test]
+    fn set_bound_at_compute_bind_point() {
+        let mut p_set_layout = Pool::<VulkanDescriptorSetLayout>::with_size(2, Default::default);
+        let mut layouts = Vec::new();
+        for stage in [vk::ShaderStageFlags::COMPUTE, vk::ShaderStageFlags::FRAGMENT] {
+            let (handle, layout) = p_set_layout.malloc();
+            layout.vk_bindings.push(
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(stage)
+                    .build(),
+            );
+            layouts.push(handle);
+        }
+        let compute_set = VulkanDescriptorSet { layout: Some(layouts[0]), ..Default::default() };
+        let fragment_set = VulkanDescriptorSet { layout: Some(layouts[1]), ..Default::default() };
+        let compute_layout = VulkanPipelineLayout {
+            set_layouts: layouts[..1].iter().copied().collect(),
+            ..Default::default()
+        };
+        let fragment_layout = VulkanPipelineLayout {
+            set_layouts: layouts[1..].iter().copied().collect(),
+            ..Default::default()
+        };
+
+        let compute = PipelineBindPoint::Compute;
+        assert!(compute_layout
+            .validate_bound_sets(compute, 0, &[&compute_set], &p_set_layout)
+            .is_ok());
+        let err = fragment_layout
+            .validate_bound_sets(compute, 0, &[&fragment_set], &p_set_layout)
+            .unwrap_err();
+        assert!(err.to_string().contains("Compute bind point"));
+        assert!(compute_layout
+            .validate_bound_sets(PipelineBindPoint::Graphics, 0, &[&compute_set], &p_set_layout)
+            .is_err());
+        assert_eq!(vk::PipelineBindPoint::from(compute), vk::PipelineBindPoint::COMPUTE);
+    }
+
+    #[test]
+    fn compute_pipeline_needs_compute_shader() {
+        assert!(check_compute_stage(vk::ShaderStageFlags::COMPUTE).is_ok());
+        let err = check_compute_stage(vk::ShaderStageFlags::FRAGMENT).unwrap_err();
+        assert!(err.to_string().contains("FRAGMENT"), "{err}");
+        assert!(check_compute_stage(vk::ShaderStageFlags::ALL).is_err());
+    }
+
+    #[test]
+    fn specialization_constants_are_packed() {
+        let group_size = 128u32.to_ne_bytes();
+        let enabled = vk::TRUE.to_ne_bytes();
+        let scale = 0.5f64.to_ne_bytes();
+        let constants = [
+            SpecializationConstant { id: 0, data: &group_size },
+            SpecializationConstant { id: 3, data: &enabled },
+            SpecializationConstant { id: 1, data: &scale },
+        ];
+        let specialization = VulkanSpecialization::new(&constants).unwrap();
+        let offsets: Vec<_> = specialization.entries.iter().map(|e| (e.offset, e.size)).collect();
+        assert_eq!(offsets, [(0, 4), (4, 4), (8, 8)]);
+        assert_eq!(specialization.info.map_entry_count, 3);
+        assert_eq!(specialization.info.data_size, 16);
+        assert_eq!(specialization.info.p_data, specialization.data.as_ptr().cast());
+
+        let twice = [constants[0], constants[0]];
+        assert!(VulkanSpecialization::new(&twice).is_err());
+        let odd = [SpecializationConstant { id: 0, data: &[0; 3] }];
+        assert!(VulkanSpecialization::new(&odd).is_err());
+        assert_eq!(VulkanSpecialization::new(&[]).unwrap().info.map_entry_count, 0);
+    }
+
+    #[test]
+    fn push_constant_ranges_and_pushes() {
+        let range = |stage_flags, offset, size| vk::PushConstantRange { stage_flags, offset, size };
+        let vertex = vk::ShaderStageFlags::VERTEX;
+        let fragment = vk::ShaderStageFlags::FRAGMENT;
+        // A Mat4 for the vertex stage followed by a color for the fragment stage
+        let ranges = [range(vertex, 0, 64), range(fragment, 64, 16)];
+        assert!(check_push_constant_ranges(&ranges, 128).is_ok());
+        assert_eq!(
+            check_push_constant_ranges(&ranges, 64).unwrap_err().to_string(),
+            "Push constant range 1 ends at byte 80, the device supports 64."
+        );
+        assert!(check_push_constant_ranges(&[range(vertex, 2, 64)], 128).is_err());
+        assert!(check_push_constant_ranges(&[range(vertex, 0, 0)], 128).is_err());
+        assert!(check_push_constant_ranges(&[range(vertex, 0, 16), range(vertex, 16, 16)], 128)
+            .is_err());
+        assert!(check_push_constant_ranges(&[], 128).is_ok());
+
+        assert!(check_push_constants(&ranges, vertex, 0, 64).is_ok());
+        assert!(check_push_constants(&ranges, vertex, 16, 16).is_ok());
+        assert!(check_push_constants(&ranges, fragment, 64, 16).is_ok());
+        assert!(check_push_constants(&ranges, vertex | fragment, 0, 80).is_err());
+        assert_eq!(
+            check_push_constants(&ranges, fragment, 0, 64).unwrap_err().to_string(),
+            "cmd_push_constants to bytes 0..64 for FRAGMENT overlaps the VERTEX range, which needs all of its stages."
+        );
+        assert!(check_push_constants(&ranges, vertex, 60, 8).is_err());
+        assert!(check_push_constants(&ranges, vertex, 2, 4).is_err());
+        assert!(check_push_constants(&[], vertex, 0, 4).is_err());
+
+        // One range shared by both stages has to be pushed for both
+        let shared = [range(vertex | fragment, 0, 64)];
+        assert!(check_push_constants(&shared, vertex | fragment, 0, 64).is_ok());
+        assert!(check_push_constants(&shared, vertex, 0, 64).is_err());
+    }
+
+    #[test]
+    fn multisampled_output_sets_rasterization_samples() {
+        let (p_shader, shaders) = vertex_fragment_shaders();
+        let layout = VulkanPipelineLayout::default();
+        let output = |depth_samples| {
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::R8G8B8A8_UNORM,
+                    ImageLayout::ColorAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample4,
+                )
+                .set_depth_stencil(
+                    Format::D32_SFLOAT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetLoadAction::DontCare,
+                    RenderTargetStoreAction::DontCare,
+                    depth_samples,
+                )
+                .build()
+        };
+        let desc = |render_pass_output| RasterPipelineCreateDesc {
+            vertex_input_bindings: None,
+            raster_state: RasterState::default(),
+            depth_state: DepthState::default(),
+            blend_states: &[],
+            logic_op: None,
+            shader_stages: &shaders,
+            render_pass_output,
+            subpass: 0,
+            pipeline_layout: Default::default(),
+            specialization: &[],
+            patch_control_points: 0,
+            dynamic_rendering: false,
+        };
+
+        let state = VulkanRasterPipelineState::new(
+            vk::RenderPass::null(),
+            &layout,
+            &desc(output(SampleCount::Sample4)),
+            &p_shader,
+        )
+        .unwrap();
+        let multisample = unsafe { &*state.create_info().p_multisample_state };
+        assert_eq!(multisample.rasterization_samples, vk::SampleCountFlags::TYPE_4);
+
+        let err = VulkanRasterPipelineState::new(
+            vk::RenderPass::null(),
+            &layout,
+            &desc(output(SampleCount::Sample1)),
+            &p_shader,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "The attachments of subpass 0 have different sample counts [TYPE_4, TYPE_1]."
+        );
     }
 }
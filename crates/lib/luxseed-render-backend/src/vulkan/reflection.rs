@@ -0,0 +1,370 @@
+//! Descriptor set layouts read back from SPIR-V, so they don't have to mirror the shader by hand.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    define::{DescriptorBindingInfo, DescriptorSetLayoutCreateDesc},
+    enums::DescriptorType,
+    flag::{DescriptorBindingFlags, ShaderStageFlags},
+};
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_TYPE_ACCELERATION_STRUCTURE: u32 = 5341;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+/// `Sampled` operand of `OpTypeImage` for images used without a sampler.
+const IMAGE_STORAGE: u32 = 2;
+
+enum SpirvType {
+    Sampler,
+    SampledImage,
+    Image { dim: u32, sampled: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Pointer { storage_class: u32, pointee: u32 },
+    AccelerationStructure,
+}
+
+/// Reflects the descriptor bindings of a SPIR-V module, one layout per set up to the highest set
+/// used. Sets in between come back empty so the result can back a pipeline layout as is.
+///
+/// SPIR-V can't tell dynamic buffers apart from plain ones, and runtime sized arrays come back
+/// with a count of 1; adjust those before creating the layout. Arrays sized by a specialization
+/// constant get its default value.
+pub fn reflect_descriptor_layouts(code: &[u32]) -> Result<Vec<DescriptorSetLayoutCreateDesc>> {
+    if code.len() < HEADER_WORDS || code[0] != SPIRV_MAGIC {
+        bail!("Not a SPIR-V module.");
+    }
+
+    let mut stage_flags = ShaderStageFlags::empty();
+    let mut types = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut sets = HashMap::new();
+    let mut bindings = HashMap::new();
+    let mut buffer_blocks = HashSet::new();
+    let mut variables = Vec::new();
+
+    let mut words = &code[HEADER_WORDS..];
+    while let Some(&first) = words.first() {
+        let (count, opcode) = ((first >> 16) as usize, first & 0xffff);
+        if count == 0 || count > words.len() {
+            bail!("Malformed SPIR-V instruction at word {}.", code.len() - words.len());
+        }
+        let ops = &words[1..count];
+        words = &words[count..];
+        let operand = |i: usize| match ops.get(i) {
+            Some(word) => Ok(*word),
+            None => bail!("SPIR-V instruction {} is missing operands.", opcode),
+        };
+
+        match opcode {
+            OP_ENTRY_POINT => stage_flags |= execution_model_stage(operand(0)?),
+            OP_TYPE_IMAGE => {
+                let image = SpirvType::Image { dim: operand(2)?, sampled: operand(6)? };
+                types.insert(operand(0)?, image);
+            }
+            OP_TYPE_SAMPLER => {
+                types.insert(operand(0)?, SpirvType::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                types.insert(operand(0)?, SpirvType::SampledImage);
+            }
+            OP_TYPE_ACCELERATION_STRUCTURE => {
+                types.insert(operand(0)?, SpirvType::AccelerationStructure);
+            }
+            OP_TYPE_ARRAY => {
+                let array = SpirvType::Array { element: operand(1)?, length: operand(2)? };
+                types.insert(operand(0)?, array);
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                types.insert(operand(0)?, SpirvType::RuntimeArray { element: operand(1)? });
+            }
+            OP_TYPE_POINTER => {
+                let pointer =
+                    SpirvType::Pointer { storage_class: operand(1)?, pointee: operand(2)? };
+                types.insert(operand(0)?, pointer);
+            }
+            // Only the low word matters for array lengths
+            OP_CONSTANT | OP_SPEC_CONSTANT => {
+                constants.insert(operand(1)?, operand(2)?);
+            }
+            OP_VARIABLE => variables.push((operand(1)?, operand(0)?)),
+            OP_DECORATE => match operand(1)? {
+                DECORATION_DESCRIPTOR_SET => {
+                    sets.insert(operand(0)?, operand(2)?);
+                }
+                DECORATION_BINDING => {
+                    bindings.insert(operand(0)?, operand(2)?);
+                }
+                DECORATION_BUFFER_BLOCK => {
+                    buffer_blocks.insert(operand(0)?);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    if stage_flags.is_empty() {
+        bail!("SPIR-V module has no entry point.");
+    }
+
+    let mut layouts = Vec::new();
+    for (id, type_id) in variables {
+        let Some(&binding) = bindings.get(&id) else {
+            continue;
+        };
+        // GLSL leaves the set out when it is 0
+        let set = sets.get(&id).copied().unwrap_or(0);
+        let Some(&SpirvType::Pointer { storage_class, pointee }) = types.get(&type_id) else {
+            continue;
+        };
+        let (element, count) = match types.get(&pointee) {
+            Some(&SpirvType::Array { element, length }) => match constants.get(&length) {
+                Some(&length) => (element, length),
+                None => {
+                    bail!("Array length of binding {} in set {} is not a constant.", binding, set)
+                }
+            },
+            Some(&SpirvType::RuntimeArray { element }) => (element, 1),
+            _ => (pointee, 1),
+        };
+        let type_ = match storage_class {
+            STORAGE_CLASS_UNIFORM_CONSTANT => match types.get(&element) {
+                Some(SpirvType::Sampler) => DescriptorType::Sampler,
+                Some(SpirvType::SampledImage) => DescriptorType::CombinedImageSampler,
+                Some(&SpirvType::Image { dim, sampled }) => image_descriptor_type(dim, sampled),
+                Some(SpirvType::AccelerationStructure) => DescriptorType::AccelerationStructure,
+                _ => continue,
+            },
+            // Before SPIR-V 1.3 storage buffers were uniform blocks decorated as BufferBlock
+            STORAGE_CLASS_UNIFORM if buffer_blocks.contains(&element) => {
+                DescriptorType::StorageBuffer
+            }
+            STORAGE_CLASS_UNIFORM => DescriptorType::UniformBuffer,
+            STORAGE_CLASS_STORAGE_BUFFER => DescriptorType::StorageBuffer,
+            _ => continue,
+        };
+        if binding > u16::MAX as u32 || count > u16::MAX as u32 {
+            bail!("Binding {} in set {} is out of range.", binding, set);
+        }
+        let info = DescriptorBindingInfo {
+            index: binding as u16,
+            type_,
+            count: count as u16,
+            stage_flags,
+            flags: DescriptorBindingFlags::empty(),
+        };
+        add_binding(&mut layouts, set as usize, info)?;
+    }
+    Ok(layouts)
+}
+
+/// Merges the layouts reflected from another stage of the same pipeline. Bindings both stages
+/// use get the stage flags of both, and have to agree on their type.
+pub fn merge_descriptor_layouts(
+    layouts: &mut Vec<DescriptorSetLayoutCreateDesc>,
+    other: &[DescriptorSetLayoutCreateDesc],
+) -> Result<()> {
+    for (set, layout) in other.iter().enumerate() {
+        if layouts.len() <= set {
+            layouts.resize_with(set + 1, Default::default);
+        }
+        for info in &layout.bindings {
+            add_binding(layouts, set, *info)?;
+        }
+    }
+    Ok(())
+}
+
+fn add_binding(
+    layouts: &mut Vec<DescriptorSetLayoutCreateDesc>,
+    set: usize,
+    info: DescriptorBindingInfo,
+) -> Result<()> {
+    if layouts.len() <= set {
+        layouts.resize_with(set + 1, Default::default);
+    }
+    let bindings = &mut layouts[set].bindings;
+    match bindings.iter_mut().find(|b| b.index == info.index) {
+        Some(existing) if existing.type_ != info.type_ => bail!(
+            "Binding {} in set {} is used as both {:?} and {:?}.",
+            info.index,
+            set,
+            existing.type_,
+            info.type_
+        ),
+        Some(existing) => {
+            existing.stage_flags |= info.stage_flags;
+            existing.count = existing.count.max(info.count);
+        }
+        None => {
+            let at = bindings.iter().position(|b| b.index > info.index).unwrap_or(bindings.len());
+            bindings.insert(at, info);
+        }
+    }
+    Ok(())
+}
+
+fn image_descriptor_type(dim: u32, sampled: u32) -> DescriptorType {
+    match (dim, sampled) {
+        (DIM_BUFFER, IMAGE_STORAGE) => DescriptorType::StorageTexelBuffer,
+        (DIM_BUFFER, _) => DescriptorType::UniformTexelBuffer,
+        (DIM_SUBPASS_DATA, _) => DescriptorType::InputAttachment,
+        (_, IMAGE_STORAGE) => DescriptorType::StorageImage,
+        _ => DescriptorType::SampledImage,
+    }
+}
+
+/// Models without a stage flag of their own, like ray tracing or mesh shaders, make the bindings
+/// visible to every stage.
+fn execution_model_stage(model: u32) -> ShaderStageFlags {
+    match model {
+        0 => ShaderStageFlags::VERTEX,
+        1 => ShaderStageFlags::TESSELLATION_CONTROL,
+        2 => ShaderStageFlags::TESSELLATION_EVALUATION,
+        3 => ShaderStageFlags::GEOMETRY,
+        4 => ShaderStageFlags::FRAGMENT,
+        5 => ShaderStageFlags::COMPUTE,
+        _ => ShaderStageFlags::ALL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// A module with the given execution model and extra instructions, `main` as its entry.
+    fn module(model: u32, body: &[Vec<u32>]) -> Vec<u32> {
+        let main = u32::from_le_bytes(*b"main");
+        let mut code = vec![SPIRV_MAGIC, 0x0001_0000, 0, 100, 0];
+        code.extend(op(OP_ENTRY_POINT, &[model, 1, main, 0]));
+        body.iter().for_each(|inst| code.extend(inst));
+        code
+    }
+
+    fn decorate(id: u32, set: u32, binding: u32) -> [Vec<u32>; 2] {
+        [
+            op(OP_DECORATE, &[id, DECORATION_DESCRIPTOR_SET, set]),
+            op(OP_DECORATE, &[id, DECORATION_BINDING, binding]),
+        ]
+    }
+
+    #[test]
+    fn bindings_come_from_the_module() {
+        let mut body = vec![
+            // Uniform block at set 0 binding 0, an array of 4 textures at set 1 binding 2
+            op(OP_TYPE_POINTER, &[10, STORAGE_CLASS_UNIFORM, 11]),
+            op(OP_VARIABLE, &[10, 20, STORAGE_CLASS_UNIFORM]),
+            op(OP_TYPE_IMAGE, &[30, 31, 1, 0, 0, 0, 1, 0]),
+            op(OP_TYPE_SAMPLED_IMAGE, &[32, 30]),
+            op(OP_CONSTANT, &[33, 34, 4]),
+            op(OP_TYPE_ARRAY, &[35, 32, 34]),
+            op(OP_TYPE_POINTER, &[36, STORAGE_CLASS_UNIFORM_CONSTANT, 35]),
+            op(OP_VARIABLE, &[36, 21, STORAGE_CLASS_UNIFORM_CONSTANT]),
+            // Storage image, not decorated so not a descriptor
+            op(OP_TYPE_IMAGE, &[40, 31, 1, 0, 0, 0, IMAGE_STORAGE, 1]),
+            op(OP_TYPE_POINTER, &[41, STORAGE_CLASS_UNIFORM_CONSTANT, 40]),
+            op(OP_VARIABLE, &[41, 22, STORAGE_CLASS_UNIFORM_CONSTANT]),
+        ];
+        body.extend(decorate(20, 0, 0));
+        body.extend(decorate(21, 1, 2));
+
+        let layouts = reflect_descriptor_layouts(&module(4, &body)).unwrap();
+        assert_eq!(layouts.len(), 2);
+        let uniform = layouts[0].bindings[0];
+        assert_eq!((uniform.index, uniform.type_), (0, DescriptorType::UniformBuffer));
+        assert_eq!(uniform.stage_flags, ShaderStageFlags::FRAGMENT);
+        let textures = layouts[1].bindings[0];
+        assert_eq!((textures.index, textures.count), (2, 4));
+        assert_eq!(textures.type_, DescriptorType::CombinedImageSampler);
+    }
+
+    #[test]
+    fn stages_merge_into_one_layout() {
+        let body = |storage_class| {
+            let mut body = vec![
+                op(OP_TYPE_POINTER, &[10, storage_class, 11]),
+                op(OP_VARIABLE, &[10, 20, storage_class]),
+            ];
+            body.extend(decorate(20, 0, 1));
+            body
+        };
+        let vertex = reflect_descriptor_layouts(&module(0, &body(STORAGE_CLASS_UNIFORM))).unwrap();
+        let fragment =
+            reflect_descriptor_layouts(&module(4, &body(STORAGE_CLASS_UNIFORM))).unwrap();
+
+        let mut layouts = vertex;
+        merge_descriptor_layouts(&mut layouts, &fragment).unwrap();
+        assert_eq!(layouts[0].bindings.len(), 1);
+        assert_eq!(
+            layouts[0].bindings[0].stage_flags,
+            ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT
+        );
+
+        let compute =
+            reflect_descriptor_layouts(&module(5, &body(STORAGE_CLASS_STORAGE_BUFFER))).unwrap();
+        assert_eq!(
+            merge_descriptor_layouts(&mut layouts, &compute).unwrap_err().to_string(),
+            "Binding 1 in set 0 is used as both UniformBuffer and StorageBuffer."
+        );
+    }
+
+    #[test]
+    fn spec_constant_arrays_and_other_stages() {
+        let mut body = vec![
+            // `layout(constant_id = 0) const uint COUNT = 8; uniform sampler2D textures[COUNT];`
+            op(OP_TYPE_IMAGE, &[30, 31, 1, 0, 0, 0, 1, 0]),
+            op(OP_TYPE_SAMPLED_IMAGE, &[32, 30]),
+            op(OP_SPEC_CONSTANT, &[33, 34, 8]),
+            op(OP_TYPE_ARRAY, &[35, 32, 34]),
+            op(OP_TYPE_POINTER, &[36, STORAGE_CLASS_UNIFORM_CONSTANT, 35]),
+            op(OP_VARIABLE, &[36, 21, STORAGE_CLASS_UNIFORM_CONSTANT]),
+        ];
+        body.extend(decorate(21, 0, 0));
+
+        let layouts = reflect_descriptor_layouts(&module(5, &body)).unwrap();
+        assert_eq!(layouts[0].bindings[0].count, 8);
+        // A ray generation shader
+        let layouts = reflect_descriptor_layouts(&module(5313, &body)).unwrap();
+        assert_eq!(layouts[0].bindings[0].stage_flags, ShaderStageFlags::ALL);
+    }
+
+    #[test]
+    fn rejects_broken_modules() {
+        assert!(reflect_descriptor_layouts(&[0; 5]).is_err());
+        assert!(reflect_descriptor_layouts(&module(4, &[vec![0]])).is_err());
+        let mut no_entry = module(4, &[]);
+        no_entry.truncate(HEADER_WORDS);
+        assert!(reflect_descriptor_layouts(&no_entry).is_err());
+    }
+}
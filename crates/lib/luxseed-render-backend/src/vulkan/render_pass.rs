@@ -2,10 +2,20 @@ use anyhow::Ok;
 use ash::vk;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::Handle;
+use std::{
+    ffi::c_void,
+    hash::{Hash, Hasher},
+};
 
-use crate::{define::RenderPass, MAX_RENDER_TARGETS};
+use crate::{
+    define::{RenderPass, SubpassDesc},
+    MAX_RENDER_TARGETS, MAX_SUBPASSES,
+};
 
-use super::device::VulkanDevice;
+use super::{
+    device::VulkanDevice,
+    util::{format_aspects, has_stencil_aspect, is_depth_stencil_format, separate_layout_aspect},
+};
 
 #[derive(Default)]
 pub struct VulkanRenderPass {
@@ -30,18 +40,344 @@ impl VulkanRenderPass {
     }
 }
 
-#[derive(Default, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+/// Compares and hashes only what describes the render pass, see `key`, so outputs that differ
+/// in unused slots share one cached render pass.
+#[derive(Default, Clone, Copy, Debug)]
 pub struct VulkanRenderPassOutput {
     pub num_colors: u8,
     pub color_formats: [vk::Format; MAX_RENDER_TARGETS],
     pub color_final_layouts: [vk::ImageLayout; MAX_RENDER_TARGETS],
     pub color_load: [vk::AttachmentLoadOp; MAX_RENDER_TARGETS],
+    pub color_store: [vk::AttachmentStoreOp; MAX_RENDER_TARGETS],
     pub color_samples: [vk::SampleCountFlags; MAX_RENDER_TARGETS],
     pub depth_stencil_format: vk::Format,
     pub depth_stencil_final_layout: vk::ImageLayout,
     pub depth_stencil_samples: vk::SampleCountFlags,
     pub depth_load: vk::AttachmentLoadOp,
     pub stencil_load: vk::AttachmentLoadOp,
+    pub depth_stencil_store: vk::AttachmentStoreOp,
+    pub resolve_mask: u8,
+    pub resolve_formats: [vk::Format; MAX_RENDER_TARGETS],
+    pub resolve_final_layouts: [vk::ImageLayout; MAX_RENDER_TARGETS],
+    pub num_subpasses: u8,
+    pub subpasses: [SubpassDesc; MAX_SUBPASSES],
+}
+
+/// Layouts of each aspect of a combined depth stencil attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeparateDepthStencilLayouts {
+    pub depth_initial: vk::ImageLayout,
+    pub depth_final: vk::ImageLayout,
+    pub stencil_initial: vk::ImageLayout,
+    pub stencil_final: vk::ImageLayout,
+}
+
+/// What describes a render pass, borrowed from a `VulkanRenderPassOutput`.
+#[derive(PartialEq, Eq, Hash)]
+struct RenderPassKey<'a> {
+    color_formats: &'a [vk::Format],
+    color_final_layouts: &'a [vk::ImageLayout],
+    color_load: &'a [vk::AttachmentLoadOp],
+    color_store: &'a [vk::AttachmentStoreOp],
+    color_samples: &'a [vk::SampleCountFlags],
+    depth_stencil: Option<(
+        vk::Format,
+        vk::ImageLayout,
+        vk::SampleCountFlags,
+        vk::AttachmentLoadOp,
+        vk::AttachmentLoadOp,
+        vk::AttachmentStoreOp,
+    )>,
+    resolves: [Option<(vk::Format, vk::ImageLayout)>; MAX_RENDER_TARGETS],
+    subpasses: &'a [SubpassDesc],
+}
+
+impl PartialEq for VulkanRenderPassOutput {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for VulkanRenderPassOutput {}
+
+impl Hash for VulkanRenderPassOutput {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+impl VulkanRenderPassOutput {
+    /// Color slots past `num_colors`, subpasses past `num_subpasses` and the depth stencil
+    /// settings without a depth stencil attachment are left out.
+    fn key(&self) -> RenderPassKey<'_> {
+        let colors = self.num_colors as usize;
+        RenderPassKey {
+            color_formats: &self.color_formats[..colors],
+            color_final_layouts: &self.color_final_layouts[..colors],
+            color_load: &self.color_load[..colors],
+            color_store: &self.color_store[..colors],
+            color_samples: &self.color_samples[..colors],
+            depth_stencil: self.has_depth_stencil().then_some((
+                self.depth_stencil_format,
+                self.depth_stencil_final_layout,
+                self.depth_stencil_samples,
+                self.depth_load,
+                self.stencil_load,
+                self.depth_stencil_store,
+            )),
+            resolves: std::array::from_fn(|i| {
+                self.is_resolved(i)
+                    .then_some((self.resolve_formats[i], self.resolve_final_layouts[i]))
+            }),
+            subpasses: &self.subpasses[..self.num_subpasses as usize],
+        }
+    }
+
+    #[inline]
+    pub fn is_resolved(&self, color: usize) -> bool {
+        self.resolve_mask & (1 << color) != 0
+    }
+
+    /// Number of resolve attachments, the framebuffer needs as many resolve views.
+    #[inline]
+    pub fn num_resolves(&self) -> u8 {
+        self.resolve_mask.count_ones() as u8
+    }
+
+    /// Formats the resolve views have to match, in color order.
+    pub fn resolve_formats(&self) -> Vec<vk::Format> {
+        (0..MAX_RENDER_TARGETS)
+            .filter(|&i| self.is_resolved(i))
+            .map(|i| self.resolve_formats[i])
+            .collect()
+    }
+
+    /// Framebuffer index of the attachment color `color` resolves into.
+    pub fn resolve_attachment(&self, color: usize) -> Option<u32> {
+        if !self.is_resolved(color) {
+            return None;
+        }
+        let before = (self.resolve_mask & ((1u16 << color) - 1) as u8).count_ones();
+        Some(self.num_colors as u32 + self.has_depth_stencil() as u32 + before)
+    }
+
+    /// Fails when a subpass writes or reads a color the render pass doesn't have.
+    pub fn check_subpasses(&self) -> anyhow::Result<()> {
+        let colors = ((1u16 << self.num_colors) - 1) as u8;
+        for (i, subpass) in self.subpasses[..self.num_subpasses as usize].iter().enumerate() {
+            let used = subpass.color_attachments | subpass.input_attachments;
+            if used & !colors != 0 {
+                anyhow::bail!(
+                    "Subpass {} uses color attachments {:#010b} but the render pass has {} colors.",
+                    i,
+                    used,
+                    self.num_colors
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// A color can only be resolved from more than one sample into the same format.
+    pub fn check_resolve_targets(&self) -> anyhow::Result<()> {
+        for i in (0..MAX_RENDER_TARGETS).filter(|&i| self.is_resolved(i)) {
+            if i >= self.num_colors as usize {
+                anyhow::bail!(
+                    "Resolve target of color {} but the render pass has {} colors.",
+                    i,
+                    self.num_colors
+                );
+            }
+            if self.color_samples[i] == vk::SampleCountFlags::TYPE_1 {
+                anyhow::bail!("Color {} is resolved but it has a single sample.", i);
+            }
+            if self.resolve_formats[i] != self.color_formats[i] {
+                anyhow::bail!(
+                    "Color {} is {:?} but its resolve target is {:?}.",
+                    i,
+                    self.color_formats[i],
+                    self.resolve_formats[i]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn has_depth_stencil(&self) -> bool {
+        self.depth_stencil_format != vk::Format::UNDEFINED
+    }
+
+    /// The explicit subpasses, or the implicit one using every attachment.
+    pub fn subpass_descs(&self) -> Vec<SubpassDesc> {
+        if self.num_subpasses == 0 {
+            let all_colors = ((1u16 << self.num_colors) - 1) as u8;
+            return vec![SubpassDesc {
+                color_attachments: all_colors,
+                input_attachments: 0,
+                use_depth_stencil: self.has_depth_stencil(),
+            }];
+        }
+        self.subpasses[..self.num_subpasses as usize].to_vec()
+    }
+
+    /// Each subpass waits for the attachment writes of the previous one before reading them.
+    pub fn subpass_dependencies(&self) -> Vec<vk::SubpassDependency2> {
+        (1..self.subpass_descs().len() as u32)
+            .map(|dst| {
+                vk::SubpassDependency2::builder()
+                    .src_subpass(dst - 1)
+                    .dst_subpass(dst)
+                    .src_stage_mask(
+                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                    )
+                    .dst_stage_mask(
+                        vk::PipelineStageFlags::FRAGMENT_SHADER
+                            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                    )
+                    .src_access_mask(
+                        vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    )
+                    .dst_access_mask(
+                        vk::AccessFlags::INPUT_ATTACHMENT_READ
+                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+                    )
+                    .dependency_flags(vk::DependencyFlags::BY_REGION)
+                    .build()
+            })
+            .collect()
+    }
+
+    /// Per-aspect layouts of the depth stencil attachment, when a combined depth stencil format
+    /// ends in a depth or stencil only layout. The other aspect stays in its attachment layout.
+    ///
+    /// Depth or stencil only layouts need the separate depth stencil layouts feature and must
+    /// match an aspect of the format.
+    pub fn separate_depth_stencil_layouts(
+        &self,
+        separate_depth_stencil_layouts: bool,
+    ) -> anyhow::Result<Option<SeparateDepthStencilLayouts>> {
+        let final_layout = self.depth_stencil_final_layout;
+        let aspect = match separate_layout_aspect(final_layout) {
+            Some(aspect) if self.has_depth_stencil() => aspect,
+            _ => return Ok(None),
+        };
+        if !separate_depth_stencil_layouts {
+            anyhow::bail!("{:?} needs the separate depth stencil layouts feature.", final_layout);
+        }
+        if !format_aspects(self.depth_stencil_format).contains(aspect) {
+            anyhow::bail!(
+                "{:?} only applies to the {:?} aspect, {:?} has none.",
+                final_layout,
+                aspect,
+                self.depth_stencil_format
+            );
+        }
+        // A single aspect format takes the layout as is
+        if !is_depth_stencil_format(self.depth_stencil_format) {
+            return Ok(None);
+        }
+
+        let loaded = self.depth_load == vk::AttachmentLoadOp::LOAD
+            || self.stencil_load == vk::AttachmentLoadOp::LOAD;
+        let (depth_initial, stencil_initial) = if loaded {
+            (vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL, vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL)
+        } else {
+            (vk::ImageLayout::UNDEFINED, vk::ImageLayout::UNDEFINED)
+        };
+        let (depth_final, stencil_final) = if aspect == vk::ImageAspectFlags::DEPTH {
+            (final_layout, vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL)
+        } else {
+            (vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL, final_layout)
+        };
+        Ok(Some(SeparateDepthStencilLayouts {
+            depth_initial,
+            depth_final,
+            stencil_initial,
+            stencil_final,
+        }))
+    }
+
+    /// Attachment descriptions in framebuffer order: colors first, then the optional depth stencil,
+    /// then the resolve targets.
+    ///
+    /// `num_colors` may be zero, which describes a depth-only pass such as a shadow map.
+    pub fn attachment_descriptions(&self) -> Vec<vk::AttachmentDescription2> {
+        let mut attachments = Vec::with_capacity(self.num_colors as usize + 1);
+
+        // Color
+        for i in 0..self.num_colors as usize {
+            let color_initial = if self.color_load[i] == vk::AttachmentLoadOp::LOAD {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::UNDEFINED
+            };
+
+            attachments.push(
+                vk::AttachmentDescription2::builder()
+                    .format(self.color_formats[i])
+                    .samples(self.color_samples[i])
+                    .load_op(self.color_load[i])
+                    .store_op(self.color_store[i])
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(color_initial)
+                    .final_layout(self.color_final_layouts[i])
+                    .build(),
+            );
+        }
+
+        // Depth stencil, only keep the content if it is loaded. A depth texture sampled in a
+        // previous frame is not in the attachment layout anymore.
+        if self.has_depth_stencil() {
+            let depth_initial = if self.depth_load == vk::AttachmentLoadOp::LOAD
+                || self.stencil_load == vk::AttachmentLoadOp::LOAD
+            {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::UNDEFINED
+            };
+
+            // Keep the stencil too so a mask can be loaded by a later pass
+            let stencil_store = if has_stencil_aspect(self.depth_stencil_format) {
+                self.depth_stencil_store
+            } else {
+                vk::AttachmentStoreOp::DONT_CARE
+            };
+
+            attachments.push(
+                vk::AttachmentDescription2::builder()
+                    .format(self.depth_stencil_format)
+                    .samples(self.depth_stencil_samples)
+                    .load_op(self.depth_load)
+                    .store_op(self.depth_stencil_store)
+                    .stencil_load_op(self.stencil_load)
+                    .stencil_store_op(stencil_store)
+                    .initial_layout(depth_initial)
+                    .final_layout(self.depth_stencil_final_layout)
+                    .build(),
+            );
+        }
+
+        // Resolve, fully overwritten at the end of the subpass
+        for i in (0..self.num_colors as usize).filter(|&i| self.is_resolved(i)) {
+            attachments.push(
+                vk::AttachmentDescription2::builder()
+                    .format(self.resolve_formats[i])
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .store_op(vk::AttachmentStoreOp::STORE)
+                    .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED)
+                    .final_layout(self.resolve_final_layouts[i])
+                    .build(),
+            );
+        }
+        attachments
+    }
 }
 
 impl VulkanDevice {
@@ -52,7 +388,11 @@ impl VulkanDevice {
         if let Some(rp) = self.render_pass_cache.get(&layout) {
             return Ok(*rp);
         }
-        let new_rp = VulkanDevice::create_vulkan_render_pass(self.raw(), layout)?;
+        let new_rp = VulkanDevice::create_vulkan_render_pass(
+            self.raw(),
+            layout,
+            self.separate_depth_stencil_layouts(),
+        )?;
         self.render_pass_cache.insert(*layout, new_rp);
         return Ok(new_rp);
     }
@@ -60,106 +400,485 @@ impl VulkanDevice {
     fn create_vulkan_render_pass(
         device: &ash::Device,
         layout: &VulkanRenderPassOutput,
+        separate_depth_stencil_layouts: bool,
     ) -> anyhow::Result<vk::RenderPass> {
-        // Depth intial image layout
-        let depth_initial = if layout.depth_load == vk::AttachmentLoadOp::DONT_CARE {
-            vk::ImageLayout::UNDEFINED
-        } else {
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
-        };
+        layout.check_subpasses()?;
+        layout.check_resolve_targets()?;
+        let has_depth_stencil = layout.has_depth_stencil();
 
-        // Color
-        let mut color_attachments = Vec::new();
-        let mut color_attachments_ref = Vec::new();
-        for i in 0..layout.num_colors {
-            let color_initial = if layout.color_load[i as usize] == vk::AttachmentLoadOp::LOAD {
-                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
-            } else {
-                vk::ImageLayout::UNDEFINED
-            };
+        // Per-aspect layouts of a combined format, chained to the depth stencil attachment and
+        // its references. The chained structs must outlive the create info.
+        let separate = layout.separate_depth_stencil_layouts(separate_depth_stencil_layouts)?;
+        let stencil_description = separate.map(|layouts| {
+            vk::AttachmentDescriptionStencilLayout::builder()
+                .stencil_initial_layout(layouts.stencil_initial)
+                .stencil_final_layout(layouts.stencil_final)
+                .build()
+        });
+        let stencil_reference = vk::AttachmentReferenceStencilLayout::builder()
+            .stencil_layout(vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL)
+            .build();
+        let mut attachment_descriptions = layout.attachment_descriptions();
+        if let (Some(layouts), Some(stencil)) = (separate, &stencil_description) {
+            let depth = &mut attachment_descriptions[layout.num_colors as usize];
+            depth.initial_layout = layouts.depth_initial;
+            depth.final_layout = layouts.depth_final;
+            depth.p_next = stencil as *const _ as *const c_void;
+        }
+
+        let attachment_refs = |mask: u8, layout: vk::ImageLayout| {
+            (0..MAX_RENDER_TARGETS as u32)
+                .filter(|i| mask & (1 << i) != 0)
+                .map(|i| {
+                    vk::AttachmentReference2::builder()
+                        .attachment(i)
+                        .layout(layout)
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .build()
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut depth_attachment_ref = vk::AttachmentReference2::builder()
+            .attachment(layout.num_colors as u32)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .aspect_mask(format_aspects(layout.depth_stencil_format))
+            .build();
+        if separate.is_some() {
+            depth_attachment_ref.layout = vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL;
+            depth_attachment_ref.p_next = &stencil_reference as *const _ as *const c_void;
+        }
 
-            let color_attachment = vk::AttachmentDescription::builder()
-                .format(layout.color_formats[i as usize])
-                .samples(layout.color_samples[i as usize])
-                .load_op(layout.color_load[i as usize])
-                .store_op(vk::AttachmentStoreOp::STORE)
-                .stencil_load_op(layout.stencil_load)
-                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-                .initial_layout(color_initial)
-                .final_layout(layout.color_final_layouts[i as usize])
-                .build();
-            color_attachments.push(color_attachment);
-
-            let color_attachment_ref = vk::AttachmentReference::builder()
-                .attachment(i as u32)
-                .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                .build();
-            color_attachments_ref.push(color_attachment_ref);
-        }
-
-        // Depth stencil
-        let has_depth_stencil = layout.depth_stencil_format != vk::Format::UNDEFINED;
-        let mut depth_attachment = Default::default();
-        let mut depth_attachment_ref = Default::default();
-        if has_depth_stencil {
-            depth_attachment = vk::AttachmentDescription::builder()
-                .format(layout.depth_stencil_format)
-                .samples(layout.depth_stencil_samples)
-                .load_op(layout.depth_load)
-                .store_op(vk::AttachmentStoreOp::STORE)
-                .stencil_load_op(layout.stencil_load)
-                .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-                .initial_layout(depth_initial)
-                .final_layout(layout.depth_stencil_final_layout)
-                .build();
-            depth_attachment_ref = vk::AttachmentReference::builder()
-                .attachment(layout.num_colors as u32)
-                .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
-                .build();
-        }
-
-        // Build attachment descriptions
-        let mut attachment_descriptions = color_attachments;
-        if has_depth_stencil {
-            attachment_descriptions.push(depth_attachment);
-        }
-
-        // Build main subpass
-        let mut subpass = vk::SubpassDescription::builder()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachments_ref);
-        if has_depth_stencil {
-            subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
-        }
-        let subpass = subpass.build();
-
-        // // Build dependency
-        // let dependency = vk::SubpassDependency::builder()
-        //     .src_subpass(vk::SUBPASS_EXTERNAL)
-        //     .dst_subpass(0)
-        //     .src_stage_mask(
-        //         vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-        //             | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        //     )
-        //     .dst_stage_mask(
-        //         vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
-        //             | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        //     )
-        //     .src_access_mask(vk::AccessFlags::empty())
-        //     .dst_access_mask(
-        //         vk::AccessFlags::COLOR_ATTACHMENT_WRITE
-        //             | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-        //     )
-        //     .build();
+        // Build subpasses, the references must outlive the descriptions
+        let subpass_descs = layout.subpass_descs();
+        let refs: Vec<_> = subpass_descs
+            .iter()
+            .enumerate()
+            .map(|(subpass, desc)| {
+                if desc.use_depth_stencil && !has_depth_stencil {
+                    anyhow::bail!("Subpass uses a depth stencil but the render pass has none.");
+                }
+                let colors = attachment_refs(
+                    desc.color_attachments,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                );
+                let inputs = attachment_refs(
+                    desc.input_attachments,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                );
+                // Each color is resolved by the last subpass writing it
+                let resolves: Vec<_> = colors
+                    .iter()
+                    .map(|color| {
+                        let color = color.attachment as usize;
+                        let last_writer = subpass_descs
+                            .iter()
+                            .rposition(|other| other.color_attachments & (1 << color) != 0);
+                        let attachment = layout
+                            .resolve_attachment(color)
+                            .filter(|_| last_writer == Some(subpass))
+                            .unwrap_or(vk::ATTACHMENT_UNUSED);
+                        vk::AttachmentReference2::builder()
+                            .attachment(attachment)
+                            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .build()
+                    })
+                    .collect();
+                Ok((colors, inputs, resolves))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let subpasses: Vec<_> = subpass_descs
+            .iter()
+            .zip(refs.iter())
+            .map(|(desc, (colors, inputs, resolves))| {
+                let mut subpass = vk::SubpassDescription2::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(colors)
+                    .input_attachments(inputs);
+                if resolves.iter().any(|r| r.attachment != vk::ATTACHMENT_UNUSED) {
+                    subpass = subpass.resolve_attachments(resolves);
+                }
+                if desc.use_depth_stencil {
+                    subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
+                }
+                subpass.build()
+            })
+            .collect();
+        let dependencies = layout.subpass_dependencies();
 
         // Create Render Pass
-        let render_pass_info = vk::RenderPassCreateInfo::builder()
-            .subpasses(&[subpass])
+        let render_pass_info = vk::RenderPassCreateInfo2::builder()
+            .subpasses(&subpasses)
             .attachments(&attachment_descriptions)
-            //.dependencies(&[dependency])
+            .dependencies(&dependencies)
             .build();
-        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None)? };
+        let render_pass = unsafe { device.create_render_pass2(&render_pass_info, None)? };
 
         Ok(render_pass)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        define::{RenderPassOutput, RenderPassOutputBuilder},
+        enums::{
+            Format, ImageLayout, RenderTargetLoadAction, RenderTargetStoreAction, SampleCount,
+        },
+    };
+
+    #[test]
+    fn equal_outputs_share_a_render_pass() {
+        let built: VulkanRenderPassOutput = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ShaderReadOnlyOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build()
+            .into();
+
+        // Same pass with leftovers in the slots it doesn't use
+        let mut manual = VulkanRenderPassOutput {
+            num_colors: 1,
+            depth_load: vk::AttachmentLoadOp::LOAD,
+            depth_stencil_samples: vk::SampleCountFlags::TYPE_4,
+            ..Default::default()
+        };
+        manual.color_formats[0] = vk::Format::R8G8B8A8_UNORM;
+        manual.color_final_layouts[0] = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        manual.color_load[0] = vk::AttachmentLoadOp::CLEAR;
+        manual.color_samples[0] = vk::SampleCountFlags::TYPE_1;
+        manual.color_formats[1] = vk::Format::R16G16B16A16_SFLOAT;
+        manual.color_load[2] = vk::AttachmentLoadOp::LOAD;
+        manual.subpasses[0].color_attachments = 1;
+
+        assert_eq!(built, manual);
+        let hash = |output: &VulkanRenderPassOutput| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            output.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&built), hash(&manual));
+
+        let mut cache = std::collections::HashMap::new();
+        cache.insert(built, vk::RenderPass::null());
+        assert!(cache.contains_key(&manual));
+
+        let mut two_colors = manual;
+        two_colors.num_colors = 2;
+        assert_ne!(built, two_colors);
+    }
+
+    #[test]
+    fn depth_only_render_pass() {
+        let output: VulkanRenderPassOutput = RenderPassOutput::builder()
+            .set_depth_stencil(
+                Format::D32_SFLOAT,
+                ImageLayout::DepthStencilReadOnlyOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build()
+            .into();
+
+        assert_eq!(output.num_colors, 0);
+        assert!(output.has_depth_stencil());
+
+        let attachments = output.attachment_descriptions();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].format, vk::Format::D32_SFLOAT);
+        assert_eq!(attachments[0].load_op, vk::AttachmentLoadOp::CLEAR);
+        assert_eq!(attachments[0].store_op, vk::AttachmentStoreOp::STORE);
+        assert_eq!(attachments[0].initial_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(attachments[0].final_layout, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL);
+        assert_eq!(attachments[0].stencil_store_op, vk::AttachmentStoreOp::DONT_CARE);
+    }
+
+    #[test]
+    fn stencil_only_render_pass() {
+        let output: VulkanRenderPassOutput = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .set_depth_stencil(
+                Format::S8_UINT,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build()
+            .into();
+
+        let attachments = output.attachment_descriptions();
+        assert_eq!(attachments[1].format, vk::Format::S8_UINT);
+        assert_eq!(attachments[1].stencil_load_op, vk::AttachmentLoadOp::CLEAR);
+        assert_eq!(attachments[1].stencil_store_op, vk::AttachmentStoreOp::STORE);
+        assert_eq!(attachments[1].initial_layout, vk::ImageLayout::UNDEFINED);
+    }
+
+    #[test]
+    fn per_aspect_final_layouts() {
+        let output = |format, final_layout| -> VulkanRenderPassOutput {
+            RenderPassOutput::builder()
+                .set_depth_stencil(
+                    format,
+                    final_layout,
+                    RenderTargetLoadAction::Load,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .build()
+                .into()
+        };
+
+        // Depth sampled after the pass while the stencil stays an attachment
+        let depth_read = output(Format::D24_UNORM_S8_UINT, ImageLayout::DepthReadOnlyOptimal);
+        assert_eq!(
+            depth_read.separate_depth_stencil_layouts(true).unwrap(),
+            Some(SeparateDepthStencilLayouts {
+                depth_initial: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                depth_final: vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL,
+                stencil_initial: vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL,
+                stencil_final: vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL,
+            })
+        );
+        assert_eq!(
+            depth_read.separate_depth_stencil_layouts(false).unwrap_err().to_string(),
+            "DEPTH_READ_ONLY_OPTIMAL needs the separate depth stencil layouts feature."
+        );
+        let stencil_read = output(Format::D32_SFLOAT_S8_UINT, ImageLayout::StencilReadOnlyOptimal);
+        let layouts = stencil_read.separate_depth_stencil_layouts(true).unwrap().unwrap();
+        assert_eq!(layouts.depth_final, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+        assert_eq!(layouts.stencil_final, vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL);
+
+        // Single aspect formats take the layout as is, once the feature is on
+        let depth_only = output(Format::D32_SFLOAT, ImageLayout::DepthReadOnlyOptimal);
+        assert_eq!(depth_only.separate_depth_stencil_layouts(true).unwrap(), None);
+        assert!(depth_only.separate_depth_stencil_layouts(false).is_err());
+        assert_eq!(
+            output(Format::D32_SFLOAT, ImageLayout::StencilReadOnlyOptimal)
+                .separate_depth_stencil_layouts(true)
+                .unwrap_err()
+                .to_string(),
+            "STENCIL_READ_ONLY_OPTIMAL only applies to the STENCIL aspect, D32_SFLOAT has none."
+        );
+
+        // Combined layouts need nothing chained
+        let combined = output(Format::D24_UNORM_S8_UINT, ImageLayout::DepthStencilReadOnlyOptimal);
+        assert_eq!(combined.separate_depth_stencil_layouts(false).unwrap(), None);
+    }
+
+    #[test]
+    fn transient_depth_stencil_is_not_stored() {
+        let builder = |store| {
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::B8G8R8A8_UNORM,
+                    ImageLayout::PresentSrcKhr,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+                .set_depth_stencil(
+                    Format::D24_UNORM_S8_UINT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetLoadAction::Clear,
+                    store,
+                    SampleCount::Sample1,
+                )
+                .build()
+        };
+        let stored: VulkanRenderPassOutput = builder(RenderTargetStoreAction::Store).into();
+        let transient: VulkanRenderPassOutput = builder(RenderTargetStoreAction::DontCare).into();
+        assert_ne!(stored, transient);
+
+        let attachments = transient.attachment_descriptions();
+        assert_eq!(attachments[0].store_op, vk::AttachmentStoreOp::STORE);
+        assert_eq!(attachments[1].store_op, vk::AttachmentStoreOp::DONT_CARE);
+        assert_eq!(attachments[1].stencil_store_op, vk::AttachmentStoreOp::DONT_CARE);
+        assert_eq!(
+            stored.attachment_descriptions()[1].stencil_store_op,
+            vk::AttachmentStoreOp::STORE
+        );
+    }
+
+    #[test]
+    fn gbuffer_then_lighting_subpasses() {
+        let mut output = RenderPassOutput::builder();
+        for _ in 0..3 {
+            output = output.add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            );
+        }
+        let output: VulkanRenderPassOutput = output
+            .set_depth_stencil(
+                Format::D32_SFLOAT,
+                ImageLayout::DepthStencilAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetLoadAction::DontCare,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .add_subpass(&[1, 2], &[], true)
+            .add_subpass(&[0], &[1, 2], false)
+            .build()
+            .into();
+
+        let subpasses = output.subpass_descs();
+        assert_eq!(subpasses.len(), 2);
+        assert_eq!(subpasses[0].color_attachments, 0b110);
+        assert_eq!(subpasses[1].input_attachments, 0b110);
+        assert_eq!(subpasses[1].num_colors(), 1);
+
+        let dependencies = output.subpass_dependencies();
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!((dependencies[0].src_subpass, dependencies[0].dst_subpass), (0, 1));
+        assert!(dependencies[0].dst_access_mask.contains(vk::AccessFlags::INPUT_ATTACHMENT_READ));
+        assert_eq!(dependencies[0].dependency_flags, vk::DependencyFlags::BY_REGION);
+    }
+
+    #[test]
+    fn implicit_subpass_uses_every_attachment() {
+        let output: VulkanRenderPassOutput = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::PresentSrcKhr,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .build()
+            .into();
+
+        let subpasses = output.subpass_descs();
+        assert_eq!(subpasses.len(), 1);
+        assert_eq!(subpasses[0].color_attachments, 0b1);
+        assert!(!subpasses[0].use_depth_stencil);
+        assert!(output.subpass_dependencies().is_empty());
+    }
+
+    #[test]
+    fn subpasses_only_use_existing_colors() {
+        let two_colors = || {
+            let color = |b: RenderPassOutputBuilder| {
+                b.add_color(
+                    Format::R8G8B8A8_UNORM,
+                    ImageLayout::ColorAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample1,
+                )
+            };
+            color(color(RenderPassOutput::builder()))
+        };
+        let output: VulkanRenderPassOutput = two_colors()
+            .add_subpass(&[0], &[], false)
+            .add_subpass(&[1], &[0], false)
+            .build()
+            .into();
+        assert!(output.check_subpasses().is_ok());
+
+        let output: VulkanRenderPassOutput = two_colors()
+            .add_subpass(&[0], &[], false)
+            .add_subpass(&[1], &[2], false)
+            .build()
+            .into();
+        assert_eq!(
+            output.check_subpasses().unwrap_err().to_string(),
+            "Subpass 1 uses color attachments 0b00000110 but the render pass has 2 colors."
+        );
+    }
+
+    #[test]
+    fn msaa_color_with_resolve_target() {
+        let builder = || {
+            RenderPassOutput::builder()
+                .add_color(
+                    Format::R8G8B8A8_UNORM,
+                    ImageLayout::ColorAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample4,
+                )
+                .set_depth_stencil(
+                    Format::D32_SFLOAT,
+                    ImageLayout::DepthStencilAttachmentOptimal,
+                    RenderTargetLoadAction::Clear,
+                    RenderTargetLoadAction::DontCare,
+                    RenderTargetStoreAction::Store,
+                    SampleCount::Sample4,
+                )
+        };
+        let output: VulkanRenderPassOutput = builder()
+            .add_resolve_target(0, Format::R8G8B8A8_UNORM, ImageLayout::PresentSrcKhr)
+            .build()
+            .into();
+        assert!(output.check_resolve_targets().is_ok());
+        assert_eq!(output.num_resolves(), 1);
+        assert_eq!(output.resolve_formats(), vec![vk::Format::R8G8B8A8_UNORM]);
+
+        // After the color and the depth stencil
+        assert_eq!(output.resolve_attachment(0), Some(2));
+        let attachments = output.attachment_descriptions();
+        assert_eq!(attachments.len(), 3);
+        assert_eq!(attachments[2].samples, vk::SampleCountFlags::TYPE_1);
+        assert_eq!(attachments[2].load_op, vk::AttachmentLoadOp::DONT_CARE);
+        assert_eq!(attachments[2].final_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+
+        let unresolved: VulkanRenderPassOutput = builder().build().into();
+        assert_ne!(output, unresolved);
+        assert_eq!(unresolved.resolve_attachment(0), None);
+
+        let wrong_format: VulkanRenderPassOutput = builder()
+            .add_resolve_target(0, Format::R8G8B8A8_SRGB, ImageLayout::PresentSrcKhr)
+            .build()
+            .into();
+        assert_eq!(
+            wrong_format.check_resolve_targets().unwrap_err().to_string(),
+            "Color 0 is R8G8B8A8_UNORM but its resolve target is R8G8B8A8_SRGB."
+        );
+
+        let single_sampled: VulkanRenderPassOutput = RenderPassOutput::builder()
+            .add_color(
+                Format::R8G8B8A8_UNORM,
+                ImageLayout::ColorAttachmentOptimal,
+                RenderTargetLoadAction::Clear,
+                RenderTargetStoreAction::Store,
+                SampleCount::Sample1,
+            )
+            .add_resolve_target(0, Format::R8G8B8A8_UNORM, ImageLayout::PresentSrcKhr)
+            .build()
+            .into();
+        assert_eq!(
+            single_sampled.check_resolve_targets().unwrap_err().to_string(),
+            "Color 0 is resolved but it has a single sample."
+        );
+
+        let missing: VulkanRenderPassOutput = builder()
+            .add_resolve_target(1, Format::R8G8B8A8_UNORM, ImageLayout::PresentSrcKhr)
+            .build()
+            .into();
+        assert_eq!(
+            missing.check_resolve_targets().unwrap_err().to_string(),
+            "Resolve target of color 1 but the render pass has 1 colors."
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use anyhow::{bail, Context};
+use ash::vk;
+use luxseed_utility::pool::Pool;
+use smallvec::SmallVec;
+
+use crate::{
+    define::{ClearDepthStencil, RenderingInfo},
+    enums::RenderTargetLoadAction,
+    MAX_RENDER_TARGETS,
+};
+
+use super::{
+    command::check_clear_color,
+    framebuffer::{check_attachment_size, render_area},
+    image::{VulkanImage, VulkanImageView},
+};
+
+/// The attachments of a `RenderingInfo`, what `rendering_info` points into.
+pub struct VulkanRenderingAttachments {
+    colors: SmallVec<[vk::RenderingAttachmentInfo; MAX_RENDER_TARGETS]>,
+    depth: Option<vk::RenderingAttachmentInfo>,
+    stencil: Option<vk::RenderingAttachmentInfo>,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+}
+
+impl VulkanRenderingAttachments {
+    pub fn from_info(
+        info: &RenderingInfo,
+        p_texture: &Pool<VulkanImage>,
+        p_texture_view: &Pool<VulkanImageView>,
+    ) -> anyhow::Result<Self> {
+        if info.color_attachments.len() > MAX_RENDER_TARGETS {
+            bail!(
+                "{} color attachments, at most {} are supported.",
+                info.color_attachments.len(),
+                MAX_RENDER_TARGETS
+            );
+        }
+        // Width, height and layers of the first attachment, the others have to match
+        let mut size = None;
+
+        let mut colors = SmallVec::new();
+        for (i, attachment) in info.color_attachments.iter().enumerate() {
+            let view =
+                p_texture_view.get(attachment.view).context("Color texture view not found")?;
+            if !view.desc.aspect_mask.contains(vk::ImageAspectFlags::COLOR) {
+                bail!(
+                    "Color attachment {} view has the {:?} aspect, it needs the color aspect.",
+                    i,
+                    view.desc.aspect_mask
+                );
+            }
+            let texture = p_texture
+                .get(view.texture.context("Texture view's texture is none")?)
+                .context("Texture not found")?;
+            check_attachment_size(
+                &mut size,
+                texture,
+                view,
+                format_args!("Color attachment {}", i),
+            )?;
+
+            let clear_value = match attachment.clear_value {
+                Some(color) => {
+                    check_clear_color(view.desc.format, color)
+                        .with_context(|| format!("Color attachment {}", i))?;
+                    color.into()
+                }
+                None if matches!(attachment.load_op, RenderTargetLoadAction::Clear) => {
+                    bail!("Color attachment {} is cleared but has no clear color.", i)
+                }
+                None => vk::ClearValue::default(),
+            };
+            colors.push(
+                vk::RenderingAttachmentInfo::builder()
+                    .image_view(view.raw)
+                    .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .load_op(attachment.load_op.into())
+                    .store_op(attachment.store_op.into())
+                    .clear_value(clear_value)
+                    .build(),
+            );
+        }
+
+        // Depth and stencil share the view, each aspect it has is attached on its own
+        let (mut depth, mut stencil) = (None, None);
+        if let Some(attachment) = info.depth_stencil_attachment {
+            let view = p_texture_view
+                .get(attachment.view)
+                .context("Depth stencil texture view not found")?;
+            let aspects = view.desc.aspect_mask;
+            if !aspects.intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL) {
+                bail!(
+                    "Depth stencil attachment view has the {:?} aspect, it needs the depth or stencil aspect.",
+                    aspects
+                );
+            }
+            let texture = p_texture
+                .get(view.texture.context("Texture view's texture is none")?)
+                .context("Texture not found")?;
+            check_attachment_size(
+                &mut size,
+                texture,
+                view,
+                format_args!("Depth stencil attachment"),
+            )?;
+
+            let has_depth = aspects.contains(vk::ImageAspectFlags::DEPTH);
+            let has_stencil = aspects.contains(vk::ImageAspectFlags::STENCIL);
+            let depth_uncleared = matches!(attachment.depth_load_op, RenderTargetLoadAction::Clear)
+                && attachment.depth_clear_value.is_none();
+            let stencil_uncleared =
+                matches!(attachment.stencil_load_op, RenderTargetLoadAction::Clear)
+                    && attachment.stencil_clear_value.is_none();
+            if (has_depth && depth_uncleared) || (has_stencil && stencil_uncleared) {
+                bail!("Depth stencil attachment is cleared but has no clear value.");
+            }
+            let clear_value = ClearDepthStencil {
+                depth: attachment.depth_clear_value.unwrap_or_default(),
+                stencil: attachment.stencil_clear_value.unwrap_or_default(),
+            }
+            .into();
+
+            let shared = vk::RenderingAttachmentInfo::builder()
+                .image_view(view.raw)
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .clear_value(clear_value)
+                .build();
+            if has_depth {
+                depth = Some(vk::RenderingAttachmentInfo {
+                    load_op: attachment.depth_load_op.into(),
+                    store_op: attachment.depth_store_op.into(),
+                    ..shared
+                });
+            }
+            if has_stencil {
+                stencil = Some(vk::RenderingAttachmentInfo {
+                    load_op: attachment.stencil_load_op.into(),
+                    store_op: attachment.stencil_store_op.into(),
+                    ..shared
+                });
+            }
+        }
+
+        let [width, height, layers] = render_area(info.extent, size)?;
+        Ok(Self { colors, depth, stencil, width, height, layers })
+    }
+
+    /// Points into `self`, which has to outlive it.
+    pub fn rendering_info(&self) -> vk::RenderingInfo {
+        let mut info = vk::RenderingInfo::builder()
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: vk::Extent2D { width: self.width, height: self.height },
+            })
+            .layer_count(self.layers)
+            .color_attachments(&self.colors);
+        if let Some(depth) = &self.depth {
+            info = info.depth_attachment(depth);
+        }
+        if let Some(stencil) = &self.stencil {
+            info = info.stencil_attachment(stencil);
+        }
+        info.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        define::{ClearColor, ColorAttachment, DepthStencilAttachment, ImageView},
+        enums::RenderTargetStoreAction,
+    };
+    use luxseed_utility::pool::Handle;
+
+    fn add_view(
+        p_texture: &mut Pool<VulkanImage>,
+        p_texture_view: &mut Pool<VulkanImageView>,
+        format: vk::Format,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Handle<ImageView> {
+        let (texture, item) = p_texture.malloc();
+        item.desc.extent = vk::Extent3D { width: 800, height: 600, depth: 1 };
+        item.desc.array_layers = 1;
+        let (view, item) = p_texture_view.malloc();
+        item.texture = Some(texture);
+        item.desc.format = format;
+        item.desc.aspect_mask = aspect_mask;
+        item.desc.level_count = 1;
+        item.desc.layer_count = 1;
+        view
+    }
+
+    #[test]
+    fn attachments_without_a_render_pass() {
+        let mut p_texture = Pool::<VulkanImage>::with_size(8, Default::default);
+        let mut p_texture_view = Pool::<VulkanImageView>::with_size(8, Default::default);
+        let color = add_view(
+            &mut p_texture,
+            &mut p_texture_view,
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let depth_stencil = add_view(
+            &mut p_texture,
+            &mut p_texture_view,
+            vk::Format::D24_UNORM_S8_UINT,
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        );
+
+        let color_attachment = ColorAttachment {
+            view: color,
+            clear_value: Some(ClearColor::new([0.0, 0.0, 0.0, 1.0])),
+            load_op: RenderTargetLoadAction::Clear,
+            store_op: RenderTargetStoreAction::Store,
+        };
+        let depth_attachment = DepthStencilAttachment {
+            view: depth_stencil,
+            depth_clear_value: Some(1.0),
+            depth_load_op: RenderTargetLoadAction::Clear,
+            depth_store_op: RenderTargetStoreAction::DontCare,
+            stencil_clear_value: None,
+            stencil_load_op: RenderTargetLoadAction::DontCare,
+            stencil_store_op: RenderTargetStoreAction::DontCare,
+        };
+        let build = |color_attachments: &[ColorAttachment], depth_stencil_attachment| {
+            VulkanRenderingAttachments::from_info(
+                &RenderingInfo { color_attachments, depth_stencil_attachment, extent: None },
+                &p_texture,
+                &p_texture_view,
+            )
+        };
+
+        let attachments = build(&[color_attachment], Some(depth_attachment)).unwrap();
+        let info = attachments.rendering_info();
+        assert_eq!((info.render_area.extent.width, info.render_area.extent.height), (800, 600));
+        assert_eq!((info.layer_count, info.color_attachment_count), (1, 1));
+        assert!(!info.p_depth_attachment.is_null());
+        assert!(!info.p_stencil_attachment.is_null());
+        let depth = attachments.depth.unwrap();
+        assert_eq!(depth.store_op, vk::AttachmentStoreOp::DONT_CARE);
+        assert_eq!(unsafe { depth.clear_value.depth_stencil.depth }, 1.0);
+        assert_eq!(attachments.stencil.unwrap().load_op, vk::AttachmentLoadOp::DONT_CARE);
+
+        let uncleared = ColorAttachment { clear_value: None, ..color_attachment };
+        assert_eq!(
+            build(&[uncleared], None).err().unwrap().to_string(),
+            "Color attachment 0 is cleared but has no clear color."
+        );
+        let stencil_cleared = DepthStencilAttachment {
+            stencil_load_op: RenderTargetLoadAction::Clear,
+            ..depth_attachment
+        };
+        assert_eq!(
+            build(&[], Some(stencil_cleared)).err().unwrap().to_string(),
+            "Depth stencil attachment is cleared but has no clear value."
+        );
+        let depth_as_color = ColorAttachment { view: depth_stencil, ..color_attachment };
+        assert!(build(&[depth_as_color], None).is_err());
+    }
+}
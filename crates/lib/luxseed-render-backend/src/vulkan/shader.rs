@@ -1,11 +1,12 @@
+use anyhow::{bail, Result};
 use ash::vk;
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::Handle;
 use std::ffi::CString;
 
-use crate::define::{Shader, ShaderModuleCreation};
+use crate::define::{DescriptorSetLayoutCreateDesc, Shader, ShaderModuleCreation};
 
-use super::device::VulkanDevice;
+use super::{device::VulkanDevice, reflection::reflect_descriptor_layouts};
 
 #[derive(Default)]
 pub struct VulkanShader {
@@ -13,27 +14,36 @@ pub struct VulkanShader {
     pub handle: Option<Handle<Shader>>,
     pub stage: vk::ShaderStageFlags,
     pub entry: CString,
+    /// Descriptor set layouts the code declares, see `reflect_descriptor_layouts`. Modules the
+    /// reflection can't read still load, the error comes back from `descriptor_layouts`.
+    reflected_layouts: Option<Result<Vec<DescriptorSetLayoutCreateDesc>>>,
 }
 impl_handle!(VulkanShader, Shader, handle);
 
 impl VulkanShader {
-    pub fn init(
-        &mut self,
-        device: &VulkanDevice,
-        creation: &ShaderModuleCreation,
-    ) -> anyhow::Result<()> {
+    pub fn init(&mut self, device: &VulkanDevice, creation: &ShaderModuleCreation) -> Result<()> {
         let create_info = vk::ShaderModuleCreateInfo::builder().code(creation.code).build();
         self.raw = unsafe { device.raw().create_shader_module(&create_info, None)? };
         self.stage = creation.stage.into();
         self.entry = CString::new(creation.entry)?;
+        self.reflected_layouts = Some(reflect_descriptor_layouts(creation.code));
         Ok(())
     }
 
+    pub fn descriptor_layouts(&self) -> Result<&[DescriptorSetLayoutCreateDesc]> {
+        match &self.reflected_layouts {
+            Some(Ok(layouts)) => Ok(layouts),
+            Some(Err(err)) => bail!("Failed to reflect the descriptor layouts: {:#}", err),
+            None => bail!("Shader module is not created."),
+        }
+    }
+
     pub fn destroy(&mut self, device: &VulkanDevice) {
         unsafe {
             device.raw().destroy_shader_module(self.raw, None);
         }
         self.raw = vk::ShaderModule::null();
         self.stage = vk::ShaderStageFlags::empty();
+        self.reflected_layouts = None;
     }
 }
@@ -1,3 +1,4 @@
+use anyhow::Context;
 use ash::{extensions::khr, vk};
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::Handle;
@@ -33,6 +34,16 @@ impl VulkanSurface {
         Ok(())
     }
 
+    /// Whether queues of `family_index` on `adapter` can present to this surface.
+    pub fn supports_present(
+        &self,
+        adapter: vk::PhysicalDevice,
+        family_index: u32,
+    ) -> anyhow::Result<bool> {
+        let loader = self.loader.as_ref().context("Surface not created.")?;
+        Ok(unsafe { loader.get_physical_device_surface_support(adapter, family_index, self.raw)? })
+    }
+
     pub fn destroy(&mut self) {
         if let Some(loader) = self.loader.as_ref() {
             unsafe {
@@ -26,11 +26,55 @@ pub struct VulkanSwapchain {
     pub surface: Option<Handle<Surface>>,
     pub loader: Option<khr::Swapchain>,
     pub surface_format: SurfaceFormatKHR,
+    pub extent: vk::Extent2D,
     pub back_buffers: Vec<Handle<Image>>,
     pub image_count: u8,
+    /// Family of the queue the surface support was checked against.
+    pub present_family_index: u32,
 }
 impl_handle!(VulkanSwapchain, Swapchain, handle);
 
+/// Image count used when `SwapchainCreateDesc::desired_image_count` is zero.
+pub const DEFAULT_SWAPCHAIN_IMAGE_COUNT: u32 = 3;
+
+/// Clamps the requested image count to `[min_image_count, max_image_count]`, a
+/// `max_image_count` of zero means the surface has no upper limit.
+pub fn clamp_swapchain_image_count(desired: u32, caps: &vk::SurfaceCapabilitiesKHR) -> u32 {
+    let desired = if desired == 0 { DEFAULT_SWAPCHAIN_IMAGE_COUNT } else { desired };
+    let count = desired.max(caps.min_image_count);
+    if caps.max_image_count != 0 {
+        count.min(caps.max_image_count)
+    } else {
+        count
+    }
+}
+
+/// The surface decides the extent unless it reports `u32::MAX`, in which case the requested
+/// size is clamped to what the surface supports.
+pub fn choose_swapchain_extent(
+    width: u32,
+    height: u32,
+    caps: &vk::SurfaceCapabilitiesKHR,
+) -> vk::Extent2D {
+    if caps.current_extent.width != u32::MAX {
+        return caps.current_extent;
+    }
+    vk::Extent2D {
+        width: width.clamp(caps.min_image_extent.width, caps.max_image_extent.width),
+        height: height.clamp(caps.min_image_extent.height, caps.max_image_extent.height),
+    }
+}
+
+/// Back buffers are written by the graphics queue and presented by the present queue, they are
+/// shared between both families when those differ so no ownership transfer is needed.
+pub fn swapchain_sharing(graphics_family: u32, present_family: u32) -> (vk::SharingMode, Vec<u32>) {
+    if graphics_family == present_family {
+        (vk::SharingMode::EXCLUSIVE, Vec::new())
+    } else {
+        (vk::SharingMode::CONCURRENT, vec![graphics_family, present_family])
+    }
+}
+
 impl VulkanSwapchain {
     pub fn init(
         &mut self,
@@ -43,17 +87,16 @@ impl VulkanSwapchain {
     ) -> Result<()> {
         let surface: &VulkanSurface = p_surface.get(desc.surface).context("Surface not found")?;
         let queue: &VulkanQueue =
+            p_queue.get(device.get_queue(QueueType::Present)?).context("Queue not found")?;
+        let graphics_queue: &VulkanQueue =
             p_queue.get(device.get_queue(QueueType::Graphics)?).context("Queue not found")?;
 
-        let surface_supported = unsafe {
-            surface.loader.as_ref().unwrap().get_physical_device_surface_support(
-                device.get_adapter().raw,
+        if !surface.supports_present(device.get_adapter().raw, queue.family_index)? {
+            anyhow::bail!(
+                "Queue family {} can't present to surface {:?}, pass it as `DeviceCreateDesc::present_surface`.",
                 queue.family_index,
-                surface.raw,
-            )?
-        };
-        if !surface_supported {
-            return Err(anyhow::anyhow!("Err no WSI support on physical device"));
+                desc.surface
+            );
         }
 
         let surface_formats = unsafe {
@@ -91,15 +134,10 @@ impl VulkanSwapchain {
             surface_capabilities.current_transform
         };
 
-        let mut desired_image_count = 3.max(surface_capabilities.min_image_count);
-        if surface_capabilities.max_image_count != 0 {
-            desired_image_count = desired_image_count.min(surface_capabilities.max_image_count);
-        }
+        let desired_image_count =
+            clamp_swapchain_image_count(desc.desired_image_count, &surface_capabilities);
 
-        let extent = match surface_capabilities.current_extent.width {
-            std::u32::MAX => vk::Extent2D { width: (desc.width), height: (desc.height) },
-            _ => surface_capabilities.current_extent,
-        };
+        let extent = choose_swapchain_extent(desc.width, desc.height, &surface_capabilities);
 
         let present_mode_preference = if desc.vsync {
             vec![vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO]
@@ -120,9 +158,12 @@ impl VulkanSwapchain {
             .find(|mode| present_modes.contains(mode))
             .unwrap_or(vk::PresentModeKHR::FIFO);
 
+        let (sharing_mode, family_indices) =
+            swapchain_sharing(graphics_queue.family_index, queue.family_index);
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .image_sharing_mode(sharing_mode)
+            .queue_family_indices(&family_indices)
             .clipped(true)
             .image_array_layers(1)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -146,6 +187,7 @@ impl VulkanSwapchain {
                 format: surface_format.format,
                 extent: vk::Extent3D { width: extent.width, height: extent.height, depth: 1 },
                 array_layers: 1,
+                samples: vk::SampleCountFlags::TYPE_1,
                 ..Default::default()
             };
             item.1.views.clear();
@@ -155,13 +197,28 @@ impl VulkanSwapchain {
         self.raw = raw;
         self.loader = Some(loader);
         self.surface = Some(desc.surface);
+        self.image_count = images.len() as u8;
         self.back_buffers = images;
         self.surface_format = surface_format;
-        self.image_count = desired_image_count as u8;
+        self.extent = extent;
+        self.present_family_index = queue.family_index;
 
         Ok(())
     }
 
+    /// Fails unless the swapchain can be presented from a queue of `family_index`.
+    pub fn check_present_queue(&self, family_index: u32) -> Result<()> {
+        if family_index != self.present_family_index {
+            anyhow::bail!(
+                "Swapchain {:?} presents from queue family {}, not {}.",
+                self.handle,
+                self.present_family_index,
+                family_index
+            );
+        }
+        Ok(())
+    }
+
     pub fn acquire_next_image(
         &self,
         timeout: u64,
@@ -195,6 +252,61 @@ impl VulkanSwapchain {
         self.loader = None;
         self.back_buffers.clear();
         self.surface_format = SurfaceFormatKHR::default();
+        self.extent = vk::Extent2D::default();
         self.image_count = 0;
+        self.present_family_index = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caps(min_image_count: u32, max_image_count: u32) -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR { min_image_count, max_image_count, ..Default::default() }
+    }
+
+    #[test]
+    fn swapchain_image_count_is_clamped() {
+        assert_eq!(clamp_swapchain_image_count(3, &caps(2, 8)), 3);
+        assert_eq!(clamp_swapchain_image_count(0, &caps(2, 8)), DEFAULT_SWAPCHAIN_IMAGE_COUNT);
+        assert_eq!(clamp_swapchain_image_count(1, &caps(2, 8)), 2);
+        assert_eq!(clamp_swapchain_image_count(3, &caps(2, 2)), 2);
+        assert_eq!(clamp_swapchain_image_count(16, &caps(2, 0)), 16);
+    }
+
+    #[test]
+    fn back_buffers_are_shared_with_a_separate_present_family() {
+        assert_eq!(swapchain_sharing(0, 0), (vk::SharingMode::EXCLUSIVE, vec![]));
+        assert_eq!(swapchain_sharing(0, 2), (vk::SharingMode::CONCURRENT, vec![0, 2]));
+    }
+
+    #[test]
+    fn swapchain_extent_follows_surface() {
+        // Fixed by the surface, e.g. a window on a high-DPI display
+        let fixed = vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D { width: 3200, height: 1800 },
+            ..Default::default()
+        };
+        assert_eq!(
+            choose_swapchain_extent(1600, 900, &fixed),
+            vk::Extent2D { width: 3200, height: 1800 }
+        );
+
+        // Left to the application, as on Wayland
+        let open = vk::SurfaceCapabilitiesKHR {
+            current_extent: vk::Extent2D { width: u32::MAX, height: u32::MAX },
+            min_image_extent: vk::Extent2D { width: 1, height: 1 },
+            max_image_extent: vk::Extent2D { width: 4096, height: 4096 },
+            ..Default::default()
+        };
+        assert_eq!(
+            choose_swapchain_extent(1600, 900, &open),
+            vk::Extent2D { width: 1600, height: 900 }
+        );
+        assert_eq!(
+            choose_swapchain_extent(8192, 0, &open),
+            vk::Extent2D { width: 4096, height: 1 }
+        );
     }
 }
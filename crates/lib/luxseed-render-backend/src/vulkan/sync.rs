@@ -1,9 +1,12 @@
-use anyhow::Ok;
+use anyhow::{bail, Ok};
 use ash::vk::{self};
 use luxseed_utility::impl_handle;
 use luxseed_utility::pool::Handle;
 
-use crate::define::{Fence, Semaphore};
+use crate::{
+    define::{Fence, Semaphore},
+    flag::DeviceFeatures,
+};
 
 use super::device::VulkanDevice;
 
@@ -27,6 +30,11 @@ impl VulkanFence {
         Ok(())
     }
 
+    /// Whether the fence is signaled, without blocking. `VK_NOT_READY` comes back as `false`.
+    pub fn status(&self, device: &VulkanDevice) -> anyhow::Result<bool> {
+        Ok(unsafe { device.raw().get_fence_status(self.raw)? })
+    }
+
     pub fn destroy(&mut self, device: &VulkanDevice) {
         unsafe {
             device.raw().destroy_fence(self.raw, None);
@@ -39,6 +47,7 @@ impl VulkanFence {
 pub struct VulkanSemaphore {
     pub handle: Option<Handle<Semaphore>>,
     pub raw: vk::Semaphore,
+    pub timeline: bool,
 }
 impl_handle!(VulkanSemaphore, Semaphore, handle);
 
@@ -49,10 +58,53 @@ impl VulkanSemaphore {
         Ok(())
     }
 
+    pub fn init_timeline(
+        &mut self,
+        device: &VulkanDevice,
+        initial_value: u64,
+    ) -> anyhow::Result<()> {
+        if !device.enabled_features().features.contains(DeviceFeatures::TIMELINE_SEMAPHORE) {
+            bail!("Timeline semaphores need DeviceFeatures::TIMELINE_SEMAPHORE.");
+        }
+        let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info).build();
+        self.raw = unsafe { device.raw().create_semaphore(&create_info, None)? };
+        self.timeline = true;
+        Ok(())
+    }
+
+    /// The raw semaphore, as long as it was created with `init_timeline`.
+    pub fn timeline_raw(&self) -> anyhow::Result<vk::Semaphore> {
+        if !self.timeline {
+            bail!("Semaphore is binary, host waits and signals need a timeline semaphore.");
+        }
+        Ok(self.raw)
+    }
+
     pub fn destroy(&mut self, device: &VulkanDevice) {
         unsafe {
             device.raw().destroy_semaphore(self.raw, None);
         }
         self.raw = vk::Semaphore::null();
+        self.timeline = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_waits_need_a_timeline_semaphore() {
+        let binary = VulkanSemaphore::default();
+        assert_eq!(
+            binary.timeline_raw().unwrap_err().to_string(),
+            "Semaphore is binary, host waits and signals need a timeline semaphore."
+        );
+
+        let timeline = VulkanSemaphore { timeline: true, ..Default::default() };
+        assert_eq!(timeline.timeline_raw().unwrap(), timeline.raw);
     }
 }
@@ -1,4 +1,5 @@
 use ash::vk;
+use std::ffi::CStr;
 
 use crate::{define::*, enums::*, flag::*};
 
@@ -19,10 +20,39 @@ impl From<Format> for vk::Format {
             Format::R32G32_SFLOAT => vk::Format::R32G32_SFLOAT,
             Format::R32G32B32_SFLOAT => vk::Format::R32G32B32_SFLOAT,
             Format::R32G32B32A32_SFLOAT => vk::Format::R32G32B32A32_SFLOAT,
+            Format::R8_UINT => vk::Format::R8_UINT,
+            Format::R16_UINT => vk::Format::R16_UINT,
+            Format::R32_UINT => vk::Format::R32_UINT,
+            Format::R32G32_UINT => vk::Format::R32G32_UINT,
+            Format::R32G32B32A32_UINT => vk::Format::R32G32B32A32_UINT,
+            Format::R32_SINT => vk::Format::R32_SINT,
+            Format::R32G32B32A32_SINT => vk::Format::R32G32B32A32_SINT,
             Format::D24_UNORM_S8_UINT => vk::Format::D24_UNORM_S8_UINT,
             Format::D32_SFLOAT => vk::Format::D32_SFLOAT,
             Format::D32_SFLOAT_S8_UINT => vk::Format::D32_SFLOAT_S8_UINT,
-            _ => vk::Format::UNDEFINED,
+            Format::S8_UINT => vk::Format::S8_UINT,
+            Format::R16_SFLOAT => vk::Format::R16_SFLOAT,
+            Format::R16G16_SFLOAT => vk::Format::R16G16_SFLOAT,
+            Format::R16G16B16A16_SFLOAT => vk::Format::R16G16B16A16_SFLOAT,
+            Format::A2B10G10R10_UNORM => vk::Format::A2B10G10R10_UNORM_PACK32,
+            Format::B10G11R11_UFLOAT => vk::Format::B10G11R11_UFLOAT_PACK32,
+            Format::BC1_RGB_UNORM => vk::Format::BC1_RGB_UNORM_BLOCK,
+            Format::BC1_RGB_SRGB => vk::Format::BC1_RGB_SRGB_BLOCK,
+            Format::BC1_RGBA_UNORM => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            Format::BC1_RGBA_SRGB => vk::Format::BC1_RGBA_SRGB_BLOCK,
+            Format::BC2_UNORM => vk::Format::BC2_UNORM_BLOCK,
+            Format::BC2_SRGB => vk::Format::BC2_SRGB_BLOCK,
+            Format::BC3_UNORM => vk::Format::BC3_UNORM_BLOCK,
+            Format::BC3_SRGB => vk::Format::BC3_SRGB_BLOCK,
+            Format::BC4_UNORM => vk::Format::BC4_UNORM_BLOCK,
+            Format::BC4_SNORM => vk::Format::BC4_SNORM_BLOCK,
+            Format::BC5_UNORM => vk::Format::BC5_UNORM_BLOCK,
+            Format::BC5_SNORM => vk::Format::BC5_SNORM_BLOCK,
+            Format::BC6H_UFLOAT => vk::Format::BC6H_UFLOAT_BLOCK,
+            Format::BC6H_SFLOAT => vk::Format::BC6H_SFLOAT_BLOCK,
+            Format::BC7_UNORM => vk::Format::BC7_UNORM_BLOCK,
+            Format::BC7_SRGB => vk::Format::BC7_SRGB_BLOCK,
+            Format::Unknown => vk::Format::UNDEFINED,
         }
     }
 }
@@ -42,11 +72,299 @@ impl From<vk::Format> for Format {
             vk::Format::R32G32_SFLOAT => Format::R32G32_SFLOAT,
             vk::Format::R32G32B32_SFLOAT => Format::R32G32B32_SFLOAT,
             vk::Format::R32G32B32A32_SFLOAT => Format::R32G32B32A32_SFLOAT,
+            vk::Format::R8_UINT => Format::R8_UINT,
+            vk::Format::R16_UINT => Format::R16_UINT,
+            vk::Format::R32_UINT => Format::R32_UINT,
+            vk::Format::R32G32_UINT => Format::R32G32_UINT,
+            vk::Format::R32G32B32A32_UINT => Format::R32G32B32A32_UINT,
+            vk::Format::R32_SINT => Format::R32_SINT,
+            vk::Format::R32G32B32A32_SINT => Format::R32G32B32A32_SINT,
+            vk::Format::D24_UNORM_S8_UINT => Format::D24_UNORM_S8_UINT,
+            vk::Format::D32_SFLOAT => Format::D32_SFLOAT,
+            vk::Format::D32_SFLOAT_S8_UINT => Format::D32_SFLOAT_S8_UINT,
+            vk::Format::S8_UINT => Format::S8_UINT,
+            vk::Format::R16_SFLOAT => Format::R16_SFLOAT,
+            vk::Format::R16G16_SFLOAT => Format::R16G16_SFLOAT,
+            vk::Format::R16G16B16A16_SFLOAT => Format::R16G16B16A16_SFLOAT,
+            vk::Format::A2B10G10R10_UNORM_PACK32 => Format::A2B10G10R10_UNORM,
+            vk::Format::B10G11R11_UFLOAT_PACK32 => Format::B10G11R11_UFLOAT,
+            vk::Format::BC1_RGB_UNORM_BLOCK => Format::BC1_RGB_UNORM,
+            vk::Format::BC1_RGB_SRGB_BLOCK => Format::BC1_RGB_SRGB,
+            vk::Format::BC1_RGBA_UNORM_BLOCK => Format::BC1_RGBA_UNORM,
+            vk::Format::BC1_RGBA_SRGB_BLOCK => Format::BC1_RGBA_SRGB,
+            vk::Format::BC2_UNORM_BLOCK => Format::BC2_UNORM,
+            vk::Format::BC2_SRGB_BLOCK => Format::BC2_SRGB,
+            vk::Format::BC3_UNORM_BLOCK => Format::BC3_UNORM,
+            vk::Format::BC3_SRGB_BLOCK => Format::BC3_SRGB,
+            vk::Format::BC4_UNORM_BLOCK => Format::BC4_UNORM,
+            vk::Format::BC4_SNORM_BLOCK => Format::BC4_SNORM,
+            vk::Format::BC5_UNORM_BLOCK => Format::BC5_UNORM,
+            vk::Format::BC5_SNORM_BLOCK => Format::BC5_SNORM,
+            vk::Format::BC6H_UFLOAT_BLOCK => Format::BC6H_UFLOAT,
+            vk::Format::BC6H_SFLOAT_BLOCK => Format::BC6H_SFLOAT,
+            vk::Format::BC7_UNORM_BLOCK => Format::BC7_UNORM,
+            vk::Format::BC7_SRGB_BLOCK => Format::BC7_SRGB,
             _ => Format::Unknown,
         }
     }
 }
 
+#[inline]
+pub fn extension_name(props: &vk::ExtensionProperties) -> &CStr {
+    unsafe { CStr::from_ptr(props.extension_name.as_ptr()) }
+}
+
+pub fn has_extension(available: &[vk::ExtensionProperties], name: &[u8]) -> bool {
+    available.iter().any(|p| extension_name(p).to_bytes() == name)
+}
+
+/// Maps the outcome of `vkWaitForFences`, other errors are passed on.
+pub fn fence_wait_result(result: ash::prelude::VkResult<()>) -> anyhow::Result<FenceWaitResult> {
+    match result {
+        Ok(()) => Ok(FenceWaitResult::Signaled),
+        Err(vk::Result::TIMEOUT) => Ok(FenceWaitResult::TimedOut),
+        Err(vk::Result::ERROR_DEVICE_LOST) => Ok(FenceWaitResult::DeviceLost),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Whether `err` or one of its causes is `VK_ERROR_DEVICE_LOST`.
+pub fn is_device_lost_error(err: &anyhow::Error) -> bool {
+    err.chain()
+        .any(|cause| cause.downcast_ref::<vk::Result>() == Some(&vk::Result::ERROR_DEVICE_LOST))
+}
+
+pub fn is_format_feature_supported(
+    props: &vk::FormatProperties,
+    tiling: ImageTiling,
+    feature: vk::FormatFeatureFlags,
+) -> bool {
+    if feature.is_empty() {
+        return false;
+    }
+    match tiling {
+        ImageTiling::Linear => props.linear_tiling_features.contains(feature),
+        ImageTiling::Optimal => props.optimal_tiling_features.contains(feature),
+    }
+}
+
+pub fn is_depth_stencil_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+    )
+}
+
+/// Whether a color format is read and written as unsigned or signed integers, those need
+/// clear values of the matching `ClearColor` variant.
+pub fn integer_format_sign(format: vk::Format) -> Option<bool> {
+    match format {
+        vk::Format::R8_UINT
+        | vk::Format::R16_UINT
+        | vk::Format::R32_UINT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32B32A32_UINT => Some(false),
+        vk::Format::R32_SINT | vk::Format::R32G32B32A32_SINT => Some(true),
+        _ => None,
+    }
+}
+
+/// Blits convert between color formats read as the same numeric type, depth and stencil
+/// formats only blit into themselves.
+pub fn is_blit_compatible(src: vk::Format, dst: vk::Format) -> bool {
+    if src == dst {
+        return true;
+    }
+    let depth_or_stencil = |format| {
+        matches!(
+            format,
+            vk::Format::D16_UNORM
+                | vk::Format::X8_D24_UNORM_PACK32
+                | vk::Format::D32_SFLOAT
+                | vk::Format::S8_UINT
+        ) || is_depth_stencil_format(format)
+    };
+    !depth_or_stencil(src)
+        && !depth_or_stencil(dst)
+        && integer_format_sign(src) == integer_format_sign(dst)
+}
+
+/// Bits per texel of an uncompressed color format, formats of the same size can view each
+/// other's images. Depth and stencil formats have no class, they only match themselves.
+pub fn format_size_class(format: vk::Format) -> Option<u32> {
+    match format {
+        vk::Format::R8_UINT => Some(8),
+        vk::Format::R16_UINT | vk::Format::R16_SFLOAT => Some(16),
+        vk::Format::B8G8R8_UNORM
+        | vk::Format::B8G8R8_SRGB
+        | vk::Format::R8G8B8_UNORM
+        | vk::Format::R8G8B8_SRGB => Some(24),
+        vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::R32_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SINT
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32 => Some(32),
+        vk::Format::R32G32_SFLOAT | vk::Format::R32G32_UINT | vk::Format::R16G16B16A16_SFLOAT => {
+            Some(64)
+        }
+        vk::Format::R32G32B32_SFLOAT => Some(96),
+        vk::Format::R32G32B32A32_SFLOAT
+        | vk::Format::R32G32B32A32_UINT
+        | vk::Format::R32G32B32A32_SINT => Some(128),
+        _ => None,
+    }
+}
+
+/// Whether `format` is one of the BC1 to BC7 block compressed formats.
+pub fn is_compressed_format(format: vk::Format) -> bool {
+    Format::from(format).is_compressed()
+}
+
+/// Aspects an image of `format` has.
+pub fn format_aspects(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        format if is_depth_stencil_format(format) => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+pub fn has_stencil_aspect(format: vk::Format) -> bool {
+    format == vk::Format::S8_UINT || is_depth_stencil_format(format)
+}
+
+/// The aspect a layout is restricted to, if it only applies to the depth or the stencil aspect.
+pub fn separate_layout_aspect(layout: vk::ImageLayout) -> Option<vk::ImageAspectFlags> {
+    match layout {
+        vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL => {
+            Some(vk::ImageAspectFlags::DEPTH)
+        }
+        vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL => Some(vk::ImageAspectFlags::STENCIL),
+        _ => None,
+    }
+}
+
+/// Resolves the aspects a layout transition of an image with `format` has to cover.
+///
+/// Without the separate depth stencil layouts feature both aspects of a combined depth stencil
+/// image always move together, so a depth or stencil only barrier is widened to both. With it,
+/// each aspect can be transitioned on its own and per-aspect layouts are allowed.
+pub fn resolve_barrier_aspect(
+    format: vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    separate_depth_stencil_layouts: bool,
+) -> anyhow::Result<vk::ImageAspectFlags> {
+    for layout in [old_layout, new_layout] {
+        if let Some(aspect) = separate_layout_aspect(layout) {
+            if !separate_depth_stencil_layouts {
+                anyhow::bail!("{:?} needs the separate depth stencil layouts feature.", layout);
+            }
+            if aspect_mask != aspect {
+                anyhow::bail!("{:?} only applies to the {:?} aspect.", layout, aspect);
+            }
+        }
+    }
+
+    let depth_stencil = vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL;
+    if is_depth_stencil_format(format)
+        && aspect_mask.intersects(depth_stencil)
+        && !separate_depth_stencil_layouts
+    {
+        return Ok(aspect_mask | depth_stencil);
+    }
+    Ok(aspect_mask)
+}
+
+impl From<&MemoryBarrier> for vk::MemoryBarrier {
+    fn from(item: &MemoryBarrier) -> Self {
+        vk::MemoryBarrier::builder()
+            .src_access_mask(item.src_access_mask.into())
+            .dst_access_mask(item.dst_access_mask.into())
+            .build()
+    }
+}
+
+impl From<&ImageMemoryBarrier> for vk::ImageSubresourceRange {
+    fn from(item: &ImageMemoryBarrier) -> Self {
+        let level_count = match item.level_count {
+            REMAINING_MIP_LEVELS => vk::REMAINING_MIP_LEVELS,
+            n => n,
+        };
+        let layer_count = match item.layer_count {
+            REMAINING_ARRAY_LAYERS => vk::REMAINING_ARRAY_LAYERS,
+            n => n,
+        };
+        vk::ImageSubresourceRange::builder()
+            .aspect_mask(item.aspect_mask.into())
+            .base_mip_level(item.base_mip_level)
+            .level_count(level_count)
+            .base_array_layer(item.base_array_layer)
+            .layer_count(layer_count)
+            .build()
+    }
+}
+
+impl From<&ImageSubresourceRange> for vk::ImageSubresourceRange {
+    fn from(item: &ImageSubresourceRange) -> Self {
+        let level_count = match item.level_count {
+            REMAINING_MIP_LEVELS => vk::REMAINING_MIP_LEVELS,
+            n => n,
+        };
+        let layer_count = match item.layer_count {
+            REMAINING_ARRAY_LAYERS => vk::REMAINING_ARRAY_LAYERS,
+            n => n,
+        };
+        vk::ImageSubresourceRange::builder()
+            .aspect_mask(item.aspect_mask.into())
+            .base_mip_level(item.base_mip_level)
+            .level_count(level_count)
+            .base_array_layer(item.base_array_layer)
+            .layer_count(layer_count)
+            .build()
+    }
+}
+
+impl From<vk::DebugUtilsMessageSeverityFlagsEXT> for Severity {
+    fn from(item: vk::DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        if item.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            Severity::Error
+        } else if item.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            Severity::Warning
+        } else if item.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            Severity::Info
+        } else {
+            Severity::Verbose
+        }
+    }
+}
+
+impl From<vk::DebugUtilsMessageTypeFlagsEXT> for MessageType {
+    fn from(item: vk::DebugUtilsMessageTypeFlagsEXT) -> Self {
+        if item.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION) {
+            MessageType::Validation
+        } else if item.contains(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE) {
+            MessageType::Performance
+        } else {
+            MessageType::General
+        }
+    }
+}
+
 impl From<TextureViewType> for vk::ImageViewType {
     fn from(item: TextureViewType) -> Self {
         match item {
@@ -131,6 +449,56 @@ impl From<FrontFace> for vk::FrontFace {
     }
 }
 
+/// Dynamic states of a raster pipeline, viewport and scissor first.
+pub fn dynamic_states(flags: DynamicStateFlags) -> Vec<vk::DynamicState> {
+    let mut states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    if flags.contains(DynamicStateFlags::DEPTH_BIAS) {
+        states.push(vk::DynamicState::DEPTH_BIAS);
+    }
+    if flags.contains(DynamicStateFlags::LINE_WIDTH) {
+        states.push(vk::DynamicState::LINE_WIDTH);
+    }
+    if flags.contains(DynamicStateFlags::BLEND_CONSTANTS) {
+        states.push(vk::DynamicState::BLEND_CONSTANTS);
+    }
+    states
+}
+
+impl From<Viewport> for vk::Viewport {
+    fn from(item: Viewport) -> Self {
+        vk::Viewport {
+            x: item.x,
+            y: item.y,
+            width: item.width,
+            height: item.height,
+            min_depth: item.min_depth,
+            max_depth: item.max_depth,
+        }
+    }
+}
+
+impl From<Rect2D> for vk::Rect2D {
+    fn from(item: Rect2D) -> Self {
+        vk::Rect2D {
+            offset: vk::Offset2D { x: item.x as i32, y: item.y as i32 },
+            extent: vk::Extent2D { width: item.width, height: item.height },
+        }
+    }
+}
+
+impl From<PrimitiveTopology> for vk::PrimitiveTopology {
+    fn from(item: PrimitiveTopology) -> Self {
+        match item {
+            PrimitiveTopology::PointList => vk::PrimitiveTopology::POINT_LIST,
+            PrimitiveTopology::LineList => vk::PrimitiveTopology::LINE_LIST,
+            PrimitiveTopology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+            PrimitiveTopology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+            PrimitiveTopology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+            PrimitiveTopology::PatchList => vk::PrimitiveTopology::PATCH_LIST,
+        }
+    }
+}
+
 impl From<PolygonMode> for vk::PolygonMode {
     fn from(item: PolygonMode) -> Self {
         match item {
@@ -141,6 +509,29 @@ impl From<PolygonMode> for vk::PolygonMode {
     }
 }
 
+impl From<LogicOp> for vk::LogicOp {
+    fn from(item: LogicOp) -> Self {
+        match item {
+            LogicOp::Clear => vk::LogicOp::CLEAR,
+            LogicOp::And => vk::LogicOp::AND,
+            LogicOp::AndReverse => vk::LogicOp::AND_REVERSE,
+            LogicOp::Copy => vk::LogicOp::COPY,
+            LogicOp::AndInverted => vk::LogicOp::AND_INVERTED,
+            LogicOp::NoOp => vk::LogicOp::NO_OP,
+            LogicOp::Xor => vk::LogicOp::XOR,
+            LogicOp::Or => vk::LogicOp::OR,
+            LogicOp::Nor => vk::LogicOp::NOR,
+            LogicOp::Equivalent => vk::LogicOp::EQUIVALENT,
+            LogicOp::Invert => vk::LogicOp::INVERT,
+            LogicOp::OrReverse => vk::LogicOp::OR_REVERSE,
+            LogicOp::CopyInverted => vk::LogicOp::COPY_INVERTED,
+            LogicOp::OrInverted => vk::LogicOp::OR_INVERTED,
+            LogicOp::Nand => vk::LogicOp::NAND,
+            LogicOp::Set => vk::LogicOp::SET,
+        }
+    }
+}
+
 impl From<CompareOp> for vk::CompareOp {
     fn from(item: CompareOp) -> Self {
         match item {
@@ -242,6 +633,10 @@ impl From<ImageLayout> for vk::ImageLayout {
             ImageLayout::DepthStencilReadOnlyOptimal => {
                 vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
             }
+            ImageLayout::DepthAttachmentOptimal => vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            ImageLayout::DepthReadOnlyOptimal => vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL,
+            ImageLayout::StencilAttachmentOptimal => vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL,
+            ImageLayout::StencilReadOnlyOptimal => vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL,
             ImageLayout::ShaderReadOnlyOptimal => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             ImageLayout::TransferSrcOptimal => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
             ImageLayout::TransferDstOptimal => vk::ImageLayout::TRANSFER_DST_OPTIMAL,
@@ -251,6 +646,15 @@ impl From<ImageLayout> for vk::ImageLayout {
     }
 }
 
+impl From<RenderTargetStoreAction> for vk::AttachmentStoreOp {
+    fn from(item: RenderTargetStoreAction) -> Self {
+        match item {
+            RenderTargetStoreAction::Store => vk::AttachmentStoreOp::STORE,
+            RenderTargetStoreAction::DontCare => vk::AttachmentStoreOp::DONT_CARE,
+        }
+    }
+}
+
 impl From<RenderPassOutput> for VulkanRenderPassOutput {
     fn from(value: RenderPassOutput) -> Self {
         let mut ret = Self {
@@ -260,31 +664,60 @@ impl From<RenderPassOutput> for VulkanRenderPassOutput {
             depth_stencil_samples: value.depth_stencil_samples.into(),
             depth_load: value.depth_load.into(),
             stencil_load: value.stencil_load.into(),
+            depth_stencil_store: value.depth_stencil_store.into(),
+            resolve_mask: value.resolve_mask,
+            num_subpasses: value.num_subpasses,
+            subpasses: value.subpasses,
             ..Default::default()
         };
         for i in 0..value.num_colors {
             ret.color_formats[i as usize] = value.color_formats[i as usize].into();
             ret.color_final_layouts[i as usize] = value.color_final_layouts[i as usize].into();
             ret.color_load[i as usize] = value.color_loads[i as usize].into();
+            ret.color_store[i as usize] = value.color_stores[i as usize].into();
             ret.color_samples[i as usize] = value.color_samples[i as usize].into();
+            ret.resolve_formats[i as usize] = value.resolve_formats[i as usize].into();
+            ret.resolve_final_layouts[i as usize] = value.resolve_final_layouts[i as usize].into();
         }
         ret
     }
 }
 
+impl From<ClearColor> for vk::ClearColorValue {
+    fn from(value: ClearColor) -> Self {
+        match value {
+            ClearColor::Float(float32) => vk::ClearColorValue { float32 },
+            ClearColor::Uint(uint32) => vk::ClearColorValue { uint32 },
+            ClearColor::Int(int32) => vk::ClearColorValue { int32 },
+        }
+    }
+}
+
 impl From<ClearColor> for vk::ClearValue {
     fn from(value: ClearColor) -> Self {
-        vk::ClearValue { color: vk::ClearColorValue { float32: value.value } }
+        vk::ClearValue { color: value.into() }
+    }
+}
+
+impl From<ClearDepthStencil> for vk::ClearDepthStencilValue {
+    fn from(value: ClearDepthStencil) -> Self {
+        vk::ClearDepthStencilValue { depth: value.depth, stencil: value.stencil as u32 }
     }
 }
 
 impl From<ClearDepthStencil> for vk::ClearValue {
     fn from(value: ClearDepthStencil) -> Self {
-        vk::ClearValue {
-            depth_stencil: vk::ClearDepthStencilValue {
-                depth: value.depth,
-                stencil: value.stencil,
-            },
+        vk::ClearValue { depth_stencil: value.into() }
+    }
+}
+
+impl From<SubpassContents> for vk::SubpassContents {
+    fn from(value: SubpassContents) -> Self {
+        match value {
+            SubpassContents::Inline => vk::SubpassContents::INLINE,
+            SubpassContents::SecondaryCommandBuffers => {
+                vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+            }
         }
     }
 }
@@ -328,6 +761,15 @@ impl From<BufferUsageFlags> for vk::BufferUsageFlags {
         if value.contains(BufferUsageFlags::INDIRECT_BUFFER) {
             ret |= vk::BufferUsageFlags::INDIRECT_BUFFER;
         }
+        if value.contains(BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            ret |= vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        }
+        if value.contains(BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT) {
+            ret |= vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR;
+        }
+        if value.contains(BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE) {
+            ret |= vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR;
+        }
         ret
     }
 }
@@ -417,6 +859,9 @@ impl From<PipelineStageFlags> for vk::PipelineStageFlags {
         if value.contains(PipelineStageFlags::ALL_COMMANDS) {
             ret |= vk::PipelineStageFlags::ALL_COMMANDS;
         }
+        if value.contains(PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD) {
+            ret |= vk::PipelineStageFlags::ACCELERATION_STRUCTURE_BUILD_KHR;
+        }
         ret
     }
 }
@@ -445,6 +890,7 @@ impl From<BufferCopyRegion> for vk::BufferCopy {
 impl From<IndexType> for vk::IndexType {
     fn from(value: IndexType) -> Self {
         match value {
+            IndexType::U8 => vk::IndexType::UINT8_EXT,
             IndexType::U16 => vk::IndexType::UINT16,
             IndexType::U32 => vk::IndexType::UINT32,
         }
@@ -465,6 +911,18 @@ impl From<DescriptorType> for vk::DescriptorType {
             DescriptorType::UniformBufferDynamic => vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
             DescriptorType::StorageBufferDynamic => vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
             DescriptorType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
+            DescriptorType::AccelerationStructure => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+        }
+    }
+}
+
+impl From<AccelerationStructureType> for vk::AccelerationStructureTypeKHR {
+    fn from(value: AccelerationStructureType) -> Self {
+        match value {
+            AccelerationStructureType::BottomLevel => {
+                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL
+            }
+            AccelerationStructureType::TopLevel => vk::AccelerationStructureTypeKHR::TOP_LEVEL,
         }
     }
 }
@@ -503,6 +961,25 @@ impl From<ImageAspectFlags> for vk::ImageAspectFlags {
     }
 }
 
+impl From<ColorComponentFlags> for vk::ColorComponentFlags {
+    fn from(value: ColorComponentFlags) -> Self {
+        let mut ret = vk::ColorComponentFlags::empty();
+        if value.contains(ColorComponentFlags::R) {
+            ret |= vk::ColorComponentFlags::R;
+        }
+        if value.contains(ColorComponentFlags::G) {
+            ret |= vk::ColorComponentFlags::G;
+        }
+        if value.contains(ColorComponentFlags::B) {
+            ret |= vk::ColorComponentFlags::B;
+        }
+        if value.contains(ColorComponentFlags::A) {
+            ret |= vk::ColorComponentFlags::A;
+        }
+        ret
+    }
+}
+
 impl From<SamplerAddressMode> for vk::SamplerAddressMode {
     fn from(value: SamplerAddressMode) -> Self {
         match value {
@@ -515,6 +992,19 @@ impl From<SamplerAddressMode> for vk::SamplerAddressMode {
     }
 }
 
+impl From<BorderColor> for vk::BorderColor {
+    fn from(value: BorderColor) -> Self {
+        match value {
+            BorderColor::FloatTransparentBlack => vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            BorderColor::IntTransparentBlack => vk::BorderColor::INT_TRANSPARENT_BLACK,
+            BorderColor::FloatOpaqueBlack => vk::BorderColor::FLOAT_OPAQUE_BLACK,
+            BorderColor::IntOpaqueBlack => vk::BorderColor::INT_OPAQUE_BLACK,
+            BorderColor::FloatOpaqueWhite => vk::BorderColor::FLOAT_OPAQUE_WHITE,
+            BorderColor::IntOpaqueWhite => vk::BorderColor::INT_OPAQUE_WHITE,
+        }
+    }
+}
+
 impl From<SamplerMipmapMode> for vk::SamplerMipmapMode {
     fn from(value: SamplerMipmapMode) -> Self {
         match value {
@@ -533,6 +1023,32 @@ impl From<FilterType> for vk::Filter {
     }
 }
 
+impl From<&PushConstantRange> for vk::PushConstantRange {
+    fn from(item: &PushConstantRange) -> Self {
+        vk::PushConstantRange {
+            stage_flags: item.stage_flags.into(),
+            offset: item.offset,
+            size: item.size,
+        }
+    }
+}
+
+impl From<DescriptorBindingFlags> for vk::DescriptorBindingFlags {
+    fn from(value: DescriptorBindingFlags) -> Self {
+        let mut ret = vk::DescriptorBindingFlags::empty();
+        if value.contains(DescriptorBindingFlags::UPDATE_AFTER_BIND) {
+            ret |= vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
+        }
+        if value.contains(DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING) {
+            ret |= vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING;
+        }
+        if value.contains(DescriptorBindingFlags::PARTIALLY_BOUND) {
+            ret |= vk::DescriptorBindingFlags::PARTIALLY_BOUND;
+        }
+        ret
+    }
+}
+
 impl From<ShaderStageFlags> for vk::ShaderStageFlags {
     fn from(value: ShaderStageFlags) -> Self {
         let mut ret = vk::ShaderStageFlags::empty();
@@ -618,6 +1134,12 @@ impl From<AccessFlags> for vk::AccessFlags {
         if value.contains(AccessFlags::MEMORY_WRITE) {
             ret |= vk::AccessFlags::MEMORY_WRITE;
         }
+        if value.contains(AccessFlags::ACCELERATION_STRUCTURE_READ) {
+            ret |= vk::AccessFlags::ACCELERATION_STRUCTURE_READ_KHR;
+        }
+        if value.contains(AccessFlags::ACCELERATION_STRUCTURE_WRITE) {
+            ret |= vk::AccessFlags::ACCELERATION_STRUCTURE_WRITE_KHR;
+        }
         ret
     }
 }
@@ -667,3 +1189,140 @@ impl From<FormatFeatureFlags> for vk::FormatFeatureFlags {
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::image::{VulkanImage, VulkanImageDesc, VulkanImageViewDesc};
+
+    #[test]
+    fn whole_image_barrier_uses_remaining_sentinels() {
+        let barrier = ImageMemoryBarrier::whole_image(
+            Default::default(),
+            ImageAspectFlags::COLOR,
+            ImageLayout::TransferDstOptimal,
+            ImageLayout::ShaderReadOnlyOptimal,
+            AccessFlags::TRANSFER_WRITE,
+            AccessFlags::SHADER_READ,
+        );
+        let range: vk::ImageSubresourceRange = (&barrier).into();
+        assert_eq!(range.aspect_mask, vk::ImageAspectFlags::COLOR);
+        assert_eq!(range.base_mip_level, 0);
+        assert_eq!(range.level_count, vk::REMAINING_MIP_LEVELS);
+        assert_eq!(range.base_array_layer, 0);
+        assert_eq!(range.layer_count, vk::REMAINING_ARRAY_LAYERS);
+
+        let single = ImageMemoryBarrier { level_count: 3, layer_count: 1, ..barrier };
+        let range: vk::ImageSubresourceRange = (&single).into();
+        assert_eq!(range.level_count, 3);
+        assert_eq!(range.layer_count, 1);
+    }
+
+    #[test]
+    fn depth_only_view_of_combined_format() {
+        let image = VulkanImage {
+            desc: VulkanImageDesc { format: vk::Format::D24_UNORM_S8_UINT, ..Default::default() },
+            ..Default::default()
+        };
+        let view = VulkanImageViewDesc::from_create_desc(&ImageViewCreateDesc::new_depth(), &image);
+        assert_eq!(view.format, vk::Format::D24_UNORM_S8_UINT);
+        assert_eq!(view.aspect_mask, vk::ImageAspectFlags::DEPTH);
+
+        // Sample the depth aspect while the stencil stays an attachment
+        let old = vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL;
+        let new = vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL;
+        let aspect = resolve_barrier_aspect(view.format, view.aspect_mask, old, new, true).unwrap();
+        assert_eq!(aspect, vk::ImageAspectFlags::DEPTH);
+        assert!(resolve_barrier_aspect(view.format, view.aspect_mask, old, new, false).is_err());
+        assert!(resolve_barrier_aspect(view.format, vk::ImageAspectFlags::STENCIL, old, new, true)
+            .is_err());
+
+        // Combined layouts always cover both aspects without the feature
+        let aspect = resolve_barrier_aspect(
+            view.format,
+            view.aspect_mask,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            false,
+        )
+        .unwrap();
+        assert_eq!(aspect, vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL);
+        let aspect = resolve_barrier_aspect(
+            vk::Format::D32_SFLOAT,
+            vk::ImageAspectFlags::DEPTH,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            false,
+        )
+        .unwrap();
+        assert_eq!(aspect, vk::ImageAspectFlags::DEPTH);
+    }
+
+    #[test]
+    fn fence_wait_outcomes() {
+        assert_eq!(fence_wait_result(Ok(())).unwrap(), FenceWaitResult::Signaled);
+        // An unsignaled fence waited on with a short timeout
+        assert_eq!(fence_wait_result(Err(vk::Result::TIMEOUT)).unwrap(), FenceWaitResult::TimedOut);
+        assert_eq!(
+            fence_wait_result(Err(vk::Result::ERROR_DEVICE_LOST)).unwrap(),
+            FenceWaitResult::DeviceLost
+        );
+        assert!(fence_wait_result(Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY)).is_err());
+    }
+
+    #[test]
+    fn device_lost_through_context() {
+        let lost = anyhow::Error::from(vk::Result::ERROR_DEVICE_LOST);
+        assert!(is_device_lost_error(&lost));
+        assert!(is_device_lost_error(&lost.context("Failed to submit.")));
+        assert!(!is_device_lost_error(&vk::Result::ERROR_OUT_OF_DATE_KHR.into()));
+        assert!(!is_device_lost_error(&anyhow::anyhow!("Device lost.")));
+    }
+
+    #[test]
+    fn subpass_contents_mapping() {
+        assert_eq!(
+            vk::SubpassContents::from(SubpassContents::default()),
+            vk::SubpassContents::INLINE
+        );
+        assert_eq!(
+            vk::SubpassContents::from(SubpassContents::SecondaryCommandBuffers),
+            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+        );
+    }
+
+    #[test]
+    fn format_feature_support() {
+        let depth_props = vk::FormatProperties {
+            optimal_tiling_features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            ..Default::default()
+        };
+        let feature = FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT.into();
+        assert!(is_format_feature_supported(&depth_props, ImageTiling::Optimal, feature));
+        assert!(!is_format_feature_supported(&depth_props, ImageTiling::Linear, feature));
+        assert!(!is_format_feature_supported(
+            &vk::FormatProperties::default(),
+            ImageTiling::Optimal,
+            feature
+        ));
+
+        for format in DEPTH_FORMAT_CANDIDATES {
+            assert_ne!(vk::Format::from(format), vk::Format::UNDEFINED);
+        }
+        for format in [Format::R16G16B16A16_SFLOAT, Format::B10G11R11_UFLOAT, Format::BC7_SRGB] {
+            assert_eq!(Format::from(vk::Format::from(format)), format);
+        }
+        assert_eq!(
+            vk::Format::from(Format::A2B10G10R10_UNORM),
+            vk::Format::A2B10G10R10_UNORM_PACK32
+        );
+        assert!(is_compressed_format(Format::BC1_RGB_UNORM.into()));
+        assert!(is_compressed_format(Format::BC7_SRGB.into()));
+        assert!(!is_compressed_format(Format::R16_SFLOAT.into()));
+        assert_eq!(DEPTH_FORMAT_CANDIDATES[0], Format::D32_SFLOAT);
+        for format in DEPTH_STENCIL_FORMAT_CANDIDATES {
+            assert!(DEPTH_FORMAT_CANDIDATES.contains(&format));
+        }
+    }
+}
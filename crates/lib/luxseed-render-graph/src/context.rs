@@ -1,7 +1,109 @@
+use std::{any::Any, borrow::Cow, collections::HashMap};
+
 use luxseed_render_backend::{define::CommandBuffer, RenderBackend};
 use luxseed_utility::pool::Handle;
 
+use crate::resource::{Resource, ResourceSlotCollection, ResourceSlotIdentifier};
+
+/// Frame data shared between nodes, keyed by name.
+///
+/// Frame-global inputs (camera, swapchain extent, ...) are set before running the graph,
+/// nodes can add their own values for the nodes that run after them.
+#[derive(Default)]
+pub struct Blackboard {
+    values: HashMap<Cow<'static, str>, Box<dyn Any>>,
+}
+
+impl Blackboard {
+    pub fn set<T: 'static>(&mut self, key: impl Into<Cow<'static, str>>, value: T) {
+        self.values.insert(key.into(), Box::new(value));
+    }
+
+    /// Returns `None` if the key is missing or holds a value of another type.
+    pub fn get<T: 'static>(&self, key: &str) -> Option<&T> {
+        self.values.get(key)?.downcast_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, key: &str) -> Option<&mut T> {
+        self.values.get_mut(key)?.downcast_mut()
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.values.remove(key).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Everything a node can access while it renders.
 pub struct RenderGraphContext<'a> {
-    pub backend: &'a mut dyn RenderBackend,
-    pub command_buffer: Handle<CommandBuffer>,
+    pub(crate) backend: Option<&'a mut dyn RenderBackend>,
+    pub(crate) command_buffer: Option<Handle<CommandBuffer>>,
+    pub(crate) blackboard: &'a mut Blackboard,
+    pub(crate) input_slots: &'a ResourceSlotCollection,
+    pub(crate) output_slots: &'a ResourceSlotCollection,
+    pub(crate) inputs: Vec<Option<Resource>>,
+    pub(crate) outputs: Vec<Option<Resource>>,
+}
+
+impl<'a> RenderGraphContext<'a> {
+    pub fn backend(&mut self) -> anyhow::Result<&mut dyn RenderBackend> {
+        match self.backend.as_mut() {
+            Some(backend) => Ok(&mut **backend),
+            None => anyhow::bail!("Render graph is running without a backend."),
+        }
+    }
+
+    pub fn command_buffer(&self) -> anyhow::Result<Handle<CommandBuffer>> {
+        self.command_buffer.ok_or_else(|| anyhow::anyhow!("No active command buffer."))
+    }
+
+    #[inline]
+    pub fn blackboard(&self) -> &Blackboard {
+        self.blackboard
+    }
+
+    #[inline]
+    pub fn blackboard_mut(&mut self) -> &mut Blackboard {
+        self.blackboard
+    }
+
+    #[inline]
+    pub fn get<T: 'static>(&self, key: &str) -> Option<&T> {
+        self.blackboard.get(key)
+    }
+
+    #[inline]
+    pub fn set<T: 'static>(&mut self, key: impl Into<Cow<'static, str>>, value: T) {
+        self.blackboard.set(key, value);
+    }
+
+    /// The resource connected to an input slot, if the upstream node produced one.
+    pub fn input(&self, slot: impl Into<ResourceSlotIdentifier>) -> Option<&Resource> {
+        let index = self.input_slots.get_slot_index(slot)?;
+        self.inputs.get(index)?.as_ref()
+    }
+
+    /// Publishes the resource of an output slot to the connected downstream nodes.
+    pub fn set_output(
+        &mut self,
+        slot: impl Into<ResourceSlotIdentifier>,
+        resource: impl Into<Resource>,
+    ) -> anyhow::Result<()> {
+        let slot = slot.into();
+        let index = self
+            .output_slots
+            .get_slot_index(&slot)
+            .filter(|i| *i < self.outputs.len())
+            .ok_or_else(|| anyhow::anyhow!("Output slot {:?} not found.", slot))?;
+        let resource = resource.into();
+        let expected = self.output_slots.get_slot(index).unwrap().resource_type;
+        if resource.resource_type() != expected {
+            anyhow::bail!("Output slot {:?} expects a {:?}.", slot, expected);
+        }
+        self.outputs[index] = Some(resource);
+        Ok(())
+    }
 }
@@ -0,0 +1,463 @@
+use anyhow::{Context, Result};
+use luxseed_render_backend::{
+    define::*,
+    enums::{CommandBufferLevel, FenceWaitResult, QueueType},
+    flag::PipelineStageFlags,
+    RenderBackend,
+};
+use luxseed_utility::pool::Handle;
+use smallvec::SmallVec;
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::{node::NodeHandle, schedule::QueueSchedule};
+
+/// Per-queue objects a `QueueSchedule` is submitted with.
+struct QueueTimeline {
+    queue: Handle<Queue>,
+    command_pool: Handle<CommandPool>,
+    semaphore: Handle<Semaphore>,
+    /// Last value submitted for the timeline semaphore.
+    value: u64,
+    /// Command buffers with the timeline value that has to be reached before they can be reused.
+    command_buffers: Vec<(Handle<CommandBuffer>, u64)>,
+}
+
+/// Records and submits the submissions of a `QueueSchedule`, one command buffer each.
+///
+/// Every queue gets a timeline semaphore that keeps counting across frames, the values of the
+/// schedule are offset by what was submitted before. Command buffers are only reused once the
+/// value they signaled has been reached, so frames can overlap.
+#[derive(Default)]
+pub struct QueueExecutor {
+    timelines: HashMap<QueueType, QueueTimeline>,
+}
+
+impl QueueExecutor {
+    /// Waits for everything that was submitted and destroys the semaphores and command pools.
+    pub fn destroy(&mut self, backend: &mut dyn RenderBackend) -> Result<()> {
+        self.destroy_with(backend)
+    }
+
+    /// The timeline semaphore of `queue` and the last value submitted for it, so work outside
+    /// the graph can wait on the graph.
+    pub fn timeline(&self, queue: QueueType) -> Option<(Handle<Semaphore>, u64)> {
+        self.timelines.get(&queue).map(|t| (t.semaphore, t.value))
+    }
+
+    /// Submits `schedule` in order, `record` fills the command buffer of each submission with
+    /// its nodes.
+    pub(crate) fn execute_with<C: SubmitCommands + ?Sized>(
+        &mut self,
+        commands: &mut C,
+        schedule: &QueueSchedule,
+        mut record: impl FnMut(&mut C, &[NodeHandle], Handle<CommandBuffer>) -> Result<()>,
+    ) -> Result<()> {
+        for submission in &schedule.submissions {
+            self.timeline_mut(commands, submission.queue)?;
+        }
+        // Schedule values start from 1 every frame
+        let bases: HashMap<QueueType, u64> =
+            self.timelines.iter().map(|(queue, t)| (*queue, t.value)).collect();
+
+        for submission in &schedule.submissions {
+            let mut wait_semaphores = SmallVec::<[Handle<Semaphore>; 2]>::new();
+            let mut wait_values = SmallVec::<[u64; 2]>::new();
+            for wait in &submission.waits {
+                let producer = &self.timelines[&wait.queue];
+                wait_semaphores.push(producer.semaphore);
+                wait_values.push(bases[&wait.queue] + wait.value);
+            }
+            let wait_stages = SmallVec::<[PipelineStageFlags; 2]>::from_elem(
+                PipelineStageFlags::ALL_COMMANDS,
+                wait_semaphores.len(),
+            );
+
+            let signal_value = bases[&submission.queue] + submission.signal_value;
+            let cb = self.acquire_command_buffer(commands, submission.queue, signal_value)?;
+            commands.begin(cb)?;
+            record(commands, &submission.nodes, cb)?;
+            commands.end(cb)?;
+
+            let timeline = self.timelines.get_mut(&submission.queue).unwrap();
+            commands.submit(
+                timeline.queue,
+                &QueueSubmitDesc {
+                    wait_semaphore: Some(&wait_semaphores),
+                    wait_values: Some(&wait_values),
+                    wait_stage: Some(&wait_stages),
+                    command_buffer: &[cb],
+                    finish_semaphore: Some(&[timeline.semaphore]),
+                    signal_values: Some(&[signal_value]),
+                    fence: None,
+                },
+            )?;
+            timeline.value = signal_value;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn destroy_with<C: SubmitCommands + ?Sized>(
+        &mut self,
+        commands: &mut C,
+    ) -> Result<()> {
+        for (queue, timeline) in self.timelines.drain() {
+            commands
+                .wait(timeline.semaphore, timeline.value)
+                .with_context(|| format!("Failed to wait for the {} queue.", queue))?;
+            commands.destroy_command_pool(timeline.command_pool)?;
+            commands.destroy_semaphore(timeline.semaphore)?;
+        }
+        Ok(())
+    }
+
+    fn timeline_mut<C: SubmitCommands + ?Sized>(
+        &mut self,
+        commands: &mut C,
+        queue_type: QueueType,
+    ) -> Result<&mut QueueTimeline> {
+        match self.timelines.entry(queue_type) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let queue = commands.get_queue(queue_type)?;
+                let command_pool = commands.create_command_pool(queue)?;
+                let semaphore = commands.create_timeline_semaphore()?;
+                Ok(entry.insert(QueueTimeline {
+                    queue,
+                    command_pool,
+                    semaphore,
+                    value: 0,
+                    command_buffers: Vec::new(),
+                }))
+            }
+        }
+    }
+
+    /// A command buffer of `queue` the GPU is done with, marked as in use until `signal_value`.
+    fn acquire_command_buffer<C: SubmitCommands + ?Sized>(
+        &mut self,
+        commands: &mut C,
+        queue: QueueType,
+        signal_value: u64,
+    ) -> Result<Handle<CommandBuffer>> {
+        let timeline = self.timeline_mut(commands, queue)?;
+        let completed = commands.completed_value(timeline.semaphore)?;
+        if let Some(entry) = timeline.command_buffers.iter_mut().find(|(_, v)| *v <= completed) {
+            entry.1 = signal_value;
+            commands.reset(entry.0)?;
+            return Ok(entry.0);
+        }
+        let cb = commands.create_command_buffer(timeline.command_pool)?;
+        timeline.command_buffers.push((cb, signal_value));
+        Ok(cb)
+    }
+}
+
+/// The backend calls a `QueueSchedule` is submitted with.
+pub(crate) trait SubmitCommands {
+    fn get_queue(&self, queue: QueueType) -> Result<Handle<Queue>>;
+    fn create_command_pool(&mut self, queue: Handle<Queue>) -> Result<Handle<CommandPool>>;
+    fn destroy_command_pool(&mut self, command_pool: Handle<CommandPool>) -> Result<()>;
+    fn create_command_buffer(
+        &mut self,
+        command_pool: Handle<CommandPool>,
+    ) -> Result<Handle<CommandBuffer>>;
+    fn create_timeline_semaphore(&mut self) -> Result<Handle<Semaphore>>;
+    fn destroy_semaphore(&mut self, semaphore: Handle<Semaphore>) -> Result<()>;
+    fn completed_value(&self, semaphore: Handle<Semaphore>) -> Result<u64>;
+    /// Blocks until the timeline semaphore reaches `value`.
+    fn wait(&self, semaphore: Handle<Semaphore>, value: u64) -> Result<()>;
+    fn reset(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    fn begin(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    fn end(&self, cb: Handle<CommandBuffer>) -> Result<()>;
+    fn submit(&self, queue: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()>;
+}
+
+impl<T: RenderBackend + ?Sized> SubmitCommands for T {
+    fn get_queue(&self, queue: QueueType) -> Result<Handle<Queue>> {
+        RenderBackend::get_queue(self, queue)
+    }
+
+    fn create_command_pool(&mut self, queue: Handle<Queue>) -> Result<Handle<CommandPool>> {
+        RenderBackend::create_command_pool(self, queue)
+    }
+
+    fn destroy_command_pool(&mut self, command_pool: Handle<CommandPool>) -> Result<()> {
+        RenderBackend::destroy_command_pool(self, command_pool)
+    }
+
+    fn create_command_buffer(
+        &mut self,
+        command_pool: Handle<CommandPool>,
+    ) -> Result<Handle<CommandBuffer>> {
+        RenderBackend::create_command_buffer(self, command_pool, CommandBufferLevel::Primary)
+    }
+
+    fn create_timeline_semaphore(&mut self) -> Result<Handle<Semaphore>> {
+        RenderBackend::create_timeline_semaphore(self, 0)
+    }
+
+    fn destroy_semaphore(&mut self, semaphore: Handle<Semaphore>) -> Result<()> {
+        RenderBackend::destroy_semaphore(self, semaphore)
+    }
+
+    fn completed_value(&self, semaphore: Handle<Semaphore>) -> Result<u64> {
+        self.get_timeline_value(semaphore)
+    }
+
+    fn wait(&self, semaphore: Handle<Semaphore>, value: u64) -> Result<()> {
+        match self.wait_timeline_semaphore(semaphore, value, u64::MAX)? {
+            FenceWaitResult::Signaled => Ok(()),
+            result => anyhow::bail!("Waiting for the timeline semaphore failed: {:?}.", result),
+        }
+    }
+
+    fn reset(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        self.reset_command_buffer(cb, false)
+    }
+
+    fn begin(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        self.cmd_begin(cb, CommandBufferBeginDesc::default())
+    }
+
+    fn end(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+        self.cmd_end(cb)
+    }
+
+    fn submit(&self, queue: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()> {
+        self.queue_submit(queue, desc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{graph::RenderGraph, resource::ResourceSlot};
+    use luxseed_utility::{impl_handle, pool::Pool};
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct TestQueue {
+        handle: Option<Handle<Queue>>,
+    }
+    impl_handle!(TestQueue, Queue, handle);
+
+    #[derive(Default)]
+    struct TestSemaphore {
+        handle: Option<Handle<Semaphore>>,
+    }
+    impl_handle!(TestSemaphore, Semaphore, handle);
+
+    #[derive(Default)]
+    struct TestCommandBuffer {
+        handle: Option<Handle<CommandBuffer>>,
+    }
+    impl_handle!(TestCommandBuffer, CommandBuffer, handle);
+
+    #[derive(Debug, PartialEq)]
+    struct Submit {
+        queue: QueueType,
+        nodes: Vec<NodeHandle>,
+        /// Producing queue and value of each wait.
+        waits: Vec<(QueueType, u64)>,
+        signal: (QueueType, u64),
+    }
+
+    struct RecordingCommands {
+        compute_queue: Handle<Queue>,
+        semaphores: Pool<TestSemaphore>,
+        command_buffers: Pool<TestCommandBuffer>,
+        /// Queue of each timeline semaphore and the value the GPU has reached.
+        timelines: HashMap<Handle<Semaphore>, (QueueType, u64)>,
+        /// Queue of the command pool created last, the semaphore is made right after it.
+        last_pool_queue: Option<QueueType>,
+        recording: RefCell<HashMap<Handle<CommandBuffer>, Vec<NodeHandle>>>,
+        submits: RefCell<Vec<Submit>>,
+        /// Timeline values signaled by submissions the GPU hasn't finished.
+        pending: RefCell<Vec<(Handle<Semaphore>, u64)>>,
+        destroyed: usize,
+    }
+
+    impl RecordingCommands {
+        fn new() -> Self {
+            let mut queues = Pool::<TestQueue>::with_size(1, Default::default);
+            Self {
+                compute_queue: queues.malloc().0,
+                semaphores: Pool::with_size(4, Default::default),
+                command_buffers: Pool::with_size(16, Default::default),
+                timelines: HashMap::new(),
+                last_pool_queue: None,
+                recording: RefCell::default(),
+                submits: RefCell::default(),
+                pending: RefCell::default(),
+                destroyed: 0,
+            }
+        }
+
+        fn queue_type(&self, queue: Handle<Queue>) -> QueueType {
+            if queue == self.compute_queue {
+                QueueType::Compute
+            } else {
+                QueueType::Graphics
+            }
+        }
+
+        /// Pretends the GPU finished everything submitted so far.
+        fn complete_all(&mut self) {
+            for (semaphore, value) in self.pending.get_mut().drain(..) {
+                let completed = &mut self.timelines.get_mut(&semaphore).unwrap().1;
+                *completed = (*completed).max(value);
+            }
+        }
+    }
+
+    impl SubmitCommands for RecordingCommands {
+        fn get_queue(&self, queue: QueueType) -> Result<Handle<Queue>> {
+            match queue {
+                QueueType::Compute => Ok(self.compute_queue),
+                _ => Ok(Handle::default()),
+            }
+        }
+
+        fn create_command_pool(&mut self, queue: Handle<Queue>) -> Result<Handle<CommandPool>> {
+            self.last_pool_queue = Some(self.queue_type(queue));
+            Ok(Handle::default())
+        }
+
+        fn destroy_command_pool(&mut self, _: Handle<CommandPool>) -> Result<()> {
+            self.destroyed += 1;
+            Ok(())
+        }
+
+        fn create_command_buffer(
+            &mut self,
+            _: Handle<CommandPool>,
+        ) -> Result<Handle<CommandBuffer>> {
+            Ok(self.command_buffers.malloc().0)
+        }
+
+        fn create_timeline_semaphore(&mut self) -> Result<Handle<Semaphore>> {
+            let semaphore = self.semaphores.malloc().0;
+            self.timelines.insert(semaphore, (self.last_pool_queue.take().unwrap(), 0));
+            Ok(semaphore)
+        }
+
+        fn destroy_semaphore(&mut self, semaphore: Handle<Semaphore>) -> Result<()> {
+            self.semaphores.free(semaphore);
+            Ok(())
+        }
+
+        fn completed_value(&self, semaphore: Handle<Semaphore>) -> Result<u64> {
+            Ok(self.timelines[&semaphore].1)
+        }
+
+        fn wait(&self, semaphore: Handle<Semaphore>, value: u64) -> Result<()> {
+            assert!(self.timelines[&semaphore].1 >= value, "the test completes the work first");
+            Ok(())
+        }
+
+        fn reset(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+            assert!(!self.recording.borrow().contains_key(&cb));
+            Ok(())
+        }
+
+        fn begin(&self, cb: Handle<CommandBuffer>) -> Result<()> {
+            assert!(self.recording.borrow_mut().insert(cb, Vec::new()).is_none());
+            Ok(())
+        }
+
+        fn end(&self, _: Handle<CommandBuffer>) -> Result<()> {
+            Ok(())
+        }
+
+        fn submit(&self, queue: Handle<Queue>, desc: &QueueSubmitDesc) -> Result<()> {
+            let timeline_of = |semaphore: &Handle<Semaphore>| self.timelines[semaphore].0;
+            let waits = desc.wait_semaphore.unwrap().iter().map(timeline_of);
+            let waits = waits.zip(desc.wait_values.unwrap().iter().copied()).collect();
+            assert_eq!(desc.wait_stage.unwrap().len(), desc.wait_semaphore.unwrap().len());
+            assert_eq!(desc.command_buffer.len(), 1);
+            let signal = desc.finish_semaphore.unwrap()[0];
+            self.pending.borrow_mut().push((signal, desc.signal_values.unwrap()[0]));
+            self.submits.borrow_mut().push(Submit {
+                queue: self.queue_type(queue),
+                nodes: self.recording.borrow_mut().remove(&desc.command_buffer[0]).unwrap(),
+                waits,
+                signal: (timeline_of(&signal), desc.signal_values.unwrap()[0]),
+            });
+            Ok(())
+        }
+    }
+
+    fn execute(
+        executor: &mut QueueExecutor,
+        commands: &mut RecordingCommands,
+        schedule: &QueueSchedule,
+    ) -> Vec<Submit> {
+        executor
+            .execute_with(commands, schedule, |commands, nodes, cb| {
+                commands.recording.borrow_mut().get_mut(&cb).unwrap().extend_from_slice(nodes);
+                Ok(())
+            })
+            .unwrap();
+        commands.submits.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn submissions_wait_on_the_timelines_of_other_queues() {
+        let mut rg = RenderGraph::default();
+        let depth = rg.add_node("depth_prepass", &[], &[ResourceSlot::image_view("depth")]);
+        let culling = rg.add_node(
+            "light_culling",
+            &[ResourceSlot::image_view("depth")],
+            &[ResourceSlot::buffer("light_lists")],
+        );
+        let lighting = rg.add_node("lighting", &[ResourceSlot::buffer("light_lists")], &[]);
+        rg.get_node_mut(culling).unwrap().queue = QueueType::Compute;
+        rg.try_add_resource_edge(depth, "depth", culling, "depth").unwrap();
+        rg.try_add_resource_edge(culling, "light_lists", lighting, "light_lists").unwrap();
+        let schedule = rg.schedule().unwrap();
+
+        let mut commands = RecordingCommands::new();
+        let mut executor = QueueExecutor::default();
+        use QueueType::*;
+        let frame = |base: u64| {
+            vec![
+                Submit {
+                    queue: Graphics,
+                    nodes: vec![depth],
+                    waits: vec![],
+                    signal: (Graphics, 2 * base + 1),
+                },
+                Submit {
+                    queue: Compute,
+                    nodes: vec![culling],
+                    waits: vec![(Graphics, 2 * base + 1)],
+                    signal: (Compute, base + 1),
+                },
+                Submit {
+                    queue: Graphics,
+                    nodes: vec![lighting],
+                    waits: vec![(Compute, base + 1)],
+                    signal: (Graphics, 2 * base + 2),
+                },
+            ]
+        };
+
+        assert_eq!(execute(&mut executor, &mut commands, &schedule), frame(0));
+        assert_eq!(commands.command_buffers.len(), 3);
+        // The timelines keep counting, the command buffers of the first frame are still in use
+        assert_eq!(execute(&mut executor, &mut commands, &schedule), frame(1));
+        assert_eq!(commands.command_buffers.len(), 6);
+        assert_eq!(executor.timeline(Graphics).map(|t| t.1), Some(4));
+
+        // Once the GPU caught up the command buffers are reused
+        commands.complete_all();
+        assert_eq!(execute(&mut executor, &mut commands, &schedule), frame(2));
+        assert_eq!(commands.command_buffers.len(), 6);
+
+        commands.complete_all();
+        executor.destroy_with(&mut commands).unwrap();
+        assert_eq!(commands.destroyed, 2);
+        assert!(commands.semaphores.is_empty());
+        assert!(executor.timeline(Graphics).is_none());
+    }
+}
@@ -1,10 +1,18 @@
 use crate::{
+    context::{Blackboard, RenderGraphContext},
     edge::Edge,
+    executor::QueueExecutor,
     node::{Node, NodeHandle, NodeIdentifier},
-    resource::{ResourceSlot, ResourceSlotIdentifier},
+    resource::{Resource, ResourceSlot, ResourceSlotIdentifier},
+    schedule::QueueSchedule,
     RenderGraphError,
 };
-use std::{borrow::Cow, collections::HashMap};
+use luxseed_render_backend::{define::CommandBuffer, RenderBackend};
+use luxseed_utility::pool::Handle;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+};
 
 const MAX_RESOURCES_COUNT: usize = 1024;
 const MAX_NODES_COUNT: usize = 1024;
@@ -13,6 +21,7 @@ const MAX_NODES_COUNT: usize = 1024;
 pub struct RenderGraph {
     nodes: HashMap<NodeHandle, Node>,
     node_names: HashMap<Cow<'static, str>, NodeHandle>,
+    node_order: Vec<NodeHandle>,
 }
 
 impl RenderGraph {
@@ -25,6 +34,129 @@ impl RenderGraph {
         Ok(())
     }
 
+    /// Runs the render callback of every node, upstream nodes first.
+    ///
+    /// Each node gets a context holding the shared blackboard, the resources published by the
+    /// nodes connected to its input slots and the active command buffer.
+    pub fn run(
+        &mut self,
+        mut backend: Option<&mut dyn RenderBackend>,
+        command_buffer: Option<Handle<CommandBuffer>>,
+        blackboard: &mut Blackboard,
+    ) -> Result<(), RenderGraphError> {
+        let mut outputs = HashMap::new();
+        for handle in self.sorted_nodes()? {
+            let backend = backend.as_mut().map(|b| &mut **b as &mut dyn RenderBackend);
+            self.render_node(handle, backend, command_buffer, blackboard, &mut outputs)?;
+        }
+        Ok(())
+    }
+
+    /// Records the graph following its `schedule`, every submission into its own command buffer
+    /// of the queue the nodes prefer, and submits them with `executor`.
+    pub fn execute(
+        &mut self,
+        backend: &mut dyn RenderBackend,
+        executor: &mut QueueExecutor,
+        blackboard: &mut Blackboard,
+    ) -> Result<(), RenderGraphError> {
+        let schedule = self.schedule()?;
+        let mut outputs = HashMap::new();
+        executor
+            .execute_with(backend, &schedule, |backend, nodes, cb| {
+                for &handle in nodes {
+                    self.render_node(handle, Some(backend), Some(cb), blackboard, &mut outputs)?;
+                }
+                Ok(())
+            })
+            .map_err(|e| e.downcast().unwrap_or_else(RenderGraphError::SubmitError))
+    }
+
+    fn render_node<'a>(
+        &'a mut self,
+        handle: NodeHandle,
+        backend: Option<&'a mut dyn RenderBackend>,
+        command_buffer: Option<Handle<CommandBuffer>>,
+        blackboard: &'a mut Blackboard,
+        outputs: &mut HashMap<NodeHandle, Vec<Option<Resource>>>,
+    ) -> Result<(), RenderGraphError> {
+        let node =
+            self.nodes.get_mut(&handle).ok_or(RenderGraphError::InvalidNode(handle.into()))?;
+
+        let mut inputs = vec![None; node.input_slots().len()];
+        for edge in node.input_edges() {
+            if let Edge::ResourceEdge {
+                output_node_handle,
+                output_slot_index,
+                input_slot_index,
+                ..
+            } = *edge
+            {
+                inputs[input_slot_index] = outputs
+                    .get(&output_node_handle)
+                    .and_then(|o| o.get(output_slot_index).cloned().flatten());
+            }
+        }
+
+        let on_render = node.on_render.take();
+        let mut context = RenderGraphContext {
+            backend,
+            command_buffer,
+            blackboard,
+            input_slots: node.input_slots(),
+            output_slots: node.output_slots(),
+            inputs,
+            outputs: vec![None; node.output_slots().len()],
+        };
+        if let Some(on_render) = on_render {
+            on_render(&mut context)
+                .map_err(|_| RenderGraphError::NodeOnRenderError(handle.into()))?;
+        }
+        outputs.insert(handle, context.outputs);
+        Ok(())
+    }
+
+    /// Splits the nodes into per-queue submissions following the queue each node prefers.
+    ///
+    /// Edges between nodes on different queues turn into timeline semaphore waits, so compute
+    /// nodes can overlap graphics work they don't depend on.
+    pub fn schedule(&self) -> Result<QueueSchedule, RenderGraphError> {
+        let sorted = self.sorted_nodes()?;
+        Ok(QueueSchedule::build(
+            &sorted,
+            |handle| self.nodes[&handle].queue,
+            |handle| self.nodes[&handle].input_edges().iter().map(Edge::get_output_node).collect(),
+        ))
+    }
+
+    /// Orders the nodes so every node comes after the nodes it depends on.
+    fn sorted_nodes(&self) -> Result<Vec<NodeHandle>, RenderGraphError> {
+        let mut in_degrees: HashMap<NodeHandle, usize> =
+            self.nodes.iter().map(|(h, n)| (*h, n.input_edges().len())).collect();
+        let mut ready: VecDeque<NodeHandle> =
+            self.node_order.iter().copied().filter(|h| in_degrees[h] == 0).collect();
+
+        let mut sorted = Vec::with_capacity(self.nodes.len());
+        while let Some(handle) = ready.pop_front() {
+            sorted.push(handle);
+            for edge in self.nodes[&handle].output_edges() {
+                let input = edge.get_input_node();
+                let in_degree = in_degrees
+                    .get_mut(&input)
+                    .ok_or(RenderGraphError::InvalidNode(input.into()))?;
+                *in_degree -= 1;
+                if *in_degree == 0 {
+                    ready.push_back(input);
+                }
+            }
+        }
+
+        if sorted.len() != self.nodes.len() {
+            return Err(RenderGraphError::Cycle);
+        }
+        Ok(sorted)
+    }
+
     pub fn get_node_handle(
         &self,
         identifier: impl Into<NodeIdentifier>,
@@ -68,6 +200,7 @@ impl RenderGraph {
         node.name = Some(name.clone());
         self.nodes.insert(handle, node);
         self.node_names.insert(name, handle);
+        self.node_order.push(handle);
         handle
     }
 
@@ -236,7 +369,14 @@ impl RenderGraph {
 
 #[cfg(test)]
 mod tests {
-    use crate::resource::ResourceSlot;
+    use crate::{
+        context::Blackboard,
+        resource::{ImageView, Resource, ResourceSlot},
+        schedule::QueueWait,
+    };
+    use luxseed_render_backend::enums::QueueType;
+    use luxseed_utility::pool::Handle;
+    use std::{cell::Cell, rc::Rc};
 
     use super::RenderGraph;
 
@@ -282,4 +422,76 @@ mod tests {
 
         assert_eq!(data.value, 10);
     }
+
+    #[test]
+    fn downstream_node_reads_upstream_data() {
+        let mut rg = RenderGraph::default();
+        // Added first so the executor has to reorder it after its dependency
+        let lighting = rg.add_node("lighting", &[ResourceSlot::image_view("shadow_map")], &[]);
+        let shadow = rg.add_node("shadow", &[], &[ResourceSlot::image_view("shadow_map")]);
+        rg.try_add_resource_edge(shadow, "shadow_map", lighting, "shadow_map").unwrap();
+
+        rg.get_node_mut(shadow).unwrap().on_render(|ctx| {
+            let extent = *ctx.get::<[u32; 2]>("extent").unwrap();
+            ctx.set("light_view_proj", [extent[0] as f32, extent[1] as f32]);
+            ctx.set_output("shadow_map", ImageView { handle: Handle::default() })
+        });
+
+        let seen = Rc::new(Cell::new(None));
+        let sink = seen.clone();
+        rg.get_node_mut(lighting).unwrap().on_render(move |ctx| {
+            assert!(matches!(ctx.input("shadow_map"), Some(Resource::ImageView(_))));
+            assert!(ctx.command_buffer().is_err());
+            sink.set(ctx.get::<[f32; 2]>("light_view_proj").copied());
+            Ok(())
+        });
+
+        let mut blackboard = Blackboard::default();
+        blackboard.set("extent", [1280u32, 720u32]);
+        rg.run(None, None, &mut blackboard).unwrap();
+
+        assert_eq!(seen.get(), Some([1280.0, 720.0]));
+        assert_eq!(blackboard.get::<[f32; 2]>("light_view_proj"), Some(&[1280.0, 720.0]));
+        assert!(blackboard.get::<u32>("extent").is_none());
+    }
+
+    #[test]
+    fn async_compute_waits_across_queues() {
+        let mut rg = RenderGraph::default();
+        let depth = rg.add_node("depth_prepass", &[], &[ResourceSlot::image_view("depth")]);
+        let culling = rg.add_node(
+            "light_culling",
+            &[ResourceSlot::image_view("depth")],
+            &[ResourceSlot::buffer("light_lists")],
+        );
+        let lighting = rg.add_node("lighting", &[ResourceSlot::buffer("light_lists")], &[]);
+        rg.get_node_mut(culling).unwrap().queue = QueueType::Compute;
+        rg.try_add_resource_edge(depth, "depth", culling, "depth").unwrap();
+        rg.try_add_resource_edge(culling, "light_lists", lighting, "light_lists").unwrap();
+
+        let schedule = rg.schedule().unwrap();
+        let submissions: Vec<_> = schedule
+            .submissions
+            .iter()
+            .map(|s| (s.queue, s.nodes.clone(), s.waits.to_vec(), s.signal_value))
+            .collect();
+        assert_eq!(
+            submissions,
+            vec![
+                (QueueType::Graphics, vec![depth], vec![], 1),
+                (
+                    QueueType::Compute,
+                    vec![culling],
+                    vec![QueueWait { queue: QueueType::Graphics, value: 1 }],
+                    1
+                ),
+                (
+                    QueueType::Graphics,
+                    vec![lighting],
+                    vec![QueueWait { queue: QueueType::Compute, value: 1 }],
+                    2
+                ),
+            ]
+        );
+    }
 }
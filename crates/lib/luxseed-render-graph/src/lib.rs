@@ -1,13 +1,18 @@
 mod context;
 mod edge;
+mod executor;
 mod graph;
 mod node;
+mod resolve;
 mod resource;
+mod schedule;
 
 use crate::graph::RenderGraph;
 use edge::Edge;
+pub use executor::QueueExecutor;
 use luxseed_render_backend::RenderBackend;
 use node::NodeIdentifier;
+pub use resolve::ResolveNode;
 use resource::ResourceSlotIdentifier;
 use thiserror::Error;
 
@@ -46,6 +51,10 @@ pub enum RenderGraphError {
     NodeOnUpdateError(NodeIdentifier),
     #[error("node {0:?} render error")]
     NodeOnRenderError(NodeIdentifier),
+    #[error("submitting the render graph failed: {0:#}")]
+    SubmitError(anyhow::Error),
+    #[error("render graph contains a cycle")]
+    Cycle,
     #[error("unknown render graph error")]
     Unknown,
 }
@@ -1,3 +1,4 @@
+use luxseed_render_backend::enums::QueueType;
 use luxseed_utility::define_atomic_id;
 use smallvec::SmallVec;
 use std::{borrow::Cow, fmt::Debug, fmt::Formatter};
@@ -19,6 +20,8 @@ type OnRenderFn = dyn FnOnce(&mut RenderGraphContext) -> anyhow::Result<()>;
 pub struct Node {
     pub handle: NodeHandle,
     pub name: Option<Cow<'static, str>>,
+    /// Queue the node prefers to run on, `Graphics` unless set otherwise.
+    pub queue: QueueType,
     pub on_update: Option<Box<OnUpdateFn>>,
     pub on_render: Option<Box<OnRenderFn>>,
     input_edges: SmallVec<[Edge; DEFAULT_EDGES_COUNT]>,
@@ -52,6 +55,7 @@ impl Node {
         Self {
             handle,
             name: None,
+            queue: QueueType::Graphics,
             input_edges: SmallVec::new(),
             input_slots,
             output_edges: SmallVec::new(),
@@ -0,0 +1,170 @@
+use std::borrow::Cow;
+
+use luxseed_render_backend::{define::ImageResolveRegion, enums::ImageLayout};
+
+use crate::{
+    context::RenderGraphContext,
+    graph::RenderGraph,
+    node::NodeHandle,
+    resource::{Image, Resource, ResourceSlot},
+    RenderGraphError,
+};
+
+/// Resolves the multisampled image on its input slot into `target` and publishes `target` on
+/// its output slot, so a graph can render at MSAA and present or blit the resolved image.
+///
+/// The source has to be in `ImageLayout::TransferSrcOptimal` and the target in
+/// `ImageLayout::TransferDstOptimal`, e.g. as the final layouts of the passes writing them.
+#[derive(Debug, Clone)]
+pub struct ResolveNode {
+    pub target: Image,
+}
+
+impl ResolveNode {
+    pub const INPUT: &'static str = "msaa_color";
+    pub const OUTPUT: &'static str = "resolved_color";
+
+    pub fn new(target: Image) -> Self {
+        Self { target }
+    }
+
+    /// Adds a node with the resolve slots, set what it resolves into with `on_render`.
+    pub fn add_to(graph: &mut RenderGraph, name: impl Into<Cow<'static, str>>) -> NodeHandle {
+        graph.add_node(
+            name,
+            &[ResourceSlot::image(Self::INPUT)],
+            &[ResourceSlot::image(Self::OUTPUT)],
+        )
+    }
+
+    /// Records the resolve the next time the graph runs.
+    pub fn on_render(
+        self,
+        graph: &mut RenderGraph,
+        node: NodeHandle,
+    ) -> Result<(), RenderGraphError> {
+        graph.get_node_mut(node)?.on_render(move |ctx| self.record(ctx));
+        Ok(())
+    }
+
+    fn record(&self, ctx: &mut RenderGraphContext) -> anyhow::Result<()> {
+        let (source, region) = self.region(ctx)?;
+        let cb = ctx.command_buffer()?;
+        ctx.backend()?.cmd_resolve_image(
+            cb,
+            source.handle,
+            ImageLayout::TransferSrcOptimal,
+            self.target.handle,
+            ImageLayout::TransferDstOptimal,
+            &[region],
+        )?;
+        ctx.set_output(Self::OUTPUT, self.target.clone())
+    }
+
+    /// The multisampled input and the region covering it, the target must be as large.
+    fn region(&self, ctx: &RenderGraphContext) -> anyhow::Result<(Image, ImageResolveRegion)> {
+        let source = match ctx.input(Self::INPUT) {
+            Some(Resource::Image(image)) => image.clone(),
+            _ => anyhow::bail!("Resolve node has no image on its {:?} input.", Self::INPUT),
+        };
+        if source.extent != self.target.extent {
+            anyhow::bail!(
+                "Resolve source is {}x{} but the target is {}x{}.",
+                source.extent[0],
+                source.extent[1],
+                self.target.extent[0],
+                self.target.extent[1]
+            );
+        }
+        let region = ImageResolveRegion::new(source.extent[0], source.extent[1]);
+        Ok((source, region))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Blackboard;
+    use luxseed_render_backend::{
+        define,
+        testing::{Call, RecordingBackend},
+    };
+    use luxseed_utility::{
+        impl_handle,
+        pool::{Handle, Pool},
+    };
+
+    #[derive(Default)]
+    struct TestImage {
+        handle: Option<Handle<define::Image>>,
+    }
+    impl_handle!(TestImage, define::Image, handle);
+
+    /// MSAA color pass -> resolve -> swapchain image imported from outside the graph.
+    fn msaa_graph(color: Image, swapchain: Image) -> RenderGraph {
+        let mut rg = RenderGraph::default();
+        let present = rg.add_node("present", &[ResourceSlot::image("swapchain")], &[]);
+        let resolve = ResolveNode::add_to(&mut rg, "resolve");
+        let msaa_color = rg.add_node("msaa_color", &[], &[ResourceSlot::image("color")]);
+        rg.try_add_resource_edge(msaa_color, "color", resolve, ResolveNode::INPUT).unwrap();
+        rg.try_add_resource_edge(resolve, ResolveNode::OUTPUT, present, "swapchain").unwrap();
+
+        rg.get_node_mut(msaa_color)
+            .unwrap()
+            .on_render(move |ctx| ctx.set_output("color", color.clone()));
+        ResolveNode::new(swapchain).on_render(&mut rg, resolve).unwrap();
+        rg.get_node_mut(present).unwrap().on_render(move |ctx| {
+            let swapchain = ctx.input("swapchain");
+            assert!(matches!(swapchain, Some(Resource::Image(i)) if i.extent == [1280, 720]));
+            Ok(())
+        });
+        rg
+    }
+
+    #[test]
+    fn msaa_color_resolves_into_swapchain() {
+        let mut images = Pool::<TestImage>::with_size(2, Default::default);
+        let color = Image { handle: images.malloc().0, extent: [1280, 720] };
+        let swapchain = Image { handle: images.malloc().0, extent: [1280, 720] };
+        let cb = Handle::default();
+
+        let backend = &mut RecordingBackend::default();
+        let mut rg = msaa_graph(color.clone(), swapchain.clone());
+        rg.run(Some(backend), Some(cb), &mut Blackboard::default()).unwrap();
+        assert_eq!(
+            *backend.calls(),
+            vec![Call::ResolveImage {
+                cb,
+                src: color.handle,
+                src_layout: ImageLayout::TransferSrcOptimal,
+                dst: swapchain.handle,
+                dst_layout: ImageLayout::TransferDstOptimal,
+                regions: vec![ImageResolveRegion::new(1280, 720)],
+            }]
+        );
+        assert_eq!(ImageResolveRegion::new(1280, 720).extent, [1280, 720, 1]);
+
+        // A source of another size records nothing
+        let backend = &mut RecordingBackend::default();
+        let larger = Image { extent: [1920, 1080], ..color };
+        let mut rg = msaa_graph(larger, swapchain);
+        assert!(matches!(
+            rg.run(Some(backend), Some(cb), &mut Blackboard::default()),
+            Err(RenderGraphError::NodeOnRenderError(_))
+        ));
+        assert!(backend.calls().is_empty());
+    }
+
+    #[test]
+    fn unconnected_resolve_fails() {
+        let mut rg = RenderGraph::default();
+        let resolve = ResolveNode::add_to(&mut rg, "resolve");
+        let target = Image { handle: Handle::default(), extent: [64, 64] };
+        ResolveNode::new(target).on_render(&mut rg, resolve).unwrap();
+        let backend = &mut RecordingBackend::default();
+        assert!(rg
+            .run(Some(backend), Some(Handle::default()), &mut Blackboard::default())
+            .is_err());
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -8,6 +8,7 @@ use luxseed_utility::pool::Handle;
 use smallvec::SmallVec;
 
 type BufferHandle = Handle<luxseed_render_backend::define::Buffer>;
+type ImageHandle = Handle<luxseed_render_backend::define::Image>;
 type ImageViewHandle = Handle<luxseed_render_backend::define::ImageView>;
 type SamplerHandle = Handle<luxseed_render_backend::define::Sampler>;
 
@@ -16,6 +17,7 @@ const DEFAULT_RESOURCE_SLOTS_COUNT: usize = 8;
 #[derive(Debug, Clone)]
 pub enum Resource {
     Buffer(Buffer),
+    Image(Image),
     ImageView(ImageView),
     Sampler(Sampler),
 }
@@ -25,6 +27,7 @@ impl Resource {
     pub fn resource_type(&self) -> ResourceType {
         match self {
             Self::Buffer(_) => ResourceType::Buffer,
+            Self::Image(_) => ResourceType::Image,
             Self::ImageView(_) => ResourceType::ImageView,
             Self::Sampler(_) => ResourceType::Sampler,
         }
@@ -34,6 +37,7 @@ impl Resource {
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ResourceType {
     Buffer,
+    Image,
     ImageView,
     Sampler,
 }
@@ -49,6 +53,19 @@ impl From<Buffer> for Resource {
     }
 }
 
+/// A whole image, for nodes recording transfers such as resolves or blits.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub handle: ImageHandle,
+    pub extent: [u32; 2],
+}
+
+impl From<Image> for Resource {
+    fn from(image: Image) -> Self {
+        Self::Image(image)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImageView {
     pub handle: ImageViewHandle,
@@ -78,6 +95,10 @@ pub struct ResourceSlot {
 }
 
 impl ResourceSlot {
+    pub fn image(name: impl Into<Cow<'static, str>>) -> Self {
+        Self { name: name.into(), resource_type: ResourceType::Image }
+    }
+
     pub fn image_view(name: impl Into<Cow<'static, str>>) -> Self {
         Self { name: name.into(), resource_type: ResourceType::ImageView }
     }
@@ -0,0 +1,77 @@
+use luxseed_render_backend::enums::QueueType;
+use smallvec::SmallVec;
+use std::collections::HashMap;
+
+use crate::node::NodeHandle;
+
+/// Timeline value another queue has to reach before a submission may start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueWait {
+    pub queue: QueueType,
+    pub value: u64,
+}
+
+/// Consecutive nodes on the same queue, recorded into one command buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueSubmission {
+    pub queue: QueueType,
+    pub nodes: Vec<NodeHandle>,
+    /// At most one wait per queue, for the highest value needed.
+    pub waits: SmallVec<[QueueWait; 2]>,
+    /// Value the timeline semaphore of `queue` is signaled with once the submission is done.
+    pub signal_value: u64,
+}
+
+/// Submissions in the order they have to be made, each queue keeps one timeline semaphore.
+///
+/// Nodes on the same queue are ordered by their submission, edges crossing queues become waits
+/// on the timeline of the producing queue.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueueSchedule {
+    pub submissions: Vec<QueueSubmission>,
+}
+
+impl QueueSchedule {
+    pub(crate) fn build(
+        sorted: &[NodeHandle],
+        queue_of: impl Fn(NodeHandle) -> QueueType,
+        dependencies_of: impl Fn(NodeHandle) -> Vec<NodeHandle>,
+    ) -> Self {
+        let mut submissions: Vec<QueueSubmission> = Vec::new();
+        let mut timelines: HashMap<QueueType, u64> = HashMap::new();
+        let mut submission_of: HashMap<NodeHandle, usize> = HashMap::new();
+
+        for &handle in sorted {
+            let queue = queue_of(handle);
+            if submissions.last().is_none_or(|s| s.queue != queue) {
+                let timeline = timelines.entry(queue).or_insert(0);
+                *timeline += 1;
+                submissions.push(QueueSubmission {
+                    queue,
+                    nodes: Vec::new(),
+                    waits: SmallVec::new(),
+                    signal_value: *timeline,
+                });
+            }
+
+            let index = submissions.len() - 1;
+            for dependency in dependencies_of(handle) {
+                if let Some(&producer) = submission_of.get(&dependency) {
+                    let producer = &submissions[producer];
+                    if producer.queue == queue {
+                        continue;
+                    }
+                    let wait = QueueWait { queue: producer.queue, value: producer.signal_value };
+                    let waits = &mut submissions[index].waits;
+                    match waits.iter_mut().find(|w| w.queue == wait.queue) {
+                        Some(existing) => existing.value = existing.value.max(wait.value),
+                        None => waits.push(wait),
+                    }
+                }
+            }
+            submissions[index].nodes.push(handle);
+            submission_of.insert(handle, index);
+        }
+        Self { submissions }
+    }
+}
@@ -1,6 +1,6 @@
-use std::marker::PhantomData;
+use std::{fmt, marker::PhantomData};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Handle<T> {
     index: u16,
     generation: u16,
@@ -13,6 +13,14 @@ impl<T> Default for Handle<T> {
     }
 }
 
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_name = std::any::type_name::<T>();
+        let type_name = type_name.rsplit("::").next().unwrap_or(type_name);
+        write!(f, "Handle<{}>(index={}, gen={})", type_name, self.index, self.generation)
+    }
+}
+
 pub trait Handled {
     type HandleType;
 
@@ -44,6 +52,7 @@ where
     items: Vec<T>,
     generations: Vec<u16>,
     free_indices: Vec<u16>,
+    names: Vec<Option<String>>,
     fp_init: Box<dyn Fn() -> T>,
 }
 
@@ -73,7 +82,10 @@ where
             generations.push(0);
         }
 
-        Self { items, generations, free_indices, fp_init: Box::new(init) }
+        let mut names = Vec::with_capacity(size);
+        names.resize(size, None);
+
+        Self { items, generations, free_indices, names, fp_init: Box::new(init) }
     }
 
     #[inline]
@@ -107,10 +119,43 @@ where
         if self.is_match(handle) {
             self.items.get_mut(handle.index as usize).unwrap().set_handle(None);
             self.free_indices.push(handle.index);
+            self.names[handle.index as usize] = None;
             self.generations[handle.index as usize] += 1;
         }
     }
 
+    /// Associates a debug name with the item, cleared again when it is freed.
+    pub fn set_name(&mut self, handle: Handle<T::HandleType>, name: &str) {
+        if self.is_match(handle) {
+            self.names[handle.index as usize] = Some(name.to_owned());
+        }
+    }
+
+    /// The debug name given to the item with `set_name`, if any.
+    #[inline]
+    pub fn resolve_name(&self, handle: Handle<T::HandleType>) -> Option<&str> {
+        if self.is_match(handle) {
+            return self.names[handle.index as usize].as_deref();
+        }
+        None
+    }
+
+    /// Number of allocated items.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.generations.len() - self.free_indices.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the allocated items with their handles.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle<T::HandleType>, &T)> {
+        self.items.iter().filter_map(|item| item.get_handle().map(|handle| (handle, item)))
+    }
+
     pub fn malloc(&mut self) -> (Handle<T::HandleType>, &mut T) {
         if let Some(index) = self.free_indices.pop() {
             let handle: Handle<T::HandleType> =
@@ -136,6 +181,7 @@ where
         }
         self.free_indices.reverse();
         self.generations.resize(new_len, 0);
+        self.names.resize(new_len, None);
         self.items.resize_with(new_len, || (self.fp_init)());
     }
 }